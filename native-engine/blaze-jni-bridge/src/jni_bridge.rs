@@ -413,6 +413,7 @@ pub struct JavaClasses<'a> {
     pub cSparkMetricNode: SparkMetricNode<'a>,
     pub cSparkUDFWrapperContext: SparkUDFWrapperContext<'a>,
     pub cSparkUDTFWrapperContext: SparkUDTFWrapperContext<'a>,
+    pub cSparkUDAFWrapperContext: SparkUDAFWrapperContext<'a>,
     pub cBlazeConf: BlazeConf<'a>,
     pub cBlazeRssPartitionWriterBase: BlazeRssPartitionWriterBase<'a>,
     pub cBlazeCallNativeWrapper: BlazeCallNativeWrapper<'a>,
@@ -474,6 +475,7 @@ impl JavaClasses<'static> {
                 cSparkMetricNode: SparkMetricNode::new(env)?,
                 cSparkUDFWrapperContext: SparkUDFWrapperContext::new(env)?,
                 cSparkUDTFWrapperContext: SparkUDTFWrapperContext::new(env)?,
+                cSparkUDAFWrapperContext: SparkUDAFWrapperContext::new(env)?,
                 cBlazeConf: BlazeConf::new(env)?,
                 cBlazeRssPartitionWriterBase: BlazeRssPartitionWriterBase::new(env)?,
                 cBlazeCallNativeWrapper: BlazeCallNativeWrapper::new(env)?,
@@ -1209,6 +1211,47 @@ impl<'a> SparkUDTFWrapperContext<'a> {
     }
 }
 
+#[allow(non_snake_case)]
+pub struct SparkUDAFWrapperContext<'a> {
+    pub class: JClass<'a>,
+    pub ctor: JMethodID,
+    pub method_update: JMethodID,
+    pub method_update_ret: ReturnType,
+    pub method_merge: JMethodID,
+    pub method_merge_ret: ReturnType,
+    pub method_eval: JMethodID,
+    pub method_eval_ret: ReturnType,
+    pub method_serialize: JMethodID,
+    pub method_serialize_ret: ReturnType,
+    pub method_deserialize: JMethodID,
+    pub method_deserialize_ret: ReturnType,
+}
+impl<'a> SparkUDAFWrapperContext<'a> {
+    pub const SIG_TYPE: &'static str = "org/apache/spark/sql/blaze/SparkUDAFWrapperContext";
+
+    pub fn new(env: &JNIEnv<'a>) -> JniResult<SparkUDAFWrapperContext<'a>> {
+        let class = get_global_jclass(env, Self::SIG_TYPE)?;
+        Ok(SparkUDAFWrapperContext {
+            class,
+            ctor: env.get_method_id(class, "<init>", "(Ljava/nio/ByteBuffer;)V")?,
+            method_update: env.get_method_id(class, "update", "(J)J")?,
+            method_update_ret: ReturnType::Primitive(Primitive::Long),
+            method_merge: env.get_method_id(class, "merge", "(J)J")?,
+            method_merge_ret: ReturnType::Primitive(Primitive::Long),
+            method_eval: env.get_method_id(class, "eval", "(JJ)V")?,
+            method_eval_ret: ReturnType::Primitive(Primitive::Void),
+            method_serialize: env.get_method_id(class, "serialize", "(I)[B")?,
+            method_serialize_ret: ReturnType::Array,
+            method_deserialize: env.get_method_id(
+                class,
+                "deserialize",
+                "(ILjava/nio/ByteBuffer;)V",
+            )?,
+            method_deserialize_ret: ReturnType::Primitive(Primitive::Void),
+        })
+    }
+}
+
 #[allow(non_snake_case)]
 pub struct BlazeCallNativeWrapper<'a> {
     pub class: JClass<'a>,
@@ -26,7 +26,7 @@ use arrow::{
 };
 use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
 use datafusion::{
-    common::stats::Precision,
+    common::{stats::Precision, JoinSide, ScalarValue},
     datasource::{
         listing::{FileRange, PartitionedFile},
         object_store::ObjectStoreUrl,
@@ -45,24 +45,35 @@ use datafusion::{
             NegativeExpr, NotExpr, PhysicalSortExpr,
         },
         union::UnionExec,
-        ColumnStatistics, ExecutionPlan, Partitioning, PhysicalExpr, Statistics,
+        ColumnStatistics, ExecutionPlan, PhysicalExpr, Statistics,
     },
     prelude::create_udf,
 };
-use datafusion_ext_commons::downcast_any;
+use datafusion_ext_commons::{downcast_any, spark_eval_mode::EvalMode};
 use datafusion_ext_exprs::{
-    bloom_filter_might_contain::BloomFilterMightContainExpr, cast::TryCastExpr,
-    get_indexed_field::GetIndexedFieldExpr, get_map_value::GetMapValueExpr,
-    named_struct::NamedStructExpr, row_num::RowNumExpr,
+    binary_arithmetic::BinaryArithmeticExpr,
+    bloom_filter_might_contain::BloomFilterMightContainExpr,
+    cast::TryCastExpr,
+    get_indexed_field::GetIndexedFieldExpr,
+    get_map_value::GetMapValueExpr,
+    higher_order_function::{HigherOrderFunction, HigherOrderFunctionExpr, LambdaFunction},
+    named_struct::NamedStructExpr,
+    row_num::RowNumExpr,
     spark_scalar_subquery_wrapper::SparkScalarSubqueryWrapperExpr,
-    spark_udf_wrapper::SparkUDFWrapperExpr, string_contains::StringContainsExpr,
-    string_ends_with::StringEndsWithExpr, string_starts_with::StringStartsWithExpr,
+    spark_udf_wrapper::SparkUDFWrapperExpr,
+    string_contains::StringContainsExpr,
+    string_ends_with::StringEndsWithExpr,
+    string_starts_with::StringStartsWithExpr,
 };
 use datafusion_ext_plans::{
-    agg::{create_agg, AggExecMode, AggExpr, AggFunction, AggMode, GroupingExpr},
+    agg::{
+        create_agg_with_eval_mode, create_udaf_agg, AggExecMode, AggExpr, AggFunction, AggMode,
+        GroupingExpr,
+    },
     agg_exec::AggExec,
     broadcast_join_build_hash_map_exec::BroadcastJoinBuildHashMapExec,
     broadcast_join_exec::BroadcastJoinExec,
+    broadcast_nested_loop_join_exec::BroadcastNestedLoopJoinExec,
     debug_exec::DebugExec,
     empty_partitions_exec::EmptyPartitionsExec,
     expand_exec::ExpandExec,
@@ -72,6 +83,7 @@ use datafusion_ext_plans::{
     generate_exec::GenerateExec,
     ipc_reader_exec::IpcReaderExec,
     ipc_writer_exec::IpcWriterExec,
+    joins::{JoinBandCondition, JoinFilter},
     limit_exec::LimitExec,
     orc_exec::OrcExec,
     parquet_exec::ParquetExec,
@@ -79,10 +91,15 @@ use datafusion_ext_plans::{
     project_exec::ProjectExec,
     rename_columns_exec::RenameColumnsExec,
     rss_shuffle_writer_exec::RssShuffleWriterExec,
+    shuffle::{Partitioning, RangePartitioning},
     shuffle_writer_exec::ShuffleWriterExec,
+    shuffled_hash_join_exec::ShuffledHashJoinExec,
     sort_exec::SortExec,
     sort_merge_join_exec::SortMergeJoinExec,
-    window::{WindowExpr, WindowFunction, WindowRankType},
+    window::{
+        WindowExpr, WindowFrame, WindowFrameBound, WindowFrameType, WindowFunction,
+        WindowOffsetType, WindowRankType,
+    },
     window_exec::WindowExec,
 };
 use object_store::{path::Path, ObjectMeta};
@@ -92,7 +109,8 @@ use crate::{
     error::PlanSerDeError,
     from_proto_binary_op, proto_error, protobuf,
     protobuf::{
-        physical_expr_node::ExprType, physical_plan_node::PhysicalPlanType, GenerateFunction,
+        physical_expr_node::ExprType, physical_hash_repartition::RepartitionType,
+        physical_plan_node::PhysicalPlanType, GenerateFunction,
     },
     Schema,
 };
@@ -223,7 +241,7 @@ impl TryInto<Arc<dyn ExecutionPlan>> for &protobuf::PhysicalPlanNode {
                 let build_side =
                     protobuf::JoinSide::try_from(hash_join.build_side).expect("invalid BuildSide");
 
-                Ok(Arc::new(BroadcastJoinExec::try_new(
+                Ok(Arc::new(ShuffledHashJoinExec::try_new(
                     schema,
                     left,
                     right,
@@ -234,8 +252,6 @@ impl TryInto<Arc<dyn ExecutionPlan>> for &protobuf::PhysicalPlanNode {
                     build_side
                         .try_into()
                         .map_err(|_| proto_error("invalid BuildSide"))?,
-                    false,
-                    None,
                 )?))
             }
             PhysicalPlanType::SortMergeJoin(sort_merge_join) => {
@@ -268,6 +284,47 @@ impl TryInto<Arc<dyn ExecutionPlan>> for &protobuf::PhysicalPlanNode {
                 let join_type = protobuf::JoinType::try_from(sort_merge_join.join_type)
                     .expect("invalid JoinType");
 
+                let band_condition = sort_merge_join
+                    .band_condition
+                    .as_ref()
+                    .map(|band| {
+                        let point_side: JoinSide = protobuf::JoinSide::try_from(band.point_side)
+                            .expect("invalid JoinSide")
+                            .into();
+                        let (point_schema, range_schema) = match point_side {
+                            JoinSide::Left => (left.schema(), right.schema()),
+                            JoinSide::Right => (right.schema(), left.schema()),
+                        };
+                        let parse_band_expr =
+                            |expr: &Option<protobuf::PhysicalExprNode>, schema: &SchemaRef| {
+                                let expr = try_parse_physical_expr_required(expr, schema)?;
+                                Ok::<_, PlanSerDeError>(bind(expr, schema)?)
+                            };
+                        let point_expr = parse_band_expr(&band.point, &point_schema)?;
+                        let lower_expr = parse_band_expr(&band.lower, &range_schema)?;
+                        let upper_expr = parse_band_expr(&band.upper, &range_schema)?;
+
+                        let data_type = point_expr.data_type(&point_schema)?;
+                        let lower_data_type = lower_expr.data_type(&range_schema)?;
+                        let upper_data_type = upper_expr.data_type(&range_schema)?;
+                        if lower_data_type != data_type || upper_data_type != data_type {
+                            return Err(proto_error(format!(
+                                "band condition data type differs: {data_type:?} <-> \
+                                 [{lower_data_type:?}, {upper_data_type:?}]"
+                            )));
+                        }
+                        Ok::<_, PlanSerDeError>(JoinBandCondition {
+                            point_side,
+                            point_expr,
+                            lower_expr,
+                            upper_expr,
+                            lower_inclusive: band.lower_inclusive,
+                            upper_inclusive: band.upper_inclusive,
+                            data_type,
+                        })
+                    })
+                    .transpose()?;
+
                 Ok(Arc::new(SortMergeJoinExec::try_new(
                     schema,
                     left,
@@ -277,12 +334,63 @@ impl TryInto<Arc<dyn ExecutionPlan>> for &protobuf::PhysicalPlanNode {
                         .try_into()
                         .map_err(|_| proto_error("invalid JoinType"))?,
                     sort_options,
+                    band_condition,
+                )?))
+            }
+            PhysicalPlanType::BroadcastNestedLoopJoin(bnlj) => {
+                let schema = Arc::new(convert_required!(bnlj.schema)?);
+                let left: Arc<dyn ExecutionPlan> = convert_box_required!(bnlj.left)?;
+                let right: Arc<dyn ExecutionPlan> = convert_box_required!(bnlj.right)?;
+                let join_type =
+                    protobuf::JoinType::try_from(bnlj.join_type).expect("invalid JoinType");
+                let broadcast_side = protobuf::JoinSide::try_from(bnlj.broadcast_side)
+                    .expect("invalid BroadcastSide");
+                let join_filter = bnlj
+                    .join_filter
+                    .as_ref()
+                    .map(|join_filter| {
+                        let filter_schema: SchemaRef =
+                            Arc::new(convert_required!(join_filter.schema)?);
+                        let expression = try_parse_physical_expr_required(
+                            &join_filter.expression,
+                            &filter_schema,
+                        )?;
+                        let column_indices = join_filter
+                            .column_indices
+                            .iter()
+                            .map(|column_index| {
+                                let side: JoinSide =
+                                    protobuf::JoinSide::try_from(column_index.side)
+                                        .expect("invalid JoinSide")
+                                        .into();
+                                (side, column_index.index as usize)
+                            })
+                            .collect();
+                        Ok::<_, PlanSerDeError>(JoinFilter {
+                            expression: bind(expression, &filter_schema)?,
+                            column_indices,
+                            schema: filter_schema,
+                        })
+                    })
+                    .transpose()?;
+
+                Ok(Arc::new(BroadcastNestedLoopJoinExec::try_new(
+                    schema,
+                    left,
+                    right,
+                    join_type
+                        .try_into()
+                        .map_err(|_| proto_error("invalid JoinType"))?,
+                    broadcast_side
+                        .try_into()
+                        .map_err(|_| proto_error("invalid BroadcastSide"))?,
+                    join_filter,
                 )?))
             }
             PhysicalPlanType::ShuffleWriter(shuffle_writer) => {
                 let input: Arc<dyn ExecutionPlan> = convert_box_required!(shuffle_writer.input)?;
 
-                let output_partitioning = parse_protobuf_hash_partitioning(
+                let output_partitioning = parse_protobuf_partitioning(
                     input.clone(),
                     shuffle_writer.output_partitioning.as_ref(),
                 )?;
@@ -298,7 +406,7 @@ impl TryInto<Arc<dyn ExecutionPlan>> for &protobuf::PhysicalPlanNode {
                 let input: Arc<dyn ExecutionPlan> =
                     convert_box_required!(rss_shuffle_writer.input)?;
 
-                let output_partitioning = parse_protobuf_hash_partitioning(
+                let output_partitioning = parse_protobuf_partitioning(
                     input.clone(),
                     rss_shuffle_writer.output_partitioning.as_ref(),
                 )?;
@@ -520,12 +628,26 @@ impl TryInto<Arc<dyn ExecutionPlan>> for &protobuf::PhysicalPlanNode {
                             })
                             .collect::<Result<Vec<_>, _>>()?;
 
-                        Ok(AggExpr {
-                            agg: create_agg(
+                        let agg = match agg_function {
+                            protobuf::AggFunction::Udaf => {
+                                let udaf = agg_node.udaf.as_ref().unwrap();
+                                let serialized = udaf.serialized.clone();
+                                let return_type = convert_required!(udaf.return_type)?;
+                                create_udaf_agg(serialized, return_type, agg_children_exprs)?
+                            }
+                            _ => create_agg_with_eval_mode(
                                 AggFunction::from(agg_function),
                                 &agg_children_exprs,
                                 &input_schema,
+                                EvalMode::from(
+                                    protobuf::EvalMode::try_from(agg_node.eval_mode)
+                                        .expect("invalid EvalMode"),
+                                ),
                             )?,
+                        };
+
+                        Ok(AggExpr {
+                            agg,
                             mode,
                             field_name: name.to_owned(),
                         })
@@ -619,39 +741,41 @@ impl TryInto<Arc<dyn ExecutionPlan>> for &protobuf::PhysicalPlanNode {
                                 protobuf::WindowFunction::DenseRank => {
                                     WindowFunction::RankLike(WindowRankType::DenseRank)
                                 }
-                            },
-                            protobuf::WindowFunctionType::Agg => match w.agg_func() {
-                                protobuf::AggFunction::Min => WindowFunction::Agg(AggFunction::Min),
-                                protobuf::AggFunction::Max => WindowFunction::Agg(AggFunction::Max),
-                                protobuf::AggFunction::Sum => WindowFunction::Agg(AggFunction::Sum),
-                                protobuf::AggFunction::Avg => WindowFunction::Agg(AggFunction::Avg),
-                                protobuf::AggFunction::Count => {
-                                    WindowFunction::Agg(AggFunction::Count)
+                                protobuf::WindowFunction::PercentRank => {
+                                    WindowFunction::RankLike(WindowRankType::PercentRank)
                                 }
-                                protobuf::AggFunction::CollectList => {
-                                    WindowFunction::Agg(AggFunction::CollectList)
+                                protobuf::WindowFunction::CumeDist => {
+                                    WindowFunction::RankLike(WindowRankType::CumeDist)
                                 }
-                                protobuf::AggFunction::CollectSet => {
-                                    WindowFunction::Agg(AggFunction::CollectSet)
+                                protobuf::WindowFunction::Ntile => {
+                                    WindowFunction::RankLike(WindowRankType::Ntile)
                                 }
-                                protobuf::AggFunction::First => {
-                                    WindowFunction::Agg(AggFunction::First)
+                                protobuf::WindowFunction::Lag => {
+                                    WindowFunction::Offset(WindowOffsetType::Lag)
                                 }
-                                protobuf::AggFunction::FirstIgnoresNull => {
-                                    WindowFunction::Agg(AggFunction::FirstIgnoresNull)
+                                protobuf::WindowFunction::Lead => {
+                                    WindowFunction::Offset(WindowOffsetType::Lead)
                                 }
-                                protobuf::AggFunction::BloomFilter => {
-                                    WindowFunction::Agg(AggFunction::BloomFilter)
+                                protobuf::WindowFunction::NthValue => {
+                                    WindowFunction::Offset(WindowOffsetType::NthValue)
                                 }
-                                protobuf::AggFunction::BrickhouseCollect => {
-                                    WindowFunction::Agg(AggFunction::BrickhouseCollect)
+                                protobuf::WindowFunction::FirstValue => {
+                                    WindowFunction::Offset(WindowOffsetType::FirstValue)
                                 }
-                                protobuf::AggFunction::BrickhouseCombineUnique => {
-                                    WindowFunction::Agg(AggFunction::BrickhouseCombineUnique)
+                                protobuf::WindowFunction::LastValue => {
+                                    WindowFunction::Offset(WindowOffsetType::LastValue)
                                 }
                             },
+                            protobuf::WindowFunctionType::Agg => {
+                                WindowFunction::Agg(w.agg_func().into())
+                            }
                         };
-                        Ok::<_, Self::Error>(WindowExpr::new(window_func, children, field))
+                        let mut window_expr = WindowExpr::new(window_func, children, field)
+                            .with_ignore_nulls(w.ignore_nulls);
+                        if let Some(frame) = &w.frame {
+                            window_expr = window_expr.with_frame(try_parse_window_frame(frame)?);
+                        }
+                        Ok::<_, Self::Error>(window_expr)
                     })
                     .collect::<Result<Vec<_>, _>>()?;
 
@@ -900,11 +1024,17 @@ fn try_parse_physical_expr(
                 let pcol: Column = bound_reference.into();
                 Arc::new(pcol)
             }
-            ExprType::BinaryExpr(binary_expr) => Arc::new(BinaryExpr::new(
-                try_parse_physical_expr_box_required(&binary_expr.l.clone(), input_schema)?,
-                from_proto_binary_op(&binary_expr.op)?,
-                try_parse_physical_expr_box_required(&binary_expr.r.clone(), input_schema)?,
-            )),
+            ExprType::BinaryExpr(binary_expr) => {
+                let l = try_parse_physical_expr_box_required(&binary_expr.l.clone(), input_schema)?;
+                let op = from_proto_binary_op(&binary_expr.op)?;
+                let r = try_parse_physical_expr_box_required(&binary_expr.r.clone(), input_schema)?;
+                let eval_mode = protobuf::EvalMode::try_from(binary_expr.eval_mode)
+                    .expect("invalid EvalMode");
+                match eval_mode {
+                    protobuf::EvalMode::Legacy => Arc::new(BinaryExpr::new(l, op, r)),
+                    _ => Arc::new(BinaryArithmeticExpr::try_new(l, op, r, eval_mode.into())?),
+                }
+            }
             ExprType::AggExpr(_) => {
                 return Err(PlanSerDeError::General(
                     "Cannot convert aggregate expr node to physical expression".to_owned(),
@@ -980,11 +1110,20 @@ fn try_parse_physical_expr(
                     .map(|e| try_parse_physical_expr(e.as_ref(), input_schema))
                     .transpose()?,
             )?),
-            ExprType::Cast(e) => Arc::new(CastExpr::new(
-                try_parse_physical_expr_box_required(&e.expr, input_schema)?,
-                convert_required!(e.arrow_type)?,
-                None,
-            )),
+            ExprType::Cast(e) => {
+                let expr = try_parse_physical_expr_box_required(&e.expr, input_schema)?;
+                let cast_type = convert_required!(e.arrow_type)?;
+                let eval_mode =
+                    protobuf::EvalMode::try_from(e.eval_mode).expect("invalid EvalMode");
+                match eval_mode {
+                    protobuf::EvalMode::Legacy => Arc::new(CastExpr::new(expr, cast_type, None)),
+                    _ => Arc::new(TryCastExpr::new_with_eval_mode(
+                        expr,
+                        cast_type,
+                        eval_mode.into(),
+                    )),
+                }
+            }
             ExprType::TryCast(e) => {
                 let expr = try_parse_physical_expr_box_required(&e.expr, input_schema)?;
                 let cast_type = convert_required!(e.arrow_type)?;
@@ -1000,13 +1139,15 @@ fn try_parse_physical_expr(
                     .collect::<Result<Vec<_>, _>>()?;
 
                 let scalar_udf = if scalar_function == protobuf::ScalarFunction::SparkExtFunctions {
-                    let fun = datafusion_ext_functions::create_spark_ext_function(&e.name)?;
+                    let return_type = convert_required!(e.return_type)?;
+                    let fun =
+                        datafusion_ext_functions::create_spark_ext_function(&e.name, &return_type)?;
                     Arc::new(create_udf(
                         "spark_ext_function",
                         args.iter()
                             .map(|e| e.data_type(input_schema))
                             .collect::<Result<Vec<_>, _>>()?,
-                        Arc::new(convert_required!(e.return_type)?),
+                        Arc::new(return_type),
                         Volatility::Volatile,
                         fun,
                     ))
@@ -1092,11 +1233,94 @@ fn try_parse_physical_expr(
                     data_type,
                 )?)
             }
+            ExprType::HigherOrderFunctionExpr(e) => {
+                let pb_func = protobuf::HigherOrderFunction::try_from(e.func)
+                    .expect("unsupported higher-order function");
+                let func = match pb_func {
+                    protobuf::HigherOrderFunction::Transform => HigherOrderFunction::Transform,
+                    protobuf::HigherOrderFunction::Filter => HigherOrderFunction::Filter,
+                    protobuf::HigherOrderFunction::Exists => HigherOrderFunction::Exists,
+                    protobuf::HigherOrderFunction::Forall => HigherOrderFunction::Forall,
+                    protobuf::HigherOrderFunction::Aggregate => HigherOrderFunction::Aggregate,
+                    protobuf::HigherOrderFunction::ZipWith => HigherOrderFunction::ZipWith,
+                    protobuf::HigherOrderFunction::MapFilter => HigherOrderFunction::MapFilter,
+                };
+                Arc::new(HigherOrderFunctionExpr::try_new(
+                    func,
+                    e.args
+                        .iter()
+                        .map(|x| try_parse_physical_expr(x, input_schema))
+                        .collect::<Result<Vec<_>, _>>()?,
+                    e.lambdas
+                        .iter()
+                        .map(|x| try_parse_lambda_function(x, input_schema))
+                        .collect::<Result<Vec<_>, _>>()?,
+                    convert_required!(e.return_type)?,
+                )?)
+            }
+            ExprType::NamedLambdaVariableExpr(e) => {
+                Arc::new(Column::new_with_schema(&e.name, input_schema)?)
+            }
         };
 
     Ok(pexpr)
 }
 
+fn try_parse_lambda_function(
+    lambda: &protobuf::PhysicalLambdaFunctionNode,
+    input_schema: &SchemaRef,
+) -> Result<LambdaFunction, PlanSerDeError> {
+    let args = lambda
+        .args
+        .iter()
+        .map(|arg| {
+            let data_type = convert_required!(arg.data_type)?;
+            Ok(Arc::new(Field::new(&arg.name, data_type, arg.nullable)))
+        })
+        .collect::<Result<Vec<FieldRef>, PlanSerDeError>>()?;
+
+    // lambda variables are appended to the input schema, so the body can refer
+    // to both input columns and lambda variables
+    let lambda_schema = Arc::new(Schema::new(
+        input_schema
+            .fields()
+            .iter()
+            .chain(&args)
+            .cloned()
+            .collect::<Vec<_>>(),
+    ));
+    let body = try_parse_physical_expr_required(&lambda.body, &lambda_schema)?;
+    Ok(LambdaFunction::new(args, bind(body, &lambda_schema)?))
+}
+
+fn try_parse_window_frame(frame: &protobuf::WindowFrame) -> Result<WindowFrame, PlanSerDeError> {
+    let parse_bound = |bound: &Option<protobuf::WindowFrameBound>| {
+        let bound = bound
+            .as_ref()
+            .ok_or_else(|| proto_error("missing window frame bound"))?;
+        let offset = || -> Result<ScalarValue, PlanSerDeError> { convert_required!(bound.offset) };
+        Ok::<_, PlanSerDeError>(match bound.bound_type() {
+            protobuf::WindowFrameBoundType::UnboundedPreceding => {
+                WindowFrameBound::UnboundedPreceding
+            }
+            protobuf::WindowFrameBoundType::Preceding => WindowFrameBound::Preceding(offset()?),
+            protobuf::WindowFrameBoundType::CurrentRow => WindowFrameBound::CurrentRow,
+            protobuf::WindowFrameBoundType::Following => WindowFrameBound::Following(offset()?),
+            protobuf::WindowFrameBoundType::UnboundedFollowing => {
+                WindowFrameBound::UnboundedFollowing
+            }
+        })
+    };
+    Ok(WindowFrame {
+        frame_type: match frame.frame_type() {
+            protobuf::WindowFrameType::Rows => WindowFrameType::Rows,
+            protobuf::WindowFrameType::Range => WindowFrameType::Range,
+        },
+        start: parse_bound(&frame.start)?,
+        end: parse_bound(&frame.end)?,
+    })
+}
+
 fn try_parse_physical_expr_required(
     proto: &Option<protobuf::PhysicalExprNode>,
     input_schema: &SchemaRef,
@@ -1119,28 +1343,70 @@ fn try_parse_physical_expr_box_required(
     }
 }
 
-pub fn parse_protobuf_hash_partitioning(
+pub fn parse_protobuf_partitioning(
     input: Arc<dyn ExecutionPlan>,
     partitioning: Option<&protobuf::PhysicalHashRepartition>,
 ) -> Result<Option<Partitioning>, PlanSerDeError> {
-    match partitioning {
-        Some(hash_part) => {
-            let expr = hash_part
+    let Some(repartition) = partitioning else {
+        return Ok(None);
+    };
+    let input_schema = input.schema();
+    let partition_count = repartition.partition_count.try_into().unwrap();
+
+    Ok(Some(match &repartition.repartition_type {
+        None => {
+            let expr = repartition
                 .hash_expr
                 .iter()
                 .map(|e| {
-                    try_parse_physical_expr(e, &input.schema())
-                        .and_then(|e| Ok(bind(e, &input.schema())?))
+                    try_parse_physical_expr(e, &input_schema)
+                        .and_then(|e| Ok(bind(e, &input_schema)?))
                 })
                 .collect::<Result<Vec<Arc<dyn PhysicalExpr>>, _>>()?;
-
-            Ok(Some(Partitioning::Hash(
-                expr,
-                hash_part.partition_count.try_into().unwrap(),
-            )))
+            Partitioning::Hash(expr, partition_count)
         }
-        None => Ok(None),
-    }
+        Some(RepartitionType::RangeRepartition(range_part)) => {
+            let sort_exprs = range_part
+                .sort_expr
+                .iter()
+                .map(|sort_expr| {
+                    let expr = sort_expr.expr.as_ref().ok_or_else(|| {
+                        proto_error("Missing required field in protobuf: sort_expr.expr")
+                    })?;
+                    Ok(PhysicalSortExpr {
+                        expr: bind(try_parse_physical_expr(expr, &input_schema)?, &input_schema)?,
+                        options: SortOptions {
+                            descending: !sort_expr.asc,
+                            nulls_first: sort_expr.nulls_first,
+                        },
+                    })
+                })
+                .collect::<Result<Vec<_>, PlanSerDeError>>()?;
+            let bounds = range_part
+                .bounds
+                .iter()
+                .map(|bound| {
+                    let bound: ScalarValue = bound.try_into()?;
+                    match bound {
+                        ScalarValue::List(list) => Ok(list.value(0)),
+                        other => Err(proto_error(format!(
+                            "invalid range partitioning bounds: {other:?}"
+                        ))),
+                    }
+                })
+                .collect::<Result<Vec<_>, PlanSerDeError>>()?;
+            Partitioning::Range(RangePartitioning::try_new(
+                sort_exprs,
+                partition_count,
+                bounds,
+                &input_schema,
+            )?)
+        }
+        Some(RepartitionType::RoundRobinRepartition(_)) => {
+            Partitioning::RoundRobin(partition_count)
+        }
+        Some(RepartitionType::SingleRepartition(_)) => Partitioning::Single,
+    }))
 }
 
 impl TryFrom<&protobuf::PartitionedFile> for PartitionedFile {
@@ -16,6 +16,7 @@ use std::sync::Arc;
 
 use arrow::datatypes::{DataType, Field, Fields, IntervalUnit, Schema, TimeUnit};
 use datafusion::{common::JoinSide, logical_expr::Operator, scalar::ScalarValue};
+use datafusion_ext_commons::spark_eval_mode::EvalMode;
 use datafusion_ext_plans::{agg::AggFunction, joins::join_utils::JoinType};
 
 use crate::error::PlanSerDeError;
@@ -136,8 +137,40 @@ impl From<protobuf::AggFunction> for AggFunction {
             protobuf::AggFunction::First => AggFunction::First,
             protobuf::AggFunction::FirstIgnoresNull => AggFunction::FirstIgnoresNull,
             protobuf::AggFunction::BloomFilter => AggFunction::BloomFilter,
+            protobuf::AggFunction::ApproxCountDistinct => AggFunction::ApproxCountDistinct,
+            protobuf::AggFunction::Percentile => AggFunction::Percentile,
+            protobuf::AggFunction::ApproxPercentile => AggFunction::ApproxPercentile,
+            protobuf::AggFunction::Median => AggFunction::Median,
+            protobuf::AggFunction::StddevSamp => AggFunction::StddevSamp,
+            protobuf::AggFunction::StddevPop => AggFunction::StddevPop,
+            protobuf::AggFunction::VarSamp => AggFunction::VarSamp,
+            protobuf::AggFunction::VarPop => AggFunction::VarPop,
+            protobuf::AggFunction::CovarSamp => AggFunction::CovarSamp,
+            protobuf::AggFunction::CovarPop => AggFunction::CovarPop,
+            protobuf::AggFunction::Corr => AggFunction::Corr,
+            protobuf::AggFunction::BoolAnd => AggFunction::BoolAnd,
+            protobuf::AggFunction::BoolOr => AggFunction::BoolOr,
+            protobuf::AggFunction::BitAnd => AggFunction::BitAnd,
+            protobuf::AggFunction::BitOr => AggFunction::BitOr,
+            protobuf::AggFunction::BitXor => AggFunction::BitXor,
+            protobuf::AggFunction::CountIf => AggFunction::CountIf,
+            protobuf::AggFunction::Last => AggFunction::Last,
+            protobuf::AggFunction::LastIgnoresNull => AggFunction::LastIgnoresNull,
+            protobuf::AggFunction::MaxBy => AggFunction::MaxBy,
+            protobuf::AggFunction::MinBy => AggFunction::MinBy,
             protobuf::AggFunction::BrickhouseCollect => AggFunction::BrickhouseCollect,
             protobuf::AggFunction::BrickhouseCombineUnique => AggFunction::BrickhouseCombineUnique,
+            protobuf::AggFunction::Udaf => AggFunction::Udaf,
+        }
+    }
+}
+
+impl From<protobuf::EvalMode> for EvalMode {
+    fn from(eval_mode: protobuf::EvalMode) -> EvalMode {
+        match eval_mode {
+            protobuf::EvalMode::Legacy => EvalMode::Legacy,
+            protobuf::EvalMode::Ansi => EvalMode::Ansi,
+            protobuf::EvalMode::Try => EvalMode::Try,
         }
     }
 }
@@ -15,7 +15,7 @@
 use std::{str::FromStr, sync::Arc};
 
 use arrow::{array::*, datatypes::*};
-use bigdecimal::{FromPrimitive, ToPrimitive};
+use bigdecimal::{BigDecimal, FromPrimitive, RoundingMode, ToPrimitive};
 use datafusion::common::{
     cast::{as_float32_array, as_float64_array},
     Result,
@@ -23,12 +23,110 @@ use datafusion::common::{
 use num::{cast::AsPrimitive, Bounded, Integer, Signed};
 use paste::paste;
 
-use crate::df_execution_err;
+use crate::{
+    df_execution_err, df_unimplemented_err,
+    spark_eval_mode::{
+        cast_invalid_input_err, cast_overflow_err, numeric_value_out_of_range_err, spark_sql_value,
+        spark_type_name, EvalMode,
+    },
+};
 
 pub fn cast(array: &dyn Array, cast_type: &DataType) -> Result<ArrayRef> {
     return cast_impl(array, cast_type, false);
 }
 
+/// spark compatible cast under the specified eval mode. in ANSI mode, an error
+/// is raised when a non-null value cannot be casted, instead of returning null.
+/// note that nested values are not checked, so complex types should not be
+/// casted in ANSI mode.
+pub fn cast_with_eval_mode(
+    array: &dyn Array,
+    cast_type: &DataType,
+    eval_mode: EvalMode,
+) -> Result<ArrayRef> {
+    if eval_mode != EvalMode::Ansi || array.data_type() == &DataType::Null {
+        return cast(array, cast_type);
+    }
+    if array.data_type() == &DataType::Utf8 {
+        // only spark compatible string parsers can tell invalid inputs
+        match cast_type {
+            DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64 => {}
+            DataType::Decimal128(..) | DataType::Utf8 => {}
+            _ => {
+                return df_unimplemented_err!(
+                    "ANSI cast from STRING to {} is not supported",
+                    spark_type_name(cast_type)
+                );
+            }
+        }
+    }
+    check_ansi_cast_input(array, cast_type)?;
+
+    let casted = cast(array, cast_type)?;
+    if casted.null_count() > array.null_count() {
+        for i in 0..array.len() {
+            if array.is_valid(i) && casted.is_null(i) {
+                if array.data_type() == &DataType::Utf8 {
+                    let value = as_string_array(array).value(i);
+                    return match cast_type {
+                        // valid decimals exceeding the precision
+                        &DataType::Decimal128(precision, scale)
+                            if parse_decimal(value).is_some() =>
+                        {
+                            numeric_value_out_of_range_err(value.trim(), precision, scale)
+                        }
+                        _ => cast_invalid_input_err(value, array.data_type(), cast_type),
+                    };
+                }
+                let value = spark_sql_value(array, i)?;
+                return match cast_type {
+                    &DataType::Decimal128(precision, scale) => {
+                        numeric_value_out_of_range_err(&value, precision, scale)
+                    }
+                    _ => cast_overflow_err(&value, array.data_type(), cast_type),
+                };
+            }
+        }
+    }
+    Ok(casted)
+}
+
+// raises errors for the inputs accepted by legacy cast but rejected by ANSI
+fn check_ansi_cast_input(array: &dyn Array, cast_type: &DataType) -> Result<()> {
+    let (min, max) = match cast_type {
+        DataType::Int8 => (i8::MIN as f64, i8::MAX as f64),
+        DataType::Int16 => (i16::MIN as f64, i16::MAX as f64),
+        DataType::Int32 => (i32::MIN as f64, i32::MAX as f64),
+        DataType::Int64 => (i64::MIN as f64, i64::MAX as f64),
+        _ => return Ok(()),
+    };
+
+    match array.data_type() {
+        DataType::Float32 | DataType::Float64 => {
+            // legacy cast saturates out-of-range floats
+            let values = arrow::compute::cast(array, &DataType::Float64)?;
+            for (i, v) in as_float64_array(&values)?.iter().enumerate() {
+                if let Some(v) = v {
+                    if !(v.floor() <= max && v.ceil() >= min) {
+                        let value = spark_sql_value(array, i)?;
+                        return cast_overflow_err(&value, array.data_type(), cast_type);
+                    }
+                }
+            }
+        }
+        DataType::Utf8 => {
+            // legacy cast truncates the fractional part
+            for v in as_string_array(array).iter().flatten() {
+                if v.contains('.') {
+                    return cast_invalid_input_err(v, array.data_type(), cast_type);
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
 pub fn cast_scan_input_array(array: &dyn Array, cast_type: &DataType) -> Result<ArrayRef> {
     return cast_impl(array, cast_type, true);
 }
@@ -363,16 +461,19 @@ fn to_integer<T: Bounded + FromPrimitive + Integer + Signed + Copy>(input: &str)
     Some(result)
 }
 
+// rounds half up to the scale like spark's Decimal.changePrecision(), returns
+// None if the rounded value exceeds the precision
 fn to_decimal(input: &str, precision: u8, scale: i8) -> Option<i128> {
-    let precision = precision as u64;
-    let scale = scale as i64;
-    bigdecimal::BigDecimal::from_str(input)
-        .ok()
-        .map(|decimal| decimal.with_prec(precision).with_scale(scale))
-        .and_then(|decimal| {
-            let (bigint, _exp) = decimal.as_bigint_and_exponent();
-            bigint.to_i128()
-        })
+    let decimal = parse_decimal(input)?.with_scale_round(scale as i64, RoundingMode::HalfUp);
+    let (bigint, _exp) = decimal.as_bigint_and_exponent();
+    let value = bigint.to_i128()?;
+    (value.unsigned_abs() < 10u128.pow(precision as u32)).then_some(value)
+}
+
+// parses decimal strings like java's BigDecimal, with leading and trailing
+// whitespaces and control characters trimmed like java's String.trim()
+fn parse_decimal(input: &str) -> Option<BigDecimal> {
+    BigDecimal::from_str(input.trim_matches(|c: char| c <= ' ')).ok()
 }
 
 #[cfg(test)]
@@ -410,4 +511,76 @@ mod test {
             ])
         );
     }
+
+    #[test]
+    fn test_ansi_cast() {
+        let string_array: ArrayRef = Arc::new(StringArray::from(vec![None, Some("123")]));
+        let casted = cast_with_eval_mode(&string_array, &DataType::Int32, EvalMode::Ansi).unwrap();
+        assert_eq!(
+            as_int32_array(&casted).unwrap(),
+            &Int32Array::from(vec![None, Some(123)])
+        );
+
+        for (input, cast_type) in [
+            ("abc", DataType::Int32),
+            ("1.5", DataType::Int64),
+            ("xyz", DataType::Decimal128(10, 2)),
+        ] {
+            let string_array: ArrayRef = Arc::new(StringArray::from(vec![Some(input)]));
+            let err = cast_with_eval_mode(&string_array, &cast_type, EvalMode::Ansi).unwrap_err();
+            assert!(err.to_string().contains("[CAST_INVALID_INPUT]"));
+
+            // legacy and try mode returns null
+            for eval_mode in [EvalMode::Legacy, EvalMode::Try] {
+                let casted = cast_with_eval_mode(&string_array, &cast_type, eval_mode).unwrap();
+                assert_eq!(casted.null_count(), usize::from(input != "1.5"));
+            }
+        }
+
+        let int_array: ArrayRef = Arc::new(Int32Array::from(vec![Some(1), Some(300)]));
+        let err = cast_with_eval_mode(&int_array, &DataType::Int8, EvalMode::Ansi).unwrap_err();
+        assert!(err.to_string().contains(
+            "[CAST_OVERFLOW] The value 300 of the type \"INT\" cannot be cast to \"TINYINT\""
+        ));
+
+        let f64_array: ArrayRef = Arc::new(Float64Array::from(vec![Some(i32::MAX as f64 + 1.0)]));
+        let err = cast_with_eval_mode(&f64_array, &DataType::Int32, EvalMode::Ansi).unwrap_err();
+        assert!(err.to_string().contains("[CAST_OVERFLOW]"));
+
+        let err = cast_with_eval_mode(&int_array, &DataType::Decimal128(3, 1), EvalMode::Ansi)
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("[NUMERIC_VALUE_OUT_OF_RANGE] 300 cannot be represented as Decimal(3, 1)"));
+
+        // valid decimal strings exceeding the precision
+        let string_array: ArrayRef = Arc::new(StringArray::from(vec![Some(" 12345.6 ")]));
+        let err = cast_with_eval_mode(&string_array, &DataType::Decimal128(5, 1), EvalMode::Ansi)
+            .unwrap_err();
+        assert!(err.to_string().contains(
+            "[NUMERIC_VALUE_OUT_OF_RANGE] 12345.6 cannot be represented as Decimal(5, 1)"
+        ));
+
+        // string parsers other than integral and decimal ones are not spark compatible
+        for cast_type in [DataType::Float64, DataType::Boolean, DataType::Date32] {
+            let string_array: ArrayRef = Arc::new(StringArray::from(vec![Some(" 1.5 ")]));
+            assert!(cast_with_eval_mode(&string_array, &cast_type, EvalMode::Ansi).is_err());
+        }
+    }
+
+    #[test]
+    fn test_string_to_decimal() {
+        let string_array: ArrayRef = Arc::new(StringArray::from(vec![
+            Some(" 1.25 "),
+            Some("-1.25"),
+            Some("1e2"),
+            Some("999.95"),
+            Some("abc"),
+        ]));
+        let casted = cast(&string_array, &DataType::Decimal128(4, 1)).unwrap();
+        let expected = Decimal128Array::from(vec![Some(13), Some(-13), Some(1000), None, None])
+            .with_precision_and_scale(4, 1)
+            .unwrap();
+        assert_eq!(as_primitive_array::<Decimal128Type>(&casted), &expected);
+    }
 }
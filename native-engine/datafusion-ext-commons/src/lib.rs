@@ -36,7 +36,10 @@ pub mod rdxsort;
 pub mod slim_bytes;
 pub mod spark_bit_array;
 pub mod spark_bloom_filter;
+pub mod spark_eval_mode;
 pub mod spark_hash;
+pub mod spark_hyperloglog;
+pub mod spark_percentile_digest;
 pub mod streams;
 pub mod uda;
 
@@ -0,0 +1,125 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use arrow::{
+    array::Array,
+    datatypes::DataType,
+    util::display::{ArrayFormatter, FormatOptions},
+};
+use datafusion::common::Result;
+
+use crate::df_execution_err;
+
+/// evaluation mode of spark expressions, decides how invalid inputs and
+/// overflows are handled, see spark's `EvalMode`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EvalMode {
+    /// returns null on invalid inputs and wraps on overflows
+    #[default]
+    Legacy,
+    /// raises errors on invalid inputs and overflows
+    /// (spark.sql.ansi.enabled=true)
+    Ansi,
+    /// returns null on invalid inputs and overflows (try_* functions)
+    Try,
+}
+
+const ANSI_HINT: &str =
+    "If necessary set \"spark.sql.ansi.enabled\" to \"false\" to bypass this error.";
+
+/// returns the sql name of a data type, as displayed in spark's error messages
+pub fn spark_type_name(dt: &DataType) -> String {
+    match dt {
+        DataType::Null => "VOID".to_string(),
+        DataType::Boolean => "BOOLEAN".to_string(),
+        DataType::Int8 => "TINYINT".to_string(),
+        DataType::Int16 => "SMALLINT".to_string(),
+        DataType::Int32 => "INT".to_string(),
+        DataType::Int64 => "BIGINT".to_string(),
+        DataType::Float32 => "FLOAT".to_string(),
+        DataType::Float64 => "DOUBLE".to_string(),
+        DataType::Decimal128(prec, scale) => format!("DECIMAL({prec},{scale})"),
+        DataType::Utf8 | DataType::LargeUtf8 => "STRING".to_string(),
+        DataType::Binary | DataType::LargeBinary => "BINARY".to_string(),
+        DataType::Date32 => "DATE".to_string(),
+        DataType::Timestamp(..) => "TIMESTAMP".to_string(),
+        other => format!("{other:?}").to_uppercase(),
+    }
+}
+
+/// returns the sql literal of a value, as displayed in spark's error messages
+pub fn spark_sql_value(array: &dyn Array, idx: usize) -> Result<String> {
+    let value = ArrayFormatter::try_new(array, &FormatOptions::default())?
+        .value(idx)
+        .to_string();
+    Ok(match array.data_type() {
+        DataType::Int8 => format!("{value}Y"),
+        DataType::Int16 => format!("{value}S"),
+        DataType::Int64 => format!("{value}L"),
+        DataType::Float64 => format!("{value}D"),
+        DataType::Decimal128(..) => format!("{value}BD"),
+        DataType::Utf8 | DataType::LargeUtf8 => format!("'{value}'"),
+        _ => value,
+    })
+}
+
+pub fn cast_invalid_input_err<T>(value: &str, from: &DataType, to: &DataType) -> Result<T> {
+    df_execution_err!(
+        "[CAST_INVALID_INPUT] The value '{value}' of the type \"{}\" cannot be cast to \"{}\" \
+            because it is malformed. Correct the value as per the syntax, or change its target \
+            type. Use `try_cast` to tolerate malformed input and return NULL instead. {ANSI_HINT}",
+        spark_type_name(from),
+        spark_type_name(to),
+    )
+}
+
+pub fn cast_overflow_err<T>(value: &str, from: &DataType, to: &DataType) -> Result<T> {
+    df_execution_err!(
+        "[CAST_OVERFLOW] The value {value} of the type \"{}\" cannot be cast to \"{}\" due to \
+            an overflow. Use `try_cast` to tolerate overflow and return NULL instead. {ANSI_HINT}",
+        spark_type_name(from),
+        spark_type_name(to),
+    )
+}
+
+pub fn numeric_value_out_of_range_err<T>(value: &str, precision: u8, scale: i8) -> Result<T> {
+    df_execution_err!(
+        "[NUMERIC_VALUE_OUT_OF_RANGE] {value} cannot be represented as Decimal({precision}, \
+            {scale}). If necessary set \"spark.sql.ansi.enabled\" to \"false\" to bypass this \
+            error, and return NULL instead."
+    )
+}
+
+/// overflow error of integral arithmetic, `try_function` is the suggested
+/// function to tolerate the overflow
+pub fn arithmetic_overflow_err<T>(data_type: &DataType, try_function: Option<&str>) -> Result<T> {
+    let type_name = match data_type {
+        DataType::Int8 => "byte",
+        DataType::Int16 => "short",
+        DataType::Int32 => "integer",
+        DataType::Decimal128(..) => "decimal",
+        _ => "long",
+    };
+    let suggestion = try_function
+        .map(|f| format!("Use '{f}' to tolerate overflow and return NULL instead. "))
+        .unwrap_or_default();
+    df_execution_err!("[ARITHMETIC_OVERFLOW] {type_name} overflow. {suggestion}{ANSI_HINT}")
+}
+
+pub fn divide_by_zero_err<T>() -> Result<T> {
+    df_execution_err!(
+        "[DIVIDE_BY_ZERO] Division by zero. Use `try_divide` to tolerate divisor being 0 and \
+            return NULL instead. {ANSI_HINT}"
+    )
+}
@@ -0,0 +1,245 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod bias_data;
+
+use std::{
+    fmt::{Debug, Formatter},
+    io::{Read, Write},
+};
+
+use byteorder::{ReadBytesExt, WriteBytesExt, LE};
+use datafusion::common::Result;
+
+use crate::df_execution_err;
+
+const REGISTER_SIZE: usize = 6;
+const REGISTERS_PER_WORD: usize = 64 / REGISTER_SIZE;
+const REGISTER_WORD_MASK: u64 = (1 << REGISTER_SIZE) - 1;
+
+// number of nearest neighbors used in bias estimation
+const K: usize = 6;
+
+// thresholds of linear counting for precisions 4 to 18
+const THRESHOLDS: [f64; 15] = [
+    10.0, 20.0, 40.0, 80.0, 220.0, 400.0, 900.0, 1800.0, 3100.0, 6500.0, 11500.0, 20000.0, 50000.0,
+    120000.0, 350000.0,
+];
+
+/// HyperLogLog++ sketch compatible with spark's HyperLogLogPlusPlus.
+///
+/// Registers are packed into 64-bit words in exactly the same layout as
+/// spark's aggregation buffer (10 registers of 6 bits per word), so that the
+/// words can be exchanged with the JVM side directly.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SparkHyperLogLog {
+    p: usize,
+    words: Box<[u64]>,
+}
+
+impl Debug for SparkHyperLogLog {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SparkHyperLogLog")
+            .field("p", &self.p)
+            .field("num_words", &self.words.len())
+            .finish()
+    }
+}
+
+impl SparkHyperLogLog {
+    /// computes precision from the relative standard deviation, same as spark
+    pub fn precision_of_relative_sd(relative_sd: f64) -> Result<usize> {
+        let p = (2.0 * (1.106 / relative_sd).ln() / 2.0f64.ln()).ceil();
+        if !(4.0..=18.0).contains(&p) {
+            return df_execution_err!(
+                "HLL++ requires precision between 4 and 18, got relativeSD={relative_sd}"
+            );
+        }
+        Ok(p as usize)
+    }
+
+    pub fn num_words_of_precision(p: usize) -> usize {
+        (1 << p) / REGISTERS_PER_WORD + 1
+    }
+
+    pub fn new(p: usize) -> Self {
+        Self {
+            p,
+            words: vec![0; Self::num_words_of_precision(p)].into(),
+        }
+    }
+
+    pub fn mem_size(&self) -> usize {
+        std::mem::size_of::<Self>() + self.words.len() * 8
+    }
+
+    /// updates with a value hashed by spark's xxhash64 with seed 42
+    pub fn update(&mut self, hash: u64) {
+        let idx = (hash >> (64 - self.p)) as usize;
+        let w_padding = 1u64 << (self.p - 1);
+        let pw = ((hash << self.p) | w_padding).leading_zeros() as u64 + 1;
+
+        let word_offset = idx / REGISTERS_PER_WORD;
+        let shift = REGISTER_SIZE * (idx - word_offset * REGISTERS_PER_WORD);
+        let mask = REGISTER_WORD_MASK << shift;
+        let word = self.words[word_offset];
+        if pw > (word & mask) >> shift {
+            self.words[word_offset] = (word & !mask) | (pw << shift);
+        }
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        for (word, other_word) in self.words.iter_mut().zip(other.words.iter()) {
+            let mut merged = 0;
+            for i in 0..REGISTERS_PER_WORD {
+                let mask = REGISTER_WORD_MASK << (i * REGISTER_SIZE);
+                merged |= (*word & mask).max(*other_word & mask);
+            }
+            *word = merged;
+        }
+    }
+
+    /// estimates the cardinality, same as spark's
+    /// HyperLogLogPlusPlusHelper.query()
+    pub fn count(&self) -> i64 {
+        let m = 1usize << self.p;
+        let mut z_inverse = 0.0;
+        let mut num_zeros = 0.0;
+        for idx in 0..m {
+            let word = self.words[idx / REGISTERS_PER_WORD];
+            let shift = REGISTER_SIZE * (idx % REGISTERS_PER_WORD);
+            let register = (word >> shift) & REGISTER_WORD_MASK;
+            z_inverse += 1.0 / (1u64 << register) as f64;
+            if register == 0 {
+                num_zeros += 1.0;
+            }
+        }
+
+        let m = m as f64;
+        let alpha_m2 = match self.p {
+            4 => 0.673 * m * m,
+            5 => 0.697 * m * m,
+            6 => 0.709 * m * m,
+            _ => (0.7213 / (1.0 + 1.079 / m)) * m * m,
+        };
+        let e = alpha_m2 / z_inverse;
+        let e_biased = if e < m * 5.0 {
+            e - self.estimate_bias(e)
+        } else {
+            e
+        };
+
+        // use linear counting for small cardinality estimates
+        let estimate = if num_zeros > 0.0 {
+            let h = m * (m / num_zeros).ln();
+            if h <= THRESHOLDS[self.p - 4] {
+                h
+            } else {
+                e_biased
+            }
+        } else {
+            e_biased
+        };
+        estimate.round() as i64
+    }
+
+    fn estimate_bias(&self, e: f64) -> f64 {
+        let estimates = bias_data::RAW_ESTIMATE_DATA[self.p - 4];
+        let biases = bias_data::BIAS_DATA[self.p - 4];
+        let num_estimates = estimates.len();
+        let nearest_idx = estimates.partition_point(|&estimate| estimate < e);
+        let distance = |i: usize| (e - estimates[i]) * (e - estimates[i]);
+
+        // keep moving bounds as long as the (exclusive) high bound is closer to
+        // the estimate than the lower (inclusive) bound
+        let mut low = (nearest_idx + 1).saturating_sub(K);
+        let mut high = (low + K).min(num_estimates);
+        while high < num_estimates && distance(high) < distance(low) {
+            low += 1;
+            high += 1;
+        }
+        biases[low..high].iter().sum::<f64>() / (high - low) as f64
+    }
+
+    pub fn read_from(mut r: impl Read, p: usize) -> Result<Self> {
+        let mut hll = Self::new(p);
+        for word in hll.words.iter_mut() {
+            *word = r.read_u64::<LE>()?;
+        }
+        Ok(hll)
+    }
+
+    /// writes words as little-endian longs, the same as spark's UnsafeRow
+    pub fn write_to(&self, mut w: impl Write) -> Result<()> {
+        for &word in self.words.iter() {
+            w.write_u64::<LE>(word)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::array::{Array, ArrayRef, Int64Array};
+
+    use crate::{spark_hash::create_xxhash64_hashes, spark_hyperloglog::SparkHyperLogLog};
+
+    fn hll_of_range(p: usize, range: std::ops::Range<i64>) -> SparkHyperLogLog {
+        let array: ArrayRef = Arc::new(Int64Array::from_iter_values(range));
+        let mut hll = SparkHyperLogLog::new(p);
+        for hash in create_xxhash64_hashes(array.len(), &[array], 42) {
+            hll.update(hash as u64);
+        }
+        hll
+    }
+
+    #[test]
+    fn test_precision() {
+        assert_eq!(SparkHyperLogLog::precision_of_relative_sd(0.05).unwrap(), 9);
+        assert_eq!(
+            SparkHyperLogLog::precision_of_relative_sd(0.01).unwrap(),
+            14
+        );
+        assert_eq!(SparkHyperLogLog::num_words_of_precision(9), 52);
+        assert!(SparkHyperLogLog::precision_of_relative_sd(0.5).is_err());
+    }
+
+    #[test]
+    fn test_count() {
+        let p = SparkHyperLogLog::precision_of_relative_sd(0.05).unwrap();
+        assert_eq!(SparkHyperLogLog::new(p).count(), 0);
+
+        for n in [10, 1000, 100000] {
+            let count = hll_of_range(p, 0..n).count();
+            let error = (count - n).abs() as f64 / n as f64;
+            assert!(error < 0.15, "n={n}, count={count}");
+        }
+    }
+
+    #[test]
+    fn test_merge_and_serde() {
+        let p = 9;
+        let mut hll1 = hll_of_range(p, 0..5000);
+        let hll2 = hll_of_range(p, 2500..10000);
+        hll1.merge(&hll2);
+        assert_eq!(hll1, hll_of_range(p, 0..10000));
+
+        let mut buf = vec![];
+        hll1.write_to(&mut buf).unwrap();
+        assert_eq!(buf.len(), SparkHyperLogLog::num_words_of_precision(p) * 8);
+        assert_eq!(SparkHyperLogLog::read_from(&buf[..], p).unwrap(), hll1);
+    }
+}
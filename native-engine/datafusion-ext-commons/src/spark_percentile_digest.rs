@@ -0,0 +1,354 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    fmt::{Debug, Formatter},
+    io::{Read, Write},
+};
+
+use byteorder::{ReadBytesExt, WriteBytesExt, BE};
+use datafusion::common::Result;
+
+use crate::df_execution_err;
+
+// same as spark's QuantileSummaries.defaultCompressThreshold
+pub const DEFAULT_COMPRESS_THRESHOLD: i32 = 10000;
+
+// same as spark's QuantileSummaries.defaultHeadSize
+const DEFAULT_HEAD_SIZE: usize = 50000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Stats {
+    value: f64,
+    g: i64,
+    delta: i64,
+}
+
+/// Greenwald-Khanna quantile digest compatible with spark's
+/// ApproximatePercentile.PercentileDigest.
+///
+/// Inserting, compressing, merging and querying follow spark's
+/// QuantileSummaries step by step, and the serialized form is the same as
+/// spark's PercentileDigestSerializer, so the results are identical to
+/// spark's percentile_approx.
+#[derive(Clone)]
+pub struct SparkPercentileDigest {
+    compress_threshold: i32,
+    relative_error: f64,
+    sampled: Vec<Stats>,
+    count: i64,
+    head_sampled: Vec<f64>,
+    compressed: bool,
+}
+
+impl Debug for SparkPercentileDigest {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SparkPercentileDigest")
+            .field("relative_error", &self.relative_error)
+            .field("count", &self.count)
+            .field("num_sampled", &self.sampled.len())
+            .field("num_head_sampled", &self.head_sampled.len())
+            .finish()
+    }
+}
+
+impl SparkPercentileDigest {
+    pub fn new(relative_error: f64) -> Self {
+        Self {
+            compress_threshold: DEFAULT_COMPRESS_THRESHOLD,
+            relative_error,
+            sampled: vec![],
+            count: 0,
+            head_sampled: vec![],
+            compressed: true,
+        }
+    }
+
+    pub fn count(&self) -> i64 {
+        self.count + self.head_sampled.len() as i64
+    }
+
+    pub fn mem_size(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.sampled.capacity() * std::mem::size_of::<Stats>()
+            + self.head_sampled.capacity() * std::mem::size_of::<f64>()
+    }
+
+    pub fn add(&mut self, value: f64) {
+        self.head_sampled.push(value);
+        self.compressed = false;
+        if self.head_sampled.len() >= DEFAULT_HEAD_SIZE {
+            self.insert_head_buffer();
+            if self.sampled.len() >= self.compress_threshold as usize {
+                self.compress();
+            }
+        }
+    }
+
+    pub fn merge(&mut self, other: &mut Self) {
+        self.compress_if_needed();
+        other.compress_if_needed();
+
+        if other.count == 0 {
+            return;
+        }
+        if self.count == 0 {
+            *self = std::mem::replace(other, Self::new(other.relative_error));
+            return;
+        }
+
+        // samples interleaving the other side suffer from the lack of precision of
+        // the other side, see spark's QuantileSummaries.merge() for details
+        let merged_relative_error = self.relative_error.max(other.relative_error);
+        let merged_count = self.count + other.count;
+        let additional_self_delta = (2.0 * other.relative_error * other.count as f64) as i64;
+        let additional_other_delta = (2.0 * self.relative_error * self.count as f64) as i64;
+
+        let mut merged = Vec::with_capacity(self.sampled.len() + other.sampled.len());
+        let mut self_idx = 0;
+        let mut other_idx = 0;
+        while self_idx < self.sampled.len() && other_idx < other.sampled.len() {
+            let self_sample = self.sampled[self_idx];
+            let other_sample = other.sampled[other_idx];
+            let (next_sample, additional_delta) = if self_sample.value < other_sample.value {
+                self_idx += 1;
+                let delta = if other_idx > 0 { additional_self_delta } else { 0 };
+                (self_sample, delta)
+            } else {
+                other_idx += 1;
+                let delta = if self_idx > 0 { additional_other_delta } else { 0 };
+                (other_sample, delta)
+            };
+            merged.push(Stats {
+                delta: next_sample.delta + additional_delta,
+                ..next_sample
+            });
+        }
+        merged.extend_from_slice(&self.sampled[self_idx..]);
+        merged.extend_from_slice(&other.sampled[other_idx..]);
+
+        let merge_threshold = 2.0 * merged_relative_error * merged_count as f64;
+        self.sampled = compress_immut(&merged, merge_threshold);
+        self.compress_threshold = other.compress_threshold;
+        self.relative_error = merged_relative_error;
+        self.count = merged_count;
+        self.compressed = true;
+    }
+
+    /// returns the approximate percentiles, or None if no values are added
+    pub fn percentiles(&mut self, percentages: &[f64]) -> Option<Vec<f64>> {
+        self.compress_if_needed();
+        if self.count == 0 || percentages.is_empty() {
+            return None;
+        }
+        Some(percentages.iter().map(|&p| self.query(p)).collect())
+    }
+
+    fn query(&self, quantile: f64) -> f64 {
+        if quantile <= self.relative_error {
+            return self.sampled[0].value;
+        }
+        if quantile >= 1.0 - self.relative_error {
+            return self.sampled[self.sampled.len() - 1].value;
+        }
+
+        let rank = (quantile * self.count as f64).ceil() as i64;
+        let target_error = self
+            .sampled
+            .iter()
+            .map(|stats| stats.delta + stats.g)
+            .max()
+            .unwrap_or(i64::MIN)
+            / 2;
+        let mut min_rank = 0;
+        for sample in &self.sampled[..self.sampled.len() - 1] {
+            min_rank += sample.g;
+            let max_rank = min_rank + sample.delta;
+            if max_rank - target_error <= rank && rank <= min_rank + target_error {
+                return sample.value;
+            }
+        }
+        self.sampled[self.sampled.len() - 1].value
+    }
+
+    fn compress_if_needed(&mut self) {
+        if !self.compressed {
+            self.compress();
+        }
+    }
+
+    fn compress(&mut self) {
+        self.insert_head_buffer();
+        let merge_threshold = 2.0 * self.relative_error * self.count as f64;
+        self.sampled = compress_immut(&self.sampled, merge_threshold);
+        self.compressed = true;
+    }
+
+    fn insert_head_buffer(&mut self) {
+        if self.head_sampled.is_empty() {
+            return;
+        }
+        let mut sorted = std::mem::take(&mut self.head_sampled);
+        sorted.sort_unstable_by(|a, b| a.total_cmp(b));
+
+        let mut new_samples = Vec::with_capacity(self.sampled.len() + sorted.len());
+        let mut sample_idx = 0;
+        for (ops_idx, &current_sample) in sorted.iter().enumerate() {
+            // add all the samples before the next observation
+            while sample_idx < self.sampled.len() && self.sampled[sample_idx].value <= current_sample
+            {
+                new_samples.push(self.sampled[sample_idx]);
+                sample_idx += 1;
+            }
+
+            // delta is zero for the first and the last one
+            self.count += 1;
+            let is_last = sample_idx == self.sampled.len() && ops_idx == sorted.len() - 1;
+            let delta = if new_samples.is_empty() || is_last {
+                0
+            } else {
+                (2.0 * self.relative_error * self.count as f64) as i64
+            };
+            new_samples.push(Stats {
+                value: current_sample,
+                g: 1,
+                delta,
+            });
+        }
+        new_samples.extend_from_slice(&self.sampled[sample_idx..]);
+        self.sampled = new_samples;
+    }
+
+    pub fn read_from(mut r: impl Read) -> Result<Self> {
+        let compress_threshold = r.read_i32::<BE>()?;
+        let relative_error = r.read_f64::<BE>()?;
+        let count = r.read_i64::<BE>()?;
+        let num_sampled = r.read_i32::<BE>()?;
+        if num_sampled < 0 {
+            return df_execution_err!("invalid number of percentile digest samples: {num_sampled}");
+        }
+        let mut sampled = Vec::with_capacity(num_sampled as usize);
+        for _ in 0..num_sampled {
+            sampled.push(Stats {
+                value: r.read_f64::<BE>()?,
+                g: r.read_i64::<BE>()?,
+                delta: r.read_i64::<BE>()?,
+            });
+        }
+        Ok(Self {
+            compress_threshold,
+            relative_error,
+            sampled,
+            count,
+            head_sampled: vec![],
+            compressed: true,
+        })
+    }
+
+    /// writes the compressed digest, the same as spark's
+    /// PercentileDigestSerializer.serialize()
+    pub fn write_to(&mut self, mut w: impl Write) -> Result<()> {
+        self.compress_if_needed();
+        w.write_i32::<BE>(self.compress_threshold)?;
+        w.write_f64::<BE>(self.relative_error)?;
+        w.write_i64::<BE>(self.count)?;
+        w.write_i32::<BE>(self.sampled.len() as i32)?;
+        for stats in &self.sampled {
+            w.write_f64::<BE>(stats.value)?;
+            w.write_i64::<BE>(stats.g)?;
+            w.write_i64::<BE>(stats.delta)?;
+        }
+        Ok(())
+    }
+}
+
+fn compress_immut(samples: &[Stats], merge_threshold: f64) -> Vec<Stats> {
+    if samples.is_empty() {
+        return vec![];
+    }
+
+    // start from the last element, which is always kept. the head may be merged
+    // with the current element
+    let mut res = vec![];
+    let mut head = samples[samples.len() - 1];
+    for sample in samples[1..samples.len().max(2) - 1].iter().rev() {
+        if ((sample.g + head.g + head.delta) as f64) < merge_threshold {
+            head.g += sample.g;
+        } else {
+            res.push(head);
+            head = *sample;
+        }
+    }
+    res.push(head);
+
+    // add the minimum element if necessary
+    let first = samples[0];
+    if first.value <= head.value && samples.len() > 1 {
+        res.push(first);
+    }
+    res.reverse();
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use crate::spark_percentile_digest::SparkPercentileDigest;
+
+    #[test]
+    fn test_percentiles() {
+        let mut digest = SparkPercentileDigest::new(1.0 / 10000.0);
+        assert_eq!(digest.percentiles(&[0.5]), None);
+
+        for i in (1..=1000).rev() {
+            digest.add(i as f64);
+        }
+        assert_eq!(digest.count(), 1000);
+        assert_eq!(
+            digest.percentiles(&[0.0, 0.25, 0.5, 0.99, 1.0]),
+            Some(vec![1.0, 250.0, 500.0, 990.0, 1000.0]),
+        );
+    }
+
+    #[test]
+    fn test_merge_and_serde() {
+        let mut digest1 = SparkPercentileDigest::new(0.01);
+        let mut digest2 = SparkPercentileDigest::new(0.01);
+        for i in 0..100000 {
+            if i % 2 == 0 {
+                digest1.add(i as f64);
+            } else {
+                digest2.add(i as f64);
+            }
+        }
+        digest1.merge(&mut digest2);
+        assert_eq!(digest1.count(), 100000);
+
+        let percentiles = digest1.percentiles(&[0.1, 0.5, 0.9]).unwrap();
+        for (percentile, expected) in percentiles.into_iter().zip([10000.0, 50000.0, 90000.0]) {
+            assert!((percentile - expected).abs() <= 0.01 * 100000.0);
+        }
+
+        let mut buf = vec![];
+        digest1.write_to(&mut buf).unwrap();
+        assert_eq!(&buf[0..4], &10000i32.to_be_bytes());
+        assert_eq!(buf.len(), 4 + 8 + 8 + 4 + digest1.sampled.len() * 24);
+
+        let mut digest3 = SparkPercentileDigest::read_from(&buf[..]).unwrap();
+        assert_eq!(digest3.sampled, digest1.sampled);
+        assert_eq!(
+            digest3.percentiles(&[0.1, 0.5, 0.9]),
+            digest1.percentiles(&[0.1, 0.5, 0.9]),
+        );
+    }
+}
@@ -0,0 +1,344 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    fmt::{Display, Formatter},
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+use arrow::{
+    array::*,
+    compute::kernels::numeric::{add_wrapping, div, mul_wrapping, rem, sub_wrapping},
+    datatypes::*,
+    downcast_primitive_array,
+    error::ArrowError,
+    record_batch::RecordBatch,
+};
+use datafusion::{
+    common::{cast::as_primitive_array, Result},
+    logical_expr::{ColumnarValue, Operator},
+    physical_expr::{expressions::BinaryExpr, PhysicalExpr},
+};
+use datafusion_ext_commons::{
+    df_unimplemented_err,
+    spark_eval_mode::{arithmetic_overflow_err, divide_by_zero_err, EvalMode},
+};
+
+use crate::down_cast_any_ref;
+
+/// arithmetic expression (+, -, *, /, %) compatible with spark's eval modes.
+/// in ANSI mode, integral overflows and division by zero raise errors, while
+/// in TRY mode they return null.
+#[derive(Debug, Hash)]
+pub struct BinaryArithmeticExpr {
+    l: Arc<dyn PhysicalExpr>,
+    op: Operator,
+    r: Arc<dyn PhysicalExpr>,
+    eval_mode: EvalMode,
+}
+
+impl BinaryArithmeticExpr {
+    pub fn try_new(
+        l: Arc<dyn PhysicalExpr>,
+        op: Operator,
+        r: Arc<dyn PhysicalExpr>,
+        eval_mode: EvalMode,
+    ) -> Result<Self> {
+        match op {
+            Operator::Plus
+            | Operator::Minus
+            | Operator::Multiply
+            | Operator::Divide
+            | Operator::Modulo => Ok(Self {
+                l,
+                op,
+                r,
+                eval_mode,
+            }),
+            other => df_unimplemented_err!("unsupported arithmetic operator: {other}"),
+        }
+    }
+}
+
+impl PartialEq<dyn Any> for BinaryArithmeticExpr {
+    fn eq(&self, other: &dyn Any) -> bool {
+        down_cast_any_ref(other)
+            .downcast_ref::<Self>()
+            .map(|x| {
+                self.l.eq(&x.l)
+                    && self.op == x.op
+                    && self.r.eq(&x.r)
+                    && self.eval_mode == x.eval_mode
+            })
+            .unwrap_or(false)
+    }
+}
+
+impl Display for BinaryArithmeticExpr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} ({:?})",
+            self.l, self.op, self.r, self.eval_mode
+        )
+    }
+}
+
+impl PhysicalExpr for BinaryArithmeticExpr {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn data_type(&self, input_schema: &Schema) -> Result<DataType> {
+        BinaryExpr::new(self.l.clone(), self.op, self.r.clone()).data_type(input_schema)
+    }
+
+    fn nullable(&self, _input_schema: &Schema) -> Result<bool> {
+        Ok(true)
+    }
+
+    fn evaluate(&self, batch: &RecordBatch) -> Result<ColumnarValue> {
+        let num_rows = batch.num_rows();
+        let l = self.l.evaluate(batch)?.into_array(num_rows)?;
+        let r = self.r.evaluate(batch)?.into_array(num_rows)?;
+
+        let result = match self.op {
+            Operator::Divide | Operator::Modulo => {
+                let r = nullify_zero_divisors(&l, &r, self.eval_mode)?;
+                match (self.op, l.data_type()) {
+                    (Operator::Divide, _) => div(&l, &r)?,
+                    (_, DataType::Int8) => wrapping_rem::<Int8Type>(&l, &r)?,
+                    (_, DataType::Int16) => wrapping_rem::<Int16Type>(&l, &r)?,
+                    (_, DataType::Int32) => wrapping_rem::<Int32Type>(&l, &r)?,
+                    (_, DataType::Int64) => wrapping_rem::<Int64Type>(&l, &r)?,
+                    _ => rem(&l, &r)?,
+                }
+            }
+            _ => match l.data_type() {
+                DataType::Int8 => checked_arithmetic::<Int8Type>(&l, self.op, &r, self.eval_mode)?,
+                DataType::Int16 => {
+                    checked_arithmetic::<Int16Type>(&l, self.op, &r, self.eval_mode)?
+                }
+                DataType::Int32 => {
+                    checked_arithmetic::<Int32Type>(&l, self.op, &r, self.eval_mode)?
+                }
+                DataType::Int64 => {
+                    checked_arithmetic::<Int64Type>(&l, self.op, &r, self.eval_mode)?
+                }
+                _ => match self.op {
+                    Operator::Plus => add_wrapping(&l, &r)?,
+                    Operator::Minus => sub_wrapping(&l, &r)?,
+                    _ => mul_wrapping(&l, &r)?,
+                },
+            },
+        };
+        Ok(ColumnarValue::Array(result))
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn PhysicalExpr>> {
+        vec![&self.l, &self.r]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn PhysicalExpr>>,
+    ) -> Result<Arc<dyn PhysicalExpr>> {
+        Ok(Arc::new(Self::try_new(
+            children[0].clone(),
+            self.op,
+            children[1].clone(),
+            self.eval_mode,
+        )?))
+    }
+
+    fn dyn_hash(&self, state: &mut dyn Hasher) {
+        let mut s = state;
+        self.hash(&mut s);
+    }
+}
+
+fn checked_arithmetic<T: ArrowPrimitiveType>(
+    l: &ArrayRef,
+    op: Operator,
+    r: &ArrayRef,
+    eval_mode: EvalMode,
+) -> Result<ArrayRef> {
+    type CheckedOp<N> = fn(N, N) -> Result<N, ArrowError>;
+    type WrappingOp<N> = fn(N, N) -> N;
+    let (checked_op, wrapping_op, try_function): (CheckedOp<T::Native>, WrappingOp<T::Native>, _) =
+        match op {
+            Operator::Plus => (|l, r| l.add_checked(r), |l, r| l.add_wrapping(r), "try_add"),
+            Operator::Minus => (
+                |l, r| l.sub_checked(r),
+                |l, r| l.sub_wrapping(r),
+                "try_subtract",
+            ),
+            _ => (
+                |l, r| l.mul_checked(r),
+                |l, r| l.mul_wrapping(r),
+                "try_multiply",
+            ),
+        };
+    let l = as_primitive_array::<T>(l)?;
+    let r = as_primitive_array::<T>(r)?;
+    let result = l
+        .iter()
+        .zip(r.iter())
+        .map(|(l, r)| match (l, r) {
+            (Some(l), Some(r)) => match (checked_op(l, r), eval_mode) {
+                (Ok(v), _) => Ok(Some(v)),
+                (Err(_), EvalMode::Legacy) => Ok(Some(wrapping_op(l, r))),
+                (Err(_), EvalMode::Ansi) => {
+                    arithmetic_overflow_err(&T::DATA_TYPE, Some(try_function))
+                }
+                (Err(_), EvalMode::Try) => Ok(None),
+            },
+            _ => Ok(None),
+        })
+        .collect::<Result<PrimitiveArray<T>>>()?;
+    Ok(Arc::new(result))
+}
+
+// spark returns 0 for MIN % -1, which overflows in arrow's rem kernel. zero
+// divisors are nullified in advance so they are never evaluated
+fn wrapping_rem<T: ArrowPrimitiveType>(l: &ArrayRef, r: &ArrayRef) -> Result<ArrayRef> {
+    let result: PrimitiveArray<T> = arrow::compute::try_binary(
+        as_primitive_array::<T>(l)?,
+        as_primitive_array::<T>(r)?,
+        |l, r| Ok::<_, ArrowError>(l.mod_wrapping(r)),
+    )?;
+    Ok(Arc::new(result))
+}
+
+// nullifies zero divisors, or raises DIVIDE_BY_ZERO in ANSI mode
+fn nullify_zero_divisors(l: &ArrayRef, r: &ArrayRef, eval_mode: EvalMode) -> Result<ArrayRef> {
+    let is_zero = downcast_primitive_array!(
+        r => BooleanArray::from_unary(r, |v| v.is_zero()),
+        other => return df_unimplemented_err!("unsupported divisor type: {other}"),
+    );
+    if eval_mode == EvalMode::Ansi {
+        let divide_by_zero = arrow::compute::and(&is_zero, &arrow::compute::is_not_null(l)?)?;
+        if divide_by_zero.true_count() > 0 {
+            return divide_by_zero_err();
+        }
+    }
+    Ok(arrow::compute::nullif(r, &is_zero)?)
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{ArrayRef, Float64Array, Int32Array},
+        datatypes::{Field, Schema},
+        record_batch::RecordBatch,
+    };
+    use datafusion::{
+        common::Result,
+        logical_expr::Operator,
+        physical_expr::{expressions::Column, PhysicalExpr},
+    };
+    use datafusion_ext_commons::spark_eval_mode::EvalMode;
+
+    use crate::binary_arithmetic::BinaryArithmeticExpr;
+
+    fn evaluate(l: ArrayRef, op: Operator, r: ArrayRef, eval_mode: EvalMode) -> Result<ArrayRef> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("l", l.data_type().clone(), true),
+            Field::new("r", r.data_type().clone(), true),
+        ]));
+        let batch = RecordBatch::try_new(schema, vec![l, r])?;
+        let expr = BinaryArithmeticExpr::try_new(
+            Arc::new(Column::new("l", 0)),
+            op,
+            Arc::new(Column::new("r", 1)),
+            eval_mode,
+        )?;
+        expr.evaluate(&batch)?.into_array(batch.num_rows())
+    }
+
+    #[test]
+    fn test_integral_overflow() -> Result<()> {
+        let l: ArrayRef = Arc::new(Int32Array::from(vec![Some(1), Some(i32::MAX), None]));
+        let r: ArrayRef = Arc::new(Int32Array::from(vec![Some(2), Some(1), Some(1)]));
+
+        let output = evaluate(l.clone(), Operator::Plus, r.clone(), EvalMode::Legacy)?;
+        let expected: ArrayRef = Arc::new(Int32Array::from(vec![Some(3), Some(i32::MIN), None]));
+        assert_eq!(&output, &expected);
+
+        let output = evaluate(l.clone(), Operator::Plus, r.clone(), EvalMode::Try)?;
+        let expected: ArrayRef = Arc::new(Int32Array::from(vec![Some(3), None, None]));
+        assert_eq!(&output, &expected);
+
+        let err = evaluate(l.clone(), Operator::Plus, r.clone(), EvalMode::Ansi).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("[ARITHMETIC_OVERFLOW] integer overflow. Use 'try_add'"));
+
+        let output = evaluate(l.clone(), Operator::Minus, r.clone(), EvalMode::Ansi)?;
+        let expected: ArrayRef =
+            Arc::new(Int32Array::from(vec![Some(-1), Some(i32::MAX - 1), None]));
+        assert_eq!(&output, &expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_divide_by_zero() -> Result<()> {
+        let l: ArrayRef = Arc::new(Float64Array::from(vec![Some(1.0), None, Some(3.0)]));
+        let r: ArrayRef = Arc::new(Float64Array::from(vec![Some(2.0), Some(0.0), Some(-0.0)]));
+
+        let output = evaluate(l.clone(), Operator::Divide, r.clone(), EvalMode::Legacy)?;
+        let expected: ArrayRef = Arc::new(Float64Array::from(vec![Some(0.5), None, None]));
+        assert_eq!(&output, &expected);
+
+        let err = evaluate(l.clone(), Operator::Modulo, r.clone(), EvalMode::Ansi).unwrap_err();
+        assert!(err.to_string().contains("[DIVIDE_BY_ZERO]"));
+
+        // zero divisors with null dividends are fine in ANSI mode
+        let output = evaluate(
+            l.slice(0, 2),
+            Operator::Divide,
+            r.slice(0, 2),
+            EvalMode::Ansi,
+        )?;
+        let expected: ArrayRef = Arc::new(Float64Array::from(vec![Some(0.5), None]));
+        assert_eq!(&output, &expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_integral_remainder() -> Result<()> {
+        let l: ArrayRef = Arc::new(Int32Array::from(vec![Some(i32::MIN), Some(7), Some(-7)]));
+        let r: ArrayRef = Arc::new(Int32Array::from(vec![Some(-1), Some(-3), Some(0)]));
+
+        // MIN % -1 does not overflow
+        for eval_mode in [EvalMode::Legacy, EvalMode::Try] {
+            let output = evaluate(l.clone(), Operator::Modulo, r.clone(), eval_mode)?;
+            let expected: ArrayRef = Arc::new(Int32Array::from(vec![Some(0), Some(1), None]));
+            assert_eq!(&output, &expected);
+        }
+        let output = evaluate(
+            l.slice(0, 2),
+            Operator::Modulo,
+            r.slice(0, 2),
+            EvalMode::Ansi,
+        )?;
+        let expected: ArrayRef = Arc::new(Int32Array::from(vec![Some(0), Some(1)]));
+        assert_eq!(&output, &expected);
+        Ok(())
+    }
+}
@@ -23,6 +23,7 @@ use arrow::{datatypes::*, record_batch::RecordBatch};
 use datafusion::{
     common::Result, logical_expr::ColumnarValue, physical_expr::PhysicalExpr, scalar::ScalarValue,
 };
+use datafusion_ext_commons::{cast::cast_with_eval_mode, spark_eval_mode::EvalMode};
 
 use crate::down_cast_any_ref;
 
@@ -31,26 +32,46 @@ use crate::down_cast_any_ref;
 pub struct TryCastExpr {
     pub expr: Arc<dyn PhysicalExpr>,
     pub cast_type: DataType,
+    pub eval_mode: EvalMode,
 }
 
 impl PartialEq<dyn Any> for TryCastExpr {
     fn eq(&self, other: &dyn Any) -> bool {
         down_cast_any_ref(other)
             .downcast_ref::<Self>()
-            .map(|x| self.expr.eq(&x.expr) && self.cast_type == x.cast_type)
+            .map(|x| {
+                self.expr.eq(&x.expr)
+                    && self.cast_type == x.cast_type
+                    && self.eval_mode == x.eval_mode
+            })
             .unwrap_or(false)
     }
 }
 
 impl TryCastExpr {
     pub fn new(expr: Arc<dyn PhysicalExpr>, cast_type: DataType) -> Self {
-        Self { expr, cast_type }
+        Self::new_with_eval_mode(expr, cast_type, EvalMode::Legacy)
+    }
+
+    pub fn new_with_eval_mode(
+        expr: Arc<dyn PhysicalExpr>,
+        cast_type: DataType,
+        eval_mode: EvalMode,
+    ) -> Self {
+        Self {
+            expr,
+            cast_type,
+            eval_mode,
+        }
     }
 }
 
 impl Display for TryCastExpr {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "cast({} AS {:?})", self.expr, self.cast_type)
+        match self.eval_mode {
+            EvalMode::Ansi => write!(f, "ansi_cast({} AS {:?})", self.expr, self.cast_type),
+            _ => write!(f, "cast({} AS {:?})", self.expr, self.cast_type),
+        }
     }
 }
 
@@ -69,13 +90,15 @@ impl PhysicalExpr for TryCastExpr {
 
     fn evaluate(&self, batch: &RecordBatch) -> Result<ColumnarValue> {
         Ok(match self.expr.evaluate(batch)? {
-            ColumnarValue::Array(array) => {
-                ColumnarValue::Array(datafusion_ext_commons::cast::cast(&array, &self.cast_type)?)
-            }
+            ColumnarValue::Array(array) => ColumnarValue::Array(cast_with_eval_mode(
+                &array,
+                &self.cast_type,
+                self.eval_mode,
+            )?),
             ColumnarValue::Scalar(scalar) => {
                 let array = scalar.to_array()?;
                 ColumnarValue::Scalar(ScalarValue::try_from_array(
-                    &datafusion_ext_commons::cast::cast(&array, &self.cast_type)?,
+                    &cast_with_eval_mode(&array, &self.cast_type, self.eval_mode)?,
                     0,
                 )?)
             }
@@ -90,9 +113,10 @@ impl PhysicalExpr for TryCastExpr {
         self: Arc<Self>,
         children: Vec<Arc<dyn PhysicalExpr>>,
     ) -> Result<Arc<dyn PhysicalExpr>> {
-        Ok(Arc::new(Self::new(
+        Ok(Arc::new(Self::new_with_eval_mode(
             children[0].clone(),
             self.cast_type.clone(),
+            self.eval_mode,
         )))
     }
 
@@ -111,6 +135,7 @@ mod test {
         record_batch::RecordBatch,
     };
     use datafusion::physical_expr::{expressions as phys_expr, PhysicalExpr};
+    use datafusion_ext_commons::spark_eval_mode::EvalMode;
 
     use crate::cast::TryCastExpr;
 
@@ -236,4 +261,36 @@ mod test {
         ]));
         assert_eq!(&ret, &expected);
     }
+
+    #[test]
+    fn test_ansi() {
+        // input: Array
+        // cast Utf8 into Int32 in ANSI mode
+        let string_arr: ArrayRef =
+            Arc::new(StringArray::from(vec![Some("123"), Some("sda"), None]));
+
+        let schema = Arc::new(Schema::new(vec![Field::new("col", DataType::Utf8, true)]));
+
+        let batch =
+            RecordBatch::try_new(schema, vec![string_arr]).expect("Error creating RecordBatch");
+
+        let expr = Arc::new(TryCastExpr::new_with_eval_mode(
+            phys_expr::col("col", &batch.schema()).unwrap(),
+            DataType::Int32,
+            EvalMode::Ansi,
+        ));
+
+        let err = expr.evaluate(&batch).expect_err("Expected casting error");
+        assert!(err.to_string().contains(
+            "[CAST_INVALID_INPUT] The value 'sda' of the type \"STRING\" cannot be cast to \"INT\""
+        ));
+
+        let ret = expr
+            .evaluate(&batch.slice(0, 1))
+            .expect("Error evaluating expr")
+            .into_array(1)
+            .unwrap();
+        let expected: ArrayRef = Arc::new(Int32Array::from(vec![Some(123)]));
+        assert_eq!(&ret, &expected);
+    }
 }
@@ -0,0 +1,825 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    fmt::{Debug, Formatter},
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+use arrow::{
+    array::{
+        new_null_array, Array, ArrayRef, BooleanArray, Int32Array, ListArray, MapArray,
+        RecordBatch, RecordBatchOptions, UInt32Array,
+    },
+    buffer::{NullBuffer, OffsetBuffer, ScalarBuffer},
+    compute::{cast, interleave, is_null, nullif, take},
+    datatypes::{DataType, Field, FieldRef, Schema},
+};
+use datafusion::{
+    common::{
+        cast::{as_boolean_array, as_list_array, as_map_array, as_struct_array},
+        Result,
+    },
+    logical_expr::ColumnarValue,
+    physical_expr::{expressions::Column, physical_exprs_bag_equal, PhysicalExpr},
+};
+use datafusion_ext_commons::df_execution_err;
+
+use crate::down_cast_any_ref;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HigherOrderFunction {
+    /// transform(array, (x[, i]) -> ...)
+    Transform,
+    /// filter(array, (x[, i]) -> ...)
+    Filter,
+    /// exists(array, x -> ...)
+    Exists,
+    /// forall(array, x -> ...)
+    Forall,
+    /// aggregate(array, zero, (acc, x) -> ..., acc -> ...)
+    Aggregate,
+    /// zip_with(left, right, (x, y) -> ...)
+    ZipWith,
+    /// map_filter(map, (k, v) -> ...)
+    MapFilter,
+}
+
+/// lambda function with its variables and body. the body is bound to the input
+/// schema of the enclosing expression followed by the lambda variables, and is
+/// evaluated on a temporary batch of flattened elements.
+#[derive(Debug, Hash)]
+pub struct LambdaFunction {
+    args: Vec<FieldRef>,
+    body: Arc<dyn PhysicalExpr>,
+    captured_columns: Vec<usize>,
+}
+
+impl LambdaFunction {
+    pub fn new(args: Vec<FieldRef>, body: Arc<dyn PhysicalExpr>) -> Self {
+        let mut captured_columns = vec![];
+        collect_column_indices(&body, &mut captured_columns);
+        captured_columns.sort_unstable();
+        captured_columns.dedup();
+        Self {
+            args,
+            body,
+            captured_columns,
+        }
+    }
+
+    /// evaluates the body on rows of the input batch specified by `rows`, with
+    /// `args` as values of the lambda variables.
+    fn evaluate(
+        &self,
+        batch: &RecordBatch,
+        rows: &UInt32Array,
+        args: Vec<ArrayRef>,
+    ) -> Result<ArrayRef> {
+        if args.len() != self.args.len() {
+            return df_execution_err!(
+                "lambda function expects {} arguments, got {}",
+                self.args.len(),
+                args.len()
+            );
+        }
+        let num_rows = rows.len();
+
+        // only columns captured by the body are taken, others are filled with
+        // nulls, so all fields are made nullable in the temporary schema
+        let mut columns = Vec::with_capacity(batch.num_columns() + args.len());
+        for (i, column) in batch.columns().iter().enumerate() {
+            if self.captured_columns.binary_search(&i).is_ok() {
+                columns.push(take(column, rows, None)?);
+            } else {
+                columns.push(new_null_array(column.data_type(), num_rows));
+            }
+        }
+        columns.extend(args);
+        let schema = Schema::new(
+            batch
+                .schema()
+                .fields()
+                .iter()
+                .chain(&self.args)
+                .zip(&columns)
+                .map(|(field, column)| Field::new(field.name(), column.data_type().clone(), true))
+                .collect::<Vec<_>>(),
+        );
+        let lambda_batch = RecordBatch::try_new_with_options(
+            Arc::new(schema),
+            columns,
+            &RecordBatchOptions::new().with_row_count(Some(num_rows)),
+        )?;
+        self.body.evaluate(&lambda_batch)?.into_array(num_rows)
+    }
+}
+
+impl PartialEq for LambdaFunction {
+    fn eq(&self, other: &Self) -> bool {
+        self.args == other.args && self.body.eq(&other.body)
+    }
+}
+
+/// expression of spark's higher-order functions taking lambda functions.
+///
+/// NOTE: lambda bodies are not exposed as children, since they are bound to a
+/// different schema from the other arguments.
+#[derive(Debug, Hash)]
+pub struct HigherOrderFunctionExpr {
+    func: HigherOrderFunction,
+    args: Vec<Arc<dyn PhysicalExpr>>,
+    lambdas: Vec<LambdaFunction>,
+    return_type: DataType,
+}
+
+impl HigherOrderFunctionExpr {
+    pub fn try_new(
+        func: HigherOrderFunction,
+        args: Vec<Arc<dyn PhysicalExpr>>,
+        lambdas: Vec<LambdaFunction>,
+        return_type: DataType,
+    ) -> Result<Self> {
+        let (num_args, num_lambdas) = match func {
+            HigherOrderFunction::Aggregate => (2, 2),
+            HigherOrderFunction::ZipWith => (2, 1),
+            _ => (1, 1),
+        };
+        if args.len() != num_args || lambdas.len() != num_lambdas {
+            return df_execution_err!(
+                "{func:?} expects {num_args} arguments and {num_lambdas} lambda functions, got \
+                 {} and {}",
+                args.len(),
+                lambdas.len()
+            );
+        }
+        Ok(Self {
+            func,
+            args,
+            lambdas,
+            return_type,
+        })
+    }
+
+    fn transform(&self, batch: &RecordBatch, list: &ListArray) -> Result<ArrayRef> {
+        let flattened = Flattened::new(list.value_offsets(), list.nulls());
+        let mut lambda_args = vec![flattened.take_elements(list.values())?];
+        if self.lambdas[0].args.len() == 2 {
+            lambda_args.push(Arc::new(flattened.positions.clone()));
+        }
+        let values = self.lambdas[0].evaluate(batch, &flattened.parent_rows, lambda_args)?;
+        let field = match &self.return_type {
+            DataType::List(field) if field.data_type() == values.data_type() => field.clone(),
+            _ => Arc::new(Field::new_list_field(values.data_type().clone(), true)),
+        };
+        Ok(Arc::new(ListArray::try_new(
+            field,
+            flattened.offsets,
+            values,
+            list.nulls().cloned(),
+        )?))
+    }
+
+    fn filter(&self, batch: &RecordBatch, list: &ListArray) -> Result<ArrayRef> {
+        let flattened = Flattened::new(list.value_offsets(), list.nulls());
+        let mut lambda_args = vec![flattened.take_elements(list.values())?];
+        if self.lambdas[0].args.len() == 2 {
+            lambda_args.push(Arc::new(flattened.positions.clone()));
+        }
+        let predicates = self.lambdas[0].evaluate(batch, &flattened.parent_rows, lambda_args)?;
+        let (offsets, indices) = flattened.filter(as_boolean_array(&predicates)?);
+        let field = match list.data_type() {
+            DataType::List(field) => field.clone(),
+            other => return df_execution_err!("filter expects list type, got {other}"),
+        };
+        Ok(Arc::new(ListArray::try_new(
+            field,
+            offsets,
+            take(list.values(), &indices, None)?,
+            list.nulls().cloned(),
+        )?))
+    }
+
+    /// exists returns true if any predicate is true, otherwise null if any
+    /// predicate is null. forall returns false if any predicate is false,
+    /// otherwise null if any predicate is null.
+    fn exists_or_forall(
+        &self,
+        batch: &RecordBatch,
+        list: &ListArray,
+        exists: bool,
+    ) -> Result<ArrayRef> {
+        let flattened = Flattened::new(list.value_offsets(), list.nulls());
+        let elements = flattened.take_elements(list.values())?;
+        let predicates = self.lambdas[0].evaluate(batch, &flattened.parent_rows, vec![elements])?;
+        let predicates = as_boolean_array(&predicates)?;
+        let offsets = &flattened.offsets;
+
+        let result: BooleanArray = (0..list.len())
+            .map(|row| {
+                if list.is_null(row) {
+                    return None;
+                }
+                let mut has_null = false;
+                for i in offsets[row] as usize..offsets[row + 1] as usize {
+                    if predicates.is_null(i) {
+                        has_null = true;
+                    } else if predicates.value(i) == exists {
+                        return Some(exists);
+                    }
+                }
+                (!has_null).then_some(!exists)
+            })
+            .collect();
+        Ok(Arc::new(result))
+    }
+
+    /// folds elements of all rows position by position, so that the merge
+    /// function is evaluated on at most one element of each row at a time.
+    /// each pass only evaluates rows that still have elements, so the total
+    /// cost is linear in the number of elements.
+    fn aggregate(&self, batch: &RecordBatch, list: &ListArray, zero: ArrayRef) -> Result<ArrayRef> {
+        let offsets = list.value_offsets();
+        let lengths = (0..list.len())
+            .map(|row| match list.is_valid(row) {
+                true => (offsets[row + 1] - offsets[row]) as usize,
+                false => 0,
+            })
+            .collect::<Vec<_>>();
+
+        // accumulators of finished rows are collected from the output of the
+        // pass which consumed their last element, rows without elements keep
+        // the zero value
+        let mut finished_accs = vec![zero.clone()];
+        let mut finished_indices = (0..list.len()).map(|row| (0, row)).collect::<Vec<_>>();
+
+        let mut rows = (0..list.len() as u32)
+            .filter(|&row| lengths[row as usize] > 0)
+            .collect::<Vec<_>>();
+        let mut acc = take(&zero, &UInt32Array::from(rows.clone()), None)?;
+        let mut pos = 0;
+        while !rows.is_empty() {
+            let element_indices = rows
+                .iter()
+                .map(|&row| offsets[row as usize] as u32 + pos as u32)
+                .collect::<UInt32Array>();
+            let lambda_args = vec![acc, take(list.values(), &element_indices, None)?];
+            let merged =
+                self.lambdas[0].evaluate(batch, &UInt32Array::from(rows.clone()), lambda_args)?;
+            let merged = match merged.data_type() == zero.data_type() {
+                true => merged,
+                false => cast(&merged, zero.data_type())?,
+            };
+            pos += 1;
+
+            let mut remaining_rows = vec![];
+            let mut remaining_indices = vec![];
+            for (i, &row) in rows.iter().enumerate() {
+                if lengths[row as usize] > pos {
+                    remaining_rows.push(row);
+                    remaining_indices.push(i as u32);
+                } else {
+                    finished_indices[row as usize] = (finished_accs.len(), i);
+                }
+            }
+            acc = take(&merged, &UInt32Array::from(remaining_indices), None)?;
+            finished_accs.push(merged);
+            rows = remaining_rows;
+        }
+        let finished_accs = finished_accs
+            .iter()
+            .map(|array| array.as_ref())
+            .collect::<Vec<_>>();
+        let acc = interleave(&finished_accs, &finished_indices)?;
+
+        let all_rows = UInt32Array::from_iter_values(0..list.len() as u32);
+        let finished = self.lambdas[1].evaluate(batch, &all_rows, vec![acc])?;
+        Ok(nullif(&finished, &is_null(list)?)?)
+    }
+
+    /// zip_with pads the shorter array with nulls.
+    fn zip_with(
+        &self,
+        batch: &RecordBatch,
+        left: &ListArray,
+        right: &ListArray,
+    ) -> Result<ArrayRef> {
+        let (left_offsets, right_offsets) = (left.value_offsets(), right.value_offsets());
+        let mut offsets = Vec::with_capacity(left.len() + 1);
+        let mut parent_rows = vec![];
+        let mut left_indices = vec![];
+        let mut right_indices = vec![];
+        offsets.push(0);
+        for row in 0..left.len() {
+            if left.is_valid(row) && right.is_valid(row) {
+                let left_len = (left_offsets[row + 1] - left_offsets[row]) as usize;
+                let right_len = (right_offsets[row + 1] - right_offsets[row]) as usize;
+                for i in 0..left_len.max(right_len) {
+                    parent_rows.push(row as u32);
+                    left_indices
+                        .push((i < left_len).then(|| (left_offsets[row] as usize + i) as u32));
+                    right_indices
+                        .push((i < right_len).then(|| (right_offsets[row] as usize + i) as u32));
+                }
+            }
+            offsets.push(parent_rows.len() as i32);
+        }
+        let lambda_args = vec![
+            take(left.values(), &UInt32Array::from(left_indices), None)?,
+            take(right.values(), &UInt32Array::from(right_indices), None)?,
+        ];
+        let values =
+            self.lambdas[0].evaluate(batch, &UInt32Array::from(parent_rows), lambda_args)?;
+        let field = match &self.return_type {
+            DataType::List(field) if field.data_type() == values.data_type() => field.clone(),
+            _ => Arc::new(Field::new_list_field(values.data_type().clone(), true)),
+        };
+        let nulls = NullBuffer::union(left.nulls(), right.nulls());
+        Ok(Arc::new(ListArray::try_new(
+            field,
+            OffsetBuffer::new(ScalarBuffer::from(offsets)),
+            values,
+            nulls,
+        )?))
+    }
+
+    fn map_filter(&self, batch: &RecordBatch, map: &MapArray) -> Result<ArrayRef> {
+        let flattened = Flattened::new(map.value_offsets(), map.nulls());
+        let lambda_args = vec![
+            flattened.take_elements(map.keys())?,
+            flattened.take_elements(map.values())?,
+        ];
+        let predicates = self.lambdas[0].evaluate(batch, &flattened.parent_rows, lambda_args)?;
+        let (offsets, indices) = flattened.filter(as_boolean_array(&predicates)?);
+        let (field, sorted) = match map.data_type() {
+            DataType::Map(field, sorted) => (field.clone(), *sorted),
+            other => return df_execution_err!("map_filter expects map type, got {other}"),
+        };
+        let entries = take(map.entries(), &indices, None)?;
+        Ok(Arc::new(MapArray::try_new(
+            field,
+            offsets,
+            as_struct_array(&entries)?.clone(),
+            map.nulls().cloned(),
+            sorted,
+        )?))
+    }
+}
+
+impl std::fmt::Display for HigherOrderFunctionExpr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}({:?})", self.func, self.args)
+    }
+}
+
+impl PartialEq<dyn Any> for HigherOrderFunctionExpr {
+    fn eq(&self, other: &dyn Any) -> bool {
+        down_cast_any_ref(other)
+            .downcast_ref::<Self>()
+            .map(|x| {
+                self.func == x.func
+                    && physical_exprs_bag_equal(&self.args, &x.args)
+                    && self.lambdas == x.lambdas
+                    && self.return_type == x.return_type
+            })
+            .unwrap_or(false)
+    }
+}
+
+impl PhysicalExpr for HigherOrderFunctionExpr {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn data_type(&self, _input_schema: &Schema) -> Result<DataType> {
+        Ok(self.return_type.clone())
+    }
+
+    fn nullable(&self, _input_schema: &Schema) -> Result<bool> {
+        Ok(true)
+    }
+
+    fn evaluate(&self, batch: &RecordBatch) -> Result<ColumnarValue> {
+        let args = self
+            .args
+            .iter()
+            .map(|arg| arg.evaluate(batch)?.into_array(batch.num_rows()))
+            .collect::<Result<Vec<_>>>()?;
+
+        let result = match self.func {
+            HigherOrderFunction::Transform => self.transform(batch, as_list_array(&args[0])?)?,
+            HigherOrderFunction::Filter => self.filter(batch, as_list_array(&args[0])?)?,
+            HigherOrderFunction::Exists => {
+                self.exists_or_forall(batch, as_list_array(&args[0])?, true)?
+            }
+            HigherOrderFunction::Forall => {
+                self.exists_or_forall(batch, as_list_array(&args[0])?, false)?
+            }
+            HigherOrderFunction::Aggregate => {
+                self.aggregate(batch, as_list_array(&args[0])?, args[1].clone())?
+            }
+            HigherOrderFunction::ZipWith => {
+                self.zip_with(batch, as_list_array(&args[0])?, as_list_array(&args[1])?)?
+            }
+            HigherOrderFunction::MapFilter => self.map_filter(batch, as_map_array(&args[0])?)?,
+        };
+        Ok(ColumnarValue::Array(result))
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn PhysicalExpr>> {
+        self.args.iter().collect()
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn PhysicalExpr>>,
+    ) -> Result<Arc<dyn PhysicalExpr>> {
+        let lambdas = self
+            .lambdas
+            .iter()
+            .map(|lambda| LambdaFunction::new(lambda.args.clone(), lambda.body.clone()))
+            .collect();
+        Ok(Arc::new(Self::try_new(
+            self.func,
+            children,
+            lambdas,
+            self.return_type.clone(),
+        )?))
+    }
+
+    fn dyn_hash(&self, state: &mut dyn Hasher) {
+        let mut s = state;
+        self.hash(&mut s);
+    }
+}
+
+/// elements of non-null lists flattened into a single array, with the input
+/// row and the 0-based position in list of each element.
+struct Flattened {
+    element_indices: UInt32Array,
+    parent_rows: UInt32Array,
+    positions: Int32Array,
+    offsets: OffsetBuffer<i32>,
+}
+
+impl Flattened {
+    fn new(value_offsets: &[i32], nulls: Option<&NullBuffer>) -> Self {
+        let num_rows = value_offsets.len() - 1;
+        let mut element_indices = vec![];
+        let mut parent_rows = vec![];
+        let mut positions = vec![];
+        let mut offsets = Vec::with_capacity(num_rows + 1);
+        offsets.push(0);
+        for row in 0..num_rows {
+            if nulls.map(|nulls| nulls.is_valid(row)).unwrap_or(true) {
+                for (pos, i) in (value_offsets[row]..value_offsets[row + 1]).enumerate() {
+                    element_indices.push(i as u32);
+                    parent_rows.push(row as u32);
+                    positions.push(pos as i32);
+                }
+            }
+            offsets.push(element_indices.len() as i32);
+        }
+        Self {
+            element_indices: element_indices.into(),
+            parent_rows: parent_rows.into(),
+            positions: positions.into(),
+            offsets: OffsetBuffer::new(ScalarBuffer::from(offsets)),
+        }
+    }
+
+    fn take_elements(&self, values: &dyn Array) -> Result<ArrayRef> {
+        Ok(take(values, &self.element_indices, None)?)
+    }
+
+    /// returns offsets and element indices of flattened elements satisfying
+    /// the predicates, null predicates are considered false.
+    fn filter(&self, predicates: &BooleanArray) -> (OffsetBuffer<i32>, UInt32Array) {
+        let mut offsets = Vec::with_capacity(self.offsets.len());
+        let mut indices = vec![];
+        offsets.push(0);
+        for range in self.offsets.windows(2) {
+            for i in range[0] as usize..range[1] as usize {
+                if predicates.is_valid(i) && predicates.value(i) {
+                    indices.push(self.element_indices.value(i));
+                }
+            }
+            offsets.push(indices.len() as i32);
+        }
+        (
+            OffsetBuffer::new(ScalarBuffer::from(offsets)),
+            UInt32Array::from(indices),
+        )
+    }
+}
+
+fn collect_column_indices(expr: &Arc<dyn PhysicalExpr>, indices: &mut Vec<usize>) {
+    if let Some(column) = expr.as_any().downcast_ref::<Column>() {
+        indices.push(column.index());
+    }
+    if let Some(func) = expr.as_any().downcast_ref::<HigherOrderFunctionExpr>() {
+        // columns captured by nested lambda functions
+        for lambda in &func.lambdas {
+            indices.extend(lambda.captured_columns.iter().copied());
+        }
+    }
+    for child in expr.children() {
+        collect_column_indices(child, indices);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::*,
+        datatypes::{DataType, Field, FieldRef, Int32Type, Schema},
+        record_batch::RecordBatch,
+    };
+    use datafusion::{
+        assert_batches_eq,
+        common::Result,
+        logical_expr::Operator,
+        physical_expr::{
+            expressions::{binary, lit, Column},
+            PhysicalExpr,
+        },
+    };
+
+    use crate::higher_order_function::{
+        HigherOrderFunction, HigherOrderFunctionExpr, LambdaFunction,
+    };
+
+    fn input_batch() -> Result<RecordBatch> {
+        let list: ArrayRef = Arc::new(ListArray::from_iter_primitive::<Int32Type, _, _>(vec![
+            Some(vec![Some(1), Some(2), Some(3)]),
+            Some(vec![Some(4), None]),
+            Some(vec![]),
+            None,
+        ]));
+        let n: ArrayRef = Arc::new(Int32Array::from(vec![10, 20, 30, 40]));
+        Ok(RecordBatch::try_from_iter_with_nullable(vec![
+            ("list", list, true),
+            ("n", n, false),
+        ])?)
+    }
+
+    fn int_var(name: &str) -> FieldRef {
+        Arc::new(Field::new(name, DataType::Int32, true))
+    }
+
+    /// input schema followed by lambda variables
+    fn lambda_schema(batch: &RecordBatch, args: &[FieldRef]) -> Schema {
+        let schema = batch.schema();
+        let fields = schema.fields().iter().chain(args).cloned();
+        Schema::new(fields.collect::<Vec<_>>())
+    }
+
+    fn col(name: &str, schema: &Schema) -> Result<Arc<dyn PhysicalExpr>> {
+        Ok(Arc::new(Column::new_with_schema(name, schema)?))
+    }
+
+    #[test]
+    fn test_transform() -> Result<()> {
+        let batch = input_batch()?;
+
+        // transform(list, (x, i) -> x * n + i)
+        let args = vec![int_var("x"), int_var("i")];
+        let schema = lambda_schema(&batch, &args);
+        let body = binary(
+            binary(
+                col("x", &schema)?,
+                Operator::Multiply,
+                col("n", &schema)?,
+                &schema,
+            )?,
+            Operator::Plus,
+            col("i", &schema)?,
+            &schema,
+        )?;
+        let expr = HigherOrderFunctionExpr::try_new(
+            HigherOrderFunction::Transform,
+            vec![col("list", &schema)?],
+            vec![LambdaFunction::new(args, body)],
+            DataType::List(Arc::new(Field::new_list_field(DataType::Int32, true))),
+        )?;
+        let result = expr.evaluate(&batch)?.into_array(batch.num_rows())?;
+        let expected = vec![
+            "+--------------+",
+            "| result       |",
+            "+--------------+",
+            "| [10, 21, 32] |",
+            "| [80, ]       |",
+            "| []           |",
+            "|              |",
+            "+--------------+",
+        ];
+        assert_batches_eq!(
+            expected,
+            &[RecordBatch::try_from_iter(vec![("result", result)])?]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_filter_exists_forall() -> Result<()> {
+        let batch = input_batch()?;
+        let args = vec![int_var("x")];
+        let schema = lambda_schema(&batch, &args);
+        let lambda = || -> Result<LambdaFunction> {
+            let body = binary(col("x", &schema)?, Operator::Gt, lit(1), &schema)?;
+            Ok(LambdaFunction::new(args.clone(), body))
+        };
+
+        // filter(list, x -> x > 1)
+        let expr = HigherOrderFunctionExpr::try_new(
+            HigherOrderFunction::Filter,
+            vec![col("list", &schema)?],
+            vec![lambda()?],
+            batch.schema().field(0).data_type().clone(),
+        )?;
+        let result = expr.evaluate(&batch)?.into_array(batch.num_rows())?;
+        let expected = vec![
+            "+--------+",
+            "| result |",
+            "+--------+",
+            "| [2, 3] |",
+            "| [4]    |",
+            "| []     |",
+            "|        |",
+            "+--------+",
+        ];
+        assert_batches_eq!(
+            expected,
+            &[RecordBatch::try_from_iter(vec![("result", result)])?]
+        );
+
+        // exists(list, x -> x > 1)
+        let expr = HigherOrderFunctionExpr::try_new(
+            HigherOrderFunction::Exists,
+            vec![col("list", &schema)?],
+            vec![lambda()?],
+            DataType::Boolean,
+        )?;
+        let result = expr.evaluate(&batch)?.into_array(batch.num_rows())?;
+        let expected: ArrayRef = Arc::new(BooleanArray::from(vec![
+            Some(true),
+            Some(true),
+            Some(false),
+            None,
+        ]));
+        assert_eq!(&result, &expected);
+
+        // forall(list, x -> x > 1)
+        let expr = HigherOrderFunctionExpr::try_new(
+            HigherOrderFunction::Forall,
+            vec![col("list", &schema)?],
+            vec![lambda()?],
+            DataType::Boolean,
+        )?;
+        let result = expr.evaluate(&batch)?.into_array(batch.num_rows())?;
+        let expected: ArrayRef = Arc::new(BooleanArray::from(vec![
+            Some(false),
+            None,
+            Some(true),
+            None,
+        ]));
+        assert_eq!(&result, &expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_aggregate() -> Result<()> {
+        let batch = input_batch()?;
+
+        // aggregate(list, n, (acc, x) -> acc + x, acc -> acc * 2)
+        let merge_args = vec![int_var("acc"), int_var("x")];
+        let merge_schema = lambda_schema(&batch, &merge_args);
+        let merge = binary(
+            col("acc", &merge_schema)?,
+            Operator::Plus,
+            col("x", &merge_schema)?,
+            &merge_schema,
+        )?;
+        let finish_args = vec![int_var("acc")];
+        let finish_schema = lambda_schema(&batch, &finish_args);
+        let finish = binary(
+            col("acc", &finish_schema)?,
+            Operator::Multiply,
+            lit(2),
+            &finish_schema,
+        )?;
+        let expr = HigherOrderFunctionExpr::try_new(
+            HigherOrderFunction::Aggregate,
+            vec![col("list", &batch.schema())?, col("n", &batch.schema())?],
+            vec![
+                LambdaFunction::new(merge_args, merge),
+                LambdaFunction::new(finish_args, finish),
+            ],
+            DataType::Int32,
+        )?;
+        let result = expr.evaluate(&batch)?.into_array(batch.num_rows())?;
+        let expected: ArrayRef = Arc::new(Int32Array::from(vec![Some(32), None, Some(60), None]));
+        assert_eq!(&result, &expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_zip_with() -> Result<()> {
+        let right: ArrayRef = Arc::new(ListArray::from_iter_primitive::<Int32Type, _, _>(vec![
+            Some(vec![Some(1)]),
+            None,
+            Some(vec![Some(3)]),
+            Some(vec![]),
+        ]));
+        let batch = RecordBatch::try_from_iter(vec![
+            ("left", input_batch()?.column(0).clone()),
+            ("right", right),
+        ])?;
+
+        // zip_with(left, right, (x, y) -> x + y)
+        let args = vec![int_var("x"), int_var("y")];
+        let schema = lambda_schema(&batch, &args);
+        let body = binary(
+            col("x", &schema)?,
+            Operator::Plus,
+            col("y", &schema)?,
+            &schema,
+        )?;
+        let expr = HigherOrderFunctionExpr::try_new(
+            HigherOrderFunction::ZipWith,
+            vec![col("left", &schema)?, col("right", &schema)?],
+            vec![LambdaFunction::new(args, body)],
+            DataType::List(Arc::new(Field::new_list_field(DataType::Int32, true))),
+        )?;
+        let result = expr.evaluate(&batch)?.into_array(batch.num_rows())?;
+        let expected: ArrayRef = Arc::new(ListArray::from_iter_primitive::<Int32Type, _, _>(vec![
+            Some(vec![Some(2), None, None]),
+            None,
+            Some(vec![None]),
+            None,
+        ]));
+        assert_eq!(&result, &expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_map_filter() -> Result<()> {
+        let mut builder = MapBuilder::new(None, StringBuilder::new(), Int32Builder::new());
+        for (key, value) in [("a", Some(1)), ("b", Some(2)), ("c", None)] {
+            builder.keys().append_value(key);
+            builder.values().append_option(value);
+        }
+        builder.append(true)?;
+        builder.append(false)?;
+        let map: ArrayRef = Arc::new(builder.finish());
+        let batch = RecordBatch::try_from_iter(vec![("map", map)])?;
+
+        // map_filter(map, (k, v) -> v > 1 or k = 'c')
+        let args = vec![
+            Arc::new(Field::new("k", DataType::Utf8, false)),
+            int_var("v"),
+        ];
+        let schema = lambda_schema(&batch, &args);
+        let body = binary(
+            binary(col("v", &schema)?, Operator::Gt, lit(1), &schema)?,
+            Operator::Or,
+            binary(col("k", &schema)?, Operator::Eq, lit("c"), &schema)?,
+            &schema,
+        )?;
+        let expr = HigherOrderFunctionExpr::try_new(
+            HigherOrderFunction::MapFilter,
+            vec![col("map", &schema)?],
+            vec![LambdaFunction::new(args, body)],
+            batch.schema().field(0).data_type().clone(),
+        )?;
+        let result = expr.evaluate(&batch)?.into_array(batch.num_rows())?;
+        let expected = vec![
+            "+-------------+",
+            "| result      |",
+            "+-------------+",
+            "| {b: 2, c: } |",
+            "|             |",
+            "+-------------+",
+        ];
+        assert_batches_eq!(
+            expected,
+            &[RecordBatch::try_from_iter(vec![("result", result)])?]
+        );
+        Ok(())
+    }
+}
@@ -16,10 +16,12 @@ use std::{any::Any, sync::Arc};
 
 use datafusion::physical_expr::PhysicalExpr;
 
+pub mod binary_arithmetic;
 pub mod bloom_filter_might_contain;
 pub mod cast;
 pub mod get_indexed_field;
 pub mod get_map_value;
+pub mod higher_order_function;
 pub mod named_struct;
 pub mod row_num;
 pub mod spark_scalar_subquery_wrapper;
@@ -16,22 +16,33 @@
 
 use std::sync::Arc;
 
+use arrow::datatypes::DataType;
 use datafusion::{common::Result, logical_expr::ScalarFunctionImplementation};
 use datafusion_ext_commons::df_unimplemented_err;
 
 mod brickhouse;
+mod spark_arrays;
 mod spark_check_overflow;
+mod spark_crypto;
 mod spark_dates;
+mod spark_datetime_format;
+mod spark_encode;
 pub mod spark_get_json_object;
+mod spark_json;
 mod spark_make_array;
 mod spark_make_decimal;
+mod spark_maps;
 mod spark_murmur3_hash;
 mod spark_null_if;
+mod spark_regexp;
 mod spark_strings;
 mod spark_unscaled_value;
 mod spark_xxhash64;
 
-pub fn create_spark_ext_function(name: &str) -> Result<ScalarFunctionImplementation> {
+pub fn create_spark_ext_function(
+    name: &str,
+    return_type: &DataType,
+) -> Result<ScalarFunctionImplementation> {
     Ok(match name {
         "Placeholder" => Arc::new(|_| panic!("placeholder() should never be called")),
         "NullIf" => Arc::new(spark_null_if::spark_null_if),
@@ -41,10 +52,45 @@ pub fn create_spark_ext_function(name: &str) -> Result<ScalarFunctionImplementat
         "CheckOverflow" => Arc::new(spark_check_overflow::spark_check_overflow),
         "Murmur3Hash" => Arc::new(spark_murmur3_hash::spark_murmur3_hash),
         "XxHash64" => Arc::new(spark_xxhash64::spark_xxhash64),
+        "Md5" => Arc::new(spark_crypto::spark_md5),
+        "Sha1" => Arc::new(spark_crypto::spark_sha1),
+        "Sha2" => Arc::new(spark_crypto::spark_sha2),
+        "Crc32" => Arc::new(spark_crypto::spark_crc32),
+        "Base64" => Arc::new(spark_encode::spark_base64),
+        "UnBase64" => Arc::new(spark_encode::spark_unbase64),
+        "Hex" => Arc::new(spark_encode::spark_hex),
+        "Unhex" => Arc::new(spark_encode::spark_unhex),
+        "Conv" => Arc::new(spark_encode::spark_conv),
         "GetJsonObject" => Arc::new(spark_get_json_object::spark_get_json_object),
         "GetParsedJsonObject" => Arc::new(spark_get_json_object::spark_get_parsed_json_object),
         "ParseJson" => Arc::new(spark_get_json_object::spark_parse_json),
+        "FromJson" => {
+            let return_type = return_type.clone();
+            Arc::new(move |args| spark_json::from_json(args, &return_type))
+        }
+        "ToJson" => Arc::new(spark_json::to_json),
+        "JsonArrayLength" => Arc::new(spark_json::json_array_length),
+        "JsonObjectKeys" => Arc::new(spark_json::json_object_keys),
         "MakeArray" => Arc::new(spark_make_array::array),
+        "ArrayContains" => Arc::new(spark_arrays::array_contains),
+        "ArrayDistinct" => Arc::new(spark_arrays::array_distinct),
+        "SortArray" => Arc::new(spark_arrays::sort_array),
+        "ArrayPosition" => Arc::new(spark_arrays::array_position),
+        "ElementAt" => Arc::new(spark_arrays::element_at),
+        "Slice" => Arc::new(spark_arrays::array_slice),
+        "Flatten" => Arc::new(spark_arrays::array_flatten),
+        "ArraysOverlap" => Arc::new(spark_arrays::arrays_overlap),
+        "ArrayJoin" => Arc::new(spark_arrays::array_join),
+        "MapKeys" => Arc::new(spark_maps::map_keys),
+        "MapValues" => Arc::new(spark_maps::map_values),
+        "MapEntries" => Arc::new(spark_maps::map_entries),
+        "MapFromArrays" => Arc::new(spark_maps::map_from_arrays),
+        "MapFromEntries" => Arc::new(spark_maps::map_from_entries),
+        "MapConcat" => Arc::new(spark_maps::map_concat),
+        "StringToMap" => {
+            let caches = Default::default();
+            Arc::new(move |args| spark_maps::str_to_map(args, &caches))
+        }
         "StringSpace" => Arc::new(spark_strings::string_space),
         "StringRepeat" => Arc::new(spark_strings::string_repeat),
         "StringSplit" => Arc::new(spark_strings::string_split),
@@ -52,9 +98,46 @@ pub fn create_spark_ext_function(name: &str) -> Result<ScalarFunctionImplementat
         "StringConcatWs" => Arc::new(spark_strings::string_concat_ws),
         "StringLower" => Arc::new(spark_strings::string_lower),
         "StringUpper" => Arc::new(spark_strings::string_upper),
+        "SubstringIndex" => Arc::new(spark_strings::string_substring_index),
+        "StringInstr" => Arc::new(spark_strings::string_instr),
+        "StringLocate" => Arc::new(spark_strings::string_locate),
+        "StringLPad" => Arc::new(spark_strings::string_lpad),
+        "StringRPad" => Arc::new(spark_strings::string_rpad),
+        "StringTranslate" => Arc::new(spark_strings::string_translate),
+        "Levenshtein" => Arc::new(spark_strings::string_levenshtein),
+        "SoundEx" => Arc::new(spark_strings::string_soundex),
+        "FormatNumber" => Arc::new(spark_strings::string_format_number),
+        "InitCap" => Arc::new(spark_strings::string_initcap),
+        "RLike" => spark_regexp::with_regex_cache(spark_regexp::rlike),
+        "RegExpExtract" => spark_regexp::with_regex_cache(spark_regexp::regexp_extract),
+        "RegExpExtractAll" => spark_regexp::with_regex_cache(spark_regexp::regexp_extract_all),
+        "RegExpReplace" => spark_regexp::with_regex_cache(spark_regexp::regexp_replace),
         "Year" => Arc::new(spark_dates::spark_year),
         "Month" => Arc::new(spark_dates::spark_month),
         "Day" => Arc::new(spark_dates::spark_day),
+        "DateAdd" => Arc::new(spark_dates::spark_date_add),
+        "DateSub" => Arc::new(spark_dates::spark_date_sub),
+        "DateDiff" => Arc::new(spark_dates::spark_date_diff),
+        "AddMonths" => Arc::new(spark_dates::spark_add_months),
+        "MonthsBetween" => Arc::new(spark_dates::spark_months_between),
+        "LastDay" => Arc::new(spark_dates::spark_last_day),
+        "NextDay" => Arc::new(spark_dates::spark_next_day),
+        "TruncDate" => Arc::new(spark_dates::spark_trunc_date),
+        "TruncTimestamp" => Arc::new(spark_dates::spark_trunc_timestamp),
+        "Hour" => Arc::new(spark_dates::spark_hour),
+        "Minute" => Arc::new(spark_dates::spark_minute),
+        "Second" => Arc::new(spark_dates::spark_second),
+        "DayOfWeek" => Arc::new(spark_dates::spark_day_of_week),
+        "WeekOfYear" => Arc::new(spark_dates::spark_week_of_year),
+        "Quarter" => Arc::new(spark_dates::spark_quarter),
+        "DateFormat" => Arc::new(spark_datetime_format::spark_date_format),
+        "FromUnixTime" => Arc::new(spark_datetime_format::spark_from_unixtime),
+        "UnixTimestamp" => Arc::new(spark_datetime_format::spark_unix_timestamp),
+        "GetTimestamp" => Arc::new(spark_datetime_format::spark_get_timestamp),
+        "ParseToDate" => Arc::new(spark_datetime_format::spark_parse_to_date),
+        "FromUTCTimestamp" => Arc::new(spark_dates::spark_from_utc_timestamp),
+        "ToUTCTimestamp" => Arc::new(spark_dates::spark_to_utc_timestamp),
+        "ConvertTimezone" => Arc::new(spark_dates::spark_convert_timezone),
         "BrickhouseArrayUnion" => Arc::new(brickhouse::array_union::array_union),
         _ => df_unimplemented_err!("spark ext function not implemented: {name}")?,
     })
@@ -0,0 +1,673 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Spark array functions over ListArray. element equality follows spark's
+//! ordering, in which NaN equals NaN and -0.0 equals 0.0.
+
+use std::{cmp::Ordering, collections::HashSet, sync::Arc};
+
+use arrow::{
+    array::{
+        make_comparator, Array, ArrayRef, AsArray, BooleanArray, DynComparator, Int64Array,
+        ListArray, StringBuilder, UInt32Array,
+    },
+    buffer::{NullBuffer, OffsetBuffer, ScalarBuffer},
+    compute::{take, SortOptions},
+    datatypes::{DataType, Float32Type, Float64Type},
+    row::{RowConverter, SortField},
+};
+use datafusion::{
+    common::{
+        cast::{as_int32_array, as_list_array, as_string_array},
+        Result, ScalarValue,
+    },
+    physical_plan::ColumnarValue,
+};
+use datafusion_ext_commons::df_execution_err;
+
+/// array_contains(array, value), returns null if no element matches and the
+/// array contains null elements
+pub fn array_contains(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(args)?;
+    let list = as_list_array(&arrays[0])?;
+    let (elements, values) = (normalize(list.values()), normalize(&arrays[1]));
+    let cmp = element_comparator(&elements, &values)?;
+
+    let result: BooleanArray = (0..list.len())
+        .map(|row| {
+            if list.is_null(row) || values.is_null(row) {
+                return None;
+            }
+            let mut has_null = false;
+            for i in element_range(list, row) {
+                if elements.is_null(i) {
+                    has_null = true;
+                } else if cmp(i, row).is_eq() {
+                    return Some(true);
+                }
+            }
+            (!has_null).then_some(false)
+        })
+        .collect();
+    Ok(ColumnarValue::Array(Arc::new(result)))
+}
+
+/// array_position(array, value), returns 1-based position of the first
+/// matched element, or 0 if not found
+pub fn array_position(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(args)?;
+    let list = as_list_array(&arrays[0])?;
+    let (elements, values) = (normalize(list.values()), normalize(&arrays[1]));
+    let cmp = element_comparator(&elements, &values)?;
+
+    let result: Int64Array = (0..list.len())
+        .map(|row| {
+            if list.is_null(row) || values.is_null(row) {
+                return None;
+            }
+            let range = element_range(list, row);
+            let start = range.start;
+            let found = range
+                .into_iter()
+                .find(|&i| elements.is_valid(i) && cmp(i, row).is_eq())
+                .map(|i| (i - start + 1) as i64);
+            Some(found.unwrap_or(0))
+        })
+        .collect();
+    Ok(ColumnarValue::Array(Arc::new(result)))
+}
+
+/// array_distinct(array), keeps the first occurrence of each element
+/// including null
+pub fn array_distinct(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(args)?;
+    let list = as_list_array(&arrays[0])?;
+    let converter = RowConverter::new(vec![SortField::new(list.value_type())])?;
+    let rows = converter.convert_columns(&[normalize(list.values())])?;
+
+    let mut builder = ListIndicesBuilder::new(list.len());
+    let mut seen = HashSet::new();
+    for row in 0..list.len() {
+        if list.is_null(row) {
+            builder.append_null();
+            continue;
+        }
+        seen.clear();
+        for i in element_range(list, row) {
+            if seen.insert(rows.row(i)) {
+                builder.append_index(i);
+            }
+        }
+        builder.append_valid();
+    }
+    builder.finish(list)
+}
+
+/// sort_array(array, ascending), nulls are placed first in ascending order
+/// and last in descending order. NaN is greater than any other value
+pub fn sort_array(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(args)?;
+    let list = as_list_array(&arrays[0])?;
+    let ascending = match &args[1] {
+        &ColumnarValue::Scalar(ScalarValue::Boolean(Some(ascending))) => ascending,
+        _ => df_execution_err!("sort_array: ascending must be a non-null literal boolean")?,
+    };
+    let elements = normalize(list.values());
+    let cmp = element_comparator(&elements, &elements)?;
+
+    let mut builder = ListIndicesBuilder::new(list.len());
+    let mut indices = vec![];
+    for row in 0..list.len() {
+        if list.is_null(row) {
+            builder.append_null();
+            continue;
+        }
+        indices.clear();
+        indices.extend(element_range(list, row));
+        indices.sort_by(|&i, &j| {
+            let ord = match (elements.is_null(i), elements.is_null(j)) {
+                (true, true) => Ordering::Equal,
+                (true, false) => Ordering::Less,
+                (false, true) => Ordering::Greater,
+                (false, false) => cmp(i, j),
+            };
+            if ascending {
+                ord
+            } else {
+                ord.reverse()
+            }
+        });
+        indices.iter().for_each(|&i| builder.append_index(i));
+        builder.append_valid();
+    }
+    builder.finish(list)
+}
+
+/// element_at(array, index), index is 1-based and counts from the end if
+/// negative. returns null if index is out of bounds
+pub fn element_at(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(args)?;
+    let list = as_list_array(&arrays[0])?;
+    let indices = as_int32_array(&arrays[1])?;
+
+    let mut take_indices = Vec::with_capacity(list.len());
+    for row in 0..list.len() {
+        if list.is_null(row) || indices.is_null(row) {
+            take_indices.push(None);
+            continue;
+        }
+        let range = element_range(list, row);
+        let (index, len) = (indices.value(row) as i64, range.len() as i64);
+        let offset = match index {
+            0 => return df_execution_err!("SQL array indices start at 1"),
+            index if index > 0 && index <= len => index - 1,
+            index if index < 0 && -index <= len => len + index,
+            _ => {
+                take_indices.push(None);
+                continue;
+            }
+        };
+        take_indices.push(Some((range.start as i64 + offset) as u32));
+    }
+    let taken = take(list.values(), &UInt32Array::from(take_indices), None)?;
+    Ok(ColumnarValue::Array(taken))
+}
+
+/// slice(array, start, length), start is 1-based and counts from the end if
+/// negative. returns empty array if start is out of bounds
+pub fn array_slice(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(args)?;
+    let list = as_list_array(&arrays[0])?;
+    let starts = as_int32_array(&arrays[1])?;
+    let lengths = as_int32_array(&arrays[2])?;
+
+    let mut builder = ListIndicesBuilder::new(list.len());
+    for row in 0..list.len() {
+        if list.is_null(row) || starts.is_null(row) || lengths.is_null(row) {
+            builder.append_null();
+            continue;
+        }
+        let range = element_range(list, row);
+        let (start, length, len) = (starts.value(row), lengths.value(row), range.len() as i64);
+        let start_offset = match start {
+            0 => {
+                return df_execution_err!(
+                    "Unexpected value for start in function slice: SQL array indices start at 1."
+                );
+            }
+            start if start < 0 => len + start as i64,
+            start => start as i64 - 1,
+        };
+        if length < 0 {
+            return df_execution_err!(
+                "Unexpected value for length in function slice: length must be greater than or \
+                 equal to 0."
+            );
+        }
+        if start_offset >= 0 && start_offset < len {
+            let end_offset = (start_offset + length as i64).min(len);
+            for offset in start_offset..end_offset {
+                builder.append_index(range.start + offset as usize);
+            }
+        }
+        builder.append_valid();
+    }
+    builder.finish(list)
+}
+
+/// flatten(array_of_arrays), returns null if any inner array is null
+pub fn array_flatten(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(args)?;
+    let list = as_list_array(&arrays[0])?;
+    let inner_list = as_list_array(list.values())?;
+
+    let mut builder = ListIndicesBuilder::new(list.len());
+    for row in 0..list.len() {
+        let inner_rows = element_range(list, row);
+        if list.is_null(row) || inner_rows.clone().any(|i| inner_list.is_null(i)) {
+            builder.append_null();
+            continue;
+        }
+        for inner_row in inner_rows {
+            element_range(inner_list, inner_row).for_each(|i| builder.append_index(i));
+        }
+        builder.append_valid();
+    }
+    builder.finish(inner_list)
+}
+
+/// arrays_overlap(array1, array2), returns true if both arrays have a common
+/// non-null element, otherwise null if either array contains null elements
+/// and both arrays are non-empty
+pub fn arrays_overlap(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(args)?;
+    let list1 = as_list_array(&arrays[0])?;
+    let list2 = as_list_array(&arrays[1])?;
+    let converter = RowConverter::new(vec![SortField::new(list1.value_type())])?;
+    let rows1 = converter.convert_columns(&[normalize(list1.values())])?;
+    let rows2 = converter.convert_columns(&[normalize(list2.values())])?;
+
+    let mut set = HashSet::new();
+    let result: BooleanArray = (0..list1.len())
+        .map(|row| {
+            if list1.is_null(row) || list2.is_null(row) {
+                return None;
+            }
+            let (range1, range2) = (element_range(list1, row), element_range(list2, row));
+            if range1.is_empty() || range2.is_empty() {
+                return Some(false);
+            }
+            let mut has_null = false;
+            set.clear();
+            for i in range1 {
+                if list1.values().is_null(i) {
+                    has_null = true;
+                } else {
+                    set.insert(rows1.row(i));
+                }
+            }
+            for i in range2 {
+                if list2.values().is_null(i) {
+                    has_null = true;
+                } else if set.contains(&rows2.row(i)) {
+                    return Some(true);
+                }
+            }
+            (!has_null).then_some(false)
+        })
+        .collect();
+    Ok(ColumnarValue::Array(Arc::new(result)))
+}
+
+/// array_join(array, delimiter[, null_replacement]), null elements are
+/// skipped unless null_replacement is specified
+pub fn array_join(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(args)?;
+    let list = as_list_array(&arrays[0])?;
+    let elements = as_string_array(list.values())?;
+    let delimiters = as_string_array(&arrays[1])?;
+    let null_replacements = arrays
+        .get(2)
+        .map(|array| as_string_array(array))
+        .transpose()?;
+
+    let mut builder = StringBuilder::new();
+    let mut joined = String::new();
+    for row in 0..list.len() {
+        let null_replacement = null_replacements.map(|r| r.is_valid(row).then(|| r.value(row)));
+        if list.is_null(row) || delimiters.is_null(row) || null_replacement == Some(None) {
+            builder.append_null();
+            continue;
+        }
+        joined.clear();
+        let mut first = true;
+        for i in element_range(list, row) {
+            let element = match (elements.is_valid(i), null_replacement) {
+                (true, _) => elements.value(i),
+                (false, Some(Some(null_replacement))) => null_replacement,
+                _ => continue,
+            };
+            if !first {
+                joined.push_str(delimiters.value(row));
+            }
+            joined.push_str(element);
+            first = false;
+        }
+        builder.append_value(&joined);
+    }
+    Ok(ColumnarValue::Array(Arc::new(builder.finish())))
+}
+
+fn args_to_arrays(args: &[ColumnarValue]) -> Result<Vec<ArrayRef>> {
+    let num_rows = args
+        .iter()
+        .find_map(|arg| match arg {
+            ColumnarValue::Array(array) => Some(array.len()),
+            ColumnarValue::Scalar(_) => None,
+        })
+        .unwrap_or(1);
+    args.iter()
+        .map(|arg| arg.clone().into_array(num_rows))
+        .collect()
+}
+
+fn element_range(list: &ListArray, row: usize) -> std::ops::Range<usize> {
+    let offsets = list.value_offsets();
+    offsets[row] as usize..offsets[row + 1] as usize
+}
+
+fn element_comparator(left: &ArrayRef, right: &ArrayRef) -> Result<DynComparator> {
+    Ok(make_comparator(left, right, SortOptions::default())?)
+}
+
+/// normalizes -0.0 to 0.0 and all NaNs to the canonical NaN, so that float
+/// elements can be compared and hashed by their bits
+pub(crate) fn normalize(array: &ArrayRef) -> ArrayRef {
+    fn normalize_f64(v: f64) -> f64 {
+        if v.is_nan() {
+            f64::NAN
+        } else {
+            v + 0.0
+        }
+    }
+    match array.data_type() {
+        DataType::Float32 => Arc::new(
+            array
+                .as_primitive::<Float32Type>()
+                .unary::<_, Float32Type>(|v| normalize_f64(v as f64) as f32),
+        ) as ArrayRef,
+        DataType::Float64 => Arc::new(
+            array
+                .as_primitive::<Float64Type>()
+                .unary::<_, Float64Type>(normalize_f64),
+        ),
+        _ => array.clone(),
+    }
+}
+
+/// Builds list array by taking elements from the values of a list array.
+struct ListIndicesBuilder {
+    indices: Vec<u32>,
+    offsets: Vec<i32>,
+    valids: Vec<bool>,
+}
+
+impl ListIndicesBuilder {
+    fn new(num_rows: usize) -> Self {
+        let mut offsets = Vec::with_capacity(num_rows + 1);
+        offsets.push(0);
+        Self {
+            indices: vec![],
+            offsets,
+            valids: Vec::with_capacity(num_rows),
+        }
+    }
+
+    fn append_index(&mut self, index: usize) {
+        self.indices.push(index as u32);
+    }
+
+    fn append_valid(&mut self) {
+        self.offsets.push(self.indices.len() as i32);
+        self.valids.push(true);
+    }
+
+    fn append_null(&mut self) {
+        self.offsets.push(self.indices.len() as i32);
+        self.valids.push(false);
+    }
+
+    /// takes elements from values of the source list, the result has the
+    /// same data type as the source list
+    fn finish(self, source: &ListArray) -> Result<ColumnarValue> {
+        let values = take(source.values(), &UInt32Array::from(self.indices), None)?;
+        let field = match source.data_type() {
+            DataType::List(field) => field.clone(),
+            other => return df_execution_err!("expect list type, got {other}"),
+        };
+        Ok(ColumnarValue::Array(Arc::new(ListArray::try_new(
+            field,
+            OffsetBuffer::new(ScalarBuffer::from(self.offsets)),
+            values,
+            Some(NullBuffer::from(self.valids)),
+        )?)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{
+            Array, ArrayRef, BooleanArray, Float64Array, Int32Array, Int64Array, ListArray,
+            StringArray,
+        },
+        datatypes::{Float64Type, Int32Type},
+    };
+    use datafusion::{
+        common::{cast::as_list_array, Result, ScalarValue},
+        physical_plan::ColumnarValue,
+    };
+
+    use crate::spark_arrays::*;
+
+    fn int_lists(lists: Vec<Option<Vec<Option<i32>>>>) -> ColumnarValue {
+        ColumnarValue::Array(Arc::new(ListArray::from_iter_primitive::<Int32Type, _, _>(
+            lists,
+        )))
+    }
+
+    fn to_int_lists(r: ColumnarValue) -> Result<Vec<Option<Vec<Option<i32>>>>> {
+        let array = r.into_array(1)?;
+        Ok(as_list_array(&array)?
+            .iter()
+            .map(|list| {
+                list.map(|list| {
+                    list.as_any()
+                        .downcast_ref::<Int32Array>()
+                        .unwrap()
+                        .iter()
+                        .collect()
+                })
+            })
+            .collect())
+    }
+
+    fn int(v: i32) -> ColumnarValue {
+        ColumnarValue::Scalar(ScalarValue::Int32(Some(v)))
+    }
+
+    #[test]
+    fn test_array_contains_position() -> Result<()> {
+        let lists = int_lists(vec![
+            Some(vec![Some(1), Some(2), Some(3)]),
+            Some(vec![Some(1), None, Some(3)]),
+            Some(vec![Some(1), None, Some(2)]),
+            Some(vec![]),
+            None,
+        ]);
+        let r = array_contains(&[lists.clone(), int(2)])?.into_array(5)?;
+        let expected: ArrayRef = Arc::new(BooleanArray::from(vec![
+            Some(true),
+            None,
+            Some(true),
+            Some(false),
+            None,
+        ]));
+        assert_eq!(&r, &expected);
+
+        let r = array_position(&[lists.clone(), int(2)])?.into_array(5)?;
+        let expected: ArrayRef = Arc::new(Int64Array::from(vec![
+            Some(2),
+            Some(0),
+            Some(3),
+            Some(0),
+            None,
+        ]));
+        assert_eq!(&r, &expected);
+
+        // null value
+        let null_value = ColumnarValue::Scalar(ScalarValue::Int32(None));
+        let r = array_contains(&[lists, null_value])?.into_array(5)?;
+        assert_eq!(r.null_count(), 5);
+
+        // NaN equals NaN and -0.0 equals 0.0
+        let float_lists =
+            ColumnarValue::Array(Arc::new(
+                ListArray::from_iter_primitive::<Float64Type, _, _>(vec![
+                    Some(vec![Some(1.0), Some(f64::NAN)]),
+                    Some(vec![Some(-0.0)]),
+                ]),
+            ));
+        let values = ColumnarValue::Array(Arc::new(Float64Array::from(vec![f64::NAN, 0.0])));
+        let r = array_contains(&[float_lists, values])?.into_array(2)?;
+        let expected: ArrayRef = Arc::new(BooleanArray::from(vec![true, true]));
+        assert_eq!(&r, &expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_array_distinct_sort() -> Result<()> {
+        let lists = int_lists(vec![
+            Some(vec![Some(3), None, Some(1), Some(3), None, Some(2)]),
+            Some(vec![]),
+            None,
+        ]);
+        assert_eq!(
+            to_int_lists(array_distinct(&[lists.clone()])?)?,
+            vec![
+                Some(vec![Some(3), None, Some(1), Some(2)]),
+                Some(vec![]),
+                None
+            ]
+        );
+
+        let asc = ColumnarValue::Scalar(ScalarValue::Boolean(Some(true)));
+        let desc = ColumnarValue::Scalar(ScalarValue::Boolean(Some(false)));
+        assert_eq!(
+            to_int_lists(sort_array(&[lists.clone(), asc])?)?,
+            vec![
+                Some(vec![None, None, Some(1), Some(2), Some(3), Some(3)]),
+                Some(vec![]),
+                None
+            ]
+        );
+        assert_eq!(
+            to_int_lists(sort_array(&[lists, desc])?)?,
+            vec![
+                Some(vec![Some(3), Some(3), Some(2), Some(1), None, None]),
+                Some(vec![]),
+                None
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_element_at_slice() -> Result<()> {
+        let lists = int_lists(vec![
+            Some(vec![Some(1), Some(2), Some(3), Some(4)]),
+            Some(vec![Some(1), None]),
+            None,
+        ]);
+        let r = element_at(&[lists.clone(), int(2)])?.into_array(3)?;
+        let expected: ArrayRef = Arc::new(Int32Array::from(vec![Some(2), None, None]));
+        assert_eq!(&r, &expected);
+        let r = element_at(&[lists.clone(), int(-4)])?.into_array(3)?;
+        let expected: ArrayRef = Arc::new(Int32Array::from(vec![Some(1), None, None]));
+        assert_eq!(&r, &expected);
+        assert!(element_at(&[lists.clone(), int(0)]).is_err());
+
+        assert_eq!(
+            to_int_lists(array_slice(&[lists.clone(), int(2), int(2)])?)?,
+            vec![Some(vec![Some(2), Some(3)]), Some(vec![None]), None]
+        );
+        assert_eq!(
+            to_int_lists(array_slice(&[lists.clone(), int(-3), int(5)])?)?,
+            vec![Some(vec![Some(2), Some(3), Some(4)]), Some(vec![]), None]
+        );
+        assert_eq!(
+            to_int_lists(array_slice(&[lists.clone(), int(5), int(1)])?)?,
+            vec![Some(vec![]), Some(vec![]), None]
+        );
+        assert!(array_slice(&[lists.clone(), int(0), int(1)]).is_err());
+        assert!(array_slice(&[lists, int(1), int(-1)]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_array_flatten() -> Result<()> {
+        let inner = ListArray::from_iter_primitive::<Int32Type, _, _>(vec![
+            Some(vec![Some(1), Some(2)]),
+            Some(vec![Some(3)]),
+            Some(vec![None]),
+            None,
+        ]);
+        let field = Arc::new(arrow::datatypes::Field::new_list_field(
+            inner.data_type().clone(),
+            true,
+        ));
+        let outer = ListArray::try_new(
+            field,
+            arrow::buffer::OffsetBuffer::new(vec![0, 2, 3, 4, 4].into()),
+            Arc::new(inner),
+            Some(vec![true, true, true, false].into()),
+        )?;
+        let r = array_flatten(&[ColumnarValue::Array(Arc::new(outer))])?;
+        assert_eq!(
+            to_int_lists(r)?,
+            vec![
+                Some(vec![Some(1), Some(2), Some(3)]),
+                Some(vec![None]),
+                None,
+                None
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_arrays_overlap() -> Result<()> {
+        let lists1 = int_lists(vec![
+            Some(vec![Some(1), Some(2)]),
+            Some(vec![Some(1), None]),
+            Some(vec![Some(1), Some(2)]),
+            Some(vec![None]),
+            None,
+        ]);
+        let lists2 = int_lists(vec![
+            Some(vec![Some(2), Some(3)]),
+            Some(vec![Some(3)]),
+            Some(vec![Some(3)]),
+            Some(vec![]),
+            Some(vec![Some(1)]),
+        ]);
+        let r = arrays_overlap(&[lists1, lists2])?.into_array(5)?;
+        let expected: ArrayRef = Arc::new(BooleanArray::from(vec![
+            Some(true),
+            None,
+            Some(false),
+            Some(false),
+            None,
+        ]));
+        assert_eq!(&r, &expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_array_join() -> Result<()> {
+        let lists = ColumnarValue::Array(Arc::new({
+            let mut builder = arrow::array::ListBuilder::new(arrow::array::StringBuilder::new());
+            builder.append_value([Some("a"), None, Some("c")]);
+            builder.append_value([None::<&str>]);
+            builder.append_null();
+            builder.finish()
+        }));
+        let delimiter = ColumnarValue::Scalar(ScalarValue::from(","));
+        let r = array_join(&[lists.clone(), delimiter.clone()])?.into_array(3)?;
+        let expected: ArrayRef = Arc::new(StringArray::from(vec![Some("a,c"), Some(""), None]));
+        assert_eq!(&r, &expected);
+
+        let null_replacement = ColumnarValue::Scalar(ScalarValue::from("?"));
+        let r = array_join(&[lists.clone(), delimiter.clone(), null_replacement])?.into_array(3)?;
+        let expected: ArrayRef = Arc::new(StringArray::from(vec![Some("a,?,c"), Some("?"), None]));
+        assert_eq!(&r, &expected);
+
+        let null_replacement = ColumnarValue::Scalar(ScalarValue::Utf8(None));
+        let r = array_join(&[lists, delimiter, null_replacement])?.into_array(3)?;
+        assert_eq!(r.null_count(), 3);
+        Ok(())
+    }
+}
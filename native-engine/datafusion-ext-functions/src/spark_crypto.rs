@@ -0,0 +1,184 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, Int64Array, StringArray};
+use datafusion::{
+    common::{cast::as_int32_array, Result},
+    physical_plan::ColumnarValue,
+};
+use md5::{Digest, Md5};
+use sha1::Sha1;
+use sha2::{Sha224, Sha256, Sha384, Sha512};
+
+use crate::spark_encode::{binary_values, to_hex};
+
+/// md5(input), returns lower-case hex string like spark
+pub fn spark_md5(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let input = args[0].clone().into_array(1)?;
+    let output: StringArray = binary_values(&input)?
+        .into_iter()
+        .map(|v| v.map(hex_digest::<Md5>))
+        .collect();
+    Ok(ColumnarValue::Array(Arc::new(output)))
+}
+
+/// sha1(input), returns lower-case hex string like spark
+pub fn spark_sha1(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let input = args[0].clone().into_array(1)?;
+    let output: StringArray = binary_values(&input)?
+        .into_iter()
+        .map(|v| v.map(hex_digest::<Sha1>))
+        .collect();
+    Ok(ColumnarValue::Array(Arc::new(output)))
+}
+
+/// sha2(input, bit_length), returns lower-case hex string like spark, or null
+/// if the bit length is not one of 224, 256 (or 0), 384 and 512
+pub fn spark_sha2(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(args)?;
+    let bit_lengths = as_int32_array(&arrays[1])?;
+    let output: StringArray = binary_values(&arrays[0])?
+        .into_iter()
+        .zip(bit_lengths)
+        .map(|(v, bit_length)| match bit_length? {
+            224 => Some(hex_digest::<Sha224>(v?)),
+            0 | 256 => Some(hex_digest::<Sha256>(v?)),
+            384 => Some(hex_digest::<Sha384>(v?)),
+            512 => Some(hex_digest::<Sha512>(v?)),
+            _ => None,
+        })
+        .collect();
+    Ok(ColumnarValue::Array(Arc::new(output)))
+}
+
+/// crc32(input), returns the checksum as bigint like spark
+pub fn spark_crc32(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let input = args[0].clone().into_array(1)?;
+    let output: Int64Array = binary_values(&input)?
+        .into_iter()
+        .map(|v| v.map(|v| crc32fast::hash(v) as i64))
+        .collect();
+    Ok(ColumnarValue::Array(Arc::new(output)))
+}
+
+fn hex_digest<D: Digest>(v: &[u8]) -> String {
+    to_hex(&D::digest(v), false)
+}
+
+fn args_to_arrays(args: &[ColumnarValue]) -> Result<Vec<ArrayRef>> {
+    let num_rows = args
+        .iter()
+        .find_map(|arg| match arg {
+            ColumnarValue::Array(array) => Some(array.len()),
+            ColumnarValue::Scalar(_) => None,
+        })
+        .unwrap_or(1);
+    args.iter()
+        .map(|arg| arg.clone().into_array(num_rows))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{Array, AsArray, BinaryArray, Int32Array, StringArray},
+        datatypes::Int64Type,
+    };
+    use datafusion::{
+        common::{Result, ScalarValue},
+        physical_plan::ColumnarValue,
+    };
+
+    use super::*;
+
+    fn strings(output: ColumnarValue) -> Result<Vec<Option<String>>> {
+        let output = output.into_array(1)?;
+        Ok(output
+            .as_string::<i32>()
+            .iter()
+            .map(|s| s.map(|s| s.to_owned()))
+            .collect())
+    }
+
+    #[test]
+    fn test_md5_sha1() -> Result<()> {
+        let input = ColumnarValue::Array(Arc::new(StringArray::from(vec![
+            Some("Spark"),
+            Some(""),
+            None,
+        ])));
+        assert_eq!(
+            strings(spark_md5(&[input.clone()])?)?,
+            vec![
+                Some("8cde774d6f7333752ed72cacddb05126".to_owned()),
+                Some("d41d8cd98f00b204e9800998ecf8427e".to_owned()),
+                None,
+            ],
+        );
+        assert_eq!(
+            strings(spark_sha1(&[input])?)?,
+            vec![
+                Some("85f5955f4b27a9a4c2aab6ffe5d7189fc298b92c".to_owned()),
+                Some("da39a3ee5e6b4b0d3255bfef95601890afd80709".to_owned()),
+                None,
+            ],
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_sha2() -> Result<()> {
+        let input = ColumnarValue::Array(Arc::new(BinaryArray::from(vec![
+            Some(b"Spark".as_ref()),
+            Some(b"Spark".as_ref()),
+            Some(b"Spark".as_ref()),
+            None,
+        ])));
+        let bit_lengths = ColumnarValue::Array(Arc::new(Int32Array::from(vec![
+            Some(256),
+            Some(0),
+            Some(100),
+            Some(256),
+        ])));
+        let sha256 = "529bc3b07127ecb7e53a4dcf1991d9152c24537d919178022b2c42657f79a26b";
+        assert_eq!(
+            strings(spark_sha2(&[input, bit_lengths])?)?,
+            vec![Some(sha256.to_owned()), Some(sha256.to_owned()), None, None],
+        );
+
+        let input = ColumnarValue::Scalar(ScalarValue::from("Spark"));
+        let output = strings(spark_sha2(&[input, ColumnarValue::Scalar(512.into())])?)?;
+        assert_eq!(output[0].as_ref().map(|s| s.len()), Some(128));
+        Ok(())
+    }
+
+    #[test]
+    fn test_crc32() -> Result<()> {
+        let input = ColumnarValue::Array(Arc::new(StringArray::from(vec![Some("Spark"), None])));
+        let output = spark_crc32(&[input])?.into_array(1)?;
+        assert_eq!(
+            output
+                .as_primitive::<Int64Type>()
+                .iter()
+                .collect::<Vec<_>>(),
+            vec![Some(1557323817), None],
+        );
+        assert_eq!(output.null_count(), 1);
+        Ok(())
+    }
+}
@@ -12,8 +12,28 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use arrow::compute::{date_part, DatePart};
-use datafusion::{common::Result, physical_plan::ColumnarValue};
+use std::sync::Arc;
+
+use arrow::{
+    array::{ArrayRef, Date32Array, Float64Array, Int32Array, TimestampMicrosecondArray},
+    compute::{date_part, DatePart},
+};
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveDateTime, Offset, TimeZone,
+    Timelike, Weekday,
+};
+use chrono_tz::Tz;
+use datafusion::{
+    common::{
+        cast::{
+            as_boolean_array, as_date32_array, as_int32_array, as_string_array,
+            as_timestamp_microsecond_array,
+        },
+        Result, ScalarValue,
+    },
+    physical_plan::ColumnarValue,
+};
+use datafusion_ext_commons::df_execution_err;
 
 pub fn spark_year(args: &[ColumnarValue]) -> Result<ColumnarValue> {
     let input = args[0].clone().into_array(1)?;
@@ -30,14 +50,628 @@ pub fn spark_day(args: &[ColumnarValue]) -> Result<ColumnarValue> {
     Ok(ColumnarValue::Array(date_part(&input, DatePart::Day)?))
 }
 
+/// date_add(start_date, num_days)
+pub fn spark_date_add(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(args)?;
+    let dates: Date32Array = as_date32_array(&arrays[0])?
+        .iter()
+        .zip(as_int32_array(&arrays[1])?)
+        .map(|(date, days)| Some(date?.wrapping_add(days?)))
+        .collect();
+    Ok(ColumnarValue::Array(Arc::new(dates)))
+}
+
+/// date_sub(start_date, num_days)
+pub fn spark_date_sub(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(args)?;
+    let dates: Date32Array = as_date32_array(&arrays[0])?
+        .iter()
+        .zip(as_int32_array(&arrays[1])?)
+        .map(|(date, days)| Some(date?.wrapping_sub(days?)))
+        .collect();
+    Ok(ColumnarValue::Array(Arc::new(dates)))
+}
+
+/// datediff(end_date, start_date)
+pub fn spark_date_diff(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(args)?;
+    let diffs: Int32Array = as_date32_array(&arrays[0])?
+        .iter()
+        .zip(as_date32_array(&arrays[1])?)
+        .map(|(end, start)| Some(end?.wrapping_sub(start?)))
+        .collect();
+    Ok(ColumnarValue::Array(Arc::new(diffs)))
+}
+
+/// add_months(start_date, num_months), the day of month is adjusted to the
+/// last day if it exceeds the resulting month
+pub fn spark_add_months(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(args)?;
+    let dates: Date32Array = as_date32_array(&arrays[0])?
+        .iter()
+        .zip(as_int32_array(&arrays[1])?)
+        .map(|(date, months)| {
+            let date = days_to_date(date?)?;
+            Some(date_to_days(&add_months(&date, months?)?))
+        })
+        .collect();
+    Ok(ColumnarValue::Array(Arc::new(dates)))
+}
+
+/// months_between(timestamp1, timestamp2, round_off, time_zone)
+pub fn spark_months_between(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(&args[..3])?;
+    let tz = time_zone_arg(&args[3])?;
+    let diffs: Float64Array = as_timestamp_microsecond_array(&arrays[0])?
+        .iter()
+        .zip(as_timestamp_microsecond_array(&arrays[1])?)
+        .zip(as_boolean_array(&arrays[2])?)
+        .map(|((micros1, micros2), round_off)| months_between(micros1?, micros2?, round_off?, &tz))
+        .collect();
+    Ok(ColumnarValue::Array(Arc::new(diffs)))
+}
+
+/// last_day(date)
+pub fn spark_last_day(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(args)?;
+    let dates: Date32Array = as_date32_array(&arrays[0])?
+        .iter()
+        .map(|date| {
+            let date = days_to_date(date?)?;
+            Some(date_to_days(&date.with_day(days_in_month(&date))?))
+        })
+        .collect();
+    Ok(ColumnarValue::Array(Arc::new(dates)))
+}
+
+/// next_day(start_date, day_of_week), returns null if day_of_week is invalid
+pub fn spark_next_day(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(args)?;
+    let dates: Date32Array = as_date32_array(&arrays[0])?
+        .iter()
+        .zip(as_string_array(&arrays[1])?)
+        .map(|(date, day_of_week)| {
+            let day_of_week = parse_day_of_week(day_of_week?)?;
+            let days_from_start = day_of_week.num_days_from_monday() as i32
+                - days_to_date(date?)?.weekday().num_days_from_monday() as i32;
+            Some(date? + (days_from_start + 6).rem_euclid(7) + 1)
+        })
+        .collect();
+    Ok(ColumnarValue::Array(Arc::new(dates)))
+}
+
+/// trunc(date, format), returns null if format is invalid or finer than week
+pub fn spark_trunc_date(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(args)?;
+    let dates: Date32Array = as_date32_array(&arrays[0])?
+        .iter()
+        .zip(as_string_array(&arrays[1])?)
+        .map(|(date, format)| {
+            let level = TruncLevel::parse(format?).filter(|&level| level >= TruncLevel::Week)?;
+            Some(date_to_days(&trunc_date(&days_to_date(date?)?, level)))
+        })
+        .collect();
+    Ok(ColumnarValue::Array(Arc::new(dates)))
+}
+
+/// date_trunc(format, timestamp, time_zone), returns null if format is invalid
+pub fn spark_trunc_timestamp(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(&args[..2])?;
+    let tz = time_zone_arg(&args[2])?;
+    let timestamps: TimestampMicrosecondArray = as_string_array(&arrays[0])?
+        .iter()
+        .zip(as_timestamp_microsecond_array(&arrays[1])?)
+        .map(|(format, micros)| trunc_timestamp(micros?, TruncLevel::parse(format?)?, &tz))
+        .collect();
+    Ok(ColumnarValue::Array(Arc::new(timestamps)))
+}
+
+/// hour(timestamp, time_zone)
+pub fn spark_hour(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    timestamp_field(args, |local| local.hour())
+}
+
+/// minute(timestamp, time_zone)
+pub fn spark_minute(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    timestamp_field(args, |local| local.minute())
+}
+
+/// second(timestamp, time_zone)
+pub fn spark_second(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    timestamp_field(args, |local| local.second())
+}
+
+/// dayofweek(date), 1 = Sunday, 2 = Monday, ..., 7 = Saturday
+pub fn spark_day_of_week(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    date_field(args, |date| date.weekday().number_from_sunday())
+}
+
+/// weekofyear(date), iso-8601 week number
+pub fn spark_week_of_year(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    date_field(args, |date| date.iso_week().week())
+}
+
+/// quarter(date)
+pub fn spark_quarter(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    date_field(args, |date| date.month0() / 3 + 1)
+}
+
+/// from_utc_timestamp(timestamp, time_zone), renders the utc timestamp as
+/// wall-clock time in the given time zone
+pub fn spark_from_utc_timestamp(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(args)?;
+    let mut time_zones = TimeZoneCache::default();
+    let timestamps = as_timestamp_microsecond_array(&arrays[0])?
+        .iter()
+        .zip(as_string_array(&arrays[1])?)
+        .map(|(micros, tz)| match (micros, tz) {
+            (Some(micros), Some(tz)) => Ok(time_zones
+                .get(tz)?
+                .micros_to_local(micros)
+                .map(|local| local.and_utc().timestamp_micros())),
+            _ => Ok(None),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(ColumnarValue::Array(Arc::new(
+        TimestampMicrosecondArray::from(timestamps).with_data_type(arrays[0].data_type().clone()),
+    )))
+}
+
+/// to_utc_timestamp(timestamp, time_zone), interprets the timestamp as
+/// wall-clock time in the given time zone and converts it to utc
+pub fn spark_to_utc_timestamp(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(args)?;
+    let mut time_zones = TimeZoneCache::default();
+    let timestamps = as_timestamp_microsecond_array(&arrays[0])?
+        .iter()
+        .zip(as_string_array(&arrays[1])?)
+        .map(|(micros, tz)| match (micros, tz) {
+            (Some(micros), Some(tz)) => {
+                let tz = time_zones.get(tz)?;
+                Ok(micros_to_naive(micros).and_then(|local| tz.local_to_micros(&local)))
+            }
+            _ => Ok(None),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(ColumnarValue::Array(Arc::new(
+        TimestampMicrosecondArray::from(timestamps).with_data_type(arrays[0].data_type().clone()),
+    )))
+}
+
+/// convert_timezone(source_time_zone, target_time_zone, timestamp_ntz),
+/// converts wall-clock time in the source time zone to the target time zone
+pub fn spark_convert_timezone(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(args)?;
+    let mut source_time_zones = TimeZoneCache::default();
+    let mut target_time_zones = TimeZoneCache::default();
+    let timestamps = as_string_array(&arrays[0])?
+        .iter()
+        .zip(as_string_array(&arrays[1])?)
+        .zip(as_timestamp_microsecond_array(&arrays[2])?)
+        .map(
+            |((source_tz, target_tz), micros)| match (source_tz, target_tz, micros) {
+                (Some(source_tz), Some(target_tz), Some(micros)) => {
+                    let source_tz = source_time_zones.get(source_tz)?;
+                    let target_tz = target_time_zones.get(target_tz)?;
+                    Ok(micros_to_naive(micros)
+                        .and_then(|local| source_tz.local_to_micros(&local))
+                        .and_then(|utc_micros| target_tz.micros_to_local(utc_micros))
+                        .map(|local| local.and_utc().timestamp_micros()))
+                }
+                _ => Ok(None),
+            },
+        )
+        .collect::<Result<Vec<_>>>()?;
+    Ok(ColumnarValue::Array(Arc::new(
+        TimestampMicrosecondArray::from(timestamps).with_data_type(arrays[2].data_type().clone()),
+    )))
+}
+
+fn args_to_arrays(args: &[ColumnarValue]) -> Result<Vec<ArrayRef>> {
+    let num_rows = args
+        .iter()
+        .find_map(|arg| match arg {
+            ColumnarValue::Array(array) => Some(array.len()),
+            ColumnarValue::Scalar(_) => None,
+        })
+        .unwrap_or(1);
+    args.iter()
+        .map(|arg| arg.clone().into_array(num_rows))
+        .collect()
+}
+
+fn time_zone_arg(arg: &ColumnarValue) -> Result<SparkTimeZone> {
+    match arg {
+        ColumnarValue::Scalar(ScalarValue::Utf8(Some(tz))) => SparkTimeZone::parse(tz),
+        _ => df_execution_err!("time zone must be a non-null literal string"),
+    }
+}
+
+/// Caches the latest parsed time zone, time zone arguments are usually
+/// literals or have few distinct values.
+#[derive(Default)]
+struct TimeZoneCache {
+    cached: Option<(String, SparkTimeZone)>,
+}
+
+impl TimeZoneCache {
+    fn get(&mut self, id: &str) -> Result<SparkTimeZone> {
+        match &self.cached {
+            Some((cached_id, tz)) if cached_id == id => Ok(*tz),
+            _ => {
+                let tz = SparkTimeZone::parse(id)?;
+                self.cached = Some((id.to_owned(), tz));
+                Ok(tz)
+            }
+        }
+    }
+}
+
+fn date_field(args: &[ColumnarValue], f: impl Fn(&NaiveDate) -> u32) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(args)?;
+    let fields: Int32Array = as_date32_array(&arrays[0])?
+        .iter()
+        .map(|date| Some(f(&days_to_date(date?)?) as i32))
+        .collect();
+    Ok(ColumnarValue::Array(Arc::new(fields)))
+}
+
+fn timestamp_field(
+    args: &[ColumnarValue],
+    f: impl Fn(&NaiveDateTime) -> u32,
+) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(&args[..1])?;
+    let tz = time_zone_arg(&args[1])?;
+    let fields: Int32Array = as_timestamp_microsecond_array(&arrays[0])?
+        .iter()
+        .map(|micros| Some(f(&tz.micros_to_local(micros?)?) as i32))
+        .collect();
+    Ok(ColumnarValue::Array(Arc::new(fields)))
+}
+
+const UNIX_EPOCH_DAYS_FROM_CE: i32 = 719163;
+const MICROS_PER_SECOND: i64 = 1_000_000;
+const SECONDS_PER_DAY: i64 = 86400;
+
+/// interprets timestamp as wall-clock time in utc
+fn micros_to_naive(micros: i64) -> Option<NaiveDateTime> {
+    Some(DateTime::from_timestamp_micros(micros)?.naive_utc())
+}
+
+pub(crate) fn days_to_date(days: i32) -> Option<NaiveDate> {
+    NaiveDate::from_num_days_from_ce_opt(days.checked_add(UNIX_EPOCH_DAYS_FROM_CE)?)
+}
+
+pub(crate) fn date_to_days(date: &NaiveDate) -> i32 {
+    date.num_days_from_ce() - UNIX_EPOCH_DAYS_FROM_CE
+}
+
+fn days_in_month(date: &NaiveDate) -> u32 {
+    let (year, month) = match date.month() {
+        12 => (date.year() + 1, 1),
+        month => (date.year(), month + 1),
+    };
+    NaiveDate::from_ymd_opt(year, month, 1)
+        .and_then(|next_month| next_month.pred_opt())
+        .map(|last_day| last_day.day())
+        .unwrap_or(31)
+}
+
+fn add_months(date: &NaiveDate, months: i32) -> Option<NaiveDate> {
+    let total_months = date.year() as i64 * 12 + date.month0() as i64 + months as i64;
+    let year = i32::try_from(total_months.div_euclid(12)).ok()?;
+    let month = total_months.rem_euclid(12) as u32 + 1;
+    let first_day = NaiveDate::from_ymd_opt(year, month, 1)?;
+    first_day.with_day(date.day().min(days_in_month(&first_day)))
+}
+
+/// same as DateTimeUtils.monthsBetween(): returns whole months if both dates
+/// have the same day of month or are both the last day of month, otherwise
+/// the difference is calculated assuming 31 days per month
+fn months_between(micros1: i64, micros2: i64, round_off: bool, tz: &SparkTimeZone) -> Option<f64> {
+    let date1 = tz.micros_to_local(micros1)?.date();
+    let date2 = tz.micros_to_local(micros2)?.date();
+    let months1 = date1.year() as i64 * 12 + date1.month() as i64;
+    let months2 = date2.year() as i64 * 12 + date2.month() as i64;
+    let month_diff = (months1 - months2) as f64;
+
+    let is_last_day = |date: &NaiveDate| date.day() == days_in_month(date);
+    if date1.day() == date2.day() || (is_last_day(&date1) && is_last_day(&date2)) {
+        return Some(month_diff);
+    }
+
+    // using seconds like hive to avoid precision loss
+    let seconds_in_day1 = (micros1 - tz.days_to_micros(date_to_days(&date1))?) / MICROS_PER_SECOND;
+    let seconds_in_day2 = (micros2 - tz.days_to_micros(date_to_days(&date2))?) / MICROS_PER_SECOND;
+    let seconds_diff = (date1.day() as i64 - date2.day() as i64) * SECONDS_PER_DAY
+        + seconds_in_day1
+        - seconds_in_day2;
+    let diff = month_diff + seconds_diff as f64 / (31 * SECONDS_PER_DAY) as f64;
+    if round_off {
+        // rounding to 8 digits
+        return Some((diff * 1e8 + 0.5).floor() / 1e8);
+    }
+    Some(diff)
+}
+
+fn parse_day_of_week(s: &str) -> Option<Weekday> {
+    Some(match s.trim().to_ascii_uppercase().as_str() {
+        "SU" | "SUN" | "SUNDAY" => Weekday::Sun,
+        "MO" | "MON" | "MONDAY" => Weekday::Mon,
+        "TU" | "TUE" | "TUESDAY" => Weekday::Tue,
+        "WE" | "WED" | "WEDNESDAY" => Weekday::Wed,
+        "TH" | "THU" | "THURSDAY" => Weekday::Thu,
+        "FR" | "FRI" | "FRIDAY" => Weekday::Fri,
+        "SA" | "SAT" | "SATURDAY" => Weekday::Sat,
+        _ => return None,
+    })
+}
+
+/// truncation levels supported by trunc() and date_trunc()
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum TruncLevel {
+    Microsecond,
+    Millisecond,
+    Second,
+    Minute,
+    Hour,
+    Day,
+    Week,
+    Month,
+    Quarter,
+    Year,
+}
+
+impl TruncLevel {
+    fn parse(format: &str) -> Option<Self> {
+        Some(match format.to_ascii_uppercase().as_str() {
+            "MICROSECOND" => TruncLevel::Microsecond,
+            "MILLISECOND" => TruncLevel::Millisecond,
+            "SECOND" => TruncLevel::Second,
+            "MINUTE" => TruncLevel::Minute,
+            "HOUR" => TruncLevel::Hour,
+            "DAY" | "DD" => TruncLevel::Day,
+            "WEEK" => TruncLevel::Week,
+            "MON" | "MONTH" | "MM" => TruncLevel::Month,
+            "QUARTER" => TruncLevel::Quarter,
+            "YEAR" | "YYYY" | "YY" => TruncLevel::Year,
+            _ => return None,
+        })
+    }
+}
+
+fn trunc_date(date: &NaiveDate, level: TruncLevel) -> NaiveDate {
+    let first_day_of_month =
+        |month0: u32| NaiveDate::from_ymd_opt(date.year(), month0 + 1, 1).expect("valid date");
+    match level {
+        TruncLevel::Week => *date - Duration::days(date.weekday().num_days_from_monday() as i64),
+        TruncLevel::Month => first_day_of_month(date.month0()),
+        TruncLevel::Quarter => first_day_of_month(date.month0() / 3 * 3),
+        TruncLevel::Year => first_day_of_month(0),
+        _ => *date,
+    }
+}
+
+/// same as DateTimeUtils.truncTimestamp(): units finer than a second are
+/// truncated on utc timestamps, other units are truncated on local time
+fn trunc_timestamp(micros: i64, level: TruncLevel, tz: &SparkTimeZone) -> Option<i64> {
+    let truncate_local = |f: fn(&NaiveDateTime) -> Option<NaiveDateTime>| {
+        let local = tz.micros_to_local(micros)?;
+        tz.local_to_micros_with_offset_hint(&f(&local)?, Some(tz.offset_of_micros(micros)?))
+    };
+    match level {
+        TruncLevel::Microsecond => Some(micros),
+        TruncLevel::Millisecond => Some(micros - micros.rem_euclid(1000)),
+        TruncLevel::Second => Some(micros - micros.rem_euclid(MICROS_PER_SECOND)),
+        TruncLevel::Minute => truncate_local(|local| local.with_second(0)?.with_nanosecond(0)),
+        TruncLevel::Hour => {
+            truncate_local(|local| local.with_minute(0)?.with_second(0)?.with_nanosecond(0))
+        }
+        TruncLevel::Day => truncate_local(|local| local.date().and_hms_opt(0, 0, 0)),
+        level => {
+            let date = tz.micros_to_local(micros)?.date();
+            tz.days_to_micros(date_to_days(&trunc_date(&date, level)))
+        }
+    }
+}
+
+/// Time zone resolved like java's ZoneId.of(), either a fixed offset or a
+/// region from the bundled tz database.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum SparkTimeZone {
+    Fixed(FixedOffset),
+    Region(Tz),
+}
+
+impl SparkTimeZone {
+    pub(crate) fn parse(id: &str) -> Result<Self> {
+        let resolved_id = java_short_id(id).unwrap_or(id);
+        let tz = if matches!(resolved_id, "Z" | "UTC" | "GMT" | "UT") {
+            FixedOffset::east_opt(0).map(SparkTimeZone::Fixed)
+        } else if let Some(offset) = ["UTC", "GMT", "UT", ""]
+            .iter()
+            .find_map(|prefix| resolved_id.strip_prefix(prefix))
+            .filter(|offset| offset.starts_with(['+', '-']))
+        {
+            parse_offset(offset).map(SparkTimeZone::Fixed)
+        } else {
+            resolved_id.parse::<Tz>().ok().map(SparkTimeZone::Region)
+        };
+        match tz {
+            Some(tz) => Ok(tz),
+            None => df_execution_err!("invalid time zone: {id}"),
+        }
+    }
+
+    pub(crate) fn offset_of_utc(&self, utc: &NaiveDateTime) -> FixedOffset {
+        match self {
+            SparkTimeZone::Fixed(offset) => *offset,
+            SparkTimeZone::Region(tz) => tz.offset_from_utc_datetime(utc).fix(),
+        }
+    }
+
+    pub(crate) fn offset_of_micros(&self, micros: i64) -> Option<FixedOffset> {
+        Some(self.offset_of_utc(&DateTime::from_timestamp_micros(micros)?.naive_utc()))
+    }
+
+    pub(crate) fn micros_to_local(&self, micros: i64) -> Option<NaiveDateTime> {
+        let utc = DateTime::from_timestamp_micros(micros)?.naive_utc();
+        let offset = self.offset_of_utc(&utc);
+        utc.checked_add_signed(Duration::seconds(offset.local_minus_utc() as i64))
+    }
+
+    /// resolves local time like java's ZonedDateTime.ofLocal(): the earlier
+    /// offset is used for overlaps, and local time in gaps is shifted later
+    /// by the length of the gap.
+    pub(crate) fn local_to_micros(&self, local: &NaiveDateTime) -> Option<i64> {
+        self.local_to_micros_with_offset_hint(local, None)
+    }
+
+    /// same as local_to_micros(), but the preferred offset is used for overlaps
+    /// if it is valid
+    pub(crate) fn local_to_micros_with_offset_hint(
+        &self,
+        local: &NaiveDateTime,
+        preferred_offset: Option<FixedOffset>,
+    ) -> Option<i64> {
+        let offset = match self {
+            SparkTimeZone::Fixed(offset) => *offset,
+            SparkTimeZone::Region(tz) => match tz.offset_from_local_datetime(local) {
+                chrono::LocalResult::Single(offset) => offset.fix(),
+                chrono::LocalResult::Ambiguous(offset1, offset2) => {
+                    let (offset1, offset2) = (offset1.fix(), offset2.fix());
+                    match preferred_offset {
+                        Some(preferred) if preferred == offset1 || preferred == offset2 => {
+                            preferred
+                        }
+                        _ => std::cmp::max_by_key(offset1, offset2, |o| o.local_minus_utc()),
+                    }
+                }
+                chrono::LocalResult::None => {
+                    // the offset before the gap
+                    self.offset_of_utc(&local.checked_sub_signed(Duration::days(1))?)
+                }
+            },
+        };
+        let utc = local.checked_sub_signed(Duration::seconds(offset.local_minus_utc() as i64))?;
+        Some(utc.and_utc().timestamp_micros())
+    }
+
+    /// converts days since epoch to the timestamp of start of the day
+    pub(crate) fn days_to_micros(&self, days: i32) -> Option<i64> {
+        self.local_to_micros(&days_to_date(days)?.and_hms_opt(0, 0, 0)?)
+    }
+}
+
+/// parses offset in forms of +h, +hh, +hh:mm, +hhmm, +hh:mm:ss, +hhmmss
+fn parse_offset(s: &str) -> Option<FixedOffset> {
+    let (sign, s) = match s.as_bytes().first()? {
+        b'+' => (1, &s[1..]),
+        b'-' => (-1, &s[1..]),
+        _ => return None,
+    };
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit() || b == b':') {
+        return None;
+    }
+    let fields = if s.contains(':') {
+        s.split(':').map(|f| f.to_owned()).collect::<Vec<_>>()
+    } else if s.len() <= 2 {
+        vec![s.to_owned()]
+    } else if s.len() % 2 == 0 {
+        s.as_bytes()
+            .chunks(2)
+            .map(|f| String::from_utf8_lossy(f).into_owned())
+            .collect()
+    } else {
+        return None;
+    };
+    // +hh:m is also accepted like spark's DateTimeUtils.getZoneId()
+    let is_short_minutes = fields.len() == 2 && fields[0].len() == 2 && fields[1].len() == 1;
+    if fields.len() > 3
+        || fields.iter().enumerate().any(|(i, f)| {
+            f.is_empty() || f.len() > 2 || (i > 0 && f.len() != 2 && !is_short_minutes)
+        })
+    {
+        return None;
+    }
+    let hours: i32 = fields[0].parse().ok()?;
+    let minutes: i32 = fields
+        .get(1)
+        .map(|f| f.parse())
+        .transpose()
+        .ok()?
+        .unwrap_or(0);
+    let seconds: i32 = fields
+        .get(2)
+        .map(|f| f.parse())
+        .transpose()
+        .ok()?
+        .unwrap_or(0);
+    if hours > 18 || minutes > 59 || seconds > 59 || (hours == 18 && minutes + seconds > 0) {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60 + seconds))
+}
+
+/// same as java's ZoneId.SHORT_IDS
+fn java_short_id(id: &str) -> Option<&'static str> {
+    Some(match id {
+        "ACT" => "Australia/Darwin",
+        "AET" => "Australia/Sydney",
+        "AGT" => "America/Argentina/Buenos_Aires",
+        "ART" => "Africa/Cairo",
+        "AST" => "America/Anchorage",
+        "BET" => "America/Sao_Paulo",
+        "BST" => "Asia/Dhaka",
+        "CAT" => "Africa/Harare",
+        "CNT" => "America/St_Johns",
+        "CST" => "America/Chicago",
+        "CTT" => "Asia/Shanghai",
+        "EAT" => "Africa/Addis_Ababa",
+        "ECT" => "Europe/Paris",
+        "IET" => "America/Indiana/Indianapolis",
+        "IST" => "Asia/Kolkata",
+        "JST" => "Asia/Tokyo",
+        "MIT" => "Pacific/Apia",
+        "NET" => "Asia/Yerevan",
+        "NST" => "Pacific/Auckland",
+        "PLT" => "Asia/Karachi",
+        "PNT" => "America/Phoenix",
+        "PRT" => "America/Puerto_Rico",
+        "PST" => "America/Los_Angeles",
+        "SST" => "Pacific/Guadalcanal",
+        "VST" => "Asia/Ho_Chi_Minh",
+        "EST" => "-05:00",
+        "MST" => "-07:00",
+        "HST" => "-10:00",
+        _ => return None,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use std::sync::Arc;
 
-    use arrow::array::{ArrayRef, Date32Array, Int32Array};
+    use arrow::array::{ArrayRef, Date32Array, Int32Array, StringArray};
 
     use super::*;
 
+    fn date(s: &str) -> i32 {
+        date_to_days(&NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap())
+    }
+
+    fn timestamp(s: &str, tz: &str) -> i64 {
+        let local = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap();
+        SparkTimeZone::parse(tz)
+            .unwrap()
+            .local_to_micros(&local)
+            .unwrap()
+    }
+
+    fn utf8(s: &str) -> ColumnarValue {
+        ColumnarValue::Scalar(ScalarValue::from(s))
+    }
+
     #[test]
     fn test_spark_year() {
         let input = Arc::new(Date32Array::from(vec![
@@ -95,4 +729,347 @@ mod tests {
             &expected_ret
         );
     }
+
+    #[test]
+    fn test_spark_date_add_sub_diff() {
+        let dates = ColumnarValue::Array(Arc::new(Date32Array::from(vec![
+            Some(date("2024-02-28")),
+            None,
+        ])));
+        let days = ColumnarValue::Scalar(ScalarValue::Int32(Some(2)));
+
+        let expected_ret: ArrayRef =
+            Arc::new(Date32Array::from(vec![Some(date("2024-03-01")), None]));
+        assert_eq!(
+            &spark_date_add(&[dates.clone(), days.clone()])
+                .unwrap()
+                .into_array(2)
+                .unwrap(),
+            &expected_ret
+        );
+        let expected_ret: ArrayRef =
+            Arc::new(Date32Array::from(vec![Some(date("2024-02-26")), None]));
+        assert_eq!(
+            &spark_date_sub(&[dates.clone(), days])
+                .unwrap()
+                .into_array(2)
+                .unwrap(),
+            &expected_ret
+        );
+
+        let start = ColumnarValue::Scalar(ScalarValue::Date32(Some(date("2023-12-31"))));
+        let expected_ret: ArrayRef = Arc::new(Int32Array::from(vec![Some(59), None]));
+        assert_eq!(
+            &spark_date_diff(&[dates, start])
+                .unwrap()
+                .into_array(2)
+                .unwrap(),
+            &expected_ret
+        );
+    }
+
+    #[test]
+    fn test_spark_add_months_and_last_day() {
+        let dates = ColumnarValue::Array(Arc::new(Date32Array::from(vec![
+            Some(date("2024-01-31")),
+            Some(date("2023-12-15")),
+            None,
+        ])));
+        let expected_ret: ArrayRef = Arc::new(Date32Array::from(vec![
+            Some(date("2024-02-29")),
+            Some(date("2024-01-15")),
+            None,
+        ]));
+        assert_eq!(
+            &spark_add_months(&[dates.clone(), ColumnarValue::Scalar(ScalarValue::from(1))])
+                .unwrap()
+                .into_array(3)
+                .unwrap(),
+            &expected_ret
+        );
+
+        let expected_ret: ArrayRef = Arc::new(Date32Array::from(vec![
+            Some(date("2024-01-31")),
+            Some(date("2023-12-31")),
+            None,
+        ]));
+        assert_eq!(
+            &spark_last_day(&[dates]).unwrap().into_array(3).unwrap(),
+            &expected_ret
+        );
+    }
+
+    #[test]
+    fn test_spark_months_between() {
+        let args = [
+            ColumnarValue::Scalar(ScalarValue::TimestampMicrosecond(
+                Some(timestamp("1997-02-28 10:30:00", "UTC")),
+                None,
+            )),
+            ColumnarValue::Scalar(ScalarValue::TimestampMicrosecond(
+                Some(timestamp("1996-10-30 00:00:00", "UTC")),
+                None,
+            )),
+            ColumnarValue::Scalar(ScalarValue::from(true)),
+            utf8("UTC"),
+        ];
+        let expected_ret: ArrayRef = Arc::new(Float64Array::from(vec![3.94959677]));
+        assert_eq!(
+            &spark_months_between(&args).unwrap().into_array(1).unwrap(),
+            &expected_ret
+        );
+    }
+
+    #[test]
+    fn test_spark_next_day() {
+        let dates = ColumnarValue::Array(Arc::new(Date32Array::from(vec![
+            Some(date("2015-01-14")), // Wednesday
+            Some(date("2015-01-15")), // Thursday
+            Some(date("2015-01-15")),
+        ])));
+        let day_of_week = ColumnarValue::Array(Arc::new(StringArray::from(vec![
+            Some("TU"),
+            Some("thursday"),
+            Some("xx"),
+        ])));
+        let expected_ret: ArrayRef = Arc::new(Date32Array::from(vec![
+            Some(date("2015-01-20")),
+            Some(date("2015-01-22")),
+            None,
+        ]));
+        assert_eq!(
+            &spark_next_day(&[dates, day_of_week])
+                .unwrap()
+                .into_array(3)
+                .unwrap(),
+            &expected_ret
+        );
+    }
+
+    #[test]
+    fn test_spark_trunc_date() {
+        let dates = ColumnarValue::Scalar(ScalarValue::Date32(Some(date("2019-08-04"))));
+        let formats = ColumnarValue::Array(Arc::new(StringArray::from(vec![
+            Some("week"),
+            Some("MM"),
+            Some("quarter"),
+            Some("YEAR"),
+            Some("DD"),
+            None,
+        ])));
+        let expected_ret: ArrayRef = Arc::new(Date32Array::from(vec![
+            Some(date("2019-07-29")),
+            Some(date("2019-08-01")),
+            Some(date("2019-07-01")),
+            Some(date("2019-01-01")),
+            None,
+            None,
+        ]));
+        assert_eq!(
+            &spark_trunc_date(&[dates, formats])
+                .unwrap()
+                .into_array(6)
+                .unwrap(),
+            &expected_ret
+        );
+    }
+
+    #[test]
+    fn test_spark_trunc_timestamp() {
+        let tz = "America/Los_Angeles";
+        let timestamps = ColumnarValue::Scalar(ScalarValue::TimestampMicrosecond(
+            Some(timestamp("2015-03-05 09:32:05", tz) + 359_000),
+            None,
+        ));
+        let formats = ColumnarValue::Array(Arc::new(StringArray::from(vec![
+            Some("YEAR"),
+            Some("MM"),
+            Some("DD"),
+            Some("HOUR"),
+            Some("MILLISECOND"),
+            Some("INVALID"),
+        ])));
+        let expected_ret: ArrayRef = Arc::new(TimestampMicrosecondArray::from(vec![
+            Some(timestamp("2015-01-01 00:00:00", tz)),
+            Some(timestamp("2015-03-01 00:00:00", tz)),
+            Some(timestamp("2015-03-05 00:00:00", tz)),
+            Some(timestamp("2015-03-05 09:00:00", tz)),
+            Some(timestamp("2015-03-05 09:32:05", tz) + 359_000),
+            None,
+        ]));
+        assert_eq!(
+            &spark_trunc_timestamp(&[formats, timestamps, utf8(tz)])
+                .unwrap()
+                .into_array(6)
+                .unwrap(),
+            &expected_ret
+        );
+    }
+
+    #[test]
+    fn test_spark_hour_minute_second() {
+        let timestamps = ColumnarValue::Array(Arc::new(TimestampMicrosecondArray::from(vec![
+            Some(timestamp("2024-07-01 12:34:56", "UTC")),
+            None,
+        ])));
+        let args = [timestamps, utf8("Asia/Shanghai")];
+        let expected_ret: ArrayRef = Arc::new(Int32Array::from(vec![Some(20), None]));
+        assert_eq!(
+            &spark_hour(&args).unwrap().into_array(2).unwrap(),
+            &expected_ret
+        );
+        let expected_ret: ArrayRef = Arc::new(Int32Array::from(vec![Some(34), None]));
+        assert_eq!(
+            &spark_minute(&args).unwrap().into_array(2).unwrap(),
+            &expected_ret
+        );
+        let expected_ret: ArrayRef = Arc::new(Int32Array::from(vec![Some(56), None]));
+        assert_eq!(
+            &spark_second(&args).unwrap().into_array(2).unwrap(),
+            &expected_ret
+        );
+    }
+
+    #[test]
+    fn test_spark_date_fields() {
+        let dates = ColumnarValue::Array(Arc::new(Date32Array::from(vec![
+            Some(date("2009-07-30")),
+            Some(date("2021-01-03")),
+            None,
+        ])));
+        let expected_ret: ArrayRef = Arc::new(Int32Array::from(vec![Some(5), Some(1), None]));
+        assert_eq!(
+            &spark_day_of_week(&[dates.clone()])
+                .unwrap()
+                .into_array(3)
+                .unwrap(),
+            &expected_ret
+        );
+        let expected_ret: ArrayRef = Arc::new(Int32Array::from(vec![Some(31), Some(53), None]));
+        assert_eq!(
+            &spark_week_of_year(&[dates.clone()])
+                .unwrap()
+                .into_array(3)
+                .unwrap(),
+            &expected_ret
+        );
+        let expected_ret: ArrayRef = Arc::new(Int32Array::from(vec![Some(3), Some(1), None]));
+        assert_eq!(
+            &spark_quarter(&[dates]).unwrap().into_array(3).unwrap(),
+            &expected_ret
+        );
+    }
+
+    #[test]
+    fn test_time_zone() {
+        let offset = |secs| SparkTimeZone::Fixed(FixedOffset::east_opt(secs).unwrap());
+        assert_eq!(SparkTimeZone::parse("UTC").unwrap(), offset(0));
+        assert_eq!(SparkTimeZone::parse("+08:00").unwrap(), offset(8 * 3600));
+        assert_eq!(SparkTimeZone::parse("GMT-5").unwrap(), offset(-5 * 3600));
+        assert_eq!(SparkTimeZone::parse("UTC+0530").unwrap(), offset(19800));
+        assert_eq!(SparkTimeZone::parse("EST").unwrap(), offset(-5 * 3600));
+        assert_eq!(
+            SparkTimeZone::parse("PST").unwrap(),
+            SparkTimeZone::Region(Tz::America__Los_Angeles)
+        );
+        assert!(SparkTimeZone::parse("Mars/Olympus").is_err());
+        assert!(SparkTimeZone::parse("+19:00").is_err());
+        assert_eq!(SparkTimeZone::parse("+8:00").unwrap(), offset(8 * 3600));
+        assert_eq!(
+            SparkTimeZone::parse("-08:3").unwrap(),
+            offset(-8 * 3600 - 180)
+        );
+
+        // local time in gap is shifted later, and overlap uses the earlier offset
+        let tz = SparkTimeZone::parse("America/Los_Angeles").unwrap();
+        assert_eq!(
+            timestamp("2024-03-10 02:30:00", "America/Los_Angeles"),
+            timestamp("2024-03-10 10:30:00", "UTC")
+        );
+        assert_eq!(
+            timestamp("2024-11-03 01:30:00", "America/Los_Angeles"),
+            timestamp("2024-11-03 08:30:00", "UTC")
+        );
+        assert_eq!(
+            tz.micros_to_local(timestamp("2024-11-03 09:30:00", "UTC"))
+                .unwrap()
+                .hour(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_spark_from_to_utc_timestamp() {
+        let timestamps = ColumnarValue::Array(Arc::new(TimestampMicrosecondArray::from(vec![
+            Some(timestamp("2016-08-31 00:00:00", "UTC")),
+            Some(timestamp("2024-03-10 02:30:00", "UTC")),
+            Some(timestamp("2024-11-03 01:30:00", "UTC")),
+            None,
+        ])));
+        let time_zones = ColumnarValue::Array(Arc::new(StringArray::from(vec![
+            Some("Asia/Seoul"),
+            Some("America/Los_Angeles"),
+            Some("America/Los_Angeles"),
+            Some("UTC"),
+        ])));
+
+        let expected_ret: ArrayRef = Arc::new(TimestampMicrosecondArray::from(vec![
+            Some(timestamp("2016-08-31 09:00:00", "UTC")),
+            Some(timestamp("2024-03-09 18:30:00", "UTC")),
+            Some(timestamp("2024-11-02 18:30:00", "UTC")),
+            None,
+        ]));
+        assert_eq!(
+            &spark_from_utc_timestamp(&[timestamps.clone(), time_zones.clone()])
+                .unwrap()
+                .into_array(4)
+                .unwrap(),
+            &expected_ret
+        );
+
+        // local time in gap is shifted later, and overlap uses the earlier offset
+        let expected_ret: ArrayRef = Arc::new(TimestampMicrosecondArray::from(vec![
+            Some(timestamp("2016-08-30 15:00:00", "UTC")),
+            Some(timestamp("2024-03-10 10:30:00", "UTC")),
+            Some(timestamp("2024-11-03 08:30:00", "UTC")),
+            None,
+        ]));
+        assert_eq!(
+            &spark_to_utc_timestamp(&[timestamps, time_zones])
+                .unwrap()
+                .into_array(4)
+                .unwrap(),
+            &expected_ret
+        );
+
+        let invalid_time_zone = utf8("Mars/Olympus");
+        let timestamps = ColumnarValue::Scalar(ScalarValue::TimestampMicrosecond(Some(0), None));
+        assert!(spark_from_utc_timestamp(&[timestamps, invalid_time_zone]).is_err());
+    }
+
+    #[test]
+    fn test_spark_convert_timezone() {
+        let timestamps = ColumnarValue::Array(Arc::new(TimestampMicrosecondArray::from(vec![
+            Some(timestamp("2021-12-06 00:00:00", "UTC")),
+            Some(timestamp("2024-03-10 10:30:00", "UTC")),
+            None,
+        ])));
+        let expected_ret: ArrayRef = Arc::new(TimestampMicrosecondArray::from(vec![
+            Some(timestamp("2021-12-05 15:00:00", "UTC")),
+            Some(timestamp("2024-03-10 03:30:00", "UTC")),
+            None,
+        ]));
+        let source_time_zones = ColumnarValue::Array(Arc::new(StringArray::from(vec![
+            Some("Europe/Brussels"),
+            Some("UTC"),
+            Some("UTC"),
+        ])));
+        assert_eq!(
+            &spark_convert_timezone(&[source_time_zones, utf8("America/Los_Angeles"), timestamps])
+                .unwrap()
+                .into_array(3)
+                .unwrap(),
+            &expected_ret
+        );
+    }
 }
@@ -0,0 +1,1675 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Datetime formatting and parsing with spark's datetime patterns, which are
+//! based on java's DateTimeFormatter (CORRECTED policy) or SimpleDateFormat
+//! (LEGACY policy).
+
+use std::sync::Arc;
+
+use arrow::{
+    array::{ArrayRef, Date32Array, Int64Array, StringArray, TimestampMicrosecondArray},
+    datatypes::{DataType, TimeUnit},
+};
+use chrono::{Datelike, Duration, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Timelike};
+use chrono_tz::OffsetName;
+use datafusion::{
+    common::{
+        cast::{as_date32_array, as_int64_array, as_string_array, as_timestamp_microsecond_array},
+        DataFusionError, Result, ScalarValue,
+    },
+    physical_plan::ColumnarValue,
+};
+use datafusion_ext_commons::df_execution_err;
+
+use crate::spark_dates::{date_to_days, SparkTimeZone};
+
+const MICROS_PER_SECOND: i64 = 1_000_000;
+
+/// date_format(timestamp, format, time_zone, time_parser_policy)
+pub fn spark_date_format(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(&args[..2])?;
+    let (tz, tz_id) = time_zone_arg(&args[2])?;
+    let mut formatters = FormatterCache::new(time_parser_policy_arg(&args[3])?, false);
+
+    let mut formatted = Vec::with_capacity(arrays[0].len());
+    for (micros, format) in as_timestamp_microsecond_array(&arrays[0])?
+        .iter()
+        .zip(as_string_array(&arrays[1])?)
+    {
+        formatted.push(match (micros, format) {
+            (Some(micros), Some(format)) => formatters.get(format)?.format(micros, &tz, tz_id),
+            _ => None,
+        });
+    }
+    Ok(ColumnarValue::Array(Arc::new(StringArray::from(formatted))))
+}
+
+/// from_unixtime(seconds, format, time_zone, time_parser_policy)
+pub fn spark_from_unixtime(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(&args[..2])?;
+    let (tz, tz_id) = time_zone_arg(&args[2])?;
+    let mut formatters = FormatterCache::new(time_parser_policy_arg(&args[3])?, false);
+
+    let mut formatted = Vec::with_capacity(arrays[0].len());
+    for (seconds, format) in as_int64_array(&arrays[0])?
+        .iter()
+        .zip(as_string_array(&arrays[1])?)
+    {
+        formatted.push(match (seconds, format) {
+            (Some(seconds), Some(format)) => match seconds.checked_mul(MICROS_PER_SECOND) {
+                Some(micros) => formatters.get(format)?.format(micros, &tz, tz_id),
+                None => None,
+            },
+            _ => None,
+        });
+    }
+    Ok(ColumnarValue::Array(Arc::new(StringArray::from(formatted))))
+}
+
+/// unix_timestamp(time, format, time_zone, time_parser_policy), returns
+/// seconds since epoch. format is only used for string inputs, and null is
+/// returned if the string cannot be parsed.
+pub fn spark_unix_timestamp(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(&args[..2])?;
+    let (tz, _) = time_zone_arg(&args[2])?;
+    let policy = time_parser_policy_arg(&args[3])?;
+
+    let to_seconds =
+        |micros: Option<i64>| micros.map(|micros| micros.div_euclid(MICROS_PER_SECOND));
+    let seconds: Int64Array = match arrays[0].data_type() {
+        DataType::Timestamp(TimeUnit::Microsecond, _) => {
+            as_timestamp_microsecond_array(&arrays[0])?
+                .iter()
+                .map(to_seconds)
+                .collect()
+        }
+        DataType::Date32 => as_date32_array(&arrays[0])?
+            .iter()
+            .map(|days| to_seconds(tz.days_to_micros(days?)))
+            .collect(),
+        DataType::Utf8 => parse_timestamps(&arrays[0], &arrays[1], &tz, policy)?
+            .into_iter()
+            .map(to_seconds)
+            .collect(),
+        other => df_execution_err!("unix_timestamp: unsupported input type: {other}")?,
+    };
+    Ok(ColumnarValue::Array(Arc::new(seconds)))
+}
+
+/// to_timestamp(str, format, time_zone, time_parser_policy), returns null if
+/// the string cannot be parsed
+pub fn spark_get_timestamp(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(&args[..2])?;
+    let (tz, _) = time_zone_arg(&args[2])?;
+    let policy = time_parser_policy_arg(&args[3])?;
+
+    let timestamps = parse_timestamps(&arrays[0], &arrays[1], &tz, policy)?;
+    Ok(ColumnarValue::Array(Arc::new(
+        TimestampMicrosecondArray::from(timestamps),
+    )))
+}
+
+/// to_date(str, format, time_zone, time_parser_policy), returns null if the
+/// string cannot be parsed
+pub fn spark_parse_to_date(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(&args[..2])?;
+    let (tz, _) = time_zone_arg(&args[2])?;
+    let policy = time_parser_policy_arg(&args[3])?;
+
+    let dates: Date32Array = parse_timestamps(&arrays[0], &arrays[1], &tz, policy)?
+        .into_iter()
+        .map(|micros| Some(date_to_days(&tz.micros_to_local(micros?)?.date())))
+        .collect();
+    Ok(ColumnarValue::Array(Arc::new(dates)))
+}
+
+fn parse_timestamps(
+    strs: &ArrayRef,
+    formats: &ArrayRef,
+    tz: &SparkTimeZone,
+    policy: TimeParserPolicy,
+) -> Result<Vec<Option<i64>>> {
+    let mut formatters = FormatterCache::new(policy, true);
+    let mut timestamps = Vec::with_capacity(strs.len());
+    for (s, format) in as_string_array(strs)?.iter().zip(as_string_array(formats)?) {
+        timestamps.push(match (s, format) {
+            (Some(s), Some(format)) => formatters.get(format)?.parse(s, tz)?,
+            _ => None,
+        });
+    }
+    Ok(timestamps)
+}
+
+fn args_to_arrays(args: &[ColumnarValue]) -> Result<Vec<ArrayRef>> {
+    let num_rows = args
+        .iter()
+        .find_map(|arg| match arg {
+            ColumnarValue::Array(array) => Some(array.len()),
+            ColumnarValue::Scalar(_) => None,
+        })
+        .unwrap_or(1);
+    args.iter()
+        .map(|arg| arg.clone().into_array(num_rows))
+        .collect()
+}
+
+fn time_zone_arg(arg: &ColumnarValue) -> Result<(SparkTimeZone, &str)> {
+    match arg {
+        ColumnarValue::Scalar(ScalarValue::Utf8(Some(tz))) => Ok((SparkTimeZone::parse(tz)?, tz)),
+        _ => df_execution_err!("time zone must be a non-null literal string"),
+    }
+}
+
+fn time_parser_policy_arg(arg: &ColumnarValue) -> Result<TimeParserPolicy> {
+    match arg {
+        ColumnarValue::Scalar(ScalarValue::Utf8(Some(policy))) => {
+            match policy.to_ascii_uppercase().as_str() {
+                "LEGACY" => Ok(TimeParserPolicy::Legacy),
+                "CORRECTED" => Ok(TimeParserPolicy::Corrected),
+                "EXCEPTION" => Ok(TimeParserPolicy::Exception),
+                _ => df_execution_err!("invalid time parser policy: {policy}"),
+            }
+        }
+        _ => df_execution_err!("time parser policy must be a non-null literal string"),
+    }
+}
+
+/// Same as spark.sql.legacy.timeParserPolicy
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeParserPolicy {
+    /// use SimpleDateFormat semantics like spark 2.x
+    Legacy,
+    /// use DateTimeFormatter semantics
+    Corrected,
+    /// same as corrected, but raises an error if the result differs from legacy
+    Exception,
+}
+
+/// Caches the formatter of the latest pattern, which is usually a literal.
+struct FormatterCache {
+    policy: TimeParserPolicy,
+    for_parsing: bool,
+    cached: Option<(String, DateTimeFormatter)>,
+}
+
+impl FormatterCache {
+    fn new(policy: TimeParserPolicy, for_parsing: bool) -> Self {
+        Self {
+            policy,
+            for_parsing,
+            cached: None,
+        }
+    }
+
+    fn get(&mut self, pattern: &str) -> Result<&DateTimeFormatter> {
+        if !matches!(&self.cached, Some((cached_pattern, _)) if cached_pattern == pattern) {
+            let formatter = DateTimeFormatter::try_new(pattern, self.policy, self.for_parsing)?;
+            self.cached = Some((pattern.to_owned(), formatter));
+        }
+        Ok(&self.cached.as_ref().expect("cached formatter").1)
+    }
+}
+
+/// Formatter/parser of a datetime pattern under the specified parser policy.
+pub struct DateTimeFormatter {
+    pattern: String,
+    policy: TimeParserPolicy,
+    corrected: Option<Pattern>,
+    legacy: Option<Pattern>,
+}
+
+impl DateTimeFormatter {
+    pub fn try_new(pattern: &str, policy: TimeParserPolicy, for_parsing: bool) -> Result<Self> {
+        let (corrected, legacy) = match policy {
+            TimeParserPolicy::Legacy => (None, Some(Pattern::try_new(pattern, true, for_parsing)?)),
+            TimeParserPolicy::Corrected => {
+                (Some(Pattern::try_new(pattern, false, for_parsing)?), None)
+            }
+            TimeParserPolicy::Exception => {
+                let legacy = Pattern::try_new(pattern, true, for_parsing).ok();
+                match Pattern::try_new(pattern, false, for_parsing) {
+                    Ok(corrected) => (Some(corrected), legacy),
+                    Err(_) if legacy.is_some() => {
+                        return Err(DataFusionError::Execution(format!(
+                            "You may get a different result due to the upgrading to Spark >= \
+                             3.0: Fail to recognize '{pattern}' pattern in the \
+                             DateTimeFormatter. 1) You can set \
+                             spark.sql.legacy.timeParserPolicy to LEGACY to restore the \
+                             behavior before Spark 3.0. 2) You can form a valid datetime pattern \
+                             with the guide from \
+                             https://spark.apache.org/docs/latest/sql-ref-datetime-pattern.html"
+                        )));
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+        };
+        Ok(Self {
+            pattern: pattern.to_owned(),
+            policy,
+            corrected,
+            legacy,
+        })
+    }
+
+    /// formats timestamp in the specified time zone, returns None if the
+    /// timestamp is out of range
+    pub fn format(&self, micros: i64, tz: &SparkTimeZone, tz_id: &str) -> Option<String> {
+        let pattern = self.corrected.as_ref().or(self.legacy.as_ref())?;
+        let offset = tz.offset_of_micros(micros)?;
+        let zone_name = match tz {
+            SparkTimeZone::Fixed(_) => tz_id.to_owned(),
+            SparkTimeZone::Region(region) => {
+                let utc = chrono::DateTime::from_timestamp_micros(micros)?.naive_utc();
+                region
+                    .offset_from_utc_datetime(&utc)
+                    .abbreviation()
+                    .to_owned()
+            }
+        };
+        let ctx = FormatContext {
+            local: tz.micros_to_local(micros)?,
+            offset_seconds: offset.local_minus_utc(),
+            zone_id: tz_id,
+            zone_name: &zone_name,
+        };
+        let mut formatted = String::new();
+        pattern.format(&mut formatted, &pattern.items, &ctx)?;
+        Some(formatted)
+    }
+
+    /// parses string into timestamp, the specified time zone is used if the
+    /// string contains no time zone. returns None if the string cannot be
+    /// parsed, or error if the result differs from legacy under EXCEPTION
+    /// policy.
+    pub fn parse(&self, s: &str, tz: &SparkTimeZone) -> Result<Option<i64>> {
+        let input = s.chars().collect::<Vec<_>>();
+        match self.policy {
+            TimeParserPolicy::Legacy => Ok(self.legacy.as_ref().and_then(|p| p.parse(&input, tz))),
+            TimeParserPolicy::Corrected => {
+                Ok(self.corrected.as_ref().and_then(|p| p.parse(&input, tz)))
+            }
+            TimeParserPolicy::Exception => {
+                let parsed = self.corrected.as_ref().and_then(|p| p.parse(&input, tz));
+                if parsed.is_none()
+                    && let Some(legacy) = &self.legacy
+                    && legacy.parse(&input, tz).is_some()
+                {
+                    return df_execution_err!(
+                        "You may get a different result due to the upgrading to Spark >= 3.0: \
+                         Fail to parse '{s}' in the new parser. You can set \
+                         spark.sql.legacy.timeParserPolicy to LEGACY to restore the behavior \
+                         before Spark 3.0, or set to CORRECTED and treat it as an invalid \
+                         datetime string. (pattern: '{}')",
+                        self.pattern,
+                    );
+                }
+                Ok(parsed)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum PatternItem {
+    Literal(String),
+    Field(char, usize),
+    Optional(Vec<PatternItem>),
+}
+
+/// A parsed datetime pattern, either in DateTimeFormatter or SimpleDateFormat
+/// semantics.
+#[derive(Debug)]
+struct Pattern {
+    items: Vec<PatternItem>,
+    legacy: bool,
+    has_era: bool,
+}
+
+struct FormatContext<'a> {
+    local: NaiveDateTime,
+    offset_seconds: i32,
+    zone_id: &'a str,
+    zone_name: &'a str,
+}
+
+/// Fields parsed from a datetime string, resolved into a timestamp after the
+/// whole pattern is parsed.
+#[derive(Clone, Debug, Default)]
+struct Parsed {
+    year: Option<i64>,
+    era: Option<u32>,
+    month: Option<u32>,
+    day: Option<u32>,
+    day_of_year: Option<u32>,
+    hour_of_day: Option<u32>,
+    hour_of_ampm: Option<u32>,
+    ampm: Option<u32>,
+    minute: Option<u32>,
+    second: Option<u32>,
+    nano: Option<u32>,
+    millis: Option<i64>,
+    offset_seconds: Option<i32>,
+    zone: Option<SparkTimeZone>,
+}
+
+const MONTH_NAMES: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+const SHORT_MONTH_NAMES: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+const WEEKDAY_NAMES: [&str; 7] = [
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+];
+const SHORT_WEEKDAY_NAMES: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+const AMPM_NAMES: [&str; 2] = ["AM", "PM"];
+const ERA_NAMES: [&str; 2] = ["BC", "AD"];
+const LONG_ERA_NAMES: [&str; 2] = ["Before Christ", "Anno Domini"];
+
+/// styles of offset patterns, e.g. `+HHmm` means hours with optional minutes
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum OffsetStyle {
+    HHmm,
+    HHMM,
+    HHColonMM,
+    HHMMss,
+    HHColonMMss,
+}
+
+impl Pattern {
+    fn try_new(pattern: &str, legacy: bool, for_parsing: bool) -> Result<Self> {
+        let items = match parse_pattern(pattern, legacy, for_parsing) {
+            Ok(items) => items,
+            Err(err) => return df_execution_err!("{err}"),
+        };
+        let has_era = pattern_has_field(&items, 'G');
+        Ok(Self {
+            items,
+            legacy,
+            has_era,
+        })
+    }
+
+    fn format(&self, out: &mut String, items: &[PatternItem], ctx: &FormatContext) -> Option<()> {
+        for item in items {
+            match item {
+                PatternItem::Literal(s) => out.push_str(s),
+                PatternItem::Optional(items) => self.format(out, items, ctx)?,
+                PatternItem::Field(c, n) if self.legacy => format_legacy_field(out, *c, *n, ctx)?,
+                PatternItem::Field(c, n) => format_field(out, *c, *n, ctx, self.has_era)?,
+            }
+        }
+        Some(())
+    }
+
+    fn parse(&self, input: &[char], tz: &SparkTimeZone) -> Option<i64> {
+        let mut parsed = Parsed::default();
+        if self.legacy {
+            // SimpleDateFormat ignores trailing text
+            self.parse_legacy_items(&self.items, input, 0, &mut parsed)?;
+            return resolve_legacy(&parsed, tz);
+        }
+        let pos = self.parse_items(&self.items, input, 0, &mut parsed)?;
+        if pos < input.len() {
+            return None;
+        }
+        self.resolve(&parsed, tz)
+    }
+
+    /// parses like DateTimeFormatter with case-insensitive and strict mode
+    fn parse_items(
+        &self,
+        items: &[PatternItem],
+        input: &[char],
+        mut pos: usize,
+        parsed: &mut Parsed,
+    ) -> Option<usize> {
+        for (i, item) in items.iter().enumerate() {
+            match item {
+                PatternItem::Literal(s) => {
+                    for c in s.chars() {
+                        if !input.get(pos)?.eq_ignore_ascii_case(&c) {
+                            return None;
+                        }
+                        pos += 1;
+                    }
+                }
+                PatternItem::Optional(optional_items) => {
+                    let mut optional_parsed = parsed.clone();
+                    if let Some(new_pos) =
+                        self.parse_items(optional_items, input, pos, &mut optional_parsed)
+                    {
+                        *parsed = optional_parsed;
+                        pos = new_pos;
+                    }
+                }
+                PatternItem::Field(c, n) => {
+                    // adjacent value parsing: a variable-width number followed by
+                    // fixed-width numbers reserves their widths
+                    let reserved_width = items[i + 1..]
+                        .iter()
+                        .map_while(|item| match item {
+                            PatternItem::Field(c, n) => match numeric_width(*c, *n) {
+                                Some((min, max)) if min == max => Some(max),
+                                _ => None,
+                            },
+                            _ => None,
+                        })
+                        .sum();
+                    pos = self.parse_field(*c, *n, input, pos, reserved_width, parsed)?;
+                }
+            }
+        }
+        Some(pos)
+    }
+
+    fn parse_field(
+        &self,
+        c: char,
+        n: usize,
+        input: &[char],
+        pos: usize,
+        reserved_width: usize,
+        parsed: &mut Parsed,
+    ) -> Option<usize> {
+        let number = |pos| {
+            let (min, max) = numeric_width(c, n)?;
+            if min == max {
+                parse_digits(input, pos, min, max)
+            } else {
+                parse_digits(input, pos, min, max + reserved_width)
+                    .map(|(_, end)| (end - pos).saturating_sub(reserved_width).max(min))
+                    .and_then(|len| parse_digits(input, pos, min, len))
+            }
+        };
+
+        let (value, pos) = match c {
+            'y' if n == 2 => {
+                let (value, pos) = number(pos)?;
+                (2000 + value, pos)
+            }
+            'y' => {
+                let (sign, digits_pos) = match input.get(pos) {
+                    Some('-') => (-1, pos + 1),
+                    Some('+') if n >= 4 => (1, pos + 1),
+                    _ => (1, pos),
+                };
+                let (value, end) = number(digits_pos)?;
+                let num_digits = end - digits_pos;
+                if n >= 4 {
+                    // sign must be present if and only if the pad width is exceeded
+                    let has_plus = input.get(pos) == Some(&'+');
+                    if (has_plus && num_digits <= n) || (!has_plus && sign > 0 && num_digits > n) {
+                        return None;
+                    }
+                }
+                if self.has_era && sign < 0 {
+                    return None;
+                }
+                (sign * value, end)
+            }
+            'G' => {
+                let names = if n == 4 { LONG_ERA_NAMES } else { ERA_NAMES };
+                let (index, pos) = parse_text(input, pos, &names)?;
+                (index as i64, pos)
+            }
+            'M' | 'L' if n >= 3 => {
+                let names = if n == 4 {
+                    MONTH_NAMES
+                } else {
+                    SHORT_MONTH_NAMES
+                };
+                let (index, pos) = parse_text(input, pos, &names)?;
+                (index as i64 + 1, pos)
+            }
+            'a' => {
+                let (index, pos) = parse_text(input, pos, &AMPM_NAMES)?;
+                (index as i64, pos)
+            }
+            'S' => {
+                let (value, end) = parse_digits(input, pos, 1, n)?;
+                (value * 10i64.pow(9 - (end - pos) as u32), end)
+            }
+            'V' | 'z' => {
+                let (zone, pos) = parse_zone(input, pos)?;
+                set_field(&mut parsed.zone, zone)?;
+                return Some(pos);
+            }
+            'O' | 'X' | 'x' | 'Z' => {
+                let (offset, pos) = parse_offset_field(c, n, input, pos)?;
+                set_field(&mut parsed.offset_seconds, offset)?;
+                return Some(pos);
+            }
+            _ => number(pos)?,
+        };
+
+        let value32 = u32::try_from(value).ok();
+        match c {
+            'y' => set_field(&mut parsed.year, value)?,
+            'G' => set_field(&mut parsed.era, value32?)?,
+            'M' | 'L' => set_field(&mut parsed.month, value32.filter(|v| (1..=12).contains(v))?)?,
+            'd' => set_field(&mut parsed.day, value32.filter(|v| (1..=31).contains(v))?)?,
+            'D' => set_field(
+                &mut parsed.day_of_year,
+                value32.filter(|v| (1..=366).contains(v))?,
+            )?,
+            'H' => set_field(&mut parsed.hour_of_day, value32.filter(|&v| v < 24)?)?,
+            'k' => set_field(
+                &mut parsed.hour_of_day,
+                value32.filter(|v| (1..=24).contains(v))? % 24,
+            )?,
+            'h' => set_field(
+                &mut parsed.hour_of_ampm,
+                value32.filter(|v| (1..=12).contains(v))? % 12,
+            )?,
+            'K' => set_field(&mut parsed.hour_of_ampm, value32.filter(|&v| v < 12)?)?,
+            'a' => set_field(&mut parsed.ampm, value32?)?,
+            'm' => set_field(&mut parsed.minute, value32.filter(|&v| v < 60)?)?,
+            's' => set_field(&mut parsed.second, value32.filter(|&v| v < 60)?)?,
+            'S' => set_field(&mut parsed.nano, value32?)?,
+            _ => return None,
+        }
+        Some(pos)
+    }
+
+    /// resolves parsed fields like spark's Iso8601TimestampFormatter: missing
+    /// date fields default to 1970-01-01, missing time fields default to zero,
+    /// and invalid dates are rejected.
+    fn resolve(&self, parsed: &Parsed, tz: &SparkTimeZone) -> Option<i64> {
+        let year = match parsed.year {
+            Some(year_of_era) if self.has_era && parsed.era == Some(0) => 1 - year_of_era,
+            Some(year) => year,
+            None => 1970,
+        };
+        let year = i32::try_from(year).ok()?;
+        let date = match parsed.day_of_year {
+            Some(day_of_year) => {
+                let date = NaiveDate::from_yo_opt(year, day_of_year)?;
+                if parsed.month.is_some_and(|month| month != date.month())
+                    || parsed.day.is_some_and(|day| day != date.day())
+                {
+                    return None;
+                }
+                date
+            }
+            None => {
+                NaiveDate::from_ymd_opt(year, parsed.month.unwrap_or(1), parsed.day.unwrap_or(1))?
+            }
+        };
+
+        // hour of am/pm without am/pm is assumed to be am
+        let hour = match (parsed.hour_of_day, parsed.hour_of_ampm, parsed.ampm) {
+            (Some(hour), Some(hour_of_ampm), _) if hour % 12 != hour_of_ampm => return None,
+            (Some(hour), _, Some(ampm)) if hour / 12 != ampm => return None,
+            (Some(hour), ..) => hour,
+            (None, Some(hour_of_ampm), ampm) => hour_of_ampm + 12 * ampm.unwrap_or(0),
+            (None, None, _) => 0,
+        };
+        let time = NaiveTime::from_hms_nano_opt(
+            hour,
+            parsed.minute.unwrap_or(0),
+            parsed.second.unwrap_or(0),
+            parsed.nano.unwrap_or(0),
+        )?;
+        to_micros(&date.and_time(time), parsed, tz)
+    }
+
+    /// parses like lenient SimpleDateFormat
+    fn parse_legacy_items(
+        &self,
+        items: &[PatternItem],
+        input: &[char],
+        mut pos: usize,
+        parsed: &mut Parsed,
+    ) -> Option<usize> {
+        for (i, item) in items.iter().enumerate() {
+            match item {
+                PatternItem::Literal(s) => {
+                    for c in s.chars() {
+                        if input.get(pos) != Some(&c) {
+                            return None;
+                        }
+                        pos += 1;
+                    }
+                }
+                PatternItem::Optional(_) => unreachable!("legacy pattern has no optional section"),
+                PatternItem::Field(c, n) => {
+                    // spaces and tabs before fields are skipped
+                    while matches!(input.get(pos), Some(' ' | '\t')) {
+                        pos += 1;
+                    }
+                    // numeric fields abutting with the next numeric field use their
+                    // pattern lengths as widths
+                    let obey_count = matches!(
+                        items.get(i + 1),
+                        Some(PatternItem::Field(next_c, next_n))
+                            if is_legacy_numeric_field(*next_c, *next_n)
+                    );
+                    pos = parse_legacy_field(*c, *n, input, pos, obey_count, parsed)?;
+                }
+            }
+        }
+        Some(pos)
+    }
+}
+
+/// parses pattern into items, validates pattern letters like spark's
+/// DateTimeFormatterHelper (or SimpleDateFormat for legacy patterns).
+fn parse_pattern(
+    pattern: &str,
+    legacy: bool,
+    for_parsing: bool,
+) -> std::result::Result<Vec<PatternItem>, String> {
+    let chars = pattern.chars().collect::<Vec<_>>();
+    let mut stack: Vec<Vec<PatternItem>> = vec![vec![]];
+    let push_literal = |stack: &mut Vec<Vec<PatternItem>>, c: char| {
+        let items = stack.last_mut().expect("non-empty stack");
+        match items.last_mut() {
+            Some(PatternItem::Literal(s)) => s.push(c),
+            _ => items.push(PatternItem::Literal(c.to_string())),
+        }
+    };
+
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_ascii_alphabetic() {
+            let n = chars[i..].iter().take_while(|&&ch| ch == c).count();
+            if legacy {
+                validate_legacy_field(c)?;
+            } else {
+                validate_field(c, n, for_parsing)?;
+            }
+            stack
+                .last_mut()
+                .expect("non-empty stack")
+                .push(PatternItem::Field(c, n));
+            i += n;
+        } else if c == '\'' {
+            // quoted literal, '' is an escaped quote
+            i += 1;
+            if chars.get(i) == Some(&'\'') {
+                push_literal(&mut stack, '\'');
+                i += 1;
+                continue;
+            }
+            loop {
+                match chars.get(i) {
+                    Some('\'') if chars.get(i + 1) == Some(&'\'') => {
+                        push_literal(&mut stack, '\'');
+                        i += 2;
+                    }
+                    Some('\'') => {
+                        i += 1;
+                        break;
+                    }
+                    Some(&ch) => {
+                        push_literal(&mut stack, ch);
+                        i += 1;
+                    }
+                    None => {
+                        return Err(format!(
+                            "Pattern ends with an incomplete string literal: {pattern}"
+                        ));
+                    }
+                }
+            }
+        } else if !legacy && c == '[' {
+            stack.push(vec![]);
+            i += 1;
+        } else if !legacy && c == ']' {
+            if stack.len() == 1 {
+                return Err(format!(
+                    "Pattern invalid as it contains ] without previous [: {pattern}"
+                ));
+            }
+            let optional_items = stack.pop().expect("non-empty stack");
+            stack
+                .last_mut()
+                .expect("non-empty stack")
+                .push(PatternItem::Optional(optional_items));
+            i += 1;
+        } else if !legacy && matches!(c, '{' | '}' | '#') {
+            return Err(format!("Pattern includes reserved character: '{c}'"));
+        } else {
+            push_literal(&mut stack, c);
+            i += 1;
+        }
+    }
+
+    // unclosed optional sections are closed at the end of pattern
+    while stack.len() > 1 {
+        let optional_items = stack.pop().expect("non-empty stack");
+        stack
+            .last_mut()
+            .expect("non-empty stack")
+            .push(PatternItem::Optional(optional_items));
+    }
+    Ok(stack.pop().expect("non-empty stack"))
+}
+
+fn validate_field(c: char, n: usize, for_parsing: bool) -> std::result::Result<(), String> {
+    if "YWwuec".contains(c) {
+        return Err(format!(
+            "All week-based patterns are unsupported since Spark 3.0, detected: {c}, Please use \
+             the SQL function EXTRACT instead"
+        ));
+    }
+    if "ABnNp".contains(c) || (for_parsing && "EFqQ".contains(c)) {
+        return Err(format!("Illegal pattern character: {c}"));
+    }
+    let max_count = match c {
+        // narrow text forms and years longer than 10 are disabled by spark
+        'G' | 'M' | 'L' | 'E' | 'Q' | 'q' => 4,
+        'y' => 10,
+        'd' | 'H' | 'h' | 'K' | 'k' | 'm' | 's' => 2,
+        'D' => 3,
+        'a' | 'F' => 1,
+        'S' => 9,
+        'V' => 2,
+        'O' => 4,
+        'X' | 'x' | 'Z' => 5,
+        'z' => 4,
+        _ => return Err(format!("Unknown pattern letter: {c}")),
+    };
+    if n > max_count {
+        return Err(format!("Too many pattern letters: {c}"));
+    }
+    match c {
+        'V' if n != 2 => Err("Pattern letter count must be 2: V".to_owned()),
+        'O' if n != 1 && n != 4 => Err("Pattern letter count must be 1 or 4: O".to_owned()),
+        'z' if n == 4 => Err("Full time zone names (zzzz) are not supported".to_owned()),
+        _ => Ok(()),
+    }
+}
+
+fn validate_legacy_field(c: char) -> std::result::Result<(), String> {
+    if !"GyYMLwWDdFEuaHkKhmsSzZX".contains(c) {
+        return Err(format!("Illegal pattern character '{c}'"));
+    }
+    Ok(())
+}
+
+fn pattern_has_field(items: &[PatternItem], c: char) -> bool {
+    items.iter().any(|item| match item {
+        PatternItem::Field(field_c, _) => *field_c == c,
+        PatternItem::Optional(items) => pattern_has_field(items, c),
+        PatternItem::Literal(_) => false,
+    })
+}
+
+/// returns (min_width, max_width) of numeric fields
+fn numeric_width(c: char, n: usize) -> Option<(usize, usize)> {
+    match (c, n) {
+        ('y', 2) => Some((2, 2)),
+        ('y', n) => Some((n, 19)),
+        ('M' | 'L' | 'Q' | 'q' | 'd' | 'H' | 'h' | 'K' | 'k' | 'm' | 's', 1) => Some((1, 19)),
+        ('M' | 'L' | 'Q' | 'q' | 'd' | 'H' | 'h' | 'K' | 'k' | 'm' | 's', 2) => Some((2, 2)),
+        ('D', 1) | ('F', 1) => Some((1, 19)),
+        ('D', 2) => Some((2, 3)),
+        ('D', 3) => Some((3, 3)),
+        _ => None,
+    }
+}
+
+fn is_legacy_numeric_field(c: char, n: usize) -> bool {
+    match c {
+        'M' | 'L' => n < 3,
+        'y' | 'Y' | 'w' | 'W' | 'D' | 'd' | 'F' | 'u' | 'H' | 'k' | 'K' | 'h' | 'm' | 's' | 'S' => {
+            true
+        }
+        _ => false,
+    }
+}
+
+fn set_field<T: PartialEq>(field: &mut Option<T>, value: T) -> Option<()> {
+    match field {
+        Some(existing) if *existing != value => None,
+        _ => {
+            *field = Some(value);
+            Some(())
+        }
+    }
+}
+
+/// parses min..=max ascii digits
+fn parse_digits(input: &[char], pos: usize, min: usize, max: usize) -> Option<(i64, usize)> {
+    let len = input[pos.min(input.len())..]
+        .iter()
+        .take(max)
+        .take_while(|c| c.is_ascii_digit())
+        .count();
+    if len < min || len == 0 || len > 18 {
+        return None;
+    }
+    let value = input[pos..pos + len]
+        .iter()
+        .fold(0i64, |value, c| value * 10 + c.to_digit(10).unwrap() as i64);
+    Some((value, pos + len))
+}
+
+/// parses case-insensitive text, returns index of the longest matched name
+fn parse_text(input: &[char], pos: usize, names: &[&str]) -> Option<(usize, usize)> {
+    names
+        .iter()
+        .enumerate()
+        .filter(|(_, name)| {
+            let len = name.chars().count();
+            input.len() >= pos + len
+                && input[pos..pos + len]
+                    .iter()
+                    .zip(name.chars())
+                    .all(|(a, b)| a.eq_ignore_ascii_case(&b))
+        })
+        .max_by_key(|(_, name)| name.len())
+        .map(|(index, name)| (index, pos + name.chars().count()))
+}
+
+/// parses time zone id or abbreviation, the longest valid prefix is used
+fn parse_zone(input: &[char], pos: usize) -> Option<(SparkTimeZone, usize)> {
+    let len = input[pos.min(input.len())..]
+        .iter()
+        .take_while(|c| c.is_ascii_alphanumeric() || matches!(c, '/' | '_' | '+' | '-' | ':' | '~'))
+        .count();
+    (1..=len).rev().find_map(|len| {
+        let id = input[pos..pos + len].iter().collect::<String>();
+        let id = match id.as_str() {
+            "PDT" => "America/Los_Angeles",
+            "MDT" => "America/Denver",
+            "CDT" => "America/Chicago",
+            "EDT" => "America/New_York",
+            id => id,
+        };
+        SparkTimeZone::parse(id).ok().map(|tz| (tz, pos + len))
+    })
+}
+
+fn parse_offset_field(c: char, n: usize, input: &[char], pos: usize) -> Option<(i32, usize)> {
+    const STYLES: [OffsetStyle; 5] = [
+        OffsetStyle::HHmm,
+        OffsetStyle::HHMM,
+        OffsetStyle::HHColonMM,
+        OffsetStyle::HHMMss,
+        OffsetStyle::HHColonMMss,
+    ];
+    const NO_OFFSET_TEXTS: [&str; 5] = ["+00", "+0000", "+00:00", "+0000", "+00:00"];
+    match (c, n) {
+        ('O', _) | ('Z', 4) => parse_localized_offset(input, pos),
+        ('X', n) => parse_offset(input, pos, STYLES[n - 1], "Z"),
+        ('x', n) => parse_offset(input, pos, STYLES[n - 1], NO_OFFSET_TEXTS[n - 1]),
+        ('Z', 5) => parse_offset(input, pos, OffsetStyle::HHColonMMss, "Z"),
+        ('Z', _) => parse_offset(input, pos, OffsetStyle::HHMM, "+0000"),
+        _ => None,
+    }
+}
+
+fn parse_offset(
+    input: &[char],
+    pos: usize,
+    style: OffsetStyle,
+    no_offset_text: &str,
+) -> Option<(i32, usize)> {
+    if let Some((_, pos)) = parse_text(input, pos, &[no_offset_text]) {
+        return Some((0, pos));
+    }
+    let sign = match input.get(pos)? {
+        '+' => 1,
+        '-' => -1,
+        _ => return None,
+    };
+    let (hours, mut pos) = parse_digits(input, pos + 1, 2, 2)?;
+    let colon = matches!(style, OffsetStyle::HHColonMM | OffsetStyle::HHColonMMss);
+    let next_field = |pos: &mut usize, required: bool| -> Option<i64> {
+        let start = *pos;
+        if colon && input.get(*pos) != Some(&':') {
+            return if required { None } else { Some(0) };
+        }
+        let digits_pos = start + colon as usize;
+        match parse_digits(input, digits_pos, 2, 2) {
+            Some((value, end)) => {
+                *pos = end;
+                Some(value)
+            }
+            None if required => None,
+            None => Some(0),
+        }
+    };
+    let minutes = next_field(&mut pos, style != OffsetStyle::HHmm)?;
+    let seconds = match style {
+        OffsetStyle::HHMMss | OffsetStyle::HHColonMMss => next_field(&mut pos, false)?,
+        _ => 0,
+    };
+    if hours > 18 || minutes > 59 || seconds > 59 {
+        return None;
+    }
+    Some((sign * (hours * 3600 + minutes * 60 + seconds) as i32, pos))
+}
+
+/// parses localized offset like `GMT`, `GMT+8`, `GMT+08:00`
+fn parse_localized_offset(input: &[char], pos: usize) -> Option<(i32, usize)> {
+    let (_, pos) = parse_text(input, pos, &["GMT"])?;
+    let sign = match input.get(pos) {
+        Some('+') => 1,
+        Some('-') => -1,
+        _ => return Some((0, pos)),
+    };
+    let (hours, mut pos) = parse_digits(input, pos + 1, 1, 2)?;
+    let mut minutes = 0;
+    let mut seconds = 0;
+    if input.get(pos) == Some(&':') {
+        (minutes, pos) = parse_digits(input, pos + 1, 2, 2)?;
+        if input.get(pos) == Some(&':') {
+            (seconds, pos) = parse_digits(input, pos + 1, 2, 2)?;
+        }
+    }
+    if hours > 18 || minutes > 59 || seconds > 59 {
+        return None;
+    }
+    Some((sign * (hours * 3600 + minutes * 60 + seconds) as i32, pos))
+}
+
+fn to_micros(local: &NaiveDateTime, parsed: &Parsed, tz: &SparkTimeZone) -> Option<i64> {
+    match (parsed.offset_seconds, &parsed.zone) {
+        (Some(offset_seconds), _) => {
+            let utc = local.checked_sub_signed(Duration::seconds(offset_seconds as i64))?;
+            Some(utc.and_utc().timestamp_micros())
+        }
+        (None, Some(zone)) => zone.local_to_micros(local),
+        (None, None) => tz.local_to_micros(local),
+    }
+}
+
+fn parse_legacy_field(
+    c: char,
+    n: usize,
+    input: &[char],
+    pos: usize,
+    obey_count: bool,
+    parsed: &mut Parsed,
+) -> Option<usize> {
+    // text fields
+    match c {
+        'G' => {
+            let (index, pos) = parse_text(input, pos, &ERA_NAMES)?;
+            parsed.era = Some(index as u32);
+            return Some(pos);
+        }
+        'M' | 'L' if n >= 3 => {
+            let names = [MONTH_NAMES, SHORT_MONTH_NAMES].concat();
+            let (index, pos) = parse_text(input, pos, &names)?;
+            parsed.month = Some(index as u32 % 12 + 1);
+            return Some(pos);
+        }
+        'E' => {
+            let names = [WEEKDAY_NAMES, SHORT_WEEKDAY_NAMES].concat();
+            return parse_text(input, pos, &names).map(|(_, pos)| pos);
+        }
+        'a' => {
+            let (index, pos) = parse_text(input, pos, &AMPM_NAMES)?;
+            parsed.ampm = Some(index as u32);
+            return Some(pos);
+        }
+        'z' | 'Z' | 'X' => {
+            if let Some((offset, pos)) = parse_localized_offset(input, pos)
+                .or_else(|| parse_offset(input, pos, OffsetStyle::HHColonMM, "Z"))
+                .or_else(|| parse_offset(input, pos, OffsetStyle::HHmm, "Z"))
+            {
+                parsed.offset_seconds = Some(offset);
+                return Some(pos);
+            }
+            let (zone, pos) = parse_zone(input, pos)?;
+            parsed.zone = Some(zone);
+            return Some(pos);
+        }
+        _ => {}
+    }
+
+    // numeric fields
+    let (negative, digits_pos) = match input.get(pos) {
+        Some('-') if !obey_count => (true, pos + 1),
+        _ => (false, pos),
+    };
+    let (value, end) = if obey_count {
+        parse_digits(input, digits_pos, n, n)?
+    } else {
+        parse_digits(input, digits_pos, 1, 18)?
+    };
+    let value = if negative { -value } else { value };
+    let value32 = u32::try_from(value).ok();
+    match c {
+        'y' => {
+            // two-digit years are adjusted to be within 80 years before and 20
+            // years after now
+            parsed.year = Some(if n <= 2 && end - pos == 2 {
+                let century_start = chrono::Utc::now().year() as i64 - 80;
+                let year = century_start / 100 * 100 + value;
+                if value < century_start % 100 {
+                    year + 100
+                } else {
+                    year
+                }
+            } else {
+                value
+            });
+        }
+        'M' | 'L' => parsed.month = Some(value32?),
+        'd' => parsed.day = Some(value32?),
+        'D' => parsed.day_of_year = Some(value32?),
+        'H' => parsed.hour_of_day = Some(value32?),
+        'k' => parsed.hour_of_day = Some(value32? % 24),
+        'h' => parsed.hour_of_ampm = Some(value32? % 12),
+        'K' => parsed.hour_of_ampm = Some(value32?),
+        'm' => parsed.minute = Some(value32?),
+        's' => parsed.second = Some(value32?),
+        'S' => parsed.millis = Some(value),
+        _ => {} // week-based fields are parsed but ignored
+    }
+    Some(end)
+}
+
+/// resolves parsed fields like lenient SimpleDateFormat: out-of-range fields
+/// are rolled into the next larger fields.
+fn resolve_legacy(parsed: &Parsed, tz: &SparkTimeZone) -> Option<i64> {
+    let year = parsed.year.unwrap_or(1970);
+    let year = i32::try_from(if parsed.era == Some(0) {
+        1 - year
+    } else {
+        year
+    })
+    .ok()?;
+    let first_day = NaiveDate::from_ymd_opt(year, 1, 1)?;
+    let date = match (parsed.day_of_year, parsed.day) {
+        (Some(day_of_year), None) => {
+            first_day.checked_add_signed(Duration::days(day_of_year as i64 - 1))?
+        }
+        _ => {
+            let months = parsed.month.unwrap_or(1) as i64 - 1;
+            let first_day_of_month = if months >= 0 {
+                first_day.checked_add_months(Months::new(u32::try_from(months).ok()?))?
+            } else {
+                first_day.checked_sub_months(Months::new(u32::try_from(-months).ok()?))?
+            };
+            first_day_of_month
+                .checked_add_signed(Duration::days(parsed.day.unwrap_or(1) as i64 - 1))?
+        }
+    };
+
+    let hours = match (parsed.hour_of_day, parsed.hour_of_ampm) {
+        (Some(hour), _) => hour as i64,
+        (None, Some(hour_of_ampm)) => hour_of_ampm as i64 + 12 * parsed.ampm.unwrap_or(0) as i64,
+        (None, None) => 0,
+    };
+    let local = date.and_hms_opt(0, 0, 0)?.checked_add_signed(
+        Duration::hours(hours)
+            + Duration::minutes(parsed.minute.unwrap_or(0) as i64)
+            + Duration::seconds(parsed.second.unwrap_or(0) as i64)
+            + Duration::milliseconds(parsed.millis.unwrap_or(0)),
+    )?;
+    to_micros(&local, parsed, tz)
+}
+
+fn pad(out: &mut String, value: i64, width: usize) {
+    if value < 0 {
+        out.push('-');
+    }
+    out.push_str(&format!("{:0width$}", value.unsigned_abs(), width = width));
+}
+
+fn text(out: &mut String, n: usize, short: &str, long: &str) {
+    out.push_str(if n == 4 { long } else { short });
+}
+
+fn format_offset(out: &mut String, offset_seconds: i32, style: OffsetStyle, no_offset_text: &str) {
+    if offset_seconds == 0 {
+        out.push_str(no_offset_text);
+        return;
+    }
+    let abs = offset_seconds.unsigned_abs();
+    let (hours, minutes, seconds) = (abs / 3600, abs / 60 % 60, abs % 60);
+    out.push(if offset_seconds < 0 { '-' } else { '+' });
+    out.push_str(&format!("{hours:02}"));
+    let colon = if matches!(style, OffsetStyle::HHColonMM | OffsetStyle::HHColonMMss) {
+        ":"
+    } else {
+        ""
+    };
+    if style != OffsetStyle::HHmm || minutes != 0 {
+        out.push_str(&format!("{colon}{minutes:02}"));
+    }
+    if matches!(style, OffsetStyle::HHMMss | OffsetStyle::HHColonMMss) && seconds != 0 {
+        out.push_str(&format!("{colon}{seconds:02}"));
+    }
+}
+
+fn format_localized_offset(out: &mut String, offset_seconds: i32, full: bool) {
+    out.push_str("GMT");
+    if offset_seconds == 0 {
+        return;
+    }
+    let abs = offset_seconds.unsigned_abs();
+    let (hours, minutes, seconds) = (abs / 3600, abs / 60 % 60, abs % 60);
+    out.push(if offset_seconds < 0 { '-' } else { '+' });
+    if full {
+        out.push_str(&format!("{hours:02}:{minutes:02}"));
+    } else {
+        out.push_str(&format!("{hours}"));
+        if minutes != 0 || seconds != 0 {
+            out.push_str(&format!(":{minutes:02}"));
+        }
+    }
+    if seconds != 0 {
+        out.push_str(&format!(":{seconds:02}"));
+    }
+}
+
+/// formats a field like DateTimeFormatter, `y` is proleptic year unless era
+/// is present in the pattern
+fn format_field(
+    out: &mut String,
+    c: char,
+    n: usize,
+    ctx: &FormatContext,
+    has_era: bool,
+) -> Option<()> {
+    let date = ctx.local.date();
+    let hour = ctx.local.hour();
+    let is_ad = date.year() >= 1;
+    match c {
+        'G' => {
+            let index = is_ad as usize;
+            text(out, n, ERA_NAMES[index], LONG_ERA_NAMES[index]);
+        }
+        'y' => {
+            let year = match (has_era, is_ad) {
+                (true, false) => 1 - date.year() as i64,
+                _ => date.year() as i64,
+            };
+            if n == 2 {
+                pad(out, year.rem_euclid(100), 2);
+            } else {
+                if n >= 4 && year >= 0 && year.to_string().len() > n {
+                    out.push('+');
+                }
+                pad(out, year, n);
+            }
+        }
+        'M' | 'L' if n >= 3 => {
+            let index = date.month0() as usize;
+            text(out, n, SHORT_MONTH_NAMES[index], MONTH_NAMES[index]);
+        }
+        'M' | 'L' => pad(out, date.month() as i64, n),
+        'Q' | 'q' => {
+            let quarter = date.month0() / 3 + 1;
+            match n {
+                1 | 2 => pad(out, quarter as i64, n),
+                3 => out.push_str(&format!("Q{quarter}")),
+                _ => {
+                    let ordinal = ["1st", "2nd", "3rd", "4th"][quarter as usize - 1];
+                    out.push_str(&format!("{ordinal} quarter"));
+                }
+            }
+        }
+        'd' => pad(out, date.day() as i64, n),
+        'D' => pad(out, date.ordinal() as i64, n),
+        'F' => pad(out, (date.day0() % 7 + 1) as i64, n),
+        'E' => {
+            let index = date.weekday().num_days_from_monday() as usize;
+            text(out, n, SHORT_WEEKDAY_NAMES[index], WEEKDAY_NAMES[index]);
+        }
+        'a' => out.push_str(AMPM_NAMES[hour as usize / 12]),
+        'h' => pad(out, (hour + 11) as i64 % 12 + 1, n),
+        'K' => pad(out, hour as i64 % 12, n),
+        'k' => pad(out, if hour == 0 { 24 } else { hour as i64 }, n),
+        'H' => pad(out, hour as i64, n),
+        'm' => pad(out, ctx.local.minute() as i64, n),
+        's' => pad(out, ctx.local.second() as i64, n),
+        'S' => {
+            let nanos = format!("{:09}", ctx.local.nanosecond() % 1_000_000_000);
+            out.push_str(&nanos[..n]);
+        }
+        'V' => out.push_str(ctx.zone_id),
+        'z' => out.push_str(ctx.zone_name),
+        'O' => format_localized_offset(out, ctx.offset_seconds, n == 4),
+        'X' | 'x' => {
+            let styles = [
+                OffsetStyle::HHmm,
+                OffsetStyle::HHMM,
+                OffsetStyle::HHColonMM,
+                OffsetStyle::HHMMss,
+                OffsetStyle::HHColonMMss,
+            ];
+            let no_offset_texts = match c {
+                'X' => ["Z"; 5],
+                _ => ["+00", "+0000", "+00:00", "+0000", "+00:00"],
+            };
+            format_offset(
+                out,
+                ctx.offset_seconds,
+                styles[n - 1],
+                no_offset_texts[n - 1],
+            );
+        }
+        'Z' => match n {
+            4 => format_localized_offset(out, ctx.offset_seconds, true),
+            5 => format_offset(out, ctx.offset_seconds, OffsetStyle::HHColonMMss, "Z"),
+            _ => format_offset(out, ctx.offset_seconds, OffsetStyle::HHMM, "+0000"),
+        },
+        _ => return None,
+    }
+    Some(())
+}
+
+/// formats a field like SimpleDateFormat with US locale
+fn format_legacy_field(out: &mut String, c: char, n: usize, ctx: &FormatContext) -> Option<()> {
+    let date = ctx.local.date();
+    let hour = ctx.local.hour();
+    let year_of_era = |year: i32| {
+        if year >= 1 {
+            year as i64
+        } else {
+            1 - year as i64
+        }
+    };
+    let year_field = |out: &mut String, year: i64| match n {
+        2 => pad(out, year % 100, 2),
+        _ => pad(out, year, n),
+    };
+    match c {
+        'G' => out.push_str(ERA_NAMES[(date.year() >= 1) as usize]),
+        'y' => year_field(out, year_of_era(date.year())),
+        'Y' => year_field(out, year_of_era(us_week_of_year(&date)?.0)),
+        'M' | 'L' => match n {
+            1 | 2 => pad(out, date.month() as i64, n),
+            3 => out.push_str(SHORT_MONTH_NAMES[date.month0() as usize]),
+            _ => out.push_str(MONTH_NAMES[date.month0() as usize]),
+        },
+        'w' => pad(out, us_week_of_year(&date)?.1 as i64, n),
+        'W' => {
+            let first_day = date.with_day(1)?;
+            let week = (date.day0() + first_day.weekday().num_days_from_sunday()) / 7 + 1;
+            pad(out, week as i64, n);
+        }
+        'D' => pad(out, date.ordinal() as i64, n),
+        'd' => pad(out, date.day() as i64, n),
+        'F' => pad(out, (date.day0() / 7 + 1) as i64, n),
+        'E' => {
+            let index = date.weekday().num_days_from_monday() as usize;
+            match n {
+                1..=3 => out.push_str(SHORT_WEEKDAY_NAMES[index]),
+                _ => out.push_str(WEEKDAY_NAMES[index]),
+            }
+        }
+        'u' => pad(out, date.weekday().number_from_monday() as i64, n),
+        'a' => out.push_str(AMPM_NAMES[hour as usize / 12]),
+        'H' => pad(out, hour as i64, n),
+        'k' => pad(out, if hour == 0 { 24 } else { hour as i64 }, n),
+        'K' => pad(out, hour as i64 % 12, n),
+        'h' => pad(out, (hour + 11) as i64 % 12 + 1, n),
+        'm' => pad(out, ctx.local.minute() as i64, n),
+        's' => pad(out, ctx.local.second() as i64, n),
+        'S' => pad(out, (ctx.local.nanosecond() / 1_000_000) as i64, n),
+        'z' => out.push_str(ctx.zone_name),
+        'Z' => format_offset(out, ctx.offset_seconds, OffsetStyle::HHMM, "+0000"),
+        'X' => {
+            let style = match n {
+                1 => OffsetStyle::HHmm,
+                2 => OffsetStyle::HHMM,
+                _ => OffsetStyle::HHColonMM,
+            };
+            if n == 1 {
+                // X only outputs hours
+                format_offset(out, ctx.offset_seconds / 3600 * 3600, style, "Z");
+            } else {
+                format_offset(out, ctx.offset_seconds, style, "Z");
+            }
+        }
+        _ => return None,
+    }
+    Some(())
+}
+
+/// returns (week_year, week_of_year) with US week definition: weeks start on
+/// Sunday and the first week contains January 1st
+fn us_week_of_year(date: &NaiveDate) -> Option<(i32, u32)> {
+    let start_of_week =
+        |date: NaiveDate| date - Duration::days(date.weekday().num_days_from_sunday() as i64);
+    let next_year_first_day = NaiveDate::from_ymd_opt(date.year() + 1, 1, 1)?;
+    if *date >= start_of_week(next_year_first_day) {
+        return Some((date.year() + 1, 1));
+    }
+    let first_day = NaiveDate::from_ymd_opt(date.year(), 1, 1)?;
+    let week = (*date - start_of_week(first_day)).num_days() / 7 + 1;
+    Some((date.year(), week as u32))
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::array::{Int64Array, StringArray, TimestampMicrosecondArray};
+    use datafusion::{
+        common::{cast::as_string_array, Result, ScalarValue},
+        physical_plan::ColumnarValue,
+    };
+
+    use crate::{
+        spark_dates::SparkTimeZone,
+        spark_datetime_format::{
+            spark_date_format, spark_get_timestamp, spark_unix_timestamp, DateTimeFormatter,
+            TimeParserPolicy,
+        },
+    };
+
+    fn utf8(s: &str) -> ColumnarValue {
+        ColumnarValue::Scalar(ScalarValue::from(s))
+    }
+
+    fn format(pattern: &str, policy: TimeParserPolicy, micros: i64, tz: &str) -> Option<String> {
+        DateTimeFormatter::try_new(pattern, policy, false)
+            .unwrap()
+            .format(micros, &SparkTimeZone::parse(tz).unwrap(), tz)
+    }
+
+    fn parse(pattern: &str, policy: TimeParserPolicy, s: &str, tz: &str) -> Result<Option<i64>> {
+        DateTimeFormatter::try_new(pattern, policy, true)?
+            .parse(s, &SparkTimeZone::parse(tz).unwrap())
+    }
+
+    // 2024-03-05 09:32:05.123456 UTC
+    const MICROS: i64 = 1709631125123456;
+
+    #[test]
+    fn test_format() {
+        use TimeParserPolicy::*;
+        let cases = [
+            ("yyyy-MM-dd HH:mm:ss", "2024-03-05 09:32:05"),
+            ("yy/M/d h:m:s a", "24/3/5 9:32:5 AM"),
+            ("EEE, dd MMM yyyy", "Tue, 05 Mar 2024"),
+            ("EEEE MMMM D", "Tuesday March 65"),
+            ("QQQ qqqq", "Q1 1st quarter"),
+            ("HH:mm:ss.SSS SSSSSS S", "09:32:05.123 123456 1"),
+            ("'quoted ''text''' yyyy", "quoted 'text' 2024"),
+            ("yyyy[-MM]", "2024-03"),
+            ("kk KK", "09 09"),
+        ];
+        for (pattern, expected) in cases {
+            assert_eq!(
+                format(pattern, Corrected, MICROS, "UTC").as_deref(),
+                Some(expected),
+                "pattern: {pattern}"
+            );
+        }
+
+        // time zones
+        let tz = "America/Los_Angeles";
+        assert_eq!(
+            format("yyyy-MM-dd HH:mm VV z O XXX xx Z", Corrected, MICROS, tz).as_deref(),
+            Some("2024-03-05 01:32 America/Los_Angeles PST GMT-8 -08:00 -0800 -0800")
+        );
+        assert_eq!(
+            format("HH:mm X ZZZZ ZZZZZ", Corrected, MICROS, "UTC").as_deref(),
+            Some("09:32 Z GMT Z")
+        );
+
+        // years
+        let year_10000 = 253402300800000000;
+        assert_eq!(
+            format("yyyy", Corrected, year_10000, "UTC").as_deref(),
+            Some("+10000")
+        );
+        assert_eq!(
+            format("yyyy", Legacy, year_10000, "UTC").as_deref(),
+            Some("10000")
+        );
+
+        // legacy patterns
+        assert_eq!(
+            format(
+                "yyyy-MM-dd HH:mm:ss.SSS u w Z",
+                Legacy,
+                MICROS,
+                "Asia/Shanghai"
+            )
+            .as_deref(),
+            Some("2024-03-05 17:32:05.123 2 10 +0800")
+        );
+    }
+
+    #[test]
+    fn test_invalid_patterns() {
+        use TimeParserPolicy::*;
+        for pattern in ["YYYY", "ww", "u", "MMMMM", "yyyyyyyyyyy", "HHH", "A", "{"] {
+            assert!(
+                DateTimeFormatter::try_new(pattern, Corrected, false).is_err(),
+                "{pattern}"
+            );
+        }
+        assert!(DateTimeFormatter::try_new("EEE", Corrected, true).is_err());
+        assert!(DateTimeFormatter::try_new("yyyy'", Corrected, false).is_err());
+        assert!(DateTimeFormatter::try_new("YYYY", Legacy, false).is_ok());
+        assert!(DateTimeFormatter::try_new("bb", Legacy, false).is_err());
+
+        // pattern only valid in legacy
+        let err = DateTimeFormatter::try_new("YYYY", Exception, false)
+            .err()
+            .unwrap();
+        assert!(err
+            .to_string()
+            .contains("spark.sql.legacy.timeParserPolicy"));
+    }
+
+    #[test]
+    fn test_parse() -> Result<()> {
+        use TimeParserPolicy::*;
+        let expected = Some(1709631125000000);
+        assert_eq!(
+            parse(
+                "yyyy-MM-dd HH:mm:ss",
+                Corrected,
+                "2024-03-05 09:32:05",
+                "UTC"
+            )?,
+            expected
+        );
+        assert_eq!(
+            parse("yyyyMMddHHmmss", Corrected, "20240305093205", "UTC")?,
+            expected
+        );
+        assert_eq!(
+            parse(
+                "dd MMM yyyy h:mm:ss a",
+                Corrected,
+                "05 mar 2024 9:32:05 am",
+                "UTC"
+            )?,
+            expected
+        );
+        assert_eq!(
+            parse(
+                "yyyy-MM-dd'T'HH:mm:ssXXX",
+                Corrected,
+                "2024-03-05T17:32:05+08:00",
+                "UTC"
+            )?,
+            expected
+        );
+        assert_eq!(
+            parse(
+                "yyyy-MM-dd HH:mm:ss VV",
+                Corrected,
+                "2024-03-05 01:32:05 America/Los_Angeles",
+                "UTC"
+            )?,
+            expected
+        );
+        assert_eq!(
+            parse(
+                "yyyy-MM-dd HH:mm:ss",
+                Corrected,
+                "2024-03-05 17:32:05",
+                "Asia/Shanghai"
+            )?,
+            expected
+        );
+
+        // variable length fractions and optional sections
+        assert_eq!(
+            parse(
+                "yyyy-MM-dd HH:mm:ss[.SSSSSS]",
+                Corrected,
+                "2024-03-05 09:32:05.12",
+                "UTC"
+            )?,
+            Some(1709631125120000)
+        );
+        assert_eq!(
+            parse(
+                "yyyy-MM-dd HH:mm:ss[.SSSSSS]",
+                Corrected,
+                "2024-03-05 09:32:05",
+                "UTC"
+            )?,
+            expected
+        );
+
+        // missing fields default to 1970-01-01 00:00:00
+        assert_eq!(parse("HH:mm", Corrected, "01:30", "UTC")?, Some(5400000000));
+        assert_eq!(
+            parse("yyyy-MM", Corrected, "1970-02", "UTC")?,
+            Some(31 * 86400000000)
+        );
+
+        // invalid strings are parsed as null
+        for s in [
+            "2024-02-30 00:00:00",
+            "2024-03-05 09:32:05 extra",
+            "2024-3-05 09:32:05",
+            "24-03-05 09:32:05",
+        ] {
+            assert_eq!(
+                parse("yyyy-MM-dd HH:mm:ss", Corrected, s, "UTC")?,
+                None,
+                "{s}"
+            );
+        }
+
+        // legacy parser is lenient and ignores trailing text
+        assert_eq!(
+            parse(
+                "yyyy-MM-dd HH:mm:ss",
+                Legacy,
+                "2024-02-34 09:32:05 extra",
+                "UTC"
+            )?,
+            expected
+        );
+        assert_eq!(
+            parse("yyyy-MM-dd HH:mm:ss", Legacy, "2024-3-5 9:32:5", "UTC")?,
+            expected
+        );
+        assert_eq!(
+            parse("yyyyMMddHHmmss", Legacy, "20240305093205", "UTC")?,
+            expected
+        );
+        assert_eq!(
+            parse("yyyy-MM-dd HH:mm:ss", Legacy, "2024-03-05", "UTC")?,
+            None
+        );
+
+        // exception policy raises error if legacy parser can parse the string
+        assert_eq!(
+            parse("yyyy-MM-dd", Exception, "2024-03-05", "UTC")?,
+            Some(1709596800000000)
+        );
+        assert_eq!(parse("yyyy-MM-dd", Exception, "xxxx-03-05", "UTC")?, None);
+        assert!(parse("yyyy-MM-dd", Exception, "2024-3-5", "UTC").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_spark_functions() -> Result<()> {
+        let timestamps = ColumnarValue::Array(Arc::new(TimestampMicrosecondArray::from(vec![
+            Some(MICROS),
+            None,
+        ])));
+        let r = spark_date_format(&[
+            timestamps,
+            utf8("yyyy-MM-dd HH:mm"),
+            utf8("Asia/Shanghai"),
+            utf8("CORRECTED"),
+        ])?;
+        let s = r.into_array(2)?;
+        assert_eq!(
+            as_string_array(&s)?.into_iter().collect::<Vec<_>>(),
+            vec![Some("2024-03-05 17:32"), None]
+        );
+
+        let strs = ColumnarValue::Array(Arc::new(StringArray::from(vec![
+            Some("2024-03-05 17:32:05"),
+            Some("invalid"),
+            None,
+        ])));
+        let r = spark_unix_timestamp(&[
+            strs.clone(),
+            utf8("yyyy-MM-dd HH:mm:ss"),
+            utf8("+08:00"),
+            utf8("CORRECTED"),
+        ])?;
+        let s = r.into_array(3)?;
+        assert_eq!(
+            s.as_any()
+                .downcast_ref::<Int64Array>()
+                .unwrap()
+                .iter()
+                .collect::<Vec<_>>(),
+            vec![Some(1709631125), None, None]
+        );
+
+        let r = spark_get_timestamp(&[
+            strs,
+            utf8("yyyy-MM-dd HH:mm:ss"),
+            utf8("+08:00"),
+            utf8("LEGACY"),
+        ])?;
+        let s = r.into_array(3)?;
+        assert_eq!(
+            s.as_any()
+                .downcast_ref::<TimestampMicrosecondArray>()
+                .unwrap()
+                .iter()
+                .collect::<Vec<_>>(),
+            vec![Some(1709631125000000), None, None]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,422 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use arrow::{
+    array::{ArrayRef, AsArray, BinaryArray, StringArray},
+    datatypes::{DataType, Int64Type},
+};
+use base64::{
+    alphabet,
+    engine::{
+        general_purpose::{GeneralPurpose, GeneralPurposeConfig, STANDARD},
+        DecodePaddingMode,
+    },
+    Engine,
+};
+use datafusion::{
+    common::{
+        cast::{as_int32_array, as_string_array},
+        Result, ScalarValue,
+    },
+    physical_plan::ColumnarValue,
+};
+use datafusion_ext_commons::df_execution_err;
+
+/// spark implicitly casts strings to binary for binary functions. the cast is
+/// removed in planning, so both types are accepted here.
+pub(crate) fn binary_values(array: &ArrayRef) -> Result<Vec<Option<&[u8]>>> {
+    Ok(match array.data_type() {
+        DataType::Binary => array.as_binary::<i32>().iter().collect(),
+        DataType::Utf8 => array
+            .as_string::<i32>()
+            .iter()
+            .map(|s| s.map(str::as_bytes))
+            .collect(),
+        other => return df_execution_err!("expect binary or string input, got {other}"),
+    })
+}
+
+pub(crate) fn to_hex(bytes: &[u8], uppercase: bool) -> String {
+    let digits = if uppercase {
+        b"0123456789ABCDEF"
+    } else {
+        b"0123456789abcdef"
+    };
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for &b in bytes {
+        hex.push(digits[(b >> 4) as usize] as char);
+        hex.push(digits[(b & 0x0f) as usize] as char);
+    }
+    hex
+}
+
+/// base64(input, chunked), spark 3.3 ~ 3.5.1 encode with java's mime encoder,
+/// which splits the output into lines of 76 chars
+pub fn spark_base64(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let chunked = match &args[1] {
+        ColumnarValue::Scalar(ScalarValue::Boolean(Some(chunked))) => *chunked,
+        _ => return df_execution_err!("base64: chunked must be a non-null literal boolean"),
+    };
+    let input = args[0].clone().into_array(1)?;
+    let output: StringArray = binary_values(&input)?
+        .into_iter()
+        .map(|v| {
+            v.map(|v| {
+                let encoded = STANDARD.encode(v);
+                if !chunked || encoded.len() <= 76 {
+                    return encoded;
+                }
+                let lines = encoded.as_bytes().chunks(76);
+                lines
+                    .map(|line| std::str::from_utf8(line).expect("base64 is ascii"))
+                    .collect::<Vec<_>>()
+                    .join("\r\n")
+            })
+        })
+        .collect();
+    Ok(ColumnarValue::Array(Arc::new(output)))
+}
+
+/// unbase64(input), decodes leniently like java's mime decoder: characters
+/// outside the base64 alphabet are ignored, and decoding stops at padding
+pub fn spark_unbase64(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    const LENIENT: GeneralPurpose = GeneralPurpose::new(
+        &alphabet::STANDARD,
+        GeneralPurposeConfig::new()
+            .with_decode_allow_trailing_bits(true)
+            .with_decode_padding_mode(DecodePaddingMode::RequireNone),
+    );
+    let input = args[0].clone().into_array(1)?;
+    let mut output = Vec::with_capacity(input.len());
+    for s in as_string_array(&input)? {
+        let Some(s) = s else {
+            output.push(None);
+            continue;
+        };
+        let mut symbols = s
+            .bytes()
+            .take_while(|&b| b != b'=')
+            .filter(|&b| b.is_ascii_alphanumeric() || b == b'+' || b == b'/')
+            .collect::<Vec<_>>();
+        // a single trailing symbol has not enough bits for a byte
+        if symbols.len() % 4 == 1 {
+            symbols.pop();
+        }
+        match LENIENT.decode(&symbols) {
+            Ok(decoded) => output.push(Some(decoded)),
+            Err(err) => return df_execution_err!("unbase64: invalid input: {err}"),
+        }
+    }
+    let output: BinaryArray = output.iter().map(|v| v.as_deref()).collect();
+    Ok(ColumnarValue::Array(Arc::new(output)))
+}
+
+/// hex(input), returns upper-case hex of bigint, string or binary values
+pub fn spark_hex(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let input = args[0].clone().into_array(1)?;
+    let output: StringArray = match input.data_type() {
+        DataType::Int64 => input
+            .as_primitive::<Int64Type>()
+            .iter()
+            .map(|v| v.map(|v| format!("{:X}", v as u64)))
+            .collect(),
+        _ => binary_values(&input)?
+            .into_iter()
+            .map(|v| v.map(|v| to_hex(v, true)))
+            .collect(),
+    };
+    Ok(ColumnarValue::Array(Arc::new(output)))
+}
+
+/// unhex(input), returns null if the input contains non-hex characters. a
+/// leading zero is padded for input of odd length.
+pub fn spark_unhex(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    fn unhex_digit(b: u8) -> Option<u8> {
+        (b as char).to_digit(16).map(|d| d as u8)
+    }
+    fn unhex(s: &str) -> Option<Vec<u8>> {
+        let bytes = s.as_bytes();
+        let mut output = Vec::with_capacity(bytes.len().div_ceil(2));
+        let (head, tail) = bytes.split_at(bytes.len() % 2);
+        if let Some(&b) = head.first() {
+            output.push(unhex_digit(b)?);
+        }
+        for pair in tail.chunks(2) {
+            output.push((unhex_digit(pair[0])? << 4) | unhex_digit(pair[1])?);
+        }
+        Some(output)
+    }
+    let input = args[0].clone().into_array(1)?;
+    let output = as_string_array(&input)?
+        .iter()
+        .map(|s| s.and_then(unhex))
+        .collect::<Vec<_>>();
+    let output: BinaryArray = output.iter().map(|v| v.as_deref()).collect();
+    Ok(ColumnarValue::Array(Arc::new(output)))
+}
+
+/// conv(num, from_base, to_base), converts number between bases like spark's
+/// NumberConverter. the number is parsed as unsigned 64-bit integer until the
+/// first invalid digit, and overflow results in -1. a negative to_base means
+/// the result is signed.
+pub fn spark_conv(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(args)?;
+    let output: StringArray = as_string_array(&arrays[0])?
+        .iter()
+        .zip(as_int32_array(&arrays[1])?)
+        .zip(as_int32_array(&arrays[2])?)
+        .map(|((num, from_base), to_base)| conv(num?, from_base?, to_base?))
+        .collect();
+    Ok(ColumnarValue::Array(Arc::new(output)))
+}
+
+fn conv(num: &str, from_base: i32, to_base: i32) -> Option<String> {
+    if !(2..=36).contains(&from_base) || !(2..=36).contains(&to_base.abs()) {
+        return None;
+    }
+    let num = num.trim_matches(' ');
+    if num.is_empty() {
+        return None;
+    }
+    let (mut negative, digits) = match num.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, num),
+    };
+
+    let mut unsigned = 0u64;
+    for b in digits.bytes() {
+        let Some(digit) = (b as char).to_digit(from_base as u32) else {
+            break;
+        };
+        match unsigned
+            .checked_mul(from_base as u64)
+            .and_then(|v| v.checked_add(digit as u64))
+        {
+            Some(v) => unsigned = v,
+            None => {
+                unsigned = u64::MAX;
+                break;
+            }
+        }
+    }
+
+    let mut v = unsigned as i64;
+    if negative && to_base > 0 {
+        v = if v < 0 { -1 } else { -v };
+    }
+    if to_base < 0 && v < 0 {
+        v = v.wrapping_neg();
+        negative = true;
+    }
+
+    let radix = to_base.unsigned_abs() as u64;
+    let mut unsigned = v as u64;
+    let mut output = vec![];
+    loop {
+        let digit = std::char::from_digit((unsigned % radix) as u32, radix as u32)?;
+        output.push(digit.to_ascii_uppercase());
+        unsigned /= radix;
+        if unsigned == 0 {
+            break;
+        }
+    }
+    if negative && to_base < 0 {
+        output.push('-');
+    }
+    Some(output.into_iter().rev().collect())
+}
+
+fn args_to_arrays(args: &[ColumnarValue]) -> Result<Vec<ArrayRef>> {
+    let num_rows = args
+        .iter()
+        .find_map(|arg| match arg {
+            ColumnarValue::Array(array) => Some(array.len()),
+            ColumnarValue::Scalar(_) => None,
+        })
+        .unwrap_or(1);
+    args.iter()
+        .map(|arg| arg.clone().into_array(num_rows))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::array::{BinaryArray, Int32Array, Int64Array, StringArray};
+    use datafusion::{
+        common::{Result, ScalarValue},
+        physical_plan::ColumnarValue,
+    };
+
+    use super::*;
+
+    fn strings(output: ColumnarValue) -> Result<Vec<Option<String>>> {
+        let output = output.into_array(1)?;
+        Ok(output
+            .as_string::<i32>()
+            .iter()
+            .map(|s| s.map(|s| s.to_owned()))
+            .collect())
+    }
+
+    fn binaries(output: ColumnarValue) -> Result<Vec<Option<Vec<u8>>>> {
+        let output = output.into_array(1)?;
+        Ok(output
+            .as_binary::<i32>()
+            .iter()
+            .map(|v| v.map(|v| v.to_vec()))
+            .collect())
+    }
+
+    #[test]
+    fn test_base64() -> Result<()> {
+        let long_input = "x".repeat(60);
+        let input = ColumnarValue::Array(Arc::new(StringArray::from(vec![
+            Some("Spark SQL"),
+            Some(long_input.as_str()),
+            None,
+        ])));
+        let long_encoded = STANDARD.encode(&long_input);
+        assert_eq!(
+            strings(spark_base64(&[
+                input.clone(),
+                ColumnarValue::Scalar(ScalarValue::Boolean(Some(false))),
+            ])?)?,
+            vec![
+                Some("U3BhcmsgU1FM".to_owned()),
+                Some(long_encoded.clone()),
+                None,
+            ],
+        );
+        assert_eq!(
+            strings(spark_base64(&[
+                input,
+                ColumnarValue::Scalar(ScalarValue::Boolean(Some(true))),
+            ])?)?,
+            vec![
+                Some("U3BhcmsgU1FM".to_owned()),
+                Some(format!(
+                    "{}\r\n{}",
+                    &long_encoded[..76],
+                    &long_encoded[76..]
+                )),
+                None,
+            ],
+        );
+
+        let input = ColumnarValue::Array(Arc::new(StringArray::from(vec![
+            Some("U3BhcmsgU1FM"),
+            Some("U3Bhcm\r\nsgU1E="),
+            Some("U3BhcmsgU1"),
+            Some(""),
+            None,
+        ])));
+        assert_eq!(
+            binaries(spark_unbase64(&[input])?)?,
+            vec![
+                Some(b"Spark SQL".to_vec()),
+                Some(b"Spark SQ".to_vec()),
+                Some(b"Spark S".to_vec()),
+                Some(vec![]),
+                None,
+            ],
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_hex_unhex() -> Result<()> {
+        let input = ColumnarValue::Array(Arc::new(Int64Array::from(vec![
+            Some(17),
+            Some(0),
+            Some(-1),
+            None,
+        ])));
+        assert_eq!(
+            strings(spark_hex(&[input])?)?,
+            vec![
+                Some("11".to_owned()),
+                Some("0".to_owned()),
+                Some("FFFFFFFFFFFFFFFF".to_owned()),
+                None,
+            ],
+        );
+        let input = ColumnarValue::Array(Arc::new(BinaryArray::from(vec![
+            Some(b"Spark SQL".as_ref()),
+            Some(b"".as_ref()),
+        ])));
+        assert_eq!(
+            strings(spark_hex(&[input])?)?,
+            vec![Some("537061726B2053514C".to_owned()), Some("".to_owned())],
+        );
+
+        let input = ColumnarValue::Array(Arc::new(StringArray::from(vec![
+            Some("537061726b2053514C"),
+            Some("123"),
+            Some("GG"),
+            None,
+        ])));
+        let output = spark_unhex(&[input])?;
+        assert_eq!(
+            binaries(output)?,
+            vec![
+                Some(b"Spark SQL".to_vec()),
+                Some(vec![0x01, 0x23]),
+                None,
+                None,
+            ],
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_conv() -> Result<()> {
+        let nums = ColumnarValue::Array(Arc::new(StringArray::from(vec![
+            Some("100"),
+            Some("-10"),
+            Some("-10"),
+            Some(" ff "),
+            Some("12x34"),
+            Some("ffffffffffffffffff"),
+            Some("10"),
+            Some(""),
+            None,
+        ])));
+        let from_bases = ColumnarValue::Array(Arc::new(Int32Array::from(vec![
+            2, 16, 16, 16, 10, 16, 37, 10, 10,
+        ])));
+        let to_bases = ColumnarValue::Array(Arc::new(Int32Array::from(vec![
+            10, -10, 10, 10, 16, -10, 10, 10, 10,
+        ])));
+        let output = strings(spark_conv(&[nums, from_bases, to_bases])?)?;
+        assert_eq!(
+            output,
+            vec![
+                Some("4".to_owned()),
+                Some("-16".to_owned()),
+                Some("18446744073709551600".to_owned()),
+                Some("255".to_owned()),
+                Some("C".to_owned()),
+                Some("-1".to_owned()),
+                None,
+                None,
+                None,
+            ],
+        );
+        Ok(())
+    }
+}
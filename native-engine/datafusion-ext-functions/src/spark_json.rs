@@ -0,0 +1,1230 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Conversions between json strings and typed arrow values, following spark's
+//! JacksonParser/JacksonGenerator with default options.
+//!
+//! json is parsed with a small hand-written parser instead of serde_json,
+//! because spark keeps the order and duplications of object keys and the
+//! exact text of numbers (for decimals), and also accepts single-quoted
+//! strings and NaN/Infinity tokens.
+
+use std::{borrow::Cow, sync::Arc};
+
+use arrow::{
+    array::{
+        Array, ArrayRef, AsArray, BooleanArray, Decimal128Array, Float32Array, Float64Array,
+        Int16Array, Int32Array, Int64Array, Int8Array, ListArray, ListBuilder, MapArray,
+        StringArray, StringBuilder, StructArray,
+    },
+    buffer::{NullBuffer, OffsetBuffer},
+    datatypes::{
+        DataType, Date32Type, Decimal128Type, Float32Type, Float64Type, Int16Type, Int32Type,
+        Int64Type, Int8Type, TimeUnit, TimestampMicrosecondType,
+    },
+};
+use datafusion::{
+    common::{cast::as_string_array, Result, ScalarValue},
+    physical_plan::ColumnarValue,
+};
+use datafusion_ext_commons::df_execution_err;
+
+use crate::{
+    spark_dates::{days_to_date, SparkTimeZone},
+    spark_datetime_format::{DateTimeFormatter, TimeParserPolicy},
+};
+
+/// from_json(json, mode), converting json strings into the return type, which
+/// must be a struct, array or map. in PERMISSIVE mode, malformed records are
+/// converted to null (or a struct with all null fields), and root struct
+/// fields failed to convert are set to null while other fields are kept.
+pub fn from_json(args: &[ColumnarValue], return_type: &DataType) -> Result<ColumnarValue> {
+    let failfast = match &args[1] {
+        ColumnarValue::Scalar(ScalarValue::Utf8(Some(mode))) => match mode.as_str() {
+            "PERMISSIVE" => false,
+            "FAILFAST" => true,
+            _ => return df_execution_err!("from_json: unsupported parse mode: {mode}"),
+        },
+        _ => return df_execution_err!("from_json: parse mode must be a non-null literal string"),
+    };
+    let jsons = args[0].clone().into_array(1)?;
+    let jsons = as_string_array(&jsons)?;
+    let num_rows = jsons.len();
+
+    // parse all records, None means null or empty record
+    let mut bad_records = vec![false; num_rows];
+    let values = jsons
+        .iter()
+        .zip(&mut bad_records)
+        .map(|(json, bad)| match json.map(parse_json) {
+            Some(Ok(value)) => value,
+            Some(Err(())) => {
+                *bad = true;
+                None
+            }
+            None => None,
+        })
+        .collect::<Vec<_>>();
+
+    let array: ArrayRef = match return_type {
+        DataType::Struct(fields) => {
+            let mut columns = vec![Vec::with_capacity(num_rows); fields.len()];
+            let mut valids = Vec::with_capacity(num_rows);
+            for (value, bad) in values.iter().zip(&mut bad_records) {
+                let object = match value {
+                    Some(JsonValue::Object(object)) => Some(object),
+                    Some(_) => {
+                        *bad = true;
+                        None
+                    }
+                    None => None,
+                };
+                for (field, column) in fields.iter().zip(&mut columns) {
+                    let field_value = object.and_then(|object| get_field(object, field.name()));
+                    if let Some(v) = field_value
+                        && !v.matches(field.data_type())
+                    {
+                        *bad = true;
+                        column.push(None);
+                        continue;
+                    }
+                    column.push(field_value);
+                }
+                valids.push(object.is_some() || *bad);
+            }
+            if failfast && bad_records.contains(&true) {
+                return malformed_record_err();
+            }
+            let columns = fields
+                .iter()
+                .zip(&columns)
+                .map(|(field, column)| build_array(column, field.data_type()))
+                .collect::<Result<Vec<_>>>()?;
+            Arc::new(StructArray::try_new(
+                fields.clone(),
+                columns,
+                Some(NullBuffer::from(valids)),
+            )?)
+        }
+        DataType::List(_) | DataType::Map(..) => {
+            // a single object is accepted as an array of one struct
+            let wrap_object = matches!(
+                return_type,
+                DataType::List(field) if matches!(field.data_type(), DataType::Struct(_))
+            );
+            let wrapped = values
+                .into_iter()
+                .map(|value| match value {
+                    Some(object @ JsonValue::Object(_)) if wrap_object => {
+                        Some(JsonValue::Array(vec![object]))
+                    }
+                    value => value,
+                })
+                .collect::<Vec<_>>();
+            let column = wrapped
+                .iter()
+                .zip(&mut bad_records)
+                .map(|(value, bad)| match value {
+                    Some(JsonValue::Null) => {
+                        *bad = true;
+                        None
+                    }
+                    Some(v) if !v.matches(return_type) => {
+                        *bad = true;
+                        None
+                    }
+                    value => value.as_ref(),
+                })
+                .collect::<Vec<_>>();
+            if failfast && bad_records.contains(&true) {
+                return malformed_record_err();
+            }
+            build_array(&column, return_type)?
+        }
+        other => return df_execution_err!("from_json: unsupported schema: {other}"),
+    };
+    Ok(ColumnarValue::Array(array))
+}
+
+fn malformed_record_err<T>() -> Result<T> {
+    df_execution_err!(
+        "Malformed records are detected in record parsing. Parse Mode: FAILFAST. To process \
+         malformed records as null result, try setting the option 'mode' as 'PERMISSIVE'."
+    )
+}
+
+/// to_json(value, timezone), converting struct, array or map values into json
+/// strings. null fields of structs are omitted.
+pub fn to_json(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let tz_id = match &args[1] {
+        ColumnarValue::Scalar(ScalarValue::Utf8(Some(tz_id))) => tz_id.as_str(),
+        _ => return df_execution_err!("to_json: timezone must be a non-null literal string"),
+    };
+    let generator = JsonGenerator {
+        tz: SparkTimeZone::parse(tz_id)?,
+        tz_id,
+        timestamp_formatter: DateTimeFormatter::try_new(
+            "yyyy-MM-dd'T'HH:mm:ss.SSSXXX",
+            TimeParserPolicy::Corrected,
+            false,
+        )?,
+    };
+    let values = args[0].clone().into_array(1)?;
+    if !matches!(
+        values.data_type(),
+        DataType::Struct(_) | DataType::List(_) | DataType::Map(..)
+    ) {
+        return df_execution_err!("to_json: unsupported type: {}", values.data_type());
+    }
+
+    let mut output = StringBuilder::new();
+    let mut json = String::new();
+    for row in 0..values.len() {
+        if values.is_null(row) {
+            output.append_null();
+            continue;
+        }
+        json.clear();
+        generator.write(&mut json, &values, row)?;
+        output.append_value(&json);
+    }
+    Ok(ColumnarValue::Array(Arc::new(output.finish())))
+}
+
+/// json_array_length(json), returns the number of elements of the outermost
+/// json array, or null if the json is not a valid array.
+pub fn json_array_length(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let jsons = args[0].clone().into_array(1)?;
+    let lengths: Int32Array = as_string_array(&jsons)?
+        .iter()
+        .map(|json| match parse_json(json?) {
+            Ok(Some(JsonValue::Array(elements))) => Some(elements.len() as i32),
+            _ => None,
+        })
+        .collect();
+    Ok(ColumnarValue::Array(Arc::new(lengths)))
+}
+
+/// json_object_keys(json), returns all keys of the outermost json object, or
+/// null if the json is not a valid object.
+pub fn json_object_keys(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let jsons = args[0].clone().into_array(1)?;
+    let mut output = ListBuilder::new(StringBuilder::new());
+    for json in as_string_array(&jsons)? {
+        match json.map(parse_json) {
+            Some(Ok(Some(JsonValue::Object(object)))) => {
+                for (key, _) in &object {
+                    output.values().append_value(key);
+                }
+                output.append(true);
+            }
+            _ => output.append_null(),
+        }
+    }
+    Ok(ColumnarValue::Array(Arc::new(output.finish())))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum JsonValue<'a> {
+    Null,
+    Bool(bool),
+    /// text of a number, including non-numeric tokens like NaN and Infinity
+    Number(&'a str),
+    String(Cow<'a, str>),
+    Array(Vec<JsonValue<'a>>),
+    Object(Vec<(Cow<'a, str>, JsonValue<'a>)>),
+}
+
+/// the last value of the specified key wins, like spark does
+fn get_field<'a, 'b>(
+    object: &'b [(Cow<'a, str>, JsonValue<'a>)],
+    name: &str,
+) -> Option<&'b JsonValue<'a>> {
+    object
+        .iter()
+        .rev()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value)
+}
+
+impl<'a> JsonValue<'a> {
+    /// checks whether the value can be converted into the data type
+    fn matches(&self, data_type: &DataType) -> bool {
+        match (self, data_type) {
+            (JsonValue::Null, _) => true,
+            (JsonValue::Bool(_), DataType::Boolean) => true,
+            (JsonValue::Number(n), DataType::Int8) => parse_byte(n).is_some(),
+            (JsonValue::Number(n), DataType::Int16) => parse_integer::<i16>(n).is_some(),
+            (JsonValue::Number(n), DataType::Int32) => parse_integer::<i32>(n).is_some(),
+            (JsonValue::Number(n), DataType::Int64) => parse_integer::<i64>(n).is_some(),
+            (JsonValue::Number(_), DataType::Float32 | DataType::Float64) => true,
+            (JsonValue::String(s), DataType::Float32 | DataType::Float64) => {
+                parse_non_numeric(s).is_some()
+            }
+            (JsonValue::Number(n), &DataType::Decimal128(precision, scale)) => {
+                parse_decimal(n, precision, scale).is_some()
+            }
+            (JsonValue::String(s), &DataType::Decimal128(precision, scale)) => {
+                parse_decimal(&s.replace(',', ""), precision, scale).is_some()
+            }
+            (_, DataType::Utf8) => true,
+            (JsonValue::Array(elements), DataType::List(field)) => elements
+                .iter()
+                .all(|element| element.matches(field.data_type())),
+            (JsonValue::Object(object), DataType::Struct(fields)) => fields.iter().all(|field| {
+                get_field(object, field.name()).map_or(true, |v| v.matches(field.data_type()))
+            }),
+            (JsonValue::Object(object), DataType::Map(entries_field, _)) => {
+                let DataType::Struct(entries) = entries_field.data_type() else {
+                    return false;
+                };
+                entries[0].data_type() == &DataType::Utf8
+                    && object
+                        .iter()
+                        .all(|(_, value)| value.matches(entries[1].data_type()))
+            }
+            _ => false,
+        }
+    }
+
+    fn write_json(&self, output: &mut String) {
+        match self {
+            JsonValue::Null => output.push_str("null"),
+            JsonValue::Bool(b) => output.push_str(if *b { "true" } else { "false" }),
+            JsonValue::Number(n) => {
+                if is_integer_text(n) {
+                    match n.parse::<i128>() {
+                        Ok(i) => output.push_str(&i.to_string()),
+                        Err(_) => output.push_str(n),
+                    }
+                } else {
+                    write_double(output, n.parse::<f64>().unwrap_or(f64::NAN));
+                }
+            }
+            JsonValue::String(s) => write_json_string(output, s),
+            JsonValue::Array(elements) => {
+                output.push('[');
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        output.push(',');
+                    }
+                    element.write_json(output);
+                }
+                output.push(']');
+            }
+            JsonValue::Object(object) => {
+                output.push('{');
+                for (i, (key, value)) in object.iter().enumerate() {
+                    if i > 0 {
+                        output.push(',');
+                    }
+                    write_json_string(output, key);
+                    output.push(':');
+                    value.write_json(output);
+                }
+                output.push('}');
+            }
+        }
+    }
+}
+
+/// builds an array from json values, which must have been checked with
+/// `JsonValue::matches()`
+fn build_array(values: &[Option<&JsonValue>], data_type: &DataType) -> Result<ArrayRef> {
+    let non_null = |value: &&Option<&JsonValue>| !matches!(value, None | Some(JsonValue::Null));
+    macro_rules! build_primitive {
+        ($arraytype:ty, $convert:expr) => {{
+            let array: $arraytype = values
+                .iter()
+                .map(|value| match value {
+                    Some(JsonValue::Null) | None => None,
+                    Some(value) => $convert(*value),
+                })
+                .collect();
+            Arc::new(array) as ArrayRef
+        }};
+    }
+    Ok(match data_type {
+        DataType::Boolean => build_primitive!(BooleanArray, |v: &JsonValue| match v {
+            &JsonValue::Bool(b) => Some(b),
+            _ => None,
+        }),
+        DataType::Int8 => build_primitive!(Int8Array, |v: &JsonValue| match v {
+            JsonValue::Number(n) => parse_byte(n),
+            _ => None,
+        }),
+        DataType::Int16 => build_primitive!(Int16Array, |v: &JsonValue| match v {
+            JsonValue::Number(n) => parse_integer::<i16>(n),
+            _ => None,
+        }),
+        DataType::Int32 => build_primitive!(Int32Array, |v: &JsonValue| match v {
+            JsonValue::Number(n) => parse_integer::<i32>(n),
+            _ => None,
+        }),
+        DataType::Int64 => build_primitive!(Int64Array, |v: &JsonValue| match v {
+            JsonValue::Number(n) => parse_integer::<i64>(n),
+            _ => None,
+        }),
+        DataType::Float32 => build_primitive!(Float32Array, |v: &JsonValue| match v {
+            JsonValue::Number(n) => n.parse::<f32>().ok(),
+            JsonValue::String(s) => parse_non_numeric(s).map(|f| f as f32),
+            _ => None,
+        }),
+        DataType::Float64 => build_primitive!(Float64Array, |v: &JsonValue| match v {
+            JsonValue::Number(n) => n.parse::<f64>().ok(),
+            JsonValue::String(s) => parse_non_numeric(s),
+            _ => None,
+        }),
+        &DataType::Decimal128(precision, scale) => {
+            let array: Decimal128Array = values
+                .iter()
+                .map(|value| match value {
+                    Some(JsonValue::Number(n)) => parse_decimal(n, precision, scale),
+                    Some(JsonValue::String(s)) => {
+                        parse_decimal(&s.replace(',', ""), precision, scale)
+                    }
+                    _ => None,
+                })
+                .collect();
+            Arc::new(array.with_precision_and_scale(precision, scale)?)
+        }
+        DataType::Utf8 => build_primitive!(StringArray, |v: &JsonValue| match v {
+            JsonValue::String(s) => Some(s.to_string()),
+            other => {
+                let mut json = String::new();
+                other.write_json(&mut json);
+                Some(json)
+            }
+        }),
+        DataType::List(field) => {
+            let mut offsets = Vec::with_capacity(values.len() + 1);
+            let mut elements = vec![];
+            offsets.push(0);
+            for value in values {
+                if let Some(JsonValue::Array(array_elements)) = value {
+                    elements.extend(array_elements.iter().map(Some));
+                }
+                offsets.push(elements.len() as i32);
+            }
+            Arc::new(ListArray::try_new(
+                field.clone(),
+                OffsetBuffer::new(offsets.into()),
+                build_array(&elements, field.data_type())?,
+                Some(NullBuffer::from_iter(values.iter().map(|v| non_null(&v)))),
+            )?)
+        }
+        DataType::Struct(fields) => {
+            let columns = fields
+                .iter()
+                .map(|field| {
+                    let column = values
+                        .iter()
+                        .map(|value| match value {
+                            Some(JsonValue::Object(object)) => get_field(object, field.name()),
+                            _ => None,
+                        })
+                        .collect::<Vec<_>>();
+                    build_array(&column, field.data_type())
+                })
+                .collect::<Result<Vec<_>>>()?;
+            Arc::new(StructArray::try_new(
+                fields.clone(),
+                columns,
+                Some(NullBuffer::from_iter(values.iter().map(|v| non_null(&v)))),
+            )?)
+        }
+        DataType::Map(entries_field, sorted) => {
+            let DataType::Struct(entries) = entries_field.data_type() else {
+                return df_execution_err!("from_json: invalid map type: {data_type}");
+            };
+            let mut offsets = Vec::with_capacity(values.len() + 1);
+            let mut keys = StringBuilder::new();
+            let mut map_values = vec![];
+            offsets.push(0);
+            for value in values {
+                if let Some(JsonValue::Object(object)) = value {
+                    for (key, value) in object {
+                        keys.append_value(key);
+                        map_values.push(Some(value));
+                    }
+                }
+                offsets.push(map_values.len() as i32);
+            }
+            let entries_array = StructArray::try_new(
+                entries.clone(),
+                vec![
+                    Arc::new(keys.finish()),
+                    build_array(&map_values, entries[1].data_type())?,
+                ],
+                None,
+            )?;
+            Arc::new(MapArray::try_new(
+                entries_field.clone(),
+                OffsetBuffer::new(offsets.into()),
+                entries_array,
+                Some(NullBuffer::from_iter(values.iter().map(|v| non_null(&v)))),
+                *sorted,
+            )?)
+        }
+        other => return df_execution_err!("from_json: unsupported type: {other}"),
+    })
+}
+
+fn is_integer_text(n: &str) -> bool {
+    n.bytes().all(|b| b == b'-' || b.is_ascii_digit())
+}
+
+fn parse_integer<T: std::str::FromStr>(n: &str) -> Option<T> {
+    if !is_integer_text(n) {
+        return None;
+    }
+    n.parse::<T>().ok()
+}
+
+/// jackson accepts byte values in [-128, 255] and wraps them into signed
+fn parse_byte(n: &str) -> Option<i8> {
+    parse_integer::<i16>(n)
+        .filter(|&i| (-128..=255).contains(&i))
+        .map(|i| i as i8)
+}
+
+/// non-numeric float values written as strings
+fn parse_non_numeric(s: &str) -> Option<f64> {
+    match s {
+        "NaN" => Some(f64::NAN),
+        "Infinity" | "+Infinity" | "INF" | "+INF" => Some(f64::INFINITY),
+        "-Infinity" | "-INF" => Some(f64::NEG_INFINITY),
+        _ => None,
+    }
+}
+
+/// parses decimal text into unscaled value with HALF_UP rounding, returns None
+/// if the text is invalid or the value overflows the precision
+fn parse_decimal(s: &str, precision: u8, scale: i8) -> Option<i128> {
+    let (negative, s) = match s.as_bytes().first()? {
+        b'-' => (true, &s[1..]),
+        b'+' => (false, &s[1..]),
+        _ => (false, s),
+    };
+    let (mantissa, exponent) = match s.find(['e', 'E']) {
+        Some(pos) => (&s[..pos], s[pos + 1..].parse::<i32>().ok()?),
+        None => (s, 0),
+    };
+    let (int_part, frac_part) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    if (int_part.is_empty() && frac_part.is_empty())
+        || !int_part
+            .bytes()
+            .chain(frac_part.bytes())
+            .all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+
+    // value = digits * 10^(exponent - frac_len), shift it to the target scale
+    let digits = format!("{int_part}{frac_part}");
+    let digits = digits.trim_start_matches('0');
+    let shift = exponent as i64 - frac_part.len() as i64 + scale as i64;
+    let kept_len = digits.len() as i64 + shift.min(0);
+    let mut unscaled: i128 = 0;
+    if kept_len >= 0 {
+        let kept_len = kept_len as usize;
+        for b in digits[..kept_len].bytes() {
+            unscaled = unscaled.checked_mul(10)?.checked_add((b - b'0') as i128)?;
+        }
+        if shift > 0 {
+            unscaled = unscaled.checked_mul(10i128.checked_pow(shift.try_into().ok()?)?)?;
+        } else if digits.as_bytes().get(kept_len).is_some_and(|&b| b >= b'5') {
+            unscaled += 1;
+        }
+    }
+    if unscaled >= 10i128.pow(precision as u32) {
+        return None;
+    }
+    Some(if negative { -unscaled } else { unscaled })
+}
+
+struct JsonGenerator<'a> {
+    tz: SparkTimeZone,
+    tz_id: &'a str,
+    timestamp_formatter: DateTimeFormatter,
+}
+
+impl<'a> JsonGenerator<'a> {
+    fn write(&self, output: &mut String, array: &ArrayRef, row: usize) -> Result<()> {
+        if array.is_null(row) {
+            output.push_str("null");
+            return Ok(());
+        }
+        match array.data_type() {
+            DataType::Boolean => {
+                let value = array.as_boolean().value(row);
+                output.push_str(if value { "true" } else { "false" });
+            }
+            DataType::Int8 => {
+                output.push_str(&array.as_primitive::<Int8Type>().value(row).to_string())
+            }
+            DataType::Int16 => {
+                output.push_str(&array.as_primitive::<Int16Type>().value(row).to_string())
+            }
+            DataType::Int32 => {
+                output.push_str(&array.as_primitive::<Int32Type>().value(row).to_string())
+            }
+            DataType::Int64 => {
+                output.push_str(&array.as_primitive::<Int64Type>().value(row).to_string())
+            }
+            DataType::Float32 => {
+                write_float(output, array.as_primitive::<Float32Type>().value(row))
+            }
+            DataType::Float64 => {
+                write_double(output, array.as_primitive::<Float64Type>().value(row))
+            }
+            &DataType::Decimal128(_, scale) => {
+                let unscaled = array.as_primitive::<Decimal128Type>().value(row);
+                output.push_str(&decimal_to_string(unscaled, scale));
+            }
+            DataType::Utf8 => write_json_string(output, array.as_string::<i32>().value(row)),
+            DataType::Date32 => {
+                let days = array.as_primitive::<Date32Type>().value(row);
+                match days_to_date(days) {
+                    Some(date) => write_json_string(output, &date.format("%Y-%m-%d").to_string()),
+                    None => return df_execution_err!("to_json: date out of range: {days}"),
+                }
+            }
+            DataType::Timestamp(TimeUnit::Microsecond, _) => {
+                let micros = array.as_primitive::<TimestampMicrosecondType>().value(row);
+                match self
+                    .timestamp_formatter
+                    .format(micros, &self.tz, self.tz_id)
+                {
+                    Some(formatted) => write_json_string(output, &formatted),
+                    None => return df_execution_err!("to_json: timestamp out of range: {micros}"),
+                }
+            }
+            DataType::List(_) => {
+                let elements = array.as_list::<i32>().value(row);
+                output.push('[');
+                for i in 0..elements.len() {
+                    if i > 0 {
+                        output.push(',');
+                    }
+                    self.write(output, &elements, i)?;
+                }
+                output.push(']');
+            }
+            DataType::Struct(fields) => {
+                let struct_array = array.as_struct();
+                let mut first = true;
+                output.push('{');
+                for (field, column) in fields.iter().zip(struct_array.columns()) {
+                    if column.is_null(row) {
+                        continue;
+                    }
+                    if !std::mem::take(&mut first) {
+                        output.push(',');
+                    }
+                    write_json_string(output, field.name());
+                    output.push(':');
+                    self.write(output, column, row)?;
+                }
+                output.push('}');
+            }
+            DataType::Map(..) => {
+                let entries = array.as_map().value(row);
+                let keys = entries.column(0);
+                let values = entries.column(1);
+                output.push('{');
+                for i in 0..entries.len() {
+                    if i > 0 {
+                        output.push(',');
+                    }
+                    // keys are written as strings
+                    let mut key = String::new();
+                    self.write(&mut key, keys, i)?;
+                    if keys.data_type() == &DataType::Utf8 {
+                        output.push_str(&key);
+                    } else {
+                        write_json_string(output, &key);
+                    }
+                    output.push(':');
+                    self.write(output, values, i)?;
+                }
+                output.push('}');
+            }
+            other => return df_execution_err!("to_json: unsupported type: {other}"),
+        }
+        Ok(())
+    }
+}
+
+/// writes quoted string, escaping like jackson
+fn write_json_string(output: &mut String, s: &str) {
+    output.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\u{08}' => output.push_str("\\b"),
+            '\u{0c}' => output.push_str("\\f"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if (c as u32) < 0x20 => output.push_str(&format!("\\u{:04X}", c as u32)),
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+}
+
+fn write_double(output: &mut String, value: f64) {
+    if value.is_finite() {
+        output.push_str(&java_float_to_string(format!("{value:e}")));
+    } else {
+        write_json_string(output, &java_non_finite_to_string(value));
+    }
+}
+
+fn write_float(output: &mut String, value: f32) {
+    if value.is_finite() {
+        output.push_str(&java_float_to_string(format!("{value:e}")));
+    } else {
+        write_json_string(output, &java_non_finite_to_string(value as f64));
+    }
+}
+
+fn java_non_finite_to_string(value: f64) -> String {
+    match value {
+        v if v.is_nan() => "NaN".to_owned(),
+        v if v > 0.0 => "Infinity".to_owned(),
+        _ => "-Infinity".to_owned(),
+    }
+}
+
+/// formats shortest scientific representation like java's Double.toString()
+fn java_float_to_string(sci: String) -> String {
+    let (mantissa, exponent) = sci.split_once('e').expect("scientific format");
+    let exponent = exponent.parse::<i32>().expect("scientific exponent");
+    let (sign, mantissa) = match mantissa.strip_prefix('-') {
+        Some(mantissa) => ("-", mantissa),
+        None => ("", mantissa),
+    };
+    let digits = mantissa.replace('.', "");
+    if digits == "0" {
+        return format!("{sign}0.0");
+    }
+    if (-3..7).contains(&exponent) {
+        if exponent < 0 {
+            let zeros = "0".repeat((-exponent - 1) as usize);
+            return format!("{sign}0.{zeros}{digits}");
+        }
+        let int_len = exponent as usize + 1;
+        if digits.len() <= int_len {
+            let zeros = "0".repeat(int_len - digits.len());
+            return format!("{sign}{digits}{zeros}.0");
+        }
+        return format!("{sign}{}.{}", &digits[..int_len], &digits[int_len..]);
+    }
+    let frac = if digits.len() > 1 { &digits[1..] } else { "0" };
+    format!("{sign}{}.{frac}E{exponent}", &digits[..1])
+}
+
+/// formats decimal like java's BigDecimal.toString()
+fn decimal_to_string(unscaled: i128, scale: i8) -> String {
+    let sign = if unscaled < 0 { "-" } else { "" };
+    let digits = unscaled.unsigned_abs().to_string();
+    let scale = scale as i64;
+    let adjusted = digits.len() as i64 - 1 - scale;
+    if scale >= 0 && adjusted >= -6 {
+        if scale == 0 {
+            return format!("{sign}{digits}");
+        }
+        let scale = scale as usize;
+        if digits.len() > scale {
+            let int_len = digits.len() - scale;
+            return format!("{sign}{}.{}", &digits[..int_len], &digits[int_len..]);
+        }
+        let zeros = "0".repeat(scale - digits.len());
+        return format!("{sign}0.{zeros}{digits}");
+    }
+    let frac = if digits.len() > 1 {
+        format!(".{}", &digits[1..])
+    } else {
+        String::new()
+    };
+    format!("{sign}{}{frac}E{adjusted:+}", &digits[..1])
+}
+
+/// parses a json record, returns Ok(None) for empty record and Err(()) for
+/// malformed record. contents after the first value are ignored.
+fn parse_json(json: &str) -> std::result::Result<Option<JsonValue<'_>>, ()> {
+    let mut parser = JsonParser { json, pos: 0 };
+    parser.skip_whitespaces();
+    if parser.pos == json.len() {
+        return Ok(None);
+    }
+    parser.parse_value().map(Some)
+}
+
+struct JsonParser<'a> {
+    json: &'a str,
+    pos: usize,
+}
+
+impl<'a> JsonParser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.json.as_bytes().get(self.pos).copied()
+    }
+
+    fn skip_whitespaces(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> std::result::Result<(), ()> {
+        if !self.json[self.pos..].starts_with(keyword) {
+            return Err(());
+        }
+        self.pos += keyword.len();
+        Ok(())
+    }
+
+    fn parse_value(&mut self) -> std::result::Result<JsonValue<'a>, ()> {
+        self.skip_whitespaces();
+        match self.peek().ok_or(())? {
+            b'{' => self.parse_object(),
+            b'[' => self.parse_array(),
+            quote @ (b'"' | b'\'') => Ok(JsonValue::String(self.parse_string(quote)?)),
+            b't' => self.expect_keyword("true").map(|_| JsonValue::Bool(true)),
+            b'f' => self.expect_keyword("false").map(|_| JsonValue::Bool(false)),
+            b'n' => self.expect_keyword("null").map(|_| JsonValue::Null),
+            _ => self.parse_number(),
+        }
+    }
+
+    fn parse_object(&mut self) -> std::result::Result<JsonValue<'a>, ()> {
+        let mut object = vec![];
+        self.pos += 1; // skip '{'
+        self.skip_whitespaces();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(JsonValue::Object(object));
+        }
+        loop {
+            self.skip_whitespaces();
+            let key = match self.peek() {
+                Some(quote @ (b'"' | b'\'')) => self.parse_string(quote)?,
+                _ => return Err(()),
+            };
+            self.skip_whitespaces();
+            if self.peek() != Some(b':') {
+                return Err(());
+            }
+            self.pos += 1;
+            object.push((key, self.parse_value()?));
+            self.skip_whitespaces();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(JsonValue::Object(object));
+                }
+                _ => return Err(()),
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> std::result::Result<JsonValue<'a>, ()> {
+        let mut elements = vec![];
+        self.pos += 1; // skip '['
+        self.skip_whitespaces();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(JsonValue::Array(elements));
+        }
+        loop {
+            elements.push(self.parse_value()?);
+            self.skip_whitespaces();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(JsonValue::Array(elements));
+                }
+                _ => return Err(()),
+            }
+        }
+    }
+
+    fn parse_string(&mut self, quote: u8) -> std::result::Result<Cow<'a, str>, ()> {
+        self.pos += 1; // skip opening quote
+        let start = self.pos;
+        let bytes = self.json.as_bytes();
+
+        // fast path: no escapes
+        while let Some(&b) = bytes.get(self.pos) {
+            if b == quote {
+                self.pos += 1;
+                return Ok(Cow::Borrowed(&self.json[start..self.pos - 1]));
+            }
+            if b == b'\\' {
+                break;
+            }
+            if b < 0x20 {
+                return Err(());
+            }
+            self.pos += 1;
+        }
+
+        let mut s = String::from(&self.json[start..self.pos]);
+        loop {
+            let c = self.json[self.pos..].chars().next().ok_or(())?;
+            self.pos += c.len_utf8();
+            match c {
+                c if c as u32 == quote as u32 => return Ok(Cow::Owned(s)),
+                c if (c as u32) < 0x20 => return Err(()),
+                '\\' => {
+                    let escaped = self.peek().ok_or(())?;
+                    self.pos += 1;
+                    match escaped {
+                        b'"' | b'\\' | b'/' => s.push(escaped as char),
+                        b'\'' if quote == b'\'' => s.push('\''),
+                        b'b' => s.push('\u{08}'),
+                        b'f' => s.push('\u{0c}'),
+                        b'n' => s.push('\n'),
+                        b'r' => s.push('\r'),
+                        b't' => s.push('\t'),
+                        b'u' => {
+                            let mut code = self.parse_hex4()?;
+                            if (0xd800..0xdc00).contains(&code)
+                                && self.json[self.pos..].starts_with("\\u")
+                            {
+                                self.pos += 2;
+                                let low = self.parse_hex4()?;
+                                if !(0xdc00..0xe000).contains(&low) {
+                                    return Err(());
+                                }
+                                code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                            }
+                            s.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                        }
+                        _ => return Err(()),
+                    }
+                }
+                c => s.push(c),
+            }
+        }
+    }
+
+    fn parse_hex4(&mut self) -> std::result::Result<u32, ()> {
+        let hex = self.json.get(self.pos..self.pos + 4).ok_or(())?;
+        let code = u32::from_str_radix(hex, 16).map_err(|_| ())?;
+        self.pos += 4;
+        Ok(code)
+    }
+
+    fn parse_number(&mut self) -> std::result::Result<JsonValue<'a>, ()> {
+        let start = self.pos;
+        for token in ["NaN", "Infinity", "+Infinity", "-Infinity"] {
+            if self.json[start..].starts_with(token) {
+                self.pos += token.len();
+                return Ok(JsonValue::Number(token));
+            }
+        }
+        let bytes = self.json.as_bytes();
+        let count_digits = |pos: usize| {
+            bytes[pos..]
+                .iter()
+                .take_while(|b| b.is_ascii_digit())
+                .count()
+        };
+
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+        }
+        let int_len = count_digits(self.pos);
+        if int_len == 0 || (int_len > 1 && bytes[self.pos] == b'0') {
+            return Err(()); // leading zeros are not allowed
+        }
+        self.pos += int_len;
+        if self.peek() == Some(b'.') {
+            let frac_len = count_digits(self.pos + 1);
+            if frac_len == 0 {
+                return Err(());
+            }
+            self.pos += 1 + frac_len;
+        }
+        if matches!(self.peek(), Some(b'e' | b'E')) {
+            self.pos += 1;
+            if matches!(self.peek(), Some(b'+' | b'-')) {
+                self.pos += 1;
+            }
+            let exp_len = count_digits(self.pos);
+            if exp_len == 0 {
+                return Err(());
+            }
+            self.pos += exp_len;
+        }
+        Ok(JsonValue::Number(&self.json[start..self.pos]))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{Array, ArrayRef, Int32Array, StringArray, StructArray},
+        datatypes::{DataType, Field, Fields},
+    };
+    use datafusion::{
+        common::{Result, ScalarValue},
+        physical_plan::ColumnarValue,
+    };
+
+    use super::*;
+
+    fn from_json_test(
+        jsons: Vec<Option<&str>>,
+        mode: &str,
+        return_type: &DataType,
+    ) -> Result<ArrayRef> {
+        let args = vec![
+            ColumnarValue::Array(Arc::new(StringArray::from(jsons))),
+            ColumnarValue::Scalar(ScalarValue::from(mode)),
+        ];
+        from_json(&args, return_type)?.into_array(1)
+    }
+
+    fn to_json_test(array: ArrayRef) -> Result<Vec<Option<String>>> {
+        let args = vec![
+            ColumnarValue::Array(array),
+            ColumnarValue::Scalar(ScalarValue::from("UTC")),
+        ];
+        let output = to_json(&args)?.into_array(1)?;
+        Ok(output
+            .as_string::<i32>()
+            .iter()
+            .map(|s| s.map(|s| s.to_owned()))
+            .collect())
+    }
+
+    #[test]
+    fn test_from_json_struct() -> Result<()> {
+        let fields = Fields::from(vec![
+            Field::new("a", DataType::Int32, true),
+            Field::new("b", DataType::Utf8, true),
+            Field::new("c", DataType::Decimal128(5, 2), true),
+        ]);
+        let return_type = DataType::Struct(fields.clone());
+        let output = from_json_test(
+            vec![
+                Some(r#"{"a": 1, "b": "x", "c": 1.005}"#),
+                Some(r#"{'b': {"k": [1, 2.50, true]}, "a": 2, "a": 3}"#),
+                Some(r#"{"a": "not int", "b": null, "c": "12.3"}"#),
+                Some(r#"{"a": 1, "b": "#),
+                Some(r#"[1, 2]"#),
+                Some(""),
+                None,
+            ],
+            "PERMISSIVE",
+            &return_type,
+        )?;
+        let expected: ArrayRef = Arc::new(StructArray::try_new(
+            fields,
+            vec![
+                Arc::new(Int32Array::from(vec![
+                    Some(1),
+                    Some(3),
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                ])),
+                Arc::new(StringArray::from(vec![
+                    Some("x"),
+                    Some(r#"{"k":[1,2.5,true]}"#),
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                ])),
+                Arc::new(
+                    Decimal128Array::from(vec![
+                        Some(101),
+                        None,
+                        Some(1230),
+                        None,
+                        None,
+                        None,
+                        None,
+                    ])
+                    .with_precision_and_scale(5, 2)?,
+                ),
+            ],
+            Some(vec![true, true, true, true, true, false, false].into()),
+        )?);
+        assert_eq!(&output, &expected);
+
+        // failfast mode
+        assert!(from_json_test(vec![Some(r#"{"a": 1}"#)], "FAILFAST", &return_type).is_ok());
+        assert!(from_json_test(vec![Some(r#"{"a": 1.5}"#)], "FAILFAST", &return_type).is_err());
+        assert!(from_json_test(vec![Some(r#"{"a": "#)], "FAILFAST", &return_type).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_json_array_map() -> Result<()> {
+        let return_type = DataType::List(Arc::new(Field::new_list_field(DataType::Float64, true)));
+        let output = from_json_test(
+            vec![
+                Some("[1, 2.5, null, NaN, \"-Infinity\"]"),
+                Some("[1, \"x\"]"),
+                Some("{\"a\": 1}"),
+                Some("null"),
+            ],
+            "PERMISSIVE",
+            &return_type,
+        )?;
+        assert_eq!(
+            to_json_test(output)?,
+            vec![
+                Some(r#"[1.0,2.5,null,"NaN","-Infinity"]"#.to_owned()),
+                None,
+                None,
+                None,
+            ],
+        );
+
+        // single object as array of struct
+        let struct_type =
+            DataType::Struct(Fields::from(vec![Field::new("a", DataType::Int64, true)]));
+        let return_type = DataType::List(Arc::new(Field::new_list_field(struct_type, true)));
+        let output = from_json_test(
+            vec![Some(r#"{"a": 1}"#), Some(r#"[{"a": 2}, {}, null]"#)],
+            "PERMISSIVE",
+            &return_type,
+        )?;
+        assert_eq!(
+            to_json_test(output)?,
+            vec![
+                Some(r#"[{"a":1}]"#.to_owned()),
+                Some(r#"[{"a":2},{},null]"#.to_owned()),
+            ],
+        );
+
+        let return_type = DataType::Map(
+            Arc::new(Field::new(
+                "entries",
+                DataType::Struct(Fields::from(vec![
+                    Field::new("key", DataType::Utf8, false),
+                    Field::new("value", DataType::Int32, true),
+                ])),
+                false,
+            )),
+            false,
+        );
+        let output = from_json_test(
+            vec![
+                Some(r#"{"x": 1, "y": null, "x": 2}"#),
+                Some(r#"{"x": "1"}"#),
+                Some(r#"{}"#),
+            ],
+            "PERMISSIVE",
+            &return_type,
+        )?;
+        assert_eq!(
+            to_json_test(output)?,
+            vec![
+                Some(r#"{"x":1,"y":null,"x":2}"#.to_owned()),
+                None,
+                Some(r#"{}"#.to_owned()),
+            ],
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_json() -> Result<()> {
+        let fields = Fields::from(vec![
+            Field::new("s", DataType::Utf8, true),
+            Field::new("f", DataType::Float64, true),
+            Field::new("d", DataType::Decimal128(10, 8), true),
+            Field::new("t", DataType::Timestamp(TimeUnit::Microsecond, None), true),
+            Field::new("dt", DataType::Date32, true),
+        ]);
+        let array: ArrayRef = Arc::new(StructArray::try_new(
+            fields,
+            vec![
+                Arc::new(StringArray::from(vec![Some("a\"b\\c\n\u{1}"), None, None])),
+                Arc::new(Float64Array::from(vec![Some(1e7), Some(0.001), Some(-0.0)])),
+                Arc::new(
+                    Decimal128Array::from(vec![Some(1), Some(123456789), None])
+                        .with_precision_and_scale(10, 8)?,
+                ),
+                Arc::new(arrow::array::TimestampMicrosecondArray::from(vec![
+                    Some(1_000_000_000_123_000i64),
+                    None,
+                    None,
+                ])),
+                Arc::new(arrow::array::Date32Array::from(vec![
+                    None,
+                    Some(19000),
+                    None,
+                ])),
+            ],
+            Some(vec![true, true, false].into()),
+        )?);
+        assert_eq!(
+            to_json_test(array)?,
+            vec![
+                Some(
+                    r#"{"s":"a\"b\\c\n\u0001","f":1.0E7,"d":1E-8,"t":"2001-09-09T01:46:40.123Z"}"#
+                        .to_owned()
+                ),
+                Some(r#"{"f":0.001,"d":1.23456789,"dt":"2022-01-08"}"#.to_owned()),
+                None,
+            ],
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_array_length_object_keys() -> Result<()> {
+        let jsons = ColumnarValue::Array(Arc::new(StringArray::from(vec![
+            Some(r#"[1, [2, 3], {"a": 4}]"#),
+            Some(r#"{"b": 1, "a": {"c": 2}, "b": 3}"#),
+            Some(r#"[1, 2"#),
+            Some(""),
+            None,
+        ])));
+        let lengths = json_array_length(&[jsons.clone()])?.into_array(1)?;
+        assert_eq!(
+            lengths
+                .as_primitive::<Int32Type>()
+                .iter()
+                .collect::<Vec<_>>(),
+            vec![Some(3), None, None, None, None],
+        );
+        let keys = json_object_keys(&[jsons])?.into_array(1)?;
+        let keys = keys.as_list::<i32>();
+        assert_eq!(keys.null_count(), 4);
+        assert_eq!(
+            keys.value(1).as_string::<i32>().iter().collect::<Vec<_>>(),
+            vec![Some("b"), Some("a"), Some("b")],
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,622 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Spark map functions over MapArray. functions building maps take the value
+//! of spark.sql.mapKeyDedupPolicy as the last argument.
+
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    ops::Range,
+    sync::Arc,
+};
+
+use arrow::{
+    array::{
+        Array, ArrayBuilder, ArrayRef, ListArray, MapArray, StringBuilder, StructArray, UInt32Array,
+    },
+    buffer::{NullBuffer, OffsetBuffer, ScalarBuffer},
+    compute::{concat, take},
+    datatypes::{DataType, Field, Fields},
+    row::{RowConverter, Rows, SortField},
+    util::display::array_value_to_string,
+};
+use datafusion::{
+    common::{
+        cast::{as_list_array, as_map_array, as_string_array, as_struct_array},
+        Result, ScalarValue,
+    },
+    physical_plan::ColumnarValue,
+};
+use datafusion_ext_commons::df_execution_err;
+
+use crate::{
+    spark_arrays::normalize,
+    spark_regexp::{JavaRegex, RegexCache},
+};
+
+/// map_keys(map)
+pub fn map_keys(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(args)?;
+    let map = as_map_array(&arrays[0])?;
+    let field = Field::new_list_field(map.key_type().clone(), false);
+    Ok(ColumnarValue::Array(Arc::new(ListArray::try_new(
+        Arc::new(field),
+        map.offsets().clone(),
+        map.keys().clone(),
+        map.nulls().cloned(),
+    )?)))
+}
+
+/// map_values(map)
+pub fn map_values(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(args)?;
+    let map = as_map_array(&arrays[0])?;
+    let field = Field::new_list_field(map.value_type().clone(), value_nullable(map));
+    Ok(ColumnarValue::Array(Arc::new(ListArray::try_new(
+        Arc::new(field),
+        map.offsets().clone(),
+        map.values().clone(),
+        map.nulls().cloned(),
+    )?)))
+}
+
+/// map_entries(map), returns array of (key, value) structs
+pub fn map_entries(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(args)?;
+    let map = as_map_array(&arrays[0])?;
+    let entries = map.entries().clone();
+    let field = Field::new_list_field(entries.data_type().clone(), false);
+    Ok(ColumnarValue::Array(Arc::new(ListArray::try_new(
+        Arc::new(field),
+        map.offsets().clone(),
+        Arc::new(entries),
+        map.nulls().cloned(),
+    )?)))
+}
+
+/// map_from_arrays(keys, values, dedup_policy)
+pub fn map_from_arrays(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let policy = MapKeyDedupPolicy::try_from_arg(&args[2])?;
+    let arrays = args_to_arrays(&args[..2])?;
+    let keys = as_list_array(&arrays[0])?;
+    let values = as_list_array(&arrays[1])?;
+    let nullable = match values.data_type() {
+        DataType::List(field) => field.is_nullable(),
+        _ => true,
+    };
+
+    let mut builder = MapBuilder::try_new(keys.values(), values.values(), nullable, policy)?;
+    for row in 0..keys.len() {
+        if keys.is_null(row) || values.is_null(row) {
+            builder.append_null();
+            continue;
+        }
+        let (key_range, value_range) = (element_range(keys, row), element_range(values, row));
+        if key_range.len() != value_range.len() {
+            return df_execution_err!(
+                "The key array and value array of MapData must have the same length."
+            );
+        }
+        builder.append_entries(key_range.zip(value_range))?;
+    }
+    builder.finish()
+}
+
+/// map_from_entries(array_of_entries, dedup_policy), returns null if any entry
+/// is null
+pub fn map_from_entries(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let policy = MapKeyDedupPolicy::try_from_arg(&args[1])?;
+    let arrays = args_to_arrays(&args[..1])?;
+    let list = as_list_array(&arrays[0])?;
+    let entries = as_struct_array(list.values())?;
+    let nullable = entries.fields()[1].is_nullable();
+
+    let mut builder = MapBuilder::try_new(entries.column(0), entries.column(1), nullable, policy)?;
+    for row in 0..list.len() {
+        let range = element_range(list, row);
+        if list.is_null(row) || range.clone().any(|i| entries.is_null(i)) {
+            builder.append_null();
+            continue;
+        }
+        builder.append_entries(range.map(|i| (i, i)))?;
+    }
+    builder.finish()
+}
+
+/// map_concat(map1, map2, ..., dedup_policy), returns null if any map is null
+pub fn map_concat(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let policy = MapKeyDedupPolicy::try_from_arg(&args[args.len() - 1])?;
+    let arrays = args_to_arrays(&args[..args.len() - 1])?;
+    let maps = arrays
+        .iter()
+        .map(|array| as_map_array(array))
+        .collect::<Result<Vec<_>>>()?;
+    let keys = concat(&maps.iter().map(|m| m.keys().as_ref()).collect::<Vec<_>>())?;
+    let values = concat(&maps.iter().map(|m| m.values().as_ref()).collect::<Vec<_>>())?;
+    let bases = maps
+        .iter()
+        .scan(0, |base, map| {
+            let cur = *base;
+            *base += map.keys().len();
+            Some(cur)
+        })
+        .collect::<Vec<_>>();
+    let nullable = maps.iter().any(|map| value_nullable(map));
+
+    let mut builder = MapBuilder::try_new(&keys, &values, nullable, policy)?;
+    for row in 0..maps[0].len() {
+        if maps.iter().any(|map| map.is_null(row)) {
+            builder.append_null();
+            continue;
+        }
+        let entries = maps.iter().zip(&bases).flat_map(|(map, &base)| {
+            let offsets = map.value_offsets();
+            let range = offsets[row] as usize + base..offsets[row + 1] as usize + base;
+            range.map(|i| (i, i))
+        });
+        builder.append_entries(entries)?;
+    }
+    builder.finish()
+}
+
+/// str_to_map(text, pair_delim, key_value_delim, dedup_policy), delimiters
+/// are java regexes
+pub fn str_to_map(
+    args: &[ColumnarValue],
+    caches: &(RegexCache, RegexCache),
+) -> Result<ColumnarValue> {
+    let policy = MapKeyDedupPolicy::try_from_arg(&args[3])?;
+    let arrays = args_to_arrays(&args[..3])?;
+    let texts = as_string_array(&arrays[0])?;
+    let pair_delims = as_string_array(&arrays[1])?;
+    let key_value_delims = as_string_array(&arrays[2])?;
+
+    let mut keys = StringBuilder::new();
+    let mut values = StringBuilder::new();
+    let mut ranges = Vec::with_capacity(texts.len());
+    for row in 0..texts.len() {
+        if texts.is_null(row) || pair_delims.is_null(row) || key_value_delims.is_null(row) {
+            ranges.push(None);
+            continue;
+        }
+        let pair_regex = caches.0.get(pair_delims.value(row))?;
+        let key_value_regex = caches.1.get(key_value_delims.value(row))?;
+        let start = keys.len();
+        for pair in java_split(&pair_regex, texts.value(row), -1) {
+            let mut key_value = java_split(&key_value_regex, pair, 2).into_iter();
+            keys.append_option(key_value.next());
+            values.append_option(key_value.next());
+        }
+        ranges.push(Some(start..keys.len()));
+    }
+
+    let (keys, values): (ArrayRef, ArrayRef) = (Arc::new(keys.finish()), Arc::new(values.finish()));
+    let mut builder = MapBuilder::try_new(&keys, &values, true, policy)?;
+    for range in ranges {
+        match range {
+            Some(range) => builder.append_entries(range.map(|i| (i, i)))?,
+            None => builder.append_null(),
+        }
+    }
+    builder.finish()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum MapKeyDedupPolicy {
+    Exception,
+    LastWin,
+}
+
+impl MapKeyDedupPolicy {
+    fn try_from_arg(arg: &ColumnarValue) -> Result<Self> {
+        match arg {
+            ColumnarValue::Scalar(ScalarValue::Utf8(Some(policy))) => match policy.as_str() {
+                "EXCEPTION" => Ok(Self::Exception),
+                "LAST_WIN" => Ok(Self::LastWin),
+                other => df_execution_err!("unsupported map key dedup policy: {other}"),
+            },
+            other => df_execution_err!("map key dedup policy must be a literal string: {other:?}"),
+        }
+    }
+}
+
+/// Builds map array by taking keys and values from the source arrays. keys are
+/// checked against null and duplicated keys are handled with the dedup policy,
+/// in which the latest value of a duplicated key replaces the previous one
+/// while keeping the key's first position.
+struct MapBuilder {
+    keys: ArrayRef,
+    values: ArrayRef,
+    value_nullable: bool,
+    policy: MapKeyDedupPolicy,
+    key_rows: Rows,
+    key_indices: Vec<u32>,
+    value_indices: Vec<u32>,
+    offsets: Vec<i32>,
+    valids: Vec<bool>,
+}
+
+impl MapBuilder {
+    fn try_new(
+        keys: &ArrayRef,
+        values: &ArrayRef,
+        value_nullable: bool,
+        policy: MapKeyDedupPolicy,
+    ) -> Result<Self> {
+        let keys = normalize(keys);
+        let converter = RowConverter::new(vec![SortField::new(keys.data_type().clone())])?;
+        let key_rows = converter.convert_columns(&[keys.clone()])?;
+        Ok(Self {
+            keys,
+            values: values.clone(),
+            value_nullable,
+            policy,
+            key_rows,
+            key_indices: vec![],
+            value_indices: vec![],
+            offsets: vec![0],
+            valids: vec![],
+        })
+    }
+
+    fn append_entries(&mut self, entries: impl Iterator<Item = (usize, usize)>) -> Result<()> {
+        let mut positions = HashMap::new();
+        for (key_idx, value_idx) in entries {
+            if self.keys.is_null(key_idx) {
+                return df_execution_err!("Cannot use null as map key.");
+            }
+            match positions.entry(self.key_rows.row(key_idx)) {
+                Entry::Occupied(position) => match self.policy {
+                    MapKeyDedupPolicy::Exception => {
+                        let key = array_value_to_string(&self.keys, key_idx)?;
+                        return df_execution_err!(
+                            "Duplicate map key {key} was found, please check the input data. If \
+                             you want to remove the duplicated keys, you can set \
+                             spark.sql.mapKeyDedupPolicy to LAST_WIN so that the key inserted \
+                             at last takes precedence."
+                        );
+                    }
+                    MapKeyDedupPolicy::LastWin => {
+                        self.value_indices[*position.get()] = value_idx as u32;
+                    }
+                },
+                Entry::Vacant(position) => {
+                    position.insert(self.key_indices.len());
+                    self.key_indices.push(key_idx as u32);
+                    self.value_indices.push(value_idx as u32);
+                }
+            }
+        }
+        self.offsets.push(self.key_indices.len() as i32);
+        self.valids.push(true);
+        Ok(())
+    }
+
+    fn append_null(&mut self) {
+        self.offsets.push(self.key_indices.len() as i32);
+        self.valids.push(false);
+    }
+
+    fn finish(self) -> Result<ColumnarValue> {
+        let keys = take(&self.keys, &UInt32Array::from(self.key_indices), None)?;
+        let values = take(&self.values, &UInt32Array::from(self.value_indices), None)?;
+        let fields = Fields::from(vec![
+            Field::new("key", keys.data_type().clone(), false),
+            Field::new("value", values.data_type().clone(), self.value_nullable),
+        ]);
+        let entries = StructArray::try_new(fields.clone(), vec![keys, values], None)?;
+        Ok(ColumnarValue::Array(Arc::new(MapArray::try_new(
+            Arc::new(Field::new("entries", DataType::Struct(fields), false)),
+            OffsetBuffer::new(ScalarBuffer::from(self.offsets)),
+            entries,
+            Some(NullBuffer::from(self.valids)),
+            false,
+        )?)))
+    }
+}
+
+fn args_to_arrays(args: &[ColumnarValue]) -> Result<Vec<ArrayRef>> {
+    let num_rows = args
+        .iter()
+        .find_map(|arg| match arg {
+            ColumnarValue::Array(array) => Some(array.len()),
+            ColumnarValue::Scalar(_) => None,
+        })
+        .unwrap_or(1);
+    args.iter()
+        .map(|arg| arg.clone().into_array(num_rows))
+        .collect()
+}
+
+fn element_range(list: &ListArray, row: usize) -> Range<usize> {
+    let offsets = list.value_offsets();
+    offsets[row] as usize..offsets[row + 1] as usize
+}
+
+fn value_nullable(map: &MapArray) -> bool {
+    map.entries().fields()[1].is_nullable()
+}
+
+/// splits like java's String.split(regex, limit), in which a zero-width match
+/// at the beginning never produces a leading empty substring
+fn java_split<'a>(regex: &JavaRegex, s: &'a str, limit: i32) -> Vec<&'a str> {
+    let mut parts = vec![];
+    let mut start = 0;
+    for m in regex.find_iter(s) {
+        if limit > 0 && parts.len() as i32 == limit - 1 {
+            break;
+        }
+        if m.end == 0 {
+            continue;
+        }
+        parts.push(&s[start..m.start]);
+        start = m.end;
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{Array, ArrayRef, Int32Array, ListArray, StringArray},
+        datatypes::{Float64Type, Int32Type},
+    };
+    use datafusion::{
+        common::{
+            cast::{as_list_array, as_map_array},
+            Result, ScalarValue,
+        },
+        physical_plan::ColumnarValue,
+    };
+
+    use crate::{spark_maps::*, spark_regexp::RegexCache};
+
+    fn maps(entries: Vec<Option<Vec<(&str, Option<i32>)>>>) -> ColumnarValue {
+        let mut builder = arrow::array::MapBuilder::new(
+            None,
+            arrow::array::StringBuilder::new(),
+            arrow::array::Int32Builder::new(),
+        );
+        for entry in entries {
+            match entry {
+                Some(entry) => {
+                    for (key, value) in entry {
+                        builder.keys().append_value(key);
+                        builder.values().append_option(value);
+                    }
+                    builder.append(true).unwrap();
+                }
+                None => builder.append(false).unwrap(),
+            }
+        }
+        ColumnarValue::Array(Arc::new(builder.finish()))
+    }
+
+    fn to_entries(r: ColumnarValue) -> Result<Vec<Option<Vec<(String, Option<String>)>>>> {
+        let array = r.into_array(1)?;
+        let map = as_map_array(&array)?;
+        Ok((0..map.len())
+            .map(|row| {
+                map.is_valid(row).then(|| {
+                    let entries = map.value(row);
+                    (0..entries.len())
+                        .map(|i| {
+                            let key =
+                                arrow::util::display::array_value_to_string(entries.column(0), i)
+                                    .unwrap();
+                            let value = entries.column(1).is_valid(i).then(|| {
+                                arrow::util::display::array_value_to_string(entries.column(1), i)
+                                    .unwrap()
+                            });
+                            (key, value)
+                        })
+                        .collect()
+                })
+            })
+            .collect())
+    }
+
+    fn policy(policy: &str) -> ColumnarValue {
+        ColumnarValue::Scalar(ScalarValue::from(policy))
+    }
+
+    #[test]
+    fn test_map_keys_values_entries() -> Result<()> {
+        let m = maps(vec![
+            Some(vec![("a", Some(1)), ("b", None)]),
+            Some(vec![]),
+            None,
+        ]);
+
+        let r = map_keys(&[m.clone()])?.into_array(3)?;
+        let keys = as_list_array(&r)?;
+        assert_eq!(keys.null_count(), 1);
+        let expected: ArrayRef = Arc::new(StringArray::from(vec!["a", "b"]));
+        assert_eq!(&keys.value(0), &expected);
+        assert_eq!(keys.value(1).len(), 0);
+
+        let r = map_values(&[m.clone()])?.into_array(3)?;
+        let values = as_list_array(&r)?;
+        let expected: ArrayRef = Arc::new(Int32Array::from(vec![Some(1), None]));
+        assert_eq!(&values.value(0), &expected);
+        assert!(values.is_null(2));
+
+        let r = map_entries(&[m])?.into_array(3)?;
+        let entries = as_list_array(&r)?;
+        assert_eq!(entries.value(0).len(), 2);
+        assert!(entries.is_null(2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_map_from_arrays() -> Result<()> {
+        let keys =
+            ColumnarValue::Array(Arc::new(ListArray::from_iter_primitive::<Int32Type, _, _>(
+                vec![Some(vec![Some(1), Some(2), Some(1)]), Some(vec![]), None],
+            )));
+        let values =
+            ColumnarValue::Array(Arc::new(
+                ListArray::from_iter_primitive::<Float64Type, _, _>(vec![
+                    Some(vec![Some(1.0), None, Some(3.0)]),
+                    Some(vec![]),
+                    Some(vec![]),
+                ]),
+            ));
+        let r = map_from_arrays(&[keys.clone(), values.clone(), policy("LAST_WIN")])?;
+        assert_eq!(
+            to_entries(r)?,
+            vec![
+                Some(vec![
+                    ("1".to_string(), Some("3.0".to_string())),
+                    ("2".to_string(), None),
+                ]),
+                Some(vec![]),
+                None,
+            ]
+        );
+        assert!(map_from_arrays(&[keys.clone(), values, policy("EXCEPTION")]).is_err());
+
+        // different lengths
+        let values =
+            ColumnarValue::Array(Arc::new(ListArray::from_iter_primitive::<Int32Type, _, _>(
+                vec![Some(vec![Some(1)]), None, None],
+            )));
+        assert!(map_from_arrays(&[keys, values, policy("LAST_WIN")]).is_err());
+
+        // null key
+        let keys = ColumnarValue::Array(Arc::new(
+            ListArray::from_iter_primitive::<Int32Type, _, _>(vec![Some(vec![None])]),
+        ));
+        let values =
+            ColumnarValue::Array(Arc::new(ListArray::from_iter_primitive::<Int32Type, _, _>(
+                vec![Some(vec![Some(1)])],
+            )));
+        assert!(map_from_arrays(&[keys, values, policy("LAST_WIN")]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_map_from_entries() -> Result<()> {
+        let m = maps(vec![Some(vec![
+            ("a", Some(1)),
+            ("b", None),
+            ("a", Some(3)),
+        ])]);
+        let entries = map_entries(&[m])?;
+        let r = map_from_entries(&[entries.clone(), policy("LAST_WIN")])?;
+        assert_eq!(
+            to_entries(r)?,
+            vec![Some(vec![
+                ("a".to_string(), Some("3".to_string())),
+                ("b".to_string(), None),
+            ])]
+        );
+        assert!(map_from_entries(&[entries, policy("EXCEPTION")]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_map_concat() -> Result<()> {
+        let m1 = maps(vec![
+            Some(vec![("a", Some(1)), ("b", Some(2))]),
+            Some(vec![]),
+            None,
+        ]);
+        let m2 = maps(vec![
+            Some(vec![("c", Some(3)), ("a", Some(4))]),
+            Some(vec![]),
+            Some(vec![]),
+        ]);
+        let r = map_concat(&[m1.clone(), m2.clone(), policy("LAST_WIN")])?;
+        assert_eq!(
+            to_entries(r)?,
+            vec![
+                Some(vec![
+                    ("a".to_string(), Some("4".to_string())),
+                    ("b".to_string(), Some("2".to_string())),
+                    ("c".to_string(), Some("3".to_string())),
+                ]),
+                Some(vec![]),
+                None,
+            ]
+        );
+        assert!(map_concat(&[m1, m2, policy("EXCEPTION")]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_str_to_map() -> Result<()> {
+        let caches = (RegexCache::default(), RegexCache::default());
+        let texts = ColumnarValue::Array(Arc::new(StringArray::from(vec![
+            Some("a:1,b:2,c"),
+            Some(""),
+            Some("a:1:2,a:3"),
+            None,
+        ])));
+        let r = str_to_map(
+            &[
+                texts.clone(),
+                ColumnarValue::Scalar(ScalarValue::from(",")),
+                ColumnarValue::Scalar(ScalarValue::from(":")),
+                policy("LAST_WIN"),
+            ],
+            &caches,
+        )?;
+        assert_eq!(
+            to_entries(r)?,
+            vec![
+                Some(vec![
+                    ("a".to_string(), Some("1".to_string())),
+                    ("b".to_string(), Some("2".to_string())),
+                    ("c".to_string(), None),
+                ]),
+                Some(vec![("".to_string(), None)]),
+                Some(vec![("a".to_string(), Some("3".to_string()))]),
+                None,
+            ]
+        );
+
+        let r = str_to_map(
+            &[
+                ColumnarValue::Scalar(ScalarValue::from("a=1;;b=2")),
+                ColumnarValue::Scalar(ScalarValue::from(";+")),
+                ColumnarValue::Scalar(ScalarValue::from("=")),
+                policy("EXCEPTION"),
+            ],
+            &caches,
+        )?;
+        assert_eq!(
+            to_entries(r)?,
+            vec![Some(vec![
+                ("a".to_string(), Some("1".to_string())),
+                ("b".to_string(), Some("2".to_string())),
+            ])]
+        );
+
+        assert!(str_to_map(
+            &[
+                texts,
+                ColumnarValue::Scalar(ScalarValue::from(",")),
+                ColumnarValue::Scalar(ScalarValue::from(":")),
+                policy("EXCEPTION"),
+            ],
+            &caches,
+        )
+        .is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,1088 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    cmp::Ordering,
+    ops::Range,
+    sync::{Arc, Mutex},
+};
+
+use arrow::array::{Array, BooleanArray, ListBuilder, StringBuilder};
+use datafusion::{
+    common::{
+        cast::{as_int32_array, as_string_array},
+        Result, ScalarValue,
+    },
+    logical_expr::ScalarFunctionImplementation,
+    physical_plan::ColumnarValue,
+};
+use datafusion_ext_commons::df_execution_err;
+use regex::Regex;
+
+/// Caches the compiled regex of the latest pattern. each expression owns its
+/// cache, so literal patterns are translated and compiled only once.
+#[derive(Default)]
+pub struct RegexCache {
+    cached: Mutex<Option<(String, Arc<JavaRegex>)>>,
+}
+
+impl RegexCache {
+    pub(crate) fn get(&self, pattern: &str) -> Result<Arc<JavaRegex>> {
+        let mut cached = self.cached.lock().unwrap();
+        if let Some((cached_pattern, regex)) = cached.as_ref()
+            && cached_pattern == pattern
+        {
+            return Ok(regex.clone());
+        }
+        let regex = Arc::new(JavaRegex::try_new(pattern)?);
+        *cached = Some((pattern.to_owned(), regex.clone()));
+        Ok(regex)
+    }
+}
+
+/// A java regex compiled into rust regex.
+///
+/// java's `$` also matches before the final line terminator, which is
+/// translated into an extra capturing group consuming the terminator. group
+/// indices and matched ranges are mapped back to java's, so the terminator is
+/// never a part of the match. matches are found like java's Matcher.find(),
+/// in which the search after an empty match starts at the next character.
+pub(crate) struct JavaRegex {
+    regex: Regex,
+    groups: Vec<usize>,
+    eol_groups: Vec<usize>,
+}
+
+impl JavaRegex {
+    fn try_new(pattern: &str) -> Result<Self> {
+        let (translated, groups, eol_groups) = translate_java_regex(pattern)?;
+        match Regex::new(&translated) {
+            Ok(regex) => Ok(Self {
+                regex,
+                groups,
+                eol_groups,
+            }),
+            Err(err) => df_execution_err!("regexp: invalid pattern '{pattern}': {err}"),
+        }
+    }
+
+    pub(crate) fn is_match(&self, s: &str) -> bool {
+        self.regex.is_match(s)
+    }
+
+    pub(crate) fn group_count(&self) -> usize {
+        self.groups.len() - 1
+    }
+
+    pub(crate) fn group_index(&self, name: &str) -> Option<usize> {
+        let idx = self
+            .regex
+            .capture_names()
+            .position(|group_name| group_name == Some(name))?;
+        self.groups.iter().position(|&group| group == idx)
+    }
+
+    pub(crate) fn captures<'t>(&self, s: &'t str) -> Option<JavaCaptures<'t>> {
+        self.captures_at(s, 0)
+    }
+
+    pub(crate) fn captures_iter<'a>(
+        &'a self,
+        s: &'a str,
+    ) -> impl Iterator<Item = JavaCaptures<'a>> + 'a {
+        let mut last_range: Option<Range<usize>> = None;
+        std::iter::from_fn(move || {
+            let start = match &last_range {
+                None => 0,
+                Some(range) if range.is_empty() => {
+                    let next_char = s[range.end..].chars().next()?;
+                    range.end + next_char.len_utf8()
+                }
+                Some(range) => range.end,
+            };
+            let captures = self.captures_at(s, start)?;
+            last_range = Some(captures.range());
+            Some(captures)
+        })
+    }
+
+    pub(crate) fn find_iter<'a>(&'a self, s: &'a str) -> impl Iterator<Item = Range<usize>> + 'a {
+        self.captures_iter(s).map(|captures| captures.range())
+    }
+
+    pub(crate) fn replace_all(
+        &self,
+        s: &str,
+        mut replacement: impl FnMut(&JavaCaptures) -> String,
+    ) -> String {
+        let mut replaced = String::with_capacity(s.len());
+        let mut last_end = 0;
+        for captures in self.captures_iter(s) {
+            let range = captures.range();
+            replaced.push_str(&s[last_end..range.start]);
+            replaced.push_str(&replacement(&captures));
+            last_end = range.end;
+        }
+        replaced.push_str(&s[last_end..]);
+        replaced
+    }
+
+    fn captures_at<'t>(&self, s: &'t str, start: usize) -> Option<JavaCaptures<'t>> {
+        let captures = self.regex.captures_at(s, start)?;
+
+        // trim the line terminator consumed by `$`
+        let eol_start = self
+            .eol_groups
+            .iter()
+            .filter_map(|&group| Some(captures.get(group)?.start()))
+            .min()
+            .unwrap_or(s.len());
+        let ranges = self
+            .groups
+            .iter()
+            .map(|&group| {
+                let m = captures.get(group)?;
+                Some(m.start().min(eol_start)..m.end().min(eol_start))
+            })
+            .collect();
+        Some(JavaCaptures { s, ranges })
+    }
+}
+
+/// Captured groups of a java regex match, indexed by java group indices.
+pub(crate) struct JavaCaptures<'t> {
+    s: &'t str,
+    ranges: Vec<Option<Range<usize>>>,
+}
+
+impl<'t> JavaCaptures<'t> {
+    pub(crate) fn get(&self, idx: usize) -> Option<&'t str> {
+        let range = self.ranges.get(idx)?.clone()?;
+        Some(&self.s[range])
+    }
+
+    pub(crate) fn range(&self) -> Range<usize> {
+        self.ranges[0].clone().expect("group 0 is always matched")
+    }
+}
+
+pub fn with_regex_cache(
+    f: fn(&[ColumnarValue], &RegexCache) -> Result<ColumnarValue>,
+) -> ScalarFunctionImplementation {
+    let cache = RegexCache::default();
+    Arc::new(move |args| f(args, &cache))
+}
+
+/// rlike(str, regexp), true if the pattern matches any substring of str
+pub fn rlike(args: &[ColumnarValue], cache: &RegexCache) -> Result<ColumnarValue> {
+    let num_rows = num_rows(args);
+    let strs = args[0].clone().into_array(num_rows)?;
+    let regexes = regexes(cache, &args[1], num_rows)?;
+
+    let matched: BooleanArray = as_string_array(&strs)?
+        .iter()
+        .zip(regexes)
+        .map(|(s, regex)| Some(regex?.is_match(s?)))
+        .collect();
+    Ok(ColumnarValue::Array(Arc::new(matched)))
+}
+
+/// regexp_extract(str, regexp, idx), returns the idx-th group of the first
+/// match, or empty string if not matched
+pub fn regexp_extract(args: &[ColumnarValue], cache: &RegexCache) -> Result<ColumnarValue> {
+    let num_rows = num_rows(args);
+    let strs = args[0].clone().into_array(num_rows)?;
+    let regexes = regexes(cache, &args[1], num_rows)?;
+    let idxs = args[2].clone().into_array(num_rows)?;
+
+    let mut extracted = StringBuilder::with_capacity(num_rows, 0);
+    for ((s, regex), idx) in as_string_array(&strs)?
+        .iter()
+        .zip(regexes)
+        .zip(as_int32_array(&idxs)?)
+    {
+        match (s, regex, idx) {
+            (Some(s), Some(regex), Some(idx)) => match regex.captures(s) {
+                Some(captures) => {
+                    extracted.append_value(captured_group(&regex, &captures, idx)?);
+                }
+                None => extracted.append_value(""),
+            },
+            _ => extracted.append_null(),
+        }
+    }
+    Ok(ColumnarValue::Array(Arc::new(extracted.finish())))
+}
+
+/// regexp_extract_all(str, regexp, idx), returns the idx-th group of all
+/// matches
+pub fn regexp_extract_all(args: &[ColumnarValue], cache: &RegexCache) -> Result<ColumnarValue> {
+    let num_rows = num_rows(args);
+    let strs = args[0].clone().into_array(num_rows)?;
+    let regexes = regexes(cache, &args[1], num_rows)?;
+    let idxs = args[2].clone().into_array(num_rows)?;
+
+    let mut extracted = ListBuilder::new(StringBuilder::new());
+    for ((s, regex), idx) in as_string_array(&strs)?
+        .iter()
+        .zip(regexes)
+        .zip(as_int32_array(&idxs)?)
+    {
+        match (s, regex, idx) {
+            (Some(s), Some(regex), Some(idx)) => {
+                for captures in regex.captures_iter(s) {
+                    let group = captured_group(&regex, &captures, idx)?;
+                    extracted.values().append_value(group);
+                }
+                extracted.append(true);
+            }
+            _ => extracted.append_null(),
+        }
+    }
+    Ok(ColumnarValue::Array(Arc::new(extracted.finish())))
+}
+
+/// regexp_replace(str, regexp, rep, pos), replaces all matches after the
+/// pos-th (1-based) character, rep uses java's replacement syntax ($1, ${name})
+pub fn regexp_replace(args: &[ColumnarValue], cache: &RegexCache) -> Result<ColumnarValue> {
+    let num_rows = num_rows(args);
+    let strs = args[0].clone().into_array(num_rows)?;
+    let regexes = regexes(cache, &args[1], num_rows)?;
+    let reps = args[2].clone().into_array(num_rows)?;
+    let positions = match args.get(3) {
+        Some(pos) => Some(pos.clone().into_array(num_rows)?),
+        None => None,
+    };
+    let positions = positions.as_ref().map(|p| as_int32_array(p)).transpose()?;
+
+    // replacement is parsed only once if both pattern and rep are literals
+    let literal_replacement = match (&args[1], &args[2]) {
+        (
+            ColumnarValue::Scalar(ScalarValue::Utf8(Some(_))),
+            ColumnarValue::Scalar(ScalarValue::Utf8(Some(rep))),
+        ) if num_rows > 0 => Some(parse_replacement(
+            regexes[0].as_ref().expect("non-null literal pattern"),
+            rep,
+        )?),
+        _ => None,
+    };
+
+    let mut replaced = StringBuilder::with_capacity(num_rows, 0);
+    for (row_idx, ((s, regex), rep)) in as_string_array(&strs)?
+        .iter()
+        .zip(regexes)
+        .zip(as_string_array(&reps)?)
+        .enumerate()
+    {
+        let pos = match positions {
+            Some(positions) if positions.is_null(row_idx) => None,
+            Some(positions) => Some(positions.value(row_idx)),
+            None => Some(1),
+        };
+        let (Some(s), Some(regex), Some(rep), Some(pos)) = (s, regex, rep, pos) else {
+            replaced.append_null();
+            continue;
+        };
+        if pos <= 0 {
+            df_execution_err!("regexp_replace: position must be positive, got {pos}")?;
+        }
+
+        // only the substring after pos is replaced, like java's Matcher.region()
+        let offset = match s.char_indices().nth(pos as usize - 1) {
+            Some((offset, _)) => offset,
+            None if s.chars().count() == pos as usize - 1 => s.len(),
+            None => {
+                replaced.append_value(s);
+                continue;
+            }
+        };
+        let parsed_replacement;
+        let replacement = match &literal_replacement {
+            Some(replacement) => replacement,
+            None => {
+                parsed_replacement = parse_replacement(&regex, rep)?;
+                &parsed_replacement
+            }
+        };
+        let (head, tail) = s.split_at(offset);
+        let tail = regex.replace_all(tail, |captures| replacement.expand(captures));
+        replaced.append_value(format!("{head}{tail}"));
+    }
+    Ok(ColumnarValue::Array(Arc::new(replaced.finish())))
+}
+
+fn num_rows(args: &[ColumnarValue]) -> usize {
+    args.iter()
+        .find_map(|arg| match arg {
+            ColumnarValue::Array(array) => Some(array.len()),
+            ColumnarValue::Scalar(_) => None,
+        })
+        .unwrap_or(1)
+}
+
+fn regexes(
+    cache: &RegexCache,
+    pattern: &ColumnarValue,
+    num_rows: usize,
+) -> Result<Vec<Option<Arc<JavaRegex>>>> {
+    match pattern {
+        ColumnarValue::Scalar(ScalarValue::Utf8(pattern)) => {
+            let regex = pattern.as_ref().map(|p| cache.get(p)).transpose()?;
+            Ok(vec![regex; num_rows])
+        }
+        ColumnarValue::Array(patterns) => as_string_array(patterns)?
+            .iter()
+            .map(|pattern| pattern.map(|p| cache.get(p)).transpose())
+            .collect(),
+        other => df_execution_err!("regexp: unsupported pattern: {other:?}"),
+    }
+}
+
+fn captured_group<'a>(regex: &JavaRegex, captures: &JavaCaptures<'a>, idx: i32) -> Result<&'a str> {
+    let group_count = regex.group_count();
+    if idx < 0 {
+        df_execution_err!("The specified group index cannot be less than zero")?;
+    }
+    if idx as usize > group_count {
+        df_execution_err!(
+            "Regex group count is {group_count}, but the specified group index is {idx}"
+        )?;
+    }
+    Ok(captures.get(idx as usize).unwrap_or(""))
+}
+
+#[derive(Debug, PartialEq)]
+enum ReplacementPart {
+    Literal(String),
+    Group(usize),
+}
+
+#[derive(Debug, PartialEq)]
+struct Replacement(Vec<ReplacementPart>);
+
+impl Replacement {
+    fn expand(&self, captures: &JavaCaptures) -> String {
+        let mut expanded = String::new();
+        for part in &self.0 {
+            match part {
+                ReplacementPart::Literal(s) => expanded.push_str(s),
+                ReplacementPart::Group(idx) => {
+                    expanded.push_str(captures.get(*idx).unwrap_or(""));
+                }
+            }
+        }
+        expanded
+    }
+}
+
+/// parses replacement string like java's Matcher.appendReplacement(): `\`
+/// escapes the next character, `$n` and `${name}` refer to captured groups.
+fn parse_replacement(regex: &JavaRegex, rep: &str) -> Result<Replacement> {
+    let group_count = regex.group_count();
+    let mut parts = vec![];
+    let mut literal = String::new();
+    let mut chars = rep.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(escaped) => literal.push(escaped),
+                None => df_execution_err!("character to be escaped is missing")?,
+            },
+            '$' => {
+                let group_idx = if chars.next_if_eq(&'{').is_some() {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) if c.is_ascii_alphanumeric() => name.push(c),
+                            _ => {
+                                df_execution_err!("named capturing group is missing trailing '}}'")?
+                            }
+                        }
+                    }
+                    regex
+                        .group_index(&name)
+                        .map(Ok)
+                        .unwrap_or_else(|| df_execution_err!("No group with name {{{name}}}"))?
+                } else {
+                    let mut group_idx = match chars.next().and_then(|c| c.to_digit(10)) {
+                        Some(digit) => digit as usize,
+                        None => df_execution_err!("Illegal group reference")?,
+                    };
+                    // consume more digits as long as the group index is valid
+                    while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+                        let new_group_idx = group_idx * 10 + digit as usize;
+                        if new_group_idx > group_count {
+                            break;
+                        }
+                        group_idx = new_group_idx;
+                        chars.next();
+                    }
+                    if group_idx > group_count {
+                        df_execution_err!("No group {group_idx}")?;
+                    }
+                    group_idx
+                };
+                if !literal.is_empty() {
+                    parts.push(ReplacementPart::Literal(std::mem::take(&mut literal)));
+                }
+                parts.push(ReplacementPart::Group(group_idx));
+            }
+            c => literal.push(c),
+        }
+    }
+    if !literal.is_empty() {
+        parts.push(ReplacementPart::Literal(literal));
+    }
+    Ok(Replacement(parts))
+}
+
+const LINE_TERMINATORS: &str = r"\n\r\x{85}\x{2028}\x{2029}";
+const VERTICAL_WHITESPACES: &str = r"\n\x0B\x0C\r\x{85}\x{2028}\x{2029}";
+const HORIZONTAL_WHITESPACES: &str =
+    r" \t\x{A0}\x{1680}\x{180E}\x{2000}-\x{200A}\x{202F}\x{205F}\x{3000}";
+
+#[derive(Clone, Copy, Default)]
+struct JavaRegexFlags {
+    dotall: bool,
+    multiline: bool,
+    case_insensitive: bool,
+    unicode_case: bool,
+}
+
+impl JavaRegexFlags {
+    /// java's CASE_INSENSITIVE only matches US-ASCII letters case-insensitively
+    /// unless UNICODE_CASE is also enabled
+    fn ascii_case_insensitive(&self) -> bool {
+        self.case_insensitive && !self.unicode_case
+    }
+
+    fn unicode_case_insensitive(&self) -> bool {
+        self.case_insensitive && self.unicode_case
+    }
+}
+
+/// translates java regex syntax into rust regex syntax. constructs with
+/// different meanings are rewritten (ascii-only \d\w\s and (?i), `.` excluding
+/// all line terminators, `$` matching before the final line terminator,
+/// \Q..\E quoting, java-specific escapes and properties), and constructs not
+/// supported by rust regex (lookarounds, backreferences, possessive
+/// quantifiers, atomic groups) are rejected.
+///
+/// returns the translated pattern, the rust group index of each java group,
+/// and the rust group indices of line terminators consumed by `$`.
+fn translate_java_regex(pattern: &str) -> Result<(String, Vec<usize>, Vec<usize>)> {
+    let unsupported = |feature: &str| {
+        df_execution_err!("regexp: {feature} is not supported in pattern '{pattern}'")
+    };
+    let chars = pattern.chars().collect::<Vec<_>>();
+    let mut translated = String::with_capacity(pattern.len());
+    let mut class_depth = 0;
+    let mut flags = JavaRegexFlags::default();
+    let mut flags_stack = vec![];
+    let mut groups = vec![0];
+    let mut eol_groups = vec![];
+    let mut num_groups = 0;
+    let mut i = 0;
+
+    // matches US-ASCII letters case-insensitively, see JavaRegexFlags
+    let ascii_case_insensitive = |c: char, flags: &JavaRegexFlags| {
+        (flags.ascii_case_insensitive() && c.is_ascii_alphabetic())
+            .then(|| format!("[{}{}]", c.to_ascii_lowercase(), c.to_ascii_uppercase()))
+    };
+
+    while i < chars.len() {
+        let c = chars[i];
+        i += 1;
+        match c {
+            '\\' => {
+                let Some(&e) = chars.get(i) else {
+                    return df_execution_err!("regexp: unexpected trailing '\\' in '{pattern}'");
+                };
+                i += 1;
+                let class = |s: &str| format!("[{s}]");
+                let negated_class = |s: &str| format!("[^{s}]");
+                let code_point = |code: u32| {
+                    char::from_u32(code)
+                        .filter(|_| class_depth == 0)
+                        .and_then(|c| ascii_case_insensitive(c, &flags))
+                        .unwrap_or_else(|| format!(r"\x{{{code:X}}}"))
+                };
+                match e {
+                    'Q' => {
+                        let quoted_len = chars[i..]
+                            .windows(2)
+                            .position(|w| w == ['\\', 'E'])
+                            .unwrap_or(chars.len() - i);
+                        for &q in &chars[i..][..quoted_len] {
+                            match ascii_case_insensitive(q, &flags).filter(|_| class_depth == 0) {
+                                Some(q) => translated.push_str(&q),
+                                None => translated.push_str(&regex::escape(&q.to_string())),
+                            }
+                        }
+                        i = (i + quoted_len + 2).min(chars.len());
+                    }
+                    'd' => translated.push_str(&class("0-9")),
+                    'D' => translated.push_str(&negated_class("0-9")),
+                    'w' => translated.push_str(&class("0-9A-Za-z_")),
+                    'W' => translated.push_str(&negated_class("0-9A-Za-z_")),
+                    's' => translated.push_str(&class(r" \t\n\x0B\x0C\r")),
+                    'S' => translated.push_str(&negated_class(r" \t\n\x0B\x0C\r")),
+                    'h' => translated.push_str(&class(HORIZONTAL_WHITESPACES)),
+                    'H' => translated.push_str(&negated_class(HORIZONTAL_WHITESPACES)),
+                    'v' => translated.push_str(&class(VERTICAL_WHITESPACES)),
+                    'V' => translated.push_str(&negated_class(VERTICAL_WHITESPACES)),
+                    'R' if class_depth == 0 => {
+                        translated.push_str(&format!(r"(?:\r\n|[{VERTICAL_WHITESPACES}])"));
+                    }
+                    'Z' if class_depth == 0 => {
+                        num_groups += 1;
+                        eol_groups.push(num_groups);
+                        translated.push_str(&final_line_terminator());
+                    }
+                    'e' => translated.push_str(r"\x1B"),
+                    'a' => translated.push_str(r"\x07"),
+                    'c' => match chars.get(i) {
+                        Some(&ctrl) => {
+                            translated.push_str(&format!(r"\x{{{:X}}}", ctrl as u32 ^ 64));
+                            i += 1;
+                        }
+                        None => return unsupported("'\\c' without control character"),
+                    },
+                    '0' => {
+                        // octal escape: \0n, \0nn, \0mnn (m <= 3)
+                        let mut value = 0u32;
+                        let mut num_digits = 0;
+                        while let Some(digit) = chars.get(i).and_then(|c| c.to_digit(8))
+                            && num_digits < 3
+                            && value * 8 + digit <= 0o377
+                        {
+                            value = value * 8 + digit;
+                            num_digits += 1;
+                            i += 1;
+                        }
+                        if num_digits == 0 {
+                            return unsupported("illegal octal escape");
+                        }
+                        translated.push_str(&code_point(value));
+                    }
+                    'u' => {
+                        let hex = |start: usize| {
+                            chars
+                                .get(start..start + 4)
+                                .map(|s| s.iter().collect::<String>())
+                                .and_then(|s| u32::from_str_radix(&s, 16).ok())
+                        };
+                        let Some(mut code) = hex(i) else {
+                            return unsupported("illegal unicode escape");
+                        };
+                        i += 4;
+
+                        // combine surrogate pairs
+                        if (0xD800..0xDC00).contains(&code)
+                            && chars.get(i..i + 2) == Some(&['\\', 'u'][..])
+                            && let Some(low) = hex(i + 2)
+                            && (0xDC00..0xE000).contains(&low)
+                        {
+                            code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+                            i += 6;
+                        }
+                        translated.push_str(&code_point(code));
+                    }
+                    'p' | 'P' => {
+                        let name = if chars.get(i) == Some(&'{') {
+                            let Some(len) = chars[i..].iter().position(|&c| c == '}') else {
+                                return unsupported("unclosed character property");
+                            };
+                            let name = chars[i + 1..i + len].iter().collect::<String>();
+                            i += len + 1;
+                            name
+                        } else if let Some(&name) = chars.get(i) {
+                            i += 1;
+                            name.to_string()
+                        } else {
+                            return unsupported("empty character property");
+                        };
+                        let Some(property) = translate_java_property(&name, e == 'P') else {
+                            return unsupported(&format!("character property '{name}'"));
+                        };
+                        translated.push_str(&property);
+                    }
+                    '1'..='9' | 'k' => return unsupported("backreference"),
+                    'G' => return unsupported("'\\G'"),
+                    'Z' => return unsupported("'\\Z'"),
+                    'X' => return unsupported("'\\X'"),
+                    'N' => return unsupported("'\\N'"),
+                    e if e.is_ascii_alphanumeric() => {
+                        // \t \n \r \f \x \b \B \A \z etc. have the same meanings
+                        translated.push('\\');
+                        translated.push(e);
+                    }
+                    e => {
+                        // escaped literal, note that \< and \> are word boundaries in
+                        // rust regex
+                        translated.push_str(&regex::escape(&e.to_string()));
+                    }
+                }
+            }
+            '[' => {
+                class_depth += 1;
+                translated.push('[');
+                if chars.get(i) == Some(&'^') {
+                    translated.push('^');
+                    i += 1;
+                }
+            }
+            ']' if class_depth > 0 => {
+                class_depth -= 1;
+                translated.push(']');
+            }
+            '-' | '~' if class_depth > 0 && chars.get(i) == Some(&c) => {
+                // '--' and '~~' are set operations in rust regex
+                translated.push('\\');
+                translated.push(c);
+            }
+            _ if class_depth > 0 => {
+                translated.push(c);
+                if flags.ascii_case_insensitive() {
+                    // add the other case of letters and letter ranges
+                    let hi = match (chars.get(i), chars.get(i + 1)) {
+                        (Some('-'), Some(&hi)) if !"[]\\-&".contains(hi) => {
+                            translated.push('-');
+                            translated.push(hi);
+                            i += 2;
+                            hi
+                        }
+                        _ => c,
+                    };
+                    for (from, to) in [('a', 'z'), ('A', 'Z')] {
+                        let (lo, hi) = (c.max(from), hi.min(to));
+                        let swap_case = |c: char| (c as u8 ^ 0x20) as char;
+                        match lo.cmp(&hi) {
+                            Ordering::Less => {
+                                translated.push(swap_case(lo));
+                                translated.push('-');
+                                translated.push(swap_case(hi));
+                            }
+                            Ordering::Equal => translated.push(swap_case(lo)),
+                            Ordering::Greater => {}
+                        }
+                    }
+                }
+            }
+
+            '(' => {
+                flags_stack.push(flags);
+                if chars.get(i) != Some(&'?') {
+                    num_groups += 1;
+                    groups.push(num_groups);
+                    translated.push('(');
+                    continue;
+                }
+                let rest = chars[i + 1..].iter().take(2).collect::<String>();
+                if rest.starts_with('=') || rest.starts_with('!') {
+                    return unsupported("lookahead");
+                }
+                if rest == "<=" || rest == "<!" {
+                    return unsupported("lookbehind");
+                }
+                if rest.starts_with('>') {
+                    return unsupported("atomic group");
+                }
+                if rest.starts_with(':') {
+                    translated.push_str("(?:");
+                    i += 2;
+                    continue;
+                }
+                if rest.starts_with('<') {
+                    // named capturing group
+                    let Some(len) = chars[i..].iter().position(|&c| c == '>') else {
+                        return unsupported("unclosed group name");
+                    };
+                    translated.push('(');
+                    translated.extend(&chars[i..=i + len]);
+                    i += len + 1;
+                    num_groups += 1;
+                    groups.push(num_groups);
+                    continue;
+                }
+
+                // embedded flags: (?flags) or (?flags:X)
+                let Some(len) = chars[i + 1..].iter().position(|&c| c == ')' || c == ':') else {
+                    return unsupported("unclosed group");
+                };
+                let outer_flags = flags;
+                let mut enabled = true;
+                let mut rust_enabled = String::new();
+                let mut rust_disabled = String::new();
+                for &flag in &chars[i + 1..][..len] {
+                    match flag {
+                        '-' => enabled = false,
+                        's' => flags.dotall = enabled,
+                        'm' => flags.multiline = enabled,
+                        'i' => flags.case_insensitive = enabled,
+                        'u' => flags.unicode_case = enabled,
+                        'x' => {}
+                        'd' => return unsupported("flag UNIX_LINES (?d)"),
+                        'U' => return unsupported("flag UNICODE_CHARACTER_CLASS (?U)"),
+                        other => return unsupported(&format!("flag '{other}'")),
+                    }
+                    if "smx".contains(flag) {
+                        match enabled {
+                            true => rust_enabled.push(flag),
+                            false => rust_disabled.push(flag),
+                        }
+                    }
+                }
+
+                // only unicode case-insensitivity is left to rust regex, letters are
+                // expanded for ascii case-insensitivity
+                match (
+                    outer_flags.unicode_case_insensitive(),
+                    flags.unicode_case_insensitive(),
+                ) {
+                    (false, true) => rust_enabled.push('i'),
+                    (true, false) => rust_disabled.push('i'),
+                    _ => {}
+                }
+                if !rust_disabled.is_empty() {
+                    rust_enabled = format!("{rust_enabled}-{rust_disabled}");
+                }
+                let end = chars[i + 1 + len];
+                if !rust_enabled.is_empty() {
+                    translated.push_str(&format!("(?{rust_enabled}{end}"));
+                } else if end == ':' {
+                    translated.push_str("(?:");
+                }
+                if end == ')' {
+                    // flags without group apply to the rest of the enclosing group
+                    flags_stack.pop();
+                }
+                i += len + 2;
+            }
+            ')' => {
+                flags = flags_stack.pop().unwrap_or(flags);
+                translated.push(')');
+            }
+            '.' if !flags.dotall => {
+                translated.push_str(&format!("[^{LINE_TERMINATORS}]"));
+            }
+            '$' if !flags.multiline => {
+                num_groups += 1;
+                eol_groups.push(num_groups);
+                translated.push_str(&final_line_terminator());
+            }
+            '*' | '+' | '?' | '{' => {
+                translated.push(c);
+                if c == '{' {
+                    while let Some(&q) = chars.get(i) {
+                        translated.push(q);
+                        i += 1;
+                        if q == '}' {
+                            break;
+                        }
+                    }
+                }
+                match chars.get(i) {
+                    Some('+') => return unsupported("possessive quantifier"),
+                    Some('?') => {
+                        translated.push('?');
+                        i += 1;
+                    }
+                    _ => {}
+                }
+            }
+            c => match ascii_case_insensitive(c, &flags) {
+                Some(c) => translated.push_str(&c),
+                None => translated.push(c),
+            },
+        }
+    }
+    Ok((translated, groups, eol_groups))
+}
+
+/// java's `$` (without MULTILINE) and `\Z` match at the end of input, or
+/// before the line terminator at the end of input. the terminator is consumed
+/// by a capturing group and trimmed from the match afterwards.
+///
+/// note that a `\n` following `\r` is also taken as a final line terminator,
+/// and constructs after `$` are matched after the terminator.
+fn final_line_terminator() -> String {
+    format!(r"(?:\z|(\r\n|[{LINE_TERMINATORS}])\z)")
+}
+
+/// translates java's \p{name}, returns None if the property is not supported
+fn translate_java_property(name: &str, negated: bool) -> Option<String> {
+    let posix_class = |class: &str| match negated {
+        false => format!("[[:{class}:]]"),
+        true => format!("[[:^{class}:]]"),
+    };
+    let property = |property: &str| match negated {
+        false => format!(r"\p{{{property}}}"),
+        true => format!(r"\P{{{property}}}"),
+    };
+    Some(match name {
+        // POSIX character classes (US-ASCII only)
+        "Lower" => posix_class("lower"),
+        "Upper" => posix_class("upper"),
+        "ASCII" => posix_class("ascii"),
+        "Alpha" => posix_class("alpha"),
+        "Digit" => posix_class("digit"),
+        "Alnum" => posix_class("alnum"),
+        "Punct" => posix_class("punct"),
+        "Graph" => posix_class("graph"),
+        "Print" => posix_class("print"),
+        "Blank" => posix_class("blank"),
+        "Cntrl" => posix_class("cntrl"),
+        "XDigit" => posix_class("xdigit"),
+        "Space" => posix_class("space"),
+
+        // java.lang.Character classes
+        "javaLowerCase" => property("Lowercase"),
+        "javaUpperCase" => property("Uppercase"),
+        "javaWhitespace" => property("White_Space"),
+        "javaAlphabetic" => property("Alphabetic"),
+        "javaLetter" => property("L"),
+        "javaDigit" => property("Nd"),
+        name if name.starts_with("java") => return None,
+
+        // unicode blocks are not supported by rust regex
+        name if name.starts_with("In") => return None,
+        name if name.starts_with("block=") || name.starts_with("blk=") => return None,
+
+        // unicode scripts, categories and binary properties
+        name if name.starts_with("Is") => property(&name[2..]),
+        name => property(name),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::array::{Array, BooleanArray, Int32Array, ListArray, StringArray};
+    use datafusion::{
+        common::{cast::as_string_array, Result, ScalarValue},
+        physical_plan::ColumnarValue,
+    };
+
+    use crate::spark_regexp::{
+        regexp_extract, regexp_extract_all, regexp_replace, rlike, translate_java_regex, JavaRegex,
+        RegexCache,
+    };
+
+    fn utf8(s: &str) -> ColumnarValue {
+        ColumnarValue::Scalar(ScalarValue::Utf8(Some(s.to_string())))
+    }
+
+    fn int32(v: i32) -> ColumnarValue {
+        ColumnarValue::Scalar(ScalarValue::Int32(Some(v)))
+    }
+
+    fn strs(values: Vec<Option<&str>>) -> ColumnarValue {
+        ColumnarValue::Array(Arc::new(StringArray::from(values)))
+    }
+
+    fn translated(pattern: &str) -> Result<String> {
+        Ok(translate_java_regex(pattern)?.0)
+    }
+
+    #[test]
+    fn test_translate_java_regex() -> Result<()> {
+        assert_eq!(translated(r"\d+")?, r"[0-9]+");
+        assert_eq!(translated(r"[\w-]")?, r"[[0-9A-Za-z_]-]");
+        assert_eq!(
+            translated(r"\Qa.b*\E.")?,
+            r"a\.b\*[^\n\r\x{85}\x{2028}\x{2029}]"
+        );
+        assert_eq!(translated(r"(?s).")?, r"(?s).");
+        assert_eq!(translated(r"(?i)a.")?, r"[aA][^\n\r\x{85}\x{2028}\x{2029}]");
+        assert_eq!(translated(r"(?i:a)(?iu)é")?, r"(?:[aA])(?i)é");
+        assert_eq!(translated(r"(?i)[a-cX\d]")?, r"[a-cA-CXx[0-9]]");
+        assert_eq!(
+            translated(r"a$")?,
+            r"a(?:\z|(\r\n|[\n\r\x{85}\x{2028}\x{2029}])\z)"
+        );
+        assert_eq!(translated(r"(?m)a$")?, r"(?m)a$");
+        assert_eq!(translated(r"é\0101\e")?, r"é\x{41}\x1B");
+        assert_eq!(
+            translated(r"\p{Alpha}\p{IsLatin}")?,
+            r"[[:alpha:]]\p{Latin}"
+        );
+        assert_eq!(translated(r"\<a\>")?, r"<a>");
+        assert_eq!(translated(r"a{2,3}?")?, r"a{2,3}?");
+        assert_eq!(translated(r"(?<year>\d{4})")?, r"(?<year>[0-9]{4})");
+
+        for unsupported in [
+            r"a(?=b)",
+            r"a(?!b)",
+            r"(?<=a)b",
+            r"(?<!a)b",
+            r"(?>a)",
+            r"(a)\1",
+            r"a*+",
+            r"a{2}+",
+            r"\p{InGreek}",
+            r"(?d)a",
+        ] {
+            assert!(
+                translated(unsupported).is_err(),
+                "{unsupported} should be rejected"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_java_semantics() -> Result<()> {
+        // \d and \w are ascii-only in java
+        assert!(!JavaRegex::try_new(r"^\d$")?.is_match("٣"));
+        assert!(!JavaRegex::try_new(r"^\w$")?.is_match("é"));
+        // . does not match \r in java
+        assert!(!JavaRegex::try_new(r"a.b")?.is_match("a\rb"));
+        assert!(JavaRegex::try_new(r"(?s)a.b")?.is_match("a\rb"));
+
+        // (?i) is ascii-only without (?u)
+        assert!(JavaRegex::try_new(r"(?i)ABC")?.is_match("abc"));
+        assert!(JavaRegex::try_new(r"(?i)[a-c]")?.is_match("B"));
+        assert!(!JavaRegex::try_new(r"(?i)é")?.is_match("É"));
+        assert!(JavaRegex::try_new(r"(?iu)é")?.is_match("É"));
+
+        // $ also matches before the final line terminator, which is not matched
+        let regex = JavaRegex::try_new(r"(\w+)$")?;
+        let captures = regex.captures("abc\r\n").expect("matched");
+        assert_eq!(regex.group_count(), 1);
+        assert_eq!(captures.range(), 0..3);
+        assert_eq!(captures.get(1), Some("abc"));
+        assert!(!JavaRegex::try_new(r"c$")?.is_match("abc\n\n"));
+
+        // searching after an empty match starts at the next character
+        let regex = JavaRegex::try_new(r"$")?;
+        assert_eq!(regex.find_iter("a\n").collect::<Vec<_>>(), vec![1..1, 2..2]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rlike() -> Result<()> {
+        let cache = RegexCache::default();
+        let r = rlike(
+            &[
+                strs(vec![Some("2024-01-02"), Some("abc"), None]),
+                utf8(r"\d{4}-\d\d"),
+            ],
+            &cache,
+        )?;
+        let r = r.into_array(3)?;
+        let r = r.as_any().downcast_ref::<BooleanArray>().unwrap();
+        assert_eq!(
+            r.iter().collect::<Vec<_>>(),
+            vec![Some(true), Some(false), None]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_regexp_extract() -> Result<()> {
+        let cache = RegexCache::default();
+        let r = regexp_extract(
+            &[
+                strs(vec![Some("100-200"), Some("foo"), Some("300-"), None]),
+                utf8(r"(\d+)-(\d+)?"),
+                int32(2),
+            ],
+            &cache,
+        )?;
+        let r = r.into_array(4)?;
+        assert_eq!(
+            as_string_array(&r)?.iter().collect::<Vec<_>>(),
+            vec![Some("200"), Some(""), Some(""), None]
+        );
+
+        // line terminator after $ is not extracted
+        let r = regexp_extract(&[utf8("x123\n"), utf8(r"\d+$"), int32(0)], &cache)?;
+        let r = r.into_array(1)?;
+        assert_eq!(as_string_array(&r)?.value(0), "123");
+
+        // group index out of range
+        assert!(regexp_extract(&[utf8("100-200"), utf8(r"(\d+)"), int32(2)], &cache).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_regexp_extract_all() -> Result<()> {
+        let cache = RegexCache::default();
+        let r = regexp_extract_all(
+            &[
+                strs(vec![Some("100-200, 300-400"), Some("foo"), None]),
+                utf8(r"(\d+)-(\d+)"),
+                int32(1),
+            ],
+            &cache,
+        )?;
+        let r = r.into_array(3)?;
+        let r = r.as_any().downcast_ref::<ListArray>().unwrap();
+        assert_eq!(
+            as_string_array(&r.value(0))?.iter().collect::<Vec<_>>(),
+            vec![Some("100"), Some("300")]
+        );
+        assert_eq!(r.value(1).len(), 0);
+        assert!(r.is_null(2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_regexp_replace() -> Result<()> {
+        let cache = RegexCache::default();
+        let r = regexp_replace(
+            &[
+                strs(vec![Some("100-200"), Some("foo"), None]),
+                utf8(r"(\d+)-(?<second>\d+)"),
+                utf8(r"${second}\$$1"),
+            ],
+            &cache,
+        )?;
+        let r = r.into_array(3)?;
+        assert_eq!(
+            as_string_array(&r)?.iter().collect::<Vec<_>>(),
+            vec![Some("200$100"), Some("foo"), None]
+        );
+
+        // replacing from the specified position, $10 is parsed as $1 followed by '0'
+        let r = regexp_replace(
+            &[
+                strs(vec![Some("a1b2c3"), Some("a1"), Some("a1")]),
+                utf8(r"([a-z])"),
+                utf8(r"<$10>"),
+                ColumnarValue::Array(Arc::new(Int32Array::from(vec![2, 3, 4]))),
+            ],
+            &cache,
+        )?;
+        let r = r.into_array(3)?;
+        assert_eq!(
+            as_string_array(&r)?.iter().collect::<Vec<_>>(),
+            vec![Some("a1<b0>2<c0>3"), Some("a1"), Some("a1")]
+        );
+
+        // line terminator after $ is kept
+        let r = regexp_replace(&[utf8("abc\n"), utf8(r"c$"), utf8("X")], &cache)?;
+        let r = r.into_array(1)?;
+        assert_eq!(as_string_array(&r)?.value(0), "abX\n");
+
+        // illegal group references
+        assert!(regexp_replace(&[utf8("a"), utf8("a"), utf8("$1")], &cache).is_err());
+        assert!(regexp_replace(&[utf8("a"), utf8("a"), utf8("$x")], &cache).is_err());
+        Ok(())
+    }
+}
@@ -12,11 +12,16 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use arrow::{
-    array::{Array, ArrayRef, AsArray, ListArray, ListBuilder, StringArray, StringBuilder},
-    datatypes::DataType,
+    array::{
+        Array, ArrayRef, AsArray, Int32Array, ListArray, ListBuilder, StringArray, StringBuilder,
+    },
+    datatypes::{
+        DataType, Decimal128Type, Float32Type, Float64Type, Int16Type, Int32Type, Int64Type,
+        Int8Type,
+    },
 };
 use datafusion::{
     common::{
@@ -311,24 +316,444 @@ pub fn string_concat_ws(args: &[ColumnarValue]) -> Result<ColumnarValue> {
     Ok(ColumnarValue::Array(concatenated_string_array))
 }
 
+/// substring_index(str, delim, count), occurrences of delim are searched
+/// byte by byte like spark, so overlapping occurrences are counted
+pub fn string_substring_index(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(args)?;
+    let result: StringArray = as_string_array(&arrays[0])?
+        .iter()
+        .zip(as_string_array(&arrays[1])?)
+        .zip(as_int32_array(&arrays[2])?)
+        .map(|((s, delim), count)| Some(substring_index(s?, delim?, count?)))
+        .collect();
+    Ok(ColumnarValue::Array(Arc::new(result)))
+}
+
+fn substring_index<'a>(s: &'a str, delim: &str, count: i32) -> &'a str {
+    let (bytes, delim_bytes) = (s.as_bytes(), delim.as_bytes());
+    if delim.is_empty() || count == 0 {
+        return "";
+    }
+    let matches_at = |idx: usize| bytes[idx..].starts_with(delim_bytes);
+    if count > 0 {
+        let mut idx = 0;
+        for i in 0..count {
+            let start = if i == 0 { 0 } else { idx + 1 };
+            match (start..bytes.len()).find(|&idx| matches_at(idx)) {
+                Some(found) => idx = found,
+                None => return s,
+            }
+        }
+        &s[..idx]
+    } else {
+        let mut idx = bytes.len() + 1;
+        for _ in 0..count.unsigned_abs() {
+            match (0..idx - 1).rev().find(|&idx| matches_at(idx)) {
+                Some(found) => idx = found + 1,
+                None => return s,
+            }
+        }
+        &s[idx - 1 + delim.len()..]
+    }
+}
+
+/// instr(str, substr), returns 1-based char position of the first occurrence,
+/// or 0 if not found
+pub fn string_instr(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(args)?;
+    let result: Int32Array = as_string_array(&arrays[0])?
+        .iter()
+        .zip(as_string_array(&arrays[1])?)
+        .map(|(s, substr)| Some(char_index_of(s?, substr?, 0).map(|i| i + 1).unwrap_or(0)))
+        .collect();
+    Ok(ColumnarValue::Array(Arc::new(result)))
+}
+
+/// locate(substr, str, pos), same as instr() but searches from 1-based char
+/// position pos. returns 0 if pos is null or less than 1
+pub fn string_locate(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(args)?;
+    let result: Int32Array = as_string_array(&arrays[0])?
+        .iter()
+        .zip(as_string_array(&arrays[1])?)
+        .zip(as_int32_array(&arrays[2])?)
+        .map(|((substr, s), pos)| match pos {
+            None => Some(0),
+            Some(pos) if pos < 1 => substr.and(s).map(|_| 0),
+            Some(pos) => {
+                let found = char_index_of(s?, substr?, pos as usize - 1);
+                Some(found.map(|i| i + 1).unwrap_or(0))
+            }
+        })
+        .collect();
+    Ok(ColumnarValue::Array(Arc::new(result)))
+}
+
+/// same as UTF8String.indexOf(): returns char index of the first occurrence
+/// of substr starting from char index start. empty substr is always found at 0
+fn char_index_of(s: &str, substr: &str, start: usize) -> Option<i32> {
+    if substr.is_empty() {
+        return Some(0);
+    }
+    s.char_indices()
+        .enumerate()
+        .skip(start)
+        .find(|(_, (byte_idx, _))| s[*byte_idx..].starts_with(substr))
+        .map(|(char_idx, _)| char_idx as i32)
+}
+
+/// lpad(str, len, pad), str is truncated to len chars if it is longer
+pub fn string_lpad(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    string_pad(args, true)
+}
+
+/// rpad(str, len, pad), str is truncated to len chars if it is longer
+pub fn string_rpad(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    string_pad(args, false)
+}
+
+fn string_pad(args: &[ColumnarValue], left: bool) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(args)?;
+    let result: StringArray = as_string_array(&arrays[0])?
+        .iter()
+        .zip(as_int32_array(&arrays[1])?)
+        .zip(as_string_array(&arrays[2])?)
+        .map(|((s, len), pad)| {
+            let (s, len, pad) = (s?, len?.max(0) as usize, pad?);
+            let num_chars = s.chars().count();
+            if num_chars >= len || pad.is_empty() {
+                return Some(s.chars().take(len).collect::<String>());
+            }
+            let padding = pad
+                .chars()
+                .cycle()
+                .take(len - num_chars)
+                .collect::<String>();
+            Some(if left {
+                padding + s
+            } else {
+                s.to_owned() + &padding
+            })
+        })
+        .collect();
+    Ok(ColumnarValue::Array(Arc::new(result)))
+}
+
+/// translate(str, matching, replace), chars in matching are replaced with
+/// chars at the same positions in replace, or removed if replace is shorter
+pub fn string_translate(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(args)?;
+    let mut cached_dict: Option<(String, String, HashMap<char, Option<char>>)> = None;
+    let mut result = StringBuilder::new();
+    for ((s, matching), replace) in as_string_array(&arrays[0])?
+        .iter()
+        .zip(as_string_array(&arrays[1])?)
+        .zip(as_string_array(&arrays[2])?)
+    {
+        let (Some(s), Some(matching), Some(replace)) = (s, matching, replace) else {
+            result.append_null();
+            continue;
+        };
+        if !matches!(&cached_dict, Some((m, r, _)) if m == matching && r == replace) {
+            let mut dict = HashMap::new();
+            let mut replace_chars = replace.chars();
+            for c in matching.chars() {
+                dict.entry(c).or_insert(replace_chars.next());
+            }
+            cached_dict = Some((matching.to_owned(), replace.to_owned(), dict));
+        }
+        let dict = &cached_dict.as_ref().expect("cached dict").2;
+        result.append_value(
+            s.chars()
+                .filter_map(|c| dict.get(&c).copied().unwrap_or(Some(c)))
+                .collect::<String>(),
+        );
+    }
+    Ok(ColumnarValue::Array(Arc::new(result.finish())))
+}
+
+/// levenshtein(str1, str2[, threshold]), distance is counted in chars.
+/// returns -1 if the distance is greater than threshold
+pub fn string_levenshtein(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(args)?;
+    let thresholds = match arrays.get(2) {
+        Some(thresholds) => as_int32_array(thresholds)?.iter().collect(),
+        None => vec![Some(i32::MAX); arrays[0].len()],
+    };
+    let result: Int32Array = as_string_array(&arrays[0])?
+        .iter()
+        .zip(as_string_array(&arrays[1])?)
+        .zip(thresholds)
+        .map(|((s1, s2), threshold)| {
+            let distance = levenshtein(s1?, s2?) as i32;
+            Some(if distance > threshold? { -1 } else { distance })
+        })
+        .collect();
+    Ok(ColumnarValue::Array(Arc::new(result)))
+}
+
+fn levenshtein(s1: &str, s2: &str) -> usize {
+    let chars1 = s1.chars().collect::<Vec<_>>();
+    let mut prev_row = (0..=chars1.len()).collect::<Vec<_>>();
+    let mut row = vec![0; chars1.len() + 1];
+    for (j, c2) in s2.chars().enumerate() {
+        row[0] = j + 1;
+        for (i, c1) in chars1.iter().enumerate() {
+            let cost = (*c1 != c2) as usize;
+            row[i + 1] = (row[i] + 1)
+                .min(prev_row[i + 1] + 1)
+                .min(prev_row[i] + cost);
+        }
+        std::mem::swap(&mut prev_row, &mut row);
+    }
+    prev_row[chars1.len()]
+}
+
+/// soundex(str), same as UTF8String.soundex(): returns str unchanged if it
+/// does not start with an ascii letter
+pub fn string_soundex(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(args)?;
+    let result: StringArray = as_string_array(&arrays[0])?
+        .iter()
+        .map(|s| s.map(soundex))
+        .collect();
+    Ok(ColumnarValue::Array(Arc::new(result)))
+}
+
+fn soundex(s: &str) -> String {
+    // codes of A-Z, '0' for vowels and '7' for H/W which are ignored
+    const US_ENGLISH_MAPPING: &[u8; 26] = b"01230127022455012623017202";
+    let bytes = s.as_bytes();
+    let Some(first) = bytes.first().map(u8::to_ascii_uppercase) else {
+        return String::new();
+    };
+    if !first.is_ascii_uppercase() {
+        return s.to_owned();
+    }
+    let mut sx = vec![first, b'0', b'0', b'0'];
+    let mut sxi = 1;
+    let mut last_code = US_ENGLISH_MAPPING[(first - b'A') as usize];
+    for b in bytes[1..].iter().map(u8::to_ascii_uppercase) {
+        if !b.is_ascii_uppercase() {
+            last_code = b'0';
+            continue;
+        }
+        let code = US_ENGLISH_MAPPING[(b - b'A') as usize];
+        if code != b'7' {
+            if code != b'0' && code != last_code {
+                sx[sxi] = code;
+                sxi += 1;
+                if sxi > 3 {
+                    break;
+                }
+            }
+            last_code = code;
+        }
+    }
+    String::from_utf8(sx).expect("ascii soundex")
+}
+
+/// format_number(x, d), formats number like '#,###,###,##0.00' with d
+/// decimal places and HALF_EVEN rounding. returns null if d is negative
+pub fn string_format_number(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(args)?;
+    let ds = as_int32_array(&arrays[1])?;
+    let values = &arrays[0];
+    let mut result = StringBuilder::new();
+    for i in 0..values.len() {
+        if values.is_null(i) || ds.is_null(i) || ds.value(i) < 0 {
+            result.append_null();
+            continue;
+        }
+        let d = ds.value(i) as usize;
+        let (negative, digits) = match values.data_type() {
+            DataType::Int8 => integer_digits(values.as_primitive::<Int8Type>().value(i) as i128),
+            DataType::Int16 => integer_digits(values.as_primitive::<Int16Type>().value(i) as i128),
+            DataType::Int32 => integer_digits(values.as_primitive::<Int32Type>().value(i) as i128),
+            DataType::Int64 => integer_digits(values.as_primitive::<Int64Type>().value(i) as i128),
+            DataType::Float32 => {
+                float_digits(values.as_primitive::<Float32Type>().value(i) as f64, d)
+            }
+            DataType::Float64 => float_digits(values.as_primitive::<Float64Type>().value(i), d),
+            DataType::Decimal128(_, scale) => decimal_digits(
+                values.as_primitive::<Decimal128Type>().value(i),
+                *scale as usize,
+                d,
+            ),
+            other => df_execution_err!("format_number: unsupported data type: {other}")?,
+        };
+        result.append_value(format_number_digits(negative, &digits, d));
+    }
+    Ok(ColumnarValue::Array(Arc::new(result.finish())))
+}
+
+fn integer_digits(value: i128) -> (bool, String) {
+    (value < 0, value.unsigned_abs().to_string())
+}
+
+/// like java's DecimalFormat, the shortest representation is used unless it
+/// has more than d decimal places, in which case the exact value is rounded
+fn float_digits(value: f64, d: usize) -> (bool, String) {
+    let negative = value.is_sign_negative() && !value.is_nan();
+    if !value.is_finite() {
+        let digits = if value.is_nan() { "NaN" } else { "\u{221e}" };
+        return (negative, digits.to_owned());
+    }
+    let shortest = value.abs().to_string();
+    let num_decimals = shortest.split_once('.').map(|(_, f)| f.len()).unwrap_or(0);
+    if num_decimals <= d {
+        return (negative, shortest);
+    }
+    (negative, format!("{:.*}", d, value.abs()))
+}
+
+/// rounds unscaled decimal value to d decimal places with HALF_EVEN rounding
+fn decimal_digits(unscaled: i128, scale: usize, d: usize) -> (bool, String) {
+    let negative = unscaled < 0;
+    let mut abs = unscaled.unsigned_abs();
+    let mut scale = scale;
+    if scale > d {
+        let divisor = 10u128.pow((scale - d) as u32);
+        let (quotient, remainder) = (abs / divisor, abs % divisor);
+        let round_up = match (remainder * 2).cmp(&divisor) {
+            std::cmp::Ordering::Greater => true,
+            std::cmp::Ordering::Equal => quotient % 2 == 1,
+            std::cmp::Ordering::Less => false,
+        };
+        abs = quotient + round_up as u128;
+        scale = d;
+    }
+    let digits = format!("{:0>width$}", abs, width = scale + 1);
+    let (int_part, frac_part) = digits.split_at(digits.len() - scale);
+    if scale == 0 {
+        return (negative, int_part.to_owned());
+    }
+    (negative, format!("{int_part}.{frac_part}"))
+}
+
+/// adds grouping separators and pads decimal places to d
+fn format_number_digits(negative: bool, digits: &str, d: usize) -> String {
+    let (int_part, frac_part) = digits.split_once('.').unwrap_or((digits, ""));
+    let mut formatted = String::with_capacity(digits.len() + digits.len() / 3 + d + 2);
+    if negative {
+        formatted.push('-');
+    }
+    if !int_part.bytes().all(|b| b.is_ascii_digit()) {
+        formatted.push_str(int_part); // NaN or infinity
+        return formatted;
+    }
+    for (i, c) in int_part.chars().enumerate() {
+        if i > 0 && (int_part.len() - i) % 3 == 0 {
+            formatted.push(',');
+        }
+        formatted.push(c);
+    }
+    if d > 0 {
+        formatted.push('.');
+        formatted.push_str(frac_part);
+        formatted.extend(std::iter::repeat('0').take(d - frac_part.len()));
+    }
+    formatted
+}
+
+/// initcap(str), same as spark's str.toLowerCase.toTitleCase: the first char
+/// and chars following a space are converted to title case
+pub fn string_initcap(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = args_to_arrays(args)?;
+    let result: StringArray = as_string_array(&arrays[0])?
+        .iter()
+        .map(|s| {
+            let lower = s?.to_lowercase();
+            let mut prev = ' ';
+            let mut title = String::with_capacity(lower.len());
+            for c in lower.chars() {
+                title.push(if prev == ' ' { to_title_case(c) } else { c });
+                prev = c;
+            }
+            Some(title)
+        })
+        .collect();
+    Ok(ColumnarValue::Array(Arc::new(result)))
+}
+
+/// same as java's Character.toTitleCase(char): only chars in the basic
+/// multilingual plane with single-char mappings are converted
+fn to_title_case(c: char) -> char {
+    match c {
+        '\u{01c4}'..='\u{01c6}' => '\u{01c5}',
+        '\u{01c7}'..='\u{01c9}' => '\u{01c8}',
+        '\u{01ca}'..='\u{01cc}' => '\u{01cb}',
+        '\u{01f1}'..='\u{01f3}' => '\u{01f2}',
+        c if (c as u32) > 0xffff => c,
+        c => {
+            let mut upper = c.to_uppercase();
+            match (upper.next(), upper.next()) {
+                (Some(upper), None) if (upper as u32) <= 0xffff => upper,
+                _ => c,
+            }
+        }
+    }
+}
+
+fn args_to_arrays(args: &[ColumnarValue]) -> Result<Vec<ArrayRef>> {
+    let num_rows = args
+        .iter()
+        .find_map(|arg| match arg {
+            ColumnarValue::Array(array) => Some(array.len()),
+            ColumnarValue::Scalar(_) => None,
+        })
+        .unwrap_or(1);
+    args.iter()
+        .map(|arg| arg.clone().into_array(num_rows))
+        .collect()
+}
+
 #[cfg(test)]
 mod test {
     use std::sync::Arc;
 
-    use arrow::array::{Int32Array, ListBuilder, StringArray, StringBuilder};
+    use arrow::{
+        array::{
+            Decimal128Array, Float64Array, Int32Array, ListBuilder, StringArray, StringBuilder,
+        },
+        datatypes::DataType,
+    };
     use datafusion::{
         common::{
-            cast::{as_list_array, as_string_array},
+            cast::{as_int32_array, as_list_array, as_string_array},
             Result, ScalarValue,
         },
         physical_plan::ColumnarValue,
     };
 
     use crate::spark_strings::{
-        string_concat, string_concat_ws, string_lower, string_repeat, string_space, string_split,
+        string_concat, string_concat_ws, string_format_number, string_initcap, string_instr,
+        string_levenshtein, string_locate, string_lower, string_lpad, string_repeat, string_rpad,
+        string_soundex, string_space, string_split, string_substring_index, string_translate,
         string_upper,
     };
 
+    fn strings(strs: Vec<Option<&str>>) -> ColumnarValue {
+        ColumnarValue::Array(Arc::new(StringArray::from(strs)))
+    }
+
+    fn ints(ints: Vec<Option<i32>>) -> ColumnarValue {
+        ColumnarValue::Array(Arc::new(Int32Array::from(ints)))
+    }
+
+    fn to_strings(r: ColumnarValue) -> Result<Vec<Option<String>>> {
+        let s = r.into_array(1)?;
+        Ok(as_string_array(&s)?
+            .iter()
+            .map(|s| s.map(|s| s.to_owned()))
+            .collect())
+    }
+
+    fn to_ints(r: ColumnarValue) -> Result<Vec<Option<i32>>> {
+        let s = r.into_array(1)?;
+        Ok(as_int32_array(&s)?.iter().collect())
+    }
+
     #[test]
     fn test_string_space() -> Result<()> {
         // positive case
@@ -514,4 +939,313 @@ mod test {
         );
         Ok(())
     }
+
+    #[test]
+    fn test_string_substring_index() -> Result<()> {
+        let r = string_substring_index(&[
+            strings(vec![
+                Some("www.apache.org"),
+                Some("www.apache.org"),
+                Some("www.apache.org"),
+                Some("www.apache.org"),
+                Some("aaa"),
+                Some("aaa"),
+                Some("数据.库"),
+                None,
+            ]),
+            strings(vec![
+                Some("."),
+                Some("."),
+                Some("."),
+                Some(""),
+                Some("aa"),
+                Some("aa"),
+                Some("."),
+                Some("."),
+            ]),
+            ints(vec![
+                Some(2),
+                Some(-2),
+                Some(5),
+                Some(1),
+                Some(2),
+                Some(-2),
+                Some(-1),
+                Some(1),
+            ]),
+        ])?;
+        assert_eq!(
+            to_strings(r)?,
+            vec![
+                Some("www.apache".to_owned()),
+                Some("apache.org".to_owned()),
+                Some("www.apache.org".to_owned()),
+                Some("".to_owned()),
+                Some("a".to_owned()),
+                Some("a".to_owned()),
+                Some("库".to_owned()),
+                None,
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_string_instr_locate() -> Result<()> {
+        let r = string_instr(&[
+            strings(vec![
+                Some("SparkSQL"),
+                Some("数据库SQL"),
+                Some("abc"),
+                Some("abc"),
+                None,
+            ]),
+            strings(vec![
+                Some("SQL"),
+                Some("SQL"),
+                Some(""),
+                Some("d"),
+                Some("a"),
+            ]),
+        ])?;
+        assert_eq!(to_ints(r)?, vec![Some(6), Some(4), Some(1), Some(0), None]);
+
+        let r = string_locate(&[
+            strings(vec![
+                Some("bar"),
+                Some("bar"),
+                Some("bar"),
+                Some("bar"),
+                Some(""),
+                None,
+            ]),
+            strings(vec![
+                Some("foobarbar"),
+                Some("foobarbar"),
+                Some("foobarbar"),
+                Some("foobarbar"),
+                Some("foo"),
+                Some("foobarbar"),
+            ]),
+            ints(vec![Some(1), Some(5), Some(0), None, Some(10), None]),
+        ])?;
+        assert_eq!(
+            to_ints(r)?,
+            vec![Some(4), Some(7), Some(0), Some(0), Some(1), Some(0)]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_string_pad() -> Result<()> {
+        let strs = strings(vec![
+            Some("hi"),
+            Some("hi"),
+            Some("hi"),
+            Some("数据"),
+            Some("hi"),
+            None,
+        ]);
+        let lens = ints(vec![Some(5), Some(1), Some(5), Some(4), Some(-1), Some(5)]);
+        let pads = strings(vec![
+            Some("??"),
+            Some("??"),
+            Some(""),
+            Some("ab"),
+            Some("?"),
+            Some("?"),
+        ]);
+        let r = string_lpad(&[strs.clone(), lens.clone(), pads.clone()])?;
+        assert_eq!(
+            to_strings(r)?,
+            vec![
+                Some("???hi".to_owned()),
+                Some("h".to_owned()),
+                Some("hi".to_owned()),
+                Some("ab数据".to_owned()),
+                Some("".to_owned()),
+                None,
+            ]
+        );
+        let r = string_rpad(&[strs, lens, pads])?;
+        assert_eq!(
+            to_strings(r)?,
+            vec![
+                Some("hi???".to_owned()),
+                Some("h".to_owned()),
+                Some("hi".to_owned()),
+                Some("数据ab".to_owned()),
+                Some("".to_owned()),
+                None,
+            ]
+        );
+
+        // null padding string
+        let r = string_lpad(&[
+            strings(vec![Some("hi")]),
+            ints(vec![Some(5)]),
+            ColumnarValue::Scalar(ScalarValue::Utf8(None)),
+        ])?;
+        assert_eq!(to_strings(r)?, vec![None]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_string_translate() -> Result<()> {
+        let r = string_translate(&[
+            strings(vec![Some("AaBbCc"), Some("abcde"), Some("数据库"), None]),
+            strings(vec![Some("abc"), Some("aab"), Some("据"), Some("a")]),
+            strings(vec![Some("123"), Some("x"), Some("X"), Some("b")]),
+        ])?;
+        assert_eq!(
+            to_strings(r)?,
+            vec![
+                Some("A1B2C3".to_owned()),
+                Some("xcde".to_owned()),
+                Some("数X库".to_owned()),
+                None,
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_string_levenshtein() -> Result<()> {
+        let strs1 = strings(vec![Some("kitten"), Some("数据库"), Some(""), None]);
+        let strs2 = strings(vec![
+            Some("sitting"),
+            Some("数据"),
+            Some("abc"),
+            Some("abc"),
+        ]);
+        let r = string_levenshtein(&[strs1.clone(), strs2.clone()])?;
+        assert_eq!(to_ints(r)?, vec![Some(3), Some(1), Some(3), None]);
+
+        let r = string_levenshtein(&[strs1, strs2, ints(vec![Some(2), Some(2), None, Some(2)])])?;
+        assert_eq!(to_ints(r)?, vec![Some(-1), Some(1), None, None]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_string_soundex() -> Result<()> {
+        let r = string_soundex(&[strings(vec![
+            Some("Miller"),
+            Some("Robert"),
+            Some("Tymczak"),
+            Some("Ashcraft"),
+            Some("a"),
+            Some(""),
+            Some("123"),
+            Some("数据"),
+            None,
+        ])])?;
+        assert_eq!(
+            to_strings(r)?,
+            vec![
+                Some("M460".to_owned()),
+                Some("R163".to_owned()),
+                Some("T522".to_owned()),
+                Some("A261".to_owned()),
+                Some("A000".to_owned()),
+                Some("".to_owned()),
+                Some("123".to_owned()),
+                Some("数据".to_owned()),
+                None,
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_string_format_number() -> Result<()> {
+        let r = string_format_number(&[
+            ColumnarValue::Array(Arc::new(Float64Array::from(vec![
+                Some(12332.123456),
+                Some(-1234567.5),
+                Some(0.125),
+                Some(0.135),
+                Some(0.1),
+                Some(1e23),
+                Some(-0.001),
+                Some(f64::NAN),
+                Some(1.0),
+                None,
+            ]))),
+            ints(vec![
+                Some(4),
+                Some(0),
+                Some(2),
+                Some(2),
+                Some(20),
+                Some(0),
+                Some(2),
+                Some(2),
+                Some(-1),
+                Some(2),
+            ]),
+        ])?;
+        assert_eq!(
+            to_strings(r)?,
+            vec![
+                Some("12,332.1235".to_owned()),
+                Some("-1,234,568".to_owned()),
+                Some("0.12".to_owned()),
+                Some("0.14".to_owned()),
+                Some("0.10000000000000000000".to_owned()),
+                Some("100,000,000,000,000,000,000,000".to_owned()),
+                Some("-0.00".to_owned()),
+                Some("NaN".to_owned()),
+                None,
+                None,
+            ]
+        );
+
+        let r = string_format_number(&[
+            ColumnarValue::Array(Arc::new(
+                Decimal128Array::from(vec![Some(12345675), Some(-5), Some(1000)])
+                    .with_data_type(DataType::Decimal128(10, 3)),
+            )),
+            ColumnarValue::Scalar(ScalarValue::Int32(Some(2))),
+        ])?;
+        assert_eq!(
+            to_strings(r)?,
+            vec![
+                Some("12,345.68".to_owned()),
+                Some("-0.00".to_owned()),
+                Some("1.00".to_owned()),
+            ]
+        );
+
+        let r = string_format_number(&[
+            ints(vec![Some(1234567), Some(-100)]),
+            ColumnarValue::Scalar(ScalarValue::Int32(Some(2))),
+        ])?;
+        assert_eq!(
+            to_strings(r)?,
+            vec![Some("1,234,567.00".to_owned()), Some("-100.00".to_owned())]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_string_initcap() -> Result<()> {
+        let r = string_initcap(&[strings(vec![
+            Some("sPark sql"),
+            Some("hello\tworld  foo"),
+            Some("ǆemal straße"),
+            Some(""),
+            None,
+        ])])?;
+        assert_eq!(
+            to_strings(r)?,
+            vec![
+                Some("Spark Sql".to_owned()),
+                Some("Hello\tworld  Foo".to_owned()),
+                Some("ǅemal Straße".to_owned()),
+                Some("".to_owned()),
+                None,
+            ]
+        );
+        Ok(())
+    }
 }
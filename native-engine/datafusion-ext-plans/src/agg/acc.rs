@@ -17,6 +17,7 @@ use std::{
     hash::BuildHasher,
     io::{Cursor, Read, Write},
     mem::{size_of, size_of_val},
+    sync::Arc,
 };
 
 use arrow::datatypes::DataType;
@@ -29,12 +30,16 @@ use datafusion_ext_commons::{
     io::{read_bytes_slice, read_len, read_scalar, read_u8, write_len, write_scalar, write_u8},
     slim_bytes::SlimBytes,
     spark_bloom_filter::SparkBloomFilter,
+    spark_hyperloglog::SparkHyperLogLog,
+    spark_percentile_digest::SparkPercentileDigest,
 };
 use hashbrown::raw::RawTable;
 use itertools::Itertools;
 use slimmer_box::SlimmerBox;
 use smallvec::SmallVec;
 
+use crate::agg::spark_udaf_wrapper::{SparkUDAFBufferRef, SparkUDAFBuffers};
+
 pub type DynVal = Option<Box<dyn AggDynValue>>;
 
 const ACC_STORE_BLOCK_SIZE: usize = 65536;
@@ -242,7 +247,11 @@ pub trait AccumStateRow {
     fn set_fixed_valid(&mut self, addr: AccumStateValAddr, valid: bool) {
         let idx = addr.fixed_valid_idx();
         let fixed_len = self.fixed().len();
-        self.fixed_mut()[fixed_len - 1 - idx / 8] |= (valid as u8) << (idx % 8);
+        if valid {
+            self.fixed_mut()[fixed_len - 1 - idx / 8] |= 1 << (idx % 8);
+        } else {
+            self.fixed_mut()[fixed_len - 1 - idx / 8] &= !(1 << (idx % 8));
+        }
     }
 
     fn fixed_value<T: Sized + Copy>(&self, addr: AccumStateValAddr) -> T {
@@ -321,6 +330,13 @@ pub enum AccumInitialValue {
         estimated_num_items: usize,
         num_bits: usize,
     },
+    HyperLogLog {
+        precision: usize,
+    },
+    PercentileDigest {
+        accuracy: usize,
+    },
+    SparkUDAFBuffer(Arc<SparkUDAFBuffers>),
 }
 
 pub fn create_acc_from_initial_value(
@@ -413,6 +429,13 @@ pub fn create_acc_from_initial_value(
                     SparkBloomFilter::new_with_expected_num_items(*estimated_num_items, *num_bits),
                 )));
             }
+            AccumInitialValue::HyperLogLog { .. }
+            | AccumInitialValue::PercentileDigest { .. }
+            | AccumInitialValue::SparkUDAFBuffer(_) => {
+                // created lazily on first update
+                addrs.push(AccumStateValAddr::new_dyn(dyns.len()));
+                dyns.push(None);
+            }
         }
     }
 
@@ -542,6 +565,31 @@ pub fn create_dyn_loaders_from_initial_value(values: &[AccumInitialValue]) -> Re
                     _ => Some(Box::new(SparkBloomFilter::read_from(&mut r.0)?)),
                 })
             }),
+            &AccumInitialValue::HyperLogLog { precision } => Box::new(move |r: &mut LoadReader| {
+                Ok(match read_len(&mut r.0)? {
+                    0 => None,
+                    _ => Some(Box::new(SparkHyperLogLog::read_from(&mut r.0, precision)?)),
+                })
+            }),
+            AccumInitialValue::PercentileDigest { .. } => Box::new(move |r: &mut LoadReader| {
+                Ok(match read_len(&mut r.0)? {
+                    0 => None,
+                    _ => Some(Box::new(SparkPercentileDigest::read_from(&mut r.0)?)),
+                })
+            }),
+            AccumInitialValue::SparkUDAFBuffer(buffers) => {
+                let buffers = buffers.clone();
+                Box::new(move |r: &mut LoadReader| {
+                    Ok(match read_len(&mut r.0)? {
+                        0 => None,
+                        n => {
+                            let data_len = n - 1;
+                            let bytes = read_bytes_slice(&mut r.0, data_len)?;
+                            Some(Box::new(buffers.deserialize(&bytes)?))
+                        }
+                    })
+                })
+            }
         };
         loaders.push(loader);
     }
@@ -677,6 +725,47 @@ pub fn create_dyn_savers_from_initial_value(values: &[AccumInitialValue]) -> Res
                 });
                 f
             }
+            AccumInitialValue::HyperLogLog { .. } => {
+                let f: SaveFn = Box::new(move |w: &mut SaveWriter, v: DynVal| -> Result<()> {
+                    if let Some(v) = v {
+                        let hll = downcast_any!(v, SparkHyperLogLog)?;
+                        write_len(1, &mut w.0)?;
+                        hll.write_to(&mut w.0)?;
+                    } else {
+                        write_len(0, &mut w.0)?;
+                    }
+                    Ok(())
+                });
+                f
+            }
+            AccumInitialValue::PercentileDigest { .. } => {
+                let f: SaveFn = Box::new(move |w: &mut SaveWriter, v: DynVal| -> Result<()> {
+                    if let Some(mut v) = v {
+                        let digest = downcast_any!(v, mut SparkPercentileDigest)?;
+                        write_len(1, &mut w.0)?;
+                        digest.write_to(&mut w.0)?;
+                    } else {
+                        write_len(0, &mut w.0)?;
+                    }
+                    Ok(())
+                });
+                f
+            }
+            AccumInitialValue::SparkUDAFBuffer(buffers) => {
+                let buffers = buffers.clone();
+                let f: SaveFn = Box::new(move |w: &mut SaveWriter, v: DynVal| -> Result<()> {
+                    if let Some(v) = v {
+                        let buffer_ref = downcast_any!(v, SparkUDAFBufferRef)?;
+                        let bytes = buffers.serialize(buffer_ref)?;
+                        write_len(bytes.len() + 1, &mut w.0)?;
+                        w.0.write_all(&bytes)?;
+                    } else {
+                        write_len(0, &mut w.0)?;
+                    }
+                    Ok(())
+                });
+                f
+            }
         };
         savers.push(saver);
     }
@@ -1095,6 +1184,50 @@ impl AggDynValue for SparkBloomFilter {
     }
 }
 
+impl AggDynValue for SparkHyperLogLog {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn as_any_boxed(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+
+    fn mem_size(&self) -> usize {
+        SparkHyperLogLog::mem_size(self)
+    }
+
+    fn clone_boxed(&self) -> Box<dyn AggDynValue> {
+        Box::new(self.clone())
+    }
+}
+
+impl AggDynValue for SparkPercentileDigest {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn as_any_boxed(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+
+    fn mem_size(&self) -> usize {
+        SparkPercentileDigest::mem_size(self)
+    }
+
+    fn clone_boxed(&self) -> Box<dyn AggDynValue> {
+        Box::new(self.clone())
+    }
+}
+
 #[derive(Default, Clone, Copy)]
 pub struct AccumStateValAddr(u64);
 
@@ -0,0 +1,220 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    fmt::{Debug, Formatter},
+    sync::{atomic::AtomicUsize, Arc},
+};
+
+use arrow::{array::*, datatypes::*};
+use datafusion::{
+    common::{Result, ScalarValue},
+    physical_expr::PhysicalExpr,
+};
+use datafusion_ext_commons::{
+    downcast_any, spark_hash::create_xxhash64_hashes, spark_hyperloglog::SparkHyperLogLog,
+};
+
+use crate::agg::{
+    acc::{AccumInitialValue, AccumStateRow, AccumStateValAddr, AggDynValue, RefAccumStateRow},
+    Agg, WithAggBufAddrs, WithMemTracking,
+};
+
+// seed used by spark's HyperLogLogPlusPlus to hash input values
+const HLL_HASH_SEED: i64 = 42;
+
+pub struct AggApproxCountDistinct {
+    child: Arc<dyn PhysicalExpr>,
+    precision: usize,
+    accums_initial: Vec<AccumInitialValue>,
+    accum_state_val_addr: AccumStateValAddr,
+    mem_used_tracker: AtomicUsize,
+}
+
+impl WithAggBufAddrs for AggApproxCountDistinct {
+    fn set_accum_state_val_addrs(&mut self, accum_state_val_addrs: &[AccumStateValAddr]) {
+        self.accum_state_val_addr = accum_state_val_addrs[0];
+    }
+}
+
+impl WithMemTracking for AggApproxCountDistinct {
+    fn mem_used_tracker(&self) -> &AtomicUsize {
+        &self.mem_used_tracker
+    }
+}
+
+impl AggApproxCountDistinct {
+    pub fn try_new(child: Arc<dyn PhysicalExpr>, relative_sd: f64) -> Result<Self> {
+        let precision = SparkHyperLogLog::precision_of_relative_sd(relative_sd)?;
+        Ok(Self::new_with_precision(child, precision))
+    }
+
+    fn new_with_precision(child: Arc<dyn PhysicalExpr>, precision: usize) -> Self {
+        Self {
+            child,
+            precision,
+            accums_initial: vec![AccumInitialValue::HyperLogLog { precision }],
+            accum_state_val_addr: AccumStateValAddr::default(),
+            mem_used_tracker: AtomicUsize::new(0),
+        }
+    }
+
+    fn get_or_create_hll<'a>(
+        &self,
+        acc: &'a mut RefAccumStateRow,
+    ) -> Result<&'a mut SparkHyperLogLog> {
+        let v = acc.dyn_value_mut(self.accum_state_val_addr);
+        if v.is_none() {
+            let hll = SparkHyperLogLog::new(self.precision);
+            self.add_mem_used(hll.mem_size());
+            *v = Some(Box::new(hll));
+        }
+        downcast_any!(v.as_mut().unwrap(), mut SparkHyperLogLog)
+    }
+}
+
+impl Debug for AggApproxCountDistinct {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "ApproxCountDistinct({:?}, precision={})",
+            self.child, self.precision,
+        )
+    }
+}
+
+impl Agg for AggApproxCountDistinct {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn exprs(&self) -> Vec<Arc<dyn PhysicalExpr>> {
+        vec![self.child.clone()]
+    }
+
+    fn data_type(&self) -> &DataType {
+        &DataType::Int64
+    }
+
+    fn nullable(&self) -> bool {
+        false
+    }
+
+    fn accums_initial(&self) -> &[AccumInitialValue] {
+        &self.accums_initial
+    }
+
+    fn with_new_exprs(&self, exprs: Vec<Arc<dyn PhysicalExpr>>) -> Result<Arc<dyn Agg>> {
+        Ok(Arc::new(Self::new_with_precision(
+            exprs[0].clone(),
+            self.precision,
+        )))
+    }
+
+    fn increase_acc_mem_used(&self, acc: &mut RefAccumStateRow) {
+        if let Some(v) = acc.dyn_value(self.accum_state_val_addr) {
+            self.add_mem_used(v.mem_size());
+        }
+    }
+
+    fn partial_update(
+        &self,
+        acc: &mut RefAccumStateRow,
+        values: &[ArrayRef],
+        row_idx: usize,
+    ) -> Result<()> {
+        if values[0].is_valid(row_idx) {
+            let value = values[0].slice(row_idx, 1);
+            let hash = create_xxhash64_hashes(1, &[value], HLL_HASH_SEED)[0];
+            self.get_or_create_hll(acc)?.update(hash as u64);
+        }
+        Ok(())
+    }
+
+    fn partial_batch_update(
+        &self,
+        accs: &mut [RefAccumStateRow],
+        values: &[ArrayRef],
+    ) -> Result<()> {
+        let hashes = create_xxhash64_hashes(values[0].len(), &values[..1], HLL_HASH_SEED);
+        for (row_idx, acc) in accs.iter_mut().enumerate() {
+            if values[0].is_valid(row_idx) {
+                self.get_or_create_hll(acc)?.update(hashes[row_idx] as u64);
+            }
+        }
+        Ok(())
+    }
+
+    fn partial_update_all(
+        &self,
+        acc: &mut RefAccumStateRow,
+        num_rows: usize,
+        values: &[ArrayRef],
+    ) -> Result<()> {
+        let hll = self.get_or_create_hll(acc)?;
+        let hashes = create_xxhash64_hashes(num_rows, &values[..1], HLL_HASH_SEED);
+        for (row_idx, hash) in hashes.into_iter().enumerate() {
+            if values[0].is_valid(row_idx) {
+                hll.update(hash as u64);
+            }
+        }
+        Ok(())
+    }
+
+    fn partial_merge(
+        &self,
+        acc: &mut RefAccumStateRow,
+        merging_acc: &mut RefAccumStateRow,
+    ) -> Result<()> {
+        match (
+            acc.dyn_value_mut(self.accum_state_val_addr),
+            merging_acc.dyn_value_mut(self.accum_state_val_addr),
+        ) {
+            (Some(w), Some(v)) => {
+                let w = downcast_any!(w, mut SparkHyperLogLog)?;
+                let v = downcast_any!(v, mut SparkHyperLogLog)?;
+                self.sub_mem_used(v.mem_size());
+                w.merge(v);
+            }
+            (w_none, v @ Some(_)) => *w_none = std::mem::take(v),
+            (None, _) => {}
+            (_, None) => {}
+        }
+        Ok(())
+    }
+
+    fn final_merge(&self, acc: &mut RefAccumStateRow) -> Result<ScalarValue> {
+        match std::mem::take(acc.dyn_value_mut(self.accum_state_val_addr)) {
+            Some(w) => {
+                self.sub_mem_used(w.mem_size());
+                let hll = downcast_any!(w, SparkHyperLogLog)?;
+                Ok(ScalarValue::Int64(Some(hll.count())))
+            }
+            None => Ok(ScalarValue::Int64(Some(0))),
+        }
+    }
+
+    fn final_batch_merge(&self, accs: &mut [RefAccumStateRow]) -> Result<ArrayRef> {
+        let counts = accs
+            .iter_mut()
+            .map(|acc| self.final_merge(acc))
+            .collect::<Result<Vec<_>>>()?;
+
+        if counts.is_empty() {
+            return Ok(new_empty_array(self.data_type()));
+        }
+        Ok(ScalarValue::iter_to_array(counts)?)
+    }
+}
@@ -0,0 +1,308 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    fmt::{Debug, Formatter},
+    sync::{atomic::AtomicUsize, Arc},
+};
+
+use arrow::{
+    array::*,
+    buffer::{NullBuffer, OffsetBuffer},
+    datatypes::*,
+};
+use datafusion::{
+    common::{Result, ScalarValue},
+    physical_expr::PhysicalExpr,
+};
+use datafusion_ext_commons::{
+    cast::cast, df_execution_err, downcast_any, spark_percentile_digest::SparkPercentileDigest,
+};
+
+use crate::agg::{
+    acc::{AccumInitialValue, AccumStateRow, AccumStateValAddr, AggDynValue, RefAccumStateRow},
+    Agg, WithAggBufAddrs, WithMemTracking,
+};
+
+// same as spark's ApproximatePercentile.DEFAULT_PERCENTILE_ACCURACY
+pub const DEFAULT_PERCENTILE_ACCURACY: usize = 10000;
+
+pub struct AggApproxPercentile {
+    child: Arc<dyn PhysicalExpr>,
+    child_data_type: DataType,
+    data_type: DataType,
+    percentages: Vec<f64>,
+    return_list: bool,
+    accuracy: usize,
+    accums_initial: Vec<AccumInitialValue>,
+    accum_state_val_addr: AccumStateValAddr,
+    mem_used_tracker: AtomicUsize,
+}
+
+impl WithAggBufAddrs for AggApproxPercentile {
+    fn set_accum_state_val_addrs(&mut self, accum_state_val_addrs: &[AccumStateValAddr]) {
+        self.accum_state_val_addr = accum_state_val_addrs[0];
+    }
+}
+
+impl WithMemTracking for AggApproxPercentile {
+    fn mem_used_tracker(&self) -> &AtomicUsize {
+        &self.mem_used_tracker
+    }
+}
+
+impl AggApproxPercentile {
+    pub fn try_new(
+        child: Arc<dyn PhysicalExpr>,
+        child_data_type: DataType,
+        percentages: Vec<f64>,
+        return_list: bool,
+        accuracy: usize,
+    ) -> Result<Self> {
+        if let Some(p) = percentages.iter().find(|p| !(0.0..=1.0).contains(*p)) {
+            return df_execution_err!("percentage must be between 0.0 and 1.0, got {p}");
+        }
+        let data_type = if return_list {
+            DataType::new_list(child_data_type.clone(), true)
+        } else {
+            child_data_type.clone()
+        };
+        Ok(Self {
+            child,
+            child_data_type,
+            data_type,
+            percentages,
+            return_list,
+            accuracy,
+            accums_initial: vec![AccumInitialValue::PercentileDigest { accuracy }],
+            accum_state_val_addr: AccumStateValAddr::default(),
+            mem_used_tracker: AtomicUsize::new(0),
+        })
+    }
+
+    fn get_or_create_digest<'a>(
+        &self,
+        acc: &'a mut RefAccumStateRow,
+    ) -> Result<&'a mut SparkPercentileDigest> {
+        let v = acc.dyn_value_mut(self.accum_state_val_addr);
+        if v.is_none() {
+            let digest = SparkPercentileDigest::new(1.0 / self.accuracy as f64);
+            self.add_mem_used(digest.mem_size());
+            *v = Some(Box::new(digest));
+        }
+        downcast_any!(v.as_mut().unwrap(), mut SparkPercentileDigest)
+    }
+
+    fn percentiles(&self, acc: &mut RefAccumStateRow) -> Result<Option<Vec<f64>>> {
+        match std::mem::take(acc.dyn_value_mut(self.accum_state_val_addr)) {
+            Some(mut w) => {
+                self.sub_mem_used(w.mem_size());
+                let digest = downcast_any!(w, mut SparkPercentileDigest)?;
+                Ok(digest.percentiles(&self.percentages))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+impl Debug for AggApproxPercentile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "ApproxPercentile({:?}, {:?}, accuracy={})",
+            self.child, self.percentages, self.accuracy,
+        )
+    }
+}
+
+impl Agg for AggApproxPercentile {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn exprs(&self) -> Vec<Arc<dyn PhysicalExpr>> {
+        vec![self.child.clone()]
+    }
+
+    fn data_type(&self) -> &DataType {
+        &self.data_type
+    }
+
+    fn nullable(&self) -> bool {
+        true
+    }
+
+    fn accums_initial(&self) -> &[AccumInitialValue] {
+        &self.accums_initial
+    }
+
+    fn with_new_exprs(&self, exprs: Vec<Arc<dyn PhysicalExpr>>) -> Result<Arc<dyn Agg>> {
+        Ok(Arc::new(Self::try_new(
+            exprs[0].clone(),
+            self.child_data_type.clone(),
+            self.percentages.clone(),
+            self.return_list,
+            self.accuracy,
+        )?))
+    }
+
+    fn increase_acc_mem_used(&self, acc: &mut RefAccumStateRow) {
+        if let Some(v) = acc.dyn_value(self.accum_state_val_addr) {
+            self.add_mem_used(v.mem_size());
+        }
+    }
+
+    fn partial_update(
+        &self,
+        acc: &mut RefAccumStateRow,
+        values: &[ArrayRef],
+        row_idx: usize,
+    ) -> Result<()> {
+        if values[0].is_valid(row_idx) {
+            let value = to_f64_array(&values[0].slice(row_idx, 1))?;
+            let digest = self.get_or_create_digest(acc)?;
+            self.sub_mem_used(digest.mem_size());
+            digest.add(value.as_primitive::<Float64Type>().value(0));
+            self.add_mem_used(digest.mem_size());
+        }
+        Ok(())
+    }
+
+    fn partial_batch_update(
+        &self,
+        accs: &mut [RefAccumStateRow],
+        values: &[ArrayRef],
+    ) -> Result<()> {
+        let values = to_f64_array(&values[0])?;
+        let values = values.as_primitive::<Float64Type>();
+        for (row_idx, acc) in accs.iter_mut().enumerate() {
+            if values.is_valid(row_idx) {
+                let digest = self.get_or_create_digest(acc)?;
+                self.sub_mem_used(digest.mem_size());
+                digest.add(values.value(row_idx));
+                self.add_mem_used(digest.mem_size());
+            }
+        }
+        Ok(())
+    }
+
+    fn partial_update_all(
+        &self,
+        acc: &mut RefAccumStateRow,
+        _num_rows: usize,
+        values: &[ArrayRef],
+    ) -> Result<()> {
+        let values = to_f64_array(&values[0])?;
+        let digest = self.get_or_create_digest(acc)?;
+        self.sub_mem_used(digest.mem_size());
+        for value in values.as_primitive::<Float64Type>().iter().flatten() {
+            digest.add(value);
+        }
+        self.add_mem_used(digest.mem_size());
+        Ok(())
+    }
+
+    fn partial_merge(
+        &self,
+        acc: &mut RefAccumStateRow,
+        merging_acc: &mut RefAccumStateRow,
+    ) -> Result<()> {
+        match (
+            acc.dyn_value_mut(self.accum_state_val_addr),
+            merging_acc.dyn_value_mut(self.accum_state_val_addr),
+        ) {
+            (Some(w), Some(v)) => {
+                let w = downcast_any!(w, mut SparkPercentileDigest)?;
+                let v = downcast_any!(v, mut SparkPercentileDigest)?;
+                self.sub_mem_used(w.mem_size());
+                self.sub_mem_used(v.mem_size());
+                w.merge(v);
+                self.add_mem_used(w.mem_size());
+            }
+            (w_none, v @ Some(_)) => *w_none = std::mem::take(v),
+            (None, _) => {}
+            (_, None) => {}
+        }
+        Ok(())
+    }
+
+    fn final_merge(&self, acc: &mut RefAccumStateRow) -> Result<ScalarValue> {
+        let array = self.final_batch_merge(std::slice::from_mut(acc))?;
+        Ok(ScalarValue::try_from_array(&array, 0)?)
+    }
+
+    fn final_batch_merge(&self, accs: &mut [RefAccumStateRow]) -> Result<ArrayRef> {
+        let mut flatten_values: Vec<f64> = vec![];
+        let mut offsets = vec![0i32];
+        let mut valids = BooleanBufferBuilder::new(accs.len());
+        for acc in accs {
+            match self.percentiles(acc)? {
+                Some(percentiles) => {
+                    flatten_values.extend(percentiles);
+                    valids.append(true);
+                }
+                None => {
+                    if !self.return_list {
+                        flatten_values.push(0.0);
+                    }
+                    valids.append(false);
+                }
+            }
+            offsets.push(flatten_values.len() as i32);
+        }
+        let nulls = NullBuffer::new(valids.finish());
+        let flatten_values: ArrayRef = Arc::new(Float64Array::from(flatten_values));
+        let flatten_values = from_f64_array(&flatten_values, &self.child_data_type)?;
+
+        if self.return_list {
+            let DataType::List(field) = &self.data_type else {
+                unreachable!("return type of percentile_approx must be list");
+            };
+            return Ok(Arc::new(ListArray::try_new(
+                field.clone(),
+                OffsetBuffer::new(offsets.into()),
+                flatten_values,
+                Some(nulls),
+            )?));
+        }
+        let nulls = NullBuffer::union(flatten_values.nulls(), Some(&nulls));
+        Ok(make_array(
+            flatten_values
+                .to_data()
+                .into_builder()
+                .nulls(nulls)
+                .build()?,
+        ))
+    }
+}
+
+/// converts input values to doubles, same as spark's ApproximatePercentile
+fn to_f64_array(array: &ArrayRef) -> Result<ArrayRef> {
+    Ok(match array.data_type() {
+        DataType::Date32 => cast(&cast(array, &DataType::Int32)?, &DataType::Float64)?,
+        DataType::Timestamp(..) => cast(&cast(array, &DataType::Int64)?, &DataType::Float64)?,
+        _ => cast(array, &DataType::Float64)?,
+    })
+}
+
+/// converts percentiles back to the input type, same as spark's
+/// ApproximatePercentile
+fn from_f64_array(array: &ArrayRef, data_type: &DataType) -> Result<ArrayRef> {
+    Ok(match data_type {
+        DataType::Date32 => cast(&cast(array, &DataType::Int32)?, data_type)?,
+        DataType::Timestamp(..) => cast(&cast(array, &DataType::Int64)?, data_type)?,
+        _ => cast(array, data_type)?,
+    })
+}
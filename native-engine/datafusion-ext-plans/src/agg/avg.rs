@@ -26,18 +26,19 @@ use datafusion::{
     },
     physical_expr::PhysicalExpr,
 };
-use datafusion_ext_commons::df_unimplemented_err;
+use datafusion_ext_commons::{df_unimplemented_err, spark_eval_mode::EvalMode};
 
 use crate::agg::{
     acc::{AccumInitialValue, AccumStateValAddr, RefAccumStateRow},
     count::AggCount,
-    sum::AggSum,
+    sum::{check_decimal_overflow, AggSum},
     Agg, WithAggBufAddrs, WithMemTracking,
 };
 
 pub struct AggAvg {
     child: Arc<dyn PhysicalExpr>,
     data_type: DataType,
+    eval_mode: EvalMode,
     agg_sum: AggSum,
     agg_count: AggCount,
     accums_initial: Vec<AccumInitialValue>,
@@ -62,7 +63,20 @@ impl WithMemTracking for AggAvg {
 
 impl AggAvg {
     pub fn try_new(child: Arc<dyn PhysicalExpr>, data_type: DataType) -> Result<Self> {
-        let agg_sum = AggSum::try_new(child.clone(), data_type.clone())?;
+        Self::try_new_with_eval_mode(child, data_type, EvalMode::Legacy)
+    }
+
+    pub fn try_new_with_eval_mode(
+        child: Arc<dyn PhysicalExpr>,
+        data_type: DataType,
+        eval_mode: EvalMode,
+    ) -> Result<Self> {
+        // decimal sums are allowed to exceed the precision of the average
+        let sum_type = match data_type {
+            DataType::Decimal128(_, scale) => DataType::Decimal128(DECIMAL128_MAX_PRECISION, scale),
+            _ => data_type.clone(),
+        };
+        let agg_sum = AggSum::try_new_with_eval_mode(child.clone(), sum_type, eval_mode)?;
         let agg_count = AggCount::try_new(child.clone(), DataType::Int64)?;
         let accums_initial = [agg_sum.accums_initial(), agg_count.accums_initial()].concat();
         let final_merger = get_final_merger(&data_type)?;
@@ -70,6 +84,7 @@ impl AggAvg {
         Ok(Self {
             child,
             data_type,
+            eval_mode,
             agg_sum,
             agg_count,
             accums_initial,
@@ -95,9 +110,10 @@ impl Agg for AggAvg {
     }
 
     fn with_new_exprs(&self, exprs: Vec<Arc<dyn PhysicalExpr>>) -> Result<Arc<dyn Agg>> {
-        Ok(Arc::new(Self::try_new(
+        Ok(Arc::new(Self::try_new_with_eval_mode(
             exprs[0].clone(),
             self.data_type.clone(),
+            self.eval_mode,
         )?))
     }
 
@@ -184,7 +200,15 @@ impl Agg for AggAvg {
             _ => unreachable!(),
         };
         let final_merger = self.final_merger;
-        Ok(final_merger(sum, count))
+        let avg = final_merger(sum, count);
+        if let (&ScalarValue::Decimal128(avg, ..), &DataType::Decimal128(prec, scale)) =
+            (&avg, &self.data_type)
+        {
+            let avgs = Decimal128Array::from(vec![avg]).with_precision_and_scale(prec, scale)?;
+            let avgs = check_decimal_overflow(Arc::new(avgs), self.eval_mode)?;
+            return ScalarValue::try_from_array(&avgs, 0);
+        }
+        Ok(avg)
     }
 
     fn final_batch_merge(&self, accs: &mut [RefAccumStateRow]) -> Result<ArrayRef> {
@@ -203,7 +227,8 @@ impl Agg for AggAvg {
                 arrow::compute::binary::<_, _, _, Decimal128Type>(&sums, &counts, |sum, count| {
                     sum.checked_div_euclid(count as i128).unwrap_or_default()
                 })?;
-            Ok(Arc::new(avgs.with_precision_and_scale(prec, scale)?))
+            let avgs = avgs.with_precision_and_scale(prec, scale)?;
+            check_decimal_overflow(Arc::new(avgs), self.eval_mode)
         } else {
             let counts = counts_zero_free;
             Ok(arrow::compute::kernels::numeric::div(
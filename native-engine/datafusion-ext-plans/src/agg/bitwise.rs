@@ -0,0 +1,278 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    fmt::{Debug, Formatter},
+    ops::{BitAnd, BitOr, BitXor},
+    sync::{atomic::AtomicUsize, Arc},
+};
+
+use arrow::{array::*, datatypes::*};
+use datafusion::{
+    common::{Result, ScalarValue},
+    physical_expr::PhysicalExpr,
+};
+use datafusion_ext_commons::df_unimplemented_err;
+
+use crate::agg::{
+    acc::{AccumInitialValue, AccumStateRow, AccumStateValAddr, RefAccumStateRow},
+    default_final_batch_merge_with_addr, default_final_merge_with_addr, Agg, WithAggBufAddrs,
+    WithMemTracking,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitwiseType {
+    BitAnd,
+    BitOr,
+    BitXor,
+}
+
+impl BitwiseType {
+    fn apply<T: BitwiseNative>(&self, v1: T, v2: T) -> T {
+        match self {
+            BitwiseType::BitAnd => v1 & v2,
+            BitwiseType::BitOr => v1 | v2,
+            BitwiseType::BitXor => v1 ^ v2,
+        }
+    }
+}
+
+trait BitwiseNative:
+    Copy + BitAnd<Output = Self> + BitOr<Output = Self> + BitXor<Output = Self>
+{
+}
+
+impl<T: Copy + BitAnd<Output = T> + BitOr<Output = T> + BitXor<Output = T>> BitwiseNative for T {}
+
+/// bit_and/bit_or/bit_xor aggregates on integral types, null values are
+/// ignored and the result is null if there are no non-null values.
+pub struct AggBitwise {
+    child: Arc<dyn PhysicalExpr>,
+    data_type: DataType,
+    bitwise_type: BitwiseType,
+    accums_initial: Vec<AccumInitialValue>,
+    accum_state_val_addr: AccumStateValAddr,
+    mem_used_tracker: AtomicUsize,
+}
+
+impl WithAggBufAddrs for AggBitwise {
+    fn set_accum_state_val_addrs(&mut self, accum_state_val_addrs: &[AccumStateValAddr]) {
+        self.accum_state_val_addr = accum_state_val_addrs[0];
+    }
+}
+
+impl WithMemTracking for AggBitwise {
+    fn mem_used_tracker(&self) -> &AtomicUsize {
+        &self.mem_used_tracker
+    }
+}
+
+macro_rules! dispatch_integral {
+    ($dt:expr, $this:ident. $f:ident($($args:expr),*)) => {{
+        match $dt {
+            DataType::Int8 => $this.$f::<Int8Type>($($args),*),
+            DataType::Int16 => $this.$f::<Int16Type>($($args),*),
+            DataType::Int32 => $this.$f::<Int32Type>($($args),*),
+            DataType::Int64 => $this.$f::<Int64Type>($($args),*),
+            other => df_unimplemented_err!("unsupported data type in bitwise aggregate: {other}"),
+        }
+    }};
+}
+
+impl AggBitwise {
+    pub fn try_new(
+        child: Arc<dyn PhysicalExpr>,
+        data_type: DataType,
+        bitwise_type: BitwiseType,
+    ) -> Result<Self> {
+        if !matches!(
+            data_type,
+            DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64
+        ) {
+            return df_unimplemented_err!(
+                "unsupported data type in bitwise aggregate: {data_type}"
+            );
+        }
+        Ok(Self {
+            child,
+            accums_initial: vec![AccumInitialValue::Scalar(ScalarValue::try_from(
+                &data_type,
+            )?)],
+            data_type,
+            bitwise_type,
+            accum_state_val_addr: AccumStateValAddr::default(),
+            mem_used_tracker: AtomicUsize::new(0),
+        })
+    }
+
+    fn update_value<T: BitwiseNative>(&self, acc: &mut RefAccumStateRow, v: T) {
+        let addr = self.accum_state_val_addr;
+        if acc.is_fixed_valid(addr) {
+            acc.update_fixed_value::<T>(addr, |w| self.bitwise_type.apply(w, v));
+        } else {
+            acc.set_fixed_value::<T>(addr, v);
+            acc.set_fixed_valid(addr, true);
+        }
+    }
+
+    fn update_row<T: ArrowPrimitiveType>(
+        &self,
+        acc: &mut RefAccumStateRow,
+        values: &ArrayRef,
+        row_idx: usize,
+    ) -> Result<()>
+    where
+        T::Native: BitwiseNative,
+    {
+        let values = values.as_primitive::<T>();
+        if values.is_valid(row_idx) {
+            self.update_value(acc, values.value(row_idx));
+        }
+        Ok(())
+    }
+
+    fn update_batch<T: ArrowPrimitiveType>(
+        &self,
+        accs: &mut [RefAccumStateRow],
+        values: &ArrayRef,
+    ) -> Result<()>
+    where
+        T::Native: BitwiseNative,
+    {
+        let values = values.as_primitive::<T>();
+        for (acc, value) in accs.iter_mut().zip(values.iter()) {
+            if let Some(value) = value {
+                self.update_value(acc, value);
+            }
+        }
+        Ok(())
+    }
+
+    fn update_all<T: ArrowPrimitiveType>(
+        &self,
+        acc: &mut RefAccumStateRow,
+        values: &ArrayRef,
+    ) -> Result<()>
+    where
+        T::Native: BitwiseNative,
+    {
+        let values = values.as_primitive::<T>();
+        let reduced = values
+            .iter()
+            .flatten()
+            .reduce(|v1, v2| self.bitwise_type.apply(v1, v2));
+        if let Some(reduced) = reduced {
+            self.update_value(acc, reduced);
+        }
+        Ok(())
+    }
+
+    fn merge<T: ArrowPrimitiveType>(
+        &self,
+        acc: &mut RefAccumStateRow,
+        merging_acc: &mut RefAccumStateRow,
+    ) -> Result<()>
+    where
+        T::Native: BitwiseNative,
+    {
+        let addr = self.accum_state_val_addr;
+        if merging_acc.is_fixed_valid(addr) {
+            self.update_value(acc, merging_acc.fixed_value::<T::Native>(addr));
+        }
+        Ok(())
+    }
+}
+
+impl Debug for AggBitwise {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}({:?})", self.bitwise_type, self.child)
+    }
+}
+
+impl Agg for AggBitwise {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn exprs(&self) -> Vec<Arc<dyn PhysicalExpr>> {
+        vec![self.child.clone()]
+    }
+
+    fn with_new_exprs(&self, exprs: Vec<Arc<dyn PhysicalExpr>>) -> Result<Arc<dyn Agg>> {
+        Ok(Arc::new(Self::try_new(
+            exprs[0].clone(),
+            self.data_type.clone(),
+            self.bitwise_type,
+        )?))
+    }
+
+    fn data_type(&self) -> &DataType {
+        &self.data_type
+    }
+
+    fn nullable(&self) -> bool {
+        true
+    }
+
+    fn accums_initial(&self) -> &[AccumInitialValue] {
+        &self.accums_initial
+    }
+
+    fn increase_acc_mem_used(&self, _acc: &mut RefAccumStateRow) {
+        // do nothing
+    }
+
+    fn partial_update(
+        &self,
+        acc: &mut RefAccumStateRow,
+        values: &[ArrayRef],
+        row_idx: usize,
+    ) -> Result<()> {
+        dispatch_integral!(&self.data_type, self.update_row(acc, &values[0], row_idx))
+    }
+
+    fn partial_batch_update(
+        &self,
+        accs: &mut [RefAccumStateRow],
+        values: &[ArrayRef],
+    ) -> Result<()> {
+        dispatch_integral!(&self.data_type, self.update_batch(accs, &values[0]))
+    }
+
+    fn partial_update_all(
+        &self,
+        acc: &mut RefAccumStateRow,
+        _num_rows: usize,
+        values: &[ArrayRef],
+    ) -> Result<()> {
+        dispatch_integral!(&self.data_type, self.update_all(acc, &values[0]))
+    }
+
+    fn partial_merge(
+        &self,
+        acc: &mut RefAccumStateRow,
+        merging_acc: &mut RefAccumStateRow,
+    ) -> Result<()> {
+        dispatch_integral!(&self.data_type, self.merge(acc, merging_acc))
+    }
+
+    fn final_merge(&self, acc: &mut RefAccumStateRow) -> Result<ScalarValue> {
+        default_final_merge_with_addr(self, acc, self.accum_state_val_addr)
+    }
+
+    fn final_batch_merge(&self, accs: &mut [RefAccumStateRow]) -> Result<ArrayRef> {
+        default_final_batch_merge_with_addr(self, accs, self.accum_state_val_addr)
+    }
+}
@@ -0,0 +1,276 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    fmt::{Debug, Formatter},
+    sync::{atomic::AtomicUsize, Arc},
+};
+
+use arrow::{array::*, datatypes::*};
+use datafusion::{
+    common::{Result, ScalarValue},
+    physical_expr::PhysicalExpr,
+};
+
+use crate::agg::{
+    acc::{AccumInitialValue, AccumStateRow, AccumStateValAddr, RefAccumStateRow},
+    Agg, WithAggBufAddrs, WithMemTracking,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CentralMomentType {
+    StddevSamp,
+    StddevPop,
+    VarSamp,
+    VarPop,
+}
+
+/// stddev/variance aggregates, with the same accumulator layout (n, avg, m2)
+/// as spark's CentralMomentAgg.
+pub struct AggCentralMoment {
+    child: Arc<dyn PhysicalExpr>,
+    moment_type: CentralMomentType,
+    null_on_divide_by_zero: bool,
+    accums_initial: Vec<AccumInitialValue>,
+    accum_state_val_addrs: [AccumStateValAddr; 3],
+    mem_used_tracker: AtomicUsize,
+}
+
+impl WithAggBufAddrs for AggCentralMoment {
+    fn set_accum_state_val_addrs(&mut self, accum_state_val_addrs: &[AccumStateValAddr]) {
+        self.accum_state_val_addrs
+            .copy_from_slice(&accum_state_val_addrs[..3]);
+    }
+}
+
+impl WithMemTracking for AggCentralMoment {
+    fn mem_used_tracker(&self) -> &AtomicUsize {
+        &self.mem_used_tracker
+    }
+}
+
+impl AggCentralMoment {
+    /// child must be evaluated to Float64. null_on_divide_by_zero is the
+    /// negation of spark.sql.legacy.statisticalAggregate
+    pub fn try_new(
+        child: Arc<dyn PhysicalExpr>,
+        moment_type: CentralMomentType,
+        null_on_divide_by_zero: bool,
+    ) -> Result<Self> {
+        Ok(Self {
+            child,
+            moment_type,
+            null_on_divide_by_zero,
+            accums_initial: vec![AccumInitialValue::Scalar(ScalarValue::Float64(Some(0.0))); 3],
+            accum_state_val_addrs: [AccumStateValAddr::default(); 3],
+            mem_used_tracker: AtomicUsize::new(0),
+        })
+    }
+
+    fn evaluate(&self, moments: Moments) -> Option<f64> {
+        let Moments { n, m2, .. } = moments;
+        if n == 0.0 {
+            return None;
+        }
+        let divide_by_zero_result = (!self.null_on_divide_by_zero).then_some(f64::NAN);
+        match self.moment_type {
+            CentralMomentType::StddevPop => Some((m2 / n).sqrt()),
+            CentralMomentType::VarPop => Some(m2 / n),
+            CentralMomentType::StddevSamp if n == 1.0 => divide_by_zero_result,
+            CentralMomentType::StddevSamp => Some((m2 / (n - 1.0)).sqrt()),
+            CentralMomentType::VarSamp if n == 1.0 => divide_by_zero_result,
+            CentralMomentType::VarSamp => Some(m2 / (n - 1.0)),
+        }
+    }
+}
+
+impl Debug for AggCentralMoment {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}({:?})", self.moment_type, self.child)
+    }
+}
+
+impl Agg for AggCentralMoment {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn exprs(&self) -> Vec<Arc<dyn PhysicalExpr>> {
+        vec![self.child.clone()]
+    }
+
+    fn with_new_exprs(&self, exprs: Vec<Arc<dyn PhysicalExpr>>) -> Result<Arc<dyn Agg>> {
+        Ok(Arc::new(Self::try_new(
+            exprs[0].clone(),
+            self.moment_type,
+            self.null_on_divide_by_zero,
+        )?))
+    }
+
+    fn data_type(&self) -> &DataType {
+        &DataType::Float64
+    }
+
+    fn nullable(&self) -> bool {
+        true
+    }
+
+    fn accums_initial(&self) -> &[AccumInitialValue] {
+        &self.accums_initial
+    }
+
+    fn increase_acc_mem_used(&self, _acc: &mut RefAccumStateRow) {
+        // do nothing
+    }
+
+    fn partial_update(
+        &self,
+        acc: &mut RefAccumStateRow,
+        values: &[ArrayRef],
+        row_idx: usize,
+    ) -> Result<()> {
+        let values = values[0].as_primitive::<Float64Type>();
+        if values.is_valid(row_idx) {
+            let mut moments = Moments::load(acc, &self.accum_state_val_addrs);
+            moments.update(values.value(row_idx));
+            moments.save(acc, &self.accum_state_val_addrs);
+        }
+        Ok(())
+    }
+
+    fn partial_batch_update(
+        &self,
+        accs: &mut [RefAccumStateRow],
+        values: &[ArrayRef],
+    ) -> Result<()> {
+        let values = values[0].as_primitive::<Float64Type>();
+        for (acc, value) in accs.iter_mut().zip(values.iter()) {
+            if let Some(value) = value {
+                let mut moments = Moments::load(acc, &self.accum_state_val_addrs);
+                moments.update(value);
+                moments.save(acc, &self.accum_state_val_addrs);
+            }
+        }
+        Ok(())
+    }
+
+    fn partial_update_all(
+        &self,
+        acc: &mut RefAccumStateRow,
+        _num_rows: usize,
+        values: &[ArrayRef],
+    ) -> Result<()> {
+        let mut moments = Moments::load(acc, &self.accum_state_val_addrs);
+        for value in values[0].as_primitive::<Float64Type>().iter().flatten() {
+            moments.update(value);
+        }
+        moments.save(acc, &self.accum_state_val_addrs);
+        Ok(())
+    }
+
+    fn partial_merge(
+        &self,
+        acc: &mut RefAccumStateRow,
+        merging_acc: &mut RefAccumStateRow,
+    ) -> Result<()> {
+        let mut moments = Moments::load(acc, &self.accum_state_val_addrs);
+        moments.merge(Moments::load(merging_acc, &self.accum_state_val_addrs));
+        moments.save(acc, &self.accum_state_val_addrs);
+        Ok(())
+    }
+
+    fn final_merge(&self, acc: &mut RefAccumStateRow) -> Result<ScalarValue> {
+        let moments = Moments::load(acc, &self.accum_state_val_addrs);
+        Ok(ScalarValue::Float64(self.evaluate(moments)))
+    }
+
+    fn final_batch_merge(&self, accs: &mut [RefAccumStateRow]) -> Result<ArrayRef> {
+        Ok(Arc::new(
+            accs.iter()
+                .map(|acc| self.evaluate(Moments::load(acc, &self.accum_state_val_addrs)))
+                .collect::<Float64Array>(),
+        ))
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Moments {
+    n: f64,
+    avg: f64,
+    m2: f64,
+}
+
+impl Moments {
+    fn load(acc: &RefAccumStateRow, addrs: &[AccumStateValAddr; 3]) -> Self {
+        Self {
+            n: acc.fixed_value(addrs[0]),
+            avg: acc.fixed_value(addrs[1]),
+            m2: acc.fixed_value(addrs[2]),
+        }
+    }
+
+    fn save(self, acc: &mut RefAccumStateRow, addrs: &[AccumStateValAddr; 3]) {
+        acc.set_fixed_value(addrs[0], self.n);
+        acc.set_fixed_value(addrs[1], self.avg);
+        acc.set_fixed_value(addrs[2], self.m2);
+    }
+
+    // same as CentralMomentAgg.updateExpressions
+    fn update(&mut self, value: f64) {
+        let n = self.n + 1.0;
+        let delta = value - self.avg;
+        let delta_n = delta / n;
+        self.n = n;
+        self.avg += delta_n;
+        self.m2 += delta * (delta - delta_n);
+    }
+
+    // same as CentralMomentAgg.mergeExpressions
+    fn merge(&mut self, other: Self) {
+        let (n1, n2) = (self.n, other.n);
+        let n = n1 + n2;
+        let delta = other.avg - self.avg;
+        let delta_n = if n == 0.0 { 0.0 } else { delta / n };
+        self.n = n;
+        self.avg += delta_n * n2;
+        self.m2 += other.m2 + delta * delta_n * n1 * n2;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::agg::central_moment::Moments;
+
+    #[test]
+    fn test_moments_update_and_merge() {
+        let values = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0];
+        let empty = Moments {
+            n: 0.0,
+            avg: 0.0,
+            m2: 0.0,
+        };
+
+        let mut all = empty;
+        values.iter().for_each(|&v| all.update(v));
+        assert_eq!((all.n, all.avg, all.m2), (8.0, 5.0, 32.0));
+
+        let (mut left, mut right) = (empty, empty);
+        values[..3].iter().for_each(|&v| left.update(v));
+        values[3..].iter().for_each(|&v| right.update(v));
+        left.merge(right);
+        left.merge(empty);
+        assert_eq!((left.n, left.avg, left.m2), (8.0, 5.0, 32.0));
+    }
+}
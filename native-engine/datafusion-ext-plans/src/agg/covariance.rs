@@ -0,0 +1,342 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    fmt::{Debug, Formatter},
+    sync::{atomic::AtomicUsize, Arc},
+};
+
+use arrow::{array::*, datatypes::*};
+use datafusion::{
+    common::{Result, ScalarValue},
+    physical_expr::PhysicalExpr,
+};
+
+use crate::agg::{
+    acc::{AccumInitialValue, AccumStateRow, AccumStateValAddr, RefAccumStateRow},
+    Agg, WithAggBufAddrs, WithMemTracking,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CovarianceType {
+    CovarSamp,
+    CovarPop,
+    Corr,
+}
+
+impl CovarianceType {
+    fn num_accums(&self) -> usize {
+        match self {
+            // n, xAvg, yAvg, ck
+            CovarianceType::CovarSamp | CovarianceType::CovarPop => 4,
+            // n, xAvg, yAvg, ck, xMk, yMk
+            CovarianceType::Corr => 6,
+        }
+    }
+}
+
+/// covar_samp/covar_pop/corr aggregates, with the same accumulator layout as
+/// spark's Covariance and PearsonCorrelation.
+pub struct AggCovariance {
+    x: Arc<dyn PhysicalExpr>,
+    y: Arc<dyn PhysicalExpr>,
+    covariance_type: CovarianceType,
+    null_on_divide_by_zero: bool,
+    accums_initial: Vec<AccumInitialValue>,
+    accum_state_val_addrs: Vec<AccumStateValAddr>,
+    mem_used_tracker: AtomicUsize,
+}
+
+impl WithAggBufAddrs for AggCovariance {
+    fn set_accum_state_val_addrs(&mut self, accum_state_val_addrs: &[AccumStateValAddr]) {
+        let num_accums = self.covariance_type.num_accums();
+        self.accum_state_val_addrs = accum_state_val_addrs[..num_accums].to_vec();
+    }
+}
+
+impl WithMemTracking for AggCovariance {
+    fn mem_used_tracker(&self) -> &AtomicUsize {
+        &self.mem_used_tracker
+    }
+}
+
+impl AggCovariance {
+    /// x and y must be evaluated to Float64. null_on_divide_by_zero is the
+    /// negation of spark.sql.legacy.statisticalAggregate
+    pub fn try_new(
+        x: Arc<dyn PhysicalExpr>,
+        y: Arc<dyn PhysicalExpr>,
+        covariance_type: CovarianceType,
+        null_on_divide_by_zero: bool,
+    ) -> Result<Self> {
+        let num_accums = covariance_type.num_accums();
+        Ok(Self {
+            x,
+            y,
+            covariance_type,
+            null_on_divide_by_zero,
+            accums_initial: vec![
+                AccumInitialValue::Scalar(ScalarValue::Float64(Some(0.0)));
+                num_accums
+            ],
+            accum_state_val_addrs: vec![AccumStateValAddr::default(); num_accums],
+            mem_used_tracker: AtomicUsize::new(0),
+        })
+    }
+
+    fn load(&self, acc: &RefAccumStateRow) -> CoMoments {
+        let addrs = &self.accum_state_val_addrs;
+        let mut co_moments = CoMoments {
+            n: acc.fixed_value(addrs[0]),
+            x_avg: acc.fixed_value(addrs[1]),
+            y_avg: acc.fixed_value(addrs[2]),
+            ck: acc.fixed_value(addrs[3]),
+            x_mk: 0.0,
+            y_mk: 0.0,
+        };
+        if self.covariance_type == CovarianceType::Corr {
+            co_moments.x_mk = acc.fixed_value(addrs[4]);
+            co_moments.y_mk = acc.fixed_value(addrs[5]);
+        }
+        co_moments
+    }
+
+    fn save(&self, acc: &mut RefAccumStateRow, co_moments: CoMoments) {
+        let addrs = &self.accum_state_val_addrs;
+        acc.set_fixed_value(addrs[0], co_moments.n);
+        acc.set_fixed_value(addrs[1], co_moments.x_avg);
+        acc.set_fixed_value(addrs[2], co_moments.y_avg);
+        acc.set_fixed_value(addrs[3], co_moments.ck);
+        if self.covariance_type == CovarianceType::Corr {
+            acc.set_fixed_value(addrs[4], co_moments.x_mk);
+            acc.set_fixed_value(addrs[5], co_moments.y_mk);
+        }
+    }
+
+    fn update(&self, acc: &mut RefAccumStateRow, x: f64, y: f64) {
+        let mut co_moments = self.load(acc);
+        co_moments.update(x, y);
+        self.save(acc, co_moments);
+    }
+
+    fn evaluate(&self, co_moments: CoMoments) -> Option<f64> {
+        let CoMoments { n, ck, .. } = co_moments;
+        if n == 0.0 {
+            return None;
+        }
+        let divide_by_zero_result = (!self.null_on_divide_by_zero).then_some(f64::NAN);
+        match self.covariance_type {
+            CovarianceType::CovarPop => Some(ck / n),
+            CovarianceType::CovarSamp if n == 1.0 => divide_by_zero_result,
+            CovarianceType::CovarSamp => Some(ck / (n - 1.0)),
+            CovarianceType::Corr if n == 1.0 => divide_by_zero_result,
+            CovarianceType::Corr => Some(ck / (co_moments.x_mk * co_moments.y_mk).sqrt()),
+        }
+    }
+}
+
+impl Debug for AggCovariance {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}({:?}, {:?})", self.covariance_type, self.x, self.y)
+    }
+}
+
+impl Agg for AggCovariance {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn exprs(&self) -> Vec<Arc<dyn PhysicalExpr>> {
+        vec![self.x.clone(), self.y.clone()]
+    }
+
+    fn with_new_exprs(&self, exprs: Vec<Arc<dyn PhysicalExpr>>) -> Result<Arc<dyn Agg>> {
+        let y = exprs.get(1).unwrap_or(&exprs[0]).clone();
+        Ok(Arc::new(Self::try_new(
+            exprs[0].clone(),
+            y,
+            self.covariance_type,
+            self.null_on_divide_by_zero,
+        )?))
+    }
+
+    fn data_type(&self) -> &DataType {
+        &DataType::Float64
+    }
+
+    fn nullable(&self) -> bool {
+        true
+    }
+
+    fn accums_initial(&self) -> &[AccumInitialValue] {
+        &self.accums_initial
+    }
+
+    fn increase_acc_mem_used(&self, _acc: &mut RefAccumStateRow) {
+        // do nothing
+    }
+
+    fn partial_update(
+        &self,
+        acc: &mut RefAccumStateRow,
+        values: &[ArrayRef],
+        row_idx: usize,
+    ) -> Result<()> {
+        let xs = values[0].as_primitive::<Float64Type>();
+        let ys = values[1].as_primitive::<Float64Type>();
+        if xs.is_valid(row_idx) && ys.is_valid(row_idx) {
+            self.update(acc, xs.value(row_idx), ys.value(row_idx));
+        }
+        Ok(())
+    }
+
+    fn partial_batch_update(
+        &self,
+        accs: &mut [RefAccumStateRow],
+        values: &[ArrayRef],
+    ) -> Result<()> {
+        let xs = values[0].as_primitive::<Float64Type>();
+        let ys = values[1].as_primitive::<Float64Type>();
+        for (acc, (x, y)) in accs.iter_mut().zip(xs.iter().zip(ys.iter())) {
+            if let (Some(x), Some(y)) = (x, y) {
+                self.update(acc, x, y);
+            }
+        }
+        Ok(())
+    }
+
+    fn partial_update_all(
+        &self,
+        acc: &mut RefAccumStateRow,
+        _num_rows: usize,
+        values: &[ArrayRef],
+    ) -> Result<()> {
+        let xs = values[0].as_primitive::<Float64Type>();
+        let ys = values[1].as_primitive::<Float64Type>();
+        let mut co_moments = self.load(acc);
+        for (x, y) in xs.iter().zip(ys.iter()) {
+            if let (Some(x), Some(y)) = (x, y) {
+                co_moments.update(x, y);
+            }
+        }
+        self.save(acc, co_moments);
+        Ok(())
+    }
+
+    fn partial_merge(
+        &self,
+        acc: &mut RefAccumStateRow,
+        merging_acc: &mut RefAccumStateRow,
+    ) -> Result<()> {
+        let mut co_moments = self.load(acc);
+        co_moments.merge(self.load(merging_acc));
+        self.save(acc, co_moments);
+        Ok(())
+    }
+
+    fn final_merge(&self, acc: &mut RefAccumStateRow) -> Result<ScalarValue> {
+        Ok(ScalarValue::Float64(self.evaluate(self.load(acc))))
+    }
+
+    fn final_batch_merge(&self, accs: &mut [RefAccumStateRow]) -> Result<ArrayRef> {
+        Ok(Arc::new(
+            accs.iter()
+                .map(|acc| self.evaluate(self.load(acc)))
+                .collect::<Float64Array>(),
+        ))
+    }
+}
+
+/// x_mk and y_mk are only maintained for corr, they are always zero for
+/// covariances and not stored in the accumulator
+#[derive(Clone, Copy)]
+struct CoMoments {
+    n: f64,
+    x_avg: f64,
+    y_avg: f64,
+    ck: f64,
+    x_mk: f64,
+    y_mk: f64,
+}
+
+impl CoMoments {
+    // same as PearsonCorrelation.updateExpressions
+    fn update(&mut self, x: f64, y: f64) {
+        let n = self.n + 1.0;
+        let dx = x - self.x_avg;
+        let dx_n = dx / n;
+        let dy = y - self.y_avg;
+        let dy_n = dy / n;
+        self.n = n;
+        self.x_avg += dx_n;
+        self.y_avg += dy_n;
+        self.ck += dx * (y - self.y_avg);
+        self.x_mk += dx * (x - self.x_avg);
+        self.y_mk += dy * (y - self.y_avg);
+    }
+
+    // same as PearsonCorrelation.mergeExpressions
+    fn merge(&mut self, other: Self) {
+        let (n1, n2) = (self.n, other.n);
+        let n = n1 + n2;
+        let dx = other.x_avg - self.x_avg;
+        let dx_n = if n == 0.0 { 0.0 } else { dx / n };
+        let dy = other.y_avg - self.y_avg;
+        let dy_n = if n == 0.0 { 0.0 } else { dy / n };
+        self.n = n;
+        self.x_avg += dx_n * n2;
+        self.y_avg += dy_n * n2;
+        self.ck += other.ck + dx * dy_n * n1 * n2;
+        self.x_mk += other.x_mk + dx * dx_n * n1 * n2;
+        self.y_mk += other.y_mk + dy * dy_n * n1 * n2;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::agg::covariance::CoMoments;
+
+    #[test]
+    fn test_co_moments_update_and_merge() {
+        let (xs, ys) = ([1.0, 2.0, 3.0, 4.0], [2.0, 4.0, 6.0, 8.0]);
+        let empty = CoMoments {
+            n: 0.0,
+            x_avg: 0.0,
+            y_avg: 0.0,
+            ck: 0.0,
+            x_mk: 0.0,
+            y_mk: 0.0,
+        };
+
+        let mut all = empty;
+        xs.iter().zip(&ys).for_each(|(&x, &y)| all.update(x, y));
+        assert_eq!((all.n, all.x_avg, all.y_avg), (4.0, 2.5, 5.0));
+        assert_eq!((all.ck, all.x_mk, all.y_mk), (10.0, 5.0, 20.0));
+
+        let (mut left, mut right) = (empty, empty);
+        xs[..2]
+            .iter()
+            .zip(&ys[..2])
+            .for_each(|(&x, &y)| left.update(x, y));
+        xs[2..]
+            .iter()
+            .zip(&ys[2..])
+            .for_each(|(&x, &y)| right.update(x, y));
+        left.merge(right);
+        left.merge(empty);
+        assert_eq!((left.n, left.x_avg, left.y_avg), (4.0, 2.5, 5.0));
+        assert_eq!((left.ck, left.x_mk, left.y_mk), (10.0, 5.0, 20.0));
+    }
+}
@@ -0,0 +1,332 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    fmt::{Debug, Formatter},
+    marker::PhantomData,
+    sync::{atomic::AtomicUsize, Arc},
+};
+
+use arrow::{array::*, datatypes::*};
+use datafusion::{
+    common::{Result, ScalarValue},
+    physical_expr::PhysicalExpr,
+};
+use datafusion_ext_commons::downcast_any;
+
+use crate::agg::{
+    acc::{
+        AccumInitialValue, AccumStateRow, AccumStateValAddr, AggDynBinary, AggDynScalar, AggDynStr,
+        AggDynValue, RefAccumStateRow,
+    },
+    default_final_batch_merge_with_addr, default_final_merge_with_addr,
+    maxmin::{AggMaxMinParams, AggMaxParams, AggMinParams},
+    Agg, WithAggBufAddrs, WithMemTracking,
+};
+
+pub type AggMaxBy = AggMaxMinBy<AggMaxParams>;
+pub type AggMinBy = AggMaxMinBy<AggMinParams>;
+
+/// max_by(value, ordering) and min_by(value, ordering), the value is stored
+/// alongside the extremum ordering, same as spark's MaxMinBy.
+pub struct AggMaxMinBy<P: AggMaxMinParams> {
+    value: Arc<dyn PhysicalExpr>,
+    ordering: Arc<dyn PhysicalExpr>,
+    data_type: DataType,
+    ordering_data_type: DataType,
+    accums_initial: Vec<AccumInitialValue>,
+    accum_state_val_addr_value: AccumStateValAddr,
+    accum_state_val_addr_ordering: AccumStateValAddr,
+    mem_used_tracker: AtomicUsize,
+    _phantom: PhantomData<P>,
+}
+
+impl<P: AggMaxMinParams> WithAggBufAddrs for AggMaxMinBy<P> {
+    fn set_accum_state_val_addrs(&mut self, accum_state_val_addrs: &[AccumStateValAddr]) {
+        self.accum_state_val_addr_value = accum_state_val_addrs[0];
+        self.accum_state_val_addr_ordering = accum_state_val_addrs[1];
+    }
+}
+
+impl<P: AggMaxMinParams> WithMemTracking for AggMaxMinBy<P> {
+    fn mem_used_tracker(&self) -> &AtomicUsize {
+        &self.mem_used_tracker
+    }
+}
+
+impl<P: AggMaxMinParams> AggMaxMinBy<P> {
+    pub fn try_new(
+        value: Arc<dyn PhysicalExpr>,
+        ordering: Arc<dyn PhysicalExpr>,
+        data_type: DataType,
+        ordering_data_type: DataType,
+    ) -> Result<Self> {
+        let accums_initial = vec![
+            AccumInitialValue::Scalar(ScalarValue::try_from(&data_type)?),
+            AccumInitialValue::Scalar(ScalarValue::try_from(&ordering_data_type)?),
+        ];
+        Ok(Self {
+            value,
+            ordering,
+            data_type,
+            ordering_data_type,
+            accums_initial,
+            accum_state_val_addr_value: AccumStateValAddr::default(),
+            accum_state_val_addr_ordering: AccumStateValAddr::default(),
+            mem_used_tracker: AtomicUsize::new(0),
+            _phantom: Default::default(),
+        })
+    }
+
+    /// replaces the current (value, ordering) if the new ordering is not null
+    /// and the current one does not win, ties are resolved in favor of the
+    /// new one, same as spark
+    fn update_with(
+        &self,
+        acc: &mut RefAccumStateRow,
+        value: impl FnOnce() -> Result<ScalarValue>,
+        ordering: ScalarValue,
+    ) -> Result<()> {
+        if ordering.is_null() {
+            return Ok(());
+        }
+        let addr_ordering = self.accum_state_val_addr_ordering;
+        let current = load_scalar(acc, addr_ordering, &self.ordering_data_type)?;
+        if current.is_null() || current.partial_cmp(&ordering) != Some(P::ORD) {
+            self.store_scalar(acc, self.accum_state_val_addr_value, value()?);
+            self.store_scalar(acc, addr_ordering, ordering);
+        }
+        Ok(())
+    }
+
+    fn store_scalar(&self, acc: &mut RefAccumStateRow, addr: AccumStateValAddr, v: ScalarValue) {
+        macro_rules! handle_fixed {
+            ($v:expr) => {{
+                match $v {
+                    Some(v) => {
+                        acc.set_fixed_value(addr, v);
+                        acc.set_fixed_valid(addr, true);
+                    }
+                    None => acc.set_fixed_valid(addr, false),
+                }
+                return;
+            }};
+        }
+        let new: Option<Box<dyn AggDynValue>> = match v {
+            ScalarValue::Null => return,
+            ScalarValue::Boolean(v) => handle_fixed!(v),
+            ScalarValue::Float32(v) => handle_fixed!(v),
+            ScalarValue::Float64(v) => handle_fixed!(v),
+            ScalarValue::Decimal128(v, ..) => handle_fixed!(v),
+            ScalarValue::Int8(v) => handle_fixed!(v),
+            ScalarValue::Int16(v) => handle_fixed!(v),
+            ScalarValue::Int32(v) => handle_fixed!(v),
+            ScalarValue::Int64(v) => handle_fixed!(v),
+            ScalarValue::UInt8(v) => handle_fixed!(v),
+            ScalarValue::UInt16(v) => handle_fixed!(v),
+            ScalarValue::UInt32(v) => handle_fixed!(v),
+            ScalarValue::UInt64(v) => handle_fixed!(v),
+            ScalarValue::Date32(v) => handle_fixed!(v),
+            ScalarValue::Date64(v) => handle_fixed!(v),
+            ScalarValue::TimestampSecond(v, _) => handle_fixed!(v),
+            ScalarValue::TimestampMillisecond(v, _) => handle_fixed!(v),
+            ScalarValue::TimestampMicrosecond(v, _) => handle_fixed!(v),
+            ScalarValue::TimestampNanosecond(v, _) => handle_fixed!(v),
+            ScalarValue::Utf8(v) => v.map(|v| Box::new(AggDynStr::from_str(&v)) as _),
+            ScalarValue::Binary(v) => v.map(|v| Box::new(AggDynBinary::from_slice(&v)) as _),
+            v if v.is_null() => None,
+            v => Some(Box::new(AggDynScalar::new(v))),
+        };
+        let w = acc.dyn_value_mut(addr);
+        if let Some(w) = w.as_ref() {
+            self.sub_mem_used(w.mem_size()); // w will be dropped
+        }
+        if let Some(new) = &new {
+            self.add_mem_used(new.mem_size());
+        }
+        *w = new;
+    }
+
+    /// releases the stored ordering, which is not needed in the final output
+    fn release_ordering(&self, acc: &mut RefAccumStateRow) -> Result<()> {
+        let null = ScalarValue::try_from(&self.ordering_data_type)?;
+        self.store_scalar(acc, self.accum_state_val_addr_ordering, null);
+        Ok(())
+    }
+}
+
+impl<P: AggMaxMinParams> Debug for AggMaxMinBy<P> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}_by({:?}, {:?})", P::NAME, self.value, self.ordering)
+    }
+}
+
+impl<P: AggMaxMinParams> Agg for AggMaxMinBy<P> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn exprs(&self) -> Vec<Arc<dyn PhysicalExpr>> {
+        vec![self.value.clone(), self.ordering.clone()]
+    }
+
+    fn with_new_exprs(&self, exprs: Vec<Arc<dyn PhysicalExpr>>) -> Result<Arc<dyn Agg>> {
+        let ordering = exprs.get(1).unwrap_or(&exprs[0]).clone();
+        Ok(Arc::new(Self::try_new(
+            exprs[0].clone(),
+            ordering,
+            self.data_type.clone(),
+            self.ordering_data_type.clone(),
+        )?))
+    }
+
+    fn data_type(&self) -> &DataType {
+        &self.data_type
+    }
+
+    fn nullable(&self) -> bool {
+        true
+    }
+
+    fn accums_initial(&self) -> &[AccumInitialValue] {
+        &self.accums_initial
+    }
+
+    fn increase_acc_mem_used(&self, acc: &mut RefAccumStateRow) {
+        for (addr, dt) in [
+            (self.accum_state_val_addr_value, &self.data_type),
+            (self.accum_state_val_addr_ordering, &self.ordering_data_type),
+        ] {
+            if dt.is_primitive() || matches!(dt, DataType::Null | DataType::Boolean) {
+                continue;
+            }
+            if let Some(v) = acc.dyn_value(addr) {
+                self.add_mem_used(v.mem_size());
+            }
+        }
+    }
+
+    fn partial_update(
+        &self,
+        acc: &mut RefAccumStateRow,
+        values: &[ArrayRef],
+        row_idx: usize,
+    ) -> Result<()> {
+        self.update_with(
+            acc,
+            || ScalarValue::try_from_array(&values[0], row_idx),
+            ScalarValue::try_from_array(&values[1], row_idx)?,
+        )
+    }
+
+    fn partial_update_all(
+        &self,
+        acc: &mut RefAccumStateRow,
+        num_rows: usize,
+        values: &[ArrayRef],
+    ) -> Result<()> {
+        for row_idx in 0..num_rows {
+            if values[1].is_valid(row_idx) {
+                self.partial_update(acc, values, row_idx)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn partial_merge(
+        &self,
+        acc: &mut RefAccumStateRow,
+        merging_acc: &mut RefAccumStateRow,
+    ) -> Result<()> {
+        let addr_value = self.accum_state_val_addr_value;
+        let addr_ordering = self.accum_state_val_addr_ordering;
+        let merging_value = load_scalar(merging_acc, addr_value, &self.data_type)?;
+        let merging_ordering = load_scalar(merging_acc, addr_ordering, &self.ordering_data_type)?;
+
+        // merging_acc will be dropped
+        self.store_scalar(
+            merging_acc,
+            addr_value,
+            ScalarValue::try_from(&self.data_type)?,
+        );
+        self.release_ordering(merging_acc)?;
+        self.update_with(acc, || Ok(merging_value), merging_ordering)
+    }
+
+    fn final_merge(&self, acc: &mut RefAccumStateRow) -> Result<ScalarValue> {
+        self.release_ordering(acc)?;
+        default_final_merge_with_addr(self, acc, self.accum_state_val_addr_value)
+    }
+
+    fn final_batch_merge(&self, accs: &mut [RefAccumStateRow]) -> Result<ArrayRef> {
+        for acc in accs.iter_mut() {
+            self.release_ordering(acc)?;
+        }
+        default_final_batch_merge_with_addr(self, accs, self.accum_state_val_addr_value)
+    }
+}
+
+/// reads a value written by store_scalar() without taking it
+fn load_scalar(
+    acc: &mut RefAccumStateRow,
+    addr: AccumStateValAddr,
+    dt: &DataType,
+) -> Result<ScalarValue> {
+    macro_rules! handle_fixed {
+        ($ty:ident $(, $extra:expr)*) => {{
+            let v = acc.is_fixed_valid(addr).then(|| acc.fixed_value(addr));
+            ScalarValue::$ty(v $(, $extra)*)
+        }};
+    }
+    Ok(match dt {
+        DataType::Null => ScalarValue::Null,
+        DataType::Boolean => handle_fixed!(Boolean),
+        DataType::Float32 => handle_fixed!(Float32),
+        DataType::Float64 => handle_fixed!(Float64),
+        DataType::Int8 => handle_fixed!(Int8),
+        DataType::Int16 => handle_fixed!(Int16),
+        DataType::Int32 => handle_fixed!(Int32),
+        DataType::Int64 => handle_fixed!(Int64),
+        DataType::UInt8 => handle_fixed!(UInt8),
+        DataType::UInt16 => handle_fixed!(UInt16),
+        DataType::UInt32 => handle_fixed!(UInt32),
+        DataType::UInt64 => handle_fixed!(UInt64),
+        DataType::Decimal128(prec, scale) => handle_fixed!(Decimal128, *prec, *scale),
+        DataType::Date32 => handle_fixed!(Date32),
+        DataType::Date64 => handle_fixed!(Date64),
+        DataType::Timestamp(TimeUnit::Second, tz) => handle_fixed!(TimestampSecond, tz.clone()),
+        DataType::Timestamp(TimeUnit::Millisecond, tz) => {
+            handle_fixed!(TimestampMillisecond, tz.clone())
+        }
+        DataType::Timestamp(TimeUnit::Microsecond, tz) => {
+            handle_fixed!(TimestampMicrosecond, tz.clone())
+        }
+        DataType::Timestamp(TimeUnit::Nanosecond, tz) => {
+            handle_fixed!(TimestampNanosecond, tz.clone())
+        }
+        DataType::Utf8 => ScalarValue::Utf8(match acc.dyn_value(addr) {
+            Some(v) => Some(downcast_any!(v, AggDynStr)?.value().to_string()),
+            None => None,
+        }),
+        DataType::Binary => ScalarValue::Binary(match acc.dyn_value(addr) {
+            Some(v) => Some(downcast_any!(v, AggDynBinary)?.value().to_vec()),
+            None => None,
+        }),
+        other => match acc.dyn_value(addr) {
+            Some(v) => downcast_any!(v, AggDynScalar)?.value().clone(),
+            None => ScalarValue::try_from(other)?,
+        },
+    })
+}
@@ -16,16 +16,26 @@ pub mod acc;
 pub mod agg_context;
 pub mod agg_hash_map;
 pub mod agg_table;
+pub mod approx_count_distinct;
+pub mod approx_percentile;
 pub mod avg;
+pub mod bitwise;
 pub mod bloom_filter;
 pub mod brickhouse;
+pub mod central_moment;
 pub mod collect_list;
 pub mod collect_set;
 pub mod count;
 pub mod count0;
+pub mod covariance;
 pub mod first;
 pub mod first_ignores_null;
+pub mod last;
+pub mod last_ignores_null;
 pub mod maxmin;
+pub mod maxmin_by;
+pub mod percentile;
+pub mod spark_udaf_wrapper;
 pub mod sum;
 
 use std::{
@@ -41,9 +51,12 @@ use arrow::{array::*, datatypes::*};
 use datafusion::{
     common::{Result, ScalarValue},
     // logical_expr::aggregate_function,
-    physical_expr::PhysicalExpr,
+    physical_expr::{
+        expressions::{CaseExpr, Literal},
+        PhysicalExpr,
+    },
 };
-use datafusion_ext_commons::df_execution_err;
+use datafusion_ext_commons::{cast::cast, df_execution_err, spark_eval_mode::EvalMode};
 use datafusion_ext_exprs::cast::TryCastExpr;
 use slimmer_box::SlimmerBox;
 
@@ -89,11 +102,33 @@ pub enum AggFunction {
     Min,
     First,
     FirstIgnoresNull,
+    Last,
+    LastIgnoresNull,
+    MaxBy,
+    MinBy,
     CollectList,
     CollectSet,
     BloomFilter,
+    ApproxCountDistinct,
+    Percentile,
+    ApproxPercentile,
+    Median,
+    StddevSamp,
+    StddevPop,
+    VarSamp,
+    VarPop,
+    CovarSamp,
+    CovarPop,
+    Corr,
+    BoolAnd,
+    BoolOr,
+    BitAnd,
+    BitOr,
+    BitXor,
+    CountIf,
     BrickhouseCollect,
     BrickhouseCombineUnique,
+    Udaf,
 }
 
 #[derive(Debug, Clone)]
@@ -203,6 +238,17 @@ pub fn create_agg(
     agg_function: AggFunction,
     children: &[Arc<dyn PhysicalExpr>],
     input_schema: &SchemaRef,
+) -> Result<Arc<dyn Agg>> {
+    create_agg_with_eval_mode(agg_function, children, input_schema, EvalMode::Legacy)
+}
+
+/// creates an agg with the specified eval mode, which decides how overflows
+/// are handled in sum() and avg()
+pub fn create_agg_with_eval_mode(
+    agg_function: AggFunction,
+    children: &[Arc<dyn PhysicalExpr>],
+    input_schema: &SchemaRef,
+    eval_mode: EvalMode,
 ) -> Result<Arc<dyn Agg>> {
     use datafusion::logical_expr::type_coercion::aggregates::*;
 
@@ -223,17 +269,19 @@ pub fn create_agg(
                 }
                 other => sum_return_type(&other)?,
             };
-            Arc::new(sum::AggSum::try_new(
+            Arc::new(sum::AggSum::try_new_with_eval_mode(
                 Arc::new(TryCastExpr::new(children[0].clone(), return_type.clone())),
                 return_type,
+                eval_mode,
             )?)
         }
         AggFunction::Avg => {
             let arg_type = children[0].data_type(input_schema)?;
             let return_type = avg_return_type("avg", &arg_type)?;
-            Arc::new(avg::AggAvg::try_new(
+            Arc::new(avg::AggAvg::try_new_with_eval_mode(
                 Arc::new(TryCastExpr::new(children[0].clone(), return_type.clone())),
                 return_type,
+                eval_mode,
             )?)
         }
         AggFunction::Max => {
@@ -255,6 +303,37 @@ pub fn create_agg(
                 dt,
             )?)
         }
+        AggFunction::Last => {
+            let dt = children[0].data_type(input_schema)?;
+            Arc::new(last::AggLast::try_new(children[0].clone(), dt)?)
+        }
+        AggFunction::LastIgnoresNull => {
+            let dt = children[0].data_type(input_schema)?;
+            Arc::new(last_ignores_null::AggLastIgnoresNull::try_new(
+                children[0].clone(),
+                dt,
+            )?)
+        }
+        AggFunction::MaxBy => {
+            let dt = children[0].data_type(input_schema)?;
+            let ordering_dt = children[1].data_type(input_schema)?;
+            Arc::new(maxmin_by::AggMaxBy::try_new(
+                children[0].clone(),
+                children[1].clone(),
+                dt,
+                ordering_dt,
+            )?)
+        }
+        AggFunction::MinBy => {
+            let dt = children[0].data_type(input_schema)?;
+            let ordering_dt = children[1].data_type(input_schema)?;
+            Arc::new(maxmin_by::AggMinBy::try_new(
+                children[0].clone(),
+                children[1].clone(),
+                dt,
+                ordering_dt,
+            )?)
+        }
         AggFunction::BloomFilter => {
             let dt = children[0].data_type(input_schema)?;
             let empty_batch = RecordBatch::new_empty(Arc::new(Schema::empty()));
@@ -275,6 +354,131 @@ pub fn create_agg(
                 num_bits as usize,
             ))
         }
+        AggFunction::ApproxCountDistinct => {
+            let empty_batch = RecordBatch::new_empty(Arc::new(Schema::empty()));
+            let relative_sd = match children.get(1) {
+                Some(relative_sd) => {
+                    let relative_sd = relative_sd.evaluate(&empty_batch)?.into_array(1)?;
+                    let relative_sd = cast(&relative_sd, &DataType::Float64)?;
+                    let relative_sd = relative_sd.as_primitive::<Float64Type>();
+                    if relative_sd.is_null(0) {
+                        return df_execution_err!("relativeSD must not be null");
+                    }
+                    relative_sd.value(0)
+                }
+                None => 0.05, // same as spark's default relativeSD
+            };
+            Arc::new(approx_count_distinct::AggApproxCountDistinct::try_new(
+                children[0].clone(),
+                relative_sd,
+            )?)
+        }
+        AggFunction::Percentile => {
+            let (percentages, return_list) = percentile::parse_percentages(&children[1])?;
+            Arc::new(percentile::AggPercentile::try_new(
+                Arc::new(TryCastExpr::new(children[0].clone(), DataType::Float64)),
+                percentages,
+                return_list,
+            )?)
+        }
+        AggFunction::ApproxPercentile => {
+            let dt = children[0].data_type(input_schema)?;
+            let (percentages, return_list) = percentile::parse_percentages(&children[1])?;
+            let accuracy = match children.get(2) {
+                Some(accuracy) => {
+                    let empty_batch = RecordBatch::new_empty(Arc::new(Schema::empty()));
+                    let accuracy = accuracy.evaluate(&empty_batch)?.into_array(1)?;
+                    let accuracy = cast(&accuracy, &DataType::Int64)?
+                        .as_primitive::<Int64Type>()
+                        .value(0);
+                    if accuracy <= 0 {
+                        return df_execution_err!(
+                            "accuracy of percentile_approx must be positive, got {accuracy}"
+                        );
+                    }
+                    accuracy as usize
+                }
+                None => approx_percentile::DEFAULT_PERCENTILE_ACCURACY,
+            };
+            Arc::new(approx_percentile::AggApproxPercentile::try_new(
+                children[0].clone(),
+                dt,
+                percentages,
+                return_list,
+                accuracy,
+            )?)
+        }
+        AggFunction::Median => Arc::new(percentile::AggPercentile::try_new(
+            Arc::new(TryCastExpr::new(children[0].clone(), DataType::Float64)),
+            vec![0.5],
+            false,
+        )?),
+        AggFunction::StddevSamp
+        | AggFunction::StddevPop
+        | AggFunction::VarSamp
+        | AggFunction::VarPop => {
+            let moment_type = match agg_function {
+                AggFunction::StddevSamp => central_moment::CentralMomentType::StddevSamp,
+                AggFunction::StddevPop => central_moment::CentralMomentType::StddevPop,
+                AggFunction::VarSamp => central_moment::CentralMomentType::VarSamp,
+                _ => central_moment::CentralMomentType::VarPop,
+            };
+            Arc::new(central_moment::AggCentralMoment::try_new(
+                Arc::new(TryCastExpr::new(children[0].clone(), DataType::Float64)),
+                moment_type,
+                parse_null_on_divide_by_zero(children.get(1))?,
+            )?)
+        }
+        AggFunction::CovarSamp | AggFunction::CovarPop | AggFunction::Corr => {
+            let covariance_type = match agg_function {
+                AggFunction::CovarSamp => covariance::CovarianceType::CovarSamp,
+                AggFunction::CovarPop => covariance::CovarianceType::CovarPop,
+                _ => covariance::CovarianceType::Corr,
+            };
+            Arc::new(covariance::AggCovariance::try_new(
+                Arc::new(TryCastExpr::new(children[0].clone(), DataType::Float64)),
+                Arc::new(TryCastExpr::new(children[1].clone(), DataType::Float64)),
+                covariance_type,
+                parse_null_on_divide_by_zero(children.get(2))?,
+            )?)
+        }
+        AggFunction::BoolAnd | AggFunction::BoolOr => {
+            let dt = children[0].data_type(input_schema)?;
+            if dt != DataType::Boolean {
+                return df_execution_err!("bool_and/bool_or expect boolean type, got {dt}");
+            }
+            // same as spark, bool_and/bool_or are min/max on booleans
+            if agg_function == AggFunction::BoolAnd {
+                Arc::new(maxmin::AggMin::try_new(children[0].clone(), dt)?)
+            } else {
+                Arc::new(maxmin::AggMax::try_new(children[0].clone(), dt)?)
+            }
+        }
+        AggFunction::BitAnd | AggFunction::BitOr | AggFunction::BitXor => {
+            let dt = children[0].data_type(input_schema)?;
+            let bitwise_type = match agg_function {
+                AggFunction::BitAnd => bitwise::BitwiseType::BitAnd,
+                AggFunction::BitOr => bitwise::BitwiseType::BitOr,
+                _ => bitwise::BitwiseType::BitXor,
+            };
+            Arc::new(bitwise::AggBitwise::try_new(
+                children[0].clone(),
+                dt,
+                bitwise_type,
+            )?)
+        }
+        AggFunction::CountIf => {
+            // same as spark, count_if(pred) is count(nullif(pred, false))
+            let true_if_matched = Arc::new(CaseExpr::try_new(
+                None,
+                vec![(
+                    children[0].clone(),
+                    Arc::new(Literal::new(ScalarValue::Boolean(Some(true)))),
+                )],
+                None,
+            )?);
+            Arc::new(count::AggCount::try_new(true_if_matched, DataType::Int64)?)
+        }
         AggFunction::CollectList => {
             let arg_type = children[0].data_type(input_schema)?;
             let return_type = DataType::new_list(arg_type.clone(), true);
@@ -315,6 +519,36 @@ pub fn create_agg(
                 arg_list_inner_type,
             )?)
         }
+        AggFunction::Udaf => {
+            return df_execution_err!("udaf must be created with create_udaf_agg()");
+        }
+    })
+}
+
+pub fn create_udaf_agg(
+    serialized: Vec<u8>,
+    return_type: DataType,
+    children: Vec<Arc<dyn PhysicalExpr>>,
+) -> Result<Arc<dyn Agg>> {
+    Ok(Arc::new(spark_udaf_wrapper::SparkUDAFWrapper::try_new(
+        serialized,
+        return_type,
+        children,
+    )?))
+}
+
+/// parses the optional nullOnDivideByZero argument of statistical aggregates,
+/// which is the negation of spark.sql.legacy.statisticalAggregate
+fn parse_null_on_divide_by_zero(expr: Option<&Arc<dyn PhysicalExpr>>) -> Result<bool> {
+    Ok(match expr {
+        Some(expr) => {
+            let empty_batch = RecordBatch::new_empty(Arc::new(Schema::empty()));
+            expr.evaluate(&empty_batch)?
+                .into_array(1)?
+                .as_boolean()
+                .value(0)
+        }
+        None => true, // same as spark's default
     })
 }
 
@@ -325,7 +559,7 @@ fn default_final_merge_with_addr(
 ) -> Result<ScalarValue> {
     // default implementation:
     // extract the only one values from acc and convert to ScalarValue
-    // this works for sum/min/max/first
+    // this works for sum/min/max/first/last
     macro_rules! handle_fixed {
         ($ty:ident) => {{
             if acc.is_fixed_valid(addr) {
@@ -422,7 +656,7 @@ fn default_final_batch_merge_with_addr(
 ) -> Result<ArrayRef> {
     // default implementation:
     // extract the only one values from acc and convert to ScalarValue
-    // this works for sum/min/max/first
+    // this works for sum/min/max/first/last
     macro_rules! handle_fixed {
         ($ty:ident) => {{
             type B = paste::paste! {[< $ty Builder >]};
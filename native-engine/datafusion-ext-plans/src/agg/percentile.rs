@@ -0,0 +1,291 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    fmt::{Debug, Formatter},
+    sync::{atomic::AtomicUsize, Arc},
+};
+
+use arrow::{array::*, datatypes::*};
+use datafusion::{
+    common::{Result, ScalarValue},
+    physical_expr::PhysicalExpr,
+};
+use datafusion_ext_commons::{cast::cast, df_execution_err, downcast_any};
+
+use crate::agg::{
+    acc::{
+        AccumInitialValue, AccumStateRow, AccumStateValAddr, AggDynList, AggDynValue,
+        RefAccumStateRow,
+    },
+    Agg, WithAggBufAddrs, WithMemTracking,
+};
+
+/// Exact percentile, collecting all non-null values of each group.
+pub struct AggPercentile {
+    child: Arc<dyn PhysicalExpr>,
+    data_type: DataType,
+    percentages: Vec<f64>,
+    return_list: bool,
+    accum_initial: [AccumInitialValue; 1],
+    accum_state_val_addr: AccumStateValAddr,
+    mem_used_tracker: AtomicUsize,
+}
+
+impl WithAggBufAddrs for AggPercentile {
+    fn set_accum_state_val_addrs(&mut self, accum_state_val_addrs: &[AccumStateValAddr]) {
+        self.accum_state_val_addr = accum_state_val_addrs[0];
+    }
+}
+
+impl WithMemTracking for AggPercentile {
+    fn mem_used_tracker(&self) -> &AtomicUsize {
+        &self.mem_used_tracker
+    }
+}
+
+impl AggPercentile {
+    /// child must be evaluated to Float64
+    pub fn try_new(
+        child: Arc<dyn PhysicalExpr>,
+        percentages: Vec<f64>,
+        return_list: bool,
+    ) -> Result<Self> {
+        if let Some(p) = percentages.iter().find(|p| !(0.0..=1.0).contains(*p)) {
+            return df_execution_err!("percentage must be between 0.0 and 1.0, got {p}");
+        }
+        let data_type = if return_list {
+            DataType::new_list(DataType::Float64, true)
+        } else {
+            DataType::Float64
+        };
+        Ok(Self {
+            child,
+            data_type,
+            percentages,
+            return_list,
+            accum_initial: [AccumInitialValue::DynList(DataType::Float64)],
+            accum_state_val_addr: AccumStateValAddr::default(),
+            mem_used_tracker: AtomicUsize::new(0),
+        })
+    }
+
+    fn get_or_create_list<'a>(&self, acc: &'a mut RefAccumStateRow) -> Result<&'a mut AggDynList> {
+        let v = acc.dyn_value_mut(self.accum_state_val_addr);
+        if v.is_none() {
+            let new_list = AggDynList::default();
+            self.add_mem_used(new_list.mem_size());
+            *v = Some(Box::new(new_list));
+        }
+        downcast_any!(v.as_mut().unwrap(), mut AggDynList)
+    }
+}
+
+impl Debug for AggPercentile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Percentile({:?}, {:?})", self.child, self.percentages)
+    }
+}
+
+impl Agg for AggPercentile {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn exprs(&self) -> Vec<Arc<dyn PhysicalExpr>> {
+        vec![self.child.clone()]
+    }
+
+    fn with_new_exprs(&self, exprs: Vec<Arc<dyn PhysicalExpr>>) -> Result<Arc<dyn Agg>> {
+        Ok(Arc::new(Self::try_new(
+            exprs[0].clone(),
+            self.percentages.clone(),
+            self.return_list,
+        )?))
+    }
+
+    fn data_type(&self) -> &DataType {
+        &self.data_type
+    }
+
+    fn nullable(&self) -> bool {
+        true
+    }
+
+    fn accums_initial(&self) -> &[AccumInitialValue] {
+        &self.accum_initial
+    }
+
+    fn increase_acc_mem_used(&self, acc: &mut RefAccumStateRow) {
+        if let Some(v) = acc.dyn_value(self.accum_state_val_addr) {
+            self.add_mem_used(v.mem_size());
+        }
+    }
+
+    fn partial_update(
+        &self,
+        acc: &mut RefAccumStateRow,
+        values: &[ArrayRef],
+        row_idx: usize,
+    ) -> Result<()> {
+        if values[0].is_valid(row_idx) {
+            let list = self.get_or_create_list(acc)?;
+            self.sub_mem_used(list.mem_size());
+            list.append(&ScalarValue::try_from_array(&values[0], row_idx)?, false);
+            self.add_mem_used(list.mem_size());
+        }
+        Ok(())
+    }
+
+    fn partial_update_all(
+        &self,
+        acc: &mut RefAccumStateRow,
+        _num_rows: usize,
+        values: &[ArrayRef],
+    ) -> Result<()> {
+        let list = self.get_or_create_list(acc)?;
+        self.sub_mem_used(list.mem_size());
+
+        for value in values[0].as_primitive::<Float64Type>().iter().flatten() {
+            list.append(&ScalarValue::Float64(Some(value)), false);
+        }
+        self.add_mem_used(list.mem_size());
+        Ok(())
+    }
+
+    fn partial_merge(
+        &self,
+        acc: &mut RefAccumStateRow,
+        merging_acc: &mut RefAccumStateRow,
+    ) -> Result<()> {
+        match (
+            acc.dyn_value_mut(self.accum_state_val_addr),
+            merging_acc.dyn_value_mut(self.accum_state_val_addr),
+        ) {
+            (Some(w), Some(v)) => {
+                let w = downcast_any!(w, mut AggDynList)?;
+                let v = downcast_any!(v, mut AggDynList)?;
+                self.sub_mem_used(w.mem_size());
+                self.sub_mem_used(v.mem_size());
+
+                w.merge(v);
+                self.add_mem_used(w.mem_size());
+            }
+            (w_none, v @ Some(_)) => *w_none = std::mem::take(v),
+            (None, _) => {}
+            (_, None) => {}
+        }
+        Ok(())
+    }
+
+    fn final_merge(&self, acc: &mut RefAccumStateRow) -> Result<ScalarValue> {
+        let sorted_values = match std::mem::take(acc.dyn_value_mut(self.accum_state_val_addr)) {
+            Some(w) => {
+                let list = w
+                    .as_any_boxed()
+                    .downcast::<AggDynList>()
+                    .or_else(|_| df_execution_err!("error downcasting to AggDynList"))?;
+                self.sub_mem_used(list.mem_size());
+
+                let mut values = list
+                    .into_values(DataType::Float64, false)
+                    .map(|value| match value {
+                        ScalarValue::Float64(Some(value)) => Ok(value),
+                        other => df_execution_err!("percentile: unexpected value {other}"),
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                values.sort_unstable_by(|a, b| a.total_cmp(b));
+                values
+            }
+            None => vec![],
+        };
+
+        if sorted_values.is_empty() {
+            return ScalarValue::try_from(&self.data_type);
+        }
+        let percentiles = self
+            .percentages
+            .iter()
+            .map(|&p| ScalarValue::Float64(Some(percentile_of_sorted(&sorted_values, p))))
+            .collect::<Vec<_>>();
+
+        if self.return_list {
+            return Ok(ScalarValue::List(ScalarValue::new_list(
+                &percentiles,
+                &DataType::Float64,
+                true,
+            )));
+        }
+        Ok(percentiles[0].clone())
+    }
+
+    fn final_batch_merge(&self, accs: &mut [RefAccumStateRow]) -> Result<ArrayRef> {
+        let values: Vec<ScalarValue> = accs
+            .iter_mut()
+            .map(|acc| self.final_merge(acc))
+            .collect::<Result<_>>()?;
+
+        if values.is_empty() {
+            return Ok(new_empty_array(self.data_type()));
+        }
+        Ok(ScalarValue::iter_to_array(values)?)
+    }
+}
+
+/// interpolates between the two closest ranks, same as spark's
+/// Percentile.getPercentile()
+fn percentile_of_sorted(sorted_values: &[f64], percentage: f64) -> f64 {
+    let position = (sorted_values.len() - 1) as f64 * percentage;
+    let lower = position.floor();
+    let higher = position.ceil();
+    let lower_value = sorted_values[lower as usize];
+    let higher_value = sorted_values[higher as usize];
+    if lower == higher || lower_value == higher_value {
+        return lower_value;
+    }
+    (higher - position) * lower_value + (position - lower) * higher_value
+}
+
+/// parses the percentage argument of percentile and percentile_approx, which
+/// is either a single literal or a literal array
+pub fn parse_percentages(expr: &Arc<dyn PhysicalExpr>) -> Result<(Vec<f64>, bool)> {
+    let empty_batch = RecordBatch::new_empty(Arc::new(Schema::empty()));
+    let value = expr.evaluate(&empty_batch)?.into_array(1)?;
+    let (percentages, return_list) = match value.data_type() {
+        DataType::List(_) => (value.as_list::<i32>().value(0), true),
+        _ => (value, false),
+    };
+    let percentages = cast(&percentages, &DataType::Float64)?;
+    let percentages = percentages.as_primitive::<Float64Type>();
+    if percentages.null_count() > 0 {
+        return df_execution_err!("percentage must not be null");
+    }
+    Ok((percentages.values().to_vec(), return_list))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::agg::percentile::percentile_of_sorted;
+
+    #[test]
+    fn test_percentile_of_sorted() {
+        let values = [1.0, 2.0, 3.0, 4.0, 10.0];
+        assert_eq!(percentile_of_sorted(&values, 0.0), 1.0);
+        assert_eq!(percentile_of_sorted(&values, 0.5), 3.0);
+        assert_eq!(percentile_of_sorted(&values, 0.625), 3.5);
+        assert_eq!(percentile_of_sorted(&values, 1.0), 10.0);
+        assert_eq!(percentile_of_sorted(&[5.0], 0.3), 5.0);
+    }
+}
@@ -0,0 +1,500 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    fmt::{Debug, Formatter},
+    mem::size_of,
+    sync::{
+        atomic::{AtomicUsize, Ordering::SeqCst},
+        Arc,
+    },
+};
+
+use arrow::{
+    array::*,
+    datatypes::*,
+    ffi::{from_ffi, FFI_ArrowArray, FFI_ArrowSchema},
+    record_batch::{RecordBatch, RecordBatchOptions},
+};
+use blaze_jni_bridge::{
+    is_task_running, jni_call, jni_convert_byte_array, jni_new_direct_byte_buffer,
+    jni_new_global_ref, jni_new_object,
+};
+use datafusion::{
+    common::{Result, ScalarValue},
+    physical_expr::PhysicalExpr,
+};
+use datafusion_ext_commons::{df_execution_err, downcast_any};
+use jni::objects::{GlobalRef, JObject};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+
+use crate::agg::{
+    acc::{AccumInitialValue, AccumStateRow, AccumStateValAddr, AggDynValue, RefAccumStateRow},
+    Agg, WithAggBufAddrs, WithMemTracking,
+};
+
+/// Aggregate function evaluated by spark, the per-group aggregation buffers
+/// are kept in the jvm side (SparkUDAFWrapperContext) and addressed by index,
+/// native accumulators only hold the indices.
+pub struct SparkUDAFWrapper {
+    return_type: DataType,
+    children: Vec<Arc<dyn PhysicalExpr>>,
+    import_schema: SchemaRef,
+    buffers: Arc<SparkUDAFBuffers>,
+    accums_initial: Vec<AccumInitialValue>,
+    accum_state_val_addr: AccumStateValAddr,
+}
+
+impl WithAggBufAddrs for SparkUDAFWrapper {
+    fn set_accum_state_val_addrs(&mut self, accum_state_val_addrs: &[AccumStateValAddr]) {
+        self.accum_state_val_addr = accum_state_val_addrs[0];
+    }
+}
+
+impl WithMemTracking for SparkUDAFWrapper {
+    fn mem_used_tracker(&self) -> &AtomicUsize {
+        &self.buffers.mem_used_tracker
+    }
+}
+
+impl SparkUDAFWrapper {
+    pub fn try_new(
+        serialized: Vec<u8>,
+        return_type: DataType,
+        children: Vec<Arc<dyn PhysicalExpr>>,
+    ) -> Result<Self> {
+        let buffers = Arc::new(SparkUDAFBuffers::new(serialized));
+        Self::try_new_with_buffers(buffers, return_type, children)
+    }
+
+    fn try_new_with_buffers(
+        buffers: Arc<SparkUDAFBuffers>,
+        return_type: DataType,
+        children: Vec<Arc<dyn PhysicalExpr>>,
+    ) -> Result<Self> {
+        Ok(Self {
+            import_schema: Arc::new(Schema::new(vec![Field::new("", return_type.clone(), true)])),
+            return_type,
+            children,
+            accums_initial: vec![AccumInitialValue::SparkUDAFBuffer(buffers.clone())],
+            buffers,
+            accum_state_val_addr: AccumStateValAddr::default(),
+        })
+    }
+
+    fn buffer_idx(&self, acc: &mut RefAccumStateRow) -> Result<Option<i32>> {
+        Ok(match acc.dyn_value(self.accum_state_val_addr) {
+            Some(v) => Some(downcast_any!(v, SparkUDAFBufferRef)?.idx),
+            None => None,
+        })
+    }
+
+    fn buffer_idx_or_alloc(&self, acc: &mut RefAccumStateRow) -> Result<i32> {
+        if let Some(idx) = self.buffer_idx(acc)? {
+            return Ok(idx);
+        }
+        let buffer_ref = self.buffers.alloc();
+        let idx = buffer_ref.idx;
+        *acc.dyn_value_mut(self.accum_state_val_addr) = Some(Box::new(buffer_ref));
+        Ok(idx)
+    }
+
+    fn take_buffer_idx(&self, acc: &mut RefAccumStateRow) -> Result<Option<i32>> {
+        Ok(match acc.dyn_value_mut(self.accum_state_val_addr).take() {
+            Some(v) => Some(downcast_any!(v, SparkUDAFBufferRef)?.idx),
+            None => None,
+        })
+    }
+
+    fn update_buffers(&self, idxs: Vec<i32>, values: &[ArrayRef]) -> Result<()> {
+        if !is_task_running() {
+            df_execution_err!("SparkUDAFWrapper: is_task_running=false")?;
+        }
+        let num_rows = idxs.len();
+        let columns = [
+            vec![Arc::new(Int32Array::from(idxs)) as ArrayRef],
+            values.to_vec(),
+        ];
+        let input = make_struct_array(columns.concat(), num_rows)?;
+        let mut export_ffi_array = FFI_ArrowArray::new(&input.to_data());
+        let buffer_size = jni_call!(SparkUDAFWrapperContext(self.buffers.jcontext()?.as_obj())
+            .update(&mut export_ffi_array as *mut FFI_ArrowArray as i64) -> i64)?;
+        self.buffers.update_mem_used(buffer_size as usize);
+        Ok(())
+    }
+
+    fn merge_buffers(
+        &self,
+        accs: &mut [RefAccumStateRow],
+        merging_accs: &mut [RefAccumStateRow],
+    ) -> Result<()> {
+        let mut idxs = vec![];
+        let mut merging_idxs = vec![];
+        for (acc, merging_acc) in accs.iter_mut().zip(merging_accs.iter_mut()) {
+            let Some(merging_idx) = self.take_buffer_idx(merging_acc)? else {
+                continue;
+            };
+            match self.buffer_idx(acc)? {
+                Some(idx) => {
+                    idxs.push(idx);
+                    merging_idxs.push(merging_idx);
+                }
+                None => {
+                    // no need to merge with an empty buffer, just take over it
+                    let buffer_ref = SparkUDAFBufferRef { idx: merging_idx };
+                    *acc.dyn_value_mut(self.accum_state_val_addr) = Some(Box::new(buffer_ref));
+                }
+            }
+        }
+        if idxs.is_empty() {
+            return Ok(());
+        }
+        if !is_task_running() {
+            df_execution_err!("SparkUDAFWrapper: is_task_running=false")?;
+        }
+
+        // merged buffers are released in the jvm side
+        let num_rows = idxs.len();
+        let input = make_struct_array(
+            vec![
+                Arc::new(Int32Array::from(idxs)),
+                Arc::new(Int32Array::from(merging_idxs.clone())),
+            ],
+            num_rows,
+        )?;
+        let mut export_ffi_array = FFI_ArrowArray::new(&input.to_data());
+        let buffer_size = jni_call!(SparkUDAFWrapperContext(self.buffers.jcontext()?.as_obj())
+            .merge(&mut export_ffi_array as *mut FFI_ArrowArray as i64) -> i64)?;
+        self.buffers.free(&merging_idxs);
+        self.buffers.update_mem_used(buffer_size as usize);
+        Ok(())
+    }
+
+    fn eval_buffers(&self, accs: &mut [RefAccumStateRow]) -> Result<ArrayRef> {
+        if !is_task_running() {
+            df_execution_err!("SparkUDAFWrapper: is_task_running=false")?;
+        }
+        let idxs = accs
+            .iter_mut()
+            .map(|acc| self.take_buffer_idx(acc))
+            .collect::<Result<Vec<_>>>()?;
+
+        // evaluated buffers are released in the jvm side, null indices are
+        // evaluated with newly initialized buffers
+        let num_rows = idxs.len();
+        let allocated_idxs = idxs.iter().flatten().copied().collect::<Vec<_>>();
+        let input = make_struct_array(vec![Arc::new(Int32Array::from(idxs))], num_rows)?;
+        let mut export_ffi_array = FFI_ArrowArray::new(&input.to_data());
+        let mut import_ffi_array = FFI_ArrowArray::empty();
+        jni_call!(SparkUDAFWrapperContext(self.buffers.jcontext()?.as_obj()).eval(
+            &mut export_ffi_array as *mut FFI_ArrowArray as i64,
+            &mut import_ffi_array as *mut FFI_ArrowArray as i64,
+        ) -> ())?;
+        self.buffers.free(&allocated_idxs);
+
+        // import output from context
+        let import_ffi_schema = FFI_ArrowSchema::try_from(self.import_schema.as_ref())?;
+        let import_struct_array =
+            make_array(unsafe { from_ffi(import_ffi_array, &import_ffi_schema)? });
+        Ok(as_struct_array(&import_struct_array).column(0).clone())
+    }
+}
+
+impl Debug for SparkUDAFWrapper {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SparkUDAFWrapper({:?})", self.children)
+    }
+}
+
+impl Agg for SparkUDAFWrapper {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn exprs(&self) -> Vec<Arc<dyn PhysicalExpr>> {
+        self.children.clone()
+    }
+
+    fn with_new_exprs(&self, exprs: Vec<Arc<dyn PhysicalExpr>>) -> Result<Arc<dyn Agg>> {
+        Ok(Arc::new(Self::try_new_with_buffers(
+            self.buffers.clone(),
+            self.return_type.clone(),
+            exprs,
+        )?))
+    }
+
+    fn data_type(&self) -> &DataType {
+        &self.return_type
+    }
+
+    fn nullable(&self) -> bool {
+        true
+    }
+
+    fn accums_initial(&self) -> &[AccumInitialValue] {
+        &self.accums_initial
+    }
+
+    fn increase_acc_mem_used(&self, _acc: &mut RefAccumStateRow) {
+        // do nothing, buffer memory is estimated by the jvm side
+    }
+
+    fn partial_update(
+        &self,
+        acc: &mut RefAccumStateRow,
+        values: &[ArrayRef],
+        row_idx: usize,
+    ) -> Result<()> {
+        let idx = self.buffer_idx_or_alloc(acc)?;
+        let values = values
+            .iter()
+            .map(|value| value.slice(row_idx, 1))
+            .collect::<Vec<_>>();
+        self.update_buffers(vec![idx], &values)
+    }
+
+    fn partial_batch_update(
+        &self,
+        accs: &mut [RefAccumStateRow],
+        values: &[ArrayRef],
+    ) -> Result<()> {
+        // accs of the same group share one buffer, so buffers must be allocated
+        // one by one before updating
+        let idxs = accs
+            .iter_mut()
+            .map(|acc| self.buffer_idx_or_alloc(acc))
+            .collect::<Result<Vec<_>>>()?;
+        self.update_buffers(idxs, values)
+    }
+
+    fn partial_update_all(
+        &self,
+        acc: &mut RefAccumStateRow,
+        num_rows: usize,
+        values: &[ArrayRef],
+    ) -> Result<()> {
+        let idx = self.buffer_idx_or_alloc(acc)?;
+        self.update_buffers(vec![idx; num_rows], values)
+    }
+
+    fn partial_merge(
+        &self,
+        acc: &mut RefAccumStateRow,
+        merging_acc: &mut RefAccumStateRow,
+    ) -> Result<()> {
+        self.merge_buffers(std::slice::from_mut(acc), std::slice::from_mut(merging_acc))
+    }
+
+    fn partial_batch_merge(
+        &self,
+        accs: &mut [RefAccumStateRow],
+        merging_accs: &mut [RefAccumStateRow],
+    ) -> Result<()> {
+        self.merge_buffers(accs, merging_accs)
+    }
+
+    fn final_merge(&self, acc: &mut RefAccumStateRow) -> Result<ScalarValue> {
+        let values = self.eval_buffers(std::slice::from_mut(acc))?;
+        ScalarValue::try_from_array(&values, 0)
+    }
+
+    fn final_batch_merge(&self, accs: &mut [RefAccumStateRow]) -> Result<ArrayRef> {
+        self.eval_buffers(accs)
+    }
+}
+
+fn make_struct_array(columns: Vec<ArrayRef>, num_rows: usize) -> Result<StructArray> {
+    let fields = columns
+        .iter()
+        .map(|column| Field::new("", column.data_type().clone(), true))
+        .collect::<Vec<_>>();
+    let batch = RecordBatch::try_new_with_options(
+        Arc::new(Schema::new(fields)),
+        columns,
+        &RecordBatchOptions::new().with_row_count(Some(num_rows)),
+    )?;
+    Ok(StructArray::from(batch))
+}
+
+/// Aggregation buffers of a SparkUDAFWrapper, buffer indices are allocated in
+/// the native side and the buffers are lazily created in the jvm side on first
+/// update. A buffer is released by the jvm side once it is merged into another
+/// buffer, evaluated or serialized, so the index can be reused.
+pub struct SparkUDAFBuffers {
+    serialized: Vec<u8>,
+    jcontext: OnceCell<GlobalRef>,
+    idxs: Mutex<BufferIdxs>,
+    estimated_buffer_size: AtomicUsize,
+    mem_used_tracker: AtomicUsize,
+}
+
+#[derive(Default)]
+struct BufferIdxs {
+    num_allocated: usize,
+    free_idxs: Vec<i32>,
+}
+
+impl BufferIdxs {
+    fn num_used(&self) -> usize {
+        self.num_allocated - self.free_idxs.len()
+    }
+}
+
+impl SparkUDAFBuffers {
+    fn new(serialized: Vec<u8>) -> Self {
+        Self {
+            serialized,
+            jcontext: OnceCell::new(),
+            idxs: Mutex::default(),
+            estimated_buffer_size: AtomicUsize::new(0),
+            mem_used_tracker: AtomicUsize::new(0),
+        }
+    }
+
+    fn jcontext(&self) -> Result<GlobalRef> {
+        self.jcontext
+            .get_or_try_init(|| {
+                let serialized_buf = jni_new_direct_byte_buffer!(&self.serialized)?;
+                let jcontext_local =
+                    jni_new_object!(SparkUDAFWrapperContext(serialized_buf.as_obj()))?;
+                jni_new_global_ref!(jcontext_local.as_obj())
+            })
+            .cloned()
+    }
+
+    fn alloc(&self) -> SparkUDAFBufferRef {
+        let mut idxs = self.idxs.lock();
+        let idx = idxs.free_idxs.pop().unwrap_or_else(|| {
+            idxs.num_allocated += 1;
+            idxs.num_allocated as i32 - 1
+        });
+        let num_used = idxs.num_used();
+        drop(idxs);
+
+        let buffer_size = self.estimated_buffer_size.load(SeqCst);
+        self.mem_used_tracker.store(num_used * buffer_size, SeqCst);
+        SparkUDAFBufferRef { idx }
+    }
+
+    fn free(&self, freed_idxs: &[i32]) {
+        let mut idxs = self.idxs.lock();
+        idxs.free_idxs.extend_from_slice(freed_idxs);
+        let num_used = idxs.num_used();
+        drop(idxs);
+
+        let buffer_size = self.estimated_buffer_size.load(SeqCst);
+        self.mem_used_tracker.store(num_used * buffer_size, SeqCst);
+    }
+
+    fn update_mem_used(&self, estimated_buffer_size: usize) {
+        let num_used = self.idxs.lock().num_used();
+        self.estimated_buffer_size
+            .store(estimated_buffer_size, SeqCst);
+        self.mem_used_tracker
+            .store(num_used * estimated_buffer_size, SeqCst);
+    }
+
+    /// serializes the buffer and releases it
+    pub fn serialize(&self, buffer_ref: &SparkUDAFBufferRef) -> Result<Vec<u8>> {
+        let serialized = jni_call!(SparkUDAFWrapperContext(self.jcontext()?.as_obj())
+            .serialize(buffer_ref.idx) -> JObject)?;
+        let bytes = jni_convert_byte_array!(serialized.as_obj())?;
+        self.free(&[buffer_ref.idx]);
+        Ok(bytes)
+    }
+
+    /// deserializes a buffer into a newly allocated index
+    pub fn deserialize(&self, bytes: &[u8]) -> Result<SparkUDAFBufferRef> {
+        let buffer_ref = self.alloc();
+        let jcontext = self.jcontext()?;
+        if bytes.is_empty() {
+            jni_call!(SparkUDAFWrapperContext(jcontext.as_obj())
+                .deserialize(buffer_ref.idx, JObject::null()) -> ())?;
+        } else {
+            let buf = jni_new_direct_byte_buffer!(bytes)?;
+            jni_call!(SparkUDAFWrapperContext(jcontext.as_obj())
+                .deserialize(buffer_ref.idx, buf.as_obj()) -> ())?;
+        }
+        Ok(buffer_ref)
+    }
+}
+
+impl Debug for SparkUDAFBuffers {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SparkUDAFBuffers")
+    }
+}
+
+impl PartialEq for SparkUDAFBuffers {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Eq for SparkUDAFBuffers {}
+
+/// Index of a buffer in SparkUDAFBuffers, cloned references are only used for
+/// moving accumulators and do not keep the buffer alive.
+#[derive(Clone, Copy)]
+pub struct SparkUDAFBufferRef {
+    idx: i32,
+}
+
+impl AggDynValue for SparkUDAFBufferRef {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn as_any_boxed(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+
+    fn mem_size(&self) -> usize {
+        size_of::<Self>()
+    }
+
+    fn clone_boxed(&self) -> Box<dyn AggDynValue> {
+        Box::new(*self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::Ordering::SeqCst;
+
+    use crate::agg::spark_udaf_wrapper::SparkUDAFBuffers;
+
+    #[test]
+    fn test_buffer_idx_reuse() {
+        let buffers = SparkUDAFBuffers::new(vec![]);
+        let idxs = (0..4).map(|_| buffers.alloc().idx).collect::<Vec<_>>();
+        assert_eq!(idxs, vec![0, 1, 2, 3]);
+
+        buffers.update_mem_used(100);
+        assert_eq!(buffers.mem_used_tracker.load(SeqCst), 400);
+
+        buffers.free(&[1, 2]);
+        assert_eq!(buffers.mem_used_tracker.load(SeqCst), 200);
+        assert_eq!(buffers.alloc().idx, 2);
+        assert_eq!(buffers.alloc().idx, 1);
+        assert_eq!(buffers.alloc().idx, 4);
+        assert_eq!(buffers.mem_used_tracker.load(SeqCst), 500);
+    }
+}
@@ -15,16 +15,18 @@
 use std::{
     any::Any,
     fmt::{Debug, Formatter},
-    ops::Add,
     sync::{atomic::AtomicUsize, Arc},
 };
 
-use arrow::{array::*, datatypes::*};
+use arrow::{array::*, datatypes::*, error::ArrowError};
 use datafusion::{
-    common::{Result, ScalarValue},
+    common::{cast::as_decimal128_array, Result, ScalarValue},
     physical_expr::PhysicalExpr,
 };
-use datafusion_ext_commons::df_unimplemented_err;
+use datafusion_ext_commons::{
+    df_unimplemented_err,
+    spark_eval_mode::{arithmetic_overflow_err, numeric_value_out_of_range_err, EvalMode},
+};
 use paste::paste;
 
 use crate::agg::{
@@ -36,11 +38,12 @@ use crate::agg::{
 pub struct AggSum {
     child: Arc<dyn PhysicalExpr>,
     data_type: DataType,
+    eval_mode: EvalMode,
     accums_initial: Vec<AccumInitialValue>,
     accum_state_val_addr: AccumStateValAddr,
-    partial_updater: fn(&Self, &mut RefAccumStateRow, &ArrayRef, usize),
-    partial_batch_updater: fn(&Self, &mut [RefAccumStateRow], &ArrayRef),
-    partial_buf_merger: fn(&Self, &mut RefAccumStateRow, &mut RefAccumStateRow),
+    partial_updater: fn(&Self, &mut RefAccumStateRow, &ArrayRef, usize) -> Result<()>,
+    partial_batch_updater: fn(&Self, &mut [RefAccumStateRow], &ArrayRef) -> Result<()>,
+    partial_buf_merger: fn(&Self, &mut RefAccumStateRow, &mut RefAccumStateRow) -> Result<()>,
     mem_used_tracker: AtomicUsize,
 }
 
@@ -58,6 +61,14 @@ impl WithMemTracking for AggSum {
 
 impl AggSum {
     pub fn try_new(child: Arc<dyn PhysicalExpr>, data_type: DataType) -> Result<Self> {
+        Self::try_new_with_eval_mode(child, data_type, EvalMode::Legacy)
+    }
+
+    pub fn try_new_with_eval_mode(
+        child: Arc<dyn PhysicalExpr>,
+        data_type: DataType,
+        eval_mode: EvalMode,
+    ) -> Result<Self> {
         let accums_initial = vec![AccumInitialValue::Scalar(ScalarValue::try_from(
             &data_type,
         )?)];
@@ -67,6 +78,7 @@ impl AggSum {
         Ok(Self {
             child,
             data_type,
+            eval_mode,
             accums_initial,
             accum_state_val_addr: AccumStateValAddr::default(),
             partial_updater,
@@ -93,9 +105,10 @@ impl Agg for AggSum {
     }
 
     fn with_new_exprs(&self, exprs: Vec<Arc<dyn PhysicalExpr>>) -> Result<Arc<dyn Agg>> {
-        Ok(Arc::new(Self::try_new(
+        Ok(Arc::new(Self::try_new_with_eval_mode(
             exprs[0].clone(),
             self.data_type.clone(),
+            self.eval_mode,
         )?))
     }
 
@@ -130,8 +143,7 @@ impl Agg for AggSum {
         row_idx: usize,
     ) -> Result<()> {
         let partial_updater = self.partial_updater;
-        partial_updater(self, acc, &values[0], row_idx);
-        Ok(())
+        partial_updater(self, acc, &values[0], row_idx)
     }
 
     fn partial_batch_update(
@@ -140,8 +152,7 @@ impl Agg for AggSum {
         values: &[ArrayRef],
     ) -> Result<()> {
         let partial_batch_updater = self.partial_batch_updater;
-        partial_batch_updater(self, accs, &values[0]);
-        Ok(())
+        partial_batch_updater(self, accs, &values[0])
     }
 
     fn partial_update_all(
@@ -154,8 +165,13 @@ impl Agg for AggSum {
             ($ty:ident) => {{
                 type TArray = paste! {[<$ty Array>]};
                 let value = values[0].as_any().downcast_ref::<TArray>().unwrap();
-                if let Some(sum) = arrow::compute::sum(value) {
-                    partial_update_prim(acc, self.accum_state_val_addr, sum);
+                let sum = match self.eval_mode {
+                    EvalMode::Ansi => arrow::compute::sum_checked(value)
+                        .or_else(|_| arithmetic_overflow_err(&self.data_type, Some("try_add")))?,
+                    _ => arrow::compute::sum(value),
+                };
+                if let Some(sum) = sum {
+                    partial_update_prim(self, acc, sum)?;
                 }
             }};
         }
@@ -183,8 +199,7 @@ impl Agg for AggSum {
         acc2: &mut RefAccumStateRow,
     ) -> Result<()> {
         let partial_buf_merger = self.partial_buf_merger;
-        partial_buf_merger(self, acc1, acc2);
-        Ok(())
+        partial_buf_merger(self, acc1, acc2)
     }
 
     fn partial_batch_merge(
@@ -194,49 +209,87 @@ impl Agg for AggSum {
     ) -> Result<()> {
         let partial_buf_merger = self.partial_buf_merger;
         for (acc, merging_acc) in accs.iter_mut().zip(merging_accs) {
-            partial_buf_merger(self, acc, merging_acc);
+            partial_buf_merger(self, acc, merging_acc)?;
         }
         Ok(())
     }
 
     fn final_merge(&self, acc: &mut RefAccumStateRow) -> Result<ScalarValue> {
-        default_final_merge_with_addr(self, acc, self.accum_state_val_addr)
+        let sum = default_final_merge_with_addr(self, acc, self.accum_state_val_addr)?;
+        if let ScalarValue::Decimal128(..) = sum {
+            let sum = check_decimal_overflow(sum.to_array()?, self.eval_mode)?;
+            return ScalarValue::try_from_array(&sum, 0);
+        }
+        Ok(sum)
     }
 
     fn final_batch_merge(&self, accs: &mut [RefAccumStateRow]) -> Result<ArrayRef> {
-        default_final_batch_merge_with_addr(self, accs, self.accum_state_val_addr)
+        let sums = default_final_batch_merge_with_addr(self, accs, self.accum_state_val_addr)?;
+        check_decimal_overflow(sums, self.eval_mode)
+    }
+}
+
+/// nullifies decimal values exceeding the precision of their data type, or
+/// raises errors in ANSI mode. values of other data types are returned as is.
+pub(crate) fn check_decimal_overflow(values: ArrayRef, eval_mode: EvalMode) -> Result<ArrayRef> {
+    if let &DataType::Decimal128(prec, scale) = values.data_type() {
+        let values = as_decimal128_array(&values)?;
+        if eval_mode == EvalMode::Ansi {
+            for v in values.iter().flatten() {
+                if !Decimal128Type::is_valid_decimal_precision(v, prec) {
+                    let value = Decimal128Type::format_decimal(v, prec, scale);
+                    return numeric_value_out_of_range_err(&value, prec, scale);
+                }
+            }
+        }
+        return Ok(Arc::new(
+            values
+                .null_if_overflow_precision(prec)
+                .with_precision_and_scale(prec, scale)?,
+        ));
     }
+    Ok(values)
 }
 
-fn partial_update_prim<T: Copy + Add<Output = T>>(
+fn partial_update_prim<T: ArrowNativeTypeOp>(
+    this: &AggSum,
     acc: &mut RefAccumStateRow,
-    addr: AccumStateValAddr,
     v: T,
-) {
+) -> Result<()> {
+    let addr = this.accum_state_val_addr;
     if acc.is_fixed_valid(addr) {
-        acc.update_fixed_value::<T>(addr, |w| w + v);
+        let sum = acc.fixed_value::<T>(addr);
+        let sum = match this.eval_mode {
+            EvalMode::Ansi => sum.add_checked(v).or_else(|_: ArrowError| {
+                arithmetic_overflow_err(&this.data_type, Some("try_add"))
+            })?,
+            _ => sum.add_wrapping(v),
+        };
+        acc.set_fixed_value::<T>(addr, sum);
     } else {
         acc.set_fixed_value::<T>(addr, v);
         acc.set_fixed_valid(addr, true);
     }
+    Ok(())
 }
 
 fn get_partial_updater(
     dt: &DataType,
-) -> Result<fn(&AggSum, &mut RefAccumStateRow, &ArrayRef, usize)> {
+) -> Result<fn(&AggSum, &mut RefAccumStateRow, &ArrayRef, usize) -> Result<()>> {
     macro_rules! fn_fixed {
         ($ty:ident) => {{
             Ok(|this, acc, v, i| {
                 type TArray = paste! {[<$ty Array>]};
                 let value = v.as_any().downcast_ref::<TArray>().unwrap();
                 if value.is_valid(i) {
-                    partial_update_prim(acc, this.accum_state_val_addr, value.value(i));
+                    partial_update_prim(this, acc, value.value(i))?;
                 }
+                Ok(())
             })
         }};
     }
     match dt {
-        DataType::Null => Ok(|_, _, _, _| ()),
+        DataType::Null => Ok(|_, _, _, _| Ok(())),
         DataType::Float32 => fn_fixed!(Float32),
         DataType::Float64 => fn_fixed!(Float64),
         DataType::Int8 => fn_fixed!(Int8),
@@ -254,7 +307,7 @@ fn get_partial_updater(
 
 fn get_partial_batch_updater(
     dt: &DataType,
-) -> Result<fn(&AggSum, &mut [RefAccumStateRow], &ArrayRef)> {
+) -> Result<fn(&AggSum, &mut [RefAccumStateRow], &ArrayRef) -> Result<()>> {
     macro_rules! fn_fixed {
         ($ty:ident) => {{
             Ok(|this, accs, v| {
@@ -262,14 +315,15 @@ fn get_partial_batch_updater(
                 let value = v.as_any().downcast_ref::<TArray>().unwrap();
                 for (acc, value) in accs.iter_mut().zip(value.iter()) {
                     if let Some(value) = value {
-                        partial_update_prim(acc, this.accum_state_val_addr, value);
+                        partial_update_prim(this, acc, value)?;
                     }
                 }
+                Ok(())
             })
         }};
     }
     match dt {
-        DataType::Null => Ok(|_, _, _| ()),
+        DataType::Null => Ok(|_, _, _| Ok(())),
         DataType::Float32 => fn_fixed!(Float32),
         DataType::Float64 => fn_fixed!(Float64),
         DataType::Int8 => fn_fixed!(Int8),
@@ -287,7 +341,7 @@ fn get_partial_batch_updater(
 
 fn get_partial_buf_merger(
     dt: &DataType,
-) -> Result<fn(&AggSum, &mut RefAccumStateRow, &mut RefAccumStateRow)> {
+) -> Result<fn(&AggSum, &mut RefAccumStateRow, &mut RefAccumStateRow) -> Result<()>> {
     macro_rules! fn_fixed {
         ($ty:ident) => {{
             Ok(|this, acc1, acc2| {
@@ -295,13 +349,14 @@ fn get_partial_buf_merger(
                 type TNative = <TType as ArrowPrimitiveType>::Native;
                 if acc2.is_fixed_valid(this.accum_state_val_addr) {
                     let v = acc2.fixed_value::<TNative>(this.accum_state_val_addr);
-                    partial_update_prim(acc1, this.accum_state_val_addr, v);
+                    partial_update_prim(this, acc1, v)?;
                 }
+                Ok(())
             })
         }};
     }
     match dt {
-        DataType::Null => Ok(|_, _, _| ()),
+        DataType::Null => Ok(|_, _, _| Ok(())),
         DataType::Float32 => fn_fixed!(Float32),
         DataType::Float64 => fn_fixed!(Float64),
         DataType::Int8 => fn_fixed!(Int8),
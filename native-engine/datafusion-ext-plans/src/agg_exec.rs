@@ -396,21 +396,23 @@ mod test {
     use std::sync::Arc;
 
     use arrow::{
-        array::Int32Array,
+        array::{Int32Array, Int64Array},
         datatypes::{DataType, Field, Schema},
         record_batch::RecordBatch,
     };
     use datafusion::{
         assert_batches_sorted_eq,
         common::{Result, ScalarValue},
-        physical_expr::{expressions as phys_expr, expressions::Column},
+        logical_expr::Operator,
+        physical_expr::{expressions as phys_expr, expressions::Column, PhysicalExpr},
         physical_plan::{common, memory::MemoryExec, ExecutionPlan},
         prelude::SessionContext,
     };
+    use datafusion_ext_commons::spark_eval_mode::EvalMode;
 
     use crate::{
         agg::{
-            create_agg,
+            create_agg, create_agg_with_eval_mode,
             AggExecMode::HashAgg,
             AggExpr, AggFunction,
             AggMode::{Final, Partial},
@@ -653,4 +655,289 @@ mod test {
         assert_batches_sorted_eq!(expected, &batches);
         Ok(())
     }
+
+    fn build_agg_input() -> Arc<dyn ExecutionPlan> {
+        build_table(
+            ("a", &vec![2, 9, 3, 1, 0, 4, 6]),
+            ("b", &vec![1, 0, 0, 3, 5, 6, 3]),
+            ("c", &vec![7, 8, 7, 8, 9, 2, 5]),
+            ("d", &vec![-7, 86, 71, 83, 90, -2, 5]),
+            ("e", &vec![-7, 86, 71, 83, 90, -2, 5]),
+            ("f", &vec![0, 1, 2, 3, 4, 5, 6]),
+            ("g", &vec![6, 3, 6, 3, 1, 5, 4]),
+            ("h", &vec![6, 3, 6, 3, 1, 5, 4]),
+        )
+    }
+
+    /// runs `aggs` as a partial aggregation grouped by `group_col`, followed by
+    /// the final aggregation, and collects the output
+    async fn run_partial_final(
+        input: Arc<dyn ExecutionPlan>,
+        group_col: &str,
+        aggs: Vec<AggExpr>,
+    ) -> Result<Vec<RecordBatch>> {
+        let agg_exec_partial = AggExec::try_new(
+            HashAgg,
+            vec![GroupingExpr {
+                field_name: group_col.to_string(),
+                expr: phys_expr::col(group_col, &input.schema())?,
+            }],
+            aggs.clone(),
+            0,
+            false,
+            input,
+        )?;
+
+        let agg_exec_final = AggExec::try_new(
+            HashAgg,
+            vec![GroupingExpr {
+                field_name: group_col.to_string(),
+                expr: Arc::new(Column::new(group_col, 0)),
+            }],
+            aggs.into_iter()
+                .map(|mut agg| {
+                    agg.agg = agg
+                        .agg
+                        .with_new_exprs(vec![Arc::new(phys_expr::Literal::new(
+                            ScalarValue::Null,
+                        ))])?;
+                    agg.mode = Final;
+                    Ok(agg)
+                })
+                .collect::<Result<_>>()?,
+            0,
+            false,
+            Arc::new(agg_exec_partial),
+        )?;
+
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+        let output_final = agg_exec_final.execute(0, task_ctx)?;
+        common::collect(output_final).await
+    }
+
+    fn partial_aggs(
+        input: &Arc<dyn ExecutionPlan>,
+        aggs: Vec<(&str, AggFunction, Vec<Arc<dyn PhysicalExpr>>)>,
+    ) -> Result<Vec<AggExpr>> {
+        aggs.into_iter()
+            .map(|(field_name, agg_function, children)| {
+                Ok(AggExpr {
+                    field_name: field_name.to_string(),
+                    mode: Partial,
+                    agg: create_agg(agg_function, &children, &input.schema())?,
+                })
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_approx_count_distinct() -> Result<()> {
+        MemManager::init(10000);
+
+        let input = build_agg_input();
+        let g = phys_expr::col("g", &input.schema())?;
+        let rsd: Arc<dyn PhysicalExpr> =
+            Arc::new(phys_expr::Literal::new(ScalarValue::Float64(Some(0.05))));
+        let null: Arc<dyn PhysicalExpr> =
+            Arc::new(phys_expr::Literal::new(ScalarValue::Utf8(None)));
+        let aggs = partial_aggs(
+            &input,
+            vec![
+                (
+                    "agg_expr_approx_count_distinct",
+                    AggFunction::ApproxCountDistinct,
+                    vec![g, rsd],
+                ),
+                (
+                    "agg_expr_approx_count_distinct_nil",
+                    AggFunction::ApproxCountDistinct,
+                    vec![null],
+                ),
+            ],
+        )?;
+
+        let batches = run_partial_final(input, "b", aggs).await?;
+        let expected = vec![
+            "+---+--------------------------------+------------------------------------+",
+            "| b | agg_expr_approx_count_distinct | agg_expr_approx_count_distinct_nil |",
+            "+---+--------------------------------+------------------------------------+",
+            "| 0 | 2                              | 0                                  |",
+            "| 1 | 1                              | 0                                  |",
+            "| 3 | 2                              | 0                                  |",
+            "| 5 | 1                              | 0                                  |",
+            "| 6 | 1                              | 0                                  |",
+            "+---+--------------------------------+------------------------------------+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_percentile() -> Result<()> {
+        MemManager::init(10000);
+
+        let input = build_agg_input();
+        let a = phys_expr::col("a", &input.schema())?;
+        let half: Arc<dyn PhysicalExpr> =
+            Arc::new(phys_expr::Literal::new(ScalarValue::Float64(Some(0.5))));
+        let aggs = partial_aggs(
+            &input,
+            vec![
+                (
+                    "agg_expr_percentile",
+                    AggFunction::Percentile,
+                    vec![a.clone(), half.clone()],
+                ),
+                ("agg_expr_median", AggFunction::Median, vec![a.clone()]),
+                (
+                    "agg_expr_approx_percentile",
+                    AggFunction::ApproxPercentile,
+                    vec![a, half],
+                ),
+            ],
+        )?;
+
+        let batches = run_partial_final(input, "b", aggs).await?;
+        let expected = vec![
+            "+---+---------------------+-----------------+----------------------------+",
+            "| b | agg_expr_percentile | agg_expr_median | agg_expr_approx_percentile |",
+            "+---+---------------------+-----------------+----------------------------+",
+            "| 0 | 6.0                 | 6.0             | 3                          |",
+            "| 1 | 2.0                 | 2.0             | 2                          |",
+            "| 3 | 3.5                 | 3.5             | 1                          |",
+            "| 5 | 0.0                 | 0.0             | 0                          |",
+            "| 6 | 4.0                 | 4.0             | 4                          |",
+            "+---+---------------------+-----------------+----------------------------+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bitwise_and_bool_aggs() -> Result<()> {
+        MemManager::init(10000);
+
+        let input = build_agg_input();
+        let g = phys_expr::col("g", &input.schema())?;
+        let a_gt_2: Arc<dyn PhysicalExpr> = Arc::new(phys_expr::BinaryExpr::new(
+            phys_expr::col("a", &input.schema())?,
+            Operator::Gt,
+            Arc::new(phys_expr::Literal::new(ScalarValue::Int32(Some(2)))),
+        ));
+        let aggs = partial_aggs(
+            &input,
+            vec![
+                ("bit_and", AggFunction::BitAnd, vec![g.clone()]),
+                ("bit_or", AggFunction::BitOr, vec![g.clone()]),
+                ("bit_xor", AggFunction::BitXor, vec![g]),
+                ("bool_and", AggFunction::BoolAnd, vec![a_gt_2.clone()]),
+                ("count_if", AggFunction::CountIf, vec![a_gt_2]),
+            ],
+        )?;
+
+        let batches = run_partial_final(input, "b", aggs).await?;
+        let expected = vec![
+            "+---+---------+--------+---------+----------+----------+",
+            "| b | bit_and | bit_or | bit_xor | bool_and | count_if |",
+            "+---+---------+--------+---------+----------+----------+",
+            "| 0 | 2       | 7      | 5       | true     | 2        |",
+            "| 1 | 6       | 6      | 6       | false    | 0        |",
+            "| 3 | 0       | 7      | 7       | false    | 1        |",
+            "| 5 | 1       | 1      | 1       | false    | 0        |",
+            "| 6 | 5       | 5      | 5       | true     | 1        |",
+            "+---+---------+--------+---------+----------+----------+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_last_and_maxmin_by() -> Result<()> {
+        MemManager::init(10000);
+
+        let input = build_agg_input();
+        let a = phys_expr::col("a", &input.schema())?;
+        let g = phys_expr::col("g", &input.schema())?;
+        let aggs = partial_aggs(
+            &input,
+            vec![
+                ("last", AggFunction::Last, vec![a.clone()]),
+                (
+                    "last_ignores_null",
+                    AggFunction::LastIgnoresNull,
+                    vec![a.clone()],
+                ),
+                ("max_by", AggFunction::MaxBy, vec![a.clone(), g.clone()]),
+                ("min_by", AggFunction::MinBy, vec![a, g]),
+            ],
+        )?;
+
+        let batches = run_partial_final(input, "b", aggs).await?;
+        let expected = vec![
+            "+---+------+-------------------+--------+--------+",
+            "| b | last | last_ignores_null | max_by | min_by |",
+            "+---+------+-------------------+--------+--------+",
+            "| 0 | 3    | 3                 | 3      | 9      |",
+            "| 1 | 2    | 2                 | 2      | 2      |",
+            "| 3 | 6    | 6                 | 6      | 1      |",
+            "| 5 | 0    | 0                 | 0      | 0      |",
+            "| 6 | 4    | 4                 | 4      | 4      |",
+            "+---+------+-------------------+--------+--------+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sum_overflow() -> Result<()> {
+        MemManager::init(10000);
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int32, false),
+            Field::new("b", DataType::Int64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 1, 2])),
+                Arc::new(Int64Array::from(vec![i64::MAX, 1, 1])),
+            ],
+        )?;
+        let input: Arc<dyn ExecutionPlan> =
+            Arc::new(MemoryExec::try_new(&[vec![batch]], schema, None)?);
+        let sum = |eval_mode| -> Result<Vec<AggExpr>> {
+            Ok(vec![AggExpr {
+                field_name: "sum".to_string(),
+                mode: Partial,
+                agg: create_agg_with_eval_mode(
+                    AggFunction::Sum,
+                    &[phys_expr::col("b", &input.schema())?],
+                    &input.schema(),
+                    eval_mode,
+                )?,
+            }])
+        };
+
+        // legacy mode wraps on overflow
+        let batches = run_partial_final(input.clone(), "a", sum(EvalMode::Legacy)?).await?;
+        let expected = vec![
+            "+---+----------------------+",
+            "| a | sum                  |",
+            "+---+----------------------+",
+            "| 1 | -9223372036854775808 |",
+            "| 2 | 1                    |",
+            "+---+----------------------+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+
+        // ANSI mode raises an error
+        let err = run_partial_final(input.clone(), "a", sum(EvalMode::Ansi)?)
+            .await
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("[ARITHMETIC_OVERFLOW] long overflow"));
+        Ok(())
+    }
 }
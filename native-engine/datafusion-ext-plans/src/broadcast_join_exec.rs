@@ -170,6 +170,7 @@ impl BroadcastJoinExec {
             sort_options: vec![SortOptions::default(); self.on.len()],
             projection,
             key_data_types,
+            band_condition: None,
         })
     }
 
@@ -317,7 +318,7 @@ impl DisplayAs for BroadcastJoinExec {
     }
 }
 
-async fn execute_join_with_map(
+pub(crate) async fn execute_join_with_map(
     mut probed: SendableRecordBatchStream,
     map: Arc<JoinHashMap>,
     join_params: JoinParams,
@@ -330,30 +331,7 @@ async fn execute_join_with_map(
     sender: Arc<WrappedRecordBatchSender>,
 ) -> Result<()> {
     let _timer = metrics.elapsed_compute().timer();
-    let mut joiner: Pin<Box<dyn Joiner + Send>> = match broadcast_side {
-        JoinSide::Left => match join_params.join_type {
-            Inner => Box::pin(RProbedInnerJoiner::new(join_params, map, sender)),
-            Left => Box::pin(RProbedLeftJoiner::new(join_params, map, sender)),
-            Right => Box::pin(RProbedRightJoiner::new(join_params, map, sender)),
-            Full => Box::pin(RProbedFullOuterJoiner::new(join_params, map, sender)),
-            LeftSemi => Box::pin(RProbedLeftSemiJoiner::new(join_params, map, sender)),
-            LeftAnti => Box::pin(RProbedLeftAntiJoiner::new(join_params, map, sender)),
-            RightSemi => Box::pin(RProbedRightSemiJoiner::new(join_params, map, sender)),
-            RightAnti => Box::pin(RProbedRightAntiJoiner::new(join_params, map, sender)),
-            Existence => Box::pin(RProbedExistenceJoiner::new(join_params, map, sender)),
-        },
-        JoinSide::Right => match join_params.join_type {
-            Inner => Box::pin(LProbedInnerJoiner::new(join_params, map, sender)),
-            Left => Box::pin(LProbedLeftJoiner::new(join_params, map, sender)),
-            Right => Box::pin(LProbedRightJoiner::new(join_params, map, sender)),
-            Full => Box::pin(LProbedFullOuterJoiner::new(join_params, map, sender)),
-            LeftSemi => Box::pin(LProbedLeftSemiJoiner::new(join_params, map, sender)),
-            LeftAnti => Box::pin(LProbedLeftAntiJoiner::new(join_params, map, sender)),
-            RightSemi => Box::pin(LProbedRightSemiJoiner::new(join_params, map, sender)),
-            RightAnti => Box::pin(LProbedRightAntiJoiner::new(join_params, map, sender)),
-            Existence => Box::pin(LProbedExistenceJoiner::new(join_params, map, sender)),
-        },
-    };
+    let mut joiner = create_joiner(broadcast_side, join_params, map, sender);
 
     while let Some(batch) = metrics
         .elapsed_compute()
@@ -381,6 +359,38 @@ async fn execute_join_with_map(
     Ok(())
 }
 
+pub(crate) fn create_joiner(
+    broadcast_side: JoinSide,
+    join_params: JoinParams,
+    map: Arc<JoinHashMap>,
+    sender: Arc<WrappedRecordBatchSender>,
+) -> Pin<Box<dyn Joiner + Send>> {
+    match broadcast_side {
+        JoinSide::Left => match join_params.join_type {
+            Inner => Box::pin(RProbedInnerJoiner::new(join_params, map, sender)),
+            Left => Box::pin(RProbedLeftJoiner::new(join_params, map, sender)),
+            Right => Box::pin(RProbedRightJoiner::new(join_params, map, sender)),
+            Full => Box::pin(RProbedFullOuterJoiner::new(join_params, map, sender)),
+            LeftSemi => Box::pin(RProbedLeftSemiJoiner::new(join_params, map, sender)),
+            LeftAnti => Box::pin(RProbedLeftAntiJoiner::new(join_params, map, sender)),
+            RightSemi => Box::pin(RProbedRightSemiJoiner::new(join_params, map, sender)),
+            RightAnti => Box::pin(RProbedRightAntiJoiner::new(join_params, map, sender)),
+            Existence => Box::pin(RProbedExistenceJoiner::new(join_params, map, sender)),
+        },
+        JoinSide::Right => match join_params.join_type {
+            Inner => Box::pin(LProbedInnerJoiner::new(join_params, map, sender)),
+            Left => Box::pin(LProbedLeftJoiner::new(join_params, map, sender)),
+            Right => Box::pin(LProbedRightJoiner::new(join_params, map, sender)),
+            Full => Box::pin(LProbedFullOuterJoiner::new(join_params, map, sender)),
+            LeftSemi => Box::pin(LProbedLeftSemiJoiner::new(join_params, map, sender)),
+            LeftAnti => Box::pin(LProbedLeftAntiJoiner::new(join_params, map, sender)),
+            RightSemi => Box::pin(LProbedRightSemiJoiner::new(join_params, map, sender)),
+            RightAnti => Box::pin(LProbedRightAntiJoiner::new(join_params, map, sender)),
+            Existence => Box::pin(LProbedExistenceJoiner::new(join_params, map, sender)),
+        },
+    }
+}
+
 async fn execute_join(
     partition: usize,
     left: SendableRecordBatchStream,
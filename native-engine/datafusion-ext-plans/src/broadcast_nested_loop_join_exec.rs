@@ -0,0 +1,360 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    fmt::{Debug, Formatter},
+    sync::{Arc, Weak},
+};
+
+use arrow::{compute::concat_batches, datatypes::SchemaRef};
+use async_trait::async_trait;
+use datafusion::{
+    common::{JoinSide, Result, Statistics},
+    execution::context::TaskContext,
+    physical_expr::EquivalenceProperties,
+    physical_plan::{
+        metrics::{BaselineMetrics, ExecutionPlanMetricsSet, MetricsSet},
+        stream::RecordBatchStreamAdapter,
+        DisplayAs, DisplayFormatType, ExecutionMode, ExecutionPlan, ExecutionPlanProperties,
+        PlanProperties, SendableRecordBatchStream,
+    },
+};
+use datafusion_ext_commons::{
+    array_size::ArraySize, batch_size, df_unimplemented_err,
+    streams::coalesce_stream::CoalesceInput,
+};
+use futures::{StreamExt, TryStreamExt};
+use once_cell::sync::OnceCell;
+
+use crate::{
+    common::{
+        column_pruning::ExecuteWithColumnPruning,
+        output::{TaskOutputter, WrappedRecordBatchSender},
+        timer_helper::{RegisterTimer, TimerHelper},
+    },
+    joins::{
+        join_utils::{JoinType, JoinType::*},
+        nlj::NestedLoopJoiner,
+        JoinFilter, JoinParams, JoinProjection,
+    },
+    memmgr::{MemConsumer, MemConsumerInfo, MemManager},
+};
+
+/// Nested loop join with one side broadcast and held in memory.
+///
+/// Joins which output unmatched/semi/anti/existence rows of the broadcast side
+/// need the matches of all probed partitions, they are only supported when the
+/// probed side has a single partition.
+#[derive(Debug)]
+pub struct BroadcastNestedLoopJoinExec {
+    left: Arc<dyn ExecutionPlan>,
+    right: Arc<dyn ExecutionPlan>,
+    join_type: JoinType,
+    broadcast_side: JoinSide,
+    join_filter: Option<JoinFilter>,
+    schema: SchemaRef,
+    metrics: ExecutionPlanMetricsSet,
+    props: OnceCell<PlanProperties>,
+}
+
+impl BroadcastNestedLoopJoinExec {
+    pub fn try_new(
+        schema: SchemaRef,
+        left: Arc<dyn ExecutionPlan>,
+        right: Arc<dyn ExecutionPlan>,
+        join_type: JoinType,
+        broadcast_side: JoinSide,
+        join_filter: Option<JoinFilter>,
+    ) -> Result<Self> {
+        // unmatched/semi/anti/existence rows of broadcast side depend on the matches
+        // of all probed partitions, which are not visible to each other
+        let (outputs_broadcast_rows, num_probed_partitions) = match broadcast_side {
+            JoinSide::Left => (
+                matches!(join_type, Left | Full | LeftSemi | LeftAnti | Existence),
+                right.output_partitioning().partition_count(),
+            ),
+            JoinSide::Right => (
+                matches!(join_type, Right | Full | RightSemi | RightAnti),
+                left.output_partitioning().partition_count(),
+            ),
+        };
+        if outputs_broadcast_rows && num_probed_partitions > 1 {
+            return df_unimplemented_err!(
+                "BroadcastNestedLoopJoinExec: {join_type:?} join with {broadcast_side:?} \
+                    broadcast side requires a single probed partition, \
+                    found {num_probed_partitions}"
+            );
+        }
+        Ok(Self {
+            left,
+            right,
+            join_type,
+            broadcast_side,
+            join_filter,
+            schema,
+            metrics: ExecutionPlanMetricsSet::new(),
+            props: OnceCell::new(),
+        })
+    }
+
+    fn create_join_params(&self, projection: &[usize]) -> Result<JoinParams> {
+        let left_schema = self.left.schema();
+        let right_schema = self.right.schema();
+        let projection = JoinProjection::try_new(
+            self.join_type,
+            &self.schema,
+            &left_schema,
+            &right_schema,
+            projection,
+        )?;
+
+        Ok(JoinParams {
+            join_type: self.join_type,
+            left_schema,
+            right_schema,
+            output_schema: self.schema(),
+            left_keys: vec![],
+            right_keys: vec![],
+            batch_size: batch_size(),
+            sort_options: vec![],
+            projection,
+            key_data_types: vec![],
+            band_condition: None,
+        })
+    }
+
+    fn execute_with_projection(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+        projection: Vec<usize>,
+    ) -> Result<SendableRecordBatchStream> {
+        let metrics = self.metrics.clone();
+        let baseline_metrics = Arc::new(BaselineMetrics::new(&metrics, partition));
+        let join_params = self.create_join_params(&projection)?;
+        let left = self.left.execute(partition, context.clone())?;
+        let right = self.right.execute(partition, context.clone())?;
+        let broadcast_side = self.broadcast_side;
+        let join_filter = self.join_filter.clone();
+
+        let baseline_metrics_cloned = baseline_metrics.clone();
+        let context_cloned = context.clone();
+        let output_stream = Box::pin(RecordBatchStreamAdapter::new(
+            join_params.projection.schema.clone(),
+            futures::stream::once(async move {
+                context_cloned.output_with_sender(
+                    "BroadcastNestedLoopJoin",
+                    join_params.projection.schema.clone(),
+                    move |sender| {
+                        sender.exclude_time(baseline_metrics_cloned.elapsed_compute());
+                        execute_join(
+                            partition,
+                            left,
+                            right,
+                            join_params,
+                            broadcast_side,
+                            join_filter,
+                            metrics,
+                            sender,
+                        )
+                    },
+                )
+            })
+            .try_flatten(),
+        ));
+        Ok(context.coalesce_with_default_batch_size(output_stream, &baseline_metrics)?)
+    }
+}
+
+impl ExecuteWithColumnPruning for BroadcastNestedLoopJoinExec {
+    fn execute_projected(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+        projection: &[usize],
+    ) -> Result<SendableRecordBatchStream> {
+        self.execute_with_projection(partition, context, projection.to_vec())
+    }
+}
+
+impl ExecutionPlan for BroadcastNestedLoopJoinExec {
+    fn name(&self) -> &str {
+        "BroadcastNestedLoopJoin"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        self.props.get_or_init(|| {
+            PlanProperties::new(
+                EquivalenceProperties::new(self.schema()),
+                match self.broadcast_side {
+                    JoinSide::Left => self.right.output_partitioning().clone(),
+                    JoinSide::Right => self.left.output_partitioning().clone(),
+                },
+                ExecutionMode::Bounded,
+            )
+        })
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![&self.left, &self.right]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(Self::try_new(
+            self.schema.clone(),
+            children[0].clone(),
+            children[1].clone(),
+            self.join_type,
+            self.broadcast_side,
+            self.join_filter.clone(),
+        )?))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let projection = (0..self.schema.fields().len()).collect();
+        self.execute_with_projection(partition, context, projection)
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+
+    fn statistics(&self) -> Result<Statistics> {
+        unimplemented!()
+    }
+}
+
+impl DisplayAs for BroadcastNestedLoopJoinExec {
+    fn fmt_as(&self, _t: DisplayFormatType, f: &mut Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "BroadcastNestedLoopJoin: join_type={:?}, broadcast_side={:?}, filter={:?}",
+            self.join_type,
+            self.broadcast_side,
+            self.join_filter.as_ref().map(|filter| &filter.expression),
+        )
+    }
+}
+
+async fn execute_join(
+    partition: usize,
+    left: SendableRecordBatchStream,
+    right: SendableRecordBatchStream,
+    join_params: JoinParams,
+    broadcast_side: JoinSide,
+    join_filter: Option<JoinFilter>,
+    metrics: ExecutionPlanMetricsSet,
+    sender: Arc<WrappedRecordBatchSender>,
+) -> Result<()> {
+    let baseline_metrics = Arc::new(BaselineMetrics::new(&metrics, partition));
+    let build_time = metrics.register_timer("build_broadcast_side_time", partition);
+    let (mut probed_input, mut broadcast_input) = match broadcast_side {
+        JoinSide::Left => (right, left),
+        JoinSide::Right => (left, right),
+    };
+
+    // the whole broadcast side is collected into one in-memory batch, which is
+    // accounted as unspillable memory until the join is finished
+    let broadcast_mem = Arc::new(BroadcastSideMemConsumer {
+        name: format!("BroadcastNestedLoopJoin[partition={partition}]"),
+        mem_consumer_info: None,
+    });
+    MemManager::register_consumer(broadcast_mem.clone(), false);
+
+    let broadcast_schema = broadcast_input.schema();
+    let mut broadcast_batches = vec![];
+    let mut broadcast_mem_used = 0;
+    while let Some(batch) = broadcast_input.next().await.transpose()? {
+        broadcast_mem_used += batch.get_array_mem_size();
+        broadcast_mem.update_mem_used(broadcast_mem_used).await?;
+        broadcast_batches.push(batch);
+    }
+    let broadcast_batch =
+        build_time.with_timer(|| concat_batches(&broadcast_schema, &broadcast_batches))?;
+    drop(broadcast_batches);
+    broadcast_mem
+        .update_mem_used(broadcast_batch.get_array_mem_size())
+        .await?;
+    baseline_metrics
+        .elapsed_compute()
+        .add_duration(build_time.duration());
+
+    let _timer = baseline_metrics.elapsed_compute().timer();
+    let mut joiner = NestedLoopJoiner::new(
+        join_params,
+        broadcast_side,
+        broadcast_batch,
+        join_filter,
+        sender,
+    );
+    while let Some(batch) = baseline_metrics
+        .elapsed_compute()
+        .exclude_timer_async(probed_input.next())
+        .await
+        .transpose()?
+    {
+        joiner.join(batch).await?;
+        if joiner.can_early_stop() {
+            break;
+        }
+    }
+    joiner.finish().await?;
+    baseline_metrics.record_output(joiner.num_output_rows());
+    Ok(())
+}
+
+/// Accounts the materialized broadcast side, which is never spilled.
+struct BroadcastSideMemConsumer {
+    name: String,
+    mem_consumer_info: Option<Weak<MemConsumerInfo>>,
+}
+
+#[async_trait]
+impl MemConsumer for BroadcastSideMemConsumer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn set_consumer_info(&mut self, consumer_info: Weak<MemConsumerInfo>) {
+        self.mem_consumer_info = Some(consumer_info);
+    }
+
+    fn get_consumer_info(&self) -> &Weak<MemConsumerInfo> {
+        self.mem_consumer_info
+            .as_ref()
+            .expect("consumer info not set")
+    }
+}
+
+impl Drop for BroadcastSideMemConsumer {
+    fn drop(&mut self) {
+        MemManager::deregister_consumer(self);
+    }
+}
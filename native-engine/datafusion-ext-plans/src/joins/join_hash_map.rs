@@ -27,6 +27,7 @@ use arrow::{
 };
 use datafusion::{common::Result, physical_expr::PhysicalExprRef};
 use datafusion_ext_commons::{
+    array_size::ArraySize,
     io::{read_len, read_raw_slice, write_len, write_raw_slice},
     prefetch_read_data,
     rdxsort::RadixSortIterExt,
@@ -180,6 +181,10 @@ impl Table {
         })
     }
 
+    fn mem_size(&self) -> usize {
+        self.map.len() * size_of::<MapValueGroup>() + self.mapped_indices.len() * size_of::<u32>()
+    }
+
     pub fn load_from_raw_bytes(raw_bytes: &[u8]) -> Result<Self> {
         let mut cursor = Cursor::new(raw_bytes);
 
@@ -387,6 +392,11 @@ impl JoinHashMap {
         &self.key_columns
     }
 
+    /// memory used by data batch and hash table
+    pub fn mem_size(&self) -> usize {
+        self.data_batch.get_array_mem_size() + self.table.mem_size()
+    }
+
     pub fn is_all_nulls(&self) -> bool {
         self.table.num_valid_items == 0
     }
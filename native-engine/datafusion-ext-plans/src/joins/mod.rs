@@ -19,7 +19,10 @@ use arrow::{
     compute::SortOptions,
     datatypes::{DataType, SchemaRef},
 };
-use datafusion::{common::Result, physical_expr::PhysicalExprRef};
+use datafusion::{
+    common::{JoinSide, Result},
+    physical_expr::PhysicalExprRef,
+};
 
 use crate::joins::{join_utils::JoinType, stream_cursor::StreamCursor};
 
@@ -29,6 +32,8 @@ pub mod stream_cursor;
 
 // join implementations
 pub mod bhj;
+pub mod nlj;
+pub mod shj;
 pub mod smj;
 mod test;
 
@@ -44,6 +49,30 @@ pub struct JoinParams {
     pub sort_options: Vec<SortOptions>,
     pub projection: JoinProjection,
     pub batch_size: usize,
+    pub band_condition: Option<JoinBandCondition>,
+}
+
+/// A band (range) condition evaluated in addition to equal join keys:
+/// `lower_expr <(=) point_expr <(=) upper_expr`, where `point_expr` is
+/// evaluated on `point_side` and both bounds are evaluated on the other side.
+#[derive(Debug, Clone)]
+pub struct JoinBandCondition {
+    pub point_side: JoinSide,
+    pub point_expr: PhysicalExprRef,
+    pub lower_expr: PhysicalExprRef,
+    pub upper_expr: PhysicalExprRef,
+    pub lower_inclusive: bool,
+    pub upper_inclusive: bool,
+    pub data_type: DataType,
+}
+
+/// A non-equal join condition, evaluated on an intermediate batch whose columns
+/// are taken from both sides as specified by `column_indices`.
+#[derive(Debug, Clone)]
+pub struct JoinFilter {
+    pub expression: PhysicalExprRef,
+    pub column_indices: Vec<(JoinSide, usize)>,
+    pub schema: SchemaRef,
 }
 
 #[derive(Debug, Clone)]
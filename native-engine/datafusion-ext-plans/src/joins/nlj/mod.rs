@@ -0,0 +1,307 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use arrow::{
+    array::{new_null_array, ArrayRef, BooleanArray, RecordBatch, RecordBatchOptions},
+    compute::filter_record_batch,
+    datatypes::{SchemaRef, UInt32Type},
+};
+use datafusion::common::{JoinSide, Result};
+use datafusion_ext_commons::df_execution_err;
+
+use crate::{
+    common::{batch_selection::take_cols, output::WrappedRecordBatchSender},
+    joins::{join_utils::JoinType::*, JoinFilter, JoinParams},
+};
+
+/// Joins each probed batch with the whole in-memory broadcast side.
+///
+/// Candidate pairs are generated chunk by chunk, each chunk contains about
+/// `batch_size` pairs, and filtered with the join filter if present. Matched
+/// flags of broadcast rows are kept until the probed side is exhausted, so that
+/// unmatched/semi/anti rows of broadcast side can be output in `finish()`.
+pub struct NestedLoopJoiner {
+    join_params: JoinParams,
+    broadcast_side: JoinSide,
+    broadcast_batch: RecordBatch,
+    broadcast_matched: Vec<bool>,
+    join_filter: Option<JoinFilter>,
+    output_sender: Arc<WrappedRecordBatchSender>,
+    output_rows: usize,
+}
+
+impl NestedLoopJoiner {
+    pub fn new(
+        join_params: JoinParams,
+        broadcast_side: JoinSide,
+        broadcast_batch: RecordBatch,
+        join_filter: Option<JoinFilter>,
+        output_sender: Arc<WrappedRecordBatchSender>,
+    ) -> Self {
+        let broadcast_matched = vec![false; broadcast_batch.num_rows()];
+        Self {
+            join_params,
+            broadcast_side,
+            broadcast_batch,
+            broadcast_matched,
+            join_filter,
+            output_sender,
+            output_rows: 0,
+        }
+    }
+
+    pub async fn join(&mut self, probed_batch: RecordBatch) -> Result<()> {
+        let num_probed_rows = probed_batch.num_rows();
+        let num_broadcast_rows = self.broadcast_batch.num_rows();
+        let mut probed_matched = vec![false; num_probed_rows];
+
+        if num_broadcast_rows > 0 {
+            let chunk_size = (self.join_params.batch_size / num_broadcast_rows).max(1);
+            let mut chunk_start = 0;
+            while chunk_start < num_probed_rows {
+                let chunk_end = (chunk_start + chunk_size).min(num_probed_rows);
+                let (probed_indices, broadcast_indices) =
+                    self.matched_pairs(&probed_batch, chunk_start..chunk_end)?;
+                chunk_start = chunk_end;
+
+                for &i in &probed_indices {
+                    probed_matched[i as usize] = true;
+                }
+                for &i in &broadcast_indices {
+                    self.broadcast_matched[i as usize] = true;
+                }
+                let num_pairs = probed_indices.len();
+                if matches!(self.join_params.join_type, Inner | Left | Right | Full)
+                    && num_pairs > 0
+                {
+                    let probed_cols = take_cols::<UInt32Type>(
+                        &self.project(self.probed_side(), probed_batch.columns()),
+                        probed_indices,
+                    )?;
+                    let broadcast_cols = take_cols::<UInt32Type>(
+                        &self.project(self.broadcast_side, self.broadcast_batch.columns()),
+                        broadcast_indices,
+                    )?;
+                    let output_cols = match self.broadcast_side {
+                        JoinSide::Left => [broadcast_cols, probed_cols].concat(),
+                        JoinSide::Right => [probed_cols, broadcast_cols].concat(),
+                    };
+                    self.output(output_cols, num_pairs).await?;
+                }
+            }
+        }
+        self.output_side_rows(self.probed_side(), &probed_batch, probed_matched)
+            .await
+    }
+
+    pub async fn finish(&mut self) -> Result<()> {
+        let broadcast_batch = self.broadcast_batch.clone();
+        let broadcast_matched = std::mem::take(&mut self.broadcast_matched);
+        self.output_side_rows(self.broadcast_side, &broadcast_batch, broadcast_matched)
+            .await
+    }
+
+    pub fn can_early_stop(&self) -> bool {
+        // the probed side is no longer needed if broadcast side is empty and no
+        // probed rows are output for unmatched rows
+        self.broadcast_batch.num_rows() == 0
+            && match self.probed_side() {
+                JoinSide::Left => matches!(self.join_params.join_type, Inner | Right | LeftSemi),
+                JoinSide::Right => matches!(self.join_params.join_type, Inner | Left | RightSemi),
+            }
+    }
+
+    pub fn num_output_rows(&self) -> usize {
+        self.output_rows
+    }
+
+    fn probed_side(&self) -> JoinSide {
+        match self.broadcast_side {
+            JoinSide::Left => JoinSide::Right,
+            JoinSide::Right => JoinSide::Left,
+        }
+    }
+
+    fn project(&self, side: JoinSide, cols: &[ArrayRef]) -> Vec<ArrayRef> {
+        match side {
+            JoinSide::Left => self.join_params.projection.project_left(cols),
+            JoinSide::Right => self.join_params.projection.project_right(cols),
+        }
+    }
+
+    fn projected_schema(&self, side: JoinSide) -> SchemaRef {
+        match side {
+            JoinSide::Left => self.join_params.projection.left_schema.clone(),
+            JoinSide::Right => self.join_params.projection.right_schema.clone(),
+        }
+    }
+
+    /// generates pairs of the specified probed rows and all broadcast rows,
+    /// returns indices of pairs that satisfy the join filter
+    fn matched_pairs(
+        &self,
+        probed_batch: &RecordBatch,
+        probed_rows: std::ops::Range<usize>,
+    ) -> Result<(Vec<u32>, Vec<u32>)> {
+        let num_broadcast_rows = self.broadcast_batch.num_rows() as u32;
+        let mut probed_indices =
+            Vec::with_capacity(probed_rows.len() * num_broadcast_rows as usize);
+        let mut broadcast_indices = Vec::with_capacity(probed_indices.capacity());
+        for probed_idx in probed_rows {
+            for broadcast_idx in 0..num_broadcast_rows {
+                probed_indices.push(probed_idx as u32);
+                broadcast_indices.push(broadcast_idx);
+            }
+        }
+
+        let Some(join_filter) = &self.join_filter else {
+            return Ok((probed_indices, broadcast_indices));
+        };
+        let num_pairs = probed_indices.len();
+        let probed_side = self.probed_side();
+        let filter_cols = join_filter
+            .column_indices
+            .iter()
+            .map(|&(side, idx)| {
+                if side == probed_side {
+                    Ok(take_cols::<UInt32Type>(
+                        &[probed_batch.column(idx).clone()],
+                        probed_indices.clone(),
+                    )?[0]
+                        .clone())
+                } else {
+                    Ok(take_cols::<UInt32Type>(
+                        &[self.broadcast_batch.column(idx).clone()],
+                        broadcast_indices.clone(),
+                    )?[0]
+                        .clone())
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let filter_batch = RecordBatch::try_new_with_options(
+            join_filter.schema.clone(),
+            filter_cols,
+            &RecordBatchOptions::new().with_row_count(Some(num_pairs)),
+        )?;
+        let filtered = join_filter
+            .expression
+            .evaluate(&filter_batch)?
+            .into_array(num_pairs)?;
+        let Some(filtered) = filtered.as_any().downcast_ref::<BooleanArray>() else {
+            return df_execution_err!("join filter must return boolean values");
+        };
+
+        // null values are treated as false
+        let (matched_probed_indices, matched_broadcast_indices) = probed_indices
+            .into_iter()
+            .zip(broadcast_indices)
+            .enumerate()
+            .filter(|&(i, _)| filtered.is_valid(i) && filtered.value(i))
+            .map(|(_, pair)| pair)
+            .unzip();
+        Ok((matched_probed_indices, matched_broadcast_indices))
+    }
+
+    /// outputs rows of one side which only depend on whether they are matched:
+    /// unmatched rows of outer joins, semi/anti joins and existence joins
+    async fn output_side_rows(
+        &mut self,
+        side: JoinSide,
+        batch: &RecordBatch,
+        matched: Vec<bool>,
+    ) -> Result<()> {
+        let join_type = self.join_params.join_type;
+        let (is_outer, is_semi, is_anti, is_existence) = match side {
+            JoinSide::Left => (
+                matches!(join_type, Left | Full),
+                join_type == LeftSemi,
+                join_type == LeftAnti,
+                join_type == Existence,
+            ),
+            JoinSide::Right => (
+                matches!(join_type, Right | Full),
+                join_type == RightSemi,
+                join_type == RightAnti,
+                false,
+            ),
+        };
+        let side_cols = self.project(side, batch.columns());
+        let side_batch = RecordBatch::try_new_with_options(
+            self.projected_schema(side),
+            side_cols,
+            &RecordBatchOptions::new().with_row_count(Some(batch.num_rows())),
+        )?;
+
+        if is_existence {
+            let exists_col: ArrayRef = Arc::new(BooleanArray::from(matched));
+            let output_cols = [side_batch.columns().to_vec(), vec![exists_col]].concat();
+            return self.output(output_cols, batch.num_rows()).await;
+        }
+
+        let selected = if is_outer || is_anti {
+            BooleanArray::from_iter(matched.iter().map(|&m| Some(!m)))
+        } else if is_semi {
+            BooleanArray::from(matched)
+        } else {
+            return Ok(());
+        };
+        let selected_batch = filter_record_batch(&side_batch, &selected)?;
+        let num_rows = selected_batch.num_rows();
+        if num_rows == 0 {
+            return Ok(());
+        }
+
+        if is_outer {
+            // fill columns of the other side with nulls
+            let other_side = match side {
+                JoinSide::Left => JoinSide::Right,
+                JoinSide::Right => JoinSide::Left,
+            };
+            let null_cols = self
+                .projected_schema(other_side)
+                .fields()
+                .iter()
+                .map(|field| new_null_array(field.data_type(), num_rows))
+                .collect::<Vec<_>>();
+            let output_cols = match side {
+                JoinSide::Left => [selected_batch.columns().to_vec(), null_cols].concat(),
+                JoinSide::Right => [null_cols, selected_batch.columns().to_vec()].concat(),
+            };
+            return self.output(output_cols, num_rows).await;
+        }
+        self.output(selected_batch.columns().to_vec(), num_rows)
+            .await
+    }
+
+    async fn output(&mut self, output_cols: Vec<ArrayRef>, num_rows: usize) -> Result<()> {
+        let output_schema = if self.join_params.join_type == Existence {
+            self.join_params.output_schema.clone()
+        } else {
+            self.join_params.projection.schema.clone()
+        };
+        let output_batch = RecordBatch::try_new_with_options(
+            output_schema,
+            output_cols,
+            &RecordBatchOptions::new().with_row_count(Some(num_rows)),
+        )?;
+
+        if output_batch.num_rows() > 0 {
+            self.output_rows += output_batch.num_rows();
+            self.output_sender.send(Ok(output_batch)).await;
+        }
+        Ok(())
+    }
+}
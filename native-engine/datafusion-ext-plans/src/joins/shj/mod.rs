@@ -0,0 +1,324 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    hash::{BuildHasher, Hasher},
+    sync::Weak,
+};
+
+use arrow::{
+    array::{ArrayRef, RecordBatch, RecordBatchOptions},
+    datatypes::{SchemaRef, UInt32Type},
+};
+use async_trait::async_trait;
+use datafusion::{common::Result, physical_expr::PhysicalExprRef};
+use datafusion_ext_commons::{
+    array_size::ArraySize,
+    df_execution_err, downcast_any,
+    io::{read_len, read_one_batch, write_len, write_one_batch},
+    spark_hash::create_hashes,
+};
+use parking_lot::Mutex;
+
+use crate::{
+    common::batch_selection::take_cols,
+    memmgr::{
+        metrics::SpillMetrics,
+        spill::{try_new_spill, Spill, SpillCompressedReader},
+        MemConsumer, MemConsumerInfo, MemManager,
+    },
+};
+
+// reserve memory for each spill
+// estimated size: bufread=64KB + lz4dec.src=64KB + lz4dec.dest=64KB
+pub const SPILL_OFFHEAP_MEM_COST: usize = 200000;
+
+// number of buckets used in spilling, both sides of a spilled join are
+// partitioned into these buckets and joined bucket by bucket
+pub const NUM_SPILL_BUCKETS: usize = 32;
+
+// max number of partitioning levels, buckets still too large for memory are
+// re-partitioned with another hash seed until this level is reached
+pub const MAX_SPILL_LEVELS: usize = 3;
+
+/// Collects batches of one join side in memory, spilling them partitioned by
+/// join keys when memory is insufficient. Buffers of different levels use
+/// different hash seeds for partitioning.
+pub struct BucketedBuffer {
+    name: String,
+    mem_consumer_info: Option<Weak<MemConsumerInfo>>,
+    key_exprs: Vec<PhysicalExprRef>,
+    level: usize,
+    staging: Mutex<StagingBatches>,
+    spills: Mutex<Vec<Box<dyn Spill>>>,
+    spill_metrics: SpillMetrics,
+}
+
+#[derive(Default)]
+struct StagingBatches {
+    batches: Vec<RecordBatch>,
+    mem_used: usize,
+}
+
+impl BucketedBuffer {
+    pub fn new(
+        name: String,
+        key_exprs: Vec<PhysicalExprRef>,
+        level: usize,
+        spill_metrics: SpillMetrics,
+    ) -> Self {
+        Self {
+            name,
+            mem_consumer_info: None,
+            key_exprs,
+            level,
+            staging: Mutex::default(),
+            spills: Mutex::default(),
+            spill_metrics,
+        }
+    }
+
+    pub async fn insert_batch(&self, batch: RecordBatch) -> Result<()> {
+        if batch.num_rows() == 0 {
+            return Ok(());
+        }
+        let mem_used = {
+            let mut staging = self.staging.lock();
+            staging.mem_used += batch.get_array_mem_size();
+            staging.batches.push(batch);
+            staging.mem_used
+        };
+        self.update_mem_used(mem_used).await
+    }
+
+    pub fn is_spilled(&self) -> bool {
+        !self.spills.lock().is_empty()
+    }
+
+    /// takes all in-memory batches, only used when nothing has been spilled
+    pub fn take_in_mem_batches(&self) -> Vec<RecordBatch> {
+        self.set_spillable(false);
+        std::mem::take(&mut self.staging.lock().batches)
+    }
+
+    /// moves remaining in-memory batches into a spill and returns all spills
+    pub async fn into_spills(&self) -> Result<Vec<Box<dyn Spill>>> {
+        self.set_spillable(false);
+        let staging = std::mem::take(&mut *self.staging.lock());
+        let mut spills = std::mem::take(&mut *self.spills.lock());
+        let mut in_mem_spill_size = 0;
+
+        if !staging.batches.is_empty() {
+            if self.mem_used_percent() < 0.25 {
+                // if in-mem data is small, try to spill it into native raw bytes
+                let mut spill: Box<dyn Spill> = Box::new(vec![]);
+                write_bucketed_spill(staging.batches, &self.key_exprs, self.level, &mut spill)?;
+
+                let in_mem_spill = downcast_any!(spill, mut Vec<u8>)?;
+                in_mem_spill.shrink_to_fit();
+                in_mem_spill_size = in_mem_spill.len();
+                spills.push(spill);
+            } else {
+                let mut spill = try_new_spill(&self.spill_metrics)?;
+                write_bucketed_spill(staging.batches, &self.key_exprs, self.level, &mut spill)?;
+                spills.push(spill);
+            }
+        }
+        self.update_mem_used(in_mem_spill_size + spills.len() * SPILL_OFFHEAP_MEM_COST)
+            .await?;
+        Ok(spills)
+    }
+}
+
+#[async_trait]
+impl MemConsumer for BucketedBuffer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn set_consumer_info(&mut self, consumer_info: Weak<MemConsumerInfo>) {
+        self.mem_consumer_info = Some(consumer_info);
+    }
+
+    fn get_consumer_info(&self) -> &Weak<MemConsumerInfo> {
+        self.mem_consumer_info
+            .as_ref()
+            .expect("consumer info not set")
+    }
+
+    async fn spill(&self) -> Result<()> {
+        let staging = std::mem::take(&mut *self.staging.lock());
+        if !staging.batches.is_empty() {
+            let mut spill = try_new_spill(&self.spill_metrics)?;
+            write_bucketed_spill(staging.batches, &self.key_exprs, self.level, &mut spill)?;
+            self.spills.lock().push(spill);
+        }
+        self.update_mem_used(0).await?;
+        Ok(())
+    }
+}
+
+impl Drop for BucketedBuffer {
+    fn drop(&mut self) {
+        MemManager::deregister_consumer(self);
+    }
+}
+
+/// Reads a spill written by [`BucketedBuffer`] bucket by bucket.
+pub struct BucketedSpillReader<'a> {
+    input: SpillCompressedReader<'a>,
+    schema: SchemaRef,
+    num_remaining_batches: usize,
+}
+
+impl<'a> BucketedSpillReader<'a> {
+    pub fn new(spill: &'a Box<dyn Spill>, schema: SchemaRef) -> Self {
+        Self {
+            input: spill.get_compressed_reader(),
+            schema,
+            num_remaining_batches: 0,
+        }
+    }
+
+    /// skips unread batches of current bucket and moves to the next bucket
+    pub fn next_bucket(&mut self) -> Result<()> {
+        while self.next_batch()?.is_some() {}
+        self.num_remaining_batches = read_len(&mut self.input)?;
+        Ok(())
+    }
+
+    pub fn next_batch(&mut self) -> Result<Option<RecordBatch>> {
+        if self.num_remaining_batches == 0 {
+            return Ok(None);
+        }
+        self.num_remaining_batches -= 1;
+
+        let Some((num_rows, cols)) = read_one_batch(&mut self.input, &self.schema)? else {
+            return df_execution_err!("unexpected end of bucketed spill");
+        };
+        Ok(Some(RecordBatch::try_new_with_options(
+            self.schema.clone(),
+            cols,
+            &RecordBatchOptions::new().with_row_count(Some(num_rows)),
+        )?))
+    }
+}
+
+fn write_bucketed_spill(
+    batches: Vec<RecordBatch>,
+    key_exprs: &[PhysicalExprRef],
+    level: usize,
+    spill: &mut Box<dyn Spill>,
+) -> Result<()> {
+    let mut bucketed_batches = batches
+        .into_iter()
+        .map(|batch| {
+            let mut bucketed_indices = vec![vec![]; NUM_SPILL_BUCKETS];
+            for (row_idx, bucket_id) in bucket_ids(&batch, key_exprs, level)?
+                .into_iter()
+                .enumerate()
+            {
+                bucketed_indices[bucket_id].push(row_idx as u32);
+            }
+            Ok((batch, bucketed_indices))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // write batches in bucket order, each bucket starts with its number of batches
+    let mut writer = spill.get_compressed_writer();
+    for bucket_id in 0..NUM_SPILL_BUCKETS {
+        let num_bucket_batches = bucketed_batches
+            .iter()
+            .filter(|(_, bucketed_indices)| !bucketed_indices[bucket_id].is_empty())
+            .count();
+        write_len(num_bucket_batches, &mut writer)?;
+
+        for (batch, bucketed_indices) in &mut bucketed_batches {
+            let indices = std::mem::take(&mut bucketed_indices[bucket_id]);
+            if !indices.is_empty() {
+                let num_rows = indices.len();
+                let cols = take_cols::<UInt32Type>(batch.columns(), indices)?;
+                write_one_batch(num_rows, &cols, &mut writer)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn bucket_ids(
+    batch: &RecordBatch,
+    key_exprs: &[PhysicalExprRef],
+    level: usize,
+) -> Result<Vec<usize>> {
+    // use a seed different from join hash map, so that rows in one bucket are
+    // still well distributed in the hash map. each level uses its own seed so
+    // that rows of one bucket are spread when the bucket is re-partitioned
+    const BUCKET_HASH_RANDOM_SEED: u32 = 0x7A3B91C5;
+    let seed = BUCKET_HASH_RANDOM_SEED.wrapping_add((level as u32).wrapping_mul(0x9E3779B9));
+    let hasher_state = foldhash::fast::FixedState::with_seed(seed as u64);
+
+    let num_rows = batch.num_rows();
+    let key_columns: Vec<ArrayRef> = key_exprs
+        .iter()
+        .map(|expr| Ok(expr.evaluate(batch)?.into_array(num_rows)?))
+        .collect::<Result<_>>()?;
+    let hashes = create_hashes(num_rows, &key_columns, seed, |v, h| {
+        let mut hasher = hasher_state.build_hasher();
+        hasher.write_u32(h);
+        hasher.write(v);
+        hasher.finish() as u32
+    });
+    Ok(hashes
+        .into_iter()
+        .map(|h| h as usize % NUM_SPILL_BUCKETS)
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{Int32Array, RecordBatch},
+        datatypes::{DataType, Field, Schema},
+    };
+    use datafusion::{
+        common::Result,
+        physical_expr::{expressions::Column, PhysicalExprRef},
+    };
+
+    use crate::joins::shj::{bucket_ids, NUM_SPILL_BUCKETS};
+
+    #[test]
+    fn test_bucket_ids_of_levels() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![Field::new("k", DataType::Int32, false)]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![Arc::new(Int32Array::from_iter_values(0..100000))],
+        )?;
+        let key_exprs: Vec<PhysicalExprRef> = vec![Arc::new(Column::new("k", 0))];
+
+        // rows of one bucket are spread over all buckets of the next level
+        let level0 = bucket_ids(&batch, &key_exprs, 0)?;
+        let level1 = bucket_ids(&batch, &key_exprs, 1)?;
+        let mut sub_bucket_counts = vec![0; NUM_SPILL_BUCKETS];
+        for (&bucket0, &bucket1) in level0.iter().zip(&level1) {
+            if bucket0 == 0 {
+                sub_bucket_counts[bucket1] += 1;
+            }
+        }
+        assert!(sub_bucket_counts.iter().all(|&count| count > 0));
+        Ok(())
+    }
+}
@@ -0,0 +1,506 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{cmp::Ordering, pin::Pin, sync::Arc};
+
+use arrow::array::{ArrayRef, BooleanArray, RecordBatch, RecordBatchOptions};
+use async_trait::async_trait;
+use datafusion::common::{JoinSide, Result};
+use datafusion_ext_commons::suggested_output_batch_mem_size;
+
+use crate::{
+    common::{batch_selection::interleave_batches, output::WrappedRecordBatchSender},
+    compare_cursor, cur_forward,
+    joins::{join_utils::JoinType::*, stream_cursor::StreamCursor, Idx, JoinParams, StreamCursors},
+    sort_merge_join_exec::Joiner,
+};
+
+/// Joiner for sort merge join with an additional band condition.
+///
+/// Both sides are merged by the equal join keys like other joiners. Inside a
+/// group of equal keys, points and ranges are sorted by their band values and
+/// swept once, only ranges that may still cover the following points are kept
+/// active, so the cartesian product of the group is never built.
+///
+/// Without equal join keys, the inputs must be sorted by band values (points by
+/// themselves and ranges by their lower bounds), and both sides are swept as
+/// streams instead of being collected into a single group.
+pub struct BandJoiner {
+    join_params: JoinParams,
+    output_sender: Arc<WrappedRecordBatchSender>,
+    point_side: JoinSide,
+    lower_inclusive: bool,
+    upper_inclusive: bool,
+    lindices: Vec<Idx>,
+    rindices: Vec<Idx>,
+    exists: Vec<bool>,
+    // pending indices are not pushed in input order (rows of a group are sorted by
+    // band values), so the smallest pending indices are tracked separately
+    min_lidx: Option<Idx>,
+    min_ridx: Option<Idx>,
+    output_rows: usize,
+}
+
+impl BandJoiner {
+    pub fn new(join_params: JoinParams, output_sender: Arc<WrappedRecordBatchSender>) -> Self {
+        let band = join_params
+            .band_condition
+            .clone()
+            .expect("BandJoiner: missing band condition");
+        Self {
+            join_params,
+            output_sender,
+            point_side: band.point_side,
+            lower_inclusive: band.lower_inclusive,
+            upper_inclusive: band.upper_inclusive,
+            lindices: vec![],
+            rindices: vec![],
+            exists: vec![],
+            min_lidx: None,
+            min_ridx: None,
+            output_rows: 0,
+        }
+    }
+
+    fn push_lidx(&mut self, lidx: Idx) {
+        self.lindices.push(lidx);
+        self.min_lidx = Some(self.min_lidx.map_or(lidx, |min_lidx| min_lidx.min(lidx)));
+    }
+
+    fn push_ridx(&mut self, ridx: Idx) {
+        self.rindices.push(ridx);
+        self.min_ridx = Some(self.min_ridx.map_or(ridx, |min_ridx| min_ridx.min(ridx)));
+    }
+
+    fn output_pair(&mut self, lidx: Idx, ridx: Idx) {
+        if matches!(self.join_params.join_type, Inner | Left | Right | Full) {
+            self.push_lidx(lidx);
+            self.push_ridx(ridx);
+        }
+    }
+
+    fn output_left(&mut self, lidx: Idx, matched: bool) {
+        match self.join_params.join_type {
+            Left | Full if !matched => {
+                self.push_lidx(lidx);
+                self.rindices.push(Idx::default()); // null row, always reserved
+            }
+            LeftSemi if matched => self.push_lidx(lidx),
+            LeftAnti if !matched => self.push_lidx(lidx),
+            Existence => {
+                self.push_lidx(lidx);
+                self.exists.push(matched);
+            }
+            _ => {}
+        }
+    }
+
+    fn output_right(&mut self, ridx: Idx, matched: bool) {
+        match self.join_params.join_type {
+            Right | Full if !matched => {
+                self.lindices.push(Idx::default()); // null row, always reserved
+                self.push_ridx(ridx);
+            }
+            RightSemi if matched => self.push_ridx(ridx),
+            RightAnti if !matched => self.push_ridx(ridx),
+            _ => {}
+        }
+    }
+
+    fn output_point(&mut self, point: Idx, matched: bool) {
+        match self.point_side {
+            JoinSide::Left => self.output_left(point, matched),
+            JoinSide::Right => self.output_right(point, matched),
+        }
+    }
+
+    fn output_range(&mut self, range: Idx, matched: bool) {
+        match self.point_side {
+            JoinSide::Left => self.output_right(range, matched),
+            JoinSide::Right => self.output_left(range, matched),
+        }
+    }
+
+    fn output_point_range(&mut self, point: Idx, range: Idx) {
+        match self.point_side {
+            JoinSide::Left => self.output_pair(point, range),
+            JoinSide::Right => self.output_pair(range, point),
+        }
+    }
+
+    /// rows of pending outputs and active ranges are reserved, other rows
+    /// before current indices can be released by cursors
+    fn update_min_reserved_idx(&self, curs: &mut StreamCursors, min_active_range: Option<Idx>) {
+        let (min_lidx, min_ridx) = match self.point_side {
+            JoinSide::Left => (
+                self.min_lidx,
+                self.min_ridx.into_iter().chain(min_active_range).min(),
+            ),
+            JoinSide::Right => (
+                self.min_lidx.into_iter().chain(min_active_range).min(),
+                self.min_ridx,
+            ),
+        };
+        curs.0
+            .set_min_reserved_idx(min_lidx.unwrap_or(curs.0.cur_idx));
+        curs.1
+            .set_min_reserved_idx(min_ridx.unwrap_or(curs.1.cur_idx));
+    }
+
+    fn should_flush(&self, curs: &StreamCursors) -> bool {
+        if self.lindices.len().max(self.rindices.len()) >= self.join_params.batch_size {
+            return true;
+        }
+
+        if curs.0.num_buffered_batches() + curs.1.num_buffered_batches() >= 6
+            && curs.0.mem_size() + curs.1.mem_size() > suggested_output_batch_mem_size()
+        {
+            if let Some(min_lidx) = self.min_lidx {
+                if min_lidx.0 < curs.0.cur_idx.0 {
+                    return true;
+                }
+            }
+            if let Some(min_ridx) = self.min_ridx {
+                if min_ridx.0 < curs.1.cur_idx.0 {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    async fn flush(mut self: Pin<&mut Self>, curs: &mut StreamCursors) -> Result<()> {
+        let lindices = std::mem::take(&mut self.lindices);
+        let rindices = std::mem::take(&mut self.rindices);
+        let exists = std::mem::take(&mut self.exists);
+        self.min_lidx = None;
+        self.min_ridx = None;
+        let num_rows = lindices.len().max(rindices.len());
+
+        let lcols = || {
+            interleave_batches(
+                curs.0.projected_batch_schema.clone(),
+                &curs.0.projected_batches,
+                &lindices,
+            )
+        };
+        let rcols = || {
+            interleave_batches(
+                curs.1.projected_batch_schema.clone(),
+                &curs.1.projected_batches,
+                &rindices,
+            )
+        };
+        let (output_schema, output_cols) = match self.join_params.join_type {
+            Inner | Left | Right | Full => (
+                self.join_params.projection.schema.clone(),
+                [lcols()?.columns(), rcols()?.columns()].concat(),
+            ),
+            LeftSemi | LeftAnti => (
+                self.join_params.projection.schema.clone(),
+                lcols()?.columns().to_vec(),
+            ),
+            RightSemi | RightAnti => (
+                self.join_params.projection.schema.clone(),
+                rcols()?.columns().to_vec(),
+            ),
+            Existence => {
+                let exists_col: ArrayRef = Arc::new(BooleanArray::from(exists));
+                (
+                    self.join_params.output_schema.clone(),
+                    [lcols()?.columns().to_vec(), vec![exists_col]].concat(),
+                )
+            }
+        };
+        let output_batch = RecordBatch::try_new_with_options(
+            output_schema,
+            output_cols,
+            &RecordBatchOptions::new().with_row_count(Some(num_rows)),
+        )?;
+
+        if output_batch.num_rows() > 0 {
+            self.output_rows += output_batch.num_rows();
+            self.output_sender.send(Ok(output_batch)).await;
+        }
+        Ok(())
+    }
+
+    async fn join_group(
+        mut self: Pin<&mut Self>,
+        curs: &mut StreamCursors,
+        lgroup: Vec<Idx>,
+        rgroup: Vec<Idx>,
+    ) -> Result<()> {
+        let point_side = self.point_side;
+        let (mut points, mut ranges) = match point_side {
+            JoinSide::Left => (lgroup, rgroup),
+            JoinSide::Right => (rgroup, lgroup),
+        };
+
+        // sort points by band values and ranges by lower bounds, nulls come first
+        {
+            let (point_cur, range_cur) = point_and_range_cursors(curs, point_side);
+            points.sort_by(|&idx1, &idx2| point_cur.band(idx1, 0).cmp(&point_cur.band(idx2, 0)));
+            ranges.sort_by(|&idx1, &idx2| range_cur.band(idx1, 0).cmp(&range_cur.band(idx2, 0)));
+        }
+
+        let mut range_matched = vec![false; ranges.len()];
+        let mut active_ranges: Vec<usize> = vec![];
+        let mut next_range = 0;
+
+        for &point in &points {
+            let point_matched = {
+                let (point_cur, range_cur) = point_and_range_cursors(curs, point_side);
+                match point_cur.band(point, 0) {
+                    Some(point_value) => {
+                        // activate ranges whose lower bounds are covering current point
+                        while next_range < ranges.len() {
+                            match range_cur.band(ranges[next_range], 0) {
+                                Some(lower)
+                                    if lower < point_value
+                                        || lower == point_value && self.lower_inclusive =>
+                                {
+                                    active_ranges.push(next_range);
+                                }
+                                Some(_) => break,
+                                None => {} // null lower bound never matches
+                            }
+                            next_range += 1;
+                        }
+
+                        // deactivate ranges whose upper bounds are below current point,
+                        // they are also below all following points
+                        active_ranges.retain(|&i| match range_cur.band(ranges[i], 1) {
+                            Some(upper) => {
+                                upper > point_value || upper == point_value && self.upper_inclusive
+                            }
+                            None => false,
+                        });
+                        !active_ranges.is_empty()
+                    }
+                    None => false,
+                }
+            };
+
+            if point_matched {
+                for &i in &active_ranges {
+                    range_matched[i] = true;
+                    self.output_point_range(point, ranges[i]);
+                }
+            }
+            self.output_point(point, point_matched);
+            if self.should_flush(curs) {
+                self.as_mut().flush(curs).await?;
+            }
+        }
+
+        for (&range, matched) in ranges.iter().zip(range_matched) {
+            self.output_range(range, matched);
+            if self.should_flush(curs) {
+                self.as_mut().flush(curs).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// sweeps both sides without equal join keys, points are sorted by their
+    /// band values and ranges by their lower bounds, so ranges are
+    /// activated in input order and only active ranges are kept in cursors
+    async fn join_sorted_by_bands(
+        mut self: Pin<&mut Self>,
+        curs: &mut StreamCursors,
+    ) -> Result<()> {
+        let point_side = self.point_side;
+
+        // active ranges are kept in input order, with flags indicating whether they
+        // have been matched
+        let mut active_ranges: Vec<(Idx, bool)> = vec![];
+
+        loop {
+            let forward_point = {
+                let (point_cur, range_cur) = point_and_range_cursors(curs, point_side);
+                if point_cur.finished {
+                    break;
+                }
+                let point = point_cur.cur_idx;
+                let range = range_cur.cur_idx;
+                match point_cur.band(point, 0) {
+                    Some(point_value) => {
+                        let lower = (!range_cur.finished).then(|| range_cur.band(range, 0));
+                        match lower {
+                            // null lower bound never matches
+                            Some(None) => {
+                                self.output_range(range, false);
+                                false
+                            }
+                            // activate range whose lower bound is covering current point
+                            Some(Some(lower))
+                                if lower < point_value
+                                    || lower == point_value && self.lower_inclusive =>
+                            {
+                                active_ranges.push((range, false));
+                                false
+                            }
+                            // all ranges covering current point are activated
+                            _ => {
+                                // deactivate ranges whose upper bounds are below current
+                                // point, they are also below all following points
+                                let upper_inclusive = self.upper_inclusive;
+                                active_ranges.retain(|&(range, matched)| {
+                                    let covered = match range_cur.band(range, 1) {
+                                        Some(upper) => {
+                                            upper > point_value
+                                                || upper == point_value && upper_inclusive
+                                        }
+                                        None => false,
+                                    };
+                                    if !covered {
+                                        self.output_range(range, matched);
+                                    }
+                                    covered
+                                });
+
+                                let point_matched = !active_ranges.is_empty();
+                                for (range, matched) in &mut active_ranges {
+                                    *matched = true;
+                                    self.output_point_range(point, *range);
+                                }
+                                self.output_point(point, point_matched);
+                                true
+                            }
+                        }
+                    }
+                    // null point never matches
+                    None => {
+                        self.output_point(point, false);
+                        true
+                    }
+                }
+            };
+
+            match (point_side, forward_point) {
+                (JoinSide::Left, true) | (JoinSide::Right, false) => cur_forward!(curs.0),
+                (JoinSide::Left, false) | (JoinSide::Right, true) => cur_forward!(curs.1),
+            }
+            if self.should_flush(curs) {
+                self.as_mut().flush(curs).await?;
+            }
+            self.update_min_reserved_idx(curs, active_ranges.first().map(|&(range, _)| range));
+        }
+
+        // points are finished, remaining active ranges will not be matched any more
+        for (range, matched) in std::mem::take(&mut active_ranges) {
+            self.output_range(range, matched);
+            if self.should_flush(curs) {
+                self.as_mut().flush(curs).await?;
+            }
+        }
+        self.update_min_reserved_idx(curs, None);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Joiner for BandJoiner {
+    async fn join(mut self: Pin<&mut Self>, curs: &mut StreamCursors) -> Result<()> {
+        if self.join_params.left_keys.is_empty() {
+            self.as_mut().join_sorted_by_bands(curs).await?;
+        } else {
+            while !curs.0.finished && !curs.1.finished {
+                let lidx = curs.0.cur_idx;
+                let ridx = curs.1.cur_idx;
+                match compare_cursor!(curs) {
+                    Ordering::Less => {
+                        self.output_left(lidx, false);
+                        cur_forward!(curs.0);
+                    }
+                    Ordering::Greater => {
+                        self.output_right(ridx, false);
+                        cur_forward!(curs.1);
+                    }
+                    Ordering::Equal => {
+                        // collect all rows with equal keys from both sides, these rows are
+                        // kept in cursors since min reserved indices are not updated here
+                        let mut lgroup = vec![];
+                        loop {
+                            let lidx = curs.0.cur_idx;
+                            lgroup.push(lidx);
+                            cur_forward!(curs.0);
+                            if curs.0.finished || curs.0.key(curs.0.cur_idx) != curs.0.key(lidx) {
+                                break;
+                            }
+                        }
+                        let mut rgroup = vec![];
+                        loop {
+                            let ridx = curs.1.cur_idx;
+                            rgroup.push(ridx);
+                            cur_forward!(curs.1);
+                            if curs.1.finished || curs.1.key(curs.1.cur_idx) != curs.1.key(ridx) {
+                                break;
+                            }
+                        }
+                        self.as_mut().join_group(curs, lgroup, rgroup).await?;
+                    }
+                }
+                if self.should_flush(curs) {
+                    self.as_mut().flush(curs).await?;
+                }
+                self.update_min_reserved_idx(curs, None);
+            }
+        }
+
+        // at least one side is finished, consume the other side if it has outputs
+        // for unmatched rows
+        while matches!(
+            self.join_params.join_type,
+            Left | Full | LeftAnti | Existence
+        ) && !curs.0.finished
+        {
+            let lidx = curs.0.cur_idx;
+            self.output_left(lidx, false);
+            cur_forward!(curs.0);
+            if self.should_flush(curs) {
+                self.as_mut().flush(curs).await?;
+            }
+            self.update_min_reserved_idx(curs, None);
+        }
+        while matches!(self.join_params.join_type, Right | Full | RightAnti) && !curs.1.finished {
+            let ridx = curs.1.cur_idx;
+            self.output_right(ridx, false);
+            cur_forward!(curs.1);
+            if self.should_flush(curs) {
+                self.as_mut().flush(curs).await?;
+            }
+            self.update_min_reserved_idx(curs, None);
+        }
+        if !self.lindices.is_empty() || !self.rindices.is_empty() {
+            self.flush(curs).await?;
+        }
+        Ok(())
+    }
+
+    fn num_output_rows(&self) -> usize {
+        self.output_rows
+    }
+}
+
+fn point_and_range_cursors(
+    curs: &StreamCursors,
+    point_side: JoinSide,
+) -> (&StreamCursor, &StreamCursor) {
+    match point_side {
+        JoinSide::Left => (&curs.0, &curs.1),
+        JoinSide::Right => (&curs.1, &curs.0),
+    }
+}
@@ -32,13 +32,15 @@ use parking_lot::Mutex;
 
 use crate::{
     common::{batch_selection::take_batch, timer_helper::TimerHelper},
-    joins::{Idx, JoinParams},
+    joins::{Idx, JoinParams, StreamCursors},
 };
 
 pub struct StreamCursor {
     stream: SendableRecordBatchStream,
     key_converter: Arc<Mutex<RowConverter>>,
     key_exprs: Vec<PhysicalExprRef>,
+    band_converter: Arc<Mutex<RowConverter>>,
+    band_exprs: Vec<PhysicalExprRef>,
     poll_time: Time,
 
     // IMPORTANT:
@@ -50,19 +52,21 @@ pub struct StreamCursor {
     min_reserved_idx: Idx,
     keys: Vec<Arc<Rows>>,
     key_has_nulls: Vec<Option<NullBuffer>>,
+    bands: Vec<Vec<(Arc<Rows>, Option<NullBuffer>)>>,
     num_null_batches: usize,
     mem_size: usize,
     pub finished: bool,
 }
 
 impl StreamCursor {
-    pub fn try_new(
-        stream: SendableRecordBatchStream,
+    /// creates cursors of both sides, sharing the same key and band converters
+    /// so that rows of both sides are comparable with each other
+    pub fn try_new_pair(
+        lstream: SendableRecordBatchStream,
+        rstream: SendableRecordBatchStream,
         poll_time: Time,
         join_params: &JoinParams,
-        join_side: JoinSide,
-        projection: &[usize],
-    ) -> Result<Self> {
+    ) -> Result<StreamCursors> {
         let key_converter = Arc::new(Mutex::new(RowConverter::new(
             join_params
                 .key_data_types
@@ -72,10 +76,57 @@ impl StreamCursor {
                 .map(|(dt, options)| SortField::new_with_options(dt, *options))
                 .collect(),
         )?));
+
+        // band values are converted with a single ascending sort field, so that they
+        // can be compared as rows between different batches and sides
+        let band_converter = Arc::new(Mutex::new(RowConverter::new(
+            join_params
+                .band_condition
+                .iter()
+                .map(|band| SortField::new(band.data_type.clone()))
+                .collect(),
+        )?));
+
+        Ok((
+            Self::try_new(
+                lstream,
+                poll_time.clone(),
+                join_params,
+                JoinSide::Left,
+                &join_params.projection.left,
+                key_converter.clone(),
+                band_converter.clone(),
+            )?,
+            Self::try_new(
+                rstream,
+                poll_time,
+                join_params,
+                JoinSide::Right,
+                &join_params.projection.right,
+                key_converter,
+                band_converter,
+            )?,
+        ))
+    }
+
+    fn try_new(
+        stream: SendableRecordBatchStream,
+        poll_time: Time,
+        join_params: &JoinParams,
+        join_side: JoinSide,
+        projection: &[usize],
+        key_converter: Arc<Mutex<RowConverter>>,
+        band_converter: Arc<Mutex<RowConverter>>,
+    ) -> Result<Self> {
         let key_exprs = match join_side {
             JoinSide::Left => join_params.left_keys.clone(),
             JoinSide::Right => join_params.right_keys.clone(),
         };
+        let band_exprs = match &join_params.band_condition {
+            Some(band) if band.point_side == join_side => vec![band.point_expr.clone()],
+            Some(band) => vec![band.lower_expr.clone(), band.upper_expr.clone()],
+            None => vec![],
+        };
 
         let empty_batch = RecordBatch::new_empty(Arc::new(Schema::new(
             stream
@@ -96,11 +147,21 @@ impl StreamCursor {
         let null_batch = take_batch(empty_batch, vec![Option::<u32>::None])?;
         let projected_null_batch = null_batch.project(projection)?;
         let null_nb = NullBuffer::new_null(1);
+        let null_bands = band_exprs
+            .iter()
+            .map(|band_expr| {
+                let band_column = band_expr.evaluate(&empty_batch)?.into_array(0)?;
+                let band_rows = band_converter.lock().convert_columns(&[band_column])?;
+                Ok((Arc::new(band_rows), Some(null_nb.clone())))
+            })
+            .collect::<Result<Vec<_>>>()?;
 
         Ok(Self {
             stream,
             key_exprs,
             key_converter,
+            band_converter,
+            band_exprs,
             poll_time,
             projection: projection.to_vec(),
             projected_batch_schema: projected_null_batch.schema(),
@@ -109,6 +170,7 @@ impl StreamCursor {
             min_reserved_idx: (0, 0),
             keys: vec![empty_keys],
             key_has_nulls: vec![Some(null_nb)],
+            bands: vec![null_bands],
             num_null_batches: 1,
             mem_size: 0,
             finished: false,
@@ -144,6 +206,18 @@ impl StreamCursor {
                         .reduce(|lhs, rhs| NullBuffer::union(lhs.as_ref(), rhs.as_ref()))
                         .unwrap_or(None);
                     let keys = Arc::new(self.key_converter.lock().convert_columns(&key_columns)?);
+                    let bands = self
+                        .band_exprs
+                        .iter()
+                        .map(|band_expr| {
+                            let band_column =
+                                band_expr.evaluate(&batch)?.into_array(batch.num_rows())?;
+                            let band_nulls = band_column.logical_nulls();
+                            let band_rows =
+                                self.band_converter.lock().convert_columns(&[band_column])?;
+                            Ok((Arc::new(band_rows), band_nulls))
+                        })
+                        .collect::<Result<Vec<_>>>()?;
 
                     self.mem_size += batch.get_array_mem_size();
                     self.mem_size += key_has_nulls
@@ -151,6 +225,7 @@ impl StreamCursor {
                         .map(|nb| nb.buffer().len())
                         .unwrap_or_default();
                     self.mem_size += keys.size();
+                    self.mem_size += bands_mem_size(&bands);
 
                     self.projected_batches
                         .push(RecordBatch::try_new_with_options(
@@ -163,6 +238,7 @@ impl StreamCursor {
                         )?);
                     self.key_has_nulls.push(key_has_nulls);
                     self.keys.push(keys);
+                    self.bands.push(bands);
 
                     // fill out-dated batches with null batches
                     if self.num_null_batches < self.min_reserved_idx.0 {
@@ -173,10 +249,12 @@ impl StreamCursor {
                                 .map(|nb| nb.buffer().len())
                                 .unwrap_or_default();
                             self.mem_size -= self.keys[i].size();
+                            self.mem_size -= bands_mem_size(&self.bands[i]);
 
                             self.projected_batches[i] = self.projected_batches[0].clone();
                             self.keys[i] = self.keys[0].clone();
                             self.key_has_nulls[i] = self.key_has_nulls[0].clone();
+                            self.bands[i] = self.bands[0].clone();
                             self.num_null_batches += 1;
                         }
                     }
@@ -204,6 +282,20 @@ impl StreamCursor {
         keys.row(idx.1)
     }
 
+    /// returns the i-th band value of the specified row, or None if it is null
+    #[inline]
+    pub fn band<'a>(&'a self, idx: Idx, i: usize) -> Option<Row<'a>> {
+        let (band_rows, band_nulls) = &self.bands[idx.0][i];
+        if band_nulls
+            .as_ref()
+            .map(|nb| nb.is_null(idx.1))
+            .unwrap_or(false)
+        {
+            return None;
+        }
+        Some(band_rows.row(idx.1))
+    }
+
     #[inline]
     pub fn num_buffered_batches(&self) -> usize {
         self.projected_batches.len() - self.num_null_batches
@@ -220,6 +312,19 @@ impl StreamCursor {
     }
 }
 
+fn bands_mem_size(bands: &[(Arc<Rows>, Option<NullBuffer>)]) -> usize {
+    bands
+        .iter()
+        .map(|(band_rows, band_nulls)| {
+            band_rows.size()
+                + band_nulls
+                    .as_ref()
+                    .map(|nb| nb.buffer().len())
+                    .unwrap_or_default()
+        })
+        .sum()
+}
+
 #[macro_export]
 macro_rules! cur_forward {
     ($cur:expr) => {{
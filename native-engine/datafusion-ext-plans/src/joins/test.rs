@@ -27,7 +27,8 @@ mod tests {
         assert_batches_sorted_eq,
         common::JoinSide,
         error::Result,
-        physical_expr::expressions::Column,
+        logical_expr::Operator,
+        physical_expr::expressions::{BinaryExpr, Column},
         physical_plan::{common, joins::utils::*, memory::MemoryExec, ExecutionPlan},
         prelude::SessionContext,
     };
@@ -36,7 +37,13 @@ mod tests {
     use crate::{
         broadcast_join_build_hash_map_exec::BroadcastJoinBuildHashMapExec,
         broadcast_join_exec::BroadcastJoinExec,
-        joins::join_utils::{JoinType, JoinType::*},
+        broadcast_nested_loop_join_exec::BroadcastNestedLoopJoinExec,
+        joins::{
+            join_utils::{JoinType, JoinType::*},
+            JoinBandCondition, JoinFilter,
+        },
+        memmgr::MemManager,
+        shuffled_hash_join_exec::ShuffledHashJoinExec,
         sort_merge_join_exec::SortMergeJoinExec,
     };
 
@@ -47,6 +54,8 @@ mod tests {
         BHJRightProbed,
         SHJLeftProbed,
         SHJRightProbed,
+        SHJLeftProbedSpilled,
+        SHJRightProbedSpilled,
     }
 
     fn columns(schema: &Schema) -> Vec<String> {
@@ -186,6 +195,7 @@ mod tests {
         on: JoinOn,
         join_type: JoinType,
     ) -> Result<(Vec<String>, Vec<RecordBatch>)> {
+        MemManager::init(1000000);
         let session_ctx = SessionContext::new();
         let task_ctx = session_ctx.task_ctx();
         let schema = build_join_schema_for_test(&left.schema(), &right.schema(), join_type)?;
@@ -200,6 +210,7 @@ mod tests {
                     on,
                     join_type,
                     sort_options,
+                    None,
                 )?)
             }
             BHJLeftProbed => {
@@ -234,26 +245,30 @@ mod tests {
                     None,
                 )?)
             }
-            SHJLeftProbed => Arc::new(BroadcastJoinExec::try_new(
+            SHJLeftProbed => Arc::new(ShuffledHashJoinExec::try_new(
                 schema,
                 left,
                 right,
                 on,
                 join_type,
                 JoinSide::Right,
-                false,
-                None,
             )?),
-            SHJRightProbed => Arc::new(BroadcastJoinExec::try_new(
+            SHJRightProbed => Arc::new(ShuffledHashJoinExec::try_new(
                 schema,
                 left,
                 right,
                 on,
                 join_type,
                 JoinSide::Left,
-                false,
-                None,
             )?),
+            SHJLeftProbedSpilled => Arc::new(
+                ShuffledHashJoinExec::try_new(schema, left, right, on, join_type, JoinSide::Right)?
+                    .with_force_spill(),
+            ),
+            SHJRightProbedSpilled => Arc::new(
+                ShuffledHashJoinExec::try_new(schema, left, right, on, join_type, JoinSide::Left)?
+                    .with_force_spill(),
+            ),
         };
         let columns = columns(&join.schema());
         let stream = join.execute(0, task_ctx)?;
@@ -261,12 +276,115 @@ mod tests {
         Ok((columns, batches))
     }
 
-    const ALL_TEST_TYPE: [TestType; 5] = [
+    async fn band_join_collect(
+        left: Arc<dyn ExecutionPlan>,
+        right: Arc<dyn ExecutionPlan>,
+        on: JoinOn,
+        join_type: JoinType,
+        band_condition: JoinBandCondition,
+    ) -> Result<(Vec<String>, Vec<RecordBatch>)> {
+        MemManager::init(1000000);
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+        let schema = build_join_schema_for_test(&left.schema(), &right.schema(), join_type)?;
+        let sort_options = vec![SortOptions::default(); on.len()];
+        let join = Arc::new(SortMergeJoinExec::try_new(
+            schema,
+            left,
+            right,
+            on,
+            join_type,
+            sort_options,
+            Some(band_condition),
+        )?);
+        let columns = columns(&join.schema());
+        let stream = join.execute(0, task_ctx)?;
+        let batches = common::collect(stream).await?;
+        Ok((columns, batches))
+    }
+
+    fn band_condition(
+        point_side: JoinSide,
+        point: &str,
+        lower: &str,
+        upper: &str,
+        lower_inclusive: bool,
+        upper_inclusive: bool,
+    ) -> JoinBandCondition {
+        JoinBandCondition {
+            point_side,
+            point_expr: Arc::new(Column::new(point, 2)),
+            lower_expr: Arc::new(Column::new(lower, 0)),
+            upper_expr: Arc::new(Column::new(upper, 2)),
+            lower_inclusive,
+            upper_inclusive,
+            data_type: DataType::Int32,
+        }
+    }
+
+    async fn nlj_collect(
+        left: Arc<dyn ExecutionPlan>,
+        right: Arc<dyn ExecutionPlan>,
+        join_type: JoinType,
+        broadcast_side: JoinSide,
+        join_filter: Option<JoinFilter>,
+    ) -> Result<(Vec<String>, Vec<RecordBatch>)> {
+        MemManager::init(1000000);
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+        let schema = build_join_schema_for_test(&left.schema(), &right.schema(), join_type)?;
+        let join = Arc::new(BroadcastNestedLoopJoinExec::try_new(
+            schema,
+            left,
+            right,
+            join_type,
+            broadcast_side,
+            join_filter,
+        )?);
+        let columns = columns(&join.schema());
+        let stream = join.execute(0, task_ctx)?;
+        let batches = common::collect(stream).await?;
+        Ok((columns, batches))
+    }
+
+    // filter: left.a1 <op> right.a2
+    fn a1_a2_filter(op: Operator) -> JoinFilter {
+        JoinFilter {
+            expression: Arc::new(BinaryExpr::new(
+                Arc::new(Column::new("a1", 0)),
+                op,
+                Arc::new(Column::new("a2", 1)),
+            )),
+            column_indices: vec![(JoinSide::Left, 0), (JoinSide::Right, 0)],
+            schema: Arc::new(Schema::new(vec![
+                Field::new("a1", DataType::Int32, false),
+                Field::new("a2", DataType::Int32, false),
+            ])),
+        }
+    }
+
+    fn build_nlj_tables() -> (Arc<dyn ExecutionPlan>, Arc<dyn ExecutionPlan>) {
+        let left = build_table(
+            ("a1", &vec![1, 2, 3]),
+            ("b1", &vec![4, 5, 6]),
+            ("c1", &vec![7, 8, 9]),
+        );
+        let right = build_table(
+            ("a2", &vec![2, 3]),
+            ("b2", &vec![20, 30]),
+            ("c2", &vec![200, 300]),
+        );
+        (left, right)
+    }
+
+    const ALL_TEST_TYPE: [TestType; 7] = [
         SMJ,
         BHJLeftProbed,
         BHJRightProbed,
         SHJLeftProbed,
         SHJRightProbed,
+        SHJLeftProbedSpilled,
+        SHJRightProbedSpilled,
     ];
 
     #[tokio::test]
@@ -979,4 +1097,477 @@ mod tests {
         }
         Ok(())
     }
+
+    #[tokio::test]
+    async fn band_join_inner() -> Result<()> {
+        let left = build_table(
+            ("a1", &vec![1, 2, 3, 4, 5]),
+            ("b1", &vec![1, 1, 1, 2, 3]),
+            ("c1", &vec![5, 10, 15, 10, 7]),
+        );
+        let right = build_table(
+            ("a2", &vec![0, 8, 11, 0]),
+            ("b2", &vec![1, 1, 2, 4]),
+            ("c2", &vec![10, 20, 12, 100]),
+        );
+        let on: JoinOn = vec![(
+            Arc::new(Column::new_with_schema("b1", &left.schema())?),
+            Arc::new(Column::new_with_schema("b2", &right.schema())?),
+        )];
+
+        // a2 <= c1 <= c2
+        let band = band_condition(JoinSide::Left, "c1", "a2", "c2", true, true);
+        let (_, batches) = band_join_collect(left, right, on, Inner, band).await?;
+        let expected = vec![
+            "+----+----+----+----+----+----+",
+            "| a1 | b1 | c1 | a2 | b2 | c2 |",
+            "+----+----+----+----+----+----+",
+            "| 1  | 1  | 5  | 0  | 1  | 10 |",
+            "| 2  | 1  | 10 | 0  | 1  | 10 |",
+            "| 2  | 1  | 10 | 8  | 1  | 20 |",
+            "| 3  | 1  | 15 | 8  | 1  | 20 |",
+            "+----+----+----+----+----+----+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn band_join_left_multiple_batches() -> Result<()> {
+        let left_batch_1 = build_table_i32(
+            ("a1", &vec![1, 2]),
+            ("b1", &vec![1, 1]),
+            ("c1", &vec![5, 10]),
+        );
+        let left_batch_2 = build_table_i32(
+            ("a1", &vec![3, 4, 5]),
+            ("b1", &vec![1, 2, 3]),
+            ("c1", &vec![15, 10, 7]),
+        );
+        let right_batch_1 = build_table_i32(("a2", &vec![0]), ("b2", &vec![1]), ("c2", &vec![10]));
+        let right_batch_2 = build_table_i32(
+            ("a2", &vec![8, 11, 0]),
+            ("b2", &vec![1, 2, 4]),
+            ("c2", &vec![20, 12, 100]),
+        );
+        let left = build_table_from_batches(vec![left_batch_1, left_batch_2]);
+        let right = build_table_from_batches(vec![right_batch_1, right_batch_2]);
+        let on: JoinOn = vec![(
+            Arc::new(Column::new_with_schema("b1", &left.schema())?),
+            Arc::new(Column::new_with_schema("b2", &right.schema())?),
+        )];
+
+        // a2 <= c1 < c2
+        let band = band_condition(JoinSide::Left, "c1", "a2", "c2", true, false);
+        let (_, batches) = band_join_collect(left, right, on, Left, band).await?;
+        let expected = vec![
+            "+----+----+----+----+----+----+",
+            "| a1 | b1 | c1 | a2 | b2 | c2 |",
+            "+----+----+----+----+----+----+",
+            "| 1  | 1  | 5  | 0  | 1  | 10 |",
+            "| 2  | 1  | 10 | 8  | 1  | 20 |",
+            "| 3  | 1  | 15 | 8  | 1  | 20 |",
+            "| 4  | 2  | 10 |    |    |    |",
+            "| 5  | 3  | 7  |    |    |    |",
+            "+----+----+----+----+----+----+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn band_join_point_on_right() -> Result<()> {
+        let build_inputs = || {
+            let left = build_table(
+                ("a1", &vec![0, 5, 1]),
+                ("b1", &vec![1, 1, 2]),
+                ("c1", &vec![3, 9, 2]),
+            );
+            let right = build_table(
+                ("a2", &vec![10, 20, 30, 40]),
+                ("b2", &vec![1, 1, 2, 3]),
+                ("c2", &vec![4, 7, 5, 1]),
+            );
+            let on: JoinOn = vec![(
+                Arc::new(Column::new_with_schema("b1", &left.schema()).unwrap()),
+                Arc::new(Column::new_with_schema("b2", &right.schema()).unwrap()),
+            )];
+            (left, right, on)
+        };
+
+        // a1 < c2 < c1
+        let band = band_condition(JoinSide::Right, "c2", "a1", "c1", false, false);
+        let (left, right, on) = build_inputs();
+        let (_, batches) = band_join_collect(left, right, on, Existence, band.clone()).await?;
+        let expected = vec![
+            "+----+----+----+----------+",
+            "| a1 | b1 | c1 | exists#0 |",
+            "+----+----+----+----------+",
+            "| 0  | 1  | 3  | false    |",
+            "| 1  | 2  | 2  | false    |",
+            "| 5  | 1  | 9  | true     |",
+            "+----+----+----+----------+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+
+        let (left, right, on) = build_inputs();
+        let (_, batches) = band_join_collect(left, right, on, RightAnti, band).await?;
+        let expected = vec![
+            "+----+----+----+",
+            "| a2 | b2 | c2 |",
+            "+----+----+----+",
+            "| 10 | 1  | 4  |",
+            "| 30 | 2  | 5  |",
+            "| 40 | 3  | 1  |",
+            "+----+----+----+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn band_join_group_across_batches() -> Result<()> {
+        // rows of group b1=1 are spread over several batches, with the smallest
+        // band value in the last batch
+        let left = build_table_from_batches(vec![
+            build_table_i32(("a1", &vec![1]), ("b1", &vec![1]), ("c1", &vec![30])),
+            build_table_i32(("a1", &vec![2]), ("b1", &vec![1]), ("c1", &vec![20])),
+            build_table_i32(("a1", &vec![3]), ("b1", &vec![1]), ("c1", &vec![10])),
+            build_table_i32(("a1", &vec![4]), ("b1", &vec![2]), ("c1", &vec![10])),
+            build_table_i32(("a1", &vec![5]), ("b1", &vec![3]), ("c1", &vec![10])),
+            build_table_i32(("a1", &vec![6]), ("b1", &vec![4]), ("c1", &vec![10])),
+        ]);
+        let right = build_table_from_batches(vec![
+            build_table_i32(("a2", &vec![0]), ("b2", &vec![1]), ("c2", &vec![100])),
+            build_table_i32(("a2", &vec![0]), ("b2", &vec![2]), ("c2", &vec![100])),
+            build_table_i32(("a2", &vec![0]), ("b2", &vec![3]), ("c2", &vec![5])),
+        ]);
+        let on: JoinOn = vec![(
+            Arc::new(Column::new_with_schema("b1", &left.schema())?),
+            Arc::new(Column::new_with_schema("b2", &right.schema())?),
+        )];
+
+        // a2 <= c1 <= c2
+        let band = band_condition(JoinSide::Left, "c1", "a2", "c2", true, true);
+        let (_, batches) = band_join_collect(left, right, on, Left, band).await?;
+        let expected = vec![
+            "+----+----+----+----+----+-----+",
+            "| a1 | b1 | c1 | a2 | b2 | c2  |",
+            "+----+----+----+----+----+-----+",
+            "| 1  | 1  | 30 | 0  | 1  | 100 |",
+            "| 2  | 1  | 20 | 0  | 1  | 100 |",
+            "| 3  | 1  | 10 | 0  | 1  | 100 |",
+            "| 4  | 2  | 10 | 0  | 2  | 100 |",
+            "| 5  | 3  | 10 |    |    |     |",
+            "| 6  | 4  | 10 |    |    |     |",
+            "+----+----+----+----+----+-----+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn band_join_without_equal_keys() -> Result<()> {
+        // points are sorted by c1 and ranges are sorted by a2
+        let left = build_table_from_batches(vec![
+            build_table_i32(
+                ("a1", &vec![1, 2]),
+                ("b1", &vec![1, 1]),
+                ("c1", &vec![1, 5]),
+            ),
+            build_table_i32(("a1", &vec![3]), ("b1", &vec![1]), ("c1", &vec![10])),
+            build_table_i32(
+                ("a1", &vec![4, 5]),
+                ("b1", &vec![1, 1]),
+                ("c1", &vec![15, 30]),
+            ),
+        ]);
+        let right = build_table_from_batches(vec![
+            build_table_i32(("a2", &vec![0]), ("b2", &vec![1]), ("c2", &vec![3])),
+            build_table_i32(
+                ("a2", &vec![4, 8]),
+                ("b2", &vec![2, 3]),
+                ("c2", &vec![9, 8]),
+            ),
+            build_table_i32(("a2", &vec![12]), ("b2", &vec![4]), ("c2", &vec![40])),
+        ]);
+
+        // a2 <= c1 <= c2
+        let band = band_condition(JoinSide::Left, "c1", "a2", "c2", true, true);
+        let (_, batches) = band_join_collect(left, right, vec![], Full, band).await?;
+        let expected = vec![
+            "+----+----+----+----+----+----+",
+            "| a1 | b1 | c1 | a2 | b2 | c2 |",
+            "+----+----+----+----+----+----+",
+            "|    |    |    | 8  | 3  | 8  |",
+            "| 1  | 1  | 1  | 0  | 1  | 3  |",
+            "| 2  | 1  | 5  | 4  | 2  | 9  |",
+            "| 3  | 1  | 10 |    |    |    |",
+            "| 4  | 1  | 15 | 12 | 4  | 40 |",
+            "| 5  | 1  | 30 | 12 | 4  | 40 |",
+            "+----+----+----+----+----+----+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn band_join_without_equal_keys_nulls() -> Result<()> {
+        // ranges are sorted by a1 and points are sorted by c2, nulls first
+        let build_inputs = || {
+            let left = build_table_i32_nullable(
+                ("a1", &vec![None, Some(1), Some(5)]),
+                ("b1", &vec![Some(1), Some(2), Some(3)]),
+                ("c1", &vec![Some(10), Some(3), Some(5)]),
+            );
+            let right = build_table_i32_nullable(
+                ("a2", &vec![Some(1), Some(2), Some(3)]),
+                ("b2", &vec![Some(1), Some(2), Some(3)]),
+                ("c2", &vec![None, Some(2), Some(6)]),
+            );
+            (left, right)
+        };
+
+        // a1 <= c2 <= c1
+        let band = band_condition(JoinSide::Right, "c2", "a1", "c1", true, true);
+        let (left, right) = build_inputs();
+        let (_, batches) = band_join_collect(left, right, vec![], Existence, band.clone()).await?;
+        let expected = vec![
+            "+----+----+----+----------+",
+            "| a1 | b1 | c1 | exists#0 |",
+            "+----+----+----+----------+",
+            "|    | 1  | 10 | false    |",
+            "| 1  | 2  | 3  | true     |",
+            "| 5  | 3  | 5  | false    |",
+            "+----+----+----+----------+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+
+        let (left, right) = build_inputs();
+        let (_, batches) = band_join_collect(left, right, vec![], RightAnti, band).await?;
+        let expected = vec![
+            "+----+----+----+",
+            "| a2 | b2 | c2 |",
+            "+----+----+----+",
+            "| 1  | 1  |    |",
+            "| 3  | 3  | 6  |",
+            "+----+----+----+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn nlj_inner_and_outer() -> Result<()> {
+        for broadcast_side in [JoinSide::Left, JoinSide::Right] {
+            let (left, right) = build_nlj_tables();
+            let filter = Some(a1_a2_filter(Operator::Lt));
+            let (_, batches) = nlj_collect(left, right, Inner, broadcast_side, filter).await?;
+            let expected = vec![
+                "+----+----+----+----+----+-----+",
+                "| a1 | b1 | c1 | a2 | b2 | c2  |",
+                "+----+----+----+----+----+-----+",
+                "| 1  | 4  | 7  | 2  | 20 | 200 |",
+                "| 1  | 4  | 7  | 3  | 30 | 300 |",
+                "| 2  | 5  | 8  | 3  | 30 | 300 |",
+                "+----+----+----+----+----+-----+",
+            ];
+            assert_batches_sorted_eq!(expected, &batches);
+
+            for join_type in [Left, Full] {
+                let (left, right) = build_nlj_tables();
+                let filter = Some(a1_a2_filter(Operator::Lt));
+                let (_, batches) =
+                    nlj_collect(left, right, join_type, broadcast_side, filter).await?;
+                let expected = vec![
+                    "+----+----+----+----+----+-----+",
+                    "| a1 | b1 | c1 | a2 | b2 | c2  |",
+                    "+----+----+----+----+----+-----+",
+                    "| 1  | 4  | 7  | 2  | 20 | 200 |",
+                    "| 1  | 4  | 7  | 3  | 30 | 300 |",
+                    "| 2  | 5  | 8  | 3  | 30 | 300 |",
+                    "| 3  | 6  | 9  |    |    |     |",
+                    "+----+----+----+----+----+-----+",
+                ];
+                assert_batches_sorted_eq!(expected, &batches);
+            }
+
+            let (left, right) = build_nlj_tables();
+            let filter = Some(a1_a2_filter(Operator::Gt));
+            let (_, batches) = nlj_collect(left, right, Right, broadcast_side, filter).await?;
+            let expected = vec![
+                "+----+----+----+----+----+-----+",
+                "| a1 | b1 | c1 | a2 | b2 | c2  |",
+                "+----+----+----+----+----+-----+",
+                "|    |    |    | 3  | 30 | 300 |",
+                "| 3  | 6  | 9  | 2  | 20 | 200 |",
+                "+----+----+----+----+----+-----+",
+            ];
+            assert_batches_sorted_eq!(expected, &batches);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn nlj_semi_anti_existence() -> Result<()> {
+        for broadcast_side in [JoinSide::Left, JoinSide::Right] {
+            let (left, right) = build_nlj_tables();
+            let filter = Some(a1_a2_filter(Operator::Lt));
+            let (_, batches) = nlj_collect(left, right, LeftSemi, broadcast_side, filter).await?;
+            let expected = vec![
+                "+----+----+----+",
+                "| a1 | b1 | c1 |",
+                "+----+----+----+",
+                "| 1  | 4  | 7  |",
+                "| 2  | 5  | 8  |",
+                "+----+----+----+",
+            ];
+            assert_batches_sorted_eq!(expected, &batches);
+
+            let (left, right) = build_nlj_tables();
+            let filter = Some(a1_a2_filter(Operator::Lt));
+            let (_, batches) = nlj_collect(left, right, LeftAnti, broadcast_side, filter).await?;
+            let expected = vec![
+                "+----+----+----+",
+                "| a1 | b1 | c1 |",
+                "+----+----+----+",
+                "| 3  | 6  | 9  |",
+                "+----+----+----+",
+            ];
+            assert_batches_sorted_eq!(expected, &batches);
+
+            let (left, right) = build_nlj_tables();
+            let filter = Some(a1_a2_filter(Operator::Gt));
+            let (_, batches) = nlj_collect(left, right, RightAnti, broadcast_side, filter).await?;
+            let expected = vec![
+                "+----+----+-----+",
+                "| a2 | b2 | c2  |",
+                "+----+----+-----+",
+                "| 3  | 30 | 300 |",
+                "+----+----+-----+",
+            ];
+            assert_batches_sorted_eq!(expected, &batches);
+
+            let (left, right) = build_nlj_tables();
+            let filter = Some(a1_a2_filter(Operator::Lt));
+            let (_, batches) = nlj_collect(left, right, Existence, broadcast_side, filter).await?;
+            let expected = vec![
+                "+----+----+----+----------+",
+                "| a1 | b1 | c1 | exists#0 |",
+                "+----+----+----+----------+",
+                "| 1  | 4  | 7  | true     |",
+                "| 2  | 5  | 8  | true     |",
+                "| 3  | 6  | 9  | false    |",
+                "+----+----+----+----------+",
+            ];
+            assert_batches_sorted_eq!(expected, &batches);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn nlj_cartesian_product() -> Result<()> {
+        let (left, right) = build_nlj_tables();
+        let (_, batches) = nlj_collect(left, right, Inner, JoinSide::Right, None).await?;
+        let expected = vec![
+            "+----+----+----+----+----+-----+",
+            "| a1 | b1 | c1 | a2 | b2 | c2  |",
+            "+----+----+----+----+----+-----+",
+            "| 1  | 4  | 7  | 2  | 20 | 200 |",
+            "| 1  | 4  | 7  | 3  | 30 | 300 |",
+            "| 2  | 5  | 8  | 2  | 20 | 200 |",
+            "| 2  | 5  | 8  | 3  | 30 | 300 |",
+            "| 3  | 6  | 9  | 2  | 20 | 200 |",
+            "| 3  | 6  | 9  | 3  | 30 | 300 |",
+            "+----+----+----+----+----+-----+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+
+        // empty broadcast side
+        let (left, right) = build_nlj_tables();
+        let right = Arc::new(MemoryExec::try_new(&[vec![]], right.schema(), None)?);
+        let (_, batches) = nlj_collect(left, right, Left, JoinSide::Right, None).await?;
+        let expected = vec![
+            "+----+----+----+----+----+----+",
+            "| a1 | b1 | c1 | a2 | b2 | c2 |",
+            "+----+----+----+----+----+----+",
+            "| 1  | 4  | 7  |    |    |    |",
+            "| 2  | 5  | 8  |    |    |    |",
+            "| 3  | 6  | 9  |    |    |    |",
+            "+----+----+----+----+----+----+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn nlj_multiple_partitions() -> Result<()> {
+        // broadcast side is replicated to every probed partition
+        let left_batch = build_table_i32(
+            ("a1", &vec![1, 2, 3]),
+            ("b1", &vec![4, 5, 6]),
+            ("c1", &vec![7, 8, 9]),
+        );
+        let left_schema = left_batch.schema();
+        let left: Arc<dyn ExecutionPlan> = Arc::new(MemoryExec::try_new(
+            &[vec![left_batch.clone()], vec![left_batch]],
+            left_schema,
+            None,
+        )?);
+        let right_batch_1 =
+            build_table_i32(("a2", &vec![2]), ("b2", &vec![20]), ("c2", &vec![200]));
+        let right_batch_2 = build_table_i32(
+            ("a2", &vec![3, 5]),
+            ("b2", &vec![30, 50]),
+            ("c2", &vec![300, 500]),
+        );
+        let right_schema = right_batch_1.schema();
+        let right: Arc<dyn ExecutionPlan> = Arc::new(MemoryExec::try_new(
+            &[vec![right_batch_1], vec![right_batch_2]],
+            right_schema,
+            None,
+        )?);
+
+        // outputs of broadcast side rows cannot be decided by a single partition
+        for join_type in [Left, Full, LeftSemi, LeftAnti, Existence] {
+            let schema = build_join_schema_for_test(&left.schema(), &right.schema(), join_type)?;
+            assert!(BroadcastNestedLoopJoinExec::try_new(
+                schema,
+                left.clone(),
+                right.clone(),
+                join_type,
+                JoinSide::Left,
+                Some(a1_a2_filter(Operator::Eq)),
+            )
+            .is_err());
+        }
+
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+        let schema = build_join_schema_for_test(&left.schema(), &right.schema(), Right)?;
+        let join = Arc::new(BroadcastNestedLoopJoinExec::try_new(
+            schema,
+            left,
+            right,
+            Right,
+            JoinSide::Left,
+            Some(a1_a2_filter(Operator::Eq)),
+        )?);
+        let mut batches = vec![];
+        for partition in 0..2 {
+            let stream = join.execute(partition, task_ctx.clone())?;
+            batches.extend(common::collect(stream).await?);
+        }
+        let expected = vec![
+            "+----+----+----+----+----+-----+",
+            "| a1 | b1 | c1 | a2 | b2 | c2  |",
+            "+----+----+----+----+----+-----+",
+            "|    |    |    | 5  | 50 | 500 |",
+            "| 2  | 5  | 8  | 2  | 20 | 200 |",
+            "| 3  | 6  | 9  | 3  | 30 | 300 |",
+            "+----+----+----+----+----+-----+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+        Ok(())
+    }
 }
@@ -24,6 +24,7 @@
 pub mod agg_exec;
 pub mod broadcast_join_build_hash_map_exec;
 pub mod broadcast_join_exec;
+pub mod broadcast_nested_loop_join_exec;
 pub mod debug_exec;
 pub mod empty_partitions_exec;
 pub mod expand_exec;
@@ -40,6 +41,7 @@ pub mod project_exec;
 pub mod rename_columns_exec;
 pub mod rss_shuffle_writer_exec;
 pub mod shuffle_writer_exec;
+pub mod shuffled_hash_join_exec;
 pub mod sort_exec;
 pub mod sort_merge_join_exec;
 pub mod window_exec;
@@ -53,5 +55,5 @@ pub mod common;
 pub mod generate;
 pub mod joins;
 mod scan;
-mod shuffle;
+pub mod shuffle;
 pub mod window;
@@ -64,6 +64,10 @@ impl MemManager {
         MEM_MANAGER.get().expect("mem manager not initialized")
     }
 
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
     pub fn num_consumers(&self) -> usize {
         self.consumers.lock().len()
     }
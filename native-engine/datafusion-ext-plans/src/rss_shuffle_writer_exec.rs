@@ -26,8 +26,9 @@ use datafusion::{
     physical_plan::{
         metrics::{BaselineMetrics, ExecutionPlanMetricsSet, MetricBuilder, MetricsSet},
         stream::RecordBatchStreamAdapter,
-        DisplayAs, DisplayFormatType, ExecutionMode, ExecutionPlan, Partitioning, PlanProperties,
-        SendableRecordBatchStream, Statistics,
+        DisplayAs, DisplayFormatType, ExecutionMode, ExecutionPlan,
+        Partitioning as DataFusionPartitioning, PlanProperties, SendableRecordBatchStream,
+        Statistics,
     },
 };
 use futures::{stream::once, TryStreamExt};
@@ -38,7 +39,7 @@ use crate::{
     memmgr::MemManager,
     shuffle::{
         rss_single_repartitioner::RssSingleShuffleRepartitioner,
-        rss_sort_repartitioner::RssSortShuffleRepartitioner, ShuffleRepartitioner,
+        rss_sort_repartitioner::RssSortShuffleRepartitioner, Partitioning, ShuffleRepartitioner,
     },
 };
 
@@ -82,7 +83,7 @@ impl ExecutionPlan for RssShuffleWriterExec {
         self.props.get_or_init(|| {
             PlanProperties::new(
                 EquivalenceProperties::new(self.schema()),
-                self.partitioning.clone(),
+                DataFusionPartitioning::UnknownPartitioning(self.partitioning.partition_count()),
                 ExecutionMode::Bounded,
             )
         })
@@ -127,7 +128,7 @@ impl ExecutionPlan for RssShuffleWriterExec {
             p if p.partition_count() == 1 => {
                 Arc::new(RssSingleShuffleRepartitioner::new(rss_partition_writer))
             }
-            Partitioning::Hash(..) => {
+            _ => {
                 let sort_time = self.metrics.register_timer("sort_time", partition);
                 let partitioner = Arc::new(RssSortShuffleRepartitioner::new(
                     partition,
@@ -138,7 +139,6 @@ impl ExecutionPlan for RssShuffleWriterExec {
                 MemManager::register_consumer(partitioner.clone(), true);
                 partitioner
             }
-            p => unreachable!("unsupported partitioning: {:?}", p),
         };
         Ok(Box::pin(RecordBatchStreamAdapter::new(
             self.schema(),
@@ -17,10 +17,7 @@ use std::io::Write;
 use arrow::{array::ArrayRef, record_batch::RecordBatch};
 use blaze_jni_bridge::jni_call;
 use count_write::CountWrite;
-use datafusion::{
-    common::Result,
-    physical_plan::{metrics::Time, Partitioning},
-};
+use datafusion::{common::Result, physical_plan::metrics::Time};
 use datafusion_ext_commons::{
     array_size::ArraySize,
     assume, compute_suggested_batch_size_for_output,
@@ -36,7 +33,7 @@ use crate::{
         batch_selection::take_batch, ipc_compression::IpcCompressionWriter,
         timer_helper::TimerHelper,
     },
-    shuffle::{evaluate_hashes, evaluate_partition_ids, rss::RssWriter},
+    shuffle::{evaluate_partition_ids, rss::RssWriter, Partitioning},
 };
 
 pub struct BufferedData {
@@ -44,6 +41,7 @@ pub struct BufferedData {
     sorted_batches: Vec<RecordBatch>,
     sorted_parts: Vec<Vec<PartitionInBatch>>,
     num_rows: usize,
+    row_offset: usize,
     staging_mem_used: usize,
     sorted_mem_used: usize,
     sort_time: Time,
@@ -56,6 +54,7 @@ impl BufferedData {
             sorted_batches: vec![],
             sorted_parts: vec![],
             num_rows: 0,
+            row_offset: 0,
             staging_mem_used: 0,
             sorted_mem_used: 0,
             sort_time,
@@ -63,15 +62,18 @@ impl BufferedData {
     }
 
     pub fn drain(&mut self) -> Self {
-        std::mem::replace(self, Self::new(self.partition_id, self.sort_time.clone()))
+        let mut drained = Self::new(self.partition_id, self.sort_time.clone());
+        drained.row_offset = self.row_offset + self.num_rows;
+        std::mem::replace(self, drained)
     }
 
     pub fn add_batch(&mut self, batch: RecordBatch, partitioning: &Partitioning) -> Result<()> {
+        let row_offset = self.row_offset + self.num_rows;
         self.num_rows += batch.num_rows();
 
-        let (parts, sorted_batch) = self
-            .sort_time
-            .with_timer(|| sort_batch_by_partition_id(batch, partitioning))?;
+        let (parts, sorted_batch) = self.sort_time.with_timer(|| {
+            sort_batch_by_partition_id(batch, partitioning, self.partition_id, row_offset)
+        })?;
         self.sorted_mem_used +=
             sorted_batch.get_array_mem_size() + parts.len() * size_of::<PartitionInBatch>();
         self.sorted_batches.push(sorted_batch);
@@ -265,14 +267,16 @@ struct PartitionInBatch {
 fn sort_batch_by_partition_id(
     batch: RecordBatch,
     partitioning: &Partitioning,
+    partition_id: usize,
+    row_offset: usize,
 ) -> Result<(Vec<PartitionInBatch>, RecordBatch)> {
     let num_partitions = partitioning.partition_count();
     let num_rows = batch.num_rows();
 
     // compute partition indices
-    let hashes = evaluate_hashes(partitioning, &batch)
-        .expect(&format!("error evaluating hashes with {partitioning}"));
-    let part_ids = evaluate_partition_ids(hashes, partitioning.partition_count());
+    let part_ids = evaluate_partition_ids(partitioning, &batch, partition_id, row_offset).expect(
+        &format!("error evaluating partition ids with {partitioning}"),
+    );
 
     // compute partitions
     let mut partitions = vec![PartitionInBatch::default(); num_partitions];
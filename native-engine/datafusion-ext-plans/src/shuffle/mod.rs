@@ -17,23 +17,31 @@ use std::sync::{
     Arc,
 };
 
-use arrow::{error::Result as ArrowResult, record_batch::RecordBatch};
+use arrow::{
+    array::ArrayRef,
+    compute::cast,
+    datatypes::SchemaRef,
+    record_batch::RecordBatch,
+    row::{RowConverter, Rows, SortField},
+};
 use async_trait::async_trait;
 use bytesize::ByteSize;
 use datafusion::{
     common::Result,
     error::DataFusionError,
     execution::context::TaskContext,
+    physical_expr::{PhysicalExprRef, PhysicalSortExpr},
     physical_plan::{
         metrics::{BaselineMetrics, Count},
-        Partitioning, SendableRecordBatchStream,
+        SendableRecordBatchStream,
     },
 };
 use datafusion_ext_commons::{
-    array_size::ArraySize, spark_hash::create_murmur3_hashes,
-    streams::coalesce_stream::CoalesceInput,
+    array_size::ArraySize, df_execution_err, hash::mur::spark_compatible_murmur3_hash,
+    spark_hash::create_murmur3_hashes, streams::coalesce_stream::CoalesceInput,
 };
 use futures::StreamExt;
+use parking_lot::Mutex as SyncMutex;
 
 use crate::{common::output::TaskOutputter, memmgr::spill::Spill};
 
@@ -109,22 +117,206 @@ struct ShuffleSpill {
     offsets: Vec<u64>,
 }
 
-fn evaluate_hashes(partitioning: &Partitioning, batch: &RecordBatch) -> ArrowResult<Vec<i32>> {
-    match partitioning {
-        Partitioning::Hash(exprs, _) => {
-            let arrays = exprs
+/// Partitioning schemes supported by shuffle writers
+#[derive(Debug, Clone)]
+pub enum Partitioning {
+    Hash(Vec<PhysicalExprRef>, usize),
+    Range(RangePartitioning),
+    RoundRobin(usize),
+    Single,
+}
+
+impl Partitioning {
+    pub fn partition_count(&self) -> usize {
+        match self {
+            Partitioning::Hash(_, n) => *n,
+            Partitioning::Range(range) => range.num_partitions,
+            Partitioning::RoundRobin(n) => *n,
+            Partitioning::Single => 1,
+        }
+    }
+}
+
+impl std::fmt::Display for Partitioning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Partitioning::Hash(exprs, n) => {
+                let exprs = exprs.iter().map(|e| format!("{e}")).collect::<Vec<_>>();
+                write!(f, "Hash([{}], {n})", exprs.join(", "))
+            }
+            Partitioning::Range(range) => {
+                let exprs = range
+                    .sort_exprs
+                    .iter()
+                    .map(|e| format!("{e}"))
+                    .collect::<Vec<_>>();
+                write!(f, "Range([{}], {})", exprs.join(", "), range.num_partitions)
+            }
+            Partitioning::RoundRobin(n) => write!(f, "RoundRobin({n})"),
+            Partitioning::Single => write!(f, "Single"),
+        }
+    }
+}
+
+/// Range partitioning with bounds sampled by spark's RangePartitioner. rows
+/// are assigned to the first partition whose upper bound is not less than the
+/// row, compared in row format.
+#[derive(Debug, Clone)]
+pub struct RangePartitioning {
+    sort_exprs: Vec<PhysicalSortExpr>,
+    num_partitions: usize,
+    bounds: Arc<Rows>,
+    row_converter: Arc<SyncMutex<RowConverter>>,
+}
+
+impl RangePartitioning {
+    /// creates a range partitioning with upper bounds of the first n-1
+    /// partitions, given as one array for each sort expr
+    pub fn try_new(
+        sort_exprs: Vec<PhysicalSortExpr>,
+        num_partitions: usize,
+        bounds: Vec<ArrayRef>,
+        input_schema: &SchemaRef,
+    ) -> Result<Self> {
+        if bounds.len() != sort_exprs.len() {
+            return df_execution_err!(
+                "range partitioning: expect {} bound columns, got {}",
+                sort_exprs.len(),
+                bounds.len(),
+            );
+        }
+        let key_data_types = sort_exprs
+            .iter()
+            .map(|expr| expr.expr.data_type(input_schema))
+            .collect::<Result<Vec<_>>>()?;
+        let row_converter = RowConverter::new(
+            sort_exprs
                 .iter()
-                .map(|expr| Ok(expr.evaluate(batch)?.into_array(batch.num_rows())?))
-                .collect::<Result<Vec<_>>>()?;
+                .zip(&key_data_types)
+                .map(|(expr, dt)| SortField::new_with_options(dt.clone(), expr.options))
+                .collect(),
+        )?;
+        let bounds = bounds
+            .iter()
+            .zip(&key_data_types)
+            .map(|(bound, dt)| Ok(cast(bound, dt)?))
+            .collect::<Result<Vec<_>>>()?;
+        let bounds = row_converter.convert_columns(&bounds)?;
+        if bounds.num_rows() >= num_partitions.max(1) {
+            return df_execution_err!(
+                "range partitioning: too many bounds ({}) for {num_partitions} partitions",
+                bounds.num_rows(),
+            );
+        }
 
-            // compute hash array, use identical seed as spark hash partition
-            Ok(create_murmur3_hashes(arrays[0].len(), &arrays, 42))
+        Ok(Self {
+            sort_exprs,
+            num_partitions,
+            bounds: Arc::new(bounds),
+            row_converter: Arc::new(SyncMutex::new(row_converter)),
+        })
+    }
+
+    fn evaluate_partition_ids(&self, batch: &RecordBatch) -> Result<Vec<u32>> {
+        let key_cols = self
+            .sort_exprs
+            .iter()
+            .map(|expr| expr.expr.evaluate(batch)?.into_array(batch.num_rows()))
+            .collect::<Result<Vec<_>>>()?;
+        let rows = self.row_converter.lock().convert_columns(&key_cols)?;
+
+        Ok(rows
+            .iter()
+            .map(|row| {
+                // binary search for the number of bounds less than current row
+                let (mut lo, mut hi) = (0, self.bounds.num_rows());
+                while lo < hi {
+                    let mid = (lo + hi) / 2;
+                    if self.bounds.row(mid) < row {
+                        lo = mid + 1;
+                    } else {
+                        hi = mid;
+                    }
+                }
+                lo as u32
+            })
+            .collect())
+    }
+}
+
+/// evaluates output partition ids of a batch. partition_id and row_offset
+/// (number of rows already partitioned in this task) are used by round-robin
+/// partitioning to keep the output deterministic.
+fn evaluate_partition_ids(
+    partitioning: &Partitioning,
+    batch: &RecordBatch,
+    partition_id: usize,
+    row_offset: usize,
+) -> Result<Vec<u32>> {
+    match partitioning {
+        Partitioning::Hash(exprs, num_partitions) => {
+            let hashes = evaluate_hashes(exprs, batch)?;
+            Ok(evaluate_hash_partition_ids(hashes, *num_partitions))
         }
-        _ => unreachable!("unsupported partitioning: {:?}", partitioning),
+        Partitioning::Range(range) => range.evaluate_partition_ids(batch),
+        Partitioning::RoundRobin(num_partitions) => {
+            // spark increases position before assigning the first row
+            let start = round_robin_start_position(partition_id, *num_partitions) + row_offset + 1;
+            Ok((0..batch.num_rows())
+                .map(|i| ((start + i) % num_partitions) as u32)
+                .collect())
+        }
+        Partitioning::Single => Ok(vec![0; batch.num_rows()]),
     }
 }
 
-fn evaluate_partition_ids(mut hashes: Vec<i32>, num_partitions: usize) -> Vec<u32> {
+/// computes the start position of round-robin partitioning, identical to
+/// `new XORShiftRandom(partitionId).nextInt(numPartitions)` in spark
+fn round_robin_start_position(partition_id: usize, num_partitions: usize) -> usize {
+    // XORShiftRandom.hashSeed()
+    let seed_bytes = (partition_id as i64).to_be_bytes();
+    let low_bits = spark_compatible_murmur3_hash(seed_bytes, 0x3c074a61);
+    let high_bits = spark_compatible_murmur3_hash(seed_bytes, low_bits);
+    let mut seed = ((high_bits as i64) << 32) | (low_bits as i64 & 0xffffffff);
+
+    // XORShiftRandom.next(31)
+    let mut next = || {
+        seed ^= seed << 21;
+        seed ^= ((seed as u64) >> 35) as i64;
+        seed ^= seed << 4;
+        (seed & ((1i64 << 31) - 1)) as i32
+    };
+
+    // Random.nextInt(bound)
+    let bound = num_partitions as i32;
+    let m = bound - 1;
+    let mut r = next();
+    if bound & m == 0 {
+        r = ((bound as i64 * r as i64) >> 31) as i32;
+    } else {
+        let mut u = r;
+        loop {
+            r = u % bound;
+            if u.wrapping_sub(r).wrapping_add(m) >= 0 {
+                break;
+            }
+            u = next();
+        }
+    }
+    r as usize
+}
+
+fn evaluate_hashes(exprs: &[PhysicalExprRef], batch: &RecordBatch) -> Result<Vec<i32>> {
+    let arrays = exprs
+        .iter()
+        .map(|expr| Ok(expr.evaluate(batch)?.into_array(batch.num_rows())?))
+        .collect::<Result<Vec<_>>>()?;
+
+    // compute hash array, use identical seed as spark hash partition
+    Ok(create_murmur3_hashes(batch.num_rows(), &arrays, 42))
+}
+
+fn evaluate_hash_partition_ids(mut hashes: Vec<i32>, num_partitions: usize) -> Vec<u32> {
     // evaluate part_id = pmod(hash, num_partitions)
     for h in &mut hashes {
         *h = h.rem_euclid(num_partitions as i32);
@@ -135,3 +327,109 @@ fn evaluate_partition_ids(mut hashes: Vec<i32>, num_partitions: usize) -> Vec<u3
         std::mem::transmute(hashes)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{ArrayRef, Int32Array},
+        compute::SortOptions,
+        datatypes::{DataType, Field, Schema},
+        record_batch::RecordBatch,
+    };
+    use datafusion::{
+        common::Result,
+        physical_expr::{expressions::Column, PhysicalSortExpr},
+    };
+
+    use crate::shuffle::{
+        evaluate_partition_ids, round_robin_start_position, Partitioning, RangePartitioning,
+    };
+
+    fn range_partition_ids(
+        values: Vec<Option<i32>>,
+        bounds: Vec<i32>,
+        options: SortOptions,
+    ) -> Result<Vec<u32>> {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, true)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from(values)) as ArrayRef],
+        )?;
+        let num_partitions = bounds.len() + 1;
+        let partitioning = Partitioning::Range(RangePartitioning::try_new(
+            vec![PhysicalSortExpr {
+                expr: Arc::new(Column::new("a", 0)),
+                options,
+            }],
+            num_partitions,
+            vec![Arc::new(Int32Array::from(bounds))],
+            &schema,
+        )?);
+        evaluate_partition_ids(&partitioning, &batch, 0, 0)
+    }
+
+    #[test]
+    fn test_range_partitioning() -> Result<()> {
+        let values = vec![
+            Some(1),
+            Some(10),
+            Some(11),
+            None,
+            Some(25),
+            Some(20),
+            Some(100),
+        ];
+        let part_ids = range_partition_ids(
+            values.clone(),
+            vec![10, 20],
+            SortOptions {
+                descending: false,
+                nulls_first: true,
+            },
+        )?;
+        assert_eq!(part_ids, vec![0, 0, 1, 0, 2, 1, 2]);
+
+        let part_ids = range_partition_ids(
+            values.clone(),
+            vec![20, 10],
+            SortOptions {
+                descending: true,
+                nulls_first: false,
+            },
+        )?;
+        assert_eq!(part_ids, vec![2, 1, 1, 2, 0, 0, 0]);
+
+        let part_ids = range_partition_ids(values, vec![], SortOptions::default())?;
+        assert_eq!(part_ids, vec![0; 7]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_round_robin_partitioning() -> Result<()> {
+        // expected values are from spark's
+        // XORShiftRandom(partitionId).nextInt(numPartitions)
+        assert_eq!(round_robin_start_position(0, 200), 28);
+        assert_eq!(round_robin_start_position(1, 200), 103);
+        assert_eq!(round_robin_start_position(7, 200), 160);
+        assert_eq!(round_robin_start_position(5, 16), 14);
+        assert_eq!(round_robin_start_position(3, 7), 6);
+
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, true)]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![Arc::new(Int32Array::from(vec![1, 2, 3, 4, 5])) as ArrayRef],
+        )?;
+        let partitioning = Partitioning::RoundRobin(7);
+        assert_eq!(
+            evaluate_partition_ids(&partitioning, &batch, 3, 0)?,
+            vec![0, 1, 2, 3, 4],
+        );
+        assert_eq!(
+            evaluate_partition_ids(&partitioning, &batch, 3, 5)?,
+            vec![5, 6, 0, 1, 2],
+        );
+        Ok(())
+    }
+}
@@ -16,17 +16,14 @@ use std::sync::Weak;
 
 use arrow::record_batch::RecordBatch;
 use async_trait::async_trait;
-use datafusion::{
-    common::Result,
-    physical_plan::{metrics::Time, Partitioning},
-};
+use datafusion::{common::Result, physical_plan::metrics::Time};
 use datafusion_ext_commons::{array_size::ArraySize, df_execution_err};
 use futures::lock::Mutex;
 use jni::objects::GlobalRef;
 
 use crate::{
     memmgr::{MemConsumer, MemConsumerInfo, MemManager},
-    shuffle::{buffered_data::BufferedData, ShuffleRepartitioner},
+    shuffle::{buffered_data::BufferedData, Partitioning, ShuffleRepartitioner},
 };
 
 pub struct RssSortShuffleRepartitioner {
@@ -23,10 +23,7 @@ use async_trait::async_trait;
 use bytesize::ByteSize;
 use datafusion::{
     common::{DataFusionError, Result},
-    physical_plan::{
-        metrics::{ExecutionPlanMetricsSet, Time},
-        Partitioning,
-    },
+    physical_plan::metrics::{ExecutionPlanMetricsSet, Time},
 };
 use datafusion_ext_commons::{
     array_size::ArraySize,
@@ -42,7 +39,7 @@ use crate::{
         spill::{try_new_spill, Spill},
         MemConsumer, MemConsumerInfo, MemManager,
     },
-    shuffle::{buffered_data::BufferedData, ShuffleRepartitioner, ShuffleSpill},
+    shuffle::{buffered_data::BufferedData, Partitioning, ShuffleRepartitioner, ShuffleSpill},
 };
 
 pub struct SortShuffleRepartitioner {
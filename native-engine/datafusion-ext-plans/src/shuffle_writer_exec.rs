@@ -25,8 +25,9 @@ use datafusion::{
     physical_plan::{
         metrics::{BaselineMetrics, ExecutionPlanMetricsSet, MetricBuilder, MetricsSet},
         stream::RecordBatchStreamAdapter,
-        DisplayAs, DisplayFormatType, ExecutionMode, ExecutionPlan, Partitioning, PlanProperties,
-        SendableRecordBatchStream, Statistics,
+        DisplayAs, DisplayFormatType, ExecutionMode, ExecutionPlan,
+        Partitioning as DataFusionPartitioning, PlanProperties, SendableRecordBatchStream,
+        Statistics,
     },
 };
 use datafusion_ext_commons::df_execution_err;
@@ -41,7 +42,7 @@ use crate::{
     memmgr::MemManager,
     shuffle::{
         single_repartitioner::SingleShuffleRepartitioner,
-        sort_repartitioner::SortShuffleRepartitioner, ShuffleRepartitioner,
+        sort_repartitioner::SortShuffleRepartitioner, Partitioning, ShuffleRepartitioner,
     },
 };
 
@@ -82,7 +83,7 @@ impl ExecutionPlan for ShuffleWriterExec {
         self.props.get_or_init(|| {
             PlanProperties::new(
                 EquivalenceProperties::new(self.schema()),
-                self.partitioning.clone(),
+                DataFusionPartitioning::UnknownPartitioning(self.partitioning.partition_count()),
                 ExecutionMode::Bounded,
             )
         })
@@ -123,7 +124,7 @@ impl ExecutionPlan for ShuffleWriterExec {
                 output_time,
                 BaselineMetrics::new(&self.metrics, partition),
             )),
-            Partitioning::Hash(..) => {
+            _ => {
                 let sort_time = self.metrics.register_timer("sort_time", partition);
                 let partitioner = Arc::new(SortShuffleRepartitioner::new(
                     partition,
@@ -137,7 +138,6 @@ impl ExecutionPlan for ShuffleWriterExec {
                 MemManager::register_consumer(partitioner.clone(), true);
                 partitioner
             }
-            p => unreachable!("unsupported partitioning: {:?}", p),
         };
 
         let input = stat_input(
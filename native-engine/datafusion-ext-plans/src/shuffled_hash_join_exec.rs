@@ -0,0 +1,569 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    fmt::{Debug, Formatter},
+    sync::Arc,
+};
+
+use arrow::{
+    array::RecordBatch,
+    compute::{concat_batches, SortOptions},
+    datatypes::{DataType, SchemaRef},
+};
+use datafusion::{
+    common::{DataFusionError, JoinSide, Result, Statistics},
+    execution::context::TaskContext,
+    physical_expr::{EquivalenceProperties, PhysicalExprRef},
+    physical_plan::{
+        joins::utils::JoinOn,
+        metrics::{BaselineMetrics, ExecutionPlanMetricsSet, MetricsSet, Time},
+        stream::RecordBatchStreamAdapter,
+        DisplayAs, DisplayFormatType, ExecutionMode, ExecutionPlan, ExecutionPlanProperties,
+        PlanProperties, SendableRecordBatchStream,
+    },
+};
+use datafusion_ext_commons::{
+    batch_size, df_execution_err, streams::coalesce_stream::CoalesceInput,
+};
+use futures::{future::BoxFuture, StreamExt, TryStreamExt};
+use once_cell::sync::OnceCell;
+
+use crate::{
+    broadcast_join_exec::{create_joiner, execute_join_with_map},
+    common::{
+        batch_statisitcs::{stat_input, InputBatchStatistics},
+        column_pruning::ExecuteWithColumnPruning,
+        output::{TaskOutputter, WrappedRecordBatchSender},
+        timer_helper::{RegisterTimer, TimerHelper},
+    },
+    joins::{
+        join_hash_map::JoinHashMap,
+        join_utils::JoinType,
+        shj::{BucketedBuffer, BucketedSpillReader, MAX_SPILL_LEVELS, NUM_SPILL_BUCKETS},
+        JoinParams, JoinProjection,
+    },
+    memmgr::{metrics::SpillMetrics, spill::Spill, MemConsumer, MemManager},
+};
+
+/// Hash join of two shuffled inputs. The build side is collected into a
+/// spillable buffer. If it fits in memory, the probed side is streamed through
+/// an in-memory hash map. Otherwise both sides are partitioned into buckets
+/// and joined bucket by bucket (grace hash join).
+#[derive(Debug)]
+pub struct ShuffledHashJoinExec {
+    left: Arc<dyn ExecutionPlan>,
+    right: Arc<dyn ExecutionPlan>,
+    on: JoinOn,
+    join_type: JoinType,
+    build_side: JoinSide,
+    schema: SchemaRef,
+    force_spill: bool,
+    metrics: ExecutionPlanMetricsSet,
+    props: OnceCell<PlanProperties>,
+}
+
+impl ShuffledHashJoinExec {
+    pub fn try_new(
+        schema: SchemaRef,
+        left: Arc<dyn ExecutionPlan>,
+        right: Arc<dyn ExecutionPlan>,
+        on: JoinOn,
+        join_type: JoinType,
+        build_side: JoinSide,
+    ) -> Result<Self> {
+        Ok(Self {
+            left,
+            right,
+            on,
+            join_type,
+            build_side,
+            schema,
+            force_spill: false,
+            metrics: ExecutionPlanMetricsSet::new(),
+            props: OnceCell::new(),
+        })
+    }
+
+    #[cfg(test)]
+    pub(crate) fn with_force_spill(mut self) -> Self {
+        self.force_spill = true;
+        self
+    }
+
+    pub fn on(&self) -> &JoinOn {
+        &self.on
+    }
+
+    pub fn join_type(&self) -> JoinType {
+        self.join_type
+    }
+
+    pub fn build_side(&self) -> JoinSide {
+        self.build_side
+    }
+
+    fn create_join_params(&self, projection: &[usize]) -> Result<JoinParams> {
+        let left_schema = self.left.schema();
+        let right_schema = self.right.schema();
+        let (left_keys, right_keys): (Vec<PhysicalExprRef>, Vec<PhysicalExprRef>) =
+            self.on.iter().cloned().unzip();
+        let key_data_types: Vec<DataType> = self
+            .on
+            .iter()
+            .map(|(left_key, right_key)| {
+                Ok({
+                    let left_dt = left_key.data_type(&left_schema)?;
+                    let right_dt = right_key.data_type(&right_schema)?;
+                    if left_dt != right_dt {
+                        df_execution_err!(
+                            "join key data type differs {left_dt:?} <-> {right_dt:?}"
+                        )?;
+                    }
+                    left_dt
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        let projection = JoinProjection::try_new(
+            self.join_type,
+            &self.schema,
+            &left_schema,
+            &right_schema,
+            projection,
+        )?;
+
+        Ok(JoinParams {
+            join_type: self.join_type,
+            left_schema,
+            right_schema,
+            output_schema: self.schema(),
+            left_keys,
+            right_keys,
+            batch_size: batch_size(),
+            sort_options: vec![SortOptions::default(); self.on.len()],
+            projection,
+            key_data_types,
+            band_condition: None,
+        })
+    }
+
+    fn execute_with_projection(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+        projection: Vec<usize>,
+    ) -> Result<SendableRecordBatchStream> {
+        let metrics = self.metrics.clone();
+        let baseline_metrics = Arc::new(BaselineMetrics::new(&metrics, partition));
+        let join_params = self.create_join_params(&projection)?;
+        let left = self.left.execute(partition, context.clone())?;
+        let right = self.right.execute(partition, context.clone())?;
+        let build_side = self.build_side;
+        let force_spill = self.force_spill;
+
+        // stat probed side
+        let input_batch_stat =
+            InputBatchStatistics::from_metrics_set_and_blaze_conf(&self.metrics, partition)?;
+        let left = stat_input(input_batch_stat.clone(), left)?;
+        let right = stat_input(input_batch_stat.clone(), right)?;
+
+        let baseline_metrics_cloned = baseline_metrics.clone();
+        let context_cloned = context.clone();
+        let output_stream = Box::pin(RecordBatchStreamAdapter::new(
+            join_params.projection.schema.clone(),
+            futures::stream::once(async move {
+                context_cloned.output_with_sender(
+                    "ShuffledHashJoin",
+                    join_params.projection.schema.clone(),
+                    move |sender| {
+                        sender.exclude_time(baseline_metrics_cloned.elapsed_compute());
+                        execute_join(
+                            partition,
+                            left,
+                            right,
+                            join_params,
+                            build_side,
+                            force_spill,
+                            metrics,
+                            sender,
+                        )
+                    },
+                )
+            })
+            .try_flatten(),
+        ));
+        Ok(context.coalesce_with_default_batch_size(output_stream, &baseline_metrics)?)
+    }
+}
+
+impl ExecuteWithColumnPruning for ShuffledHashJoinExec {
+    fn execute_projected(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+        projection: &[usize],
+    ) -> Result<SendableRecordBatchStream> {
+        self.execute_with_projection(partition, context, projection.to_vec())
+    }
+}
+
+impl ExecutionPlan for ShuffledHashJoinExec {
+    fn name(&self) -> &str {
+        "ShuffledHashJoin"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        self.props.get_or_init(|| {
+            PlanProperties::new(
+                EquivalenceProperties::new(self.schema()),
+                match self.build_side {
+                    JoinSide::Left => self.right.output_partitioning().clone(),
+                    JoinSide::Right => self.left.output_partitioning().clone(),
+                },
+                ExecutionMode::Bounded,
+            )
+        })
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![&self.left, &self.right]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let mut new = Self::try_new(
+            self.schema.clone(),
+            children[0].clone(),
+            children[1].clone(),
+            self.on.iter().cloned().collect(),
+            self.join_type,
+            self.build_side,
+        )?;
+        new.force_spill = self.force_spill;
+        Ok(Arc::new(new))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let projection = (0..self.schema.fields().len()).collect();
+        self.execute_with_projection(partition, context, projection)
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+
+    fn statistics(&self) -> Result<Statistics> {
+        unimplemented!()
+    }
+}
+
+impl DisplayAs for ShuffledHashJoinExec {
+    fn fmt_as(&self, _t: DisplayFormatType, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "ShuffledHashJoin")
+    }
+}
+
+async fn execute_join(
+    partition: usize,
+    left: SendableRecordBatchStream,
+    right: SendableRecordBatchStream,
+    join_params: JoinParams,
+    build_side: JoinSide,
+    force_spill: bool,
+    metrics: ExecutionPlanMetricsSet,
+    sender: Arc<WrappedRecordBatchSender>,
+) -> Result<()> {
+    let baseline_metrics = Arc::new(BaselineMetrics::new(&metrics, partition));
+    let spill_metrics = SpillMetrics::new(&metrics, partition);
+    let build_time = metrics.register_timer("build_hash_map_time", partition);
+    let probed_side_hash_time = metrics.register_timer("probed_side_hash_time", partition);
+    let probed_side_search_time = metrics.register_timer("probed_side_search_time", partition);
+    let probed_side_compare_time = metrics.register_timer("probed_side_compare_time", partition);
+    let build_output_time = metrics.register_timer("build_output_time", partition);
+
+    let (probed_input, mut built_input) = match build_side {
+        JoinSide::Left => (right, left),
+        JoinSide::Right => (left, right),
+    };
+    let (built_keys, probed_keys) = match build_side {
+        JoinSide::Left => (
+            join_params.left_keys.clone(),
+            join_params.right_keys.clone(),
+        ),
+        JoinSide::Right => (
+            join_params.right_keys.clone(),
+            join_params.left_keys.clone(),
+        ),
+    };
+    let built_schema = built_input.schema();
+    let probed_schema = probed_input.schema();
+
+    let built_buffer = Arc::new(BucketedBuffer::new(
+        format!("ShuffledHashJoin[partition={partition}].built"),
+        built_keys.clone(),
+        0,
+        spill_metrics.clone(),
+    ));
+    MemManager::register_consumer(built_buffer.clone(), true);
+
+    // fetch two sides asynchronously to eagerly fetch probed side
+    let (mut probed, _) = futures::try_join!(
+        async {
+            let mut probed_peeked = Box::pin(probed_input.peekable());
+            probed_peeked.as_mut().peek().await;
+            Ok(Box::pin(RecordBatchStreamAdapter::new(
+                probed_schema.clone(),
+                probed_peeked,
+            )))
+        },
+        async {
+            while let Some(batch) = built_input.next().await.transpose()? {
+                built_buffer.insert_batch(batch).await?;
+            }
+            if force_spill {
+                built_buffer.spill().await?;
+            }
+            Ok::<_, DataFusionError>(())
+        }
+    )?;
+
+    // built side fits in memory, join with a single hash map
+    if !built_buffer.is_spilled() {
+        let built_batches = built_buffer.take_in_mem_batches();
+        let map = build_join_hash_map(&built_schema, &built_batches, &built_keys, &build_time)?;
+        drop(built_batches);
+        built_buffer.update_mem_used(map.mem_size()).await?;
+        baseline_metrics
+            .elapsed_compute()
+            .add_duration(build_time.duration());
+
+        return execute_join_with_map(
+            probed,
+            map,
+            join_params,
+            build_side,
+            baseline_metrics.clone(),
+            probed_side_hash_time,
+            probed_side_search_time,
+            probed_side_compare_time,
+            build_output_time,
+            sender,
+        )
+        .await;
+    }
+    log::info!(
+        "ShuffledHashJoin[partition={partition}] built side spilled, joining in {NUM_SPILL_BUCKETS} buckets"
+    );
+    let built_spills = built_buffer.into_spills().await?;
+
+    // partition probed side into buckets
+    let probed_buffer = Arc::new(BucketedBuffer::new(
+        format!("ShuffledHashJoin[partition={partition}].probed"),
+        probed_keys.clone(),
+        0,
+        spill_metrics.clone(),
+    ));
+    MemManager::register_consumer(probed_buffer.clone(), true);
+    while let Some(batch) = probed.next().await.transpose()? {
+        probed_buffer.insert_batch(batch).await?;
+    }
+    let probed_spills = probed_buffer.into_spills().await?;
+
+    // join bucket by bucket
+    let _timer = baseline_metrics.elapsed_compute().timer();
+    let bucket_join_context = BucketJoinContext {
+        partition,
+        join_params,
+        build_side,
+        built_schema,
+        probed_schema,
+        built_keys,
+        probed_keys,
+        force_spill,
+        spill_metrics,
+        build_time,
+        probed_side_hash_time,
+        probed_side_search_time,
+        probed_side_compare_time,
+        build_output_time,
+        sender,
+    };
+    let num_output_rows =
+        join_buckets(&bucket_join_context, 0, built_spills, probed_spills).await?;
+    baseline_metrics.record_output(num_output_rows);
+    Ok(())
+}
+
+struct BucketJoinContext {
+    partition: usize,
+    join_params: JoinParams,
+    build_side: JoinSide,
+    built_schema: SchemaRef,
+    probed_schema: SchemaRef,
+    built_keys: Vec<PhysicalExprRef>,
+    probed_keys: Vec<PhysicalExprRef>,
+    force_spill: bool,
+    spill_metrics: SpillMetrics,
+    build_time: Time,
+    probed_side_hash_time: Time,
+    probed_side_search_time: Time,
+    probed_side_compare_time: Time,
+    build_output_time: Time,
+    sender: Arc<WrappedRecordBatchSender>,
+}
+
+/// Joins spilled buckets of the given level one by one, returns the number of
+/// output rows. The built side of each bucket is loaded into a memory-managed
+/// buffer, and a bucket that is still too large for memory is re-partitioned
+/// into the next level together with its probed side.
+fn join_buckets<'a>(
+    ctx: &'a BucketJoinContext,
+    level: usize,
+    built_spills: Vec<Box<dyn Spill>>,
+    probed_spills: Vec<Box<dyn Spill>>,
+) -> BoxFuture<'a, Result<usize>> {
+    Box::pin(async move {
+        let partition = ctx.partition;
+        let sub_level = level + 1;
+        let mut built_readers = built_spills
+            .iter()
+            .map(|spill| BucketedSpillReader::new(spill, ctx.built_schema.clone()))
+            .collect::<Vec<_>>();
+        let mut probed_readers = probed_spills
+            .iter()
+            .map(|spill| BucketedSpillReader::new(spill, ctx.probed_schema.clone()))
+            .collect::<Vec<_>>();
+        let mut num_output_rows = 0;
+
+        for bucket_id in 0..NUM_SPILL_BUCKETS {
+            for reader in built_readers.iter_mut().chain(&mut probed_readers) {
+                reader.next_bucket()?;
+            }
+
+            // buckets of the last level are never spilled again, since rows with
+            // the same key cannot be further partitioned
+            let built_buffer = Arc::new(BucketedBuffer::new(
+                format!(
+                    "ShuffledHashJoin[partition={partition}].built[level={sub_level}, bucket={bucket_id}]"
+                ),
+                ctx.built_keys.clone(),
+                sub_level,
+                ctx.spill_metrics.clone(),
+            ));
+            MemManager::register_consumer(built_buffer.clone(), sub_level < MAX_SPILL_LEVELS);
+            for reader in &mut built_readers {
+                while let Some(batch) = reader.next_batch()? {
+                    built_buffer.insert_batch(batch).await?;
+                }
+            }
+            if ctx.force_spill && sub_level + 1 < MAX_SPILL_LEVELS {
+                built_buffer.spill().await?;
+            }
+
+            if !built_buffer.is_spilled() {
+                let built_batches = built_buffer.take_in_mem_batches();
+                let map = build_join_hash_map(
+                    &ctx.built_schema,
+                    &built_batches,
+                    &ctx.built_keys,
+                    &ctx.build_time,
+                )?;
+                drop(built_batches);
+                built_buffer.update_mem_used(map.mem_size()).await?;
+
+                let mut joiner = create_joiner(
+                    ctx.build_side,
+                    ctx.join_params.clone(),
+                    map,
+                    ctx.sender.clone(),
+                );
+                'probing: for reader in &mut probed_readers {
+                    while let Some(batch) = reader.next_batch()? {
+                        joiner
+                            .as_mut()
+                            .join(
+                                batch,
+                                &ctx.probed_side_hash_time,
+                                &ctx.probed_side_search_time,
+                                &ctx.probed_side_compare_time,
+                                &ctx.build_output_time,
+                            )
+                            .await?;
+
+                        if joiner.can_early_stop() {
+                            break 'probing;
+                        }
+                    }
+                }
+                joiner.as_mut().finish(&ctx.build_output_time).await?;
+                num_output_rows += joiner.num_output_rows();
+                continue;
+            }
+
+            // bucket is too large for memory, re-partition both sides of it
+            // into the next level
+            log::info!(
+                "ShuffledHashJoin[partition={partition}] bucket {bucket_id} of level {level} spilled, re-partitioning into {NUM_SPILL_BUCKETS} buckets"
+            );
+            let sub_built_spills = built_buffer.into_spills().await?;
+            let probed_buffer = Arc::new(BucketedBuffer::new(
+                format!(
+                    "ShuffledHashJoin[partition={partition}].probed[level={sub_level}, bucket={bucket_id}]"
+                ),
+                ctx.probed_keys.clone(),
+                sub_level,
+                ctx.spill_metrics.clone(),
+            ));
+            MemManager::register_consumer(probed_buffer.clone(), true);
+            for reader in &mut probed_readers {
+                while let Some(batch) = reader.next_batch()? {
+                    probed_buffer.insert_batch(batch).await?;
+                }
+            }
+            let sub_probed_spills = probed_buffer.into_spills().await?;
+            num_output_rows +=
+                join_buckets(ctx, sub_level, sub_built_spills, sub_probed_spills).await?;
+        }
+        Ok(num_output_rows)
+    })
+}
+
+fn build_join_hash_map(
+    data_schema: &SchemaRef,
+    data_batches: &[RecordBatch],
+    key_exprs: &[PhysicalExprRef],
+    build_time: &Time,
+) -> Result<Arc<JoinHashMap>> {
+    build_time.with_timer(|| {
+        let data_batch = concat_batches(data_schema, data_batches)?;
+        let join_hash_map = JoinHashMap::create_from_data_batch(data_batch, key_exprs)?;
+        Ok::<_, DataFusionError>(Arc::new(join_hash_map))
+    })
+}
@@ -17,7 +17,7 @@ use std::{any::Any, fmt::Formatter, pin::Pin, sync::Arc};
 use arrow::{compute::SortOptions, datatypes::SchemaRef};
 use async_trait::async_trait;
 use datafusion::{
-    common::{DataFusionError, JoinSide},
+    common::DataFusionError,
     error::Result,
     execution::context::TaskContext,
     physical_expr::{EquivalenceProperties, PhysicalExprRef},
@@ -45,12 +45,13 @@ use crate::{
     joins::{
         join_utils::{JoinType, JoinType::*},
         smj::{
+            band_join::BandJoiner,
             existence_join::ExistenceJoiner,
             full_join::{FullOuterJoiner, InnerJoiner, LeftOuterJoiner, RightOuterJoiner},
             semi_join::{LeftAntiJoiner, LeftSemiJoiner, RightAntiJoiner, RightSemiJoiner},
         },
         stream_cursor::StreamCursor,
-        JoinParams, JoinProjection, StreamCursors,
+        JoinBandCondition, JoinParams, JoinProjection, StreamCursors,
     },
 };
 
@@ -61,6 +62,7 @@ pub struct SortMergeJoinExec {
     on: JoinOn,
     join_type: JoinType,
     sort_options: Vec<SortOptions>,
+    band_condition: Option<JoinBandCondition>,
     schema: SchemaRef,
     metrics: ExecutionPlanMetricsSet,
     props: OnceCell<PlanProperties>,
@@ -74,6 +76,7 @@ impl SortMergeJoinExec {
         on: JoinOn,
         join_type: JoinType,
         sort_options: Vec<SortOptions>,
+        band_condition: Option<JoinBandCondition>,
     ) -> Result<Self> {
         Ok(Self {
             schema,
@@ -82,6 +85,7 @@ impl SortMergeJoinExec {
             on,
             join_type,
             sort_options,
+            band_condition,
             metrics: ExecutionPlanMetricsSet::new(),
             props: OnceCell::new(),
         })
@@ -127,6 +131,7 @@ impl SortMergeJoinExec {
             sort_options: self.sort_options.clone(),
             projection,
             batch_size: batch_size(),
+            band_condition: self.band_condition.clone(),
         })
     }
 
@@ -164,7 +169,19 @@ impl DisplayAs for SortMergeJoinExec {
             f,
             "SortMergeJoin: join_type={:?}, on={:?}, schema={:?}",
             self.join_type, self.on, self.schema,
-        )
+        )?;
+        if let Some(band) = &self.band_condition {
+            write!(
+                f,
+                ", band=({:?} {} {:?} {} {:?})",
+                band.lower_expr,
+                if band.lower_inclusive { "<=" } else { "<" },
+                band.point_expr,
+                if band.upper_inclusive { "<=" } else { "<" },
+                band.upper_expr,
+            )?;
+        }
+        Ok(())
     }
 }
 
@@ -217,6 +234,7 @@ impl ExecutionPlan for SortMergeJoinExec {
             self.on.clone(),
             self.join_type,
             self.sort_options.clone(),
+            self.band_condition.clone(),
         )?))
     }
 
@@ -248,22 +266,7 @@ pub async fn execute_join(
     let _timer = metrics.elapsed_compute().timer();
     let poll_time = Time::new();
 
-    let mut curs = (
-        StreamCursor::try_new(
-            lstream,
-            poll_time.clone(),
-            &join_params,
-            JoinSide::Left,
-            &join_params.projection.left,
-        )?,
-        StreamCursor::try_new(
-            rstream,
-            poll_time.clone(),
-            &join_params,
-            JoinSide::Right,
-            &join_params.projection.right,
-        )?,
-    );
+    let mut curs = StreamCursor::try_new_pair(lstream, rstream, poll_time.clone(), &join_params)?;
 
     // start first batches of both side asynchronously
     tokio::try_join!(
@@ -273,6 +276,7 @@ pub async fn execute_join(
 
     let join_type = join_params.join_type;
     let mut joiner: Pin<Box<dyn Joiner + Send>> = match join_type {
+        _ if join_params.band_condition.is_some() => Box::pin(BandJoiner::new(join_params, sender)),
         Inner => Box::pin(InnerJoiner::new(join_params, sender)),
         Left => Box::pin(LeftOuterJoiner::new(join_params, sender)),
         Right => Box::pin(RightOuterJoiner::new(join_params, sender)),
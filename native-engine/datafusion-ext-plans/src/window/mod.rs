@@ -14,26 +14,41 @@
 
 use std::sync::Arc;
 
-use arrow::{array::ArrayRef, datatypes::FieldRef, record_batch::RecordBatch};
-use datafusion::{common::Result, physical_expr::PhysicalExpr};
+use arrow::{
+    array::ArrayRef,
+    datatypes::{DataType, FieldRef},
+    record_batch::RecordBatch,
+};
+use datafusion::{
+    common::{Result, ScalarValue},
+    physical_expr::{expressions::Literal, PhysicalExpr},
+};
+use datafusion_ext_commons::{df_execution_err, downcast_any};
 
 use crate::{
     agg::{create_agg, AggFunction},
     window::{
         processors::{
-            agg_processor::AggProcessor, rank_processor::RankProcessor,
+            agg_processor::AggProcessor,
+            distribution_processor::{DistributionProcessor, DistributionType},
+            frame_agg_processor::{validate_frame, FrameAggProcessor},
+            lag_lead_processor::LagLeadProcessor,
+            nth_value_processor::NthValueProcessor,
+            rank_processor::RankProcessor,
             row_number_processor::RowNumberProcessor,
         },
         window_context::WindowContext,
     },
 };
 
+pub mod partition_buffer;
 pub mod processors;
 pub mod window_context;
 
 #[derive(Debug, Clone, Copy)]
 pub enum WindowFunction {
     RankLike(WindowRankType),
+    Offset(WindowOffsetType),
     Agg(AggFunction),
 }
 
@@ -42,6 +57,57 @@ pub enum WindowRankType {
     RowNumber,
     Rank,
     DenseRank,
+    PercentRank,
+    CumeDist,
+    Ntile,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum WindowOffsetType {
+    Lag,
+    Lead,
+    NthValue,
+    FirstValue,
+    LastValue,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowFrameType {
+    Rows,
+    Range,
+}
+
+/// Frame bound. Offsets of ROWS frames are numbers of rows, offsets of RANGE
+/// frames are added to/subtracted from the value of the only order expr.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WindowFrameBound {
+    UnboundedPreceding,
+    Preceding(ScalarValue),
+    CurrentRow,
+    Following(ScalarValue),
+    UnboundedFollowing,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowFrame {
+    pub frame_type: WindowFrameType,
+    pub start: WindowFrameBound,
+    pub end: WindowFrameBound,
+}
+
+impl WindowFrame {
+    /// ROWS BETWEEN UNBOUNDED PRECEDING AND CURRENT ROW
+    pub fn running() -> Self {
+        Self {
+            frame_type: WindowFrameType::Rows,
+            start: WindowFrameBound::UnboundedPreceding,
+            end: WindowFrameBound::CurrentRow,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self == &Self::running()
+    }
 }
 
 pub trait WindowFunctionProcessor: Send + Sync {
@@ -58,6 +124,8 @@ pub struct WindowExpr {
     field: FieldRef,
     func: WindowFunction,
     children: Vec<Arc<dyn PhysicalExpr>>,
+    frame: WindowFrame,
+    ignore_nulls: bool,
 }
 
 impl WindowExpr {
@@ -70,6 +138,50 @@ impl WindowExpr {
             field,
             func,
             children,
+            frame: WindowFrame::running(),
+            ignore_nulls: false,
+        }
+    }
+
+    pub fn with_frame(mut self, frame: WindowFrame) -> Self {
+        self.frame = frame;
+        self
+    }
+
+    /// skips null values in offset functions
+    pub fn with_ignore_nulls(mut self, ignore_nulls: bool) -> Self {
+        self.ignore_nulls = ignore_nulls;
+        self
+    }
+
+    /// whether processor of this expr requires every input batch to contain
+    /// only whole partitions
+    pub fn requires_whole_partitions(&self) -> bool {
+        match self.func {
+            WindowFunction::RankLike(
+                WindowRankType::RowNumber | WindowRankType::Rank | WindowRankType::DenseRank,
+            ) => false,
+            WindowFunction::RankLike(
+                WindowRankType::PercentRank | WindowRankType::CumeDist | WindowRankType::Ntile,
+            ) => true,
+            WindowFunction::Offset(_) => true,
+            WindowFunction::Agg(_) => !self.frame.is_running(),
+        }
+    }
+
+    /// checks the frame at planning time, so that unsupported frames are not
+    /// found only after execution starts
+    pub fn validate(&self, context: &WindowContext) -> Result<()> {
+        match self.func {
+            WindowFunction::Offset(
+                WindowOffsetType::NthValue
+                | WindowOffsetType::FirstValue
+                | WindowOffsetType::LastValue,
+            ) => validate_frame(&self.frame, context),
+            WindowFunction::Agg(_) if !self.frame.is_running() => {
+                validate_frame(&self.frame, context)
+            }
+            _ => Ok(()),
         }
     }
 
@@ -87,10 +199,85 @@ impl WindowExpr {
             WindowFunction::RankLike(WindowRankType::DenseRank) => {
                 Ok(Box::new(RankProcessor::new(true)))
             }
+            WindowFunction::RankLike(WindowRankType::PercentRank) => Ok(Box::new(
+                DistributionProcessor::new(DistributionType::PercentRank),
+            )),
+            WindowFunction::RankLike(WindowRankType::CumeDist) => Ok(Box::new(
+                DistributionProcessor::new(DistributionType::CumeDist),
+            )),
+            WindowFunction::RankLike(WindowRankType::Ntile) => {
+                // children: number of buckets
+                if self.children.len() != 1 {
+                    return df_execution_err!("window: Ntile requires 1 child");
+                }
+                let num_buckets = match literal_int(&self.children[0])? {
+                    n if n > 0 => n as usize,
+                    n => return df_execution_err!("window: invalid ntile buckets: {n}"),
+                };
+                Ok(Box::new(DistributionProcessor::new(
+                    DistributionType::Ntile(num_buckets),
+                )))
+            }
+            WindowFunction::Offset(
+                offset_type @ (WindowOffsetType::Lag | WindowOffsetType::Lead),
+            ) => {
+                // children: input, offset, default
+                if self.children.len() != 3 {
+                    return df_execution_err!("window: {offset_type:?} requires 3 children");
+                }
+                Ok(Box::new(LagLeadProcessor::new(
+                    matches!(offset_type, WindowOffsetType::Lead),
+                    self.children[0].clone(),
+                    literal_int(&self.children[1])?,
+                    self.children[2].clone(),
+                    self.ignore_nulls,
+                )))
+            }
+            WindowFunction::Offset(offset_type) => {
+                // children: input, n (only for nth_value)
+                let nth = match offset_type {
+                    WindowOffsetType::NthValue => {
+                        if self.children.len() != 2 {
+                            return df_execution_err!("window: NthValue requires 2 children");
+                        }
+                        match literal_int(&self.children[1])? {
+                            n if n > 0 => Some(n as usize),
+                            n => return df_execution_err!("window: invalid nth_value offset: {n}"),
+                        }
+                    }
+                    WindowOffsetType::FirstValue => Some(1),
+                    WindowOffsetType::LastValue => None,
+                    WindowOffsetType::Lag | WindowOffsetType::Lead => unreachable!(),
+                };
+                Ok(Box::new(NthValueProcessor::try_new(
+                    self.children[0].clone(),
+                    nth,
+                    self.ignore_nulls,
+                    self.frame.clone(),
+                    context,
+                )?))
+            }
             WindowFunction::Agg(agg_func) => {
                 let agg = create_agg(agg_func, &self.children, &context.input_schema)?;
-                Ok(Box::new(AggProcessor::try_new(agg)?))
+                if self.frame.is_running() {
+                    return Ok(Box::new(AggProcessor::try_new(agg)?));
+                }
+                Ok(Box::new(FrameAggProcessor::try_new(
+                    agg,
+                    self.frame.clone(),
+                    context,
+                )?))
             }
         }
     }
 }
+
+fn literal_int(expr: &Arc<dyn PhysicalExpr>) -> Result<i64> {
+    match downcast_any!(expr, Literal)?
+        .value()
+        .cast_to(&DataType::Int64)?
+    {
+        ScalarValue::Int64(Some(offset)) => Ok(offset),
+        other => df_execution_err!("window: invalid offset: {other:?}"),
+    }
+}
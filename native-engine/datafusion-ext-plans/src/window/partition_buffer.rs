@@ -0,0 +1,253 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering::SeqCst},
+    Arc, Weak,
+};
+
+use arrow::{
+    compute::concat_batches,
+    record_batch::{RecordBatch, RecordBatchOptions},
+};
+use async_trait::async_trait;
+use datafusion::common::Result;
+use datafusion_ext_commons::{
+    array_size::ArraySize,
+    df_execution_err,
+    io::{read_one_batch, write_one_batch},
+};
+use parking_lot::Mutex;
+
+use crate::{
+    memmgr::{
+        metrics::SpillMetrics,
+        spill::{try_new_spill, Spill},
+        MemConsumer, MemConsumerInfo, MemManager,
+    },
+    window::window_context::WindowContext,
+};
+
+/// Buffers input batches and only releases rows of completed partitions, so
+/// that processors can access all rows of a partition in one batch.
+///
+/// Buffered rows are spilled when memory is insufficient and read back once
+/// their partition is completed. The released batch stays accounted until the
+/// next batch is pushed, since it is processed as a whole and cannot be
+/// spilled.
+pub struct PartitionBuffer {
+    name: String,
+    mem_consumer_info: Option<Weak<MemConsumerInfo>>,
+    context: Arc<WindowContext>,
+    staging: Mutex<StagingBatches>,
+    output_mem_used: AtomicUsize,
+    spill_metrics: SpillMetrics,
+}
+
+#[derive(Default)]
+struct StagingBatches {
+    batches: Vec<RecordBatch>,
+    partition: Box<[u8]>,
+    spills: Vec<Box<dyn Spill>>,
+    mem_used: usize,
+}
+
+impl StagingBatches {
+    fn is_empty(&self) -> bool {
+        self.batches.is_empty() && self.spills.is_empty()
+    }
+
+    fn push(&mut self, batch: RecordBatch) {
+        if batch.num_rows() > 0 {
+            self.mem_used += batch.get_array_mem_size();
+            self.batches.push(batch);
+        }
+    }
+}
+
+impl PartitionBuffer {
+    pub fn new(name: String, context: Arc<WindowContext>, spill_metrics: SpillMetrics) -> Self {
+        Self {
+            name,
+            mem_consumer_info: None,
+            context,
+            staging: Mutex::default(),
+            output_mem_used: AtomicUsize::new(0),
+            spill_metrics,
+        }
+    }
+
+    /// buffers the input batch, returns all rows of completed partitions if any
+    pub async fn push(&self, batch: RecordBatch) -> Result<Option<RecordBatch>> {
+        let num_rows = batch.num_rows();
+        if num_rows == 0 {
+            return Ok(None);
+        }
+
+        // the previously released batch has been processed
+        self.output_mem_used.store(0, SeqCst);
+
+        if !self.context.has_partition() {
+            let mem_used = {
+                let mut staging = self.staging.lock();
+                staging.push(batch);
+                staging.mem_used
+            };
+            self.update_mem_used(mem_used).await?;
+            return Ok(None);
+        }
+
+        // find the start of the last partition in this batch
+        let partition_rows = self.context.get_partition_rows(&batch)?;
+        let last_partition = partition_rows.row(num_rows - 1);
+        let mut split_idx = num_rows - 1;
+        while split_idx > 0 && partition_rows.row(split_idx - 1) == last_partition {
+            split_idx -= 1;
+        }
+
+        let (completed, mem_used) = {
+            let mut staging = self.staging.lock();
+            let same_partition = split_idx == 0
+                && (staging.is_empty() || last_partition.as_ref() == staging.partition.as_ref());
+            staging.partition = last_partition.as_ref().into();
+
+            if same_partition {
+                staging.push(batch);
+                (None, staging.mem_used)
+            } else {
+                // staging rows and rows before split_idx are all completed
+                staging.push(batch.slice(0, split_idx));
+                let completed = std::mem::take(&mut *staging);
+                staging.partition = last_partition.as_ref().into();
+                staging.push(batch.slice(split_idx, num_rows - split_idx));
+                (Some(completed), staging.mem_used)
+            }
+        };
+        let completed = match completed {
+            Some(completed) => self.concat_staging_batches(completed)?,
+            None => None,
+        };
+
+        // account the completed batch together with staging batches, staging batches
+        // are spilled if they do not fit in memory any more
+        let output_mem_used = completed
+            .as_ref()
+            .map(|batch| batch.get_array_mem_size())
+            .unwrap_or_default();
+        self.output_mem_used.store(output_mem_used, SeqCst);
+        self.update_mem_used(mem_used + output_mem_used).await?;
+        Ok(completed)
+    }
+
+    /// returns all remaining rows after input is exhausted
+    pub async fn finish(&self) -> Result<Option<RecordBatch>> {
+        self.set_spillable(false);
+        let staging = std::mem::take(&mut *self.staging.lock());
+        self.output_mem_used.store(0, SeqCst);
+        self.update_mem_used(0).await?;
+
+        let completed = self.concat_staging_batches(staging)?;
+        let output_mem_used = completed
+            .as_ref()
+            .map(|batch| batch.get_array_mem_size())
+            .unwrap_or_default();
+        self.output_mem_used.store(output_mem_used, SeqCst);
+        self.update_mem_used(output_mem_used).await?;
+        Ok(completed)
+    }
+
+    fn concat_staging_batches(&self, staging: StagingBatches) -> Result<Option<RecordBatch>> {
+        let schema = &self.context.input_schema;
+
+        // all rows of the partition are processed in one batch, which can never be
+        // spilled, so fail if it cannot fit in the whole managed memory
+        let mem_total = MemManager::get().total();
+        let mut mem_size = staging.mem_used;
+        let mut check_mem_size = |batch: &RecordBatch| {
+            mem_size += batch.get_array_mem_size();
+            if mem_size > mem_total {
+                return df_execution_err!(
+                    "{}: partition size exceeds memory limit ({mem_size} > {mem_total})",
+                    self.name,
+                );
+            }
+            Ok(())
+        };
+
+        // spilled batches always come before in-memory batches
+        let mut batches = vec![];
+        for spill in &staging.spills {
+            let mut reader = spill.get_compressed_reader();
+            while let Some((num_rows, cols)) = read_one_batch(&mut reader, schema)? {
+                let batch = RecordBatch::try_new_with_options(
+                    schema.clone(),
+                    cols,
+                    &RecordBatchOptions::new().with_row_count(Some(num_rows)),
+                )?;
+                check_mem_size(&batch)?;
+                batches.push(batch);
+            }
+        }
+        batches.extend(staging.batches);
+
+        let batch = concat_batches(schema, &batches)?;
+        if batch.num_rows() == 0 {
+            return Ok(None);
+        }
+        Ok(Some(batch))
+    }
+}
+
+#[async_trait]
+impl MemConsumer for PartitionBuffer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn set_consumer_info(&mut self, consumer_info: Weak<MemConsumerInfo>) {
+        self.mem_consumer_info = Some(consumer_info);
+    }
+
+    fn get_consumer_info(&self) -> &Weak<MemConsumerInfo> {
+        self.mem_consumer_info
+            .as_ref()
+            .expect("consumer info not set")
+    }
+
+    async fn spill(&self) -> Result<()> {
+        {
+            let mut staging = self.staging.lock();
+            let batches = std::mem::take(&mut staging.batches);
+            if !batches.is_empty() {
+                let mut spill = try_new_spill(&self.spill_metrics)?;
+                let mut writer = spill.get_compressed_writer();
+                for batch in batches {
+                    write_one_batch(batch.num_rows(), batch.columns(), &mut writer)?;
+                }
+                drop(writer);
+                staging.spills.push(spill);
+            }
+            staging.mem_used = 0;
+        }
+        self.update_mem_used(self.output_mem_used.load(SeqCst))
+            .await?;
+        Ok(())
+    }
+}
+
+impl Drop for PartitionBuffer {
+    fn drop(&mut self) {
+        MemManager::deregister_consumer(self);
+    }
+}
@@ -0,0 +1,117 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{ops::Range, sync::Arc};
+
+use arrow::{
+    array::{ArrayRef, Float64Builder, Int32Builder},
+    record_batch::RecordBatch,
+};
+use datafusion::common::Result;
+
+use crate::window::{
+    processors::frame_agg_processor::split_partitions, window_context::WindowContext,
+    WindowFunctionProcessor,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub enum DistributionType {
+    PercentRank,
+    CumeDist,
+    Ntile(usize),
+}
+
+/// Evaluates percent_rank/cume_dist/ntile, which depend on the number of rows
+/// in the partition.
+///
+/// Input batches must contain only whole partitions (see `PartitionBuffer`).
+pub struct DistributionProcessor {
+    dist_type: DistributionType,
+}
+
+impl DistributionProcessor {
+    pub fn new(dist_type: DistributionType) -> Self {
+        Self { dist_type }
+    }
+
+    fn process_partitions(
+        &mut self,
+        context: &WindowContext,
+        batch: &RecordBatch,
+        partitions: Vec<Range<usize>>,
+    ) -> Result<ArrayRef> {
+        let num_rows = batch.num_rows();
+
+        if let DistributionType::Ntile(num_buckets) = self.dist_type {
+            // the first (num_rows % num_buckets) buckets get one more row
+            let mut builder = Int32Builder::with_capacity(num_rows);
+            for partition in partitions {
+                let n = partition.len();
+                let bucket_size = n / num_buckets;
+                let num_larger_buckets = n % num_buckets;
+                let num_larger_bucket_rows = num_larger_buckets * (bucket_size + 1);
+                for i in 0..n {
+                    let bucket = if i < num_larger_bucket_rows {
+                        i / (bucket_size + 1)
+                    } else {
+                        num_larger_buckets + (i - num_larger_bucket_rows) / bucket_size
+                    };
+                    builder.append_value(bucket as i32 + 1);
+                }
+            }
+            return Ok(Arc::new(builder.finish()));
+        }
+
+        let order_rows = context.get_order_rows(batch)?;
+        let mut builder = Float64Builder::with_capacity(num_rows);
+        for partition in partitions {
+            let n = partition.len();
+            let mut peer_start = partition.start;
+            for i in partition.clone() {
+                if i + 1 < partition.end && order_rows.row(i + 1) == order_rows.row(i) {
+                    continue;
+                }
+                // rows in peer_start..=i are peers
+                let value = match self.dist_type {
+                    DistributionType::PercentRank if n > 1 => {
+                        (peer_start - partition.start) as f64 / (n - 1) as f64
+                    }
+                    DistributionType::PercentRank => 0.0,
+                    DistributionType::CumeDist => (i + 1 - partition.start) as f64 / n as f64,
+                    DistributionType::Ntile(_) => unreachable!(),
+                };
+                for _ in peer_start..=i {
+                    builder.append_value(value);
+                }
+                peer_start = i + 1;
+            }
+        }
+        Ok(Arc::new(builder.finish()))
+    }
+}
+
+impl WindowFunctionProcessor for DistributionProcessor {
+    fn process_batch(&mut self, context: &WindowContext, batch: &RecordBatch) -> Result<ArrayRef> {
+        let partitions = split_partitions(context, batch)?;
+        self.process_partitions(context, batch, partitions)
+    }
+
+    fn process_batch_without_partitions(
+        &mut self,
+        context: &WindowContext,
+        batch: &RecordBatch,
+    ) -> Result<ArrayRef> {
+        self.process_partitions(context, batch, vec![0..batch.num_rows()])
+    }
+}
@@ -0,0 +1,432 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    ops::{Add, Range, Sub},
+    sync::Arc,
+};
+
+use arrow::{
+    array::{Array, ArrayRef, AsArray},
+    datatypes::{DataType, Decimal128Type, Float64Type, Int64Type},
+    record_batch::RecordBatch,
+};
+use datafusion::common::{Result, ScalarValue};
+use datafusion_ext_commons::{cast::cast, df_execution_err, df_unimplemented_err};
+
+use crate::{
+    agg::{
+        acc::{create_acc_from_initial_value, OwnedAccumStateRow},
+        Agg,
+    },
+    window::{
+        window_context::WindowContext, WindowFrame, WindowFrameBound, WindowFrameType,
+        WindowFunctionProcessor,
+    },
+};
+
+/// Aggregates over explicit ROWS/RANGE frames.
+///
+/// Input batches must contain only whole partitions (see `PartitionBuffer`).
+/// Aggregation is incremental when the frame start does not move, otherwise the
+/// frame is recomputed like Spark's sliding window frame.
+pub struct FrameAggProcessor {
+    agg: Arc<dyn Agg>,
+    acc_init: OwnedAccumStateRow,
+    frame: WindowFrame,
+}
+
+impl FrameAggProcessor {
+    pub fn try_new(agg: Arc<dyn Agg>, frame: WindowFrame, context: &WindowContext) -> Result<Self> {
+        validate_frame(&frame, context)?;
+
+        let (acc, accum_state_val_addrs) = create_acc_from_initial_value(agg.accums_initial())?;
+        let mut agg = agg;
+        unsafe {
+            // safety - accum_state_val_addrs is guaranteed not to be used at this time
+            Arc::get_mut_unchecked(&mut agg).set_accum_state_val_addrs(&accum_state_val_addrs);
+        }
+
+        Ok(Self {
+            agg,
+            acc_init: acc,
+            frame,
+        })
+    }
+
+    fn process_partitions(
+        &mut self,
+        context: &WindowContext,
+        batch: &RecordBatch,
+        partitions: Vec<Range<usize>>,
+    ) -> Result<ArrayRef> {
+        let frames = compute_frames(&self.frame, context, batch, &partitions)?;
+
+        let children_cols: Vec<ArrayRef> = self
+            .agg
+            .exprs()
+            .iter()
+            .map(|expr| {
+                expr.evaluate(batch)
+                    .and_then(|v| v.into_array(batch.num_rows()))
+            })
+            .collect::<Result<_>>()?;
+
+        let mut output = Vec::with_capacity(batch.num_rows());
+        let mut acc = self.acc_init.clone();
+        let mut acc_frame = 0..0;
+        for frame in frames {
+            // reuse result of previous row if the frame is not changed
+            if frame == acc_frame && !output.is_empty() {
+                output.push(output.last().cloned().unwrap());
+                continue;
+            }
+
+            // update incrementally if frame start is not moved, otherwise recompute
+            if frame.start != acc_frame.start || frame.end < acc_frame.end {
+                acc = self.acc_init.clone();
+                acc_frame = frame.start..frame.start;
+            }
+            for row_idx in acc_frame.end..frame.end {
+                self.agg
+                    .partial_update(&mut acc.as_mut(), &children_cols, row_idx)
+                    .map_err(|err| {
+                        err.context("window: frame_agg_processor partial_update() error")
+                    })?;
+            }
+            acc_frame = frame;
+            output.push(self.agg.final_merge(&mut acc.clone().as_mut())?);
+        }
+        Ok(Arc::new(ScalarValue::iter_to_array(output.into_iter())?))
+    }
+}
+
+/// checks that the frame can be evaluated with the order spec of the context
+pub fn validate_frame(frame: &WindowFrame, context: &WindowContext) -> Result<()> {
+    let has_offset = |bound: &WindowFrameBound| {
+        matches!(
+            bound,
+            WindowFrameBound::Preceding(_) | WindowFrameBound::Following(_)
+        )
+    };
+    if frame.frame_type == WindowFrameType::Range
+        && (has_offset(&frame.start) || has_offset(&frame.end))
+    {
+        if context.order_spec.len() != 1 {
+            return df_execution_err!(
+                "window: RANGE frame with offsets requires exactly one order expr"
+            );
+        }
+        let order_type = context.order_spec[0]
+            .expr
+            .data_type(&context.input_schema)?;
+        if !is_supported_range_offset_type(&order_type) {
+            return df_unimplemented_err!("window: RANGE frame with offsets on {order_type}");
+        }
+    }
+    if frame.start == WindowFrameBound::UnboundedFollowing
+        || frame.end == WindowFrameBound::UnboundedPreceding
+    {
+        return df_execution_err!("window: invalid frame: {frame:?}");
+    }
+    Ok(())
+}
+
+/// whether RANGE frames with offsets can be evaluated on the order type
+fn is_supported_range_offset_type(data_type: &DataType) -> bool {
+    matches!(
+        data_type,
+        DataType::Float32
+            | DataType::Float64
+            | DataType::Decimal128(..)
+            | DataType::Int8
+            | DataType::Int16
+            | DataType::Int32
+            | DataType::Int64
+            | DataType::UInt8
+            | DataType::UInt16
+            | DataType::UInt32
+            | DataType::Date32
+    )
+}
+
+/// splits a batch containing only whole partitions into partition ranges
+pub fn split_partitions(context: &WindowContext, batch: &RecordBatch) -> Result<Vec<Range<usize>>> {
+    if !context.has_partition() {
+        return Ok(vec![0..batch.num_rows()]);
+    }
+    let partition_rows = context.get_partition_rows(batch)?;
+    let mut partitions = vec![];
+    let mut partition_start = 0;
+    for i in 0..batch.num_rows() {
+        if i + 1 == batch.num_rows() || partition_rows.row(i + 1) != partition_rows.row(i) {
+            partitions.push(partition_start..i + 1);
+            partition_start = i + 1;
+        }
+    }
+    Ok(partitions)
+}
+
+/// computes frame of every row, rows of each partition must be sorted by the
+/// order spec of the context
+pub fn compute_frames(
+    frame: &WindowFrame,
+    context: &WindowContext,
+    batch: &RecordBatch,
+    partitions: &[Range<usize>],
+) -> Result<Vec<Range<usize>>> {
+    match frame.frame_type {
+        WindowFrameType::Rows => rows_frames(frame, partitions),
+        WindowFrameType::Range => range_frames(frame, context, batch, partitions),
+    }
+}
+
+fn rows_frames(frame: &WindowFrame, partitions: &[Range<usize>]) -> Result<Vec<Range<usize>>> {
+    let rows_offset = |offset: &ScalarValue| -> Result<usize> {
+        match offset.cast_to(&DataType::Int64)? {
+            ScalarValue::Int64(Some(n)) if n >= 0 => Ok(n as usize),
+            _ => df_execution_err!("window: invalid ROWS frame offset: {offset:?}"),
+        }
+    };
+    let start_bound = |partition: &Range<usize>, i: usize| -> Result<usize> {
+        Ok(match &frame.start {
+            WindowFrameBound::UnboundedPreceding => partition.start,
+            WindowFrameBound::Preceding(n) => i.saturating_sub(rows_offset(n)?),
+            WindowFrameBound::CurrentRow => i,
+            WindowFrameBound::Following(n) => i + rows_offset(n)?,
+            WindowFrameBound::UnboundedFollowing => partition.end,
+        })
+    };
+    let end_bound = |partition: &Range<usize>, i: usize| -> Result<usize> {
+        Ok(match &frame.end {
+            WindowFrameBound::UnboundedPreceding => partition.start,
+            WindowFrameBound::Preceding(n) => (i + 1).saturating_sub(rows_offset(n)?),
+            WindowFrameBound::CurrentRow => i + 1,
+            WindowFrameBound::Following(n) => i + 1 + rows_offset(n)?,
+            WindowFrameBound::UnboundedFollowing => partition.end,
+        })
+    };
+
+    let mut frames = vec![];
+    for partition in partitions {
+        for i in partition.clone() {
+            let start = start_bound(partition, i)?.clamp(partition.start, partition.end);
+            let end = end_bound(partition, i)?.clamp(start, partition.end);
+            frames.push(start..end);
+        }
+    }
+    Ok(frames)
+}
+
+fn range_frames(
+    frame: &WindowFrame,
+    context: &WindowContext,
+    batch: &RecordBatch,
+    partitions: &[Range<usize>],
+) -> Result<Vec<Range<usize>>> {
+    // rows with equal order values are peers and always share the same frame
+    let order_rows = context.get_order_rows(batch)?;
+    let mut peers = vec![0..0; batch.num_rows()];
+    for partition in partitions {
+        let mut peer_start = partition.start;
+        for i in partition.clone() {
+            if i + 1 == partition.end || order_rows.row(i + 1) != order_rows.row(i) {
+                peers[peer_start..=i].fill(peer_start..i + 1);
+                peer_start = i + 1;
+            }
+        }
+    }
+
+    let is_offset = |bound: &WindowFrameBound| {
+        matches!(
+            bound,
+            WindowFrameBound::Preceding(_) | WindowFrameBound::Following(_)
+        )
+    };
+    if !is_offset(&frame.start) && !is_offset(&frame.end) {
+        let range_bound =
+            |bound: &WindowFrameBound, is_start: bool, partition: &Range<usize>, i: usize| {
+                match bound {
+                    WindowFrameBound::UnboundedPreceding => partition.start,
+                    WindowFrameBound::UnboundedFollowing => partition.end,
+                    _ if is_start => peers[i].start,
+                    _ => peers[i].end,
+                }
+            };
+        let mut frames = vec![];
+        for partition in partitions {
+            for i in partition.clone() {
+                let start = range_bound(&frame.start, true, partition, i);
+                let end = range_bound(&frame.end, false, partition, i).max(start);
+                frames.push(start..end);
+            }
+        }
+        return Ok(frames);
+    }
+
+    // evaluate order values, values of descending order are negated so that
+    // they are always in ascending order
+    let order_expr = &context.order_spec[0];
+    let order_values = order_expr
+        .expr
+        .evaluate(batch)
+        .and_then(|v| v.into_array(batch.num_rows()))?;
+    let descending = order_expr.options.descending;
+
+    match order_values.data_type() {
+        DataType::Float32 | DataType::Float64 => {
+            let values = cast(&order_values, &DataType::Float64)?;
+            let keys = values
+                .as_primitive::<Float64Type>()
+                .iter()
+                .map(|v| v.map(|v| if descending { -v } else { v }))
+                .collect::<Vec<_>>();
+            let to_offset = |offset: &ScalarValue| -> Result<f64> {
+                match offset.cast_to(&DataType::Float64)? {
+                    ScalarValue::Float64(Some(v)) => Ok(v),
+                    _ => df_execution_err!("window: invalid RANGE frame offset: {offset:?}"),
+                }
+            };
+            range_offset_frames(&keys, partitions, &peers, frame, to_offset)
+        }
+        DataType::Decimal128(..) => {
+            let keys = order_values
+                .as_primitive::<Decimal128Type>()
+                .iter()
+                .map(|v| v.map(|v| if descending { -v } else { v }))
+                .collect::<Vec<_>>();
+            let data_type = order_values.data_type().clone();
+            let to_offset = |offset: &ScalarValue| -> Result<i128> {
+                match offset.cast_to(&data_type)? {
+                    ScalarValue::Decimal128(Some(v), ..) => Ok(v),
+                    _ => df_execution_err!("window: invalid RANGE frame offset: {offset:?}"),
+                }
+            };
+            range_offset_frames(&keys, partitions, &peers, frame, to_offset)
+        }
+        DataType::Int8
+        | DataType::Int16
+        | DataType::Int32
+        | DataType::Int64
+        | DataType::UInt8
+        | DataType::UInt16
+        | DataType::UInt32
+        | DataType::Date32 => {
+            let values = cast(&order_values, &DataType::Int64)?;
+            let keys = values
+                .as_primitive::<Int64Type>()
+                .iter()
+                .map(|v| v.map(|v| if descending { -(v as i128) } else { v as i128 }))
+                .collect::<Vec<_>>();
+            let to_offset = |offset: &ScalarValue| -> Result<i128> {
+                match offset.cast_to(&DataType::Int64)? {
+                    ScalarValue::Int64(Some(v)) => Ok(v as i128),
+                    _ => df_execution_err!("window: invalid RANGE frame offset: {offset:?}"),
+                }
+            };
+            range_offset_frames(&keys, partitions, &peers, frame, to_offset)
+        }
+        other => df_execution_err!("window: RANGE frame with offsets on {other}"),
+    }
+}
+
+/// computes RANGE frames with offset bounds, keys must be sorted ascending in
+/// each partition with nulls placed together at either end
+fn range_offset_frames<T: Copy + PartialOrd + Add<Output = T> + Sub<Output = T>>(
+    keys: &[Option<T>],
+    partitions: &[Range<usize>],
+    peers: &[Range<usize>],
+    frame: &WindowFrame,
+    to_offset: impl Fn(&ScalarValue) -> Result<T>,
+) -> Result<Vec<Range<usize>>> {
+    let start_offset = match &frame.start {
+        WindowFrameBound::Preceding(v) | WindowFrameBound::Following(v) => Some(to_offset(v)?),
+        _ => None,
+    };
+    let end_offset = match &frame.end {
+        WindowFrameBound::Preceding(v) | WindowFrameBound::Following(v) => Some(to_offset(v)?),
+        _ => None,
+    };
+
+    let mut frames = vec![];
+    for partition in partitions {
+        let non_nulls = {
+            let first_non_null = (partition.start..partition.end)
+                .find(|&i| keys[i].is_some())
+                .unwrap_or(partition.end);
+            let last_non_null = (first_non_null..partition.end)
+                .rev()
+                .find(|&i| keys[i].is_some())
+                .map(|i| i + 1)
+                .unwrap_or(first_non_null);
+            first_non_null..last_non_null
+        };
+        let non_null_keys = &keys[non_nulls.clone()];
+
+        // first index of non-null keys satisfying key > target (or key >= target)
+        let search = |target: T, inclusive: bool| {
+            non_nulls.start
+                + non_null_keys.partition_point(|k| {
+                    let k = k.expect("non-null");
+                    if inclusive {
+                        k < target
+                    } else {
+                        k <= target
+                    }
+                })
+        };
+        let bound = |bound: &WindowFrameBound, offset: Option<T>, is_start: bool, i: usize| {
+            let key = match (bound, keys[i]) {
+                (WindowFrameBound::UnboundedPreceding, _) => return partition.start,
+                (WindowFrameBound::UnboundedFollowing, _) => return partition.end,
+                (WindowFrameBound::CurrentRow, _) | (_, None) => {
+                    // null keys only match other null keys
+                    return if is_start {
+                        peers[i].start
+                    } else {
+                        peers[i].end
+                    };
+                }
+                (_, Some(key)) => key,
+            };
+            let target = match bound {
+                WindowFrameBound::Preceding(_) => key - offset.expect("offset"),
+                _ => key + offset.expect("offset"),
+            };
+            search(target, is_start)
+        };
+
+        for i in partition.clone() {
+            let start = bound(&frame.start, start_offset, true, i);
+            let end = bound(&frame.end, end_offset, false, i).max(start);
+            frames.push(start..end);
+        }
+    }
+    Ok(frames)
+}
+
+impl WindowFunctionProcessor for FrameAggProcessor {
+    fn process_batch(&mut self, context: &WindowContext, batch: &RecordBatch) -> Result<ArrayRef> {
+        let partitions = split_partitions(context, batch)?;
+        self.process_partitions(context, batch, partitions)
+    }
+
+    fn process_batch_without_partitions(
+        &mut self,
+        context: &WindowContext,
+        batch: &RecordBatch,
+    ) -> Result<ArrayRef> {
+        self.process_partitions(context, batch, vec![0..batch.num_rows()])
+    }
+}
@@ -0,0 +1,126 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{ops::Range, sync::Arc};
+
+use arrow::{
+    array::{Array, ArrayRef, UInt32Builder},
+    compute::{is_not_null, kernels::zip::zip, take},
+    record_batch::RecordBatch,
+};
+use datafusion::{common::Result, physical_expr::PhysicalExpr};
+use datafusion_ext_commons::cast::cast;
+
+use crate::window::{
+    processors::frame_agg_processor::split_partitions, window_context::WindowContext,
+    WindowFunctionProcessor,
+};
+
+/// Evaluates lag/lead, rows out of the partition take the default value.
+///
+/// Input batches must contain only whole partitions (see `PartitionBuffer`),
+/// since lead needs rows after the current batch.
+pub struct LagLeadProcessor {
+    is_lead: bool,
+    input: Arc<dyn PhysicalExpr>,
+    offset: i64,
+    default: Arc<dyn PhysicalExpr>,
+    ignore_nulls: bool,
+}
+
+impl LagLeadProcessor {
+    pub fn new(
+        is_lead: bool,
+        input: Arc<dyn PhysicalExpr>,
+        offset: i64,
+        default: Arc<dyn PhysicalExpr>,
+        ignore_nulls: bool,
+    ) -> Self {
+        Self {
+            is_lead,
+            input,
+            offset,
+            default,
+            ignore_nulls,
+        }
+    }
+
+    fn process_partitions(
+        &mut self,
+        batch: &RecordBatch,
+        partitions: Vec<Range<usize>>,
+    ) -> Result<ArrayRef> {
+        let num_rows = batch.num_rows();
+        let values = self.input.evaluate(batch)?.into_array(num_rows)?;
+        let delta = if self.is_lead {
+            self.offset
+        } else {
+            -self.offset
+        };
+
+        // index of the offset row for each row, null if out of partition
+        let mut indices = UInt32Builder::with_capacity(num_rows);
+        for partition in partitions {
+            if !self.ignore_nulls || delta == 0 {
+                for i in partition.clone() {
+                    let target = i as i64 + delta;
+                    if (partition.start as i64..partition.end as i64).contains(&target) {
+                        indices.append_value(target as u32);
+                    } else {
+                        indices.append_null();
+                    }
+                }
+                continue;
+            }
+
+            // with ignore nulls, offset counts only non-null rows
+            let non_nulls = partition
+                .clone()
+                .filter(|&i| values.is_valid(i))
+                .collect::<Vec<_>>();
+            for i in partition.clone() {
+                let target = if delta > 0 {
+                    let k = non_nulls.partition_point(|&j| j <= i);
+                    non_nulls.get(k + delta as usize - 1).copied()
+                } else {
+                    let k = non_nulls.partition_point(|&j| j < i);
+                    k.checked_sub(delta.unsigned_abs() as usize)
+                        .map(|k| non_nulls[k])
+                };
+                indices.append_option(target.map(|target| target as u32));
+            }
+        }
+        let indices = indices.finish();
+
+        let offset_values = take(&values, &indices, None)?;
+        let defaults = self.default.evaluate(batch)?.into_array(num_rows)?;
+        let defaults = cast(&defaults, values.data_type())?;
+        Ok(zip(&is_not_null(&indices)?, &offset_values, &defaults)?)
+    }
+}
+
+impl WindowFunctionProcessor for LagLeadProcessor {
+    fn process_batch(&mut self, context: &WindowContext, batch: &RecordBatch) -> Result<ArrayRef> {
+        let partitions = split_partitions(context, batch)?;
+        self.process_partitions(batch, partitions)
+    }
+
+    fn process_batch_without_partitions(
+        &mut self,
+        _: &WindowContext,
+        batch: &RecordBatch,
+    ) -> Result<ArrayRef> {
+        self.process_partitions(batch, vec![0..batch.num_rows()])
+    }
+}
@@ -13,5 +13,9 @@
 // limitations under the License.
 
 pub mod agg_processor;
+pub mod distribution_processor;
+pub mod frame_agg_processor;
+pub mod lag_lead_processor;
+pub mod nth_value_processor;
 pub mod rank_processor;
 pub mod row_number_processor;
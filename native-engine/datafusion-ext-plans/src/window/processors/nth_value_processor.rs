@@ -0,0 +1,112 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{ops::Range, sync::Arc};
+
+use arrow::{
+    array::{Array, ArrayRef, UInt32Builder},
+    compute::take,
+    record_batch::RecordBatch,
+};
+use datafusion::{common::Result, physical_expr::PhysicalExpr};
+
+use crate::window::{
+    processors::frame_agg_processor::{compute_frames, split_partitions, validate_frame},
+    window_context::WindowContext,
+    WindowFrame, WindowFunctionProcessor,
+};
+
+/// Evaluates nth_value/first_value/last_value over the window frame.
+///
+/// Input batches must contain only whole partitions (see `PartitionBuffer`).
+pub struct NthValueProcessor {
+    input: Arc<dyn PhysicalExpr>,
+    nth: Option<usize>, // 1-based, None for the last value
+    ignore_nulls: bool,
+    frame: WindowFrame,
+}
+
+impl NthValueProcessor {
+    pub fn try_new(
+        input: Arc<dyn PhysicalExpr>,
+        nth: Option<usize>,
+        ignore_nulls: bool,
+        frame: WindowFrame,
+        context: &WindowContext,
+    ) -> Result<Self> {
+        validate_frame(&frame, context)?;
+        Ok(Self {
+            input,
+            nth,
+            ignore_nulls,
+            frame,
+        })
+    }
+
+    fn process_partitions(
+        &mut self,
+        context: &WindowContext,
+        batch: &RecordBatch,
+        partitions: Vec<Range<usize>>,
+    ) -> Result<ArrayRef> {
+        let num_rows = batch.num_rows();
+        let values = self.input.evaluate(batch)?.into_array(num_rows)?;
+        let frames = compute_frames(&self.frame, context, batch, &partitions)?;
+
+        let mut indices = UInt32Builder::with_capacity(num_rows);
+        for partition in partitions {
+            let non_nulls = if self.ignore_nulls {
+                partition
+                    .clone()
+                    .filter(|&i| values.is_valid(i))
+                    .collect::<Vec<_>>()
+            } else {
+                vec![]
+            };
+
+            for frame in &frames[partition] {
+                let idx = match (self.nth, self.ignore_nulls) {
+                    (Some(nth), false) => Some(frame.start + nth - 1),
+                    (None, false) => frame.end.checked_sub(1),
+                    (Some(nth), true) => {
+                        let k = non_nulls.partition_point(|&j| j < frame.start);
+                        non_nulls.get(k + nth - 1).copied()
+                    }
+                    (None, true) => {
+                        let k = non_nulls.partition_point(|&j| j < frame.end);
+                        k.checked_sub(1).map(|k| non_nulls[k])
+                    }
+                };
+                let idx = idx.filter(|idx| frame.contains(idx));
+                indices.append_option(idx.map(|idx| idx as u32));
+            }
+        }
+        Ok(take(&values, &indices.finish(), None)?)
+    }
+}
+
+impl WindowFunctionProcessor for NthValueProcessor {
+    fn process_batch(&mut self, context: &WindowContext, batch: &RecordBatch) -> Result<ArrayRef> {
+        let partitions = split_partitions(context, batch)?;
+        self.process_partitions(context, batch, partitions)
+    }
+
+    fn process_batch_without_partitions(
+        &mut self,
+        context: &WindowContext,
+        batch: &RecordBatch,
+    ) -> Result<ArrayRef> {
+        self.process_partitions(context, batch, vec![0..batch.num_rows()])
+    }
+}
@@ -37,7 +37,11 @@ use once_cell::sync::OnceCell;
 
 use crate::{
     common::output::TaskOutputter,
-    window::{window_context::WindowContext, WindowExpr, WindowFunctionProcessor},
+    memmgr::{metrics::SpillMetrics, MemManager},
+    window::{
+        partition_buffer::PartitionBuffer, window_context::WindowContext, WindowExpr,
+        WindowFunctionProcessor,
+    },
 };
 
 #[derive(Debug)]
@@ -61,6 +65,9 @@ impl WindowExec {
             partition_spec,
             order_spec,
         )?);
+        for window_expr in &context.window_exprs {
+            window_expr.validate(&context)?;
+        }
         Ok(Self {
             input,
             context,
@@ -120,7 +127,6 @@ impl ExecutionPlan for WindowExec {
         partition: usize,
         context: Arc<TaskContext>,
     ) -> Result<SendableRecordBatchStream> {
-        // at this moment only supports ROWS BETWEEN UNBOUNDED PRECEDING AND CURRENT ROW
         let input = self.input.execute(partition, context.clone())?;
         let coalesced = context.coalesce_with_default_batch_size(
             input,
@@ -129,9 +135,11 @@ impl ExecutionPlan for WindowExec {
 
         let stream = execute_window(
             coalesced,
+            partition,
             context.clone(),
             self.context.clone(),
             BaselineMetrics::new(&self.metrics, partition),
+            SpillMetrics::new(&self.metrics, partition),
         )
         .map_err(|e| ArrowError::ExternalError(Box::new(e)));
 
@@ -152,9 +160,11 @@ impl ExecutionPlan for WindowExec {
 
 async fn execute_window(
     mut input: SendableRecordBatchStream,
+    partition: usize,
     task_context: Arc<TaskContext>,
     context: Arc<WindowContext>,
     metrics: BaselineMetrics,
+    spill_metrics: SpillMetrics,
 ) -> Result<SendableRecordBatchStream> {
     let mut processors: Vec<Box<dyn WindowFunctionProcessor>> = context
         .window_exprs
@@ -162,6 +172,22 @@ async fn execute_window(
         .map(|expr: &WindowExpr| expr.create_processor(&context))
         .collect::<Result<_>>()?;
 
+    // offset functions and frames other than the running frame need all rows
+    // of a partition
+    let partition_buffer = context
+        .window_exprs
+        .iter()
+        .any(|expr| expr.requires_whole_partitions())
+        .then(|| {
+            let partition_buffer = Arc::new(PartitionBuffer::new(
+                format!("Window[partition={partition}]"),
+                context.clone(),
+                spill_metrics,
+            ));
+            MemManager::register_consumer(partition_buffer.clone(), true);
+            partition_buffer
+        });
+
     // start processing input batches
     let output_schema = context.output_schema.clone();
     task_context.output_with_sender("Window", output_schema.clone(), |sender| async move {
@@ -169,42 +195,65 @@ async fn execute_window(
 
         while let Some(batch) = input.next().await.transpose()? {
             let _timer = metrics.elapsed_compute().timer();
-            let window_cols: Vec<ArrayRef> = processors
-                .iter_mut()
-                .map(|processor| {
-                    if context.partition_spec.is_empty() {
-                        processor.process_batch_without_partitions(&context, &batch)
-                    } else {
-                        processor.process_batch(&context, &batch)
-                    }
-                })
-                .collect::<Result<_>>()?;
-
-            let outputs: Vec<ArrayRef> = batch
-                .columns()
-                .iter()
-                .chain(&window_cols)
-                .zip(context.output_schema.fields())
-                .map(|(array, field)| {
-                    if array.data_type() != field.data_type() {
-                        return cast(&array, field.data_type());
-                    }
-                    Ok(array.clone())
-                })
-                .collect::<Result<_>>()?;
-            let output_batch = RecordBatch::try_new_with_options(
-                context.output_schema.clone(),
-                outputs,
-                &RecordBatchOptions::new().with_row_count(Some(batch.num_rows())),
-            )?;
-
+            let batch = match &partition_buffer {
+                Some(partition_buffer) => match partition_buffer.push(batch).await? {
+                    Some(completed) => completed,
+                    None => continue,
+                },
+                None => batch,
+            };
+            let output_batch = process_batch(&context, &mut processors, &batch)?;
             metrics.record_output(output_batch.num_rows());
             sender.send(Ok(output_batch)).await;
         }
+
+        if let Some(partition_buffer) = &partition_buffer {
+            let _timer = metrics.elapsed_compute().timer();
+            if let Some(batch) = partition_buffer.finish().await? {
+                let output_batch = process_batch(&context, &mut processors, &batch)?;
+                metrics.record_output(output_batch.num_rows());
+                sender.send(Ok(output_batch)).await;
+            }
+        }
         Ok(())
     })
 }
 
+fn process_batch(
+    context: &WindowContext,
+    processors: &mut [Box<dyn WindowFunctionProcessor>],
+    batch: &RecordBatch,
+) -> Result<RecordBatch> {
+    let window_cols: Vec<ArrayRef> = processors
+        .iter_mut()
+        .map(|processor| {
+            if context.partition_spec.is_empty() {
+                processor.process_batch_without_partitions(context, batch)
+            } else {
+                processor.process_batch(context, batch)
+            }
+        })
+        .collect::<Result<_>>()?;
+
+    let outputs: Vec<ArrayRef> = batch
+        .columns()
+        .iter()
+        .chain(&window_cols)
+        .zip(context.output_schema.fields())
+        .map(|(array, field)| {
+            if array.data_type() != field.data_type() {
+                return cast(&array, field.data_type());
+            }
+            Ok(array.clone())
+        })
+        .collect::<Result<_>>()?;
+    Ok(RecordBatch::try_new_with_options(
+        context.output_schema.clone(),
+        outputs,
+        &RecordBatchOptions::new().with_row_count(Some(batch.num_rows())),
+    )?)
+}
+
 #[cfg(test)]
 mod test {
     use std::sync::Arc;
@@ -212,14 +261,22 @@ mod test {
     use arrow::{array::*, datatypes::*, record_batch::RecordBatch};
     use datafusion::{
         assert_batches_eq,
-        physical_expr::{expressions::Column, PhysicalSortExpr},
+        common::ScalarValue,
+        physical_expr::{
+            expressions::{Column, Literal},
+            PhysicalExpr, PhysicalSortExpr,
+        },
         physical_plan::{memory::MemoryExec, ExecutionPlan},
         prelude::SessionContext,
     };
 
     use crate::{
         agg::AggFunction,
-        window::{WindowExpr, WindowFunction, WindowRankType},
+        memmgr::MemManager,
+        window::{
+            WindowExpr, WindowFrame, WindowFrameBound, WindowFrameType, WindowFunction,
+            WindowOffsetType, WindowRankType,
+        },
         window_exec::WindowExec,
     };
 
@@ -367,4 +424,287 @@ mod test {
         assert_batches_eq!(expected, &batches);
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_window_frames() -> Result<(), Box<dyn std::error::Error>> {
+        MemManager::init(10000);
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+
+        // partition a1=1 is split into two input batches
+        let batch1 = build_table_i32(
+            ("a1", &vec![1, 1, 1]),
+            ("b1", &vec![1, 2, 2]),
+            ("c1", &vec![0, 0, 0]),
+        );
+        let batch2 = build_table_i32(
+            ("a1", &vec![1, 2, 3, 3]),
+            ("b1", &vec![3, 4, 1, 1]),
+            ("c1", &vec![0, 0, 0, 0]),
+        );
+        let schema = batch1.schema();
+        let input = Arc::new(MemoryExec::try_new(&[vec![batch1, batch2]], schema, None)?);
+
+        let sum_b1 = |name: &str, frame: WindowFrame| {
+            WindowExpr::new(
+                WindowFunction::Agg(AggFunction::Sum),
+                vec![Arc::new(Column::new("b1", 1))],
+                Arc::new(Field::new(name, DataType::Int64, false)),
+            )
+            .with_frame(frame)
+        };
+        let window = Arc::new(WindowExec::try_new(
+            input,
+            vec![
+                sum_b1(
+                    "rows_sum",
+                    WindowFrame {
+                        frame_type: WindowFrameType::Rows,
+                        start: WindowFrameBound::Preceding(ScalarValue::Int32(Some(1))),
+                        end: WindowFrameBound::Following(ScalarValue::Int32(Some(1))),
+                    },
+                ),
+                sum_b1(
+                    "rows_sum_unbounded",
+                    WindowFrame {
+                        frame_type: WindowFrameType::Rows,
+                        start: WindowFrameBound::CurrentRow,
+                        end: WindowFrameBound::UnboundedFollowing,
+                    },
+                ),
+                sum_b1(
+                    "range_sum",
+                    WindowFrame {
+                        frame_type: WindowFrameType::Range,
+                        start: WindowFrameBound::Preceding(ScalarValue::Int32(Some(1))),
+                        end: WindowFrameBound::CurrentRow,
+                    },
+                ),
+                WindowExpr::new(
+                    WindowFunction::Agg(AggFunction::Count),
+                    vec![Arc::new(Column::new("b1", 1))],
+                    Arc::new(Field::new("range_count", DataType::Int64, false)),
+                )
+                .with_frame(WindowFrame {
+                    frame_type: WindowFrameType::Range,
+                    start: WindowFrameBound::UnboundedPreceding,
+                    end: WindowFrameBound::UnboundedFollowing,
+                }),
+            ],
+            vec![Arc::new(Column::new("a1", 0))],
+            vec![PhysicalSortExpr {
+                expr: Arc::new(Column::new("b1", 1)),
+                options: Default::default(),
+            }],
+        )?);
+        let stream = window.execute(0, task_ctx.clone())?;
+        let batches = datafusion::physical_plan::common::collect(stream).await?;
+        let expected = vec![
+            "+----+----+----+----------+--------------------+-----------+-------------+",
+            "| a1 | b1 | c1 | rows_sum | rows_sum_unbounded | range_sum | range_count |",
+            "+----+----+----+----------+--------------------+-----------+-------------+",
+            "| 1  | 1  | 0  | 3        | 8                  | 1         | 4           |",
+            "| 1  | 2  | 0  | 5        | 7                  | 5         | 4           |",
+            "| 1  | 2  | 0  | 7        | 5                  | 5         | 4           |",
+            "| 1  | 3  | 0  | 5        | 3                  | 7         | 4           |",
+            "| 2  | 4  | 0  | 4        | 4                  | 4         | 1           |",
+            "| 3  | 1  | 0  | 2        | 2                  | 2         | 2           |",
+            "| 3  | 1  | 0  | 2        | 1                  | 2         | 2           |",
+            "+----+----+----+----------+--------------------+-----------+-------------+",
+        ];
+        assert_batches_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unsupported_range_frame() -> Result<(), Box<dyn std::error::Error>> {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "ts",
+            DataType::Timestamp(TimeUnit::Microsecond, None),
+            false,
+        )]));
+        let input = Arc::new(MemoryExec::try_new(&[vec![]], schema, None)?);
+
+        // RANGE offsets on timestamps are rejected before execution
+        let window = WindowExec::try_new(
+            input,
+            vec![WindowExpr::new(
+                WindowFunction::Agg(AggFunction::Count),
+                vec![Arc::new(Column::new("ts", 0))],
+                Arc::new(Field::new("range_count", DataType::Int64, false)),
+            )
+            .with_frame(WindowFrame {
+                frame_type: WindowFrameType::Range,
+                start: WindowFrameBound::Preceding(ScalarValue::Int64(Some(1000))),
+                end: WindowFrameBound::CurrentRow,
+            })],
+            vec![],
+            vec![PhysicalSortExpr {
+                expr: Arc::new(Column::new("ts", 0)),
+                options: Default::default(),
+            }],
+        );
+        assert!(window.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_window_offset_functions() -> Result<(), Box<dyn std::error::Error>> {
+        MemManager::init(10000);
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+
+        // partition a1=1 is split into two input batches
+        let build_batch = |a1: Vec<i32>, b1: Vec<i32>, c1: Vec<Option<i32>>| {
+            let schema = Schema::new(vec![
+                Field::new("a1", DataType::Int32, false),
+                Field::new("b1", DataType::Int32, false),
+                Field::new("c1", DataType::Int32, true),
+            ]);
+            RecordBatch::try_new(
+                Arc::new(schema),
+                vec![
+                    Arc::new(Int32Array::from(a1)),
+                    Arc::new(Int32Array::from(b1)),
+                    Arc::new(Int32Array::from(c1)),
+                ],
+            )
+        };
+        let batch1 = build_batch(vec![1, 1, 1], vec![1, 2, 3], vec![Some(10), None, Some(30)])?;
+        let batch2 = build_batch(vec![1, 2, 2], vec![4, 1, 2], vec![None, None, Some(60)])?;
+        let schema = batch1.schema();
+        let input = Arc::new(MemoryExec::try_new(&[vec![batch1, batch2]], schema, None)?);
+
+        let c1: Arc<dyn PhysicalExpr> = Arc::new(Column::new("c1", 2));
+        let lit = |v: Option<i32>| -> Arc<dyn PhysicalExpr> {
+            Arc::new(Literal::new(ScalarValue::Int32(v)))
+        };
+        let offset_expr = |offset_type, children, name: &str| {
+            WindowExpr::new(
+                WindowFunction::Offset(offset_type),
+                children,
+                Arc::new(Field::new(name, DataType::Int32, true)),
+            )
+        };
+        let window = Arc::new(WindowExec::try_new(
+            input,
+            vec![
+                offset_expr(
+                    WindowOffsetType::Lag,
+                    vec![c1.clone(), lit(Some(1)), lit(Some(-1))],
+                    "lag",
+                ),
+                offset_expr(
+                    WindowOffsetType::Lead,
+                    vec![c1.clone(), lit(Some(1)), lit(Some(-1))],
+                    "lead",
+                ),
+                offset_expr(
+                    WindowOffsetType::Lag,
+                    vec![c1.clone(), lit(Some(1)), lit(None)],
+                    "lag_ignore_nulls",
+                )
+                .with_ignore_nulls(true),
+                offset_expr(
+                    WindowOffsetType::FirstValue,
+                    vec![c1.clone()],
+                    "first_value",
+                )
+                .with_ignore_nulls(true)
+                .with_frame(WindowFrame {
+                    frame_type: WindowFrameType::Range,
+                    start: WindowFrameBound::UnboundedPreceding,
+                    end: WindowFrameBound::CurrentRow,
+                }),
+                offset_expr(WindowOffsetType::LastValue, vec![c1.clone()], "last_value")
+                    .with_ignore_nulls(true)
+                    .with_frame(WindowFrame {
+                        frame_type: WindowFrameType::Rows,
+                        start: WindowFrameBound::UnboundedPreceding,
+                        end: WindowFrameBound::UnboundedFollowing,
+                    }),
+                offset_expr(
+                    WindowOffsetType::NthValue,
+                    vec![c1, lit(Some(3))],
+                    "nth_value",
+                ),
+            ],
+            vec![Arc::new(Column::new("a1", 0))],
+            vec![PhysicalSortExpr {
+                expr: Arc::new(Column::new("b1", 1)),
+                options: Default::default(),
+            }],
+        )?);
+        let stream = window.execute(0, task_ctx.clone())?;
+        let batches = datafusion::physical_plan::common::collect(stream).await?;
+        let expected = vec![
+            "+----+----+----+-----+------+------------------+-------------+------------+-----------+",
+            "| a1 | b1 | c1 | lag | lead | lag_ignore_nulls | first_value | last_value | nth_value |",
+            "+----+----+----+-----+------+------------------+-------------+------------+-----------+",
+            "| 1  | 1  | 10 | -1  |      |                  | 10          | 30         |           |",
+            "| 1  | 2  |    | 10  | 30   | 10               | 10          | 30         |           |",
+            "| 1  | 3  | 30 |     |      | 10               | 10          | 30         | 30        |",
+            "| 1  | 4  |    | 30  | -1   | 30               | 10          | 30         | 30        |",
+            "| 2  | 1  |    | -1  | 60   |                  |             | 60         |           |",
+            "| 2  | 2  | 60 |     | -1   |                  | 60          | 60         |           |",
+            "+----+----+----+-----+------+------------------+-------------+------------+-----------+",
+        ];
+        assert_batches_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_window_distribution_functions() -> Result<(), Box<dyn std::error::Error>> {
+        MemManager::init(10000);
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+
+        let input = build_table(
+            ("a1", &vec![1, 1, 1, 1, 2, 3, 3]),
+            ("b1", &vec![1, 2, 2, 3, 4, 1, 1]),
+            ("c1", &vec![0, 0, 0, 0, 0, 0, 0]),
+        );
+        let window = Arc::new(WindowExec::try_new(
+            input,
+            vec![
+                WindowExpr::new(
+                    WindowFunction::RankLike(WindowRankType::PercentRank),
+                    vec![],
+                    Arc::new(Field::new("percent_rank", DataType::Float64, false)),
+                ),
+                WindowExpr::new(
+                    WindowFunction::RankLike(WindowRankType::CumeDist),
+                    vec![],
+                    Arc::new(Field::new("cume_dist", DataType::Float64, false)),
+                ),
+                WindowExpr::new(
+                    WindowFunction::RankLike(WindowRankType::Ntile),
+                    vec![Arc::new(Literal::new(ScalarValue::Int32(Some(3))))],
+                    Arc::new(Field::new("ntile", DataType::Int32, false)),
+                ),
+            ],
+            vec![Arc::new(Column::new("a1", 0))],
+            vec![PhysicalSortExpr {
+                expr: Arc::new(Column::new("b1", 1)),
+                options: Default::default(),
+            }],
+        )?);
+        let stream = window.execute(0, task_ctx.clone())?;
+        let batches = datafusion::physical_plan::common::collect(stream).await?;
+        let expected = vec![
+            "+----+----+----+--------------------+-----------+-------+",
+            "| a1 | b1 | c1 | percent_rank       | cume_dist | ntile |",
+            "+----+----+----+--------------------+-----------+-------+",
+            "| 1  | 1  | 0  | 0.0                | 0.25      | 1     |",
+            "| 1  | 2  | 0  | 0.3333333333333333 | 0.75      | 1     |",
+            "| 1  | 2  | 0  | 0.3333333333333333 | 0.75      | 2     |",
+            "| 1  | 3  | 0  | 1.0                | 1.0       | 3     |",
+            "| 2  | 4  | 0  | 0.0                | 1.0       | 1     |",
+            "| 3  | 1  | 0  | 0.0                | 1.0       | 1     |",
+            "| 3  | 1  | 0  | 0.0                | 1.0       | 2     |",
+            "+----+----+----+--------------------+-----------+-------+",
+        ];
+        assert_batches_eq!(expected, &batches);
+        Ok(())
+    }
 }
@@ -44,9 +44,11 @@ import org.apache.spark.sql.catalyst.expressions.NamedExpression
 import org.apache.spark.sql.catalyst.expressions.SortOrder
 import org.apache.spark.sql.catalyst.expressions.StringSplit
 import org.apache.spark.sql.catalyst.expressions.TaggingExpression
+import org.apache.spark.sql.catalyst.expressions.WindowFrame
 import org.apache.spark.sql.catalyst.expressions.aggregate.AggregateExpression
 import org.apache.spark.sql.catalyst.expressions.aggregate.AggregateFunction
 import org.apache.spark.sql.catalyst.expressions.aggregate.First
+import org.apache.spark.sql.catalyst.expressions.aggregate.Last
 import org.apache.spark.sql.catalyst.plans.JoinType
 import org.apache.spark.sql.catalyst.plans.physical.BroadcastMode
 import org.apache.spark.sql.catalyst.plans.physical.Partitioning
@@ -67,6 +69,7 @@ import org.apache.spark.sql.execution.blaze.plan.NativeAggBase.AggExecMode
 import org.apache.spark.sql.execution.blaze.plan.NativeAggExec
 import org.apache.spark.sql.execution.blaze.plan.NativeBroadcastExchangeBase
 import org.apache.spark.sql.execution.blaze.plan.NativeBroadcastExchangeExec
+import org.apache.spark.sql.execution.blaze.plan.NativeBroadcastNestedLoopJoinBase
 import org.apache.spark.sql.execution.blaze.plan.NativeExpandBase
 import org.apache.spark.sql.execution.blaze.plan.NativeExpandExec
 import org.apache.spark.sql.execution.blaze.plan.NativeFilterBase
@@ -101,10 +104,13 @@ import org.apache.spark.sql.execution.blaze.shuffle.BlazeBlockStoreShuffleReader
 import org.apache.spark.sql.execution.exchange.BroadcastExchangeLike
 import org.apache.spark.sql.execution.exchange.ReusedExchangeExec
 import org.apache.spark.sql.execution.joins.blaze.plan.NativeBroadcastJoinExec
+import org.apache.spark.sql.execution.joins.blaze.plan.NativeBroadcastNestedLoopJoinExec
 import org.apache.spark.sql.execution.joins.blaze.plan.NativeShuffledHashJoinExecProvider
 import org.apache.spark.sql.execution.joins.blaze.plan.NativeSortMergeJoinExecProvider
 import org.apache.spark.sql.execution.metric.SQLMetric
 import org.apache.spark.sql.hive.execution.InsertIntoHiveTable
+import org.apache.spark.sql.internal.SQLConf
+import org.apache.spark.sql.types.ArrayType
 import org.apache.spark.sql.types.DataType
 import org.apache.spark.sql.types.IntegerType
 import org.apache.spark.sql.types.StringType
@@ -195,13 +201,29 @@ class ShimsImpl extends Shims with Logging {
       joinType,
       broadcastSide)
 
+  override def createNativeBroadcastNestedLoopJoinExec(
+      left: SparkPlan,
+      right: SparkPlan,
+      outputPartitioning: Partitioning,
+      joinType: JoinType,
+      broadcastSide: BroadcastSide,
+      condition: Option[Expression]): NativeBroadcastNestedLoopJoinBase =
+    NativeBroadcastNestedLoopJoinExec(
+      left,
+      right,
+      outputPartitioning,
+      joinType,
+      broadcastSide,
+      condition)
+
   override def createNativeSortMergeJoinExec(
       left: SparkPlan,
       right: SparkPlan,
       leftKeys: Seq[Expression],
       rightKeys: Seq[Expression],
-      joinType: JoinType): NativeSortMergeJoinBase =
-    NativeSortMergeJoinExecProvider.provide(left, right, leftKeys, rightKeys, joinType)
+      joinType: JoinType,
+      condition: Option[Expression]): NativeSortMergeJoinBase =
+    NativeSortMergeJoinExecProvider.provide(left, right, leftKeys, rightKeys, joinType, condition)
 
   override def createNativeShuffledHashJoinExec(
       left: SparkPlan,
@@ -493,6 +515,18 @@ class ShimsImpl extends Shims with Logging {
           case Some(v) => return Some(v)
           case None =>
         }
+        convertRegExpFunctions(e, isPruningExpr, fallback) match {
+          case Some(v) => return Some(v)
+          case None =>
+        }
+        convertConvertTimezone(e, isPruningExpr, fallback) match {
+          case Some(v) => return Some(v)
+          case None =>
+        }
+        convertJsonFunctions(e, isPruningExpr, fallback) match {
+          case Some(v) => return Some(v)
+          case None =>
+        }
         None
     }
   }
@@ -501,6 +535,173 @@ class ShimsImpl extends Shims with Logging {
     expr.asInstanceOf[Like].escapeChar
   }
 
+  // percentile_cont/percentile_disc with descending orderings are reversed since spark3.3
+  @enableIf(Seq("spark-3.3", "spark-3.4", "spark-3.5").contains(System.getProperty("blaze.shim")))
+  override def isPercentileReversed(expr: Expression): Boolean = {
+    import org.apache.spark.sql.catalyst.expressions.aggregate.Percentile
+    expr.asInstanceOf[Percentile].reverse
+  }
+
+  @enableIf(Seq("spark-3.0", "spark-3.1", "spark-3.2").contains(System.getProperty("blaze.shim")))
+  override def isPercentileReversed(expr: Expression): Boolean = false
+
+  @enableIf(
+    Seq("spark-3.1", "spark-3.2", "spark-3.3", "spark-3.4", "spark-3.5").contains(
+      System.getProperty("blaze.shim")))
+  override def getNullOnDivideByZero(expr: Expression): Boolean = {
+    import org.apache.spark.sql.catalyst.expressions.aggregate.Corr
+    import org.apache.spark.sql.catalyst.expressions.aggregate.CovPopulation
+    import org.apache.spark.sql.catalyst.expressions.aggregate.CovSample
+    import org.apache.spark.sql.catalyst.expressions.aggregate.StddevPop
+    import org.apache.spark.sql.catalyst.expressions.aggregate.StddevSamp
+    import org.apache.spark.sql.catalyst.expressions.aggregate.VariancePop
+    import org.apache.spark.sql.catalyst.expressions.aggregate.VarianceSamp
+    expr match {
+      case e: StddevSamp => e.nullOnDivideByZero
+      case e: StddevPop => e.nullOnDivideByZero
+      case e: VarianceSamp => e.nullOnDivideByZero
+      case e: VariancePop => e.nullOnDivideByZero
+      case e: CovSample => e.nullOnDivideByZero
+      case e: CovPopulation => e.nullOnDivideByZero
+      case e: Corr => e.nullOnDivideByZero
+    }
+  }
+
+  // statistical aggregates always behave like spark.sql.legacy.statisticalAggregate=true
+  @enableIf(Seq("spark-3.0").contains(System.getProperty("blaze.shim")))
+  override def getNullOnDivideByZero(expr: Expression): Boolean = false
+
+  // spark3.3+ encodes base64 with java's mime encoder, which chunks the output into lines of
+  // 76 chars. spark3.5.2+ has an explicit chunkBase64 flag
+  @enableIf(Seq("spark-3.5").contains(System.getProperty("blaze.shim")))
+  override def isBase64Chunked(expr: Expression): Boolean = {
+    import org.apache.spark.sql.catalyst.expressions.Base64
+    expr.asInstanceOf[Base64].chunkBase64
+  }
+
+  @enableIf(Seq("spark-3.3", "spark-3.4").contains(System.getProperty("blaze.shim")))
+  override def isBase64Chunked(expr: Expression): Boolean = true
+
+  @enableIf(Seq("spark-3.0", "spark-3.1", "spark-3.2").contains(System.getProperty("blaze.shim")))
+  override def isBase64Chunked(expr: Expression): Boolean = false
+
+  // failOnError of unhex (spark3.3+) and unbase64 (spark3.4+), ansiEnabled of conv (spark3.5+)
+  @enableIf(Seq("spark-3.5").contains(System.getProperty("blaze.shim")))
+  override def getStringConversionFailOnError(expr: Expression): Boolean = {
+    import org.apache.spark.sql.catalyst.expressions.Conv
+    import org.apache.spark.sql.catalyst.expressions.UnBase64
+    import org.apache.spark.sql.catalyst.expressions.Unhex
+    expr match {
+      case e: Unhex => e.failOnError
+      case e: UnBase64 => e.failOnError
+      case e: Conv => e.ansiEnabled
+      case _ => false
+    }
+  }
+
+  @enableIf(Seq("spark-3.4").contains(System.getProperty("blaze.shim")))
+  override def getStringConversionFailOnError(expr: Expression): Boolean = {
+    import org.apache.spark.sql.catalyst.expressions.UnBase64
+    import org.apache.spark.sql.catalyst.expressions.Unhex
+    expr match {
+      case e: Unhex => e.failOnError
+      case e: UnBase64 => e.failOnError
+      case _ => false
+    }
+  }
+
+  @enableIf(Seq("spark-3.3").contains(System.getProperty("blaze.shim")))
+  override def getStringConversionFailOnError(expr: Expression): Boolean = {
+    import org.apache.spark.sql.catalyst.expressions.Unhex
+    expr match {
+      case e: Unhex => e.failOnError
+      case _ => false
+    }
+  }
+
+  @enableIf(Seq("spark-3.0", "spark-3.1", "spark-3.2").contains(System.getProperty("blaze.shim")))
+  override def getStringConversionFailOnError(expr: Expression): Boolean = false
+
+  @enableIf(Seq("spark-3.4", "spark-3.5").contains(System.getProperty("blaze.shim")))
+  override def getEvalMode(expr: Expression): pb.EvalMode = {
+    import org.apache.spark.sql.catalyst.expressions.Add
+    import org.apache.spark.sql.catalyst.expressions.Cast
+    import org.apache.spark.sql.catalyst.expressions.Divide
+    import org.apache.spark.sql.catalyst.expressions.EvalMode
+    import org.apache.spark.sql.catalyst.expressions.Multiply
+    import org.apache.spark.sql.catalyst.expressions.Remainder
+    import org.apache.spark.sql.catalyst.expressions.Subtract
+    import org.apache.spark.sql.catalyst.expressions.aggregate.Average
+    import org.apache.spark.sql.catalyst.expressions.aggregate.Sum
+
+    val evalMode = expr match {
+      case e: Cast => e.evalMode
+      case e: Add => e.evalMode
+      case e: Subtract => e.evalMode
+      case e: Multiply => e.evalMode
+      case e: Divide => e.evalMode
+      case e: Remainder => e.evalMode
+      case e: Sum => e.evalMode
+      case e: Average => e.evalMode
+      case _ => EvalMode.fromSQLConf(SQLConf.get)
+    }
+    evalMode match {
+      case EvalMode.ANSI => pb.EvalMode.ANSI
+      case EvalMode.TRY => pb.EvalMode.TRY
+      case EvalMode.LEGACY => pb.EvalMode.LEGACY
+    }
+  }
+
+  @enableIf(Seq("spark-3.3").contains(System.getProperty("blaze.shim")))
+  override def getEvalMode(expr: Expression): pb.EvalMode = {
+    import org.apache.spark.sql.catalyst.expressions.Add
+    import org.apache.spark.sql.catalyst.expressions.Cast
+    import org.apache.spark.sql.catalyst.expressions.Divide
+    import org.apache.spark.sql.catalyst.expressions.Multiply
+    import org.apache.spark.sql.catalyst.expressions.Remainder
+    import org.apache.spark.sql.catalyst.expressions.Subtract
+    import org.apache.spark.sql.catalyst.expressions.aggregate.Average
+    import org.apache.spark.sql.catalyst.expressions.aggregate.Sum
+
+    ansiEvalMode(expr match {
+      case e: Cast => e.ansiEnabled
+      case e: Add => e.failOnError
+      case e: Subtract => e.failOnError
+      case e: Multiply => e.failOnError
+      case e: Divide => e.failOnError
+      case e: Remainder => e.failOnError
+      case e: Sum => e.useAnsiAdd
+      case e: Average => e.useAnsiAdd
+      case _ => SQLConf.get.ansiEnabled
+    })
+  }
+
+  // cast and aggregates have no ansi flag in spark3.2, which is taken from the conf
+  @enableIf(Seq("spark-3.2").contains(System.getProperty("blaze.shim")))
+  override def getEvalMode(expr: Expression): pb.EvalMode = {
+    import org.apache.spark.sql.catalyst.expressions.Add
+    import org.apache.spark.sql.catalyst.expressions.Divide
+    import org.apache.spark.sql.catalyst.expressions.Multiply
+    import org.apache.spark.sql.catalyst.expressions.Remainder
+    import org.apache.spark.sql.catalyst.expressions.Subtract
+
+    ansiEvalMode(expr match {
+      case e: Add => e.failOnError
+      case e: Subtract => e.failOnError
+      case e: Multiply => e.failOnError
+      case e: Divide => e.failOnError
+      case e: Remainder => e.failOnError
+      case _ => SQLConf.get.ansiEnabled
+    })
+  }
+
+  @enableIf(Seq("spark-3.0", "spark-3.1").contains(System.getProperty("blaze.shim")))
+  override def getEvalMode(expr: Expression): pb.EvalMode =
+    ansiEvalMode(SQLConf.get.ansiEnabled)
+
+  private def ansiEvalMode(ansiEnabled: Boolean): pb.EvalMode =
+    if (ansiEnabled) pb.EvalMode.ANSI else pb.EvalMode.LEGACY
+
   override def convertMoreAggregateExpr(e: AggregateExpression): Option[pb.PhysicalExprNode] = {
     assert(getAggregateExpressionFilter(e).isEmpty)
 
@@ -530,6 +731,46 @@ class ShimsImpl extends Shims with Logging {
     expr.asInstanceOf[AggregateExpression].filter
   }
 
+  override def convertMoreWindowFunction(
+      function: Expression,
+      frame: WindowFrame,
+      orderSpec: Seq[SortOrder]): Option[pb.WindowExprNode] = {
+    function match {
+      case First(child, ignoresNull) =>
+        val nativeFrame = NativeWindowBase.convertWindowFrame(frame, orderSpec)
+        Some(
+          buildWindowExpr(pb.WindowFunction.FIRST_VALUE, child :: Nil, ignoresNull)
+            .setFrame(nativeFrame)
+            .build())
+      case Last(child, ignoresNull) =>
+        val nativeFrame = NativeWindowBase.convertWindowFrame(frame, orderSpec)
+        Some(
+          buildWindowExpr(pb.WindowFunction.LAST_VALUE, child :: Nil, ignoresNull)
+            .setFrame(nativeFrame)
+            .build())
+      case e => convertOffsetWindowFunction(e, frame, orderSpec)
+    }
+  }
+
+  private def buildWindowExpr(
+      windowFunc: pb.WindowFunction,
+      children: Seq[Expression],
+      ignoreNulls: Boolean): pb.WindowExprNode.Builder = {
+    val windowExprBuilder = pb.WindowExprNode
+      .newBuilder()
+      .setFuncType(pb.WindowFunctionType.Window)
+      .setWindowFunc(windowFunc)
+      .setIgnoreNulls(ignoreNulls)
+    children.foreach(child => windowExprBuilder.addChildren(NativeConverters.convertExpr(child)))
+    windowExprBuilder
+  }
+
+  // native offset window functions require literal offsets
+  private def literalOffset(offset: Expression): Expression = {
+    assert(offset.foldable, s"window function offset must be foldable: $offset")
+    Literal.create(offset.eval(), offset.dataType)
+  }
+
   @enableIf(
     Seq("spark-3.2", "spark-3.3", "spark-3.4", "spark-3.5").contains(
       System.getProperty("blaze.shim")))
@@ -835,6 +1076,76 @@ class ShimsImpl extends Shims with Logging {
     }
   }
 
+  @enableIf(
+    Seq("spark-3.2", "spark-3.3", "spark-3.4", "spark-3.5").contains(
+      System.getProperty("blaze.shim")))
+  private def convertOffsetWindowFunction(
+      e: Expression,
+      frame: WindowFrame,
+      orderSpec: Seq[SortOrder]): Option[pb.WindowExprNode] = {
+    import org.apache.spark.sql.catalyst.expressions.Lag
+    import org.apache.spark.sql.catalyst.expressions.Lead
+    import org.apache.spark.sql.catalyst.expressions.NthValue
+    e match {
+      case e: Lag =>
+        val children = e.input :: literalOffset(e.inputOffset) :: e.default :: Nil
+        Some(buildWindowExpr(pb.WindowFunction.LAG, children, e.ignoreNulls).build())
+      case e: Lead =>
+        val children = e.input :: literalOffset(e.offset) :: e.default :: Nil
+        Some(buildWindowExpr(pb.WindowFunction.LEAD, children, e.ignoreNulls).build())
+      case e: NthValue =>
+        val children = e.input :: literalOffset(e.offset) :: Nil
+        Some(
+          buildWindowExpr(pb.WindowFunction.NTH_VALUE, children, e.ignoreNulls)
+            .setFrame(NativeWindowBase.convertWindowFrame(frame, orderSpec))
+            .build())
+      case _ => None
+    }
+  }
+
+  @enableIf(Seq("spark-3.1").contains(System.getProperty("blaze.shim")))
+  private def convertOffsetWindowFunction(
+      e: Expression,
+      frame: WindowFrame,
+      orderSpec: Seq[SortOrder]): Option[pb.WindowExprNode] = {
+    import org.apache.spark.sql.catalyst.expressions.Lag
+    import org.apache.spark.sql.catalyst.expressions.Lead
+    import org.apache.spark.sql.catalyst.expressions.NthValue
+    e match {
+      case e: Lag =>
+        val children = e.input :: literalOffset(e.inputOffset) :: e.default :: Nil
+        Some(buildWindowExpr(pb.WindowFunction.LAG, children, ignoreNulls = false).build())
+      case e: Lead =>
+        val children = e.input :: literalOffset(e.offset) :: e.default :: Nil
+        Some(buildWindowExpr(pb.WindowFunction.LEAD, children, ignoreNulls = false).build())
+      case e: NthValue =>
+        val children = e.input :: literalOffset(e.offset) :: Nil
+        Some(
+          buildWindowExpr(pb.WindowFunction.NTH_VALUE, children, e.ignoreNulls)
+            .setFrame(NativeWindowBase.convertWindowFrame(frame, orderSpec))
+            .build())
+      case _ => None
+    }
+  }
+
+  @enableIf(Seq("spark-3.0").contains(System.getProperty("blaze.shim")))
+  private def convertOffsetWindowFunction(
+      e: Expression,
+      frame: WindowFrame,
+      orderSpec: Seq[SortOrder]): Option[pb.WindowExprNode] = {
+    import org.apache.spark.sql.catalyst.expressions.Lag
+    import org.apache.spark.sql.catalyst.expressions.Lead
+    e match {
+      case e: Lag =>
+        val children = e.input :: literalOffset(e.offset) :: e.default :: Nil
+        Some(buildWindowExpr(pb.WindowFunction.LAG, children, ignoreNulls = false).build())
+      case e: Lead =>
+        val children = e.input :: literalOffset(e.offset) :: e.default :: Nil
+        Some(buildWindowExpr(pb.WindowFunction.LEAD, children, ignoreNulls = false).build())
+      case _ => None
+    }
+  }
+
   @enableIf(Seq("spark-3.4", "spark-3.5").contains(System.getProperty("blaze.shim")))
   private def convertPromotePrecision(
       e: Expression,
@@ -898,6 +1209,119 @@ class ShimsImpl extends Shims with Logging {
       isPruningExpr: Boolean,
       fallback: Expression => pb.PhysicalExprNode): Option[pb.PhysicalExprNode] = None
 
+  @enableIf(
+    Seq("spark-3.1", "spark-3.2", "spark-3.3", "spark-3.4", "spark-3.5").contains(
+      System.getProperty("blaze.shim")))
+  private def convertRegExpFunctions(
+      e: Expression,
+      isPruningExpr: Boolean,
+      fallback: Expression => pb.PhysicalExprNode): Option[pb.PhysicalExprNode] = {
+    import org.apache.spark.sql.catalyst.expressions.RegExpExtractAll
+    import org.apache.spark.sql.catalyst.expressions.RegExpReplace
+    e match {
+      case e: RegExpExtractAll if NativeConverters.isNativeRegex(e.regexp) =>
+        Some(
+          NativeConverters.buildExtScalarFunctionNode(
+            "RegExpExtractAll",
+            e.children,
+            ArrayType(StringType),
+            isPruningExpr,
+            fallback))
+      case e: RegExpReplace if NativeConverters.isNativeRegex(e.regexp) =>
+        Some(
+          NativeConverters.buildExtScalarFunctionNode(
+            "RegExpReplace",
+            e.subject :: e.regexp :: e.rep :: e.pos :: Nil,
+            StringType,
+            isPruningExpr,
+            fallback))
+      case _ => None
+    }
+  }
+
+  @enableIf(Seq("spark-3.0").contains(System.getProperty("blaze.shim")))
+  private def convertRegExpFunctions(
+      e: Expression,
+      isPruningExpr: Boolean,
+      fallback: Expression => pb.PhysicalExprNode): Option[pb.PhysicalExprNode] = {
+    import org.apache.spark.sql.catalyst.expressions.RegExpReplace
+    e match {
+      case e: RegExpReplace if NativeConverters.isNativeRegex(e.regexp) =>
+        Some(
+          NativeConverters.buildExtScalarFunctionNode(
+            "RegExpReplace",
+            e.subject :: e.regexp :: e.rep :: Nil,
+            StringType,
+            isPruningExpr,
+            fallback))
+      case _ => None
+    }
+  }
+
+  @enableIf(Seq("spark-3.4", "spark-3.5").contains(System.getProperty("blaze.shim")))
+  private def convertConvertTimezone(
+      e: Expression,
+      isPruningExpr: Boolean,
+      fallback: Expression => pb.PhysicalExprNode): Option[pb.PhysicalExprNode] = {
+    import org.apache.spark.sql.catalyst.expressions.ConvertTimezone
+    import org.apache.spark.sql.types.TimestampNTZType
+    e match {
+      // falls back if timestamp_ntz values cannot be converted to native timestamps
+      case e: ConvertTimezone =>
+        Some(
+          NativeConverters.buildExtScalarFunctionNode(
+            "ConvertTimezone",
+            e.sourceTz :: e.targetTz :: e.sourceTs :: Nil,
+            TimestampNTZType,
+            isPruningExpr,
+            fallback))
+      case _ => None
+    }
+  }
+
+  @enableIf(
+    Seq("spark-3.0", "spark-3.1", "spark-3.2", "spark-3.3").contains(
+      System.getProperty("blaze.shim")))
+  private def convertConvertTimezone(
+      e: Expression,
+      isPruningExpr: Boolean,
+      fallback: Expression => pb.PhysicalExprNode): Option[pb.PhysicalExprNode] = None
+
+  @enableIf(
+    Seq("spark-3.1", "spark-3.2", "spark-3.3", "spark-3.4", "spark-3.5").contains(
+      System.getProperty("blaze.shim")))
+  private def convertJsonFunctions(
+      e: Expression,
+      isPruningExpr: Boolean,
+      fallback: Expression => pb.PhysicalExprNode): Option[pb.PhysicalExprNode] = {
+    import org.apache.spark.sql.catalyst.expressions.JsonObjectKeys
+    import org.apache.spark.sql.catalyst.expressions.LengthOfJsonArray
+    e match {
+      case e: LengthOfJsonArray =>
+        Some(
+          NativeConverters.buildExtScalarFunctionNode(
+            "JsonArrayLength",
+            e.child :: Nil,
+            IntegerType,
+            isPruningExpr,
+            fallback))
+      case e: JsonObjectKeys =>
+        Some(
+          NativeConverters.buildExtScalarFunctionNode(
+            "JsonObjectKeys",
+            e.child :: Nil,
+            e.dataType,
+            isPruningExpr,
+            fallback))
+      case _ => None
+    }
+  }
+
+  @enableIf(Seq("spark-3.0").contains(System.getProperty("blaze.shim")))
+  private def convertJsonFunctions(
+      e: Expression,
+      isPruningExpr: Boolean,
+      fallback: Expression => pb.PhysicalExprNode): Option[pb.PhysicalExprNode] = None
 }
 
 case class ForceNativeExecutionWrapper(override val child: SparkPlan)
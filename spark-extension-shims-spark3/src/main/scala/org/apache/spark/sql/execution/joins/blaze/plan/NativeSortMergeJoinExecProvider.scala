@@ -32,7 +32,8 @@ case object NativeSortMergeJoinExecProvider {
       right: SparkPlan,
       leftKeys: Seq[Expression],
       rightKeys: Seq[Expression],
-      joinType: JoinType): NativeSortMergeJoinBase = {
+      joinType: JoinType,
+      condition: Option[Expression]): NativeSortMergeJoinBase = {
 
     import org.apache.spark.rdd.RDD
     import org.apache.spark.sql.catalyst.InternalRow
@@ -43,12 +44,11 @@ case object NativeSortMergeJoinExecProvider {
         override val right: SparkPlan,
         override val leftKeys: Seq[Expression],
         override val rightKeys: Seq[Expression],
-        override val joinType: JoinType)
-        extends NativeSortMergeJoinBase(left, right, leftKeys, rightKeys, joinType)
+        override val joinType: JoinType,
+        override val condition: Option[Expression])
+        extends NativeSortMergeJoinBase(left, right, leftKeys, rightKeys, joinType, condition)
         with org.apache.spark.sql.execution.joins.ShuffledJoin {
 
-      override def condition: Option[Expression] = None
-
       override def isSkewJoin: Boolean = false
 
       override def supportCodegen: Boolean = false
@@ -68,7 +68,7 @@ case object NativeSortMergeJoinExecProvider {
 
       override def nodeName: String = "NativeSortMergeJoinExec"
     }
-    NativeSortMergeJoinExec(left, right, leftKeys, rightKeys, joinType)
+    NativeSortMergeJoinExec(left, right, leftKeys, rightKeys, joinType, condition)
   }
 
   @enableIf(Seq("spark-3.0", "spark-3.1").contains(System.getProperty("blaze.shim")))
@@ -77,7 +77,8 @@ case object NativeSortMergeJoinExecProvider {
       right: SparkPlan,
       leftKeys: Seq[Expression],
       rightKeys: Seq[Expression],
-      joinType: JoinType): NativeSortMergeJoinBase = {
+      joinType: JoinType,
+      condition: Option[Expression]): NativeSortMergeJoinBase = {
 
     import org.apache.spark.sql.catalyst.expressions.Attribute
     import org.apache.spark.sql.execution.joins.SortMergeJoinExec
@@ -87,11 +88,19 @@ case object NativeSortMergeJoinExecProvider {
         override val right: SparkPlan,
         leftKeys: Seq[Expression],
         rightKeys: Seq[Expression],
-        joinType: JoinType)
-        extends NativeSortMergeJoinBase(left, right, leftKeys, rightKeys, joinType) {
+        joinType: JoinType,
+        condition: Option[Expression])
+        extends NativeSortMergeJoinBase(left, right, leftKeys, rightKeys, joinType, condition) {
 
       private def smj: SortMergeJoinExec =
-        SortMergeJoinExec(leftKeys, rightKeys, joinType, None, left, right, isSkewJoin = false)
+        SortMergeJoinExec(
+          leftKeys,
+          rightKeys,
+          joinType,
+          condition,
+          left,
+          right,
+          isSkewJoin = false)
 
       override def output: Seq[Attribute] = smj.output
 
@@ -103,6 +112,6 @@ case object NativeSortMergeJoinExecProvider {
 
       override def nodeName: String = "NativeSortMergeJoinExec"
     }
-    NativeSortMergeJoinExec(left, right, leftKeys, rightKeys, joinType)
+    NativeSortMergeJoinExec(left, right, leftKeys, rightKeys, joinType, condition)
   }
 }
@@ -0,0 +1,122 @@
+/*
+ * Copyright 2022 The Blaze Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+package org.apache.spark.sql.blaze
+
+import org.apache.spark.sql.SparkSession
+import org.apache.spark.sql.catalyst.expressions.ArrayFilter
+import org.apache.spark.sql.catalyst.expressions.ArrayTransform
+import org.apache.spark.sql.catalyst.expressions.CreateArray
+import org.apache.spark.sql.catalyst.expressions.EqualTo
+import org.apache.spark.sql.catalyst.expressions.Expression
+import org.apache.spark.sql.catalyst.expressions.LambdaFunction
+import org.apache.spark.sql.catalyst.expressions.Literal
+import org.apache.spark.sql.catalyst.expressions.NamedLambdaVariable
+import org.apache.spark.sql.catalyst.expressions.UnaryExpression
+import org.apache.spark.sql.catalyst.expressions.codegen.CodegenFallback
+import org.apache.spark.sql.types.DataType
+import org.apache.spark.sql.types.IntegerType
+import org.blaze.{protobuf => pb}
+import org.scalatest.BeforeAndAfterAll
+import org.scalatest.funsuite.AnyFunSuite
+
+class NativeConvertersSuite extends AnyFunSuite with BeforeAndAfterAll {
+  import NativeConvertersSuite._
+
+  private var spark: SparkSession = _
+
+  override def beforeAll(): Unit = {
+    super.beforeAll()
+    spark = SparkSession
+      .builder()
+      .master("local[1]")
+      .appName(getClass.getSimpleName)
+      .getOrCreate()
+  }
+
+  override def afterAll(): Unit = {
+    try {
+      spark.stop()
+    } finally {
+      super.afterAll()
+    }
+  }
+
+  private def fallbackExpr(converted: pb.PhysicalExprNode): Expression = {
+    assert(converted.hasSparkUdfWrapperExpr)
+    val serialized = converted.getSparkUdfWrapperExpr.getSerialized.toByteArray
+    NativeConverters.deserializeExpression[Expression](serialized)._1
+  }
+
+  test("higher-order functions with inconvertible lambda bodies fall back entirely") {
+    val x = NamedLambdaVariable("x", IntegerType, nullable = false)
+    val array = CreateArray(Seq(Literal(1), Literal(2), Literal(3)))
+
+    val transform = ArrayTransform(array, LambdaFunction(NonNativeNegate(x), Seq(x)))
+    assert(fallbackExpr(NativeConverters.convertExpr(transform)).isInstanceOf[ArrayTransform])
+
+    val filter =
+      ArrayFilter(array, LambdaFunction(EqualTo(NonNativeNegate(x), Literal(-2)), Seq(x)))
+    assert(fallbackExpr(NativeConverters.convertExpr(filter)).isInstanceOf[ArrayFilter])
+  }
+
+  test("regex patterns with unsupported constructs are rejected without native calls") {
+    val supported = Seq(
+      "^a.*b$",
+      "(?i)abc",
+      "(?<year>\\d{4})-(\\d{2})",
+      "a{2,3}?",
+      "[(?=]x",
+      "\\Q(?=\\1)\\E",
+      "\\p{javaLowerCase}+",
+      "a\\Z")
+    supported.foreach { pattern =>
+      assert(NativeConverters.unsupportedRegexReason(pattern).isEmpty, pattern)
+    }
+
+    val unsupported = Seq(
+      "a(?=b)" -> "lookahead",
+      "a(?!b)" -> "lookahead",
+      "(?<=a)b" -> "lookbehind",
+      "(?<!a)b" -> "lookbehind",
+      "(a)\\1" -> "backreference",
+      "(?<x>a)\\k<x>" -> "backreference",
+      "a++" -> "possessive quantifier",
+      "a{2}+" -> "possessive quantifier",
+      "(?>a)" -> "atomic group",
+      "\\Ga" -> "'\\G'",
+      "(?d)a" -> "flag 'd'",
+      "\\p{InGreek}" -> "character property 'InGreek'",
+      "(a" -> "invalid pattern")
+    unsupported.foreach { case (pattern, reason) =>
+      val actual = NativeConverters.unsupportedRegexReason(pattern)
+      assert(actual.exists(_.startsWith(reason)), s"$pattern: $actual")
+    }
+    assert(!NativeConverters.isNativeRegex(Literal("a(?=b)")))
+  }
+}
+
+object NativeConvertersSuite {
+
+  // an expression without native implementation
+  case class NonNativeNegate(child: Expression) extends UnaryExpression with CodegenFallback {
+    override def dataType: DataType = child.dataType
+
+    override protected def nullSafeEval(input: Any): Any = -input.asInstanceOf[Int]
+
+    protected def withNewChildInternal(newChild: Expression): Expression =
+      copy(child = newChild)
+  }
+}
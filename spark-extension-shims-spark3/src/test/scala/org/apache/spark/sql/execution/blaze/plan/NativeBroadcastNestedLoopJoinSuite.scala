@@ -0,0 +1,94 @@
+/*
+ * Copyright 2022 The Blaze Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+package org.apache.spark.sql.execution.blaze.plan
+
+import org.apache.spark.rdd.RDD
+import org.apache.spark.sql.catalyst.InternalRow
+import org.apache.spark.sql.catalyst.expressions.Attribute
+import org.apache.spark.sql.catalyst.expressions.AttributeReference
+import org.apache.spark.sql.catalyst.plans.FullOuter
+import org.apache.spark.sql.catalyst.plans.Inner
+import org.apache.spark.sql.catalyst.plans.JoinType
+import org.apache.spark.sql.catalyst.plans.LeftAnti
+import org.apache.spark.sql.catalyst.plans.LeftOuter
+import org.apache.spark.sql.catalyst.plans.LeftSemi
+import org.apache.spark.sql.catalyst.plans.RightOuter
+import org.apache.spark.sql.catalyst.plans.physical.Partitioning
+import org.apache.spark.sql.catalyst.plans.physical.UnknownPartitioning
+import org.apache.spark.sql.execution.LeafExecNode
+import org.apache.spark.sql.execution.joins.blaze.plan.NativeBroadcastNestedLoopJoinExec
+import org.apache.spark.sql.types.IntegerType
+import org.scalatest.funsuite.AnyFunSuite
+
+class NativeBroadcastNestedLoopJoinSuite extends AnyFunSuite {
+  private val a = AttributeReference("a", IntegerType)()
+  private val b = AttributeReference("b", IntegerType)()
+
+  private def createJoin(
+      joinType: JoinType,
+      broadcastSide: BroadcastSide,
+      numProbedPartitions: Int): NativeBroadcastNestedLoopJoinExec = {
+    val (left, right) = broadcastSide match {
+      case BroadcastLeft =>
+        (TestLeafExec(Seq(a), 1), TestLeafExec(Seq(b), numProbedPartitions))
+      case BroadcastRight =>
+        (TestLeafExec(Seq(a), numProbedPartitions), TestLeafExec(Seq(b), 1))
+    }
+    NativeBroadcastNestedLoopJoinExec(
+      left,
+      right,
+      UnknownPartitioning(numProbedPartitions),
+      joinType,
+      broadcastSide,
+      None)
+  }
+
+  test("joins outputting broadcast rows over multiple probed partitions fall back") {
+    Seq(
+      (LeftOuter, BroadcastLeft),
+      (LeftSemi, BroadcastLeft),
+      (LeftAnti, BroadcastLeft),
+      (FullOuter, BroadcastLeft),
+      (RightOuter, BroadcastRight),
+      (FullOuter, BroadcastRight)).foreach { case (joinType, broadcastSide) =>
+      assertThrows[NotImplementedError] {
+        createJoin(joinType, broadcastSide, numProbedPartitions = 2)
+      }
+      // a single probed partition sees all matches of the broadcast side
+      createJoin(joinType, broadcastSide, numProbedPartitions = 1)
+    }
+  }
+
+  test("joins outputting only probed rows are converted over multiple partitions") {
+    Seq(
+      (Inner, BroadcastLeft),
+      (RightOuter, BroadcastLeft),
+      (Inner, BroadcastRight),
+      (LeftOuter, BroadcastRight),
+      (LeftSemi, BroadcastRight),
+      (LeftAnti, BroadcastRight)).foreach { case (joinType, broadcastSide) =>
+      createJoin(joinType, broadcastSide, numProbedPartitions = 2)
+    }
+  }
+}
+
+private case class TestLeafExec(output: Seq[Attribute], numPartitions: Int)
+    extends LeafExecNode {
+  override def outputPartitioning: Partitioning = UnknownPartitioning(numPartitions)
+
+  override protected def doExecute(): RDD[InternalRow] =
+    throw new UnsupportedOperationException()
+}
@@ -0,0 +1,112 @@
+/*
+ * Copyright 2022 The Blaze Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+package org.apache.spark.sql.execution.blaze.plan
+
+import scala.collection.JavaConverters._
+
+import org.apache.spark.sql.SparkSession
+import org.apache.spark.sql.blaze.NativeConverters
+import org.apache.spark.sql.catalyst.InternalRow
+import org.apache.spark.sql.catalyst.expressions.Ascending
+import org.apache.spark.sql.catalyst.expressions.AttributeReference
+import org.apache.spark.sql.catalyst.expressions.Descending
+import org.apache.spark.sql.catalyst.expressions.Murmur3Hash
+import org.apache.spark.sql.catalyst.expressions.SortOrder
+import org.apache.spark.sql.types.IntegerType
+import org.apache.spark.sql.types.MapType
+import org.blaze.{protobuf => pb}
+import org.scalatest.BeforeAndAfterAll
+import org.scalatest.funsuite.AnyFunSuite
+
+class NativeShuffleExchangeSuite extends AnyFunSuite with BeforeAndAfterAll {
+  private var spark: SparkSession = _
+
+  override def beforeAll(): Unit = {
+    super.beforeAll()
+    spark = SparkSession
+      .builder()
+      .master("local[1]")
+      .appName(getClass.getSimpleName)
+      .getOrCreate()
+  }
+
+  override def afterAll(): Unit = {
+    try {
+      spark.stop()
+    } finally {
+      super.afterAll()
+    }
+  }
+
+  private val key = AttributeReference("key", IntegerType, nullable = false)()
+
+  private def computeBounds(sortOrder: SortOrder): Seq[Int] = {
+    val rdd = spark.sparkContext
+      .parallelize(0 until 1000, 4)
+      .map(i => InternalRow(999 - i))
+    NativeShuffleExchangeBase
+      .computeRangeBounds(rdd, Seq(key), Seq(sortOrder), numPartitions = 4)
+      .map(_.getInt(0))
+  }
+
+  test("range bounds are sampled in sort order") {
+    val ascBounds = computeBounds(SortOrder(key, Ascending))
+    assert(ascBounds.length == 3)
+    assert(ascBounds == ascBounds.sorted)
+    assert(ascBounds.forall(b => b > 0 && b < 999))
+
+    val descBounds = computeBounds(SortOrder(key, Descending))
+    assert(descBounds.length == 3)
+    assert(descBounds == descBounds.sorted.reverse)
+    assert(descBounds.forall(b => b > 0 && b < 999))
+  }
+
+  test("range bounds are converted to one native list per sort expr") {
+    val sortOrder = Seq(SortOrder(key, Descending))
+    val bounds = Array(700, 400, 100).map(b => InternalRow(b))
+    val nativeSortExprs = sortOrder.map { sortOrder =>
+      pb.PhysicalSortExprNode
+        .newBuilder()
+        .setExpr(NativeConverters.convertExpr(sortOrder.child))
+        .setAsc(false)
+        .setNullsFirst(false)
+        .build()
+    }
+    val rangeRepartition =
+      NativeShuffleExchangeBase.buildRangeRepartition(sortOrder, nativeSortExprs, bounds)
+
+    assert(rangeRepartition.getSortExprCount == 1)
+    assert(!rangeRepartition.getSortExpr(0).getAsc)
+    assert(rangeRepartition.getBoundsCount == 1)
+    val values = rangeRepartition.getBounds(0).getValuesList.asScala.map(_.getInt32Value)
+    assert(values == Seq(700, 400, 100))
+  }
+
+  test("round-robin rows are sorted by hash and orderable columns") {
+    val map = AttributeReference("map", MapType(IntegerType, IntegerType))()
+    val sortOrder = NativeShuffleExchangeBase.roundRobinSortOrder(Seq(key, map))
+
+    assert(sortOrder.length == 2)
+    assert(sortOrder.head.child == Murmur3Hash(Seq(key, map), 42))
+    assert(sortOrder(1).child == key)
+  }
+
+  test("range bounds of unsupported types are rejected") {
+    assert(NativeShuffleExchangeBase.isSupportedRangeBoundType(IntegerType))
+    assert(
+      !NativeShuffleExchangeBase.isSupportedRangeBoundType(MapType(IntegerType, IntegerType)))
+  }
+}
@@ -0,0 +1,81 @@
+/*
+ * Copyright 2022 The Blaze Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+package org.apache.spark.sql.execution.blaze.plan
+
+import org.apache.spark.sql.catalyst.expressions.And
+import org.apache.spark.sql.catalyst.expressions.Ascending
+import org.apache.spark.sql.catalyst.expressions.AttributeReference
+import org.apache.spark.sql.catalyst.expressions.EqualTo
+import org.apache.spark.sql.catalyst.expressions.Expression
+import org.apache.spark.sql.catalyst.expressions.GreaterThanOrEqual
+import org.apache.spark.sql.catalyst.expressions.LessThan
+import org.apache.spark.sql.catalyst.expressions.LessThanOrEqual
+import org.apache.spark.sql.types.LongType
+import org.blaze.protobuf.JoinSide
+import org.scalatest.funsuite.AnyFunSuite
+
+class NativeSortMergeJoinSuite extends AnyFunSuite {
+  private val ts = AttributeReference("ts", LongType)()
+  private val start = AttributeReference("start", LongType)()
+  private val end = AttributeReference("end", LongType)()
+
+  test("between conditions are converted to band conditions") {
+    // r.start <= l.ts AND l.ts < r.end
+    val condition = And(GreaterThanOrEqual(ts, start), LessThan(ts, end))
+
+    val band =
+      NativeSortMergeJoinBase.convertBandCondition(condition, Seq(ts), Seq(start, end))
+    assert(band.getPointSide == JoinSide.LEFT_SIDE)
+    assert(band.getLowerInclusive)
+    assert(!band.getUpperInclusive)
+
+    // conjuncts in the other order, with the point on the right side
+    val swapped = And(LessThan(ts, end), LessThanOrEqual(start, ts))
+    val swappedBand =
+      NativeSortMergeJoinBase.convertBandCondition(swapped, Seq(start, end), Seq(ts))
+    assert(swappedBand.getPointSide == JoinSide.RIGHT_SIDE)
+    assert(swappedBand.getLowerInclusive)
+    assert(!swappedBand.getUpperInclusive)
+  }
+
+  test("band joins without equal keys sort points and lower bounds") {
+    // r.start <= l.ts AND l.ts <= r.end
+    val condition = And(LessThanOrEqual(start, ts), LessThanOrEqual(ts, end))
+
+    val (leftOrders, rightOrders) =
+      NativeSortMergeJoinBase.bandSortOrders(condition, Seq(ts), Seq(start, end))
+    assert(leftOrders.map(_.child) == Seq(ts))
+    assert(rightOrders.map(_.child) == Seq(start))
+    assert((leftOrders ++ rightOrders).forall(_.direction == Ascending))
+
+    val (swappedLeftOrders, swappedRightOrders) =
+      NativeSortMergeJoinBase.bandSortOrders(condition, Seq(start, end), Seq(ts))
+    assert(swappedLeftOrders.map(_.child) == Seq(start))
+    assert(swappedRightOrders.map(_.child) == Seq(ts))
+  }
+
+  test("non-band conditions are rejected") {
+    val reject = (condition: Expression) =>
+      assertThrows[NotImplementedError] {
+        NativeSortMergeJoinBase.convertBandCondition(condition, Seq(ts), Seq(start, end))
+      }
+    reject(EqualTo(ts, start))
+    reject(LessThan(ts, end))
+    reject(And(GreaterThanOrEqual(ts, start), LessThan(start, end)))
+    // point and bounds on the same side
+    reject(And(GreaterThanOrEqual(start, ts), LessThan(start, end)))
+  }
+}
@@ -0,0 +1,61 @@
+/*
+ * Copyright 2022 The Blaze Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+package org.apache.spark.sql.execution.blaze.plan
+
+import org.apache.spark.sql.catalyst.expressions.Ascending
+import org.apache.spark.sql.catalyst.expressions.AttributeReference
+import org.apache.spark.sql.catalyst.expressions.CurrentRow
+import org.apache.spark.sql.catalyst.expressions.Literal
+import org.apache.spark.sql.catalyst.expressions.RangeFrame
+import org.apache.spark.sql.catalyst.expressions.RowFrame
+import org.apache.spark.sql.catalyst.expressions.SortOrder
+import org.apache.spark.sql.catalyst.expressions.SpecifiedWindowFrame
+import org.apache.spark.sql.catalyst.expressions.UnboundedPreceding
+import org.apache.spark.sql.types.IntegerType
+import org.apache.spark.sql.types.TimestampType
+import org.blaze.{protobuf => pb}
+import org.scalatest.funsuite.AnyFunSuite
+
+class NativeWindowSuite extends AnyFunSuite {
+  private val intOrder = SortOrder(AttributeReference("i", IntegerType)(), Ascending)
+  private val tsOrder = SortOrder(AttributeReference("ts", TimestampType)(), Ascending)
+
+  test("rows frame offsets are converted to non-negative native offsets") {
+    val frame = SpecifiedWindowFrame(RowFrame, Literal(-2), Literal(1))
+    val nativeFrame = NativeWindowBase.convertWindowFrame(frame, Seq(intOrder))
+
+    assert(nativeFrame.getFrameType == pb.WindowFrameType.ROWS)
+    assert(nativeFrame.getStart.getBoundType == pb.WindowFrameBoundType.PRECEDING)
+    assert(nativeFrame.getStart.getOffset.getInt64Value == 2)
+    assert(nativeFrame.getEnd.getBoundType == pb.WindowFrameBoundType.FOLLOWING)
+    assert(nativeFrame.getEnd.getOffset.getInt64Value == 1)
+  }
+
+  test("range frames with offsets are rejected on unsupported order types") {
+    val frame = SpecifiedWindowFrame(RangeFrame, Literal(-1), CurrentRow)
+    val nativeFrame = NativeWindowBase.convertWindowFrame(frame, Seq(intOrder))
+    assert(nativeFrame.getFrameType == pb.WindowFrameType.RANGE)
+    assert(nativeFrame.getEnd.getBoundType == pb.WindowFrameBoundType.CURRENT_ROW)
+
+    assertThrows[AssertionError] {
+      NativeWindowBase.convertWindowFrame(frame, Seq(tsOrder))
+    }
+
+    // frames without offsets do not depend on the order type
+    val unboundedFrame = SpecifiedWindowFrame(RangeFrame, UnboundedPreceding, CurrentRow)
+    NativeWindowBase.convertWindowFrame(unboundedFrame, Seq(tsOrder))
+  }
+}
@@ -48,6 +48,10 @@ public enum BlazeConf {
     /// improves performance for special case that UDF concurrency matters
     UDF_WRAPPER_NUM_THREADS("spark.blaze.udfWrapperNumThreads", 1),
 
+    /// fallbacks unsupported imperative aggregate functions (like hive UDAFs) to spark UDAF
+    /// wrapper, aggregation buffers are kept in JVM and evaluated by spark.
+    UDAF_FALLBACK_ENABLE("spark.blaze.udafFallback.enable", true),
+
     /// enable extra metrics of input batch statistics
     INPUT_BATCH_STATISTICS_ENABLE("spark.blaze.enableInputBatchStatistics", true),
 
@@ -42,6 +42,8 @@ import org.apache.spark.sql.catalyst.expressions.aggregate.AggregateFunction
 import org.apache.spark.sql.catalyst.expressions.Ascending
 import org.apache.spark.sql.catalyst.expressions.SortOrder
 import org.apache.spark.sql.catalyst.plans.physical.HashPartitioning
+import org.apache.spark.sql.catalyst.plans.physical.RangePartitioning
+import org.apache.spark.sql.catalyst.plans.physical.RoundRobinPartitioning
 import org.apache.spark.sql.catalyst.plans.physical.Partitioning
 import org.apache.spark.sql.execution.FileSourceScanExec
 import org.apache.spark.sql.execution.FilterExec
@@ -258,15 +260,13 @@ object BlazeConverters extends Logging {
     logDebug(s"Converting ShuffleExchangeExec: ${Shims.get.simpleStringWithNodeId(exec)}")
 
     assert(
-      exec.outputPartitioning.numPartitions == 1 || exec.outputPartitioning
-        .isInstanceOf[HashPartitioning],
+      exec.outputPartitioning.numPartitions == 1 || (exec.outputPartitioning match {
+        case _: HashPartitioning | _: RangePartitioning | _: RoundRobinPartitioning => true
+        case _ => false
+      }),
       s"partitioning not supported: ${exec.outputPartitioning}")
 
-    val convertedChild = outputPartitioning match {
-      case p if p.isInstanceOf[HashPartitioning] || p.numPartitions == 1 =>
-        convertToNative(child)
-      case _ => child
-    }
+    val convertedChild = convertToNative(child)
     Shims.get.createNativeShuffleExchangeExec(
       outputPartitioning,
       addRenameColumnsExec(convertedChild))
@@ -386,14 +386,15 @@ object BlazeConverters extends Logging {
     logDebug(s"  rightKeys: $rightKeys")
     logDebug(s"  joinType: $joinType")
     logDebug(s"  condition: $condition")
-    assert(condition.isEmpty, "join condition is not supported")
 
+    // only band conditions like `l.ts BETWEEN r.start AND r.end` are supported
     Shims.get.createNativeSortMergeJoinExec(
       addRenameColumnsExec(convertToNative(left)),
       addRenameColumnsExec(convertToNative(right)),
       leftKeys,
       rightKeys,
-      joinType)
+      joinType,
+      condition)
   }
 
   def convertShuffledHashJoinExec(exec: ShuffledHashJoinExec): SparkPlan = {
@@ -521,7 +522,6 @@ object BlazeConverters extends Logging {
       logDebug(s"  joinType: ${exec.joinType}")
       logDebug(s"  buildSide: ${exec.buildSide}")
       logDebug(s"  condition: ${exec.condition}")
-      assert(condition.isEmpty, "join condition is not supported")
 
       // verify build side is native
       buildSide match {
@@ -531,18 +531,16 @@ object BlazeConverters extends Logging {
           assert(NativeHelper.isNative(left), "broadcast join build side is not native")
       }
 
-      // reuse NativeBroadcastJoin with empty equility keys
-      Shims.get.createNativeBroadcastJoinExec(
+      Shims.get.createNativeBroadcastNestedLoopJoinExec(
         addRenameColumnsExec(convertToNative(left)),
         addRenameColumnsExec(convertToNative(right)),
         exec.outputPartitioning,
-        Nil,
-        Nil,
         joinType,
         buildSide match {
           case BuildLeft => BroadcastLeft
           case BuildRight => BroadcastRight
-        })
+        },
+        condition)
 
     } catch {
       case e @ (_: NotImplementedError | _: Exception) =>
@@ -19,6 +19,10 @@ import java.io.ByteArrayInputStream
 import java.io.ByteArrayOutputStream
 import java.io.ObjectInputStream
 import java.io.ObjectOutputStream
+import java.time.ZoneId
+import java.util.Locale
+import java.util.regex.Pattern
+import java.util.regex.PatternSyntaxException
 
 import scala.collection.JavaConverters._
 import scala.collection.mutable
@@ -29,15 +33,35 @@ import com.google.protobuf.ByteString
 import org.apache.spark.SparkEnv
 import org.blaze.{protobuf => pb}
 import org.apache.spark.internal.Logging
-import org.apache.spark.sql.catalyst.expressions.{Abs, Acos, Add, Alias, And, Asin, Atan, AttributeReference, BitwiseAnd, BitwiseOr, BoundReference, CaseWhen, Cast, Ceil, CheckOverflow, Coalesce, Concat, ConcatWs, Contains, Cos, CreateArray, CreateNamedStruct, Divide, EndsWith, EqualTo, Exp, Expression, Floor, GetArrayItem, GetMapValue, GetStructField, GreaterThan, GreaterThanOrEqual, If, In, InSet, IsNotNull, IsNull, Length, LessThan, LessThanOrEqual, Like, Literal, Log, Log10, Log2, Lower, MakeDecimal, Md5, Multiply, Murmur3Hash, Not, NullIf, OctetLength, Or, Remainder, Sha2, ShiftLeft, ShiftRight, Signum, Sin, Sqrt, StartsWith, StringRepeat, StringSpace, StringTrim, StringTrimLeft, StringTrimRight, Substring, Subtract, Tan, TruncDate, Unevaluable, UnscaledValue, Upper}
+import org.apache.spark.sql.catalyst.expressions.{Abs, Acos, Add, AddMonths, Alias, And, ArrayAggregate, ArrayContains, ArrayDistinct, ArrayExists, ArrayFilter, ArrayForAll, ArrayJoin, ArrayPosition, ArrayTransform, ArraysOverlap, Asin, Atan, AttributeReference, Base64, BitwiseAnd, BitwiseOr, BoundReference, CaseWhen, Cast, Ceil, CheckOverflow, Coalesce, Concat, ConcatWs, Contains, Conv, Cos, Crc32, CreateArray, CreateNamedStruct, DateAdd, DateDiff, DateFormatClass, DateSub, DayOfWeek, Divide, ElementAt, EndsWith, EqualTo, Exp, Expression, Flatten, Floor, FormatNumber, FromUTCTimestamp, FromUnixTime, GetArrayItem, GetMapValue, GetStructField, GetTimestamp, GreaterThan, GreaterThanOrEqual, Hex, Hour, If, In, InSet, InitCap, IsNotNull, IsNull, JsonToStructs, LambdaFunction, LastDay, Length, LessThan, LessThanOrEqual, Levenshtein, Like, Literal, Log, Log10, Log2, Lower, MakeDecimal, MapConcat, MapEntries, MapFilter, MapFromArrays, MapFromEntries, MapKeys, MapValues, Md5, Minute, MonthsBetween, Multiply, Murmur3Hash, NamedLambdaVariable, NextDay, Not, NullIf, OctetLength, Or, Quarter, RLike, RegExpExtract, Remainder, Second, Sha1, Sha2, ShiftLeft, ShiftRight, Signum, Sin, Slice, SortArray, SoundEx, Sqrt, StartsWith, StringInstr, StringLPad, StringLocate, StringRPad, StringRepeat, StringSpace, StringToMap, StringTranslate, StringTrim, StringTrimLeft, StringTrimRight, StructsToJson, Substring, SubstringIndex, Subtract, Tan, ToUTCTimestamp, ToUnixTimestamp, TruncDate, TruncTimestamp, UnBase64, Unevaluable, Unhex, UnixTimestamp, UnscaledValue, Upper, WeekOfYear, ZipWith}
 import org.apache.spark.sql.catalyst.expressions.aggregate.AggregateExpression
+import org.apache.spark.sql.catalyst.expressions.aggregate.ApproximatePercentile
 import org.apache.spark.sql.catalyst.expressions.aggregate.Average
+import org.apache.spark.sql.catalyst.expressions.aggregate.BitAndAgg
+import org.apache.spark.sql.catalyst.expressions.aggregate.BitOrAgg
+import org.apache.spark.sql.catalyst.expressions.aggregate.BitXorAgg
+import org.apache.spark.sql.catalyst.expressions.aggregate.BoolAnd
+import org.apache.spark.sql.catalyst.expressions.aggregate.BoolOr
 import org.apache.spark.sql.catalyst.expressions.aggregate.CollectList
 import org.apache.spark.sql.catalyst.expressions.aggregate.CollectSet
+import org.apache.spark.sql.catalyst.expressions.aggregate.Corr
 import org.apache.spark.sql.catalyst.expressions.aggregate.Count
+import org.apache.spark.sql.catalyst.expressions.aggregate.CountIf
+import org.apache.spark.sql.catalyst.expressions.aggregate.CovPopulation
+import org.apache.spark.sql.catalyst.expressions.aggregate.CovSample
+import org.apache.spark.sql.catalyst.expressions.aggregate.HyperLogLogPlusPlus
+import org.apache.spark.sql.catalyst.expressions.aggregate.ImperativeAggregate
+import org.apache.spark.sql.catalyst.expressions.aggregate.Last
 import org.apache.spark.sql.catalyst.expressions.aggregate.Max
+import org.apache.spark.sql.catalyst.expressions.aggregate.MaxBy
 import org.apache.spark.sql.catalyst.expressions.aggregate.Min
+import org.apache.spark.sql.catalyst.expressions.aggregate.MinBy
+import org.apache.spark.sql.catalyst.expressions.aggregate.Percentile
+import org.apache.spark.sql.catalyst.expressions.aggregate.StddevPop
+import org.apache.spark.sql.catalyst.expressions.aggregate.StddevSamp
 import org.apache.spark.sql.catalyst.expressions.aggregate.Sum
+import org.apache.spark.sql.catalyst.expressions.aggregate.VariancePop
+import org.apache.spark.sql.catalyst.expressions.aggregate.VarianceSamp
 import org.apache.spark.sql.catalyst.expressions.Attribute
 import org.apache.spark.sql.catalyst.expressions.aggregate.First
 import org.apache.spark.sql.catalyst.expressions.codegen.CodegenContext
@@ -75,8 +99,10 @@ import org.apache.spark.sql.types.DecimalType
 import org.apache.spark.sql.types.DoubleType
 import org.apache.spark.sql.types.FloatType
 import org.apache.spark.sql.types.IntegerType
+import org.apache.spark.sql.types.IntegralType
 import org.apache.spark.sql.types.LongType
 import org.apache.spark.sql.types.MapType
+import org.apache.spark.sql.types.NumericType
 import org.apache.spark.sql.types.NullType
 import org.apache.spark.sql.types.ShortType
 import org.apache.spark.sql.types.StringType
@@ -343,6 +369,10 @@ object NativeConverters extends Logging {
       }
 
     } catch {
+      case e: NotImplementedError if hasUnboundLambdaVariables(sparkExpr) =>
+        // lambda variables are only assigned when evaluating the enclosing higher-order
+        // function, so the whole higher-order function should fall back instead
+        throw e
       case e: NotImplementedError =>
         logWarning(s"native expression fallbacks to spark: $e")
 
@@ -350,6 +380,8 @@ object NativeConverters extends Logging {
         val convertedChildren = mutable.LinkedHashMap[pb.PhysicalExprNode, BoundReference]()
         val bound = sparkExpr.mapChildren(_.transformDown {
           case p: Literal => p
+          // lambda variables are only resolvable within their lambda functions
+          case p if p.find(_.isInstanceOf[NamedLambdaVariable]).isDefined => p
           case p =>
             try {
               val convertedChild =
@@ -410,6 +442,8 @@ object NativeConverters extends Logging {
       fallback: Expression => pb.PhysicalExprNode): pb.PhysicalExprNode = {
 
     val buildBinaryExprNode = this.buildBinaryExprNode(_, _, _, isPruningExpr, fallback)
+    val buildArithmeticExprNode =
+      this.buildArithmeticExprNode(_, _, _, _, isPruningExpr, fallback)
     val buildScalarFunction = this.buildScalarFunctionNode(_, _, _, isPruningExpr, fallback)
     val buildExtScalarFunction = this.buildExtScalarFunctionNode(_, _, _, isPruningExpr, fallback)
 
@@ -482,9 +516,24 @@ object NativeConverters extends Logging {
               .setReturnNullable(subquery.nullable))
         }
 
+      // to_date(str, format) is replaced with cast(get_timestamp(str, format) as date)
+      case cast: Cast
+          if cast.dataType == DateType && (cast.child match {
+            case e: GetTimestamp => isSupportedGetTimestamp(e)
+            case _ => false
+          }) =>
+        val e = cast.child.asInstanceOf[GetTimestamp]
+        buildExtScalarFunction(
+          "ParseToDate",
+          e.left :: dateTimeFormatArgs(e.right, e.zoneId),
+          DateType)
+
       // cast
       // not performing native cast for timestamp/dates (will use UDFWrapper instead)
-      case cast: Cast if !Seq(cast.dataType, cast.child.dataType).contains(TimestampType) =>
+      // ansi cast raising errors on invalid inputs is only supported for atomic types
+      case cast: Cast
+          if !Seq(cast.dataType, cast.child.dataType).contains(TimestampType)
+            && evalModeOf(cast) != pb.EvalMode.ANSI =>
         buildExprNode {
           _.setTryCast(
             pb.PhysicalTryCastNode
@@ -493,6 +542,24 @@ object NativeConverters extends Logging {
               .setArrowType(convertDataType(cast.dataType))
               .build())
         }
+      // strings are only parsed natively into integrals/decimals in ansi mode, other parsers
+      // are not spark compatible and cannot tell invalid inputs
+      case cast: Cast
+          if !Seq(cast.dataType, cast.child.dataType).contains(TimestampType)
+            && Seq(cast.dataType, cast.child.dataType).forall(_.isInstanceOf[AtomicType])
+            && (cast.child.dataType != StringType || (cast.dataType match {
+              case _: IntegralType | _: DecimalType | StringType => true
+              case _ => false
+            })) =>
+        buildExprNode {
+          _.setCast(
+            pb.PhysicalCastNode
+              .newBuilder()
+              .setExpr(convertExprWithFallback(cast.child, isPruningExpr, fallback))
+              .setArrowType(convertDataType(cast.dataType))
+              .setEvalMode(pb.EvalMode.ANSI)
+              .build())
+        }
 
       // in
       case In(value, list) if list.forall(_.isInstanceOf[Literal]) =>
@@ -590,10 +657,11 @@ object NativeConverters extends Logging {
                     .setL(convertExprWithFallback(Cast(lhs, resultType), isPruningExpr, fallback))
                     .setR(convertExprWithFallback(rhs, isPruningExpr, fallback))
                     .setOp("Plus"))
-              }))
+              })
+              .setEvalMode(decimalOverflowEvalMode(e)))
           }
         } else {
-          buildBinaryExprNode(lhs, rhs, "Plus")
+          buildArithmeticExprNode(lhs, rhs, "Plus", evalModeOf(e))
         }
 
       case e: Subtract =>
@@ -631,10 +699,11 @@ object NativeConverters extends Logging {
                     .setL(convertExprWithFallback(Cast(lhs, resultType), isPruningExpr, fallback))
                     .setR(convertExprWithFallback(rhs, isPruningExpr, fallback))
                     .setOp("Minus"))
-              }))
+              })
+              .setEvalMode(decimalOverflowEvalMode(e)))
           }
         } else {
-          buildBinaryExprNode(lhs, rhs, "Minus")
+          buildArithmeticExprNode(lhs, rhs, "Minus", evalModeOf(e))
         }
 
       case e: Multiply =>
@@ -671,10 +740,11 @@ object NativeConverters extends Logging {
                     .setL(convertExprWithFallback(Cast(lhs, resultType), isPruningExpr, fallback))
                     .setR(convertExprWithFallback(rhs, isPruningExpr, fallback))
                     .setOp("Multiply"))
-              }))
+              })
+              .setEvalMode(decimalOverflowEvalMode(e)))
           }
         } else {
-          buildBinaryExprNode(lhs, rhs, "Multiply")
+          buildArithmeticExprNode(lhs, rhs, "Multiply", evalModeOf(e))
         }
 
       case e: Divide =>
@@ -708,30 +778,50 @@ object NativeConverters extends Logging {
                 rhsType.scale)
           }
 
-          buildExprNode {
-            _.setCast(pb.PhysicalCastNode
-              .newBuilder()
-              .setArrowType(convertDataType(resultType))
-              .setExpr(buildExprNode {
-                _.setBinaryExpr(
-                  pb.PhysicalBinaryExprNode
-                    .newBuilder()
-                    .setL(convertExprWithFallback(Cast(lhs, resultType), isPruningExpr, fallback))
-                    .setR(buildExtScalarFunction("NullIfZero", rhs :: Nil, rhs.dataType))
-                    .setOp("Divide"))
-              }))
+          if (evalModeOf(e) == pb.EvalMode.ANSI) {
+            // divisors are checked natively to raise DIVIDE_BY_ZERO
+            buildExprNode {
+              _.setCast(pb.PhysicalCastNode
+                .newBuilder()
+                .setArrowType(convertDataType(resultType))
+                .setExpr(buildArithmeticExprNode(
+                  Cast(lhs, resultType),
+                  rhs,
+                  "Divide",
+                  pb.EvalMode.ANSI))
+                .setEvalMode(pb.EvalMode.ANSI))
+            }
+          } else {
+            buildExprNode {
+              _.setCast(pb.PhysicalCastNode
+                .newBuilder()
+                .setArrowType(convertDataType(resultType))
+                .setExpr(buildExprNode {
+                  _.setBinaryExpr(
+                    pb.PhysicalBinaryExprNode
+                      .newBuilder()
+                      .setL(
+                        convertExprWithFallback(Cast(lhs, resultType), isPruningExpr, fallback))
+                      .setR(buildExtScalarFunction("NullIfZero", rhs :: Nil, rhs.dataType))
+                      .setOp("Divide"))
+                }))
+            }
           }
         } else {
           val resultType = e.dataType
           val lhsCasted = castIfNecessary(lhs, resultType)
           val rhsCasted = castIfNecessary(rhs, resultType)
-          buildExprNode {
-            _.setBinaryExpr(
-              pb.PhysicalBinaryExprNode
-                .newBuilder()
-                .setL(convertExprWithFallback(lhsCasted, isPruningExpr, fallback))
-                .setR(buildExtScalarFunction("NullIfZero", rhsCasted :: Nil, rhs.dataType))
-                .setOp("Divide"))
+          if (evalModeOf(e) == pb.EvalMode.ANSI) {
+            buildArithmeticExprNode(lhsCasted, rhsCasted, "Divide", pb.EvalMode.ANSI)
+          } else {
+            buildExprNode {
+              _.setBinaryExpr(
+                pb.PhysicalBinaryExprNode
+                  .newBuilder()
+                  .setL(convertExprWithFallback(lhsCasted, isPruningExpr, fallback))
+                  .setR(buildExtScalarFunction("NullIfZero", rhsCasted :: Nil, rhs.dataType))
+                  .setOp("Divide"))
+            }
           }
         }
 
@@ -740,6 +830,10 @@ object NativeConverters extends Logging {
         val rhs = e.right
         val resultType = e.dataType
         rhs match {
+          case _ if evalModeOf(e) == pb.EvalMode.ANSI =>
+            val lhsCasted = castIfNecessary(lhs, resultType)
+            val rhsCasted = castIfNecessary(rhs, resultType)
+            buildArithmeticExprNode(lhsCasted, rhsCasted, "Modulo", pb.EvalMode.ANSI)
           case rhs: Literal if rhs == Literal.default(rhs.dataType) =>
             buildExprNode(_.setLiteral(convertValue(null, e.dataType)))
           case rhs: Literal if rhs != Literal.default(rhs.dataType) =>
@@ -854,29 +948,114 @@ object NativeConverters extends Logging {
         buildScalarFunction(pb.ScalarFunction.Rtrim, e.srcStr +: e.trimStr.toSeq, e.dataType)
       case e @ NullIf(left, right, _) =>
         buildExtScalarFunction("NullIf", left :: right :: Nil, e.dataType)
-      case e: TruncDate =>
-        buildScalarFunction(pb.ScalarFunction.DateTrunc, e.children, e.dataType)
-      case Md5(_1) =>
-        buildScalarFunction(pb.ScalarFunction.MD5, Seq(unpackBinaryTypeCast(_1)), StringType)
-      case Sha2(_1, Literal(224, _)) =>
-        buildScalarFunction(pb.ScalarFunction.SHA224, Seq(unpackBinaryTypeCast(_1)), StringType)
-      case Sha2(_1, Literal(0, _)) =>
-        buildScalarFunction(pb.ScalarFunction.SHA256, Seq(unpackBinaryTypeCast(_1)), StringType)
-      case Sha2(_1, Literal(256, _)) =>
-        buildScalarFunction(pb.ScalarFunction.SHA256, Seq(unpackBinaryTypeCast(_1)), StringType)
-      case Sha2(_1, Literal(384, _)) =>
-        buildScalarFunction(pb.ScalarFunction.SHA384, Seq(unpackBinaryTypeCast(_1)), StringType)
-      case Sha2(_1, Literal(512, _)) =>
-        buildScalarFunction(pb.ScalarFunction.SHA512, Seq(unpackBinaryTypeCast(_1)), StringType)
+      case e: Md5 if isBinaryInput(e.child) =>
+        buildExtScalarFunction("Md5", unpackBinaryTypeCast(e.child) :: Nil, StringType)
+      case e: Sha1 if isBinaryInput(e.child) =>
+        buildExtScalarFunction("Sha1", unpackBinaryTypeCast(e.child) :: Nil, StringType)
+      case e: Sha2 if isBinaryInput(e.left) =>
+        buildExtScalarFunction("Sha2", unpackBinaryTypeCast(e.left) :: e.right :: Nil, StringType)
+      case e: Crc32 if isBinaryInput(e.child) =>
+        buildExtScalarFunction("Crc32", unpackBinaryTypeCast(e.child) :: Nil, LongType)
       case Murmur3Hash(children, 42) =>
         buildExtScalarFunction("Murmur3Hash", children, IntegerType)
       case XxHash64(children, 42L) =>
         buildExtScalarFunction("XxHash64", children, LongType)
 
+      case e: Base64 if isBinaryInput(e.child) =>
+        buildExtScalarFunction(
+          "Base64",
+          unpackBinaryTypeCast(e.child) :: Literal(Shims.get.isBase64Chunked(e)) :: Nil,
+          StringType)
+      // failOnError/ansiEnabled flags of newer versions are not supported
+      case e: UnBase64 if !Shims.get.getStringConversionFailOnError(e) =>
+        buildExtScalarFunction("UnBase64", e.child :: Nil, BinaryType)
+      case e: Hex => buildExtScalarFunction("Hex", e.child :: Nil, StringType)
+      case e: Unhex if !Shims.get.getStringConversionFailOnError(e) =>
+        buildExtScalarFunction("Unhex", e.child :: Nil, BinaryType)
+      case e: Conv if !Shims.get.getStringConversionFailOnError(e) =>
+        val args = e.numExpr :: e.fromBaseExpr :: e.toBaseExpr :: Nil
+        buildExtScalarFunction("Conv", args, StringType)
+
       case Year(child) => buildExtScalarFunction("Year", child :: Nil, DateType)
       case Month(child) => buildExtScalarFunction("Month", child :: Nil, DateType)
       case Days(child) => buildExtScalarFunction("Day", child :: Nil, DateType)
 
+      // date/time functions, timezone-aware functions take session time zone as the last arg
+      case e: DateAdd =>
+        val days = castIfNecessary(e.days, IntegerType)
+        buildExtScalarFunction("DateAdd", e.startDate :: days :: Nil, DateType)
+      case e: DateSub =>
+        val days = castIfNecessary(e.days, IntegerType)
+        buildExtScalarFunction("DateSub", e.startDate :: days :: Nil, DateType)
+      case e: DateDiff =>
+        buildExtScalarFunction("DateDiff", e.endDate :: e.startDate :: Nil, IntegerType)
+      case e: AddMonths =>
+        buildExtScalarFunction("AddMonths", e.startDate :: e.numMonths :: Nil, DateType)
+      case e: MonthsBetween =>
+        buildExtScalarFunction(
+          "MonthsBetween",
+          e.date1 :: e.date2 :: e.roundOff :: Literal(e.zoneId.getId) :: Nil,
+          DoubleType)
+      case e: LastDay =>
+        buildExtScalarFunction("LastDay", e.startDate :: Nil, DateType)
+      case e: NextDay if !SQLConf.get.ansiEnabled =>
+        buildExtScalarFunction("NextDay", e.startDate :: e.dayOfWeek :: Nil, DateType)
+      case e: TruncDate =>
+        buildExtScalarFunction("TruncDate", e.date :: e.format :: Nil, DateType)
+      case e: TruncTimestamp =>
+        buildExtScalarFunction(
+          "TruncTimestamp",
+          e.format :: e.timestamp :: Literal(e.zoneId.getId) :: Nil,
+          TimestampType)
+      case e: Hour if e.child.dataType == TimestampType =>
+        buildExtScalarFunction("Hour", e.child :: Literal(e.zoneId.getId) :: Nil, IntegerType)
+      case e: Minute if e.child.dataType == TimestampType =>
+        buildExtScalarFunction("Minute", e.child :: Literal(e.zoneId.getId) :: Nil, IntegerType)
+      case e: Second if e.child.dataType == TimestampType =>
+        buildExtScalarFunction("Second", e.child :: Literal(e.zoneId.getId) :: Nil, IntegerType)
+      case e: DayOfWeek =>
+        buildExtScalarFunction("DayOfWeek", e.child :: Nil, IntegerType)
+      case e: WeekOfYear =>
+        buildExtScalarFunction("WeekOfYear", e.child :: Nil, IntegerType)
+      case e: Quarter =>
+        buildExtScalarFunction("Quarter", e.child :: Nil, IntegerType)
+      case e: FromUTCTimestamp if e.left.dataType == TimestampType =>
+        buildExtScalarFunction("FromUTCTimestamp", e.left :: e.right :: Nil, TimestampType)
+      case e: ToUTCTimestamp if e.left.dataType == TimestampType =>
+        buildExtScalarFunction("ToUTCTimestamp", e.left :: e.right :: Nil, TimestampType)
+
+      // datetime formatting/parsing functions, take format, session time zone and time
+      // parser policy as the last args. parsing functions fail on invalid strings in ansi
+      // mode, which is not supported natively
+      case e: DateFormatClass
+          if e.left.dataType == TimestampType && isSupportedDateTimeFormat(e.right) =>
+        buildExtScalarFunction(
+          "DateFormat",
+          e.left :: dateTimeFormatArgs(e.right, e.zoneId),
+          StringType)
+      case e: FromUnixTime if e.sec.dataType == LongType && isSupportedDateTimeFormat(e.format) =>
+        buildExtScalarFunction(
+          "FromUnixTime",
+          e.sec :: dateTimeFormatArgs(e.format, e.zoneId),
+          StringType)
+      case e: UnixTimestamp
+          if !SQLConf.get.ansiEnabled && isSupportedUnixTimestampInput(e.timeExp, e.format) =>
+        buildExtScalarFunction(
+          "UnixTimestamp",
+          e.timeExp :: dateTimeFormatArgs(e.format, e.zoneId),
+          LongType)
+      case e: ToUnixTimestamp
+          if !SQLConf.get.ansiEnabled && isSupportedUnixTimestampInput(e.timeExp, e.format) =>
+        buildExtScalarFunction(
+          "UnixTimestamp",
+          e.timeExp :: dateTimeFormatArgs(e.format, e.zoneId),
+          LongType)
+      case e: GetTimestamp if isSupportedGetTimestamp(e) =>
+        buildExtScalarFunction(
+          "GetTimestamp",
+          e.left :: dateTimeFormatArgs(e.right, e.zoneId),
+          TimestampType)
+
       // startswith is converted to scalar function in pruning-expr mode
       case StartsWith(expr, Literal(prefix, StringType)) if isPruningExpr =>
         buildExprNode(
@@ -928,6 +1107,43 @@ object NativeConverters extends Logging {
       case StringRepeat(str, n @ Literal(_, IntegerType)) =>
         buildExtScalarFunction("StringRepeat", str :: n :: Nil, StringType)
 
+      case e: SubstringIndex =>
+        buildExtScalarFunction(
+          "SubstringIndex",
+          e.strExpr :: e.delimExpr :: e.countExpr :: Nil,
+          StringType)
+      case e: StringInstr =>
+        buildExtScalarFunction("StringInstr", e.str :: e.substr :: Nil, IntegerType)
+      case e: StringLocate =>
+        buildExtScalarFunction("StringLocate", e.substr :: e.str :: e.start :: Nil, IntegerType)
+      case e: StringLPad if e.str.dataType == StringType =>
+        buildExtScalarFunction("StringLPad", e.str :: e.len :: e.pad :: Nil, StringType)
+      case e: StringRPad if e.str.dataType == StringType =>
+        buildExtScalarFunction("StringRPad", e.str :: e.len :: e.pad :: Nil, StringType)
+      case e: StringTranslate =>
+        buildExtScalarFunction(
+          "StringTranslate",
+          e.srcExpr :: e.matchingExpr :: e.replaceExpr :: Nil,
+          StringType)
+      case e: Levenshtein =>
+        buildExtScalarFunction("Levenshtein", e.children, IntegerType)
+      case e: SoundEx =>
+        buildExtScalarFunction("SoundEx", e.child :: Nil, StringType)
+      case e: FormatNumber
+          if e.d.dataType == IntegerType && (e.x.dataType match {
+            case ByteType | ShortType | IntegerType | LongType | FloatType | DoubleType => true
+            case _: DecimalType => true
+            case _ => false
+          }) =>
+        buildExtScalarFunction("FormatNumber", e.x :: e.d :: Nil, StringType)
+      case e: InitCap =>
+        buildExtScalarFunction("InitCap", e.child :: Nil, StringType)
+
+      case e: RLike if isNativeRegex(e.right) =>
+        buildExtScalarFunction("RLike", e.left :: e.right :: Nil, BooleanType)
+      case e: RegExpExtract if isNativeRegex(e.regexp) =>
+        buildExtScalarFunction("RegExpExtract", e.children, StringType)
+
       case e: Concat if e.children.forall(_.dataType == StringType) =>
         buildExtScalarFunction("StringConcat", e.children, e.dataType)
 
@@ -983,6 +1199,105 @@ object NativeConverters extends Logging {
 
       case e: CreateArray => buildExtScalarFunction("MakeArray", e.children, e.dataType)
 
+      case e: ArrayContains =>
+        buildExtScalarFunction("ArrayContains", e.left :: e.right :: Nil, BooleanType)
+      case e: ArrayDistinct =>
+        buildExtScalarFunction("ArrayDistinct", e.child :: Nil, e.dataType)
+      case e @ SortArray(base, ascendingOrder @ Literal(_: Boolean, BooleanType)) =>
+        buildExtScalarFunction("SortArray", base :: ascendingOrder :: Nil, e.dataType)
+      case e: ArrayPosition =>
+        buildExtScalarFunction("ArrayPosition", e.left :: e.right :: Nil, LongType)
+      case e: ElementAt if e.left.dataType.isInstanceOf[ArrayType] && !SQLConf.get.ansiEnabled =>
+        buildExtScalarFunction("ElementAt", e.left :: e.right :: Nil, e.dataType)
+      case e: Slice =>
+        buildExtScalarFunction("Slice", e.x :: e.start :: e.length :: Nil, e.dataType)
+      case e: Flatten =>
+        buildExtScalarFunction("Flatten", e.child :: Nil, e.dataType)
+      case e: ArraysOverlap =>
+        buildExtScalarFunction("ArraysOverlap", e.left :: e.right :: Nil, BooleanType)
+      case e: ArrayJoin if e.array.dataType == ArrayType(StringType) =>
+        val args = e.array :: e.delimiter :: e.nullReplacement.toList
+        buildExtScalarFunction("ArrayJoin", args, StringType)
+
+      case e: MapKeys => buildExtScalarFunction("MapKeys", e.child :: Nil, e.dataType)
+      case e: MapValues => buildExtScalarFunction("MapValues", e.child :: Nil, e.dataType)
+      case e: MapEntries => buildExtScalarFunction("MapEntries", e.child :: Nil, e.dataType)
+      case e: MapFromArrays =>
+        val args = e.left :: e.right :: mapKeyDedupPolicy :: Nil
+        buildExtScalarFunction("MapFromArrays", args, e.dataType)
+      case e: MapFromEntries =>
+        val args = e.child :: mapKeyDedupPolicy :: Nil
+        buildExtScalarFunction("MapFromEntries", args, e.dataType)
+      case e: MapConcat if e.children.nonEmpty =>
+        buildExtScalarFunction("MapConcat", e.children :+ mapKeyDedupPolicy, e.dataType)
+      case e: StringToMap if isNativeRegex(e.pairDelim) && isNativeRegex(e.keyValueDelim) =>
+        val args = e.text :: e.pairDelim :: e.keyValueDelim :: mapKeyDedupPolicy :: Nil
+        buildExtScalarFunction("StringToMap", args, e.dataType)
+
+      // higher-order functions
+      case e: ArrayTransform if isLambdaFunction(e.function) =>
+        buildHigherOrderFunction(
+          pb.HigherOrderFunction.TRANSFORM,
+          e.argument :: Nil,
+          e.function :: Nil,
+          e.dataType,
+          isPruningExpr,
+          fallback)
+      case e: ArrayFilter if isLambdaFunction(e.function) =>
+        buildHigherOrderFunction(
+          pb.HigherOrderFunction.FILTER,
+          e.argument :: Nil,
+          e.function :: Nil,
+          e.dataType,
+          isPruningExpr,
+          fallback)
+      case e: ArrayExists if e.followThreeValuedLogic && isLambdaFunction(e.function) =>
+        buildHigherOrderFunction(
+          pb.HigherOrderFunction.EXISTS,
+          e.argument :: Nil,
+          e.function :: Nil,
+          e.dataType,
+          isPruningExpr,
+          fallback)
+      case e: ArrayForAll if isLambdaFunction(e.function) =>
+        buildHigherOrderFunction(
+          pb.HigherOrderFunction.FORALL,
+          e.argument :: Nil,
+          e.function :: Nil,
+          e.dataType,
+          isPruningExpr,
+          fallback)
+      case e: ArrayAggregate if isLambdaFunction(e.merge) && isLambdaFunction(e.finish) =>
+        buildHigherOrderFunction(
+          pb.HigherOrderFunction.AGGREGATE,
+          e.argument :: e.zero :: Nil,
+          e.merge :: e.finish :: Nil,
+          e.dataType,
+          isPruningExpr,
+          fallback)
+      case e: ZipWith if isLambdaFunction(e.function) =>
+        buildHigherOrderFunction(
+          pb.HigherOrderFunction.ZIP_WITH,
+          e.left :: e.right :: Nil,
+          e.function :: Nil,
+          e.dataType,
+          isPruningExpr,
+          fallback)
+      case e: MapFilter if isLambdaFunction(e.function) =>
+        buildHigherOrderFunction(
+          pb.HigherOrderFunction.MAP_FILTER,
+          e.argument :: Nil,
+          e.function :: Nil,
+          e.dataType,
+          isPruningExpr,
+          fallback)
+
+      // lambda functions are converted within their higher-order functions, converting a
+      // standalone lambda function only checks whether its body is convertible
+      case e: LambdaFunction => convertExprWithFallback(e.function, isPruningExpr, fallback)
+      case e: NamedLambdaVariable =>
+        buildExprNode(_.setNamedLambdaVariableExpr(convertNamedLambdaVariable(e)))
+
       case e: CreateNamedStruct =>
         buildExprNode {
           _.setNamedStruct(
@@ -1051,6 +1366,18 @@ object NativeConverters extends Logging {
           nullable = false)
         buildExtScalarFunction("GetParsedJsonObject", parsed :: e.children(1) :: Nil, StringType)
 
+      // json functions, only default options are supported except the parse mode of from_json
+      case e: JsonToStructs if isSupportedFromJson(e) =>
+        val mode = jsonParseMode(e).getOrElse("PERMISSIVE")
+        buildExtScalarFunction("FromJson", e.child :: Literal(mode) :: Nil, e.dataType)
+      case e: StructsToJson
+          if e.options.isEmpty && isSupportedToJsonType(e.child.dataType)
+            && SQLConf.get.getConf(SQLConf.JSON_GENERATOR_IGNORE_NULL_FIELDS) =>
+        buildExtScalarFunction("ToJson", e.child :: Literal(e.zoneId.getId) :: Nil, StringType)
+      // schema_of_json is not converted: it only accepts foldable input and is constant-folded
+      // by the optimizer, and its output format differs between spark versions. any instance
+      // reaching here falls back to the jvm implementation
+
       // hive UDF brickhouse.array_union
       case e
           if getFunctionClassName(e).contains("brickhouse.udf.collect.ArrayUnionUDF")
@@ -1079,11 +1406,15 @@ object NativeConverters extends Logging {
       case e: Min =>
         aggBuilder.setAggFunction(pb.AggFunction.MIN)
         aggBuilder.addChildren(convertExpr(e.child))
-      case e: Sum if e.dataType.isInstanceOf[AtomicType] =>
+      // try_sum/try_avg returning null on overflows are not supported natively
+      case e: Sum if e.dataType.isInstanceOf[AtomicType] && evalModeOf(e) != pb.EvalMode.TRY =>
         aggBuilder.setAggFunction(pb.AggFunction.SUM)
+        aggBuilder.setEvalMode(evalModeOf(e))
         aggBuilder.addChildren(convertExpr(e.child))
-      case e: Average if e.dataType.isInstanceOf[AtomicType] =>
+      case e: Average
+          if e.dataType.isInstanceOf[AtomicType] && evalModeOf(e) != pb.EvalMode.TRY =>
         aggBuilder.setAggFunction(pb.AggFunction.AVG)
+        aggBuilder.setEvalMode(evalModeOf(e))
         aggBuilder.addChildren(convertExpr(e.child))
       case Count(children) if !children.exists(_.nullable) =>
         aggBuilder.setAggFunction(pb.AggFunction.COUNT)
@@ -1113,12 +1444,107 @@ object NativeConverters extends Logging {
         })
         aggBuilder.addChildren(convertExpr(child))
 
+      case Last(child, ignoresNullExpr) =>
+        val ignoresNull = ignoresNullExpr.asInstanceOf[Any] match {
+          case Literal(v: Boolean, BooleanType) => v
+          case v: Boolean => v
+        }
+        aggBuilder.setAggFunction(if (ignoresNull) {
+          pb.AggFunction.LAST_IGNORES_NULL
+        } else {
+          pb.AggFunction.LAST
+        })
+        aggBuilder.addChildren(convertExpr(child))
+
+      case MaxBy(valueExpr, orderingExpr) =>
+        aggBuilder.setAggFunction(pb.AggFunction.MAX_BY)
+        aggBuilder.addChildren(convertExpr(valueExpr))
+        aggBuilder.addChildren(convertExpr(orderingExpr))
+      case MinBy(valueExpr, orderingExpr) =>
+        aggBuilder.setAggFunction(pb.AggFunction.MIN_BY)
+        aggBuilder.addChildren(convertExpr(valueExpr))
+        aggBuilder.addChildren(convertExpr(orderingExpr))
+
       case CollectList(child, _, _) if child.dataType.isInstanceOf[AtomicType] =>
         aggBuilder.setAggFunction(pb.AggFunction.COLLECT_LIST)
         aggBuilder.addChildren(convertExpr(child))
       case CollectSet(child, _, _) if child.dataType.isInstanceOf[AtomicType] =>
         aggBuilder.setAggFunction(pb.AggFunction.COLLECT_SET)
         aggBuilder.addChildren(convertExpr(child))
+      case e: HyperLogLogPlusPlus if e.child.dataType.isInstanceOf[AtomicType] =>
+        aggBuilder.setAggFunction(pb.AggFunction.APPROX_COUNT_DISTINCT)
+        aggBuilder.addChildren(convertExpr(e.child))
+        aggBuilder.addChildren(convertExpr(Literal(e.relativeSD)))
+      case e: Percentile
+          if e.child.dataType.isInstanceOf[NumericType]
+            && e.frequencyExpression == Literal(1L)
+            && !Shims.get.isPercentileReversed(e) => // reversed percentile is not supported
+        aggBuilder.setAggFunction(pb.AggFunction.PERCENTILE)
+        aggBuilder.addChildren(convertExpr(e.child))
+        aggBuilder.addChildren(convertExpr(e.percentageExpression))
+      case e: ApproximatePercentile
+          if e.child.dataType.isInstanceOf[NumericType]
+            || e.child.dataType == DateType
+            || e.child.dataType == TimestampType =>
+        aggBuilder.setAggFunction(pb.AggFunction.APPROX_PERCENTILE)
+        aggBuilder.addChildren(convertExpr(e.child))
+        aggBuilder.addChildren(convertExpr(e.percentageExpression))
+        aggBuilder.addChildren(convertExpr(e.accuracyExpression))
+
+      // statistical aggregates, nullOnDivideByZero is missing before spark 3.1,
+      // which always behaves like spark.sql.legacy.statisticalAggregate=true
+      case e: StddevSamp =>
+        aggBuilder.setAggFunction(pb.AggFunction.STDDEV_SAMP)
+        aggBuilder.addChildren(convertExpr(e.child))
+        aggBuilder.addChildren(convertExpr(Literal(Shims.get.getNullOnDivideByZero(e))))
+      case e: StddevPop =>
+        aggBuilder.setAggFunction(pb.AggFunction.STDDEV_POP)
+        aggBuilder.addChildren(convertExpr(e.child))
+        aggBuilder.addChildren(convertExpr(Literal(Shims.get.getNullOnDivideByZero(e))))
+      case e: VarianceSamp =>
+        aggBuilder.setAggFunction(pb.AggFunction.VAR_SAMP)
+        aggBuilder.addChildren(convertExpr(e.child))
+        aggBuilder.addChildren(convertExpr(Literal(Shims.get.getNullOnDivideByZero(e))))
+      case e: VariancePop =>
+        aggBuilder.setAggFunction(pb.AggFunction.VAR_POP)
+        aggBuilder.addChildren(convertExpr(e.child))
+        aggBuilder.addChildren(convertExpr(Literal(Shims.get.getNullOnDivideByZero(e))))
+      case e: CovSample =>
+        aggBuilder.setAggFunction(pb.AggFunction.COVAR_SAMP)
+        aggBuilder.addChildren(convertExpr(e.left))
+        aggBuilder.addChildren(convertExpr(e.right))
+        aggBuilder.addChildren(convertExpr(Literal(Shims.get.getNullOnDivideByZero(e))))
+      case e: CovPopulation =>
+        aggBuilder.setAggFunction(pb.AggFunction.COVAR_POP)
+        aggBuilder.addChildren(convertExpr(e.left))
+        aggBuilder.addChildren(convertExpr(e.right))
+        aggBuilder.addChildren(convertExpr(Literal(Shims.get.getNullOnDivideByZero(e))))
+      case e: Corr =>
+        aggBuilder.setAggFunction(pb.AggFunction.CORR)
+        aggBuilder.addChildren(convertExpr(e.x))
+        aggBuilder.addChildren(convertExpr(e.y))
+        aggBuilder.addChildren(convertExpr(Literal(Shims.get.getNullOnDivideByZero(e))))
+
+      // boolean and bitwise aggregates, bool_and/bool_or/count_if are usually
+      // rewritten into min/max/count by the optimizer before reaching here
+      case e: BoolAnd =>
+        aggBuilder.setAggFunction(pb.AggFunction.BOOL_AND)
+        aggBuilder.addChildren(convertExpr(e.children.head))
+      case e: BoolOr =>
+        aggBuilder.setAggFunction(pb.AggFunction.BOOL_OR)
+        aggBuilder.addChildren(convertExpr(e.children.head))
+      case e: CountIf =>
+        aggBuilder.setAggFunction(pb.AggFunction.COUNT_IF)
+        aggBuilder.addChildren(convertExpr(e.children.head))
+      case e: BitAndAgg if e.child.dataType.isInstanceOf[IntegralType] =>
+        aggBuilder.setAggFunction(pb.AggFunction.BIT_AND)
+        aggBuilder.addChildren(convertExpr(e.child))
+      case e: BitOrAgg if e.child.dataType.isInstanceOf[IntegralType] =>
+        aggBuilder.setAggFunction(pb.AggFunction.BIT_OR)
+        aggBuilder.addChildren(convertExpr(e.child))
+      case e: BitXorAgg if e.child.dataType.isInstanceOf[IntegralType] =>
+        aggBuilder.setAggFunction(pb.AggFunction.BIT_XOR)
+        aggBuilder.addChildren(convertExpr(e.child))
 
       // brickhouse UDAFs
       case udaf
@@ -1147,7 +1573,12 @@ object NativeConverters extends Logging {
           case Some(converted) => return converted
           case _ =>
         }
-        throw new NotImplementedError(s"unsupported aggregate expression: (${e.getClass}) $e")
+        e.aggregateFunction match {
+          case udaf: ImperativeAggregate if BlazeConf.UDAF_FALLBACK_ENABLE.booleanConf() =>
+            convertUDAFWrapper(aggBuilder, udaf)
+          case _ =>
+            throw new NotImplementedError(s"unsupported aggregate expression: (${e.getClass}) $e")
+        }
     }
     pb.PhysicalExprNode
       .newBuilder()
@@ -1155,6 +1586,39 @@ object NativeConverters extends Logging {
       .build()
   }
 
+  private def convertUDAFWrapper(
+      aggBuilder: pb.PhysicalAggExprNode.Builder,
+      udaf: ImperativeAggregate): Unit = {
+
+    // bind all children, literals are kept since some functions require foldable children
+    val convertedChildren = mutable.LinkedHashMap[pb.PhysicalExprNode, BoundReference]()
+    val bound = udaf.mapChildren {
+      case p: Literal => p
+      case p =>
+        val convertedChild = convertExpr(p)
+        val nextBindIndex = convertedChildren.size
+        convertedChildren.getOrElseUpdate(
+          convertedChild,
+          BoundReference(nextBindIndex, p.dataType, p.nullable))
+    }
+
+    val paramsSchema = StructType(
+      convertedChildren.values
+        .map(ref => StructField("", ref.dataType, ref.nullable))
+        .toSeq)
+
+    val serialized =
+      serializeExpression(bound.asInstanceOf[Expression with Serializable], paramsSchema)
+
+    aggBuilder.setAggFunction(pb.AggFunction.UDAF)
+    aggBuilder.setUdaf(
+      pb.AggUdaf
+        .newBuilder()
+        .setSerialized(ByteString.copyFrom(serialized))
+        .setReturnType(convertDataType(bound.dataType)))
+    aggBuilder.addAllChildren(convertedChildren.keys.asJava)
+  }
+
   def convertJoinType(joinType: JoinType): pb.JoinType = {
     joinType match {
       case Inner => pb.JoinType.INNER
@@ -1183,6 +1647,35 @@ object NativeConverters extends Logging {
           .setOp(op))
     }
 
+  // arithmetic in non-legacy eval modes, raising errors (ANSI) or returning nulls (TRY)
+  // on overflows and zero divisors
+  def buildArithmeticExprNode(
+      left: Expression,
+      right: Expression,
+      op: String,
+      evalMode: pb.EvalMode,
+      isPruningExpr: Boolean,
+      fallback: Expression => pb.PhysicalExprNode): pb.PhysicalExprNode =
+    buildExprNode {
+      _.setBinaryExpr(
+        pb.PhysicalBinaryExprNode
+          .newBuilder()
+          .setL(convertExprWithFallback(left, isPruningExpr, fallback))
+          .setR(convertExprWithFallback(right, isPruningExpr, fallback))
+          .setOp(op)
+          .setEvalMode(evalMode))
+    }
+
+  // eval mode of cast/arithmetic/aggregate expressions, which is a boolean field
+  // (ansiEnabled/failOnError/useAnsiAdd) in spark3.2/3.3, an EvalMode field since spark3.4,
+  // or taken from the conf in spark3.0/3.1
+  def evalModeOf(e: Expression): pb.EvalMode = Shims.get.getEvalMode(e)
+
+  // decimal arithmetic overflows are detected by casting to the result precision, which
+  // raises NUMERIC_VALUE_OUT_OF_RANGE in ANSI mode and returns null otherwise
+  private def decimalOverflowEvalMode(e: Expression): pb.EvalMode =
+    if (evalModeOf(e) == pb.EvalMode.ANSI) pb.EvalMode.ANSI else pb.EvalMode.LEGACY
+
   def buildScalarFunctionNode(
       fn: pb.ScalarFunction,
       args: Seq[Expression],
@@ -1230,6 +1723,238 @@ object NativeConverters extends Logging {
       case expr => expr
     }
 
+  // strings are implicitly cast to binary for binary functions, which is unnecessary natively
+  private def isBinaryInput(e: Expression): Boolean =
+    Seq(StringType, BinaryType).contains(unpackBinaryTypeCast(e).dataType)
+
+  // full zone names (zzzz) are not supported in native datetime formatter
+  private def isSupportedDateTimeFormat(format: Expression): Boolean =
+    format match {
+      case Literal(null, StringType) => true
+      case Literal(format, StringType) => !format.toString.contains("zzzz")
+      case _ => false
+    }
+
+  private def isSupportedUnixTimestampInput(timeExp: Expression, format: Expression): Boolean =
+    Seq(StringType, DateType, TimestampType).contains(timeExp.dataType) &&
+      isSupportedDateTimeFormat(format)
+
+  private def isSupportedGetTimestamp(e: GetTimestamp): Boolean =
+    !SQLConf.get.ansiEnabled &&
+      e.left.dataType == StringType &&
+      e.dataType == TimestampType &&
+      isSupportedDateTimeFormat(e.right)
+
+  private def dateTimeFormatArgs(format: Expression, zoneId: ZoneId): List[Expression] =
+    format :: Literal(zoneId.getId) :: Literal(SQLConf.get.legacyTimeParserPolicy.toString) :: Nil
+
+  private def mapKeyDedupPolicy: Expression =
+    Literal(SQLConf.get.getConf(SQLConf.MAP_KEY_DEDUP_POLICY))
+
+  // json options are case-insensitive
+  private def jsonParseMode(e: JsonToStructs): Option[String] =
+    e.options.collectFirst {
+      case (k, v) if k.equalsIgnoreCase("mode") => v.toUpperCase(Locale.ROOT)
+    }
+
+  private def isSupportedFromJson(e: JsonToStructs): Boolean = {
+    // the corrupt record column is not filled natively
+    val hasCorruptRecordColumn = e.dataType match {
+      case t: StructType => t.fieldNames.contains(SQLConf.get.columnNameOfCorruptRecord)
+      case _ => false
+    }
+    e.options.keys.forall(_.equalsIgnoreCase("mode")) &&
+    jsonParseMode(e).forall(mode => mode == "PERMISSIVE" || mode == "FAILFAST") &&
+    !hasCorruptRecordColumn &&
+    isSupportedFromJsonType(e.dataType)
+  }
+
+  private def isSupportedFromJsonType(dataType: DataType): Boolean = dataType match {
+    case BooleanType | ByteType | ShortType | IntegerType | LongType => true
+    case FloatType | DoubleType | StringType | _: DecimalType => true
+    case t: ArrayType => isSupportedFromJsonType(t.elementType)
+    case t: StructType => t.fields.forall(f => isSupportedFromJsonType(f.dataType))
+    case t: MapType => t.keyType == StringType && isSupportedFromJsonType(t.valueType)
+    case _ => false
+  }
+
+  private def isSupportedToJsonType(dataType: DataType): Boolean = dataType match {
+    case BooleanType | ByteType | ShortType | IntegerType | LongType => true
+    case FloatType | DoubleType | StringType | _: DecimalType => true
+    case DateType | TimestampType => true
+    case t: ArrayType => isSupportedToJsonType(t.elementType)
+    case t: StructType => t.fields.forall(f => isSupportedToJsonType(f.dataType))
+    case MapType(StringType | BooleanType | _: IntegralType, valueType, _) =>
+      isSupportedToJsonType(valueType)
+    case _ => false
+  }
+
+  // regex patterns are translated into rust regex natively, literal patterns with
+  // unsupported constructs (lookarounds, backreferences, etc.) are checked here so that
+  // they fall back instead of failing at runtime
+  def isNativeRegex(pattern: Expression): Boolean = pattern match {
+    case Literal(pattern, StringType) if pattern != null =>
+      val reason = unsupportedRegexReason(pattern.toString)
+      reason.foreach(reason => logWarning(s"regex pattern is not supported natively: $reason"))
+      reason.isEmpty
+    case _ => true
+  }
+
+  // returns the reason if the java regex pattern cannot be translated natively, rejecting
+  // the same constructs as the native translator (see spark_regexp.rs)
+  def unsupportedRegexReason(pattern: String): Option[String] = {
+    try {
+      Pattern.compile(pattern)
+    } catch {
+      case e: PatternSyntaxException => return Some(s"invalid pattern: ${e.getDescription}")
+    }
+
+    val supportedJavaProperties = Set(
+      "javaLowerCase",
+      "javaUpperCase",
+      "javaWhitespace",
+      "javaAlphabetic",
+      "javaLetter",
+      "javaDigit")
+    val charAt = (i: Int) => if (i < pattern.length) pattern.charAt(i) else 0.toChar
+    var classDepth = 0
+    var i = 0
+
+    while (i < pattern.length) {
+      val c = pattern.charAt(i)
+      i += 1
+      c match {
+        case '\\' =>
+          val e = charAt(i)
+          i += 1
+          e match {
+            case 'Q' =>
+              val end = pattern.indexOf("\\E", i)
+              i = if (end >= 0) end + 2 else pattern.length
+            case 'c' => i += 1
+            case 'p' | 'P' =>
+              val name = if (charAt(i) == '{') {
+                val end = pattern.indexOf('}', i)
+                val name = pattern.substring(i + 1, end)
+                i = end + 1
+                name
+              } else {
+                i += 1
+                charAt(i - 1).toString
+              }
+              if (name.startsWith("java") && !supportedJavaProperties.contains(name)
+                || name.startsWith("In")
+                || name.startsWith("block=")
+                || name.startsWith("blk=")) {
+                return Some(s"character property '$name'")
+              }
+            case e if e >= '1' && e <= '9' || e == 'k' => return Some("backreference")
+            case 'G' => return Some("'\\G'")
+            case 'Z' if classDepth > 0 => return Some("'\\Z'")
+            case 'X' => return Some("'\\X'")
+            case 'N' => return Some("'\\N'")
+            case _ =>
+          }
+
+        case '[' =>
+          classDepth += 1
+        case ']' if classDepth > 0 =>
+          classDepth -= 1
+        case _ if classDepth > 0 =>
+
+        case '(' if charAt(i) == '?' =>
+          val rest = pattern.slice(i + 1, i + 3)
+          if (rest.startsWith("=") || rest.startsWith("!")) {
+            return Some("lookahead")
+          }
+          if (rest == "<=" || rest == "<!") {
+            return Some("lookbehind")
+          }
+          if (rest.startsWith(">")) {
+            return Some("atomic group")
+          }
+          if (rest.startsWith(":")) {
+            i += 2
+          } else if (rest.startsWith("<")) {
+            i = pattern.indexOf('>', i) + 1
+          } else {
+            // embedded flags: (?flags) or (?flags:X)
+            val flags = pattern.drop(i + 1).takeWhile(c => c != ')' && c != ':')
+            flags.find(flag => !"-smiux".contains(flag)).foreach { flag =>
+              return Some(s"flag '$flag'")
+            }
+            i += flags.length + 2
+          }
+
+        case '*' | '+' | '?' | '{' =>
+          if (c == '{') {
+            val end = pattern.indexOf('}', i)
+            i = if (end >= 0) end + 1 else pattern.length
+          }
+          charAt(i) match {
+            case '+' => return Some("possessive quantifier")
+            case '?' => i += 1
+            case _ =>
+          }
+        case _ =>
+      }
+    }
+    None
+  }
+
+  // a lambda function, or an expression referencing lambda variables which are not
+  // declared by lambda functions inside it
+  private def hasUnboundLambdaVariables(e: Expression): Boolean = {
+    val declared = e.collect { case f: LambdaFunction =>
+      f.arguments.collect { case v: NamedLambdaVariable => v.exprId }
+    }.flatten
+    e.isInstanceOf[LambdaFunction] || e.collectFirst {
+      case v: NamedLambdaVariable if !declared.contains(v.exprId) => v
+    }.isDefined
+  }
+
+  private def isLambdaFunction(e: Expression): Boolean = e match {
+    case f: LambdaFunction => f.arguments.forall(_.isInstanceOf[NamedLambdaVariable])
+    case _ => false
+  }
+
+  private def buildHigherOrderFunction(
+      func: pb.HigherOrderFunction,
+      args: Seq[Expression],
+      lambdas: Seq[Expression],
+      dataType: DataType,
+      isPruningExpr: Boolean,
+      fallback: Expression => pb.PhysicalExprNode): pb.PhysicalExprNode = {
+    val lambdaNodes = lambdas.map { case lambda: LambdaFunction =>
+      pb.PhysicalLambdaFunctionNode
+        .newBuilder()
+        .addAllArgs(lambda.arguments
+          .map(arg => convertNamedLambdaVariable(arg.asInstanceOf[NamedLambdaVariable]))
+          .asJava)
+        .setBody(convertExprWithFallback(lambda.function, isPruningExpr, fallback))
+        .build()
+    }
+    buildExprNode {
+      _.setHigherOrderFunctionExpr(
+        pb.PhysicalHigherOrderFunctionExprNode
+          .newBuilder()
+          .setFunc(func)
+          .addAllArgs(args.map(convertExprWithFallback(_, isPruningExpr, fallback)).asJava)
+          .addAllLambdas(lambdaNodes.asJava)
+          .setReturnType(convertDataType(dataType)))
+    }
+  }
+
+  private def convertNamedLambdaVariable(
+      v: NamedLambdaVariable): pb.PhysicalNamedLambdaVariableExprNode = {
+    pb.PhysicalNamedLambdaVariableExprNode
+      .newBuilder()
+      .setName(Util.getFieldNameByExprId(v))
+      .setDataType(convertDataType(v.dataType))
+      .setNullable(v.nullable)
+      .build()
+  }
+
   def serializeExpression[E <: Expression](
       expr: E with Serializable,
       paramsSchema: StructType): Array[Byte] = {
@@ -40,6 +40,7 @@ import org.apache.spark.sql.catalyst.expressions.Attribute
 import org.apache.spark.sql.catalyst.expressions.Generator
 import org.apache.spark.sql.catalyst.expressions.NamedExpression
 import org.apache.spark.sql.catalyst.expressions.SortOrder
+import org.apache.spark.sql.catalyst.expressions.WindowFrame
 import org.apache.spark.sql.catalyst.plans.JoinType
 import org.apache.spark.sql.execution.blaze.plan.NativeBroadcastJoinBase
 import org.apache.spark.sql.execution.blaze.plan.NativeSortMergeJoinBase
@@ -83,12 +84,21 @@ abstract class Shims {
       joinType: JoinType,
       broadcastSide: BroadcastSide): NativeBroadcastJoinBase
 
+  def createNativeBroadcastNestedLoopJoinExec(
+      left: SparkPlan,
+      right: SparkPlan,
+      outputPartitioning: Partitioning,
+      joinType: JoinType,
+      broadcastSide: BroadcastSide,
+      condition: Option[Expression]): NativeBroadcastNestedLoopJoinBase
+
   def createNativeSortMergeJoinExec(
       left: SparkPlan,
       right: SparkPlan,
       leftKeys: Seq[Expression],
       rightKeys: Seq[Expression],
-      joinType: JoinType): NativeSortMergeJoinBase
+      joinType: JoinType,
+      condition: Option[Expression]): NativeSortMergeJoinBase
 
   def createNativeShuffledHashJoinExec(
       left: SparkPlan,
@@ -199,8 +209,23 @@ abstract class Shims {
 
   def convertMoreAggregateExpr(e: AggregateExpression): Option[pb.PhysicalExprNode]
 
+  def convertMoreWindowFunction(
+      function: Expression,
+      frame: WindowFrame,
+      orderSpec: Seq[SortOrder]): Option[pb.WindowExprNode]
+
   def getLikeEscapeChar(expr: Expression): Char
 
+  def isPercentileReversed(expr: Expression): Boolean
+
+  def getNullOnDivideByZero(expr: Expression): Boolean
+
+  def isBase64Chunked(expr: Expression): Boolean
+
+  def getStringConversionFailOnError(expr: Expression): Boolean
+
+  def getEvalMode(expr: Expression): pb.EvalMode
+
   def getAggregateExpressionFilter(expr: Expression): Option[Expression]
 
   def createFileSegment(file: File, offset: Long, length: Long, numRecords: Long): FileSegment
@@ -0,0 +1,245 @@
+/*
+ * Copyright 2022 The Blaze Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+package org.apache.spark.sql.blaze
+
+import java.nio.ByteBuffer
+
+import scala.collection.mutable
+
+import org.apache.arrow.c.ArrowArray
+import org.apache.arrow.c.Data
+import org.apache.arrow.vector.VectorSchemaRoot
+import org.apache.arrow.vector.dictionary.DictionaryProvider
+import org.apache.arrow.vector.dictionary.DictionaryProvider.MapDictionaryProvider
+import org.apache.arrow.vector.types.pojo.Schema
+import org.apache.spark.TaskContext
+import org.apache.spark.internal.Logging
+import org.apache.spark.sql.blaze.util.Using
+import org.apache.spark.sql.catalyst.InternalRow
+import org.apache.spark.sql.catalyst.expressions.BoundReference
+import org.apache.spark.sql.catalyst.expressions.Nondeterministic
+import org.apache.spark.sql.catalyst.expressions.SpecificInternalRow
+import org.apache.spark.sql.catalyst.expressions.UnsafeProjection
+import org.apache.spark.sql.catalyst.expressions.UnsafeRow
+import org.apache.spark.sql.catalyst.expressions.aggregate.ImperativeAggregate
+import org.apache.spark.sql.catalyst.expressions.aggregate.TypedImperativeAggregate
+import org.apache.spark.sql.execution.blaze.arrowio.ColumnarHelper
+import org.apache.spark.sql.execution.blaze.arrowio.util.ArrowUtils
+import org.apache.spark.sql.execution.blaze.arrowio.util.ArrowWriter
+import org.apache.spark.sql.types.IntegerType
+import org.apache.spark.sql.types.ObjectType
+import org.apache.spark.sql.types.StructField
+import org.apache.spark.sql.types.StructType
+import org.apache.spark.util.SizeEstimator
+
+/**
+ * Holds aggregation buffers of a spark aggregate function evaluated for native AggExec. buffers
+ * are addressed by indices allocated in the native side, and are released once they are merged,
+ * evaluated or serialized.
+ */
+case class SparkUDAFWrapperContext(serialized: ByteBuffer) extends Logging {
+  private val (expr, javaParamsSchema) =
+    NativeConverters.deserializeExpression[ImperativeAggregate]({
+      val bytes = new Array[Byte](serialized.remaining())
+      serialized.get(bytes)
+      bytes
+    })
+
+  // initialize all nondeterministic children exprs
+  expr.foreach {
+    case nondeterministic: Nondeterministic =>
+      nondeterministic.initialize(TaskContext.get.partitionId())
+    case _ =>
+  }
+
+  private val aggregate = expr.withNewMutableAggBufferOffset(0).withNewInputAggBufferOffset(0)
+  private val bufferSchema = aggregate.aggBufferSchema
+  private val buffers = mutable.ArrayBuffer[InternalRow]()
+  private var estimatedBufferSize = 0L
+
+  private val dictionaryProvider: DictionaryProvider = new MapDictionaryProvider()
+  private val updateInputSchema = ArrowUtils.toArrowSchema(
+    StructType(StructField("", IntegerType, nullable = false) +: javaParamsSchema.fields))
+  private val mergeInputSchema = ArrowUtils.toArrowSchema(
+    StructType(
+      Seq(
+        StructField("", IntegerType, nullable = false),
+        StructField("", IntegerType, nullable = false))))
+  private val evalInputSchema = ArrowUtils.toArrowSchema(
+    StructType(Seq(StructField("", IntegerType, nullable = true))))
+  private val outputSchema = ArrowUtils.toArrowSchema(
+    StructType(Seq(StructField("", aggregate.dataType, nullable = true))))
+
+  // update inputs are (bufferIdx, params...)
+  private val inputToParams = {
+    val toParams = UnsafeProjection.create(javaParamsSchema.fields.zipWithIndex.map {
+      case (field, i) => BoundReference(i + 1, field.dataType, field.nullable)
+    })
+    toParams.initialize(Option(TaskContext.get()).map(_.partitionId()).getOrElse(0))
+    toParams
+  }
+  private lazy val bufferToUnsafe = UnsafeProjection.create(bufferSchema)
+
+  def update(importFFIArrayPtr: Long): Long = {
+    importBatch(updateInputSchema, importFFIArrayPtr) { inputRows =>
+      for (inputRow <- inputRows) {
+        val buffer = getOrCreateBuffer(inputRow.getInt(0))
+        aggregate.update(buffer, inputToParams(inputRow))
+        sampleBufferSize(buffer)
+      }
+    }
+    estimatedBufferSize
+  }
+
+  def merge(importFFIArrayPtr: Long): Long = {
+    importBatch(mergeInputSchema, importFFIArrayPtr) { inputRows =>
+      for (inputRow <- inputRows) {
+        val buffer = getOrCreateBuffer(inputRow.getInt(0))
+        val mergingBuffer = releaseBuffer(inputRow.getInt(1))
+        aggregate match {
+          case typed: TypedImperativeAggregate[_] =>
+            val typedAny = typed.asInstanceOf[TypedImperativeAggregate[Any]]
+            val merged = typedAny.merge(getBufferObject(buffer), getBufferObject(mergingBuffer))
+            buffer.update(0, merged)
+          case _ =>
+            aggregate.merge(buffer, mergingBuffer)
+        }
+        sampleBufferSize(buffer)
+      }
+    }
+    estimatedBufferSize
+  }
+
+  def eval(importFFIArrayPtr: Long, exportFFIArrayPtr: Long): Unit = {
+    Using.resource(ArrowUtils.newChildAllocator(getClass.getName)) { batchAllocator =>
+      Using.resources(
+        VectorSchemaRoot.create(outputSchema, batchAllocator),
+        ArrowArray.wrap(exportFFIArrayPtr)) { (outputRoot, exportArray) =>
+        // evaluate buffers and write to output root
+        val outputWriter = ArrowWriter.create(outputRoot)
+        importBatch(evalInputSchema, importFFIArrayPtr) { inputRows =>
+          for (inputRow <- inputRows) {
+            val buffer = if (inputRow.isNullAt(0)) {
+              createBuffer()
+            } else {
+              releaseBuffer(inputRow.getInt(0))
+            }
+            outputWriter.write(InternalRow(aggregate.eval(buffer)))
+          }
+        }
+        outputWriter.finish()
+
+        // export to output using root allocator
+        Data.exportVectorSchemaRoot(
+          ArrowUtils.rootAllocator,
+          outputRoot,
+          dictionaryProvider,
+          exportArray)
+      }
+    }
+  }
+
+  def serialize(idx: Int): Array[Byte] = {
+    val buffer = releaseBuffer(idx)
+    aggregate match {
+      case typed: TypedImperativeAggregate[_] =>
+        typed.asInstanceOf[TypedImperativeAggregate[Any]].serialize(getBufferObject(buffer))
+      case _ =>
+        bufferToUnsafe(buffer).getBytes
+    }
+  }
+
+  def deserialize(idx: Int, serialized: ByteBuffer): Unit = {
+    val bytes = if (serialized != null) {
+      val bytes = new Array[Byte](serialized.remaining())
+      serialized.get(bytes)
+      bytes
+    } else {
+      Array.emptyByteArray
+    }
+    val buffer = new SpecificInternalRow(bufferSchema.map(_.dataType))
+    aggregate match {
+      case typed: TypedImperativeAggregate[_] =>
+        buffer.update(0, typed.deserialize(bytes))
+      case _ =>
+        val unsafeRow = new UnsafeRow(bufferSchema.length)
+        unsafeRow.pointTo(bytes, bytes.length)
+        for ((field, i) <- bufferSchema.fields.zipWithIndex) {
+          if (unsafeRow.isNullAt(i)) {
+            buffer.setNullAt(i)
+          } else {
+            buffer.update(i, unsafeRow.get(i, field.dataType))
+          }
+        }
+    }
+    ensureCapacity(idx)
+    buffers(idx) = buffer
+  }
+
+  private def importBatch(schema: Schema, ptr: Long)(
+      process: Iterator[InternalRow] => Unit): Unit = {
+    Using.resource(ArrowUtils.newChildAllocator(getClass.getName)) { batchAllocator =>
+      Using.resources(VectorSchemaRoot.create(schema, batchAllocator), ArrowArray.wrap(ptr)) {
+        (inputRoot, importArray) =>
+          Data.importIntoVectorSchemaRoot(
+            batchAllocator,
+            importArray,
+            inputRoot,
+            dictionaryProvider)
+          process(ColumnarHelper.batchAsRowIter(ColumnarHelper.rootAsBatch(inputRoot)))
+      }
+    }
+  }
+
+  private def createBuffer(): InternalRow = {
+    val buffer = new SpecificInternalRow(bufferSchema.map(_.dataType))
+    aggregate.initialize(buffer)
+    buffer
+  }
+
+  private def ensureCapacity(idx: Int): Unit = {
+    while (buffers.length <= idx) {
+      buffers += null
+    }
+  }
+
+  private def getOrCreateBuffer(idx: Int): InternalRow = {
+    ensureCapacity(idx)
+    if (buffers(idx) == null) {
+      buffers(idx) = createBuffer()
+    }
+    buffers(idx)
+  }
+
+  private def releaseBuffer(idx: Int): InternalRow = {
+    val buffer = getOrCreateBuffer(idx)
+    buffers(idx) = null
+    buffer
+  }
+
+  private def getBufferObject(buffer: InternalRow): Any = {
+    buffer.get(0, ObjectType(classOf[AnyRef]))
+  }
+
+  // buffer size is estimated by sampling, which is expensive for big buffers
+  private var numSamplingRows = 0L
+  private def sampleBufferSize(buffer: InternalRow): Unit = {
+    if (numSamplingRows % 1000 == 0) {
+      estimatedBufferSize = SizeEstimator.estimate(buffer)
+    }
+    numSamplingRows += 1
+  }
+}
@@ -0,0 +1,230 @@
+/*
+ * Copyright 2022 The Blaze Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+package org.apache.spark.sql.execution.blaze.plan
+
+import scala.collection.JavaConverters._
+import scala.collection.immutable.SortedMap
+import scala.util.Try
+
+import org.apache.spark.OneToOneDependency
+import org.apache.spark.Partition
+import org.apache.spark.sql.blaze.MetricNode
+import org.apache.spark.sql.blaze.NativeConverters
+import org.apache.spark.sql.blaze.NativeHelper
+import org.apache.spark.sql.blaze.NativeRDD
+import org.apache.spark.sql.blaze.NativeSupports
+import org.apache.spark.sql.catalyst.expressions.Ascending
+import org.apache.spark.sql.catalyst.expressions.Attribute
+import org.apache.spark.sql.catalyst.expressions.Expression
+import org.apache.spark.sql.catalyst.expressions.NullsFirst
+import org.apache.spark.sql.catalyst.expressions.SortOrder
+import org.apache.spark.sql.catalyst.plans.ExistenceJoin
+import org.apache.spark.sql.catalyst.plans.FullOuter
+import org.apache.spark.sql.catalyst.plans.InnerLike
+import org.apache.spark.sql.catalyst.plans.JoinType
+import org.apache.spark.sql.catalyst.plans.LeftAnti
+import org.apache.spark.sql.catalyst.plans.LeftOuter
+import org.apache.spark.sql.catalyst.plans.LeftSemi
+import org.apache.spark.sql.catalyst.plans.RightOuter
+import org.apache.spark.sql.catalyst.plans.physical.BroadcastDistribution
+import org.apache.spark.sql.catalyst.plans.physical.Distribution
+import org.apache.spark.sql.catalyst.plans.physical.IdentityBroadcastMode
+import org.apache.spark.sql.catalyst.plans.physical.Partitioning
+import org.apache.spark.sql.catalyst.plans.physical.UnspecifiedDistribution
+import org.apache.spark.sql.execution.BinaryExecNode
+import org.apache.spark.sql.execution.SparkPlan
+import org.apache.spark.sql.execution.metric.SQLMetric
+import org.blaze.{protobuf => pb}
+
+abstract class NativeBroadcastNestedLoopJoinBase(
+    override val left: SparkPlan,
+    override val right: SparkPlan,
+    override val outputPartitioning: Partitioning,
+    joinType: JoinType,
+    broadcastSide: BroadcastSide,
+    condition: Option[Expression])
+    extends BinaryExecNode
+    with NativeSupports {
+
+  override lazy val metrics: Map[String, SQLMetric] = SortedMap[String, SQLMetric]() ++ Map(
+    NativeHelper
+      .getDefaultNativeMetrics(sparkContext)
+      .filterKeys(
+        Set(
+          "stage_id",
+          "output_rows",
+          "elapsed_compute",
+          "input_batch_count",
+          "input_batch_mem_size",
+          "input_row_count"))
+      .toSeq: _*)
+
+  override def output: Seq[Attribute] = joinType match {
+    case _: InnerLike => left.output ++ right.output
+    case LeftOuter => left.output ++ right.output.map(_.withNullability(true))
+    case RightOuter => left.output.map(_.withNullability(true)) ++ right.output
+    case FullOuter =>
+      left.output.map(_.withNullability(true)) ++ right.output.map(_.withNullability(true))
+    case j: ExistenceJoin => left.output :+ j.exists
+    case LeftSemi | LeftAnti => left.output
+    case other => throw new NotImplementedError(s"join type not supported: $other")
+  }
+
+  override def requiredChildDistribution: Seq[Distribution] = broadcastSide match {
+    case BroadcastLeft =>
+      BroadcastDistribution(IdentityBroadcastMode) :: UnspecifiedDistribution :: Nil
+    case BroadcastRight =>
+      UnspecifiedDistribution :: BroadcastDistribution(IdentityBroadcastMode) :: Nil
+  }
+
+  private def nativeSchema = Util.getNativeSchema(output)
+
+  private def nativeJoinType = NativeConverters.convertJoinType(joinType)
+
+  private def nativeBroadcastSide = broadcastSide match {
+    case BroadcastLeft => pb.JoinSide.LEFT_SIDE
+    case BroadcastRight => pb.JoinSide.RIGHT_SIDE
+  }
+
+  private def nativeJoinFilter = condition.map { condition =>
+    NativeConverters.convertJoinFilter(condition, left.output, right.output)
+  }
+
+  // band conditions like `a.ts BETWEEN b.start AND b.end` are joined by a native sort merge
+  // join without equal keys, which sweeps both sides sorted by band values instead of
+  // evaluating the condition on every pair of rows
+  private def nativeBandJoin = condition.flatMap { condition =>
+    Try {
+      val band =
+        NativeSortMergeJoinBase.convertBandCondition(condition, left.output, right.output)
+      val (leftOrders, rightOrders) =
+        NativeSortMergeJoinBase.bandSortOrders(condition, left.output, right.output)
+      (band, leftOrders.map(toNativeSortExpr), rightOrders.map(toNativeSortExpr))
+    }.toOption
+  }
+
+  private def toNativeSortExpr(sortOrder: SortOrder): pb.PhysicalExprNode =
+    pb.PhysicalExprNode
+      .newBuilder()
+      .setSort(
+        pb.PhysicalSortExprNode
+          .newBuilder()
+          .setExpr(NativeConverters.convertExpr(sortOrder.child))
+          .setAsc(sortOrder.direction == Ascending)
+          .setNullsFirst(sortOrder.nullOrdering == NullsFirst)
+          .build())
+      .build()
+
+  // unmatched/semi/anti/existence rows of the broadcast side depend on the matches of all
+  // probed partitions, which are not visible to each other in native execution
+  private def checkProbedPartitions(): Unit = {
+    val (outputsBroadcastRows, probed) = broadcastSide match {
+      case BroadcastLeft =>
+        val outputs = joinType match {
+          case LeftOuter | FullOuter | LeftSemi | LeftAnti | ExistenceJoin(_) => true
+          case _ => false
+        }
+        (outputs, right)
+      case BroadcastRight =>
+        (joinType == RightOuter || joinType == FullOuter, left)
+    }
+    if (outputsBroadcastRows && probed.outputPartitioning.numPartitions > 1) {
+      throw new NotImplementedError(
+        s"$joinType join with $broadcastSide requires a single probed partition")
+    }
+  }
+
+  // check whether native converting is supported
+  nativeSchema
+  nativeJoinType
+  nativeBroadcastSide
+  if (nativeBandJoin.isEmpty) {
+    nativeJoinFilter
+  }
+  checkProbedPartitions()
+
+  override def doExecuteNative(): NativeRDD = {
+    val leftRDD = NativeHelper.executeNative(left)
+    val rightRDD = NativeHelper.executeNative(right)
+    val nativeMetrics = MetricNode(metrics, leftRDD.metrics :: rightRDD.metrics :: Nil)
+    val nativeSchema = this.nativeSchema
+    val nativeJoinType = this.nativeJoinType
+    val nativeBroadcastSide = this.nativeBroadcastSide
+    val nativeBandJoin = this.nativeBandJoin
+    val nativeJoinFilter = if (nativeBandJoin.isEmpty) this.nativeJoinFilter else None
+
+    val probedRDD = broadcastSide match {
+      case BroadcastLeft => rightRDD
+      case BroadcastRight => leftRDD
+    }
+
+    new NativeRDD(
+      sparkContext,
+      nativeMetrics,
+      probedRDD.partitions,
+      rddDependencies = new OneToOneDependency(probedRDD) :: Nil,
+      probedRDD.isShuffleReadFull,
+      (partition, context) => {
+        val partition0 = new Partition() {
+          override def index: Int = 0
+        }
+        val (leftChild, rightChild) = broadcastSide match {
+          case BroadcastLeft =>
+            (
+              leftRDD.nativePlan(partition0, context),
+              rightRDD.nativePlan(rightRDD.partitions(partition.index), context))
+          case BroadcastRight =>
+            (
+              leftRDD.nativePlan(leftRDD.partitions(partition.index), context),
+              rightRDD.nativePlan(partition0, context))
+        }
+
+        nativeBandJoin match {
+          case Some((band, leftSortExprs, rightSortExprs)) =>
+            val sorted = (input: pb.PhysicalPlanNode, sortExprs: Seq[pb.PhysicalExprNode]) => {
+              val sortExec = pb.SortExecNode
+                .newBuilder()
+                .setInput(input)
+                .addAllExpr(sortExprs.asJava)
+              pb.PhysicalPlanNode.newBuilder().setSort(sortExec).build()
+            }
+            val sortMergeJoinExec = pb.SortMergeJoinExecNode
+              .newBuilder()
+              .setSchema(nativeSchema)
+              .setLeft(sorted(leftChild, leftSortExprs))
+              .setRight(sorted(rightChild, rightSortExprs))
+              .setJoinType(nativeJoinType)
+              .setBandCondition(band)
+            pb.PhysicalPlanNode.newBuilder().setSortMergeJoin(sortMergeJoinExec).build()
+
+          case None =>
+            val nestedLoopJoinExec = pb.BroadcastNestedLoopJoinExecNode
+              .newBuilder()
+              .setSchema(nativeSchema)
+              .setLeft(leftChild)
+              .setRight(rightChild)
+              .setJoinType(nativeJoinType)
+              .setBroadcastSide(nativeBroadcastSide)
+            nativeJoinFilter.foreach(nestedLoopJoinExec.setJoinFilter)
+            pb.PhysicalPlanNode
+              .newBuilder()
+              .setBroadcastNestedLoopJoin(nestedLoopJoinExec)
+              .build()
+        }
+      },
+      friendlyName = "NativeRDD.BroadcastNestedLoopJoin")
+  }
+}
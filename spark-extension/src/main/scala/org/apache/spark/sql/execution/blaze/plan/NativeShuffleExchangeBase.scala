@@ -18,12 +18,23 @@ package org.apache.spark.sql.execution.blaze.plan
 import java.util.UUID
 
 import scala.collection.JavaConverters._
+import scala.collection.mutable
+import scala.collection.mutable.ArrayBuffer
+import scala.util.hashing.byteswap32
 
 import org.apache.spark.Partitioner
+import org.apache.spark.RangePartitioner
 import org.apache.spark.ShuffleDependency
 import org.apache.spark.SparkEnv
 import org.apache.spark.TaskContext
 import org.blaze.protobuf.{IpcReaderExecNode, PhysicalHashRepartition, PhysicalPlanNode, Schema}
+import org.blaze.protobuf.PhysicalExprNode
+import org.blaze.protobuf.PhysicalRangeRepartition
+import org.blaze.protobuf.PhysicalRoundRobinRepartition
+import org.blaze.protobuf.PhysicalSortExprNode
+import org.blaze.protobuf.ScalarListValue
+import org.blaze.protobuf.SortExecNode
+import org.apache.spark.rdd.PartitionPruningRDD
 import org.apache.spark.rdd.RDD
 import org.apache.spark.serializer.Serializer
 import org.apache.spark.shuffle.ShuffleWriteProcessor
@@ -36,8 +47,18 @@ import org.apache.spark.sql.blaze.NativeSupports
 import org.apache.spark.sql.blaze.Shims
 import org.apache.spark.sql.catalyst.plans.physical.Partitioning
 import org.apache.spark.sql.catalyst.InternalRow
+import org.apache.spark.sql.catalyst.expressions.Ascending
 import org.apache.spark.sql.catalyst.expressions.Attribute
+import org.apache.spark.sql.catalyst.expressions.BoundReference
+import org.apache.spark.sql.catalyst.expressions.Murmur3Hash
+import org.apache.spark.sql.catalyst.expressions.NullsFirst
+import org.apache.spark.sql.catalyst.expressions.RowOrdering
+import org.apache.spark.sql.catalyst.expressions.SortOrder
+import org.apache.spark.sql.catalyst.expressions.UnsafeProjection
+import org.apache.spark.sql.catalyst.expressions.codegen.LazilyGeneratedOrdering
 import org.apache.spark.sql.catalyst.plans.physical.HashPartitioning
+import org.apache.spark.sql.catalyst.plans.physical.RangePartitioning
+import org.apache.spark.sql.catalyst.plans.physical.RoundRobinPartitioning
 import org.apache.spark.sql.catalyst.plans.physical.SinglePartition
 import org.apache.spark.sql.execution.exchange.ShuffleExchangeLike
 import org.apache.spark.sql.execution.metric.SQLMetric
@@ -47,6 +68,8 @@ import org.apache.spark.sql.execution.SparkPlan
 import org.apache.spark.sql.execution.UnsafeRowSerializer
 import org.apache.spark.sql.execution.blaze.shuffle.BlazeBlockStoreShuffleReaderBase
 import org.apache.spark.sql.execution.blaze.shuffle.BlazeShuffleDependency
+import org.apache.spark.sql.internal.SQLConf
+import org.apache.spark.sql.types._
 import org.apache.spark.util.CompletionIterator
 import org.apache.spark.OneToOneDependency
 
@@ -91,9 +114,44 @@ abstract class NativeShuffleExchangeBase(
     case _ => null
   }
 
+  private def nativeSortExprs = outputPartitioning match {
+    case RangePartitioning(sortOrder, _) =>
+      sortOrder.foreach { sortOrder =>
+        if (!NativeShuffleExchangeBase.isSupportedRangeBoundType(sortOrder.dataType)) {
+          throw new NotImplementedError(s"unsupported range bound type: ${sortOrder.dataType}")
+        }
+      }
+      sortOrder.map(toNativeSortExpr).toList
+    case _ => null
+  }
+
+  // like spark.sql.execution.sortBeforeRepartition, input rows are sorted before round-robin
+  // partitioning, so that a retried map task sends rows to the same reducers even if its
+  // input order differs
+  private def nativeRoundRobinSortExprs = outputPartitioning match {
+    case RoundRobinPartitioning(numPartitions)
+        if numPartitions > 1 && SQLConf.get.sortBeforeRepartition =>
+      NativeShuffleExchangeBase
+        .roundRobinSortOrder(child.output)
+        .map(toNativeSortExpr)
+        .map(sortExpr => PhysicalExprNode.newBuilder().setSort(sortExpr).build())
+        .toList
+    case _ => null
+  }
+
+  private def toNativeSortExpr(sortOrder: SortOrder): PhysicalSortExprNode =
+    PhysicalSortExprNode
+      .newBuilder()
+      .setExpr(NativeConverters.convertExpr(sortOrder.child))
+      .setAsc(sortOrder.direction == Ascending)
+      .setNullsFirst(sortOrder.nullOrdering == NullsFirst)
+      .build()
+
   // check whether native converting is supported
   nativeSchema
   nativeHashExprs
+  nativeSortExprs
+  nativeRoundRobinSortExprs
 
   protected def doExecuteNonNative(): RDD[InternalRow]
 
@@ -181,6 +239,18 @@ abstract class NativeShuffleExchangeBase(
         case _ =>
       }))
     val nativeHashExprs = this.nativeHashExprs
+    val nativeRoundRobinSortExprs = this.nativeRoundRobinSortExprs
+    val nativeRangeRepartition = outputPartitioning match {
+      case RangePartitioning(sortOrder, _) =>
+        // bounds are sampled from the input before writing, like spark's RangePartitioner
+        val bounds = NativeShuffleExchangeBase.computeRangeBounds(
+          rdd,
+          outputAttributes,
+          sortOrder,
+          numPartitions)
+        NativeShuffleExchangeBase.buildRangeRepartition(sortOrder, nativeSortExprs, bounds)
+      case _ => null
+    }
 
     val nativeShuffleRDD = new NativeRDD(
       nativeInputRDD.sparkContext,
@@ -200,11 +270,29 @@ abstract class NativeShuffleExchangeBase(
               .newBuilder()
               .setPartitionCount(numPartitions)
               .addAllHashExpr(nativeHashExprs.asJava)
+          case RangePartitioning(_, _) =>
+            PhysicalHashRepartition
+              .newBuilder()
+              .setPartitionCount(numPartitions)
+              .setRangeRepartition(nativeRangeRepartition)
+          case RoundRobinPartitioning(_) =>
+            PhysicalHashRepartition
+              .newBuilder()
+              .setPartitionCount(numPartitions)
+              .setRoundRobinRepartition(PhysicalRoundRobinRepartition.newBuilder())
           case p =>
             throw new NotImplementedError(s"cannot convert partitioning to native: $p")
         }
 
-        val input = nativeInputRDD.nativePlan(nativeInputPartition, taskContext)
+        val input = nativeRoundRobinSortExprs match {
+          case null => nativeInputRDD.nativePlan(nativeInputPartition, taskContext)
+          case sortExprs =>
+            val nativeSortExec = SortExecNode
+              .newBuilder()
+              .setInput(nativeInputRDD.nativePlan(nativeInputPartition, taskContext))
+              .addAllExpr(sortExprs.asJava)
+            PhysicalPlanNode.newBuilder().setSort(nativeSortExec).build()
+        }
         val nativeShuffleWriteExec =
           Shims.get.getShuffleWriteExec(input, nativeOutputPartitioning)
         nativeShuffleWriteExec
@@ -224,3 +312,96 @@ abstract class NativeShuffleExchangeBase(
     dependency
   }
 }
+
+object NativeShuffleExchangeBase {
+
+  // rows are sorted by their hash first, then by all orderable columns to break ties, which
+  // mirrors spark's sort by hash code and binary row comparison
+  def roundRobinSortOrder(output: Seq[Attribute]): Seq[SortOrder] = {
+    val hashOrder = SortOrder(Murmur3Hash(output, 42), Ascending)
+    val columnOrders = output
+      .filter(attr => RowOrdering.isOrderable(attr.dataType))
+      .map(attr => SortOrder(attr, Ascending))
+    hashOrder +: columnOrders
+  }
+
+  // types of range bounds which can be converted to native scalar values
+  def isSupportedRangeBoundType(dataType: DataType): Boolean = dataType match {
+    case BooleanType | ByteType | ShortType | IntegerType | LongType | FloatType | DoubleType |
+        StringType | DateType | TimestampType =>
+      true
+    case t: DecimalType => t.precision <= Decimal.MAX_LONG_DIGITS
+    case _ => false
+  }
+
+  // samples upper bounds of the first (numPartitions - 1) partitions, copied from spark's
+  // RangePartitioner so that the bounds can be passed to the native shuffle writer
+  def computeRangeBounds(
+      rdd: RDD[InternalRow],
+      outputAttributes: Seq[Attribute],
+      sortOrder: Seq[SortOrder],
+      numPartitions: Int): Array[InternalRow] = {
+    if (numPartitions <= 1) {
+      return Array.empty
+    }
+    val sortKeys = rdd.mapPartitionsInternal { iter =>
+      val projection = UnsafeProjection.create(sortOrder.map(_.child), outputAttributes)
+      iter.map(row => projection(row).copy(): InternalRow)
+    }
+    implicit val ordering: Ordering[InternalRow] =
+      new LazilyGeneratedOrdering(sortOrder.zipWithIndex.map { case (order, i) =>
+        order.copy(child = BoundReference(i, order.dataType, order.nullable))
+      })
+
+    val sampleSize =
+      math.min(SQLConf.get.rangeExchangeSampleSizePerPartition.toDouble * numPartitions, 1e6)
+    // assume the input is not too imbalanced, and oversample by 3x
+    val sampleSizePerPartition = math.ceil(3.0 * sampleSize / rdd.partitions.length).toInt
+    val (numItems, sketched) = RangePartitioner.sketch(sortKeys, sampleSizePerPartition)
+    if (numItems == 0L) {
+      return Array.empty
+    }
+
+    // resample imbalanced partitions with the same fraction
+    val fraction = math.min(sampleSize / math.max(numItems, 1L), 1.0)
+    val candidates = ArrayBuffer.empty[(InternalRow, Float)]
+    val imbalancedPartitions = mutable.Set.empty[Int]
+    sketched.foreach { case (idx, n, sample) =>
+      if (fraction * n > sampleSizePerPartition) {
+        imbalancedPartitions += idx
+      } else {
+        val weight = (n.toDouble / sample.length).toFloat
+        sample.foreach(key => candidates += ((key, weight)))
+      }
+    }
+    if (imbalancedPartitions.nonEmpty) {
+      val imbalanced = new PartitionPruningRDD(sortKeys, imbalancedPartitions.contains)
+      val seed = byteswap32(-rdd.id - 1)
+      val reSampled = imbalanced.sample(withReplacement = false, fraction, seed).collect()
+      val weight = (1.0 / fraction).toFloat
+      candidates ++= reSampled.map(x => (x, weight))
+    }
+    RangePartitioner.determineBounds(candidates, math.min(numPartitions, candidates.size))
+  }
+
+  def buildRangeRepartition(
+      sortOrder: Seq[SortOrder],
+      nativeSortExprs: Seq[PhysicalSortExprNode],
+      bounds: Array[InternalRow]): PhysicalRangeRepartition = {
+    val nativeBounds = sortOrder.zipWithIndex.map { case (order, i) =>
+      val values = ScalarListValue
+        .newBuilder()
+        .setDatatype(NativeConverters.convertScalarType(order.dataType))
+      bounds.foreach { bound =>
+        val value = bound.get(i, order.dataType)
+        values.addValues(NativeConverters.convertValue(value, order.dataType))
+      }
+      values.build()
+    }
+    PhysicalRangeRepartition
+      .newBuilder()
+      .addAllSortExpr(nativeSortExprs.asJava)
+      .addAllBounds(nativeBounds.asJava)
+      .build()
+  }
+}
@@ -27,8 +27,15 @@ import org.apache.spark.sql.blaze.NativeConverters
 import org.apache.spark.sql.blaze.NativeHelper
 import org.apache.spark.sql.blaze.NativeRDD
 import org.apache.spark.sql.blaze.NativeSupports
+import org.apache.spark.sql.catalyst.expressions.And
 import org.apache.spark.sql.catalyst.expressions.Ascending
+import org.apache.spark.sql.catalyst.expressions.Attribute
+import org.apache.spark.sql.catalyst.expressions.AttributeSet
 import org.apache.spark.sql.catalyst.expressions.Expression
+import org.apache.spark.sql.catalyst.expressions.GreaterThan
+import org.apache.spark.sql.catalyst.expressions.GreaterThanOrEqual
+import org.apache.spark.sql.catalyst.expressions.LessThan
+import org.apache.spark.sql.catalyst.expressions.LessThanOrEqual
 import org.apache.spark.sql.catalyst.expressions.SortOrder
 import org.apache.spark.sql.catalyst.plans.ExistenceJoin
 import org.apache.spark.sql.catalyst.plans.InnerLike
@@ -36,7 +43,9 @@ import org.apache.spark.sql.catalyst.plans.JoinType
 import org.apache.spark.sql.catalyst.plans.LeftAnti
 import org.apache.spark.sql.catalyst.plans.LeftSemi
 import org.apache.spark.sql.execution.BinaryExecNode
+import org.blaze.protobuf.JoinBandCondition
 import org.blaze.protobuf.JoinOn
+import org.blaze.protobuf.JoinSide
 import org.blaze.protobuf.PhysicalPlanNode
 import org.blaze.protobuf.SortMergeJoinExecNode
 import org.blaze.protobuf.SortOptions
@@ -46,7 +55,8 @@ abstract class NativeSortMergeJoinBase(
     override val right: SparkPlan,
     leftKeys: Seq[Expression],
     rightKeys: Seq[Expression],
-    joinType: JoinType)
+    joinType: JoinType,
+    condition: Option[Expression])
     extends BinaryExecNode
     with NativeSupports {
 
@@ -63,8 +73,15 @@ abstract class NativeSortMergeJoinBase(
           "input_row_count"))
       .toSeq: _*)
 
-  override def requiredChildOrdering: Seq[Seq[SortOrder]] =
-    requiredOrders(leftKeys) :: requiredOrders(rightKeys) :: Nil
+  override def requiredChildOrdering: Seq[Seq[SortOrder]] = condition match {
+    case Some(condition) if leftKeys.isEmpty =>
+      // without equal join keys, both sides are swept in the order of band values
+      val (leftOrders, rightOrders) =
+        NativeSortMergeJoinBase.bandSortOrders(condition, left.output, right.output)
+      leftOrders :: rightOrders :: Nil
+    case _ =>
+      requiredOrders(leftKeys) :: requiredOrders(rightKeys) :: Nil
+  }
 
   private def requiredOrders(keys: Seq[Expression]): Seq[SortOrder] = {
     // This must be ascending in order to agree with the `keyOrdering` defined in `doExecute()`.
@@ -93,11 +110,16 @@ abstract class NativeSortMergeJoinBase(
 
   private def nativeJoinType = NativeConverters.convertJoinType(joinType)
 
+  private def nativeBandCondition = condition.map { condition =>
+    NativeSortMergeJoinBase.convertBandCondition(condition, left.output, right.output)
+  }
+
   // check whether native converting is supported
   nativeSchema
   nativeSortOptions
   nativeJoinOn
   nativeJoinType
+  nativeBandCondition
 
   override def doExecuteNative(): NativeRDD = {
     val leftRDD = NativeHelper.executeNative(left)
@@ -106,6 +128,7 @@ abstract class NativeSortMergeJoinBase(
     val nativeSortOptions = this.nativeSortOptions
     val nativeJoinOn = this.nativeJoinOn
     val nativeJoinType = this.nativeJoinType
+    val nativeBandCondition = this.nativeBandCondition
 
     val partitions = if (joinType != RightOuter) {
       leftRDD.partitions
@@ -147,8 +170,98 @@ abstract class NativeSortMergeJoinBase(
           .setJoinType(nativeJoinType)
           .addAllOn(nativeJoinOn.asJava)
           .addAllSortOptions(nativeSortOptions.asJava)
+        nativeBandCondition.foreach(sortMergeJoinExec.setBandCondition)
         PhysicalPlanNode.newBuilder().setSortMergeJoin(sortMergeJoinExec).build()
       },
       friendlyName = "NativeRDD.SortMergeJoin")
   }
 }
+
+object NativeSortMergeJoinBase {
+
+  // converts `lower <(=) point AND point <(=) upper`, where point references only one side
+  // and lower/upper reference only the other side
+  def convertBandCondition(
+      condition: Expression,
+      leftOutput: Seq[Attribute],
+      rightOutput: Seq[Attribute]): JoinBandCondition = {
+    val band = extractBandCondition(condition, leftOutput, rightOutput)
+    JoinBandCondition
+      .newBuilder()
+      .setPointSide(band.pointSide)
+      .setPoint(NativeConverters.convertExpr(band.point))
+      .setLower(NativeConverters.convertExpr(band.lower))
+      .setUpper(NativeConverters.convertExpr(band.upper))
+      .setLowerInclusive(band.lowerInclusive)
+      .setUpperInclusive(band.upperInclusive)
+      .build()
+  }
+
+  // orders of left/right inputs required by band joins without equal join keys: points are
+  // sorted by themselves and ranges are sorted by their lower bounds
+  def bandSortOrders(
+      condition: Expression,
+      leftOutput: Seq[Attribute],
+      rightOutput: Seq[Attribute]): (Seq[SortOrder], Seq[SortOrder]) = {
+    val band = extractBandCondition(condition, leftOutput, rightOutput)
+    val pointOrders = SortOrder(band.point, Ascending) :: Nil
+    val rangeOrders = SortOrder(band.lower, Ascending) :: Nil
+    band.pointSide match {
+      case JoinSide.LEFT_SIDE => (pointOrders, rangeOrders)
+      case _ => (rangeOrders, pointOrders)
+    }
+  }
+
+  private case class BandCondition(
+      pointSide: JoinSide,
+      point: Expression,
+      lower: Expression,
+      upper: Expression,
+      lowerInclusive: Boolean,
+      upperInclusive: Boolean)
+
+  private def extractBandCondition(
+      condition: Expression,
+      leftOutput: Seq[Attribute],
+      rightOutput: Seq[Attribute]): BandCondition = {
+
+    // normalizes comparisons to (smaller, larger, inclusive)
+    val comparisons = (condition match {
+      case And(c1, c2) => Seq(c1, c2)
+      case other => Seq(other)
+    }).map {
+      case LessThan(l, r) => (l, r, false)
+      case LessThanOrEqual(l, r) => (l, r, true)
+      case GreaterThan(l, r) => (r, l, false)
+      case GreaterThanOrEqual(l, r) => (r, l, true)
+      case other => throw new NotImplementedError(s"join condition not supported: $other")
+    }
+    if (comparisons.length != 2) {
+      throw new NotImplementedError(s"join condition not supported: $condition")
+    }
+    val ((lower, point, lowerInclusive), (_, upper, upperInclusive)) =
+      Seq(comparisons, comparisons.reverse)
+        .map(cs => (cs(0), cs(1)))
+        .find { case ((_, point, _), (point2, _, _)) => point.semanticEquals(point2) }
+        .getOrElse(throw new NotImplementedError(s"join condition not supported: $condition"))
+
+    val leftSet = AttributeSet(leftOutput)
+    val rightSet = AttributeSet(rightOutput)
+    val isRangeOn = (set: AttributeSet) =>
+      lower.references.subsetOf(set) && upper.references.subsetOf(set)
+    val pointSide = if (point.references.isEmpty) {
+      throw new NotImplementedError(s"join condition not supported: $condition")
+    } else if (point.references.subsetOf(leftSet) && isRangeOn(rightSet)) {
+      JoinSide.LEFT_SIDE
+    } else if (point.references.subsetOf(rightSet) && isRangeOn(leftSet)) {
+      JoinSide.RIGHT_SIDE
+    } else {
+      throw new NotImplementedError(s"join condition not supported: $condition")
+    }
+    if (lower.dataType != point.dataType || upper.dataType != point.dataType) {
+      throw new NotImplementedError(s"join condition data types differ: $condition")
+    }
+
+    BandCondition(pointSide, point, lower, upper, lowerInclusive, upperInclusive)
+  }
+}
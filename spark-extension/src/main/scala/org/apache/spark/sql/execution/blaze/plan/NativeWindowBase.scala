@@ -24,6 +24,7 @@ import org.apache.spark.sql.blaze.NativeConverters
 import org.apache.spark.sql.blaze.NativeHelper
 import org.apache.spark.sql.blaze.NativeRDD
 import org.apache.spark.sql.blaze.NativeSupports
+import org.apache.spark.sql.blaze.Shims
 import org.apache.spark.sql.catalyst.expressions.Ascending
 import org.apache.spark.sql.catalyst.expressions.Attribute
 import org.apache.spark.sql.catalyst.expressions.Expression
@@ -38,15 +39,29 @@ import org.apache.spark.sql.catalyst.plans.physical.Partitioning
 import org.apache.spark.sql.execution.SparkPlan
 import org.apache.spark.sql.execution.UnaryExecNode
 import org.apache.spark.sql.execution.metric.SQLMetric
+import org.apache.spark.sql.execution.blaze.plan.NativeWindowBase.convertWindowFrame
 import org.blaze.{protobuf => pb}
+import org.apache.spark.sql.catalyst.expressions.CumeDist
+import org.apache.spark.sql.catalyst.expressions.CurrentRow
 import org.apache.spark.sql.catalyst.expressions.DenseRank
+import org.apache.spark.sql.catalyst.expressions.Literal
+import org.apache.spark.sql.catalyst.expressions.NTile
+import org.apache.spark.sql.catalyst.expressions.PercentRank
+import org.apache.spark.sql.catalyst.expressions.RangeFrame
+import org.apache.spark.sql.catalyst.expressions.RowFrame
+import org.apache.spark.sql.catalyst.expressions.SpecifiedWindowFrame
+import org.apache.spark.sql.catalyst.expressions.UnboundedFollowing
+import org.apache.spark.sql.catalyst.expressions.UnboundedPreceding
+import org.apache.spark.sql.catalyst.expressions.WindowFrame
 import org.apache.spark.sql.catalyst.expressions.RowNumber
 import org.apache.spark.sql.catalyst.expressions.WindowExpression
+import org.apache.spark.sql.catalyst.expressions.aggregate.AggregateExpression
 import org.apache.spark.sql.catalyst.expressions.aggregate.Average
 import org.apache.spark.sql.catalyst.expressions.aggregate.Count
 import org.apache.spark.sql.catalyst.expressions.aggregate.Max
 import org.apache.spark.sql.catalyst.expressions.aggregate.Min
 import org.apache.spark.sql.catalyst.expressions.aggregate.Sum
+import org.apache.spark.sql.types._
 
 abstract class NativeWindowBase(
     windowExpression: Seq[NamedExpression],
@@ -83,7 +98,14 @@ abstract class NativeWindowBase(
 
     named.children.head match {
       case WindowExpression(function, spec) =>
-        function match {
+        // aggregate functions are wrapped in AggregateExpression
+        val windowFunction = function match {
+          case e: AggregateExpression =>
+            assert(!e.isDistinct, s"distinct window aggregate not supported: $e")
+            e.aggregateFunction
+          case e => e
+        }
+        windowFunction match {
           case e @ RowNumber() =>
             assert(
               spec.frameSpecification == e.frame,
@@ -105,48 +127,66 @@ abstract class NativeWindowBase(
             windowExprBuilder.setFuncType(pb.WindowFunctionType.Window)
             windowExprBuilder.setWindowFunc(pb.WindowFunction.DENSE_RANK)
 
-          case e: Sum =>
+          case e: PercentRank =>
             assert(
-              spec.frameSpecification == RowNumber().frame, // only supports RowFrame(Unbounde, CurrentRow)
+              spec.frameSpecification == e.frame,
               s"window frame not supported: ${spec.frameSpecification}")
+            windowExprBuilder.setFuncType(pb.WindowFunctionType.Window)
+            windowExprBuilder.setWindowFunc(pb.WindowFunction.PERCENT_RANK)
+
+          case e: CumeDist =>
+            assert(
+              spec.frameSpecification == e.frame,
+              s"window frame not supported: ${spec.frameSpecification}")
+            windowExprBuilder.setFuncType(pb.WindowFunctionType.Window)
+            windowExprBuilder.setWindowFunc(pb.WindowFunction.CUME_DIST)
+
+          case e @ NTile(buckets) =>
+            assert(
+              spec.frameSpecification == e.frame,
+              s"window frame not supported: ${spec.frameSpecification}")
+            assert(buckets.foldable, s"ntile buckets must be foldable: $buckets")
+            windowExprBuilder.setFuncType(pb.WindowFunctionType.Window)
+            windowExprBuilder.setWindowFunc(pb.WindowFunction.NTILE)
+            windowExprBuilder.addChildren(
+              NativeConverters.convertExpr(Literal.create(buckets.eval(), buckets.dataType)))
+
+          case e: Sum =>
             windowExprBuilder.setFuncType(pb.WindowFunctionType.Agg)
             windowExprBuilder.setAggFunc(pb.AggFunction.SUM)
             windowExprBuilder.addChildren(NativeConverters.convertExpr(e.child))
+            windowExprBuilder.setFrame(convertWindowFrame(spec.frameSpecification, orderSpec))
 
           case e: Average =>
-            assert(
-              spec.frameSpecification == RowNumber().frame, // only supports RowFrame(Unbounded, CurrentRow)
-              s"window frame not supported: ${spec.frameSpecification}")
             windowExprBuilder.setFuncType(pb.WindowFunctionType.Agg)
             windowExprBuilder.setAggFunc(pb.AggFunction.AVG)
             windowExprBuilder.addChildren(NativeConverters.convertExpr(e.child))
+            windowExprBuilder.setFrame(convertWindowFrame(spec.frameSpecification, orderSpec))
 
           case e: Max =>
-            assert(
-              spec.frameSpecification == RowNumber().frame, // only supports RowFrame(Unbounded, CurrentRow)
-              s"window frame not supported: ${spec.frameSpecification}")
             windowExprBuilder.setFuncType(pb.WindowFunctionType.Agg)
             windowExprBuilder.setAggFunc(pb.AggFunction.MAX)
             windowExprBuilder.addChildren(NativeConverters.convertExpr(e.child))
+            windowExprBuilder.setFrame(convertWindowFrame(spec.frameSpecification, orderSpec))
 
           case e: Min =>
-            assert(
-              spec.frameSpecification == RowNumber().frame, // only supports RowFrame(Unbounded, CurrentRow)
-              s"window frame not supported: ${spec.frameSpecification}")
             windowExprBuilder.setFuncType(pb.WindowFunctionType.Agg)
             windowExprBuilder.setAggFunc(pb.AggFunction.MIN)
             windowExprBuilder.addChildren(NativeConverters.convertExpr(e.child))
+            windowExprBuilder.setFrame(convertWindowFrame(spec.frameSpecification, orderSpec))
 
           case Count(child :: Nil) =>
-            assert(
-              spec.frameSpecification == RowNumber().frame, // only supports RowFrame(Unbounded, CurrentRow)
-              s"window frame not supported: ${spec.frameSpecification}")
             windowExprBuilder.setFuncType(pb.WindowFunctionType.Agg)
             windowExprBuilder.setAggFunc(pb.AggFunction.COUNT)
             windowExprBuilder.addChildren(NativeConverters.convertExpr(child))
+            windowExprBuilder.setFrame(convertWindowFrame(spec.frameSpecification, orderSpec))
 
           case other =>
-            throw new NotImplementedError(s"window function not supported: $other")
+            Shims.get.convertMoreWindowFunction(other, spec.frameSpecification, orderSpec) match {
+              case Some(nativeWindowExpr) => windowExprBuilder.mergeFrom(nativeWindowExpr)
+              case None =>
+                throw new NotImplementedError(s"window function not supported: $other")
+            }
         }
       case other =>
         throw new NotImplementedError(s"expect WindowExpression, got: $other")
@@ -203,3 +243,78 @@ abstract class NativeWindowBase(
       friendlyName = "NativeRDD.Window")
   }
 }
+
+object NativeWindowBase {
+
+  def convertWindowFrame(frame: WindowFrame, orderSpec: Seq[SortOrder]): pb.WindowFrame = {
+    frame match {
+      case SpecifiedWindowFrame(frameType, lower, upper) =>
+        val nativeFrameType = frameType match {
+          case RowFrame => pb.WindowFrameType.ROWS
+          case RangeFrame =>
+            // native RANGE frames with offsets are evaluated on the only order expr
+            val hasOffset = !Seq(lower, upper).forall(isSpecialFrameBound)
+            if (hasOffset) {
+              assert(orderSpec.length == 1, s"window frame not supported: $frame")
+              assert(
+                isSupportedRangeOrderType(orderSpec.head.dataType),
+                s"window frame not supported on ${orderSpec.head.dataType}: $frame")
+            }
+            pb.WindowFrameType.RANGE
+        }
+        pb.WindowFrame
+          .newBuilder()
+          .setFrameType(nativeFrameType)
+          .setStart(convertWindowFrameBound(lower))
+          .setEnd(convertWindowFrameBound(upper))
+          .build()
+      case other =>
+        throw new NotImplementedError(s"window frame not supported: $other")
+    }
+  }
+
+  private def isSpecialFrameBound(bound: Expression): Boolean = bound match {
+    case UnboundedPreceding | UnboundedFollowing | CurrentRow => true
+    case _ => false
+  }
+
+  private def isSupportedRangeOrderType(dataType: DataType): Boolean = dataType match {
+    case ByteType | ShortType | IntegerType | LongType | FloatType | DoubleType | DateType =>
+      true
+    case _: DecimalType => true
+    case _ => false
+  }
+
+  // spark uses negative offsets for PRECEDING bounds and positive ones for FOLLOWING bounds,
+  // while native offsets are always non-negative
+  private def convertWindowFrameBound(bound: Expression): pb.WindowFrameBound = {
+    val builder = pb.WindowFrameBound.newBuilder()
+    bound match {
+      case UnboundedPreceding => builder.setBoundType(pb.WindowFrameBoundType.UNBOUNDED_PRECEDING)
+      case UnboundedFollowing => builder.setBoundType(pb.WindowFrameBoundType.UNBOUNDED_FOLLOWING)
+      case CurrentRow => builder.setBoundType(pb.WindowFrameBoundType.CURRENT_ROW)
+      case e if e.foldable =>
+        val (isPreceding, offset, offsetType) = (e.eval(), e.dataType) match {
+          case (v: Number, ByteType | ShortType | IntegerType | LongType) =>
+            (v.longValue() < 0, math.abs(v.longValue()), LongType)
+          case (v: Number, FloatType | DoubleType) =>
+            (v.doubleValue() < 0, math.abs(v.doubleValue()), DoubleType)
+          case (v: Decimal, t: DecimalType) if t.precision <= Decimal.MAX_LONG_DIGITS =>
+            val abs = Decimal(v.toJavaBigDecimal.abs(), t.precision, t.scale)
+            (v.toJavaBigDecimal.signum() < 0, abs, t)
+          case _ =>
+            throw new NotImplementedError(s"window frame bound not supported: $e")
+        }
+        builder
+          .setBoundType(if (isPreceding) {
+            pb.WindowFrameBoundType.PRECEDING
+          } else {
+            pb.WindowFrameBoundType.FOLLOWING
+          })
+          .setOffset(NativeConverters.convertValue(offset, offsetType))
+      case other =>
+        throw new NotImplementedError(s"window frame bound not supported: $other")
+    }
+    builder.build()
+  }
+}